@@ -143,6 +143,7 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
             .collect();
 
         Object {
+            start: input.start,
             degree,
             pil: self.pil,
             links,
@@ -152,9 +153,10 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
     fn handle_link_def(
         &mut self,
         LinkDefinitionStatement {
-            start: _,
+            start,
             flag,
             params,
+            kind,
             to: CallableRef { instance, callable },
         }: LinkDefinitionStatement<T>,
     ) -> Link<T> {
@@ -177,7 +179,9 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
         let instance_location = self.location.clone().join(instance);
 
         Link {
+            start,
             from,
+            kind,
             to: instance_ty
                 .operation_definitions()
                 .find(|o| o.name == callable)
@@ -15,7 +15,9 @@ pub fn enforce<T: FieldElement>(mut file: AnalysisASMFile<T>) -> AnalysisASMFile
             // add the necessary embedded constraints which apply to both static and dynamic machines
             let embedded_constraints = [
                 // inject last step
-                parse_pil_statement(&format!("col constant {last_step} = [0]* + [1]")),
+                parse_pil_statement(&format!(
+                    "#[role(last_row)] col constant {last_step} = [0]* + [1]"
+                )),
                 // the operation id must be constant within a block.
                 // TODO: use an intermediate polynomial, currently it yields an error later in `analyzed`
                 parse_pil_statement(&format!("col witness {operation_id_no_change}")),
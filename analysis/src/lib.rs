@@ -3,6 +3,7 @@
 mod block_enforcer;
 mod vm;
 
+use asm_to_pil::MergeReport;
 use ast::{asm_analysis::AnalysisASMFile, parsed::asm::ASMProgram, DiffMonitor};
 use number::FieldElement;
 
@@ -48,13 +49,37 @@ pub fn convert_analyzed_to_pil_constraints<T: FieldElement>(
     file
 }
 
+/// Like [convert_analyzed_to_pil_constraints], but lets assignment registers which are never
+/// used in the same row share their columns. Also returns a report of the merged registers.
+pub fn convert_analyzed_to_pil_constraints_merging_registers<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+    monitor: &mut DiffMonitor,
+) -> (AnalysisASMFile<T>, MergeReport) {
+    log::debug!("Run asm_to_pil, merging assignment registers");
+    let (file, report) = asm_to_pil::compile_merging_assignment_registers(file);
+    monitor.push(&file);
+    for (machine, merges) in &report.machines {
+        log::info!(
+            "Merged assignment registers {:?} in {machine}, saving {} columns",
+            merges.merged,
+            merges.saved_columns
+        );
+    }
+
+    log::debug!("Run enforce_block analysis step");
+    let file = block_enforcer::enforce(file);
+    monitor.push(&file);
+
+    (file, report)
+}
+
 pub mod utils {
     use ast::parsed::PilStatement;
     use number::FieldElement;
 
     pub fn parse_pil_statement<T: FieldElement>(input: &str) -> PilStatement<T> {
         parser::powdr::PilStatementParser::new()
-            .parse(input)
+            .parse(true, input)
             .unwrap()
     }
 }
@@ -8,7 +8,10 @@ use number::FieldElement;
 
 pub fn infer<T: FieldElement>(file: AnalysisASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<String>> {
     let mut errors = vec![];
-    let mut res = AnalysisASMFile::default();
+    let mut res = AnalysisASMFile {
+        sources: file.sources,
+        ..Default::default()
+    };
 
     for (name, m) in file.machines {
         match infer_machine(m) {
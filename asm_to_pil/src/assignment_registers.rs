@@ -0,0 +1,417 @@
+//! Analysis of the assignment registers used by the instructions and statements of a virtual machine.
+//! Each assignment register is lowered to several columns, so assignment registers whose uses never
+//! overlap can share their columns by renaming one of them to the other.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ast::{
+    asm_analysis::{Expression, FunctionStatement, Machine},
+    parsed::{
+        asm::{AbsoluteSymbolPath, AssignmentRegister, InstructionBody, Param},
+        visitor::ExpressionVisitable,
+    },
+};
+use number::FieldElement;
+
+/// The uses of the assignment registers of a machine.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AssignmentRegisterUsage {
+    /// The assignment registers in declaration order.
+    registers: Vec<String>,
+    /// The assignment registers used by each instruction, in its parameters or its body.
+    pub instructions: BTreeMap<String, BTreeSet<String>>,
+    /// The pairs of assignment registers which are used in the same row, i.e. in the same
+    /// instruction or the same batch of statements. The first element is the smaller one.
+    conflicts: BTreeSet<(String, String)>,
+    /// The assignment registers which are referenced outside of instructions and function
+    /// statements, for example in PIL constraints or links of the machine, and cannot be merged.
+    pinned: BTreeSet<String>,
+}
+
+impl AssignmentRegisterUsage {
+    pub fn of_machine<T>(machine: &Machine<T>) -> Self {
+        let registers: Vec<String> = machine
+            .assignment_register_names()
+            .map(|r| r.to_string())
+            .collect();
+        let is_register = |name: &str| registers.iter().any(|r| r == name);
+
+        let instructions: BTreeMap<String, BTreeSet<String>> = machine
+            .instructions
+            .iter()
+            .map(|def| {
+                let params = &def.instruction.params;
+                let mut used: BTreeSet<String> = params
+                    .inputs
+                    .params
+                    .iter()
+                    .chain(params.outputs.iter().flat_map(|o| o.params.iter()))
+                    .filter(|Param { name, ty, .. }| ty.is_none() && is_register(name))
+                    .map(|p| p.name.clone())
+                    .collect();
+                if let InstructionBody::Local(body) = &def.instruction.body {
+                    for s in body {
+                        s.pre_visit_expressions(&mut |e| {
+                            if let Some(name) = referenced_name(e).filter(|n| is_register(n)) {
+                                used.insert(name.to_string());
+                            }
+                        });
+                    }
+                }
+                (def.name.clone(), used)
+            })
+            .collect();
+
+        let mut pinned = BTreeSet::new();
+        for s in &machine.pil {
+            s.pre_visit_expressions(&mut |e| {
+                if let Some(name) = referenced_name(e).filter(|n| is_register(n)) {
+                    pinned.insert(name.to_string());
+                }
+            });
+        }
+        for link in &machine.links {
+            link.flag.pre_visit_expressions(&mut |e| {
+                if let Some(name) = referenced_name(e).filter(|n| is_register(n)) {
+                    pinned.insert(name.to_string());
+                }
+            });
+            let params = &link.params;
+            pinned.extend(
+                params
+                    .inputs
+                    .params
+                    .iter()
+                    .chain(params.outputs.iter().flat_map(|o| o.params.iter()))
+                    .filter(|p| is_register(&p.name))
+                    .map(|p| p.name.clone()),
+            );
+        }
+
+        let mut conflicts = BTreeSet::new();
+        let mut add_conflicts = |used: &BTreeSet<String>| {
+            for (i, a) in used.iter().enumerate() {
+                for b in used.iter().skip(i + 1) {
+                    conflicts.insert((a.clone(), b.clone()));
+                }
+            }
+        };
+        instructions.values().for_each(&mut add_conflicts);
+        for f in machine.functions() {
+            for batch in f.body.statements.iter_batches() {
+                let used = batch
+                    .statements
+                    .iter()
+                    .flat_map(|s| statement_registers(s, &instructions))
+                    .filter(|r| is_register(r))
+                    .collect();
+                add_conflicts(&used);
+            }
+        }
+
+        Self {
+            registers,
+            instructions,
+            conflicts,
+            pinned,
+        }
+    }
+
+    /// Returns true if `a` and `b` can share their columns because they are never used in the same row.
+    pub fn can_share_columns(&self, a: &str, b: &str) -> bool {
+        let pair = if a < b { (a, b) } else { (b, a) };
+        a != b
+            && !self.pinned.contains(a)
+            && !self.pinned.contains(b)
+            && !self
+                .conflicts
+                .contains(&(pair.0.to_string(), pair.1.to_string()))
+    }
+
+    /// Groups the registers greedily in declaration order: each register joins the first group
+    /// none of whose members it conflicts with. Returns the map from each register that is
+    /// merged to the first register of its group.
+    pub fn merges(&self) -> BTreeMap<String, String> {
+        let mut groups: Vec<Vec<&String>> = vec![];
+        let mut merges = BTreeMap::new();
+        for reg in &self.registers {
+            match groups
+                .iter_mut()
+                .find(|group| group.iter().all(|r| self.can_share_columns(r, reg)))
+            {
+                Some(group) => {
+                    merges.insert(reg.clone(), group[0].clone());
+                    group.push(reg);
+                }
+                None => groups.push(vec![reg]),
+            }
+        }
+        merges
+    }
+}
+
+/// The merges performed in each machine and the number of columns they saved.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub machines: BTreeMap<AbsoluteSymbolPath, MachineMergeReport>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MachineMergeReport {
+    /// Maps each merged register to the register whose columns it now uses.
+    pub merged: BTreeMap<String, String>,
+    /// The number of witness and fixed columns saved by the merges.
+    pub saved_columns: usize,
+}
+
+impl MergeReport {
+    pub fn saved_columns(&self) -> usize {
+        self.machines.values().map(|m| m.saved_columns).sum()
+    }
+}
+
+/// Merges the assignment registers of `machine` according to [AssignmentRegisterUsage::merges]
+/// by renaming them. Returns the renamed registers.
+pub(crate) fn merge_assignment_registers<T: FieldElement>(
+    machine: &mut Machine<T>,
+) -> BTreeMap<String, String> {
+    if !machine.has_pc() {
+        return BTreeMap::new();
+    }
+    let merges = AssignmentRegisterUsage::of_machine(machine).merges();
+    if merges.is_empty() {
+        return merges;
+    }
+
+    machine.registers.retain(|r| !merges.contains_key(&r.name));
+    // the pc is referenced by index
+    machine.pc = machine.registers.iter().position(|r| r.ty.is_pc());
+
+    let rename = |name: &mut String| {
+        if let Some(new_name) = merges.get(name) {
+            *name = new_name.clone();
+        }
+    };
+    let mut rename_references = |e: &mut Expression<T>| {
+        if let Expression::Reference(r) = e {
+            if r.namespace.is_none() {
+                rename(&mut r.name);
+            }
+        }
+    };
+
+    for def in &mut machine.instructions {
+        let params = &mut def.instruction.params;
+        params
+            .inputs
+            .params
+            .iter_mut()
+            .chain(params.outputs.iter_mut().flat_map(|o| o.params.iter_mut()))
+            .filter(|p| p.ty.is_none())
+            .for_each(|p| rename(&mut p.name));
+        if let InstructionBody::Local(body) = &mut def.instruction.body {
+            for s in body {
+                s.pre_visit_expressions_mut(&mut rename_references);
+            }
+        }
+    }
+
+    for f in machine.callable.functions_mut() {
+        // renaming does not change the batches
+        for s in f.body.statements.iter_mut() {
+            if let FunctionStatement::Assignment(a) = s {
+                for (_, reg) in &mut a.lhs_with_reg {
+                    if let AssignmentRegister::Register(reg) = reg {
+                        rename(reg);
+                    }
+                }
+            }
+            s.pre_visit_expressions_mut(&mut rename_references);
+        }
+    }
+
+    merges
+}
+
+fn referenced_name<T>(e: &Expression<T>) -> Option<&str> {
+    match e {
+        Expression::Reference(r) if r.namespace.is_none() => Some(&r.name),
+        _ => None,
+    }
+}
+
+/// The assignment registers used by a function statement.
+fn statement_registers<'a, T>(
+    s: &'a FunctionStatement<T>,
+    instructions: &'a BTreeMap<String, BTreeSet<String>>,
+) -> Vec<String> {
+    let instruction_registers = |name: &str| {
+        instructions
+            .get(name)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    match s {
+        FunctionStatement::Assignment(a) => {
+            let mut used: Vec<String> = a
+                .lhs_with_reg
+                .iter()
+                .filter_map(|(_, reg)| match reg {
+                    AssignmentRegister::Register(r) => Some(r.clone()),
+                    AssignmentRegister::Wildcard => None,
+                })
+                .collect();
+            if let Expression::FunctionCall(c) = a.rhs.as_ref() {
+                if let Some(name) = referenced_name(&c.function) {
+                    used.extend(instruction_registers(name));
+                }
+            }
+            used
+        }
+        FunctionStatement::Instruction(i) => instruction_registers(&i.instruction),
+        FunctionStatement::Label(_)
+        | FunctionStatement::DebugDirective(_)
+        | FunctionStatement::Return(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::{
+        asm_analysis::{AnalysisASMFile, Batch},
+        parsed::{asm::parse_absolute_path, PilStatement},
+    };
+    use number::GoldilocksField;
+
+    use super::*;
+
+    fn check_str(src: &str) -> AnalysisASMFile<GoldilocksField> {
+        type_check::check(parser::parse_asm(None, src).unwrap()).unwrap()
+    }
+
+    fn column_count(file: &AnalysisASMFile<GoldilocksField>) -> usize {
+        file.machines
+            .values()
+            .flat_map(|m| &m.pil)
+            .map(|s| match s {
+                PilStatement::PolynomialCommitDeclaration(_, _, names, ..)
+                | PilStatement::PolynomialConstantDeclaration(_, _, names) => names.len(),
+                PilStatement::PolynomialConstantDefinition(..) => 1,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    const MERGEABLE: &str = r#"
+        machine Main {
+            reg pc[@pc];
+            reg X[<=];
+            reg Y[<=];
+            reg Z[<=];
+            reg A;
+
+            instr incr X -> Y { Y = X + 1 }
+            instr assert_zero Z { Z = 0 }
+
+            function main {
+                A <=X= 2;
+                A <=Y= incr(A);
+                assert_zero A - 3;
+                return;
+            }
+        }
+    "#;
+
+    #[test]
+    fn usage() {
+        let file = check_str(MERGEABLE);
+        let usage =
+            AssignmentRegisterUsage::of_machine(file.get_machine(parse_absolute_path("Main")));
+        assert_eq!(
+            usage.instructions["incr"],
+            ["X".to_string(), "Y".to_string()].into()
+        );
+        assert_eq!(usage.instructions["assert_zero"], ["Z".to_string()].into());
+        assert!(!usage.can_share_columns("X", "Y"));
+        assert!(usage.can_share_columns("X", "Z"));
+        assert!(usage.can_share_columns("Y", "Z"));
+        assert_eq!(usage.merges(), [("Z".to_string(), "X".to_string())].into());
+    }
+
+    #[test]
+    fn merge_saves_columns() {
+        let (merged, report) = crate::compile_merging_assignment_registers(check_str(MERGEABLE));
+        let unmerged = crate::compile(check_str(MERGEABLE));
+        let main = &report.machines[&parse_absolute_path("Main")];
+        assert_eq!(main.merged, [("Z".to_string(), "X".to_string())].into());
+        assert!(main.saved_columns > 0);
+        assert_eq!(
+            column_count(&unmerged) - column_count(&merged),
+            report.saved_columns()
+        );
+    }
+
+    #[test]
+    fn same_batch() {
+        let file = check_str(
+            r#"
+            machine Main {
+                reg pc[@pc];
+                reg X[<=];
+                reg Y[<=];
+                reg A;
+                reg B;
+
+                function main {
+                    A <=X= 1;
+                    B <=Y= 2;
+                    return;
+                }
+            }
+        "#,
+        );
+        let mut machine = file.get_machine(parse_absolute_path("Main")).clone();
+        assert!(AssignmentRegisterUsage::of_machine(&machine).can_share_columns("X", "Y"));
+
+        // put both assignments into the same batch
+        for f in machine.callable.functions_mut() {
+            let statements = std::mem::take(&mut f.body.statements).into_inner();
+            let (assignments, ret) = statements.split_at(2);
+            f.body.statements = [Batch::from(assignments.to_vec()), Batch::from(ret.to_vec())]
+                .into_iter()
+                .collect();
+        }
+        assert!(!AssignmentRegisterUsage::of_machine(&machine).can_share_columns("X", "Y"));
+    }
+
+    #[test]
+    fn pinned_by_constraint() {
+        let file = check_str(
+            r#"
+            machine Main {
+                reg pc[@pc];
+                reg X[<=];
+                reg Y[<=];
+                reg A;
+
+                col witness XInv;
+                X * XInv = 0;
+
+                instr assert_zero Y { Y = 0 }
+
+                function main {
+                    A <=X= 1;
+                    assert_zero A - 1;
+                    return;
+                }
+            }
+        "#,
+        );
+        let usage =
+            AssignmentRegisterUsage::of_machine(file.get_machine(parse_absolute_path("Main")));
+        assert!(!usage.can_share_columns("X", "Y"));
+        assert!(usage.merges().is_empty());
+    }
+}
@@ -1,12 +1,16 @@
 #![deny(clippy::print_stdout)]
 
+use assignment_registers::MachineMergeReport;
 use ast::asm_analysis::AnalysisASMFile;
 use number::FieldElement;
 use romgen::generate_machine_rom;
+mod assignment_registers;
 mod common;
 mod romgen;
 mod vm_to_constrained;
 
+pub use assignment_registers::{AssignmentRegisterUsage, MergeReport};
+
 /// Remove all ASM from the machine tree. Takes a tree of virtual or constrained machines and returns a tree of constrained machines
 pub fn compile<T: FieldElement>(file: AnalysisASMFile<T>) -> AnalysisASMFile<T> {
     AnalysisASMFile {
@@ -20,9 +24,46 @@ pub fn compile<T: FieldElement>(file: AnalysisASMFile<T>) -> AnalysisASMFile<T>
                 })
             })
             .collect(),
+        sources: file.sources,
     }
 }
 
+/// Like [compile], but first lets the assignment registers of each virtual machine share their
+/// columns if they are never used in the same row, see [AssignmentRegisterUsage].
+/// Returns the tree of constrained machines and a report of the merged registers.
+pub fn compile_merging_assignment_registers<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+) -> (AnalysisASMFile<T>, MergeReport) {
+    let mut report = MergeReport::default();
+    let machines = file
+        .machines
+        .into_iter()
+        .map(|(name, mut m)| {
+            let merged = assignment_registers::merge_assignment_registers(&mut m);
+            let (m, rom) = generate_machine_rom(m);
+            if !merged.is_empty() {
+                let saved_columns =
+                    merged.len() * vm_to_constrained::columns_per_assignment_register(&m);
+                report.machines.insert(
+                    name.clone(),
+                    MachineMergeReport {
+                        merged,
+                        saved_columns,
+                    },
+                );
+            }
+            (name, vm_to_constrained::convert_machine(m, rom))
+        })
+        .collect();
+    (
+        AnalysisASMFile {
+            machines,
+            sources: file.sources,
+        },
+        report,
+    )
+}
+
 pub mod utils {
     use ast::{
         asm_analysis::{
@@ -40,7 +81,7 @@ pub mod utils {
         input: &str,
     ) -> InstructionDefinitionStatement<T> {
         match parser::powdr::InstructionDeclarationParser::new()
-            .parse(input)
+            .parse(true, input)
             .unwrap()
         {
             MachineStatement::InstructionDeclaration(start, name, instruction) => {
@@ -59,7 +100,7 @@ pub mod utils {
 
     pub fn parse_instruction<T: FieldElement>(input: &str) -> Instruction<T> {
         let instr = parser::powdr::InstructionParser::new()
-            .parse(input)
+            .parse(true, input)
             .unwrap();
         Instruction {
             params: instr.params,
@@ -69,13 +110,13 @@ pub mod utils {
 
     pub fn parse_instruction_body<T: FieldElement>(input: &str) -> InstructionBody<T> {
         parser::powdr::InstructionBodyParser::new()
-            .parse(input)
+            .parse(true, input)
             .unwrap()
     }
 
     pub fn parse_function_statement<T: FieldElement>(input: &str) -> FunctionStatement<T> {
         match parser::powdr::FunctionStatementParser::new()
-            .parse::<T>(input)
+            .parse::<T>(true, input)
             .unwrap()
         {
             ast::parsed::asm::FunctionStatement::Assignment(start, lhs, reg, rhs) => {
@@ -108,7 +149,7 @@ pub mod utils {
 
     pub fn parse_pil_statement<T: FieldElement>(input: &str) -> PilStatement<T> {
         parser::powdr::PilStatementParser::new()
-            .parse(input)
+            .parse(true, input)
             .unwrap()
     }
 
@@ -116,7 +157,7 @@ pub mod utils {
         input: &str,
     ) -> RegisterDeclarationStatement {
         match parser::powdr::RegisterDeclarationParser::new()
-            .parse::<T>(input)
+            .parse::<T>(true, input)
             .unwrap()
         {
             MachineStatement::RegisterDeclaration(start, name, flag) => {
@@ -16,9 +16,9 @@ use ast::{
         build::{direct_reference, next_reference},
         folder::ExpressionFolder,
         visitor::ExpressionVisitable,
-        ArrayExpression, BinaryOperator, Expression, FunctionCall, FunctionDefinition, MatchArm,
-        MatchPattern, NamespacedPolynomialReference, PilStatement, PolynomialName,
-        SelectedExpressions, UnaryOperator,
+        ArrayExpression, BinaryOperator, ColumnAttribute, ColumnRole, Expression, FunctionCall,
+        FunctionDefinition, MatchArm, MatchPattern, NamespacedPolynomialReference, PilStatement,
+        PolynomialName, SelectedExpressions, UnaryOperator,
     },
 };
 
@@ -41,6 +41,23 @@ pub fn convert_machine<T: FieldElement>(machine: Machine<T>, rom: Option<Rom<T>>
     ASMPILConverter::with_output_count(output_count).convert_machine(machine, rom)
 }
 
+/// The number of witness and fixed columns each assignment register of `machine` is lowered to,
+/// see [ASMPILConverter::handle_register_declaration] and [ASMPILConverter::create_constraints_for_assignment_reg].
+/// Expects the machine to already contain the registers added during ROM generation.
+pub(crate) fn columns_per_assignment_register<T>(machine: &Machine<T>) -> usize {
+    let write_registers = machine.write_register_names().count();
+    let readable_registers = write_registers
+        + machine
+            .registers
+            .iter()
+            .filter(|r| r.ty.is_pc() || r.ty.is_read_only())
+            .count();
+    // the register itself and its free value, plus a pair of witness and fixed column for
+    // assigning a constant, reading the free value, reading each readable register and
+    // writing each write register
+    2 + 2 * (2 + readable_registers + write_registers)
+}
+
 pub enum Input {
     Register(String),
     Literal(String, LiteralKind),
@@ -116,6 +133,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
         // introduce `first_step` which is used for register updates
         self.pil.push(PilStatement::PolynomialConstantDefinition(
             0,
+            Some(ColumnAttribute::Role(ColumnRole::FirstRow)),
             "first_step".to_string(),
             FunctionDefinition::Array(
                 ArrayExpression::value(vec![T::one().into()]).pad_with_zeroes(),
@@ -138,12 +156,12 @@ impl<T: FieldElement> ASMPILConverter<T> {
 
                                 vec![
                                     PilStatement::PolynomialDefinition(
-                                        0,
+                                        reg.start,
                                         pc_update_name.to_string(),
                                         rhs,
                                     ),
                                     PilStatement::PolynomialIdentity(
-                                        0,
+                                        reg.start,
                                         None,
                                         lhs - (Expression::from(T::one())
                                             - next_reference("first_step"))
@@ -156,13 +174,13 @@ impl<T: FieldElement> ASMPILConverter<T> {
                                 let not_reset: Expression<T> =
                                     Expression::from(T::one()) - direct_reference("instr__reset");
                                 vec![PilStatement::PolynomialIdentity(
-                                    0,
+                                    reg.start,
                                     None,
                                     not_reset * (lhs - rhs),
                                 )]
                             }
                             _ => {
-                                vec![PilStatement::PolynomialIdentity(0, None, lhs - rhs)]
+                                vec![PilStatement::PolynomialIdentity(reg.start, None, lhs - rhs)]
                             }
                         }
                     })
@@ -308,6 +326,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
         self.registers.insert(
             name.to_string(),
             Register {
+                start,
                 conditioned_updates,
                 default_update,
                 ty,
@@ -394,8 +413,10 @@ impl<T: FieldElement> ASMPILConverter<T> {
                     });
                 });
 
+                // The positions in generated instructions are not source positions.
+                let source_position = |start: usize| if s.start == 0 { 0 } else { start };
                 for mut statement in body {
-                    if let PilStatement::PolynomialIdentity(_start, _attr, expr) = statement {
+                    if let PilStatement::PolynomialIdentity(start, _attr, expr) = statement {
                         match extract_update(expr) {
                             (Some(var), expr) => {
                                 let reference = direct_reference(&instruction_flag);
@@ -411,20 +432,21 @@ impl<T: FieldElement> ASMPILConverter<T> {
                                     .push((reference, expr));
                             }
                             (None, expr) => self.pil.push(PilStatement::PolynomialIdentity(
-                                0,
+                                source_position(start),
                                 None,
                                 direct_reference(&instruction_flag) * expr.clone(),
                             )),
                         }
                     } else {
                         match &mut statement {
-                            PilStatement::PermutationIdentity(_, _attr, left, _)
-                            | PilStatement::PlookupIdentity(_, _attr, left, _) => {
+                            PilStatement::PermutationIdentity(start, _attr, left, _)
+                            | PilStatement::PlookupIdentity(start, _attr, left, _) => {
                                 assert!(
                                     left.selector.is_none(),
                                     "LHS selector not supported, could and-combine with instruction flag later."
                                 );
                                 left.selector = Some(direct_reference(&instruction_flag));
+                                *start = source_position(*start);
                                 self.pil.push(statement)
                             }
                             _ => {
@@ -435,10 +457,11 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 }
                 None
             }
-            InstructionBody::CallableRef(to) => Some(LinkDefinitionStatement {
+            InstructionBody::CallableRef(kind, to) => Some(LinkDefinitionStatement {
                 start: s.start,
                 flag: direct_reference(instruction_flag),
                 params: s.instruction.params,
+                kind,
                 to,
             }),
         };
@@ -732,6 +755,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
     fn translate_code_lines(&mut self) {
         self.pil.push(PilStatement::PolynomialConstantDefinition(
             0,
+            None,
             "p_line".to_string(),
             FunctionDefinition::Array(
                 ArrayExpression::Value(
@@ -853,6 +877,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
         for (name, values) in rom_constants {
             self.pil.push(PilStatement::PolynomialConstantDefinition(
                 0,
+                None,
                 name.clone(),
                 FunctionDefinition::Array(
                     ArrayExpression::value(values.into_iter().map(Expression::from).collect())
@@ -1003,6 +1028,8 @@ impl<T: FieldElement> ExpressionFolder<T, NamespacedPolynomialReference> for Nex
 }
 
 struct Register<T> {
+    /// The source position of the declaration.
+    start: usize,
     /// Constraints to update this register, first item being the
     /// condition, second item the value.
     /// TODO check that condition is bool
@@ -1086,12 +1113,14 @@ fn witness_column<S: Into<String>, T>(
 ) -> PilStatement<T> {
     PilStatement::PolynomialCommitDeclaration(
         start,
+        None,
         vec![PolynomialName {
             name: name.into(),
             array_size: None,
         }],
         def,
         public_info,
+        None,
     )
 }
 
@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use number::FieldElement;
+
+use crate::parsed::visitor::ExpressionVisitable;
+
+use super::{AlgebraicExpression, Analyzed, PolyID, PolynomialType};
+
+/// How the columns occur together in the identities, see [Analyzed::column_dependency_graph].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ColumnDependencyGraph {
+    pub columns: BTreeMap<PolyID, ColumnDependencies>,
+}
+
+/// The occurrences of a single column in the identities.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ColumnDependencies {
+    /// The name of the column, `name[i]` for an array element.
+    pub name: String,
+    /// The namespace (i.e. the machine) the column is declared in.
+    pub namespace: String,
+    /// The indices (into [Analyzed::identities]) of the identities the column occurs in.
+    pub identities: BTreeSet<usize>,
+    /// The other columns referenced in the current row in an identity with this column.
+    pub same_row: BTreeSet<PolyID>,
+    /// The other columns referenced in the next row in an identity with this column.
+    pub next_row: BTreeSet<PolyID>,
+}
+
+impl<T: FieldElement> Analyzed<T> {
+    /// @returns for each witness and fixed column the identities it occurs in and the
+    /// columns it occurs together with. Intermediate columns are inlined.
+    pub fn column_dependency_graph(&self) -> ColumnDependencyGraph {
+        let mut columns: BTreeMap<PolyID, ColumnDependencies> = self
+            .committed_polys_in_source_order()
+            .into_iter()
+            .chain(self.constant_polys_in_source_order())
+            .flat_map(|(symbol, _)| symbol.array_elements())
+            .map(|(name, poly_id)| {
+                let namespace = name.rsplit_once('.').map(|(namespace, _)| namespace);
+                let dependencies = ColumnDependencies {
+                    namespace: namespace.unwrap_or_default().to_string(),
+                    name,
+                    ..Default::default()
+                };
+                (poly_id, dependencies)
+            })
+            .collect();
+
+        for (index, identity) in self
+            .identities_with_inlined_intermediate_polynomials()
+            .iter()
+            .enumerate()
+        {
+            let mut references = BTreeSet::new();
+            identity.pre_visit_expressions(&mut |e| {
+                if let AlgebraicExpression::Reference(r) = e {
+                    assert_ne!(r.poly_id.ptype, PolynomialType::Intermediate);
+                    references.insert((r.poly_id, r.next));
+                }
+            });
+            for (poly_id, _) in &references {
+                let dependencies = columns.get_mut(poly_id).unwrap();
+                dependencies.identities.insert(index);
+                for (other, next) in &references {
+                    if other == poly_id {
+                        continue;
+                    }
+                    if *next {
+                        dependencies.next_row.insert(*other);
+                    } else {
+                        dependencies.same_row.insert(*other);
+                    }
+                }
+            }
+        }
+
+        ColumnDependencyGraph { columns }
+    }
+}
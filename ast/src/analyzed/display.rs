@@ -7,6 +7,8 @@ use std::fmt::{Display, Formatter, Result};
 
 use itertools::Itertools;
 
+use crate::parsed::ColumnAttribute;
+
 use super::*;
 
 impl<T: Display> Display for Analyzed<T> {
@@ -38,24 +40,38 @@ impl<T: Display> Display for Analyzed<T> {
                                     PolynomialType::Constant => "fixed ",
                                     PolynomialType::Intermediate => panic!(),
                                 };
-                                write!(f, "    col {kind}{name}")?;
+                                write!(f, "    ")?;
+                                if let Some(bits) = symbol.assert_range {
+                                    write!(f, "{} ", ColumnAttribute::AssertRange(bits))?;
+                                }
+                                if let Some(role) = symbol.role {
+                                    write!(f, "{} ", ColumnAttribute::Role(role))?;
+                                }
+                                write!(f, "col {kind}{name}")?;
                                 if let Some(length) = symbol.length {
                                     write!(f, "[{length}]")?;
                                 }
+                                if let Some(range) = &symbol.range {
+                                    write!(f, " {range}")?;
+                                }
                                 if let Some(value) = definition {
                                     writeln!(f, "{value};")?
                                 } else {
                                     writeln!(f, ";")?
                                 }
                             }
-                            SymbolKind::Constant() => {
+                            SymbolKind::Constant() if symbol.is_global_constant() => {
                                 let indentation = if is_local { "    " } else { "" };
                                 writeln!(
                                     f,
-                                    "{indentation}constant {name}{};",
+                                    "{indentation}constant %{name}{};",
                                     definition.as_ref().unwrap()
                                 )?;
                             }
+                            // Constants in a namespace are declared with `let`.
+                            SymbolKind::Constant() => {
+                                writeln!(f, "    let {name}{};", definition.as_ref().unwrap())?;
+                            }
                             SymbolKind::Other() => {
                                 write!(f, "    let {name}")?;
                                 if let Some(value) = definition {
@@ -103,6 +119,7 @@ impl<T: Display> Display for FunctionValueDefinition<T> {
                 write!(f, " = {}", items.iter().format(" + "))
             }
             FunctionValueDefinition::Query(e) => format_outer_function(e, Some("query"), f),
+            FunctionValueDefinition::Hint(e) => write!(f, " hint {e}"),
             FunctionValueDefinition::Expression(e) => format_outer_function(e, None, f),
         }
     }
@@ -115,7 +132,10 @@ fn format_outer_function<T: Display>(
 ) -> Result {
     let q = qualifier.map(|s| format!(" {s}")).unwrap_or_default();
     match e {
-        parsed::Expression::LambdaExpression(lambda) if lambda.params.len() == 1 => {
+        // Queries of arrays take two parameters, the row and the array index.
+        parsed::Expression::LambdaExpression(lambda)
+            if lambda.params.len() == 1 || qualifier.is_some() =>
+        {
             let body = if q.is_empty() {
                 format!("{{ {} }}", lambda.body)
             } else {
@@ -208,6 +228,9 @@ impl Display for Reference {
                 write!(f, "{name}")
             }
             Reference::Poly(r) => write!(f, "{r}"),
+            Reference::Constant(name) => write!(f, "%{name}"),
+            Reference::Degree => write!(f, "{DEGREE_BUILTIN}"),
+            Reference::Builtin(builtin) => write!(f, "{}", builtin.name()),
         }
     }
 }
@@ -1,20 +1,31 @@
+mod dependency_graph;
 mod display;
+mod serialize;
+mod stats;
+mod unconstrained;
 pub mod visitor;
 
+pub use dependency_graph::{ColumnDependencies, ColumnDependencyGraph};
+pub use serialize::{ANALYZED_FILE_NAME, ANALYZED_FORMAT_VERSION};
+pub use stats::{MaxDegree, NamespaceStats, Stats};
+pub use unconstrained::UnconstrainedReason;
+
 use core::hash::Hash;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
 use std::ops::{self, ControlFlow};
 
 use number::{DegreeType, FieldElement};
+use serde::{Deserialize, Serialize};
 
 use crate::parsed::utils::expr_any;
 use crate::parsed::visitor::ExpressionVisitable;
 pub use crate::parsed::BinaryOperator;
 pub use crate::parsed::UnaryOperator;
 use crate::parsed::{self, SelectedExpressions};
+use crate::parsed::{ColumnRole, ValueRange};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StatementIdentifier {
     /// Either an intermediate column or a definition.
     Definition(String),
@@ -23,12 +34,15 @@ pub enum StatementIdentifier {
     Identity(usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Analyzed<T> {
     /// The degree of all namespaces, which must match. If there are no namespaces, then `None`.
     pub degree: Option<DegreeType>,
+    #[serde(serialize_with = "serialize::sorted_map")]
     pub definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition<T>>)>,
+    #[serde(serialize_with = "serialize::sorted_map")]
     pub public_declarations: HashMap<String, PublicDeclaration>,
+    #[serde(serialize_with = "serialize::sorted_map")]
     pub intermediate_columns: HashMap<String, (Symbol, AlgebraicExpression<T>)>,
     pub identities: Vec<Identity<AlgebraicExpression<T>>>,
     /// The order in which definitions and identities
@@ -123,49 +137,49 @@ impl<T> Analyzed<T> {
     /// There must not be any reference to the removed polynomials left.
     /// Does not support arrays or array elements.
     pub fn remove_polynomials(&mut self, to_remove: &BTreeSet<PolyID>) {
-        let mut replacements: BTreeMap<PolyID, PolyID> = [
+        let replacements: BTreeMap<PolyID, PolyID> = [
             // We have to do it separately because we need to re-start the counter
             // for each kind.
-            self.committed_polys_in_source_order(),
-            self.constant_polys_in_source_order(),
+            self.committed_polys_in_source_order()
+                .into_iter()
+                .map(|(poly, _)| poly)
+                .collect::<Vec<_>>(),
+            self.constant_polys_in_source_order()
+                .into_iter()
+                .map(|(poly, _)| poly)
+                .collect(),
+            self.intermediate_polys_in_source_order()
+                .into_iter()
+                .map(|(poly, _)| poly)
+                .collect(),
         ]
         .map(|polys| {
             polys
-                .iter()
-                .fold(
-                    (0, BTreeMap::new()),
-                    |(shift, mut replacements), (poly, _def)| {
-                        let poly_id = poly.into();
-                        let length = poly.length.unwrap_or(1);
-                        if to_remove.contains(&poly_id) {
-                            (shift + length, replacements)
-                        } else {
-                            for (_name, id) in poly.array_elements() {
-                                replacements.insert(
-                                    id,
-                                    PolyID {
-                                        id: id.id - shift,
-                                        ..id
-                                    },
-                                );
-                            }
-                            (shift, replacements)
+                .into_iter()
+                .fold((0, BTreeMap::new()), |(shift, mut replacements), poly| {
+                    let poly_id = poly.into();
+                    let length = poly.length.unwrap_or(1);
+                    if to_remove.contains(&poly_id) {
+                        (shift + length, replacements)
+                    } else {
+                        for (_name, id) in poly.array_elements() {
+                            replacements.insert(
+                                id,
+                                PolyID {
+                                    id: id.id - shift,
+                                    ..id
+                                },
+                            );
                         }
-                    },
-                )
+                        (shift, replacements)
+                    }
+                })
                 .1
         })
         .into_iter()
         .flatten()
         .collect();
 
-        // We assume for now that intermediate columns are not removed.
-        for (poly, _) in self.intermediate_columns.values() {
-            let poly_id: PolyID = poly.into();
-            assert!(!to_remove.contains(&poly_id));
-            replacements.insert(poly_id, poly_id);
-        }
-
         let mut names_to_remove: HashSet<String> = Default::default();
         self.definitions.retain(|name, (poly, _def)| {
             if matches!(poly.kind, SymbolKind::Poly(_))
@@ -177,6 +191,14 @@ impl<T> Analyzed<T> {
                 true
             }
         });
+        self.intermediate_columns.retain(|name, (poly, _)| {
+            if to_remove.contains(&(poly as &Symbol).into()) {
+                names_to_remove.insert(name.clone());
+                false
+            } else {
+                true
+            }
+        });
         self.source_order.retain(|s| {
             if let StatementIdentifier::Definition(name) = s {
                 if names_to_remove.contains(name) {
@@ -192,6 +214,11 @@ impl<T> Analyzed<T> {
                 poly.id = replacements[&poly_id].id;
             }
         });
+        self.intermediate_columns
+            .values_mut()
+            .for_each(|(poly, _)| {
+                poly.id = replacements[&PolyID::from(poly as &Symbol)].id;
+            });
         let visitor = &mut |expr: &mut Expression<_>| {
             if let Expression::Reference(Reference::Poly(poly)) = expr {
                 poly.poly_id = poly.poly_id.map(|poly_id| {
@@ -210,6 +237,54 @@ impl<T> Analyzed<T> {
         self.post_visit_expressions_in_identities_mut(algebraic_visitor);
     }
 
+    /// Adds a witness column at the end of the source order and returns its ID.
+    /// Panics if there already is a symbol with the same name.
+    pub fn add_committed_column(&mut self, name: &str) -> PolyID {
+        self.add_column(name, PolynomialType::Committed, None)
+    }
+
+    /// Adds a fixed column with the given values at the end of the source order
+    /// and returns its ID.
+    /// Panics if there already is a symbol with the same name.
+    pub fn add_fixed_column(
+        &mut self,
+        name: &str,
+        definition: FunctionValueDefinition<T>,
+    ) -> PolyID {
+        self.add_column(name, PolynomialType::Constant, Some(definition))
+    }
+
+    fn add_column(
+        &mut self,
+        name: &str,
+        ptype: PolynomialType,
+        definition: Option<FunctionValueDefinition<T>>,
+    ) -> PolyID {
+        assert!(
+            !self.definitions.contains_key(name)
+                && !self.intermediate_columns.contains_key(name)
+                && !self.public_declarations.contains_key(name),
+            "Symbol {name} already exists."
+        );
+        // IDs are contiguous, so the next free ID is the number of columns of that type.
+        let id = self.declaration_type_count(ptype) as u64;
+        let symbol = Symbol {
+            id,
+            source: SourceRef::default(),
+            absolute_name: name.to_string(),
+            kind: SymbolKind::Poly(ptype),
+            length: None,
+            assert_range: None,
+            role: None,
+            range: None,
+        };
+        self.definitions
+            .insert(name.to_string(), (symbol, definition));
+        self.source_order
+            .push(StatementIdentifier::Definition(name.to_string()));
+        PolyID { id, ptype }
+    }
+
     /// Adds a polynomial identity and returns the ID.
     pub fn append_polynomial_identity(
         &mut self,
@@ -238,7 +313,7 @@ impl<T> Analyzed<T> {
             },
         });
         self.source_order
-            .push(StatementIdentifier::Identity(id as usize));
+            .push(StatementIdentifier::Identity(self.identities.len() - 1));
         id
     }
 
@@ -284,7 +359,9 @@ impl<T> Analyzed<T> {
         self.definitions
             .values_mut()
             .for_each(|(_poly, definition)| match definition {
-                Some(FunctionValueDefinition::Query(e)) => e.post_visit_expressions_mut(f),
+                Some(FunctionValueDefinition::Query(e) | FunctionValueDefinition::Hint(e)) => {
+                    e.post_visit_expressions_mut(f)
+                }
                 Some(FunctionValueDefinition::Array(elements)) => elements
                     .iter_mut()
                     .flat_map(|e| e.pattern.iter_mut())
@@ -374,19 +451,31 @@ fn inlined_expression_from_intermediate_poly_id<T: Copy>(
     expr
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
     pub id: u64,
     pub source: SourceRef,
     pub absolute_name: String,
     pub kind: SymbolKind,
     pub length: Option<DegreeType>,
+    /// The number of bits witness generation has to derive a range constraint for,
+    /// from `#[assert_range(bits)]`. Only used for witness columns.
+    pub assert_range: Option<u64>,
+    /// The role of the column from `#[role(...)]`. Only used for fixed columns.
+    pub role: Option<ColumnRole>,
+    /// The values of the column from `in [min, max]`. Only used for witness columns.
+    pub range: Option<ValueRange>,
 }
 
 impl Symbol {
     pub fn is_array(&self) -> bool {
         self.length.is_some()
     }
+    /// Whether this is a constant declared as `constant %name`. These are not namespaced,
+    /// their absolute name does not include the `%`.
+    pub fn is_global_constant(&self) -> bool {
+        self.kind == SymbolKind::Constant() && !self.absolute_name.contains('.')
+    }
     /// Returns an iterator producing either just the symbol (if it is not an array),
     /// or all the elements of the array with their names in the form `array[index]`.
     pub fn array_elements(&self) -> impl Iterator<Item = (String, PolyID)> + '_ {
@@ -431,7 +520,7 @@ impl Symbol {
 
 /// The "kind" of a symbol. In the future, this will be mostly
 /// replaced by its type.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum SymbolKind {
     /// Fixed, witness or intermediate polynomial
     Poly(PolynomialType),
@@ -442,16 +531,20 @@ pub enum SymbolKind {
     Other(),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FunctionValueDefinition<T> {
     Array(Vec<RepeatedArray<T>>),
     Query(Expression<T>),
+    /// A function computing the value of a witness column in a row, which witness
+    /// generation only uses if it cannot make progress otherwise.
+    /// The value still has to satisfy the constraints.
+    Hint(Expression<T>),
     Expression(Expression<T>),
     Number(usize),
 }
 
 /// An array of elements that might be repeated.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepeatedArray<T> {
     /// The pattern to be repeated
     pattern: Vec<Expression<T>>,
@@ -491,7 +584,7 @@ impl<T> RepeatedArray<T> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublicDeclaration {
     pub id: u64,
     pub source: SourceRef,
@@ -502,7 +595,7 @@ pub struct PublicDeclaration {
     pub index: DegreeType,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Identity<Expr> {
     /// The ID is specific to the identity kind.
     pub id: u64,
@@ -533,7 +626,7 @@ impl<T> Identity<AlgebraicExpression<T>> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
 pub enum IdentityKind {
     Polynomial,
     Plookup,
@@ -554,13 +647,51 @@ impl<T> SelectedExpressions<AlgebraicExpression<T>> {
 
 pub type Expression<T> = parsed::Expression<T, Reference>;
 
-#[derive(Debug, Clone)]
+/// The name of the builtin that refers to the degree, see [Reference::Degree].
+pub const DEGREE_BUILTIN: &str = "DEGREE";
+
+/// Builtin functions that convert between field elements and integers.
+/// They can only be evaluated, i.e. they are not allowed in constraints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuiltinFunction {
+    /// `int(x)` or `int(x, bits)`: the canonical integer representation of the field element `x`.
+    /// With `bits`, evaluation fails if the integer does not fit into `bits` bits.
+    Int,
+    /// `fe(x)`: the field element of the integer `x`.
+    Fe,
+}
+
+impl BuiltinFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinFunction::Int => "int",
+            BuiltinFunction::Fe => "fe",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(BuiltinFunction::Int),
+            "fe" => Some(BuiltinFunction::Fe),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Reference {
     LocalVar(u64, String),
     Poly(PolynomialReference),
+    /// A reference `%name` to a global constant, with the name without the `%`.
+    Constant(String),
+    /// The builtin `DEGREE`, the degree of the namespaces. In contrast to a constant,
+    /// it is resolved at evaluation time, i.e. it follows a degree override.
+    Degree,
+    /// A builtin function, see [BuiltinFunction].
+    Builtin(BuiltinFunction),
 }
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct AlgebraicReference {
     /// Name of the polynomial - just for informational purposes.
     /// Comparisons are based on polynomial ID.
@@ -607,7 +738,7 @@ impl Hash for AlgebraicReference {
         self.next.hash(state);
     }
 }
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub enum AlgebraicExpression<T> {
     Reference(AlgebraicReference),
     PublicReference(String),
@@ -621,7 +752,7 @@ pub enum AlgebraicExpression<T> {
     UnaryOperation(AlgebraicUnaryOperator, Box<AlgebraicExpression<T>>),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum AlgebraicBinaryOperator {
     Add,
     Sub,
@@ -657,7 +788,7 @@ impl TryFrom<BinaryOperator> for AlgebraicBinaryOperator {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum AlgebraicUnaryOperator {
     Plus,
     Minus,
@@ -747,7 +878,7 @@ impl<T> From<T> for AlgebraicExpression<T> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PolynomialReference {
     /// Name of the polynomial - just for informational purposes.
     /// Comparisons are based on polynomial ID.
@@ -758,7 +889,7 @@ pub struct PolynomialReference {
     pub poly_id: Option<PolyID>,
 }
 
-#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PolyID {
     pub id: u64,
     pub ptype: PolynomialType,
@@ -776,7 +907,7 @@ impl From<&Symbol> for PolyID {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum PolynomialType {
     Committed,
     Constant,
@@ -797,7 +928,8 @@ impl Display for PolynomialType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// The location of a statement, defaults to an unknown location for generated statements.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct SourceRef {
     pub file: String, // TODO should maybe be a shared pointer
     pub line: usize,
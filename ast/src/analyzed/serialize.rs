@@ -0,0 +1,162 @@
+//! The binary artifact `analyzed.bin`, which stores analyzed PIL so that tools
+//! downstream of powdr do not have to analyze it again.
+//!
+//! The artifact starts with a magic number and a header, which records the format
+//! version and the field, followed by the analyzed PIL, both encoded with bincode. Field elements are stored as their
+//! canonical integers and maps are stored sorted by key, so the same PIL always results
+//! in the same artifact.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{Read, Write};
+
+use number::{DegreeType, FieldElement};
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::{Analyzed, PolynomialType, StatementIdentifier, SymbolKind};
+
+/// The name of the artifact written next to the optimized PIL.
+pub const ANALYZED_FILE_NAME: &str = "analyzed.bin";
+
+/// The version of the artifact format. It has to be increased whenever the
+/// serialized types change.
+pub const ANALYZED_FORMAT_VERSION: u32 = 2;
+
+const MAGIC: [u8; 8] = *b"powdrpil";
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    /// The modulus of the field, in decimal.
+    modulus: String,
+}
+
+impl Header {
+    fn new<T: FieldElement>() -> Self {
+        Header {
+            version: ANALYZED_FORMAT_VERSION,
+            modulus: T::modulus().to_string(),
+        }
+    }
+}
+
+/// Serializes a map sorted by key, so that the output does not depend on the
+/// iteration order of the map.
+pub(super) fn sorted_map<K: Ord + Serialize, V: Serialize, S: Serializer>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+impl<T: FieldElement> Analyzed<T> {
+    /// Writes the analyzed PIL in the format of [ANALYZED_FILE_NAME].
+    pub fn write_binary(&self, mut writer: impl Write) -> Result<(), String> {
+        writer
+            .write_all(&MAGIC)
+            .map_err(bincode::Error::from)
+            .and_then(|_| bincode::serialize_into(&mut writer, &Header::new::<T>()))
+            .and_then(|_| bincode::serialize_into(&mut writer, self))
+            .map_err(|e| format!("Could not write analyzed PIL: {e}"))
+    }
+
+    /// Reads analyzed PIL written by [Analyzed::write_binary]. Fails if the artifact
+    /// has a different format version or field, or if it is inconsistent.
+    pub fn read_binary(mut reader: impl Read) -> Result<Self, String> {
+        let mut magic = [0; MAGIC.len()];
+        if reader.read_exact(&mut magic).is_err() || magic != MAGIC {
+            return Err("Not an analyzed PIL artifact.".to_string());
+        }
+        let header: Header = bincode::deserialize_from(&mut reader)
+            .map_err(|e| format!("Invalid analyzed PIL artifact: {e}"))?;
+        if header.version != ANALYZED_FORMAT_VERSION {
+            return Err(format!(
+                "The analyzed PIL artifact has format version {}, but this version of powdr \
+                 reads version {ANALYZED_FORMAT_VERSION}. Generate it again.",
+                header.version
+            ));
+        }
+        let expected = Header::new::<T>();
+        if header.modulus != expected.modulus {
+            return Err(format!(
+                "The analyzed PIL artifact is for the field with modulus {}, not {}.",
+                header.modulus, expected.modulus
+            ));
+        }
+        let analyzed: Self = bincode::deserialize_from(&mut reader)
+            .map_err(|e| format!("Invalid analyzed PIL artifact: {e}"))?;
+        analyzed
+            .check_consistency()
+            .map_err(|e| format!("Inconsistent analyzed PIL artifact: {e}"))?;
+        Ok(analyzed)
+    }
+
+    /// Checks the invariants the analyzer guarantees: the IDs of the witness and fixed
+    /// columns are contiguous and the source order refers to every definition and
+    /// identity exactly once.
+    fn check_consistency(&self) -> Result<(), String> {
+        for (name, (symbol, _)) in &self.definitions {
+            if *name != symbol.absolute_name {
+                return Err(format!(
+                    "Definition {name} is for the symbol {}.",
+                    symbol.absolute_name
+                ));
+            }
+        }
+        for ptype in [PolynomialType::Committed, PolynomialType::Constant] {
+            let mut symbols = self
+                .definitions
+                .values()
+                .map(|(symbol, _)| symbol)
+                .filter(|symbol| symbol.kind == SymbolKind::Poly(ptype))
+                .collect::<Vec<_>>();
+            symbols.sort_by_key(|symbol| symbol.id);
+            let mut next_id: DegreeType = 0;
+            for symbol in symbols {
+                if symbol.id != next_id {
+                    return Err(format!(
+                        "The {ptype} column {} has ID {}, expected {next_id}.",
+                        symbol.absolute_name, symbol.id
+                    ));
+                }
+                next_id += symbol.length.unwrap_or(1);
+            }
+        }
+
+        let mut definitions = BTreeSet::new();
+        let mut public_declarations = BTreeSet::new();
+        let mut identities = BTreeSet::new();
+        for statement in &self.source_order {
+            let (exists, new) = match statement {
+                StatementIdentifier::Definition(name) => (
+                    self.definitions.contains_key(name)
+                        || self.intermediate_columns.contains_key(name),
+                    definitions.insert(name),
+                ),
+                StatementIdentifier::PublicDeclaration(name) => (
+                    self.public_declarations.contains_key(name),
+                    public_declarations.insert(name),
+                ),
+                StatementIdentifier::Identity(index) => {
+                    (*index < self.identities.len(), identities.insert(*index))
+                }
+            };
+            if !exists {
+                return Err(format!(
+                    "The source order refers to the unknown {statement:?}."
+                ));
+            }
+            if !new {
+                return Err(format!(
+                    "The source order refers to {statement:?} more than once."
+                ));
+            }
+        }
+        if definitions.len() != self.definitions.len() + self.intermediate_columns.len()
+            || public_declarations.len() != self.public_declarations.len()
+            || identities.len() != self.identities.len()
+        {
+            return Err("The source order does not refer to every statement.".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use number::{DegreeType, FieldElement};
+
+use crate::parsed::visitor::ExpressionVisitable;
+
+use super::{
+    AlgebraicBinaryOperator, AlgebraicExpression, Analyzed, IdentityKind, PolynomialType,
+    SourceRef, SymbolKind,
+};
+
+/// Statistics about the size of a circuit, see [Analyzed::stats].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The declared degree.
+    pub degree: DegreeType,
+    /// The number of columns per namespace.
+    pub namespaces: BTreeMap<String, NamespaceStats>,
+    /// The number of identities per kind.
+    pub identities: BTreeMap<IdentityKind, usize>,
+    /// The polynomial identity of the highest degree, None if there are no polynomial identities.
+    pub max_degree: Option<MaxDegree>,
+    /// The number of distinct lookups and permutations into each namespace,
+    /// i.e. with a right-hand side in the namespace.
+    pub lookups: BTreeMap<String, usize>,
+}
+
+/// The number of columns of a namespace, array elements are counted separately.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NamespaceStats {
+    pub witness: usize,
+    pub fixed: usize,
+    pub intermediate: usize,
+}
+
+/// The polynomial identity of the highest degree, the first one if there are several.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MaxDegree {
+    pub degree: DegreeType,
+    /// The index of the identity in [Analyzed::identities].
+    pub identity: usize,
+    pub source: SourceRef,
+}
+
+impl<T: FieldElement> Analyzed<T> {
+    /// @returns statistics about the columns and identities. The degrees of the
+    /// identities are computed with the intermediate columns inlined.
+    pub fn stats(&self) -> Stats {
+        let mut namespaces: BTreeMap<String, NamespaceStats> = BTreeMap::new();
+        let symbols = self
+            .committed_polys_in_source_order()
+            .into_iter()
+            .chain(self.constant_polys_in_source_order())
+            .map(|(symbol, _)| symbol)
+            .chain(
+                self.intermediate_polys_in_source_order()
+                    .into_iter()
+                    .map(|(symbol, _)| symbol),
+            );
+        for symbol in symbols {
+            let stats = namespaces
+                .entry(namespace(&symbol.absolute_name))
+                .or_default();
+            let count = symbol.array_elements().count();
+            match symbol.kind {
+                SymbolKind::Poly(PolynomialType::Committed) => stats.witness += count,
+                SymbolKind::Poly(PolynomialType::Constant) => stats.fixed += count,
+                SymbolKind::Poly(PolynomialType::Intermediate) => stats.intermediate += count,
+                _ => unreachable!(),
+            }
+        }
+
+        let mut identities = BTreeMap::new();
+        let mut max_degree: Option<MaxDegree> = None;
+        let mut lookups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (index, identity) in self
+            .identities_with_inlined_intermediate_polynomials()
+            .iter()
+            .enumerate()
+        {
+            *identities.entry(identity.kind).or_default() += 1;
+            match identity.kind {
+                IdentityKind::Polynomial => {
+                    let degree = expression_degree(identity.expression_for_poly_id());
+                    if !matches!(&max_degree, Some(max) if max.degree >= degree) {
+                        max_degree = Some(MaxDegree {
+                            degree,
+                            identity: index,
+                            source: identity.source.clone(),
+                        });
+                    }
+                }
+                IdentityKind::Plookup | IdentityKind::Permutation => {
+                    let mut target = None;
+                    identity.right.pre_visit_expressions(&mut |e| {
+                        if let AlgebraicExpression::Reference(r) = e {
+                            target.get_or_insert_with(|| namespace(&r.name));
+                        }
+                    });
+                    if let Some(target) = target {
+                        lookups.entry(target).or_default().insert(format!(
+                            "{} {:?} {}",
+                            identity.left, identity.kind, identity.right
+                        ));
+                    }
+                }
+                IdentityKind::Connect => {}
+            }
+        }
+
+        Stats {
+            degree: self.degree(),
+            namespaces,
+            identities,
+            max_degree,
+            lookups: lookups
+                .into_iter()
+                .map(|(namespace, lookups)| (namespace, lookups.len()))
+                .collect(),
+        }
+    }
+}
+
+fn namespace(name: &str) -> String {
+    name.rsplit_once('.')
+        .map(|(namespace, _)| namespace)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The algebraic degree of an expression without references to intermediate columns.
+fn expression_degree<T: FieldElement>(e: &AlgebraicExpression<T>) -> DegreeType {
+    match e {
+        AlgebraicExpression::Reference(r) => {
+            assert_ne!(r.poly_id.ptype, PolynomialType::Intermediate);
+            1
+        }
+        AlgebraicExpression::PublicReference(_) | AlgebraicExpression::Number(_) => 0,
+        AlgebraicExpression::BinaryOperation(left, op, right) => match op {
+            AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                expression_degree(left).max(expression_degree(right))
+            }
+            AlgebraicBinaryOperator::Mul => expression_degree(left) + expression_degree(right),
+            AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                AlgebraicExpression::Number(exponent) => {
+                    expression_degree(left) * exponent.to_degree()
+                }
+                _ => panic!("Exponent has to be a number: {right}"),
+            },
+        },
+        AlgebraicExpression::UnaryOperation(_, inner) => expression_degree(inner),
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+
+use number::FieldElement;
+
+use crate::parsed::visitor::ExpressionVisitable;
+
+use super::{
+    AlgebraicBinaryOperator, AlgebraicExpression, Analyzed, IdentityKind, PolyID, PolynomialType,
+};
+
+/// Why a witness column is considered unconstrained, see [Analyzed::unconstrained_witnesses].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnconstrainedReason {
+    /// The column does not occur in any identity.
+    Unreferenced,
+    /// The column only occurs on the left-hand side of lookups or permutations
+    /// whose selector can be zero in all rows.
+    OnlyInDisabledLookups,
+}
+
+impl<T: FieldElement> Analyzed<T> {
+    /// @returns the witness columns (array elements separately) that the identities
+    /// do not constrain, in source order. Intermediate columns are inlined.
+    pub fn unconstrained_witnesses(&self) -> Vec<PolyID> {
+        self.unconstrained_witnesses_with_reasons()
+            .into_iter()
+            .map(|(_, poly_id, _)| poly_id)
+            .collect()
+    }
+
+    /// Like [Analyzed::unconstrained_witnesses], but also returns the names of the
+    /// columns and why they are considered unconstrained.
+    ///
+    /// A column on the left-hand side of a lookup or permutation is only constrained
+    /// if the selector cannot be zero in all rows. The selector can be zero if it
+    /// vanishes when all witness columns that are not constrained otherwise are set to zero.
+    pub fn unconstrained_witnesses_with_reasons(
+        &self,
+    ) -> Vec<(String, PolyID, UnconstrainedReason)> {
+        let identities = self.identities_with_inlined_intermediate_polynomials();
+
+        let is_lookup = |kind| matches!(kind, IdentityKind::Plookup | IdentityKind::Permutation);
+
+        let mut referenced = BTreeSet::new();
+        let mut constrained = BTreeSet::new();
+        for identity in &identities {
+            collect_witnesses(identity, &mut referenced);
+            if is_lookup(identity.kind) {
+                collect_witnesses(&identity.right, &mut constrained);
+            } else {
+                collect_witnesses(identity, &mut constrained);
+            }
+        }
+        // The left-hand sides of lookups that cannot be disabled also constrain their columns,
+        // which might in turn prevent other lookups from being disabled.
+        loop {
+            let free = referenced
+                .difference(&constrained)
+                .cloned()
+                .collect::<BTreeSet<_>>();
+            let count = constrained.len();
+            for identity in identities.iter().filter(|i| is_lookup(i.kind)) {
+                match &identity.left.selector {
+                    Some(selector) if vanishes(selector, &free) => {}
+                    _ => collect_witnesses(&identity.left, &mut constrained),
+                }
+            }
+            if constrained.len() == count {
+                break;
+            }
+        }
+
+        self.committed_polys_in_source_order()
+            .into_iter()
+            .flat_map(|(symbol, _)| symbol.array_elements())
+            .filter_map(|(name, poly_id)| {
+                let reason = if !referenced.contains(&poly_id) {
+                    UnconstrainedReason::Unreferenced
+                } else if !constrained.contains(&poly_id) {
+                    UnconstrainedReason::OnlyInDisabledLookups
+                } else {
+                    return None;
+                };
+                Some((name, poly_id, reason))
+            })
+            .collect()
+    }
+}
+
+fn collect_witnesses<T>(
+    e: &impl ExpressionVisitable<AlgebraicExpression<T>>,
+    witnesses: &mut BTreeSet<PolyID>,
+) {
+    e.pre_visit_expressions(&mut |e| {
+        if let AlgebraicExpression::Reference(r) = e {
+            if r.is_witness() {
+                witnesses.insert(r.poly_id);
+            }
+        }
+    });
+}
+
+/// @returns true if `e` is zero when the witness columns in `zero` are zero.
+fn vanishes<T: FieldElement>(e: &AlgebraicExpression<T>, zero: &BTreeSet<PolyID>) -> bool {
+    match e {
+        AlgebraicExpression::Reference(r) => {
+            assert_ne!(r.poly_id.ptype, PolynomialType::Intermediate);
+            zero.contains(&r.poly_id)
+        }
+        AlgebraicExpression::PublicReference(_) => false,
+        AlgebraicExpression::Number(n) => n.is_zero(),
+        AlgebraicExpression::BinaryOperation(left, op, right) => match op {
+            AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                vanishes(left, zero) && vanishes(right, zero)
+            }
+            AlgebraicBinaryOperator::Mul => vanishes(left, zero) || vanishes(right, zero),
+            AlgebraicBinaryOperator::Pow => {
+                vanishes(left, zero)
+                    && !matches!(right.as_ref(), AlgebraicExpression::Number(n) if n.is_zero())
+            }
+        },
+        AlgebraicExpression::UnaryOperation(_, inner) => vanishes(inner, zero),
+    }
+}
@@ -40,7 +40,7 @@ impl<T: Display> Display for Machine<T> {
         write_items_indented(f, &self.registers)?;
         write_items_indented(f, &self.instructions)?;
         write_items_indented(f, &self.callable)?;
-        write_items_indented(f, &self.pil)?;
+        write_items_indented(f, self.pil.iter().map(|s| format!("{s};")))?;
         write_items_indented(f, &self.links)?;
 
         writeln!(f, "}}")
@@ -51,9 +51,10 @@ impl<T: Display> Display for LinkDefinitionStatement<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
             f,
-            "link {}{} = {};",
+            "link {}{} {} {};",
             self.flag,
             self.params.prepend_space_if_non_empty(),
+            self.kind,
             self.to
         )
     }
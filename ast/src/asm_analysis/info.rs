@@ -0,0 +1,168 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::parsed::asm::{InstructionBody, LinkKind, Params};
+use crate::source_map::SourceMap;
+
+use super::{AnalysisASMFile, CallableSymbol, RegisterTy};
+
+/// The structure of a machine of an analyzed ASM program, see [AnalysisASMFile::machine_info].
+/// Signatures are rendered as in the source, e.g. `X, Y -> Z`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct MachineInfo {
+    /// The absolute name of the machine type.
+    pub name: String,
+    pub degree: Option<String>,
+    pub registers: Vec<RegisterInfo>,
+    pub instructions: Vec<InstructionInfo>,
+    /// The operations and functions of the machine.
+    pub callables: Vec<CallableInfo>,
+    pub submachines: Vec<SubmachineInfo>,
+    pub links: Vec<LinkInfo>,
+    pub position: Option<SourcePosition>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct RegisterInfo {
+    pub name: String,
+    pub ty: RegisterTy,
+    pub position: Option<SourcePosition>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct InstructionInfo {
+    pub name: String,
+    pub signature: String,
+    /// The operation called by an external instruction, e.g. `arith.add`,
+    /// None for instructions with constraints in the machine.
+    pub calls: Option<String>,
+    pub position: Option<SourcePosition>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct CallableInfo {
+    pub name: String,
+    /// Whether this is an operation (or a function).
+    pub operation: bool,
+    pub signature: String,
+    pub position: Option<SourcePosition>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct SubmachineInfo {
+    /// The name of the instance.
+    pub name: String,
+    /// The absolute name of the machine type.
+    pub ty: String,
+    pub position: Option<SourcePosition>,
+}
+
+/// A link to an operation of a submachine, declared with `link`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct LinkInfo {
+    pub flag: String,
+    pub signature: String,
+    /// The called operation, e.g. `arith.add`.
+    pub to: String,
+    pub permutation: bool,
+    pub position: Option<SourcePosition>,
+}
+
+/// The start of a declaration. The AST does not store where declarations end.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct SourcePosition {
+    /// The file name, without directories.
+    pub file: String,
+    /// The offset in bytes from the start of the file.
+    pub offset: usize,
+}
+
+impl<T: Display> AnalysisASMFile<T> {
+    /// @returns the structure of all machines, ordered by name, e.g. for editor tooling.
+    /// Positions are None for declarations generated by the compiler.
+    pub fn machine_info(&self) -> Vec<MachineInfo> {
+        let position = |pos| position(&self.sources, pos);
+        self.machines
+            .iter()
+            .map(|(name, machine)| MachineInfo {
+                name: name.to_string(),
+                degree: machine.degree.as_ref().map(|d| d.degree.to_string()),
+                registers: machine
+                    .registers
+                    .iter()
+                    .map(|r| RegisterInfo {
+                        name: r.name.clone(),
+                        ty: r.ty.clone(),
+                        position: position(r.start),
+                    })
+                    .collect(),
+                instructions: machine
+                    .instructions
+                    .iter()
+                    .map(|i| InstructionInfo {
+                        name: i.name.clone(),
+                        signature: signature(&i.instruction.params),
+                        calls: match &i.instruction.body {
+                            InstructionBody::Local(_) => None,
+                            InstructionBody::CallableRef(_, to) => Some(to.to_string()),
+                        },
+                        position: position(i.start),
+                    })
+                    .collect(),
+                callables: machine
+                    .callable
+                    .0
+                    .iter()
+                    .map(|(name, symbol)| {
+                        let (operation, params, start) = match symbol {
+                            CallableSymbol::Operation(o) => (true, &o.params, o.start),
+                            CallableSymbol::Function(f) => (false, &f.params, f.start),
+                        };
+                        CallableInfo {
+                            name: name.clone(),
+                            operation,
+                            signature: signature(params),
+                            position: position(start),
+                        }
+                    })
+                    .collect(),
+                submachines: machine
+                    .submachines
+                    .iter()
+                    .map(|s| SubmachineInfo {
+                        name: s.name.clone(),
+                        ty: s.ty.to_string(),
+                        position: position(s.start),
+                    })
+                    .collect(),
+                links: machine
+                    .links
+                    .iter()
+                    .map(|l| LinkInfo {
+                        flag: l.flag.to_string(),
+                        signature: signature(&l.params),
+                        to: l.to.to_string(),
+                        permutation: l.kind == LinkKind::Permutation,
+                        position: position(l.start),
+                    })
+                    .collect(),
+                position: position(machine.start),
+            })
+            .collect()
+    }
+}
+
+fn position(sources: &SourceMap, pos: usize) -> Option<SourcePosition> {
+    sources
+        .file_offset(pos)
+        .map(|(file, offset)| SourcePosition {
+            file: file.to_string(),
+            offset,
+        })
+}
+
+fn signature<T: Display>(params: &Params<T>) -> String {
+    // Without outputs after `->`, the rendered parameters end in a space.
+    params.to_string().trim_end().to_string()
+}
@@ -1,4 +1,5 @@
 mod display;
+mod info;
 
 use std::{
     collections::{
@@ -12,16 +13,23 @@ use std::{
 use itertools::Either;
 use num_bigint::BigUint;
 use number::FieldElement;
+use serde::Serialize;
 
 use crate::parsed::{
     asm::{
-        AbsoluteSymbolPath, AssignmentRegister, CallableRef, InstructionBody, OperationId, Params,
+        AbsoluteSymbolPath, AssignmentRegister, CallableRef, InstructionBody, LinkKind,
+        OperationId, Params,
     },
     visitor::{ExpressionVisitable, VisitOrder},
     NamespacedPolynomialReference, PilStatement,
 };
+use crate::source_map::SourceMap;
 
 pub use crate::parsed::Expression;
+pub use info::{
+    CallableInfo, InstructionInfo, LinkInfo, MachineInfo, RegisterInfo, SourcePosition,
+    SubmachineInfo,
+};
 
 #[derive(Clone, Debug)]
 pub struct RegisterDeclarationStatement {
@@ -30,7 +38,7 @@ pub struct RegisterDeclarationStatement {
     pub ty: RegisterTy,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub enum RegisterTy {
     Pc,
     Assignment,
@@ -76,6 +84,8 @@ pub struct LinkDefinitionStatement<T> {
     pub flag: Expression<T>,
     /// the parameters to pass to the callable
     pub params: Params<T>,
+    /// whether the call is a lookup or a permutation
+    pub kind: LinkKind,
     /// the callable to invoke when the flag is on. TODO: check this during type checking
     pub to: CallableRef,
 }
@@ -660,6 +670,7 @@ pub struct Return<T> {
 
 #[derive(Clone, Debug)]
 pub struct SubmachineDeclaration {
+    pub start: usize,
     /// the name of this instance
     pub name: String,
     /// the type of the submachine
@@ -668,6 +679,8 @@ pub struct SubmachineDeclaration {
 
 #[derive(Clone, Default, Debug)]
 pub struct Machine<T> {
+    /// The source position of the declaration
+    pub start: usize,
     /// The degree if any, i.e. the number of rows in instances of this machine type
     pub degree: Option<DegreeStatement>,
     /// The latch, i.e. the boolean column whose values must be 1 in order for this machine to be accessed. Must be defined in one of the constraint blocks of this machine.
@@ -775,6 +788,8 @@ pub struct Rom<T> {
 #[derive(Default, Debug)]
 pub struct AnalysisASMFile<T> {
     pub machines: BTreeMap<AbsoluteSymbolPath, Machine<T>>,
+    /// The files the program was loaded from, to map the source positions to lines.
+    pub sources: SourceMap,
 }
 impl<T> AnalysisASMFile<T> {
     pub fn get_machine(&self, path: AbsoluteSymbolPath) -> &Machine<T> {
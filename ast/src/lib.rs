@@ -14,6 +14,8 @@ pub mod asm_analysis;
 pub mod object;
 /// A parsed ASM + PIL AST
 pub mod parsed;
+/// The source files of an ASM program
+pub mod source_map;
 
 #[derive(Default)]
 /// A monitor of the changes applied to the program as we run through the analysis pipeline
@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter, Result};
 
+use crate::parsed::asm::LinkKind;
+
 use super::{Link, LinkFrom, LinkTo, Location, Machine, Object, Operation, PILGraph};
 
 impl Display for Location {
@@ -25,7 +27,7 @@ impl<T: Display> Display for Object<T> {
             writeln!(f, "// Degree {}", degree)?;
         }
         for s in &self.pil {
-            writeln!(f, "{s}")?;
+            writeln!(f, "{s};")?;
         }
         if !self.links.is_empty() {
             writeln!(f, "// Links:")?;
@@ -39,7 +41,11 @@ impl<T: Display> Display for Object<T> {
 
 impl<T: Display> Display for Link<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{} links to {}", self.from, self.to)
+        let kind = match self.kind {
+            LinkKind::Lookup => "lookup",
+            LinkKind::Permutation => "permutation",
+        };
+        write!(f, "{} links to {} ({kind})", self.from, self.to)
     }
 }
 
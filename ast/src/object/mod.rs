@@ -1,6 +1,9 @@
 use std::collections::BTreeMap;
 
-use crate::parsed::{asm::Params, Expression, PilStatement};
+use crate::parsed::{
+    asm::{LinkKind, Params},
+    Expression, PilStatement,
+};
 
 mod display;
 
@@ -30,6 +33,8 @@ pub struct PILGraph<T> {
 
 #[derive(Default)]
 pub struct Object<T> {
+    /// the source position of the declaration of the machine type
+    pub start: usize,
     pub degree: Option<u64>,
     /// the pil identities for this machine
     pub pil: Vec<PilStatement<T>>,
@@ -47,10 +52,14 @@ impl<T> Object<T> {
 #[derive(Clone)]
 /// A link between two machines
 pub struct Link<T> {
+    /// the source position of the link or of the instruction it was generated from
+    pub start: usize,
     /// the link source, i.e. a flag and some arguments
     pub from: LinkFrom<T>,
     /// the link target, i.e. a callable in some machine
     pub to: LinkTo<T>,
+    /// whether the call is a lookup or a permutation
+    pub kind: LinkKind,
 }
 
 #[derive(Clone)]
@@ -5,10 +5,13 @@ use number::AbstractNumberType;
 use derive_more::From;
 
 use super::{Expression, PilStatement};
+use crate::source_map::SourceMap;
 
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct ASMProgram<T> {
     pub main: ASMModule<T>,
+    /// The files the program was loaded from.
+    pub sources: SourceMap,
 }
 
 #[derive(Default, Debug, PartialEq, Eq)]
@@ -198,6 +201,8 @@ impl SymbolPath {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Machine<T> {
+    /// The source position of the declaration.
+    pub start: usize,
     pub arguments: MachineArguments,
     pub statements: Vec<MachineStatement<T>>,
 }
@@ -277,9 +282,21 @@ pub struct LinkDeclaration<T> {
     pub start: usize,
     pub flag: Expression<T>,
     pub params: Params<T>,
+    pub kind: LinkKind,
     pub to: CallableRef,
 }
 
+/// How a link connects the calling machine to the callable.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum LinkKind {
+    /// The call is looked up in the callee (`=`), which can contain rows
+    /// that are not called.
+    Lookup,
+    /// Each call is matched with exactly one row of the callee (`~`), which cannot
+    /// contain selected rows that are not called.
+    Permutation,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct CallableRef {
     pub instance: String,
@@ -289,7 +306,7 @@ pub struct CallableRef {
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum InstructionBody<T> {
     Local(Vec<PilStatement<T>>),
-    CallableRef(CallableRef),
+    CallableRef(LinkKind, CallableRef),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -326,6 +343,9 @@ pub enum DebugDirective {
     File(usize, String, String),
     Loc(usize, usize, usize),
     OriginalInstruction(String),
+    /// Prints the lowest byte of the register to the output of the executor.
+    /// Does not create any constraints, so it is ignored during witness generation.
+    Print(String),
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
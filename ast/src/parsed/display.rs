@@ -13,7 +13,7 @@ use super::{asm::*, *};
 
 impl<T: Display> Display for PILFile<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write_items(f, &self.0)
+        write_items(f, self.0.iter().map(|s| format!("{s};")))
     }
 }
 
@@ -34,13 +34,13 @@ impl<T: Display> Display for ModuleStatement<T> {
         match self {
             ModuleStatement::SymbolDefinition(SymbolDefinition { name, value }) => match value {
                 SymbolValue::Machine(m) => {
-                    write!(f, "machine {name} {m}")
+                    write!(f, "machine {name}{m}")
                 }
                 SymbolValue::Import(i) => {
-                    write!(f, "{i} as {name}")
+                    write!(f, "{i} as {name};")
                 }
                 SymbolValue::Module(m) => {
-                    write!(f, "mod {name} {m}")
+                    write!(f, "mod {name}{m}")
                 }
             },
         }
@@ -50,9 +50,9 @@ impl<T: Display> Display for ModuleStatement<T> {
 impl<T: Display> Display for Module<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
-            Module::External(name) => write!(f, "{name};"),
+            Module::External(_) => write!(f, ";"),
             Module::Local(module) => {
-                writeln!(f, "{{")?;
+                writeln!(f, " {{")?;
                 write_items_indented(f, &module.statements)?;
                 write!(f, "}}")
             }
@@ -89,17 +89,33 @@ impl Display for Part {
 
 impl<T: Display> Display for Machine<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        writeln!(f, "{{")?;
+        writeln!(f, "{} {{", self.arguments)?;
         write_items_indented(f, &self.statements)?;
         write!(f, "}}")
     }
 }
 
+impl Display for MachineArguments {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match (&self.latch, &self.operation_id) {
+            (None, None) => Ok(()),
+            (latch, operation_id) => write!(
+                f,
+                "({}, {})",
+                latch.as_deref().unwrap_or("_"),
+                operation_id.as_deref().unwrap_or("_")
+            ),
+        }
+    }
+}
+
 impl<T: Display> Display for InstructionBody<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
-            InstructionBody::Local(elements) => write!(f, "{{ {} }}", elements.iter().format(", ")),
-            InstructionBody::CallableRef(r) => write!(f, " = {r};"),
+            InstructionBody::Local(elements) => {
+                write!(f, " {{ {} }}", elements.iter().format(", "))
+            }
+            InstructionBody::CallableRef(kind, r) => write!(f, " {kind} {r}"),
         }
     }
 }
@@ -119,14 +135,24 @@ impl<T: Display> Display for LinkDeclaration<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
             f,
-            "link {}{} = {};",
+            "link {}{} {} {};",
             self.flag,
             self.params.prepend_space_if_non_empty(),
+            self.kind,
             self.to
         )
     }
 }
 
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            LinkKind::Lookup => write!(f, "="),
+            LinkKind::Permutation => write!(f, "~"),
+        }
+    }
+}
+
 impl Display for CallableRef {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}.{}", self.instance, self.callable)
@@ -241,6 +267,9 @@ impl Display for DebugDirective {
             DebugDirective::OriginalInstruction(insn) => {
                 write!(f, "debug insn \"{insn}\";")
             }
+            DebugDirective::Print(reg) => {
+                write!(f, "debug print {reg};")
+            }
         }
     }
 }
@@ -350,74 +379,109 @@ pub fn quote(input: &str) -> String {
 impl<T: Display> Display for PilStatement<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
-            PilStatement::Include(_, path) => write!(f, "include {};", quote(path)),
+            PilStatement::Include(_, path, prefix) => {
+                write!(f, "include {}", quote(path))?;
+                if let Some(prefix) = prefix {
+                    write!(f, " as {prefix}")?;
+                }
+                Ok(())
+            }
             PilStatement::Namespace(_, name, poly_length) => {
-                write!(f, "namespace {name}({poly_length});")
+                write!(f, "namespace {name}({poly_length})")
             }
-            PilStatement::LetStatement(_, name, None) => write!(f, "let {name};"),
-            PilStatement::LetStatement(_, name, Some(expr)) => write!(f, "let {name} = {expr};"),
+            PilStatement::LetStatement(_, name, None) => write!(f, "let {name}"),
+            PilStatement::LetStatement(_, name, Some(expr)) => write!(f, "let {name} = {expr}"),
             PilStatement::PolynomialDefinition(_, name, value) => {
-                write!(f, "pol {name} = {value};")
+                write!(f, "pol {name} = {value}")
             }
             PilStatement::PublicDeclaration(_, name, poly, array_index, index) => {
                 write!(
                     f,
-                    "public {name} = {poly}{}({index});",
+                    "public {name} = {poly}{}({index})",
                     array_index
                         .as_ref()
                         .map(|i| format!("[{i}]"))
                         .unwrap_or_default()
                 )
             }
-            PilStatement::PolynomialConstantDeclaration(_, names) => {
-                write!(f, "pol constant {};", names.iter().format(", "))
+            PilStatement::PolynomialConstantDeclaration(_, attribute, names) => {
+                if let Some(attribute) = attribute {
+                    write!(f, "{attribute} ")?;
+                }
+                write!(f, "pol constant {}", names.iter().format(", "))
+            }
+            PilStatement::PolynomialConstantDefinition(_, attribute, name, definition) => {
+                if let Some(attribute) = attribute {
+                    write!(f, "{attribute} ")?;
+                }
+                write!(f, "pol constant {name}{definition}")
             }
-            PilStatement::PolynomialConstantDefinition(_, name, definition) => {
-                write!(f, "pol constant {name}{definition};")
+            PilStatement::PolynomialConstantTableDefinition(_, name, table) => {
+                write!(f, "pol constant {name}{table}")
             }
-            PilStatement::PolynomialCommitDeclaration(_, names, value, public) => {
+            PilStatement::PolynomialCommitDeclaration(
+                _,
+                attribute,
+                names,
+                value,
+                public,
+                range,
+            ) => {
+                if let Some(attribute) = attribute {
+                    write!(f, "{attribute} ")?;
+                }
                 write!(
                     f,
-                    "pol commit {}{}{};",
+                    "pol {}{}{}{}",
                     if let Some(n) = public {
-                        format!("public : {n}")
+                        format!("public({n}) ")
                     } else {
-                        " ".to_string()
+                        "commit ".to_string()
                     },
                     names.iter().format(", "),
+                    range.as_ref().map(|r| format!(" {r}")).unwrap_or_default(),
                     value.as_ref().map(|v| format!("{v}")).unwrap_or_default(),
                 )
             }
-            PilStatement::PolynomialIdentity(_, _attr, expression) => {
+            PilStatement::PolynomialIdentity(_, attribute, expression) => {
+                write_attribute(f, attribute)?;
                 if let Expression::BinaryOperation(left, BinaryOperator::Sub, right) = expression {
-                    write!(f, "{left} = {right};")
+                    write!(f, "{left} = {right}")
                 } else {
-                    write!(f, "{expression} = 0;")
+                    write!(f, "{expression} = 0")
                 }
             }
-            PilStatement::PlookupIdentity(_, _, left, right) => write!(f, "{left} in {right};"),
-            PilStatement::PermutationIdentity(
-                _, //
-                _, //
-                left,
-                right,
-            ) => write!(f, "{left} is {right};"), //
+            PilStatement::PlookupIdentity(_, attribute, left, right) => {
+                write_attribute(f, attribute)?;
+                write!(f, "{left} in {right}")
+            }
+            PilStatement::PermutationIdentity(_, attribute, left, right) => {
+                write_attribute(f, attribute)?;
+                write!(f, "{left} is {right}")
+            }
             PilStatement::ConnectIdentity(_, left, right) => write!(
                 f,
-                "{{ {} }} connect {{ {} }};",
+                "{{ {} }} connect {{ {} }}",
                 format_expressions(left),
                 format_expressions(right)
             ),
             PilStatement::ConstantDefinition(_, name, value) => {
-                write!(f, "constant {name} = {value};")
+                write!(f, "constant {name} = {value}")
             }
             PilStatement::Expression(_, e) => {
-                write!(f, "{e};")
+                write!(f, "{e}")
             }
         }
     }
 }
 
+fn write_attribute(f: &mut Formatter<'_>, attribute: &Option<String>) -> Result {
+    match attribute {
+        Some(attribute) => write!(f, "#[{attribute}] "),
+        None => Ok(()),
+    }
+}
+
 impl<T: Display> Display for ArrayExpression<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -432,6 +496,19 @@ impl<T: Display> Display for ArrayExpression<T> {
     }
 }
 
+impl<T: Display> Display for TableDefinition<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "({}) -> {} {{ {} }} over ({})",
+            self.params.iter().format(", "),
+            self.output,
+            self.body,
+            self.dimensions.iter().format(", ")
+        )
+    }
+}
+
 impl<T: Display> Display for FunctionDefinition<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -444,6 +521,9 @@ impl<T: Display> Display for FunctionDefinition<T> {
             FunctionDefinition::Query(params, value) => {
                 write!(f, "({}) query {value}", params.join(", "),)
             }
+            FunctionDefinition::Hint(value) => {
+                write!(f, " hint {value}")
+            }
             FunctionDefinition::Expression(e) => match e {
                 Expression::LambdaExpression(lambda) if lambda.params.len() == 1 => {
                     write!(
@@ -506,6 +586,30 @@ impl<T: Display> Display for PolynomialName<T> {
     }
 }
 
+impl Display for ColumnAttribute {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ColumnAttribute::AssertRange(bits) => write!(f, "#[assert_range({bits})]"),
+            ColumnAttribute::Role(role) => write!(f, "#[role({role})]"),
+        }
+    }
+}
+
+impl Display for ValueRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "in [{}, {}]", self.min, self.max)
+    }
+}
+
+impl Display for ColumnRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ColumnRole::FirstRow => write!(f, "first_row"),
+            ColumnRole::LastRow => write!(f, "last_row"),
+        }
+    }
+}
+
 impl Display for NamespacedPolynomialReference {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
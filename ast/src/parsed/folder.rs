@@ -13,7 +13,10 @@ pub trait Folder<T> {
     fn fold_program(&mut self, p: ASMProgram<T>) -> Result<ASMProgram<T>, Self::Error> {
         let main = self.fold_module_value(p.main)?;
 
-        Ok(ASMProgram { main })
+        Ok(ASMProgram {
+            main,
+            sources: p.sources,
+        })
     }
 
     fn fold_module_value(&mut self, module: ASMModule<T>) -> Result<ASMModule<T>, Self::Error> {
@@ -5,17 +5,19 @@ pub mod folder;
 pub mod utils;
 pub mod visitor;
 
-use std::ops;
+use std::{iter::once, ops};
 
-use number::{DegreeType, FieldElement};
+use number::{AbstractNumberType, DegreeType, FieldElement};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct PILFile<T>(pub Vec<PilStatement<T>>);
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum PilStatement<T> {
-    /// File name
-    Include(usize, String),
+    /// File name and, for `include "file" as Prefix`, the prefix of the
+    /// namespaces declared in the file.
+    Include(usize, String, Option<String>),
     /// Name of namespace and polynomial degree (constant)
     Namespace(usize, String, Expression<T>),
     LetStatement(usize, String, Option<Expression<T>>),
@@ -31,13 +33,22 @@ pub enum PilStatement<T> {
         /// The row number of the public value.
         Expression<T>,
     ),
-    PolynomialConstantDeclaration(usize, Vec<PolynomialName<T>>),
-    PolynomialConstantDefinition(usize, String, FunctionDefinition<T>),
+    PolynomialConstantDeclaration(usize, Option<ColumnAttribute>, Vec<PolynomialName<T>>),
+    PolynomialConstantDefinition(
+        usize,
+        Option<ColumnAttribute>,
+        String,
+        FunctionDefinition<T>,
+    ),
+    /// A fixed lookup table defined as a function of two indices.
+    PolynomialConstantTableDefinition(usize, String, TableDefinition<T>),
     PolynomialCommitDeclaration(
         usize,
+        Option<ColumnAttribute>,
         Vec<PolynomialName<T>>,
         Option<FunctionDefinition<T>>,
         Option<usize>,
+        Option<ValueRange>,
     ),
     PolynomialIdentity(usize, Option<String>, Expression<T>),
     PlookupIdentity(
@@ -62,7 +73,7 @@ pub struct Attribute {
     pub name: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct SelectedExpressions<Expr> {
     pub selector: Option<Expr>,
     pub expressions: Vec<Expr>,
@@ -77,7 +88,7 @@ impl<Expr> Default for SelectedExpressions<Expr> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub enum Expression<T, Ref = NamespacedPolynomialReference> {
     Reference(Ref),
     PublicReference(String),
@@ -119,6 +130,46 @@ impl<T, Ref> Expression<T, Ref> {
         })
         .is_break()
     }
+
+    /// Returns the direct sub-expressions of this expression.
+    /// In contrast to the visitors, this does not recurse, so it can be used
+    /// to traverse arbitrarily deep expressions with an explicit stack.
+    pub fn children_mut(&mut self) -> Vec<&mut Self> {
+        match self {
+            Expression::Reference(_)
+            | Expression::PublicReference(_)
+            | Expression::Number(_)
+            | Expression::String(_) => vec![],
+            Expression::Tuple(items) | Expression::ArrayLiteral(ArrayLiteral { items }) => {
+                items.iter_mut().collect()
+            }
+            Expression::LambdaExpression(LambdaExpression { body, .. }) => vec![body.as_mut()],
+            Expression::BinaryOperation(left, _, right) => vec![left.as_mut(), right.as_mut()],
+            Expression::UnaryOperation(_, e) | Expression::FreeInput(e) => vec![e.as_mut()],
+            Expression::IndexAccess(IndexAccess { array, index }) => {
+                vec![array.as_mut(), index.as_mut()]
+            }
+            Expression::FunctionCall(FunctionCall {
+                function,
+                arguments,
+            }) => once(function.as_mut()).chain(arguments).collect(),
+            Expression::MatchExpression(scrutinee, arms) => once(scrutinee.as_mut())
+                .chain(arms.iter_mut().flat_map(|MatchArm { pattern, value }| {
+                    match pattern {
+                        MatchPattern::CatchAll => None,
+                        MatchPattern::Pattern(e) => Some(e),
+                    }
+                    .into_iter()
+                    .chain(once(value))
+                }))
+                .collect(),
+            Expression::IfExpression(IfExpression {
+                condition,
+                body,
+                else_body,
+            }) => vec![condition.as_mut(), body.as_mut(), else_body.as_mut()],
+        }
+    }
 }
 
 impl<T, Ref> ops::Add for Expression<T, Ref> {
@@ -169,6 +220,34 @@ pub struct PolynomialName<T> {
     pub array_size: Option<Expression<T>>,
 }
 
+/// An attribute of a column declaration. It does not add any constraints.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum ColumnAttribute {
+    /// `#[assert_range(bits)]`: Witness generation has to be able to derive
+    /// that the values of the witness column fit into the given number of bits.
+    AssertRange(u64),
+    /// `#[role(first_row)]` or `#[role(last_row)]`: The fixed column has to have
+    /// the canonical shape of the role.
+    Role(ColumnRole),
+}
+
+/// A special role of a fixed column, see [ColumnAttribute::Role].
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
+pub enum ColumnRole {
+    /// The column is one in the first row and zero everywhere else: `[1] + [0]*`.
+    FirstRow,
+    /// The column is one in the last row and zero everywhere else: `[0]* + [1]`.
+    LastRow,
+}
+
+/// The values of a witness column annotated with `in [min, max]`, both bounds inclusive.
+/// Witness generation uses it as a range constraint and checks that the values are in it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
+pub struct ValueRange {
+    pub min: AbstractNumberType,
+    pub max: AbstractNumberType,
+}
+
 #[derive(Debug, PartialEq, Eq, Default, Clone, PartialOrd, Ord)]
 /// A polynomial with an optional namespace
 pub struct NamespacedPolynomialReference {
@@ -178,18 +257,25 @@ pub struct NamespacedPolynomialReference {
     pub name: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+impl NamespacedPolynomialReference {
+    /// The name of the global constant without the `%` if this is a reference `%name`.
+    pub fn constant_name(&self) -> Option<&str> {
+        self.name.strip_prefix('%')
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct LambdaExpression<T, Ref = NamespacedPolynomialReference> {
     pub params: Vec<String>,
     pub body: Box<Expression<T, Ref>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ArrayLiteral<T, Ref = NamespacedPolynomialReference> {
     pub items: Vec<Expression<T, Ref>>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum UnaryOperator {
     Plus,
     Minus,
@@ -207,7 +293,7 @@ impl UnaryOperator {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum BinaryOperator {
     Add,
     Sub,
@@ -230,32 +316,32 @@ pub enum BinaryOperator {
     Greater,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct IndexAccess<T, Ref = NamespacedPolynomialReference> {
     pub array: Box<Expression<T, Ref>>,
     pub index: Box<Expression<T, Ref>>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct FunctionCall<T, Ref = NamespacedPolynomialReference> {
     pub function: Box<Expression<T, Ref>>,
     pub arguments: Vec<Expression<T, Ref>>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct MatchArm<T, Ref = NamespacedPolynomialReference> {
     pub pattern: MatchPattern<T, Ref>,
     pub value: Expression<T, Ref>,
 }
 
 /// A pattern for a match arm. We could extend this in the future.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub enum MatchPattern<T, Ref = NamespacedPolynomialReference> {
     CatchAll,
     Pattern(Expression<T, Ref>),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct IfExpression<T, Ref = NamespacedPolynomialReference> {
     pub condition: Box<Expression<T, Ref>>,
     pub body: Box<Expression<T, Ref>>,
@@ -269,12 +355,29 @@ pub enum FunctionDefinition<T> {
     Array(ArrayExpression<T>),
     /// Prover query.
     Query(Vec<String>, Expression<T>),
+    /// Hint for witness generation, a function from the row to the value.
+    Hint(Expression<T>),
     /// Generic expression
     Expression(Expression<T>),
     /// Constant for public inputs
     Number(usize),
 }
 
+/// The definition of a two-dimensional fixed table (excluding its name), e.g.
+/// `col fixed AND(a, b) -> c { a & b } over (256, 256);`
+/// It is lowered into one fixed column per parameter and one for the output.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct TableDefinition<T> {
+    /// The names of the two indices.
+    pub params: Vec<String>,
+    /// The name of the output.
+    pub output: String,
+    /// The value of the output as a function of the indices.
+    pub body: Box<Expression<T>>,
+    /// The number of values of each of the indices.
+    pub dimensions: Vec<Expression<T>>,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum ArrayExpression<T> {
     Value(Vec<Expression<T>>),
@@ -217,13 +217,18 @@ impl<T> ExpressionVisitable<Expression<T, NamespacedPolynomialReference>> for Pi
                 .into_iter()
                 .try_for_each(|e| e.visit_expressions_mut(f, o)),
 
-            PilStatement::PolynomialConstantDefinition(_, _, fundef)
-            | PilStatement::PolynomialCommitDeclaration(_, _, Some(fundef), _) => {
+            PilStatement::PolynomialConstantDefinition(_, _, _, fundef)
+            | PilStatement::PolynomialCommitDeclaration(_, _, _, Some(fundef), _, _) => {
                 fundef.visit_expressions_mut(f, o)
             }
-            PilStatement::PolynomialCommitDeclaration(_, _, None, _)
-            | PilStatement::Include(_, _)
-            | PilStatement::PolynomialConstantDeclaration(_, _)
+            PilStatement::PolynomialConstantTableDefinition(_, _, table) => {
+                once(table.body.as_mut())
+                    .chain(table.dimensions.iter_mut())
+                    .try_for_each(|e| e.visit_expressions_mut(f, o))
+            }
+            PilStatement::PolynomialCommitDeclaration(_, _, _, None, _, _)
+            | PilStatement::Include(_, _, _)
+            | PilStatement::PolynomialConstantDeclaration(_, _, _)
             | PilStatement::LetStatement(_, _, None) => ControlFlow::Continue(()),
         }
     }
@@ -259,13 +264,18 @@ impl<T> ExpressionVisitable<Expression<T, NamespacedPolynomialReference>> for Pi
                 .into_iter()
                 .try_for_each(|e| e.visit_expressions(f, o)),
 
-            PilStatement::PolynomialConstantDefinition(_, _, fundef)
-            | PilStatement::PolynomialCommitDeclaration(_, _, Some(fundef), _) => {
+            PilStatement::PolynomialConstantDefinition(_, _, _, fundef)
+            | PilStatement::PolynomialCommitDeclaration(_, _, _, Some(fundef), _, _) => {
                 fundef.visit_expressions(f, o)
             }
-            PilStatement::PolynomialCommitDeclaration(_, _, None, _)
-            | PilStatement::Include(_, _)
-            | PilStatement::PolynomialConstantDeclaration(_, _)
+            PilStatement::PolynomialConstantTableDefinition(_, _, table) => {
+                once(table.body.as_ref())
+                    .chain(table.dimensions.iter())
+                    .try_for_each(|e| e.visit_expressions(f, o))
+            }
+            PilStatement::PolynomialCommitDeclaration(_, _, _, None, _, _)
+            | PilStatement::Include(_, _, _)
+            | PilStatement::PolynomialConstantDeclaration(_, _, _)
             | PilStatement::LetStatement(_, _, None) => ControlFlow::Continue(()),
         }
     }
@@ -301,7 +311,9 @@ impl<T> ExpressionVisitable<Expression<T>> for FunctionDefinition<T> {
         F: FnMut(&mut Expression<T>) -> ControlFlow<B>,
     {
         match self {
-            FunctionDefinition::Query(_, e) => e.visit_expressions_mut(f, o),
+            FunctionDefinition::Query(_, e) | FunctionDefinition::Hint(e) => {
+                e.visit_expressions_mut(f, o)
+            }
             FunctionDefinition::Array(ae) => ae.visit_expressions_mut(f, o),
             FunctionDefinition::Expression(e) => e.visit_expressions_mut(f, o),
             FunctionDefinition::Number(_) => ControlFlow::Continue(()),
@@ -313,7 +325,9 @@ impl<T> ExpressionVisitable<Expression<T>> for FunctionDefinition<T> {
         F: FnMut(&Expression<T>) -> ControlFlow<B>,
     {
         match self {
-            FunctionDefinition::Query(_, e) => e.visit_expressions(f, o),
+            FunctionDefinition::Query(_, e) | FunctionDefinition::Hint(e) => {
+                e.visit_expressions(f, o)
+            }
             FunctionDefinition::Array(ae) => ae.visit_expressions(f, o),
             FunctionDefinition::Expression(e) => e.visit_expressions(f, o),
             FunctionDefinition::Number(_) => ControlFlow::Continue(()),
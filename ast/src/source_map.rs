@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use parser_util::lines::{compute_line_starts, offset_to_line};
+
+use crate::analyzed::SourceRef;
+use crate::parsed::asm::{
+    ASMModule, FunctionStatement, InstructionBody, MachineStatement, Module, ModuleStatement,
+    SymbolValue,
+};
+use crate::parsed::PilStatement;
+
+/// The files an ASM program was loaded from.
+///
+/// The source positions in the ASM AST are global: the positions in each file are
+/// shifted by the position at which the file starts in the map, so that a position
+/// identifies both the file and the line. Position 0 is not part of any file and is
+/// used for generated statements.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SourceFile {
+    /// The file name, without directories.
+    name: String,
+    /// The global position of the first character.
+    start: usize,
+    len: usize,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    /// Adds a file and returns the global position of its first character.
+    pub fn add_file(&mut self, name: &str, contents: &str) -> usize {
+        let start = self
+            .files
+            .last()
+            .map(|file| file.start + file.len + 1)
+            .unwrap_or(1);
+        let name = Path::new(name)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string());
+        self.files.push(SourceFile {
+            name,
+            start,
+            len: contents.len(),
+            line_starts: compute_line_starts(contents),
+        });
+        start
+    }
+
+    /// Adds the file a module was parsed from and turns the positions in the module
+    /// (but not in its external submodules, which are loaded separately) into global ones.
+    pub fn add_module<T>(&mut self, name: &str, contents: &str, module: &mut ASMModule<T>) {
+        let start = self.add_file(name, contents);
+        shift_module(module, start);
+    }
+
+    /// The file and line of a global position, `None` for generated statements.
+    pub fn source_ref(&self, pos: usize) -> Option<SourceRef> {
+        let file = self.file(pos)?;
+        Some(SourceRef {
+            file: file.name.clone(),
+            line: offset_to_line(pos - file.start, &file.line_starts),
+        })
+    }
+
+    /// The file name and byte offset in the file of a global position,
+    /// `None` for generated statements.
+    pub fn file_offset(&self, pos: usize) -> Option<(&str, usize)> {
+        let file = self.file(pos)?;
+        Some((&file.name, pos - file.start))
+    }
+
+    fn file(&self, pos: usize) -> Option<&SourceFile> {
+        let index = self.files.partition_point(|file| file.start <= pos);
+        let file = self.files[..index].last()?;
+        (pos <= file.start + file.len).then_some(file)
+    }
+}
+
+fn shift_module<T>(module: &mut ASMModule<T>, offset: usize) {
+    for statement in &mut module.statements {
+        let ModuleStatement::SymbolDefinition(definition) = statement;
+        match &mut definition.value {
+            SymbolValue::Machine(machine) => {
+                machine.start += offset;
+                for statement in &mut machine.statements {
+                    shift_machine_statement(statement, offset);
+                }
+            }
+            SymbolValue::Module(Module::Local(module)) => shift_module(module, offset),
+            SymbolValue::Module(Module::External(_)) | SymbolValue::Import(_) => {}
+        }
+    }
+}
+
+fn shift_machine_statement<T>(statement: &mut MachineStatement<T>, offset: usize) {
+    match statement {
+        MachineStatement::Pil(start, statement) => {
+            *start += offset;
+            shift_pil_statement(statement, offset);
+        }
+        MachineStatement::InstructionDeclaration(start, _, instruction) => {
+            *start += offset;
+            if let InstructionBody::Local(body) = &mut instruction.body {
+                for statement in body {
+                    shift_pil_statement(statement, offset);
+                }
+            }
+        }
+        MachineStatement::FunctionDeclaration(start, _, _, statements) => {
+            *start += offset;
+            for statement in statements {
+                match statement {
+                    FunctionStatement::Assignment(start, ..)
+                    | FunctionStatement::Instruction(start, ..)
+                    | FunctionStatement::Label(start, _)
+                    | FunctionStatement::DebugDirective(start, _)
+                    | FunctionStatement::Return(start, _) => *start += offset,
+                }
+            }
+        }
+        MachineStatement::LinkDeclaration(link) => link.start += offset,
+        MachineStatement::Degree(start, _)
+        | MachineStatement::Submachine(start, ..)
+        | MachineStatement::RegisterDeclaration(start, ..)
+        | MachineStatement::OperationDeclaration(start, ..) => *start += offset,
+    }
+}
+
+fn shift_pil_statement<T>(statement: &mut PilStatement<T>, offset: usize) {
+    match statement {
+        PilStatement::Include(start, ..)
+        | PilStatement::Namespace(start, ..)
+        | PilStatement::LetStatement(start, ..)
+        | PilStatement::PolynomialDefinition(start, ..)
+        | PilStatement::PublicDeclaration(start, ..)
+        | PilStatement::PolynomialConstantDeclaration(start, ..)
+        | PilStatement::PolynomialConstantDefinition(start, ..)
+        | PilStatement::PolynomialConstantTableDefinition(start, ..)
+        | PilStatement::PolynomialCommitDeclaration(start, ..)
+        | PilStatement::PolynomialIdentity(start, ..)
+        | PilStatement::PlookupIdentity(start, ..)
+        | PilStatement::PermutationIdentity(start, ..)
+        | PilStatement::ConnectIdentity(start, ..)
+        | PilStatement::ConstantDefinition(start, ..)
+        | PilStatement::Expression(start, _) => *start += offset,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn source_refs() {
+        let mut sources = SourceMap::default();
+        let main = sources.add_file("dir/main.asm", "a\nb\n");
+        let module = sources.add_file("module.asm", "c\nd");
+        assert_eq!((main, module), (1, 6));
+        let source_ref = |pos| {
+            sources
+                .source_ref(pos)
+                .map(|s| format!("{}:{}", s.file, s.line))
+        };
+        assert_eq!(source_ref(0), None);
+        assert_eq!(source_ref(main), Some("main.asm:1".to_string()));
+        assert_eq!(source_ref(main + 2), Some("main.asm:2".to_string()));
+        assert_eq!(source_ref(module + 2), Some("module.asm:2".to_string()));
+        assert_eq!(source_ref(module + 10), None);
+        assert_eq!(sources.file_offset(module + 2), Some(("module.asm", 2)));
+        assert_eq!(sources.file_offset(0), None);
+    }
+}
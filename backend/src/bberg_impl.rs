@@ -7,6 +7,7 @@ use ast::analyzed::Analyzed;
 // however we can just perform codegen
 use bberg::bberg_codegen::BBergCodegen;
 use number::{DegreeType, FieldElement};
+use rand::RngCore;
 
 impl<T: FieldElement> BackendImpl<T> for BBergCodegen {
     fn new(degree: DegreeType) -> Self {
@@ -19,6 +20,7 @@ impl<T: FieldElement> BackendImpl<T> for BBergCodegen {
         pil: &Analyzed<T>,
         fixed: &[(String, Vec<T>)],
         witness: &[(String, Vec<T>)],
+        _publics: &[(String, T)],
         _prev_proof: Option<Proof>,
         bname: Option<String>,
     ) -> (Option<Proof>, Option<String>) {
@@ -32,6 +34,13 @@ impl<T: FieldElement> BackendImpl<T> for BBergCodegen {
 }
 
 impl<T: FieldElement> BackendImplWithSetup<T> for BBergCodegen {
+    fn new_with_rng(degree: DegreeType, _rng: &mut dyn RngCore) -> Self {
+        // Code generation does not need any randomness, so the setup does not depend on
+        // the seed. Note that the BBerg backend is registered without setup, so this is
+        // not reached from `powdr setup`.
+        <Self as BackendImpl<T>>::new(degree)
+    }
+
     fn new_from_setup(mut input: &mut dyn io::Read) -> Result<Self, io::Error> {
         BBergCodegen::assert_field_is_compatible::<T>();
         BBergCodegen::new_from_setup(&mut input)
@@ -55,6 +64,7 @@ impl<T: FieldElement> BackendImpl<T> for BBergMock {
         _pil: &Analyzed<T>,
         _fixed: &[(String, Vec<T>)],
         _witness: &[(String, Vec<T>)],
+        _publics: &[(String, T)],
         prev_proof: Option<Proof>,
         _bname: Option<String>,
     ) -> (Option<Proof>, Option<String>) {
@@ -2,14 +2,21 @@
 
 // #[cfg(feature = "bberg")]
 mod bberg_impl;
+mod metadata;
 mod pilstark;
+mod proof_file;
 
-use ast::analyzed::Analyzed;
-use number::{DegreeType, FieldElement};
-use std::{io, marker::PhantomData};
+pub use metadata::{check_aggregation_compatible, pil_hash, ProofMetadata};
+pub use proof_file::{ProofFile, PROOF_FORMAT_VERSION};
+
+use ast::analyzed::{Analyzed, IdentityKind, PolynomialType};
+use number::{ColumnAccess, DegreeType, FieldElement};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::{borrow::Cow, io, marker::PhantomData};
 use strum::{Display, EnumString, EnumVariantNames};
 
-#[derive(Clone, EnumString, EnumVariantNames, Display)]
+#[derive(Clone, Debug, PartialEq, EnumString, EnumVariantNames, Display)]
 pub enum BackendType {
     // #[cfg(feature = "bberg")]
     #[strum(serialize = "bberg")]
@@ -35,6 +42,26 @@ impl BackendType {
             BackendType::BBerg => &BBERG_FACTORY,
         }
     }
+
+    /// Checks that the backend supports all identities of `pil`. Only the backends
+    /// based on pil-stark support connect identities.
+    pub fn check_supported<T>(&self, pil: &Analyzed<T>) -> Result<(), String> {
+        let supports_connect = match self {
+            BackendType::EStark | BackendType::PilStarkCli => true,
+            BackendType::BBerg => false,
+        };
+        let has_connect = pil
+            .identities
+            .iter()
+            .any(|identity| identity.kind == IdentityKind::Connect);
+        if has_connect && !supports_connect {
+            Err(format!(
+                "The backend {self} does not support connect identities."
+            ))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// Factory for backends without setup.
@@ -46,9 +73,23 @@ impl<F: FieldElement, B: BackendImpl<F> + 'static> BackendFactory<F> for Without
         Box::new(ConcreteBackendWithoutSetup(B::new(degree)))
     }
 
+    fn create_with_seed(&self, degree: DegreeType, _seed: u64) -> Box<dyn Backend<F>> {
+        // There is no setup, so there is no randomness to seed.
+        self.create(degree)
+    }
+
     fn create_from_setup(&self, _input: &mut dyn io::Read) -> Result<Box<dyn Backend<F>>, Error> {
         Err(Error::NoSetupAvailable)
     }
+
+    fn create_from_verification_key(
+        &self,
+        input: &mut dyn io::Read,
+    ) -> Result<Box<dyn Backend<F>>, Error> {
+        Ok(Box::new(ConcreteBackendWithoutSetup(
+            B::new_from_verification_key(input)?,
+        )))
+    }
 }
 
 /// Concrete dynamic dispatch Backend object, for backends without setup.
@@ -61,15 +102,86 @@ impl<F: FieldElement, B: BackendImpl<F>> Backend<F> for ConcreteBackendWithoutSe
         pil: &Analyzed<F>,
         fixed: &[(String, Vec<F>)],
         witness: &[(String, Vec<F>)],
+        publics: &[(String, F)],
         prev_proof: Option<Proof>,
         bname: Option<String>,
-    ) -> (Option<Proof>, Option<String>) {
-        self.0.prove(pil, fixed, witness, prev_proof, bname)
+    ) -> (Option<(Proof, ProofMetadata)>, Option<String>) {
+        let fixed =
+            in_pil_order(pil, PolynomialType::Constant, fixed).unwrap_or_else(|e| panic!("{e}"));
+        let witness =
+            in_pil_order(pil, PolynomialType::Committed, witness).unwrap_or_else(|e| panic!("{e}"));
+        let (proof, constraints_serialization) = self
+            .0
+            .prove(pil, &fixed, &witness, publics, prev_proof, bname);
+        (
+            proof.map(|proof| {
+                let metadata = ProofMetadata::new(pil, &proof);
+                (proof, metadata)
+            }),
+            constraints_serialization,
+        )
+    }
+
+    fn prove_columns(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, &dyn ColumnAccess<F>)],
+        witness: &[(String, &dyn ColumnAccess<F>)],
+        publics: &[(String, F)],
+        prev_proof: Option<Proof>,
+        bname: Option<String>,
+    ) -> (Option<(Proof, ProofMetadata)>, Option<String>) {
+        let fixed =
+            in_pil_order(pil, PolynomialType::Constant, fixed).unwrap_or_else(|e| panic!("{e}"));
+        let witness =
+            in_pil_order(pil, PolynomialType::Committed, witness).unwrap_or_else(|e| panic!("{e}"));
+        let (proof, constraints_serialization) = self
+            .0
+            .prove_columns(pil, &fixed, &witness, publics, prev_proof, bname);
+        (
+            proof.map(|proof| {
+                let metadata = ProofMetadata::new(pil, &proof);
+                (proof, metadata)
+            }),
+            constraints_serialization,
+        )
+    }
+
+    fn aggregate(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        accumulated: &[u8],
+        proof: &[u8],
+    ) -> Result<Proof, Error> {
+        let fixed = in_pil_order(pil, PolynomialType::Constant, fixed)?;
+        self.0.aggregate(pil, &fixed, accumulated, proof)
+    }
+
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        proof: &[u8],
+        publics: &[(String, F)],
+    ) -> Result<(), Error> {
+        let fixed = in_pil_order(pil, PolynomialType::Constant, fixed)?;
+        self.0.verify(pil, &fixed, proof, publics)
     }
 
     fn write_setup(&self, _output: &mut dyn io::Write) -> Result<(), Error> {
         Err(Error::NoSetupAvailable)
     }
+
+    fn export_verification_key(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), Error> {
+        let fixed = in_pil_order(pil, PolynomialType::Constant, fixed)?;
+        self.0.export_verification_key(pil, &fixed, output)
+    }
 }
 
 /// Factory for backends with setup.
@@ -83,11 +195,25 @@ impl<F: FieldElement, B: BackendImplWithSetup<F> + 'static> BackendFactory<F>
         Box::new(ConcreteBackendWithSetup(B::new(degree)))
     }
 
+    fn create_with_seed(&self, degree: DegreeType, seed: u64) -> Box<dyn Backend<F>> {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        Box::new(ConcreteBackendWithSetup(B::new_with_rng(degree, &mut rng)))
+    }
+
     fn create_from_setup(&self, input: &mut dyn io::Read) -> Result<Box<dyn Backend<F>>, Error> {
         Ok(Box::new(ConcreteBackendWithSetup(B::new_from_setup(
             input,
         )?)))
     }
+
+    fn create_from_verification_key(
+        &self,
+        input: &mut dyn io::Read,
+    ) -> Result<Box<dyn Backend<F>>, Error> {
+        Ok(Box::new(ConcreteBackendWithSetup(
+            B::new_from_verification_key(input)?,
+        )))
+    }
 }
 
 /// Concrete dynamic dispatch Backend object, for backends with setup.
@@ -100,15 +226,86 @@ impl<F: FieldElement, B: BackendImplWithSetup<F>> Backend<F> for ConcreteBackend
         pil: &Analyzed<F>,
         fixed: &[(String, Vec<F>)],
         witness: &[(String, Vec<F>)],
+        publics: &[(String, F)],
         prev_proof: Option<Proof>,
         bname: Option<String>,
-    ) -> (Option<Proof>, Option<String>) {
-        self.0.prove(pil, fixed, witness, prev_proof, bname)
+    ) -> (Option<(Proof, ProofMetadata)>, Option<String>) {
+        let fixed =
+            in_pil_order(pil, PolynomialType::Constant, fixed).unwrap_or_else(|e| panic!("{e}"));
+        let witness =
+            in_pil_order(pil, PolynomialType::Committed, witness).unwrap_or_else(|e| panic!("{e}"));
+        let (proof, constraints_serialization) = self
+            .0
+            .prove(pil, &fixed, &witness, publics, prev_proof, bname);
+        (
+            proof.map(|proof| {
+                let metadata = ProofMetadata::new(pil, &proof);
+                (proof, metadata)
+            }),
+            constraints_serialization,
+        )
+    }
+
+    fn prove_columns(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, &dyn ColumnAccess<F>)],
+        witness: &[(String, &dyn ColumnAccess<F>)],
+        publics: &[(String, F)],
+        prev_proof: Option<Proof>,
+        bname: Option<String>,
+    ) -> (Option<(Proof, ProofMetadata)>, Option<String>) {
+        let fixed =
+            in_pil_order(pil, PolynomialType::Constant, fixed).unwrap_or_else(|e| panic!("{e}"));
+        let witness =
+            in_pil_order(pil, PolynomialType::Committed, witness).unwrap_or_else(|e| panic!("{e}"));
+        let (proof, constraints_serialization) = self
+            .0
+            .prove_columns(pil, &fixed, &witness, publics, prev_proof, bname);
+        (
+            proof.map(|proof| {
+                let metadata = ProofMetadata::new(pil, &proof);
+                (proof, metadata)
+            }),
+            constraints_serialization,
+        )
+    }
+
+    fn aggregate(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        accumulated: &[u8],
+        proof: &[u8],
+    ) -> Result<Proof, Error> {
+        let fixed = in_pil_order(pil, PolynomialType::Constant, fixed)?;
+        self.0.aggregate(pil, &fixed, accumulated, proof)
+    }
+
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        proof: &[u8],
+        publics: &[(String, F)],
+    ) -> Result<(), Error> {
+        let fixed = in_pil_order(pil, PolynomialType::Constant, fixed)?;
+        self.0.verify(pil, &fixed, proof, publics)
     }
 
     fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), Error> {
         Ok(self.0.write_setup(output)?)
     }
+
+    fn export_verification_key(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), Error> {
+        let fixed = in_pil_order(pil, PolynomialType::Constant, fixed)?;
+        self.0.export_verification_key(pil, &fixed, output)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -117,10 +314,94 @@ pub enum Error {
     IO(#[from] std::io::Error),
     #[error("the backend has not setup operations")]
     NoSetupAvailable,
+    #[error("the backend does not support proof verification")]
+    NoVerificationAvailable,
+    #[error("the backend does not support verification keys")]
+    NoVerificationKeyAvailable,
+    #[error("the backend does not support proof aggregation")]
+    NoAggregationAvailable,
+    #[error("the proof is invalid: {0}")]
+    InvalidProof(String),
+    #[error("the columns do not match the PIL: {0}")]
+    ColumnMismatch(String),
 }
 
+/// Returns the fixed or witness `columns` in the source order of `pil`, which the backends
+/// rely on. Columns given in a different order are reordered, missing or unknown columns
+/// are an error. An empty list (for a backend that does not need the fixed columns) is
+/// returned unchanged.
+fn in_pil_order<'a, F: FieldElement, C: Clone>(
+    pil: &Analyzed<F>,
+    ptype: PolynomialType,
+    columns: &'a [(String, C)],
+) -> Result<Cow<'a, [(String, C)]>, Error> {
+    let polys = match ptype {
+        PolynomialType::Constant => pil.constant_polys_in_source_order(),
+        PolynomialType::Committed => pil.committed_polys_in_source_order(),
+        PolynomialType::Intermediate => unreachable!(),
+    };
+    let names = polys
+        .iter()
+        .flat_map(|(poly, _)| poly.array_elements())
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    if columns.is_empty() || columns.iter().map(|(name, _)| name).eq(names.iter()) {
+        return Ok(Cow::Borrowed(columns));
+    }
+    if let Some((name, _)) = columns.iter().find(|(name, _)| !names.contains(name)) {
+        return Err(Error::ColumnMismatch(format!("unknown column {name}")));
+    }
+    log::warn!("The columns are not in the order of the PIL, reordering them.");
+    names
+        .iter()
+        .map(|name| {
+            columns
+                .iter()
+                .find(|(n, _)| n == name)
+                .cloned()
+                .ok_or_else(|| Error::ColumnMismatch(format!("missing column {name}")))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Cow::Owned)
+}
+
+/// The bytes of a proof as generated by a backend. Proof files wrap it in a [ProofFile].
 pub type Proof = Vec<u8>;
 
+/// Evaluates the public declarations of `pil` on the witness, i.e. looks up the
+/// value of the declared column at the declared row.
+/// Returns the name and value of each public, in declaration order.
+pub fn evaluate_publics<F: FieldElement, C: ColumnAccess<F>>(
+    pil: &Analyzed<F>,
+    witness: &[(String, C)],
+) -> Result<Vec<(String, F)>, String> {
+    let mut declarations = pil.public_declarations.values().collect::<Vec<_>>();
+    declarations.sort_by_key(|decl| decl.id);
+    declarations
+        .into_iter()
+        .map(|decl| {
+            let column = match decl.array_index {
+                Some(i) => format!("{}[{i}]", decl.polynomial.name),
+                None => decl.polynomial.name.clone(),
+            };
+            let values = witness
+                .iter()
+                .find(|(name, _)| *name == column)
+                .map(|(_, values)| values)
+                .ok_or_else(|| {
+                    format!("Witness column {column} of public {} not found.", decl.name)
+                })?;
+            if decl.index as usize >= values.len() {
+                return Err(format!(
+                    "Row {} of public {} is out of range for column {column}.",
+                    decl.index, decl.name
+                ));
+            }
+            Ok((decl.name.clone(), values.index(decl.index as usize)))
+        })
+        .collect()
+}
+
 /*
     Bellow are the public interface traits. They are implemented in this
     module, wrapping the traits implemented by each backend.
@@ -130,21 +411,72 @@ pub type Proof = Vec<u8>;
 pub trait Backend<F: FieldElement> {
     /// Perform the proving.
     ///
+    /// `publics` are the values of the public declarations, see [evaluate_publics].
     /// If prev_proof is provided, proof aggregation is performed.
     ///
-    /// Returns the generated proof, and the string serialization of the
-    /// constraints.
+    /// Returns the generated proof with its metadata, and the string serialization
+    /// of the constraints.
     fn prove(
         &self,
         pil: &Analyzed<F>,
         fixed: &[(String, Vec<F>)],
         witness: &[(String, Vec<F>)],
+        publics: &[(String, F)],
         prev_proof: Option<Proof>,
         bname: Option<String>,
-    ) -> (Option<Proof>, Option<String>);
+    ) -> (Option<(Proof, ProofMetadata)>, Option<String>);
+
+    /// Like [Backend::prove], but takes the columns as [ColumnAccess], e.g. columns
+    /// mapped from the files with [number::map_polys_file]. Backends that do not
+    /// support it copy the columns into memory first.
+    fn prove_columns(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, &dyn ColumnAccess<F>)],
+        witness: &[(String, &dyn ColumnAccess<F>)],
+        publics: &[(String, F)],
+        prev_proof: Option<Proof>,
+        bname: Option<String>,
+    ) -> (Option<(Proof, ProofMetadata)>, Option<String>);
+
+    /// Aggregates `proof` into `accumulated`, which is a proof for the same PIL or the
+    /// result of a previous aggregation, and returns the aggregated proof.
+    ///
+    /// Returns [Error::NoAggregationAvailable] if the backend does not support it.
+    fn aggregate(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        accumulated: &[u8],
+        proof: &[u8],
+    ) -> Result<Proof, Error>;
+
+    /// Verify a proof previously generated by [Backend::prove] against the
+    /// given PIL, fixed columns and expected values of the public declarations.
+    ///
+    /// Returns [Error::InvalidProof] if the proof does not verify.
+    /// If the backend was created from a verification key, `fixed` is not used
+    /// and can be empty.
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        proof: &[u8],
+        publics: &[(String, F)],
+    ) -> Result<(), Error>;
 
     /// Write the prover setup to a file, so that it can be loaded later.
     fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), Error>;
+
+    /// Write the verification key for the given PIL and fixed columns to a file.
+    /// Together with the PIL, it is enough to verify proofs, see
+    /// [BackendFactory::create_from_verification_key].
+    fn export_verification_key(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), Error>;
 }
 
 /// Dynamic interface for a backend factory.
@@ -152,8 +484,19 @@ pub trait BackendFactory<F: FieldElement> {
     /// Maybe perform the setup, and create a new backend object.
     fn create(&self, degree: DegreeType) -> Box<dyn Backend<F>>;
 
+    /// Like [BackendFactory::create], but derives all randomness of the setup
+    /// from `seed`, so that the written setup is reproducible.
+    fn create_with_seed(&self, degree: DegreeType, seed: u64) -> Box<dyn Backend<F>>;
+
     /// Create a backend object from a prover setup loaded from a file.
     fn create_from_setup(&self, input: &mut dyn io::Read) -> Result<Box<dyn Backend<F>>, Error>;
+
+    /// Create a backend object that can only verify proofs, from a verification key
+    /// written by [Backend::export_verification_key].
+    fn create_from_verification_key(
+        &self,
+        input: &mut dyn io::Read,
+    ) -> Result<Box<dyn Backend<F>>, Error>;
 }
 
 /*
@@ -169,9 +512,72 @@ trait BackendImpl<F: FieldElement> {
         pil: &Analyzed<F>,
         fixed: &[(String, Vec<F>)],
         witness: &[(String, Vec<F>)],
+        publics: &[(String, F)],
         prev_proof: Option<Proof>,
         bname: Option<String>,
     ) -> (Option<Proof>, Option<String>);
+
+    /// See [Backend::prove_columns]. By default, the columns are copied into memory.
+    fn prove_columns(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, &dyn ColumnAccess<F>)],
+        witness: &[(String, &dyn ColumnAccess<F>)],
+        publics: &[(String, F)],
+        prev_proof: Option<Proof>,
+        bname: Option<String>,
+    ) -> (Option<Proof>, Option<String>) {
+        let to_vec = |columns: &[(String, &dyn ColumnAccess<F>)]| {
+            columns
+                .iter()
+                .map(|(name, column)| (name.clone(), column.values().collect()))
+                .collect::<Vec<_>>()
+        };
+        self.prove(
+            pil,
+            &to_vec(fixed),
+            &to_vec(witness),
+            publics,
+            prev_proof,
+            bname,
+        )
+    }
+
+    fn aggregate(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(String, Vec<F>)],
+        _accumulated: &[u8],
+        _proof: &[u8],
+    ) -> Result<Proof, Error> {
+        Err(Error::NoAggregationAvailable)
+    }
+
+    fn verify(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(String, Vec<F>)],
+        _proof: &[u8],
+        _publics: &[(String, F)],
+    ) -> Result<(), Error> {
+        Err(Error::NoVerificationAvailable)
+    }
+
+    fn new_from_verification_key(_input: &mut dyn io::Read) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Err(Error::NoVerificationKeyAvailable)
+    }
+
+    fn export_verification_key(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(String, Vec<F>)],
+        _output: &mut dyn io::Write,
+    ) -> Result<(), Error> {
+        Err(Error::NoVerificationKeyAvailable)
+    }
 }
 
 /// Trait implemented by backends that have a setup phase that must be saved to
@@ -180,9 +586,191 @@ trait BackendImplWithSetup<F: FieldElement>
 where
     Self: Sized + BackendImpl<F>,
 {
+    /// Perform the setup, taking all randomness from `rng`.
+    fn new_with_rng(degree: DegreeType, rng: &mut dyn RngCore) -> Self;
+
     /// Create a backend object from a setup loaded from a file.
     fn new_from_setup(input: &mut dyn io::Read) -> Result<Self, io::Error>;
 
     /// Write the setup to a file.
     fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), io::Error>;
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use number::{column_refs, map_polys_file, write_polys_file, GoldilocksField};
+
+    use super::*;
+
+    /// A backend whose setup consists of random bytes only.
+    struct RandomSetup(Vec<u8>);
+
+    impl<F: FieldElement> BackendImpl<F> for RandomSetup {
+        fn new(degree: DegreeType) -> Self {
+            <Self as BackendImplWithSetup<F>>::new_with_rng(degree, &mut rand::thread_rng())
+        }
+
+        fn prove(
+            &self,
+            _pil: &Analyzed<F>,
+            _fixed: &[(String, Vec<F>)],
+            _witness: &[(String, Vec<F>)],
+            _publics: &[(String, F)],
+            _prev_proof: Option<Proof>,
+            _bname: Option<String>,
+        ) -> (Option<Proof>, Option<String>) {
+            unimplemented!()
+        }
+    }
+
+    impl<F: FieldElement> BackendImplWithSetup<F> for RandomSetup {
+        fn new_with_rng(degree: DegreeType, rng: &mut dyn RngCore) -> Self {
+            let mut params = vec![0; degree as usize];
+            rng.fill_bytes(&mut params);
+            Self(params)
+        }
+
+        fn new_from_setup(input: &mut dyn io::Read) -> Result<Self, io::Error> {
+            let mut params = vec![];
+            input.read_to_end(&mut params)?;
+            Ok(Self(params))
+        }
+
+        fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), io::Error> {
+            output.write_all(&self.0)
+        }
+    }
+
+    /// A backend whose proof consists of the columns it was given.
+    struct ColumnsProof;
+
+    impl<F: FieldElement> BackendImpl<F> for ColumnsProof {
+        fn new(_degree: DegreeType) -> Self {
+            Self
+        }
+
+        fn prove(
+            &self,
+            _pil: &Analyzed<F>,
+            fixed: &[(String, Vec<F>)],
+            witness: &[(String, Vec<F>)],
+            publics: &[(String, F)],
+            _prev_proof: Option<Proof>,
+            _bname: Option<String>,
+        ) -> (Option<Proof>, Option<String>) {
+            let mut proof = vec![];
+            write_polys_file(&mut proof, fixed);
+            write_polys_file(&mut proof, witness);
+            for (_, value) in publics {
+                proof.extend(value.to_bytes_le());
+            }
+            (Some(proof), None)
+        }
+    }
+
+    fn write_params(seed: Option<u64>) -> Vec<u8> {
+        let factory: WithSetupFactory<RandomSetup> = WithSetupFactory(PhantomData);
+        let backend: Box<dyn Backend<GoldilocksField>> = match seed {
+            Some(seed) => factory.create_with_seed(64, seed),
+            None => factory.create(64),
+        };
+        let mut params = vec![];
+        backend.write_setup(&mut params).unwrap();
+        params
+    }
+
+    #[test]
+    fn same_seed_same_setup() {
+        assert_eq!(write_params(Some(7)), write_params(Some(7)));
+    }
+
+    #[test]
+    fn different_seed_different_setup() {
+        assert_ne!(write_params(Some(7)), write_params(Some(8)));
+        assert_ne!(write_params(None), write_params(None));
+    }
+
+    #[test]
+    fn column_order() {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(4);\n    pol commit x;\n    pol commit y[2];\n    x = y[0] + y[1];",
+        );
+        let column = |name: &str| (name.to_string(), vec![GoldilocksField::from(0); 4]);
+        let names = |columns: &[(String, Vec<GoldilocksField>)]| {
+            columns.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>()
+        };
+
+        let ordered = [column("N.x"), column("N.y[0]"), column("N.y[1]")];
+        let result = in_pil_order(&pil, PolynomialType::Committed, &ordered).unwrap();
+        assert!(matches!(result, Cow::Borrowed(_)));
+
+        let reordered = [column("N.y[1]"), column("N.x"), column("N.y[0]")];
+        let result = in_pil_order(&pil, PolynomialType::Committed, &reordered).unwrap();
+        assert_eq!(names(&result), ["N.x", "N.y[0]", "N.y[1]"]);
+
+        let renamed = [column("N.x"), column("N.z[0]"), column("N.y[1]")];
+        let err = in_pil_order(&pil, PolynomialType::Committed, &renamed).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the columns do not match the PIL: unknown column N.z[0]"
+        );
+        let err = in_pil_order(&pil, PolynomialType::Committed, &ordered[..2]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the columns do not match the PIL: missing column N.y[1]"
+        );
+    }
+
+    #[test]
+    fn mapped_columns() {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(8);\n    pol constant FIRST = [1] + [0]*;\n    pol commit x;\n    pol commit y[2];\n    public out = x(7);\n    x = y[0] + y[1];",
+        );
+        let column = |name: &str, offset: u64| {
+            let values = (0..8).map(|i| GoldilocksField::from(i * 3 + offset));
+            (name.to_string(), values.collect::<Vec<_>>())
+        };
+        let fixed = vec![column("N.FIRST", 0)];
+        let witness = vec![column("N.x", 1), column("N.y[0]", 2), column("N.y[1]", 3)];
+
+        let backend: Box<dyn Backend<GoldilocksField>> =
+            WithoutSetupFactory::<ColumnsProof>(PhantomData).create(8);
+        let publics = evaluate_publics(&pil, &witness).unwrap();
+        let (in_memory, _) = backend.prove(&pil, &fixed, &witness, &publics, None, None);
+
+        // The files can have the columns in a different order than the PIL.
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let mut reordered = witness.clone();
+        reordered.reverse();
+        write_polys_file(
+            &mut File::create(dir.join("constants.bin")).unwrap(),
+            &fixed,
+        );
+        write_polys_file(
+            &mut File::create(dir.join("commits.bin")).unwrap(),
+            &reordered,
+        );
+        let map = |file: &str, columns: &[(String, Vec<GoldilocksField>)]| {
+            let names = columns.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+            map_polys_file::<GoldilocksField>(&File::open(dir.join(file)).unwrap(), &names)
+                .unwrap()
+                .0
+        };
+        let mapped_fixed = map("constants.bin", &fixed);
+        let mapped_witness = map("commits.bin", &reordered);
+
+        let mapped_publics = evaluate_publics(&pil, &mapped_witness).unwrap();
+        assert_eq!(mapped_publics, publics);
+        let (mapped, _) = backend.prove_columns(
+            &pil,
+            &column_refs(&mapped_fixed),
+            &column_refs(&mapped_witness),
+            &mapped_publics,
+            None,
+            None,
+        );
+        assert_eq!(mapped.unwrap().0, in_memory.unwrap().0);
+    }
+}
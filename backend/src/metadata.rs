@@ -0,0 +1,162 @@
+use ast::analyzed::Analyzed;
+use number::{DegreeType, FieldElement};
+use parser_util::hash::sha256_hex;
+use serde::{Deserialize, Serialize};
+
+/// Information about a proof that is needed to aggregate it with other proofs.
+/// It is written next to the proof, see [ProofMetadata::file_name].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofMetadata {
+    pub degree: DegreeType,
+    pub witness_columns: usize,
+    pub fixed_columns: usize,
+    /// The SHA-256 hash (hex) of the PIL the proof is for.
+    pub pil_hash: String,
+    /// The SHA-256 hash (hex) of the proof. The proof contains all commitments and
+    /// evaluations the verifier's transcript is built from, so this identifies it.
+    pub transcript_hash: String,
+    /// The index of the chunk the proof is for, if it is a chunk of a continuations run.
+    pub chunk_index: Option<u64>,
+    /// The transcript hashes of the proofs that were aggregated into this proof,
+    /// empty if it is not an aggregated proof.
+    pub aggregated: Vec<String>,
+}
+
+impl ProofMetadata {
+    pub fn new<F: FieldElement>(pil: &Analyzed<F>, proof: &[u8]) -> Self {
+        ProofMetadata {
+            degree: pil.degree(),
+            witness_columns: pil.commitment_count(),
+            fixed_columns: pil.constant_count(),
+            pil_hash: pil_hash(pil),
+            transcript_hash: sha256_hex(proof),
+            chunk_index: None,
+            aggregated: vec![],
+        }
+    }
+
+    /// The name of the metadata file of the given proof file, `proof.meta.json` for `proof.bin`.
+    pub fn file_name(proof_file_name: &str) -> String {
+        let stem = proof_file_name
+            .rsplit_once('.')
+            .map_or(proof_file_name, |(stem, _)| stem);
+        format!("{stem}.meta.json")
+    }
+}
+
+/// The SHA-256 hash (hex) of the PIL, as recorded in [ProofMetadata::pil_hash].
+pub fn pil_hash<F: FieldElement>(pil: &Analyzed<F>) -> String {
+    sha256_hex(pil.to_string().as_bytes())
+}
+
+/// Checks that the proofs with the given metadata can be aggregated in the given order:
+/// they have to be proofs for the same PIL and, if they are chunks of a continuations
+/// run, for consecutive chunks.
+pub fn check_aggregation_compatible(metadata: &[ProofMetadata]) -> Result<(), String> {
+    let Some(first) = metadata.first() else {
+        return Err("No proofs to aggregate.".to_string());
+    };
+    for (i, m) in metadata.iter().enumerate().skip(1) {
+        if m.pil_hash != first.pil_hash {
+            return Err(format!(
+                "Proof {i} is for a different PIL than proof 0 (hash {} instead of {}).",
+                m.pil_hash, first.pil_hash
+            ));
+        }
+        if (m.degree, m.witness_columns, m.fixed_columns)
+            != (first.degree, first.witness_columns, first.fixed_columns)
+        {
+            return Err(format!(
+                "Proof {i} has a different degree or number of columns than proof 0 \
+                 ({} rows, {} witness and {} fixed columns instead of {}, {} and {}).",
+                m.degree,
+                m.witness_columns,
+                m.fixed_columns,
+                first.degree,
+                first.witness_columns,
+                first.fixed_columns
+            ));
+        }
+    }
+    if metadata.iter().any(|m| m.chunk_index.is_some()) {
+        for (i, pair) in metadata.windows(2).enumerate() {
+            let (Some(previous), Some(current)) = (pair[0].chunk_index, pair[1].chunk_index) else {
+                return Err("Either all or none of the proofs have to be for a chunk.".to_string());
+            };
+            if current != previous + 1 {
+                return Err(format!(
+                    "Proof {} is for chunk {current}, but proof {i} is for chunk {previous}, \
+                     chunks have to be consecutive.",
+                    i + 1
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+
+    use super::*;
+
+    fn metadata(chunk_index: Option<u64>) -> ProofMetadata {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(4);\n    col witness x;\n    x = x;\n",
+        );
+        ProofMetadata {
+            chunk_index,
+            ..ProofMetadata::new(&pil, &[chunk_index.unwrap_or_default() as u8])
+        }
+    }
+
+    #[test]
+    fn new_metadata() {
+        let m = metadata(None);
+        assert_eq!((m.degree, m.witness_columns, m.fixed_columns), (4, 1, 0));
+        assert_eq!(
+            m.transcript_hash,
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+        assert_eq!(ProofMetadata::file_name("proof.bin"), "proof.meta.json");
+        assert_eq!(
+            ProofMetadata::file_name("proof_aggr.bin"),
+            "proof_aggr.meta.json"
+        );
+    }
+
+    #[test]
+    fn compatible() {
+        assert_eq!(
+            check_aggregation_compatible(&[metadata(None), metadata(None)]),
+            Ok(())
+        );
+        assert_eq!(
+            check_aggregation_compatible(&[metadata(Some(3)), metadata(Some(4))]),
+            Ok(())
+        );
+        assert!(check_aggregation_compatible(&[]).is_err());
+    }
+
+    #[test]
+    fn incompatible() {
+        assert_eq!(
+            check_aggregation_compatible(&[metadata(Some(0)), metadata(Some(2))]),
+            Err("Proof 1 is for chunk 2, but proof 0 is for chunk 0, \
+                 chunks have to be consecutive."
+                .to_string())
+        );
+        assert_eq!(
+            check_aggregation_compatible(&[metadata(Some(0)), metadata(None)]),
+            Err("Either all or none of the proofs have to be for a chunk.".to_string())
+        );
+        let other = ProofMetadata {
+            pil_hash: "00".to_string(),
+            ..metadata(None)
+        };
+        assert!(check_aggregation_compatible(&[metadata(None), other])
+            .unwrap_err()
+            .starts_with("Proof 1 is for a different PIL"));
+    }
+}
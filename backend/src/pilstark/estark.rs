@@ -1,11 +1,15 @@
+use std::io;
 use std::iter::{once, repeat};
 use std::time::Instant;
 
-use crate::{pilstark, BackendImpl};
+use crate::{pilstark, BackendImpl, Error};
 use ast::analyzed::Analyzed;
-use number::{BigInt, DegreeType, FieldElement, GoldilocksField};
+use number::{column_refs, BigInt, ColumnAccess, DegreeType, FieldElement, GoldilocksField};
+use serde::Deserialize;
+use serde_json::json;
 
 use starky::{
+    field_gl::Fr as FGL,
     merklehash::MerkleTreeGL,
     polsarray::{PolKind, PolsArray},
     stark_gen::StarkProof,
@@ -17,6 +21,17 @@ use starky::{
 
 pub struct EStark {
     params: StarkStruct,
+    /// The (serialized) Merkle root of the fixed columns, if the backend was created
+    /// from a verification key.
+    const_root: Option<serde_json::Value>,
+}
+
+/// The verification key: the parameters and the Merkle root of the fixed columns.
+/// The constraints are not part of it, they are taken from the PIL.
+#[derive(Deserialize)]
+struct VerificationKey {
+    params: StarkStruct,
+    const_root: serde_json::Value,
 }
 
 impl<F: FieldElement> BackendImpl<F> for EStark {
@@ -45,7 +60,18 @@ impl<F: FieldElement> BackendImpl<F> for EStark {
             steps,
         };
 
-        Self { params }
+        Self {
+            params,
+            const_root: None,
+        }
+    }
+
+    fn new_from_verification_key(input: &mut dyn io::Read) -> Result<Self, Error> {
+        let vkey: VerificationKey = serde_json::from_reader(input).map_err(io::Error::from)?;
+        Ok(Self {
+            params: vkey.params,
+            const_root: Some(vkey.const_root),
+        })
     }
 
     fn prove(
@@ -53,6 +79,28 @@ impl<F: FieldElement> BackendImpl<F> for EStark {
         pil: &Analyzed<F>,
         fixed: &[(String, Vec<F>)],
         witness: &[(String, Vec<F>)],
+        publics: &[(String, F)],
+        prev_proof: Option<crate::Proof>,
+        bname: Option<String>,
+    ) -> (Option<crate::Proof>, Option<String>) {
+        self.prove_columns(
+            pil,
+            &column_refs(fixed),
+            &column_refs(witness),
+            publics,
+            prev_proof,
+            bname,
+        )
+    }
+
+    /// The columns are converted to starky's representation directly, without copying
+    /// them into vectors first.
+    fn prove_columns(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, &dyn ColumnAccess<F>)],
+        witness: &[(String, &dyn ColumnAccess<F>)],
+        publics: &[(String, F)],
         prev_proof: Option<crate::Proof>,
         _bname: Option<String>,
     ) -> (Option<crate::Proof>, Option<String>) {
@@ -62,6 +110,122 @@ impl<F: FieldElement> BackendImpl<F> for EStark {
 
         log::info!("Creating eSTARK proof.");
 
+        if witness.is_empty() {
+            return (None, None);
+        }
+
+        let (pil, const_pols, mut setup) = self.setup(pil, fixed);
+        let cm_pols = to_starky_pols_array(witness, &pil, PolKind::Commit);
+
+        let start = Instant::now();
+        let starkproof = StarkProof::<MerkleTreeGL>::stark_gen::<TranscriptGL>(
+            &cm_pols,
+            &const_pols,
+            &setup.const_tree,
+            &setup.starkinfo,
+            &setup.program,
+            &pil,
+            &self.params,
+            "",
+        )
+        .unwrap();
+        let duration = start.elapsed();
+
+        log::info!("Proof done in: {:?}", duration);
+
+        assert!(
+            starkproof.publics == to_starky_publics(publics),
+            "The public values computed by starky differ from the ones given."
+        );
+
+        assert!(stark_verify::<MerkleTreeGL, TranscriptGL>(
+            &starkproof,
+            &setup.const_root,
+            &setup.starkinfo,
+            &self.params,
+            &mut setup.program,
+        )
+        .unwrap());
+
+        (
+            Some(serde_json::to_vec(&starkproof).unwrap()),
+            Some(serde_json::to_string(&pil).unwrap()),
+        )
+    }
+
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        proof: &[u8],
+        publics: &[(String, F)],
+    ) -> Result<(), Error> {
+        let starkproof: StarkProof<MerkleTreeGL> = serde_json::from_slice(proof)
+            .map_err(|e| Error::InvalidProof(format!("could not deserialize proof: {e}")))?;
+        if starkproof.publics != to_starky_publics(publics) {
+            return Err(Error::InvalidProof(format!(
+                "the proof is not for the expected public values {}",
+                publics
+                    .iter()
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let (_, _, mut setup) = match &self.const_root {
+            // The fixed columns only determine the Merkle root, which we already know.
+            Some(_) => self.setup(pil, &column_refs(&zero_fixed_columns(pil))),
+            None => self.setup(pil, &column_refs(fixed)),
+        };
+        let const_root = match &self.const_root {
+            Some(const_root) => serde_json::from_value(const_root.clone())
+                .map_err(|e| Error::InvalidProof(format!("invalid verification key: {e}")))?,
+            None => setup.const_root,
+        };
+
+        let valid = stark_verify::<MerkleTreeGL, TranscriptGL>(
+            &starkproof,
+            &const_root,
+            &setup.starkinfo,
+            &self.params,
+            &mut setup.program,
+        )
+        .map_err(|e| Error::InvalidProof(e.to_string()))?;
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidProof(
+                "verification of the eSTARK proof failed".to_string(),
+            ))
+        }
+    }
+
+    fn export_verification_key(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), Error> {
+        let const_root = match &self.const_root {
+            Some(const_root) => const_root.clone(),
+            None => serde_json::to_value(self.setup(pil, &column_refs(fixed)).2.const_root)
+                .map_err(io::Error::from)?,
+        };
+        let vkey = json!({ "params": &self.params, "const_root": const_root });
+        serde_json::to_writer(output, &vkey).map_err(io::Error::from)?;
+        Ok(())
+    }
+}
+
+impl EStark {
+    /// Exports the PIL and fixed columns to starky and runs the setup,
+    /// which is shared by the prover and the verifier.
+    fn setup<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(String, &dyn ColumnAccess<F>)],
+    ) -> (PIL, PolsArray, StarkSetup<MerkleTreeGL>) {
         let degree = pil.degree();
 
         let mut pil: PIL = pilstark::json_exporter::export(pil);
@@ -73,6 +237,10 @@ impl<F: FieldElement> BackendImpl<F> for EStark {
         // but directly given PIL may not have it.
         // This is a hack to inject such column if it doesn't exist.
         // It should be eventually improved.
+        let first_step: Vec<F> = once(F::one())
+            .chain(repeat(F::zero()))
+            .take(degree as usize)
+            .collect();
         let mut fixed = fixed.to_vec();
         if !fixed.iter().any(|(k, _)| k == "main.first_step") {
             use starky::types::Reference;
@@ -91,22 +259,13 @@ impl<F: FieldElement> BackendImpl<F> for EStark {
             );
             fixed.push((
                 "main.first_step".to_string(),
-                once(F::one())
-                    .chain(repeat(F::zero()))
-                    .take(degree as usize)
-                    .collect(),
+                &first_step as &dyn ColumnAccess<F>,
             ));
         }
 
         let const_pols = to_starky_pols_array(&fixed, &pil, PolKind::Constant);
 
-        if witness.is_empty() {
-            return (None, None);
-        }
-
-        let cm_pols = to_starky_pols_array(witness, &pil, PolKind::Commit);
-
-        let mut setup = StarkSetup::<MerkleTreeGL>::new(
+        let setup = StarkSetup::<MerkleTreeGL>::new(
             &const_pols,
             &mut pil,
             &self.params,
@@ -114,40 +273,33 @@ impl<F: FieldElement> BackendImpl<F> for EStark {
         )
         .unwrap();
 
-        let start = Instant::now();
-        let starkproof = StarkProof::<MerkleTreeGL>::stark_gen::<TranscriptGL>(
-            &cm_pols,
-            &const_pols,
-            &setup.const_tree,
-            &setup.starkinfo,
-            &setup.program,
-            &pil,
-            &self.params,
-            "",
-        )
-        .unwrap();
-        let duration = start.elapsed();
-
-        log::info!("Proof done in: {:?}", duration);
+        (pil, const_pols, setup)
+    }
+}
 
-        assert!(stark_verify::<MerkleTreeGL, TranscriptGL>(
-            &starkproof,
-            &setup.const_root,
-            &setup.starkinfo,
-            &self.params,
-            &mut setup.program,
-        )
-        .unwrap());
+/// Returns all fixed columns of `pil` with zero values, to run the setup without
+/// the actual fixed columns.
+fn zero_fixed_columns<F: FieldElement>(pil: &Analyzed<F>) -> Vec<(String, Vec<F>)> {
+    pil.constant_polys_in_source_order()
+        .into_iter()
+        .flat_map(|(symbol, _)| symbol.array_elements())
+        .map(|(name, _)| (name, vec![F::zero(); pil.degree() as usize]))
+        .collect()
+}
 
-        (
-            Some(serde_json::to_vec(&starkproof).unwrap()),
-            Some(serde_json::to_string(&pil).unwrap()),
-        )
-    }
+fn to_starky_publics<F: FieldElement>(publics: &[(String, F)]) -> Vec<FGL> {
+    publics
+        .iter()
+        .map(|(_, value)| {
+            TryInto::<u64>::try_into(value.to_integer().to_arbitrary_integer())
+                .unwrap()
+                .into()
+        })
+        .collect()
 }
 
 fn to_starky_pols_array<F: FieldElement>(
-    array: &[(String, Vec<F>)],
+    array: &[(String, &dyn ColumnAccess<F>)],
     pil: &PIL,
     kind: PolKind,
 ) -> PolsArray {
@@ -156,7 +308,7 @@ fn to_starky_pols_array<F: FieldElement>(
     for ((_, from), to) in array.iter().zip(output.array.iter_mut()) {
         assert_eq!(from.len(), to.len());
 
-        for (f, t) in from.iter().zip(to.iter_mut()) {
+        for (f, t) in from.values().zip(to.iter_mut()) {
             *t = TryInto::<u64>::try_into(f.to_integer().to_arbitrary_integer())
                 .unwrap()
                 .into();
@@ -35,7 +35,7 @@ pub fn compute_intermediate_expression_ids<T>(analyzed: &Analyzed<T>) -> HashMap
     ids
 }
 
-trait ExpressionCounter {
+pub trait ExpressionCounter {
     /// Returns the number of (top-level) expression generated for this item.
     fn expression_count(&self) -> usize;
 }
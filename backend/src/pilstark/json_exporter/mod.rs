@@ -1,17 +1,19 @@
 use number::FieldElement;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use std::cmp;
 use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
 
 use ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicUnaryOperator, Analyzed,
-    IdentityKind, PolyID, PolynomialType, StatementIdentifier, SymbolKind,
+    Identity, IdentityKind, PolyID, PolynomialType, StatementIdentifier, SymbolKind,
 };
 use starky::types::{
     ConnectionIdentity, Expression as StarkyExpr, PermutationIdentity, PlookupIdentity,
-    PolIdentity, Reference, PIL,
+    PolIdentity, Public, Reference, PIL,
 };
 
-use self::expression_counter::compute_intermediate_expression_ids;
+use self::expression_counter::{compute_intermediate_expression_ids, ExpressionCounter};
 
 mod expression_counter;
 
@@ -29,121 +31,99 @@ const DEFAULT_EXPR: StarkyExpr = StarkyExpr {
 };
 struct Exporter<'a, T> {
     analyzed: &'a Analyzed<T>,
-    expressions: Vec<StarkyExpr>,
     /// Translates from polynomial IDs to expression IDs for intermediate
     /// polynomials.
     intermediate_poly_expression_ids: HashMap<u64, u64>,
-    number_q: u64,
+}
+
+/// The expression IDs of the two sides of an identity.
+struct IdentityExpressionIds {
+    left: Vec<usize>,
+    sel_left: Option<usize>,
+    right: Vec<usize>,
+    sel_right: Option<usize>,
 }
 
 pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
-    let mut exporter = Exporter::new(analyzed);
-    let mut publics = Vec::new();
-    let mut pol_identities = Vec::new();
-    let mut plookup_identities = Vec::new();
-    let mut permutation_identities = Vec::new();
-    let mut connection_identities = Vec::new();
-    for item in &analyzed.source_order {
-        match item {
-            StatementIdentifier::Definition(name) => {
-                if let Some((poly, value)) = analyzed.intermediate_columns.get(name) {
-                    assert_eq!(poly.kind, SymbolKind::Poly(PolynomialType::Intermediate));
-                    let expression_id = exporter.extract_expression(value, 1);
-                    assert_eq!(
-                        expression_id,
-                        exporter.intermediate_poly_expression_ids[&poly.id] as usize
-                    );
-                }
-            }
-            StatementIdentifier::PublicDeclaration(name) => {
-                let pub_def = &analyzed.public_declarations[name];
-                let pub_ref = &pub_def.polynomial;
-                let poly_id = pub_ref.poly_id.unwrap();
-                let (_, expr) = exporter.polynomial_reference_to_json(
-                    PolyID {
-                        id: poly_id.id + pub_def.array_index.unwrap_or_default() as u64,
-                        ..poly_id
-                    },
-                    false,
-                );
-                let id = publics.len();
-                publics.push(starky::types::Public {
-                    polType: polynomial_reference_type_to_type(&expr.op).to_string(),
-                    polId: expr.id.unwrap(),
-                    idx: pub_def.index as usize,
-                    id,
-                    name: name.clone(),
-                });
-            }
-            StatementIdentifier::Identity(id) => {
-                let identity = &analyzed.identities[*id];
-                let file_name = identity.source.file.clone();
-                let line = identity.source.line;
-                let selector_degree = if identity.kind == IdentityKind::Polynomial {
-                    2
-                } else {
-                    1
-                };
-                let left = exporter.extract_expression_vec(&identity.left.expressions, 1);
-                let sel_left =
-                    exporter.extract_expression_opt(&identity.left.selector, selector_degree);
-                let right = exporter.extract_expression_vec(&identity.right.expressions, 1);
-                let sel_right = exporter.extract_expression_opt(&identity.right.selector, 1);
-                match identity.kind {
-                    IdentityKind::Polynomial => pol_identities.push(PolIdentity {
-                        e: sel_left.unwrap(),
-                        fileName: file_name,
-                        line,
-                    }),
-                    IdentityKind::Plookup => {
-                        plookup_identities.push(PlookupIdentity {
-                            selF: sel_left,
-                            f: Some(left),
-                            selT: sel_right,
-                            t: Some(right),
-                            fileName: file_name,
-                            line,
-                        });
-                    }
-                    IdentityKind::Permutation => {
-                        permutation_identities.push(PermutationIdentity {
-                            selF: sel_left,
-                            f: Some(left),
-                            selT: sel_right,
-                            t: Some(right),
-                            fileName: file_name,
-                            line,
-                        });
-                    }
-                    IdentityKind::Connect => {
-                        connection_identities.push(ConnectionIdentity {
-                            pols: Some(left),
-                            connections: Some(right),
-                            fileName: file_name,
-                            line,
-                        });
-                    }
-                }
-            }
-        }
-    }
+    let exporter = Exporter::new(analyzed);
     PIL {
         nCommitments: analyzed.commitment_count(),
-        nQ: exporter.number_q as usize,
+        nQ: exporter.number_q(),
         nIm: analyzed.intermediate_count(),
         nConstants: analyzed.constant_count(),
-        publics,
-        references: exporter.references(),
-        expressions: exporter.expressions,
-        polIdentities: pol_identities,
-        plookupIdentities: plookup_identities,
-        permutationIdentities: Some(permutation_identities),
-        connectionIdentities: Some(connection_identities),
+        publics: exporter.publics().collect(),
+        references: exporter.references().collect(),
+        expressions: exporter.expressions().collect(),
+        polIdentities: exporter.pol_identities().collect(),
+        plookupIdentities: exporter.plookup_identities().collect(),
+        permutationIdentities: Some(exporter.permutation_identities().collect()),
+        connectionIdentities: Some(exporter.connection_identities().collect()),
         cm_dims: Vec::new(),
         q2exp: Vec::new(),
     }
 }
 
+/// Writes the same JSON as serializing the result of [export], but without
+/// building the expressions and identities in memory first. The only difference
+/// is that the references are written in source order instead of hash map order.
+pub fn export_to_writer<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    writer: impl Write,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    StreamedPil(Exporter::new(analyzed))
+        .serialize(&mut serde_json::Serializer::new(&mut writer))?;
+    writer.flush()
+}
+
+/// Serializes like [PIL], creating the items of each field on the fly.
+/// As in [PIL], `cm_dims` and `q2exp` are not serialized.
+struct StreamedPil<'a, T>(Exporter<'a, T>);
+
+impl<'a, T: FieldElement> Serialize for StreamedPil<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let exporter = &self.0;
+        let analyzed = exporter.analyzed;
+        let mut pil = serializer.serialize_struct("PIL", 11)?;
+        pil.serialize_field("nCommitments", &analyzed.commitment_count())?;
+        pil.serialize_field("nQ", &exporter.number_q())?;
+        pil.serialize_field("nIm", &analyzed.intermediate_count())?;
+        pil.serialize_field("nConstants", &analyzed.constant_count())?;
+        pil.serialize_field("publics", &Seq(|| exporter.publics()))?;
+        pil.serialize_field("references", &Map(|| exporter.references()))?;
+        pil.serialize_field("expressions", &Seq(|| exporter.expressions()))?;
+        pil.serialize_field("polIdentities", &Seq(|| exporter.pol_identities()))?;
+        pil.serialize_field("plookupIdentities", &Seq(|| exporter.plookup_identities()))?;
+        pil.serialize_field(
+            "permutationIdentities",
+            &Seq(|| exporter.permutation_identities()),
+        )?;
+        pil.serialize_field(
+            "connectionIdentities",
+            &Seq(|| exporter.connection_identities()),
+        )?;
+        pil.end()
+    }
+}
+
+/// Serializes the items returned by the iterator as a sequence.
+struct Seq<F>(F);
+
+impl<F: Fn() -> I, I: Iterator<Item = V>, V: Serialize> Serialize for Seq<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((self.0)())
+    }
+}
+
+/// Serializes the key-value pairs returned by the iterator as a map.
+struct Map<F>(F);
+
+impl<F: Fn() -> I, I: Iterator<Item = (K, V)>, K: Serialize, V: Serialize> Serialize for Map<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map((self.0)())
+    }
+}
+
 fn symbol_kind_to_json_string(k: SymbolKind) -> &'static str {
     match k {
         SymbolKind::Poly(poly_type) => polynomial_type_to_json_string(poly_type),
@@ -177,24 +157,64 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
     fn new(analyzed: &'a Analyzed<T>) -> Self {
         Self {
             analyzed,
-            expressions: vec![],
             intermediate_poly_expression_ids: compute_intermediate_expression_ids(analyzed),
-            number_q: 0,
         }
     }
 
-    fn references(&self) -> HashMap<String, Reference> {
+    fn publics(&self) -> impl Iterator<Item = Public> + '_ {
         self.analyzed
-            .definitions
+            .source_order
             .iter()
-            .filter_map(|(name, (symbol, _value))| {
-                let id = match symbol.kind {
-                    SymbolKind::Poly(PolynomialType::Intermediate) => {
-                        panic!("Should be in intermediates")
-                    }
-                    SymbolKind::Poly(_) => Some(symbol.id),
-                    SymbolKind::Other() | SymbolKind::Constant() => None,
-                }?;
+            .filter_map(|item| match item {
+                StatementIdentifier::PublicDeclaration(name) => Some(name),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(id, name)| {
+                let pub_def = &self.analyzed.public_declarations[name];
+                let pub_ref = &pub_def.polynomial;
+                let poly_id = pub_ref.poly_id.unwrap();
+                let (_, expr) = self.polynomial_reference_to_json(
+                    PolyID {
+                        id: poly_id.id + pub_def.array_index.unwrap_or_default() as u64,
+                        ..poly_id
+                    },
+                    false,
+                );
+                Public {
+                    polType: polynomial_reference_type_to_type(&expr.op).to_string(),
+                    polId: expr.id.unwrap(),
+                    idx: pub_def.index as usize,
+                    id,
+                    name: name.clone(),
+                }
+            })
+    }
+
+    /// Returns the references to all polynomials in source order.
+    fn references(&self) -> impl Iterator<Item = (String, Reference)> + '_ {
+        self.analyzed
+            .source_order
+            .iter()
+            .filter_map(|item| match item {
+                StatementIdentifier::Definition(name) => Some(name),
+                _ => None,
+            })
+            .filter_map(|name| {
+                let (symbol, id) = if let Some((symbol, _)) = self.analyzed.definitions.get(name) {
+                    let id = match symbol.kind {
+                        SymbolKind::Poly(PolynomialType::Intermediate) => {
+                            panic!("Should be in intermediates")
+                        }
+                        SymbolKind::Poly(_) => Some(symbol.id),
+                        SymbolKind::Other() | SymbolKind::Constant() => None,
+                    }?;
+                    (symbol, id)
+                } else {
+                    let (symbol, _) = &self.analyzed.intermediate_columns[name];
+                    assert_eq!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate));
+                    (symbol, self.intermediate_poly_expression_ids[&symbol.id])
+                };
 
                 let out = Reference {
                     polType: None,
@@ -207,56 +227,166 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                 };
                 Some((name.clone(), out))
             })
-            .chain(
-                self.analyzed
-                    .intermediate_columns
-                    .iter()
-                    .map(|(name, (symbol, _))| {
-                        assert_eq!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate));
-                        let id = self.intermediate_poly_expression_ids[&symbol.id];
-
-                        let out = Reference {
-                            polType: None,
-                            type_: symbol_kind_to_json_string(symbol.kind).to_string(),
-                            id: id as usize,
-                            polDeg: self.analyzed.degree() as usize,
-                            isArray: symbol.is_array(),
-                            elementType: None,
-                            len: symbol.length.map(|l| l as usize),
-                        };
-                        (name.clone(), out)
-                    }),
-            )
-            .collect::<HashMap<String, Reference>>()
-    }
-
-    /// Processes the given expression
-    /// @returns the expression ID
-    fn extract_expression(&mut self, expr: &Expression<T>, max_degree: u32) -> usize {
-        let id = self.expressions.len();
-        let (degree, mut expr) = self.expression_to_json(expr);
-        if degree > max_degree {
-            expr.idQ = Some(self.number_q as usize);
-            expr.deg = 1;
-            self.number_q += 1;
-        }
-        self.expressions.push(expr);
-        id
     }
 
-    fn extract_expression_opt(
-        &mut self,
-        expr: &Option<Expression<T>>,
-        max_degree: u32,
-    ) -> Option<usize> {
-        expr.as_ref()
-            .map(|e| self.extract_expression(e, max_degree))
+    /// Returns the top-level expressions in the order of their IDs, each together with
+    /// the maximum degree it can have before it is turned into a quotient polynomial.
+    fn top_level_expressions(&self) -> impl Iterator<Item = (&'a Expression<T>, u32)> + '_ {
+        self.analyzed
+            .source_order
+            .iter()
+            .flat_map(|item| match item {
+                StatementIdentifier::Definition(name) => {
+                    match self.analyzed.intermediate_columns.get(name) {
+                        Some((poly, value)) => {
+                            assert_eq!(poly.kind, SymbolKind::Poly(PolynomialType::Intermediate));
+                            vec![(value, 1)]
+                        }
+                        None => vec![],
+                    }
+                }
+                StatementIdentifier::PublicDeclaration(_) => vec![],
+                StatementIdentifier::Identity(id) => {
+                    let identity = &self.analyzed.identities[*id];
+                    let selector_degree = if identity.kind == IdentityKind::Polynomial {
+                        2
+                    } else {
+                        1
+                    };
+                    identity
+                        .left
+                        .expressions
+                        .iter()
+                        .map(|e| (e, 1))
+                        .chain(identity.left.selector.iter().map(|e| (e, selector_degree)))
+                        .chain(identity.right.expressions.iter().map(|e| (e, 1)))
+                        .chain(identity.right.selector.iter().map(|e| (e, 1)))
+                        .collect()
+                }
+            })
+    }
+
+    /// The number of expressions that exceed their maximum degree.
+    fn number_q(&self) -> usize {
+        self.top_level_expressions()
+            .filter(|(expr, max_degree)| self.expression_to_json(expr).0 > *max_degree)
+            .count()
+    }
+
+    fn expressions(&self) -> impl Iterator<Item = StarkyExpr> + '_ {
+        let mut number_q = 0;
+        self.top_level_expressions().map(move |(expr, max_degree)| {
+            let (degree, mut expr) = self.expression_to_json(expr);
+            if degree > max_degree {
+                expr.idQ = Some(number_q);
+                expr.deg = 1;
+                number_q += 1;
+            }
+            expr
+        })
+    }
+
+    /// Returns the identities in source order, together with the IDs of their expressions.
+    fn identities(
+        &self,
+    ) -> impl Iterator<Item = (&'a Identity<Expression<T>>, IdentityExpressionIds)> + '_ {
+        let analyzed = self.analyzed;
+        let mut expression_counter = 0;
+        analyzed.source_order.iter().filter_map(move |item| {
+            let StatementIdentifier::Identity(id) = item else {
+                if let StatementIdentifier::Definition(name) = item {
+                    if let Some((poly, _)) = analyzed.intermediate_columns.get(name) {
+                        assert_eq!(
+                            expression_counter,
+                            self.intermediate_poly_expression_ids[&poly.id] as usize
+                        );
+                        expression_counter += 1;
+                    }
+                }
+                return None;
+            };
+            let identity = &analyzed.identities[*id];
+            let mut next_ids = expression_counter..;
+            let left = next_ids
+                .by_ref()
+                .take(identity.left.expressions.len())
+                .collect();
+            let sel_left = identity
+                .left
+                .selector
+                .as_ref()
+                .map(|_| next_ids.next().unwrap());
+            let right = next_ids
+                .by_ref()
+                .take(identity.right.expressions.len())
+                .collect();
+            let sel_right = identity
+                .right
+                .selector
+                .as_ref()
+                .map(|_| next_ids.next().unwrap());
+            expression_counter += identity.expression_count();
+            Some((
+                identity,
+                IdentityExpressionIds {
+                    left,
+                    sel_left,
+                    right,
+                    sel_right,
+                },
+            ))
+        })
     }
 
-    fn extract_expression_vec(&mut self, expr: &[Expression<T>], max_degree: u32) -> Vec<usize> {
-        expr.iter()
-            .map(|e| self.extract_expression(e, max_degree))
-            .collect()
+    fn identities_of_kind(
+        &self,
+        kind: IdentityKind,
+    ) -> impl Iterator<Item = (&'a Identity<Expression<T>>, IdentityExpressionIds)> + '_ {
+        self.identities()
+            .filter(move |(identity, _)| identity.kind == kind)
+    }
+
+    fn pol_identities(&self) -> impl Iterator<Item = PolIdentity> + '_ {
+        self.identities_of_kind(IdentityKind::Polynomial)
+            .map(|(identity, ids)| PolIdentity {
+                e: ids.sel_left.unwrap(),
+                fileName: identity.source.file.clone(),
+                line: identity.source.line,
+            })
+    }
+
+    fn plookup_identities(&self) -> impl Iterator<Item = PlookupIdentity> + '_ {
+        self.identities_of_kind(IdentityKind::Plookup)
+            .map(|(identity, ids)| PlookupIdentity {
+                selF: ids.sel_left,
+                f: Some(ids.left),
+                selT: ids.sel_right,
+                t: Some(ids.right),
+                fileName: identity.source.file.clone(),
+                line: identity.source.line,
+            })
+    }
+
+    fn permutation_identities(&self) -> impl Iterator<Item = PermutationIdentity> + '_ {
+        self.identities_of_kind(IdentityKind::Permutation)
+            .map(|(identity, ids)| PermutationIdentity {
+                selF: ids.sel_left,
+                f: Some(ids.left),
+                selT: ids.sel_right,
+                t: Some(ids.right),
+                fileName: identity.source.file.clone(),
+                line: identity.source.line,
+            })
+    }
+
+    fn connection_identities(&self) -> impl Iterator<Item = ConnectionIdentity> + '_ {
+        self.identities_of_kind(IdentityKind::Connect)
+            .map(|(identity, ids)| ConnectionIdentity {
+                pols: Some(ids.left),
+                connections: Some(ids.right),
+                fileName: identity.source.file.clone(),
+                line: identity.source.line,
+            })
     }
 
     /// returns the degree and the JSON value (intermediate polynomial IDs)
@@ -353,22 +483,32 @@ mod test {
     use pil_analyzer::analyze;
     use pretty_assertions::assert_eq;
     use serde_json::Value as JsonValue;
-    use std::{fs, process::Command};
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        fs,
+        path::PathBuf,
+        process::Command,
+    };
     use test_log::test;
 
     use number::GoldilocksField;
 
     use super::*;
 
+    fn polygon_hermez_file(file: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/../test_data/polygon-hermez/",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .join(file)
+    }
+
     fn generate_json_pair(file: &str) -> (JsonValue, JsonValue) {
         let temp_dir = mktemp::Temp::new_dir().unwrap();
         let output_file = temp_dir.join("out.json");
 
-        let file = std::path::PathBuf::from(format!(
-            "{}/../test_data/polygon-hermez/",
-            env!("CARGO_MANIFEST_DIR")
-        ))
-        .join(file);
+        let file = polygon_hermez_file(file);
 
         let analyzed = analyze::<GoldilocksField>(&file);
         let pil_out = export(&analyzed);
@@ -524,4 +664,112 @@ mod test {
         compare_export_file_ignore_idq_hex("rom.pil");
         compare_export_file_ignore_idq_hex("main.pil");
     }
+
+    /// Checks that the streamed JSON is byte by byte the serialization of [export],
+    /// apart from the order of the references.
+    fn compare_streamed_export(analyzed: &Analyzed<GoldilocksField>) {
+        let mut pil = export(analyzed);
+        let references = std::mem::take(&mut pil.references);
+        let ordered_references = Exporter::new(analyzed)
+            .references()
+            .map(|(name, _)| {
+                format!(
+                    "{}:{}",
+                    serde_json::to_string(&name).unwrap(),
+                    serde_json::to_string(&references[&name]).unwrap()
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ordered_references.len(), references.len());
+        let expected = serde_json::to_string(&pil).unwrap().replacen(
+            r#""references":{}"#,
+            &format!(r#""references":{{{}}}"#, ordered_references.join(",")),
+            1,
+        );
+
+        let mut streamed = vec![];
+        export_to_writer(analyzed, &mut streamed).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+    }
+
+    #[test]
+    fn streamed_export() {
+        for file in [
+            "arith.pil",
+            "binary.pil",
+            "byte4.pil",
+            "config.pil",
+            "global.pil",
+            "keccakf.pil",
+            "main.pil",
+            "mem.pil",
+            "mem_align.pil",
+            "nine2one.pil",
+            "padding_kk.pil",
+            "padding_kkbit.pil",
+            "padding_pg.pil",
+            "poseidong.pil",
+            "rom.pil",
+            "storage.pil",
+        ] {
+            compare_streamed_export(&analyze(&polygon_hermez_file(file)));
+        }
+    }
+
+    /// Keeps track of the bytes allocated by each thread, so that the tests running
+    /// in parallel do not influence each other.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+        static PEAK_ALLOCATED: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn record_allocation(bytes: isize) {
+        let _ = ALLOCATED.try_with(|allocated| {
+            allocated.set(allocated.get() + bytes);
+            let _ = PEAK_ALLOCATED.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                record_allocation(layout.size() as isize);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            record_allocation(-(layout.size() as isize));
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Returns the maximum number of bytes that were allocated at the same time
+    /// while exporting the PIL.
+    fn peak_export_memory(analyzed: &Analyzed<GoldilocksField>) -> isize {
+        let start = ALLOCATED.with(Cell::get);
+        PEAK_ALLOCATED.with(|peak| peak.set(start));
+        export_to_writer(analyzed, std::io::sink()).unwrap();
+        PEAK_ALLOCATED.with(Cell::get) - start
+    }
+
+    #[test]
+    fn streamed_export_memory() {
+        let system = |identities: usize| {
+            let mut pil = "namespace main(8);\ncol witness a, b, c;\n".to_string();
+            for _ in 0..identities {
+                pil += "a * (b + 1) = c';\n{ a, b } in { b, c };\n";
+            }
+            pil_analyzer::analyze_string::<GoldilocksField>(&pil)
+        };
+        let small = peak_export_memory(&system(100));
+        let large = peak_export_memory(&system(10000));
+        assert_eq!(small, large);
+    }
 }
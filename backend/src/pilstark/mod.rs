@@ -17,6 +17,7 @@ impl<T: FieldElement> BackendImpl<T> for PilStarkCli {
         pil: &Analyzed<T>,
         _fixed: &[(String, Vec<T>)],
         _witness: &[(String, Vec<T>)],
+        _publics: &[(String, T)],
         prev_proof: Option<Proof>,
         _bname: Option<String>,
     ) -> (Option<Proof>, Option<String>) {
@@ -24,9 +25,8 @@ impl<T: FieldElement> BackendImpl<T> for PilStarkCli {
             unimplemented!("Aggregration is not implemented for pil-stark CLI backend");
         }
 
-        (
-            None,
-            Some(serde_json::to_string(&json_exporter::export(pil)).unwrap()),
-        )
+        let mut pil_json = vec![];
+        json_exporter::export_to_writer(pil, &mut pil_json).unwrap();
+        (None, Some(String::from_utf8(pil_json).unwrap()))
     }
 }
@@ -0,0 +1,227 @@
+//! The format of proof files like `proof.bin`, see [ProofFile].
+//!
+//! A proof file starts with a magic number and the format version (`u32`), followed by
+//! the name of the backend, the name of the field, the hash of the PIL (see [crate::pil_hash])
+//! and the proof itself. Numbers are little-endian and each of the four fields is
+//! prefixed by its length in bytes as a `u64`.
+
+use std::io::{self, Write};
+
+use number::{FieldElement, KnownField};
+
+use crate::{BackendType, Proof};
+
+/// The version of the proof file format. It has to be increased whenever the format changes.
+pub const PROOF_FORMAT_VERSION: u32 = 1;
+
+const MAGIC: [u8; 8] = *b"powdrprf";
+
+/// A proof together with what it was generated for, so that a proof for a different
+/// backend, field or PIL is rejected with a clear error instead of failing to verify.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofFile {
+    /// The backend the proof was generated with, as in `--backend`.
+    pub backend: String,
+    /// The field of the proof, as in `--field`, or the modulus for an unknown field.
+    pub field: String,
+    /// The SHA-256 hash (hex) of the PIL the proof is for, see [crate::pil_hash].
+    pub pil_hash: String,
+    pub proof: Proof,
+}
+
+impl ProofFile {
+    pub fn new<F: FieldElement>(backend: &BackendType, pil_hash: String, proof: Proof) -> Self {
+        ProofFile {
+            backend: backend.to_string(),
+            field: field_name::<F>(),
+            pil_hash,
+            proof,
+        }
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&PROOF_FORMAT_VERSION.to_le_bytes())?;
+        for field in [
+            self.backend.as_bytes(),
+            self.field.as_bytes(),
+            self.pil_hash.as_bytes(),
+            &self.proof,
+        ] {
+            writer.write_all(&(field.len() as u64).to_le_bytes())?;
+            writer.write_all(field)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the contents of a proof file written by [ProofFile::write].
+    /// Fails if the file has a different format version.
+    pub fn read(data: &[u8]) -> Result<Self, String> {
+        let Some(data) = data.strip_prefix(&MAGIC) else {
+            return Err(
+                "Not a powdr proof file, bare proofs have to be read as raw proofs.".to_string(),
+            );
+        };
+        let invalid = || "Invalid powdr proof file: it is truncated.".to_string();
+        let (version, mut data) = split(data, 4).ok_or_else(invalid)?;
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != PROOF_FORMAT_VERSION {
+            return Err(format!(
+                "The proof file has format version {version}, but this version of powdr \
+                 reads version {PROOF_FORMAT_VERSION}. Generate the proof again."
+            ));
+        }
+        let mut fields = vec![];
+        for _ in 0..4 {
+            let (len, rest) = split(data, 8).ok_or_else(invalid)?;
+            let len = usize::try_from(u64::from_le_bytes(len.try_into().unwrap()))
+                .map_err(|_| invalid())?;
+            let (field, rest) = split(rest, len).ok_or_else(invalid)?;
+            fields.push(field);
+            data = rest;
+        }
+        if !data.is_empty() {
+            return Err("Invalid powdr proof file: it has trailing bytes.".to_string());
+        }
+        let string = |field: &[u8]| {
+            String::from_utf8(field.to_vec())
+                .map_err(|_| "Invalid powdr proof file: a name is not UTF-8.".to_string())
+        };
+        Ok(ProofFile {
+            backend: string(fields[0])?,
+            field: string(fields[1])?,
+            pil_hash: string(fields[2])?,
+            proof: fields[3].to_vec(),
+        })
+    }
+
+    /// Checks that the proof was generated with the given backend and field for the PIL
+    /// with the given hash.
+    pub fn check<F: FieldElement>(
+        &self,
+        backend: &BackendType,
+        pil_hash: &str,
+    ) -> Result<(), String> {
+        if self.backend != backend.to_string() {
+            return Err(format!(
+                "The proof was generated with the backend {}, not {backend}.",
+                self.backend
+            ));
+        }
+        let field = field_name::<F>();
+        if self.field != field {
+            return Err(format!(
+                "The proof is for the field {}, not {field}.",
+                self.field
+            ));
+        }
+        if self.pil_hash != pil_hash {
+            return Err(format!(
+                "The proof is for a different PIL (hash {} instead of {pil_hash}).",
+                self.pil_hash
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parses the contents of a proof file and checks it like [ProofFile::check].
+    /// @returns the proof.
+    pub fn open<F: FieldElement>(
+        data: &[u8],
+        backend: &BackendType,
+        pil_hash: &str,
+    ) -> Result<Proof, String> {
+        let file = Self::read(data)?;
+        file.check::<F>(backend, pil_hash)?;
+        Ok(file.proof)
+    }
+}
+
+fn split(data: &[u8], len: usize) -> Option<(&[u8], &[u8])> {
+    (data.len() >= len).then(|| data.split_at(len))
+}
+
+fn field_name<F: FieldElement>() -> String {
+    match F::known_field() {
+        Some(KnownField::GoldilocksField) => "gl".to_string(),
+        Some(KnownField::Bn254Field) => "bn254".to_string(),
+        None => F::modulus().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::{Bn254Field, GoldilocksField};
+
+    use super::*;
+
+    fn written(file: &ProofFile) -> Vec<u8> {
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+        data
+    }
+
+    fn proof_file() -> ProofFile {
+        ProofFile::new::<GoldilocksField>(&BackendType::EStark, "ab".to_string(), vec![1, 2, 3])
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = proof_file();
+        assert_eq!(file.field, "gl");
+        assert_eq!(file.backend, "estark");
+        let data = written(&file);
+        assert_eq!(
+            data.len(),
+            8 + 4 + 4 * 8 + "estark".len() + "gl".len() + 2 + 3
+        );
+        assert_eq!(ProofFile::read(&data), Ok(file));
+        assert_eq!(
+            ProofFile::open::<GoldilocksField>(&data, &BackendType::EStark, "ab"),
+            Ok(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn mismatch() {
+        let data = written(&proof_file());
+        assert_eq!(
+            ProofFile::open::<GoldilocksField>(&data, &BackendType::PilStarkCli, "ab"),
+            Err("The proof was generated with the backend estark, not pil-stark-cli.".to_string())
+        );
+        assert_eq!(
+            ProofFile::open::<Bn254Field>(&data, &BackendType::EStark, "ab"),
+            Err("The proof is for the field gl, not bn254.".to_string())
+        );
+        assert_eq!(
+            ProofFile::open::<GoldilocksField>(&data, &BackendType::EStark, "cd"),
+            Err("The proof is for a different PIL (hash ab instead of cd).".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            ProofFile::read(&[1, 2, 3]),
+            Err("Not a powdr proof file, bare proofs have to be read as raw proofs.".to_string())
+        );
+
+        let mut data = written(&proof_file());
+        data[8] += 1;
+        assert_eq!(
+            ProofFile::read(&data),
+            Err(
+                "The proof file has format version 2, but this version of powdr \
+                 reads version 1. Generate the proof again."
+                    .to_string()
+            )
+        );
+        data[8] -= 1;
+
+        assert!(ProofFile::read(&data[..data.len() - 1])
+            .unwrap_err()
+            .contains("truncated"));
+        data.push(0);
+        assert!(ProofFile::read(&data).unwrap_err().contains("trailing"));
+    }
+}
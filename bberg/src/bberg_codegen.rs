@@ -14,16 +14,40 @@ use crate::vm_builder::analyzed_to_cpp;
 pub struct BBergCodegen {
     // Note: Im not sure we need to know the degree ahead of time
     // degree: DegreeType,
+    /// Only report which files would be written or deleted.
+    dry_run: bool,
+    /// The maximum number of subrelations per generated relation file.
+    max_subrelations_per_file: Option<usize>,
 }
 
 impl BBergCodegen {
     pub fn new(_degree: DegreeType) -> Self {
-        Self {}
+        Self {
+            dry_run: false,
+            max_subrelations_per_file: None,
+        }
     }
 
     pub fn new_from_setup(_input: &mut impl io::Read) -> Result<Self, io::Error> {
         log::warn!("warning bberg: new_from_setup not implemented");
-        Ok(Self {})
+        Ok(Self::new(0))
+    }
+
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Splits the relations of a pil file into files of at most `max` subrelations
+    /// (in addition to splitting them by degree), which reduces the C++ compile times.
+    pub fn with_max_subrelations_per_file(self, max: Option<usize>) -> Self {
+        assert!(
+            max != Some(0),
+            "A relation file needs at least one subrelation."
+        );
+        Self {
+            max_subrelations_per_file: max,
+            ..self
+        }
     }
 
     // Note: only returns vec<u8> to keep with the interface
@@ -34,7 +58,15 @@ impl BBergCodegen {
         witness: &[(String, Vec<F>)],
         bname: Option<String>,
     ) -> Vec<u8> {
-        analyzed_to_cpp(pil, fixed, witness, bname);
+        analyzed_to_cpp(
+            pil,
+            fixed,
+            witness,
+            bname,
+            true,
+            self.dry_run,
+            self.max_subrelations_per_file,
+        );
 
         Vec::new()
     }
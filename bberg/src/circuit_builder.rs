@@ -1,15 +1,20 @@
+use itertools::Itertools;
+
 use crate::{
     file_writer::BBFiles,
+    lookup_builder::Lookup,
     relation_builder::create_row_type,
     utils::{get_relations_imports, map_with_newline, snake_case},
 };
 
 pub trait CircuitBuilder {
+    #[allow(clippy::too_many_arguments)]
     fn create_circuit_builder_hpp(
         &mut self,
         name: &str,
         relations: &[String],
         permutations: &[String],
+        lookups: &[Lookup],
         all_cols_without_inverses: &[String],
         all_cols: &[String],
         to_be_shifted: &[String],
@@ -26,6 +31,8 @@ fn circuit_hpp_includes(name: &str, relations: &[String], permutations: &[String
     // AUTOGENERATED FILE
     #pragma once
 
+    #include <array>
+    #include <map>
     #include <vector>
 #ifndef __wasm__
     #include <future>
@@ -65,11 +72,13 @@ fn get_params() -> &'static str {
 impl CircuitBuilder for BBFiles {
     // Create circuit builder
     // Generate some code that can read a commits.bin and constants.bin into data structures that bberg understands
+    #[allow(clippy::too_many_arguments)]
     fn create_circuit_builder_hpp(
         &mut self,
         name: &str,
         relations: &[String],
         permutations: &[String],
+        lookups: &[Lookup],
         all_cols_without_inverses: &[String],
         all_cols: &[String],
         to_be_shifted: &[String],
@@ -126,8 +135,24 @@ impl CircuitBuilder for BBFiles {
         };
 
         // Apply transformations
+        // The counts of lookups into fixed tables are not part of the trace, they are computed below
+        let computed_counts = lookups
+            .iter()
+            .filter(|lookup| lookup.table_is_fixed)
+            .map(|lookup| lookup.counts_poly.clone())
+            .collect_vec();
+        let assigned_cols = all_cols_without_inverses
+            .iter()
+            .filter(|col| !computed_counts.contains(col))
+            .cloned()
+            .collect_vec();
         let compute_polys_assignemnt =
-            map_with_newline(all_cols_without_inverses, compute_polys_transformation);
+            map_with_newline(&assigned_cols, compute_polys_transformation);
+        let compute_lookup_counts = lookups
+            .iter()
+            .filter(|lookup| lookup.table_is_fixed)
+            .map(create_fixed_table_counts)
+            .join("\n");
         let all_poly_shifts = map_with_newline(to_be_shifted, all_polys_transformation);
         let check_circuit_for_each_relation =
             map_with_newline(relations, check_circuit_transformation);
@@ -191,6 +216,8 @@ class {name}CircuitBuilder {{
                 {compute_polys_assignemnt}
             }}
 
+            {compute_lookup_counts}
+
             {all_poly_shifts }
 
             return polys;
@@ -312,6 +339,55 @@ template std::vector<std::string> AvmFullRow<bb::AvmFlavor::FF>::names();
     }
 }
 
+/// Computes the counts of a lookup into a table of fixed columns
+///
+/// Every looked up tuple is matched to the first row of the table that contains it,
+/// which is the row whose count is increased.
+fn create_fixed_table_counts(lookup: &Lookup) -> String {
+    let lookup_name = lookup.attribute.clone().unwrap();
+    let counts_poly = &lookup.counts_poly;
+    let lhs_selector = lookup
+        .left
+        .selector
+        .clone()
+        .expect("Left hand side selector for lookup required");
+    let rhs_selector = lookup
+        .right
+        .selector
+        .clone()
+        .expect("Right hand side selector for lookup required");
+    let tuple_size = lookup.right.cols.len();
+    let tuple = |cols: &[String]| {
+        cols.iter()
+            .map(|col| format!("uint256_t(polys.{col}[i])"))
+            .join(", ")
+    };
+    let lhs_tuple = tuple(&lookup.left.cols);
+    let rhs_tuple = tuple(&lookup.right.cols);
+
+    format!(
+        "
+            // Counts of the fixed lookup table {lookup_name}
+            {{
+                std::map<std::array<uint256_t, {tuple_size}>, size_t> table_rows;
+                for (size_t i = 0; i < rows.size(); i++) {{
+                    if (polys.{rhs_selector}[i] == 1) {{
+                        table_rows.try_emplace(std::array<uint256_t, {tuple_size}>{{ {rhs_tuple} }}, i);
+                    }}
+                }}
+                for (size_t i = 0; i < rows.size(); i++) {{
+                    if (polys.{lhs_selector}[i] == 1) {{
+                        auto entry = table_rows.find(std::array<uint256_t, {tuple_size}>{{ {lhs_tuple} }});
+                        if (entry != table_rows.end()) {{
+                            polys.{counts_poly}[entry->second] += FF(1);
+                        }}
+                    }}
+                }}
+            }}
+"
+    )
+}
+
 fn get_lookup_check_closure() -> String {
     "
             const auto evaluate_logderivative = [&]<typename LogDerivativeSettings>(const std::string& lookup_name) {
@@ -372,3 +448,37 @@ fn get_relation_check_closure() -> String {
             };
     ".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use number::Bn254Field;
+    use pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+
+    use crate::lookup_builder::lookups_from_identities;
+
+    use super::*;
+
+    #[test]
+    fn fixed_table_counts() {
+        let input = r#"
+namespace main(256);
+    col fixed BYTE(i) { i & 0xff };
+    col fixed sel_byte = [1]*;
+    col witness sel, x, table_sel, y;
+    #[lookup_byte] sel { x } in sel_byte { BYTE };
+    #[lookup_y] sel { x } in table_sel { y };
+"#;
+        let analyzed = analyze_string::<Bn254Field>(input);
+        let lookups = lookups_from_identities(&analyzed);
+        let [byte, y] = &lookups[..] else {
+            panic!("Expected two lookups.");
+        };
+        assert!(byte.table_is_fixed);
+        assert!(!y.table_is_fixed);
+        assert_eq!(
+            create_fixed_table_counts(byte),
+            include_str!("../../test_data/bberg/lookup_byte_counts.hpp")
+        );
+    }
+}
@@ -1,5 +1,14 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use parser_util::hash::sha256_hex;
+use parser_util::paths::platform_path;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::snake_case;
 
 pub struct BBFiles {
     // Relative paths
@@ -10,11 +19,28 @@ pub struct BBFiles {
     pub flavor: String,
     pub composer: String,
     pub prover: String, // path for both prover and verifier files
+    pub test: String,
+    /// If set, only reports which files would be written or deleted.
+    pub dry_run: bool,
+    /// The maximum number of subrelations in a generated relation file, see
+    /// [crate::relation_builder::RelationBuilder::create_relations].
+    pub max_subrelations_per_file: Option<usize>,
+    /// The files written so far, relative to `base`, with the hashes of their contents.
+    written: RefCell<BTreeMap<String, String>>,
+}
+
+/// The list of files written by one run of the code generator, stored in `base`
+/// so that the next run can delete the files it does not generate anymore.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The generated files, relative to `base` and with `/` as separator,
+    /// with the SHA-256 hashes (hex) of their contents.
+    pub files: BTreeMap<String, String>,
 }
 
 impl BBFiles {
     pub fn default(file_name: String) -> Self {
-        Self::new(file_name, None, None, None, None, None, None)
+        Self::new(file_name, None, None, None, None, None, None, None)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -26,6 +52,7 @@ impl BBFiles {
         flavor: Option<String>,
         composer: Option<String>,
         prover: Option<String>,
+        test: Option<String>,
     ) -> Self {
         let base = base.unwrap_or("src/barretenberg".to_owned());
         let rel = rel.unwrap_or("relations/generated".to_owned());
@@ -33,6 +60,7 @@ impl BBFiles {
         let flavor = flavor.unwrap_or("vm/generated".to_owned());
         let composer = composer.unwrap_or("vm/generated".to_owned());
         let prover = prover.unwrap_or("vm/generated".to_owned());
+        let test = test.unwrap_or("vm/tests".to_owned());
 
         Self {
             file_name,
@@ -43,17 +71,208 @@ impl BBFiles {
             flavor,
             composer,
             prover,
+            test,
+            dry_run: false,
+            max_subrelations_per_file: None,
+            written: Default::default(),
         }
     }
 
     pub fn write_file(&self, folder: &str, filename: &str, contents: &String) {
+        let relative = [folder, filename]
+            .iter()
+            .flat_map(|p| p.split('/'))
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.written
+            .borrow_mut()
+            .insert(relative, sha256_hex(contents.as_bytes()));
+
+        let base_path = self.base_path().join(platform_path(folder));
+        let joined = base_path.join(filename);
+        if self.dry_run {
+            println!("Would write file: {}", joined.display());
+            return;
+        }
+
         // attempt to create dir
-        let base_path = format!("{}/{}", self.base, folder);
         let _ = std::fs::create_dir_all(&base_path);
 
-        let joined = format!("{}/{}", base_path, filename);
-        println!("Writing file: {}", joined);
+        println!("Writing file: {}", joined.display());
         let mut file = File::create(joined).unwrap();
         file.write_all(contents.as_bytes()).unwrap();
     }
+
+    fn base_path(&self) -> PathBuf {
+        if Path::new(&self.base).is_absolute() {
+            PathBuf::from(&self.base)
+        } else {
+            platform_path(&self.base)
+        }
+    }
+
+    /// The path of the manifest of the files generated for `file_name`.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.base_path().join(format!(
+            "{}_generated.manifest.json",
+            snake_case(&self.file_name)
+        ))
+    }
+
+    /// The manifest of the files written so far.
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            files: self.written.borrow().clone(),
+        }
+    }
+
+    /// Deletes the files listed in the manifest of the previous run that have not been
+    /// written in this run and replaces the manifest by the one of this run.
+    /// Has to be called after all files have been written.
+    /// Returns the stale files (relative to `base`), which are only reported in a dry run.
+    pub fn finish(&self) -> Vec<String> {
+        let manifest_path = self.manifest_path();
+        let previous = match std::fs::read_to_string(&manifest_path) {
+            Ok(contents) => serde_json::from_str::<Manifest>(&contents).unwrap_or_else(|e| {
+                log::warn!(
+                    "Ignoring invalid manifest {}: {e}. Stale files will not be deleted.",
+                    manifest_path.display()
+                );
+                Manifest::default()
+            }),
+            Err(_) => Manifest::default(),
+        };
+
+        let manifest = self.manifest();
+        let stale = previous
+            .files
+            .into_keys()
+            .filter(|file| !manifest.files.contains_key(file))
+            .collect::<Vec<_>>();
+
+        let base = self.base_path();
+        for file in &stale {
+            let path = platform_path(file);
+            if !is_plain_relative(&path) {
+                log::warn!(
+                    "Not deleting {file} from the manifest: it is not inside the base directory."
+                );
+                continue;
+            }
+            let path = base.join(path);
+            if self.dry_run {
+                println!("Would delete stale file: {}", path.display());
+            } else if path.exists() {
+                println!("Deleting stale file: {}", path.display());
+                std::fs::remove_file(&path).unwrap();
+            }
+        }
+
+        if self.dry_run {
+            println!("Would write manifest: {}", manifest_path.display());
+        } else {
+            let _ = std::fs::create_dir_all(&base);
+            std::fs::write(
+                &manifest_path,
+                serde_json::to_string_pretty(&manifest).unwrap(),
+            )
+            .unwrap();
+        }
+
+        stale
+    }
+}
+
+/// Manifests are only trusted to delete files below `base`.
+fn is_plain_relative(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn files_in(dir: &Path) -> BBFiles {
+        let mut files = BBFiles::default("Example".to_owned());
+        files.base = dir.to_string_lossy().to_string();
+        files
+    }
+
+    #[test]
+    fn removes_stale_files() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+
+        let files = files_in(&dir);
+        files.write_file("relations/generated", "alu.hpp", &"alu".to_owned());
+        files.write_file("relations/generated", "mem.hpp", &"mem".to_owned());
+        assert!(files.finish().is_empty());
+        assert!(dir.join("relations/generated/mem.hpp").exists());
+
+        // The column `mem` has been renamed to `memory`.
+        let files = files_in(&dir);
+        files.write_file("relations/generated", "alu.hpp", &"alu".to_owned());
+        files.write_file("relations/generated", "memory.hpp", &"memory".to_owned());
+        assert_eq!(files.finish(), vec!["relations/generated/mem.hpp"]);
+
+        assert!(!dir.join("relations/generated/mem.hpp").exists());
+        assert!(dir.join("relations/generated/alu.hpp").exists());
+        assert!(dir.join("relations/generated/memory.hpp").exists());
+
+        let manifest: Manifest = serde_json::from_str(
+            &fs::read_to_string(dir.join("example_generated.manifest.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.files.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("relations/generated/alu.hpp".to_owned(), sha256_hex(b"alu")),
+                (
+                    "relations/generated/memory.hpp".to_owned(),
+                    sha256_hex(b"memory")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_does_not_touch_files() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+
+        let files = files_in(&dir);
+        files.write_file("vm/generated", "old.hpp", &"old".to_owned());
+        files.finish();
+        let manifest = fs::read_to_string(files.manifest_path()).unwrap();
+
+        let mut files = files_in(&dir);
+        files.dry_run = true;
+        files.write_file("vm/generated", "new.hpp", &"new".to_owned());
+        assert_eq!(files.finish(), vec!["vm/generated/old.hpp"]);
+
+        assert!(dir.join("vm/generated/old.hpp").exists());
+        assert!(!dir.join("vm/generated/new.hpp").exists());
+        assert_eq!(fs::read_to_string(files.manifest_path()).unwrap(), manifest);
+    }
+
+    #[test]
+    fn does_not_delete_outside_of_base() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let base = dir.join("base");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(dir.join("outside.hpp"), "").unwrap();
+
+        let files = files_in(&base);
+        let manifest = Manifest {
+            files: [("../outside.hpp".to_owned(), sha256_hex(b""))].into(),
+        };
+        fs::write(
+            files.manifest_path(),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(files.finish(), vec!["../outside.hpp"]);
+        assert!(dir.join("outside.hpp").exists());
+    }
 }
@@ -7,6 +7,7 @@ pub mod lookup_builder;
 pub mod permutation_builder;
 mod prover_builder;
 mod relation_builder;
+mod test_builder;
 mod utils;
 mod verifier_builder;
 pub mod vm_builder;
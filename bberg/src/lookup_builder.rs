@@ -3,7 +3,7 @@ use crate::{
     utils::{create_get_const_entities, create_get_nonconst_entities, snake_case},
 };
 use ast::{
-    analyzed::{AlgebraicExpression, Analyzed, Identity, IdentityKind},
+    analyzed::{AlgebraicExpression, Analyzed, IdentityKind, PolynomialType},
     parsed::SelectedExpressions,
 };
 use itertools::Itertools;
@@ -26,6 +26,9 @@ pub struct Lookup {
     pub left: LookupSide,
     /// the right side of the lookup
     pub right: LookupSide,
+    /// Whether the right side only consists of fixed columns. The counts of such a lookup
+    /// cannot be provided by the trace and are computed by the circuit builder instead.
+    pub table_is_fixed: bool,
 }
 
 #[derive(Debug)]
@@ -34,9 +37,9 @@ pub struct Lookup {
 /// One side of a two sided lookup relationship
 pub struct LookupSide {
     /// -> Option<String> - the selector for the lookup ( on / off toggle )
-    selector: Option<String>,
+    pub selector: Option<String>,
     /// The columns involved in this side of the lookup
-    cols: Vec<String>,
+    pub cols: Vec<String>,
 }
 
 pub trait LookupBuilder {
@@ -55,29 +58,32 @@ impl LookupBuilder for BBFiles {
         project_name: &str,
         analyzed: &Analyzed<F>,
     ) -> Vec<Lookup> {
-        let lookups: Vec<&Identity<AlgebraicExpression<F>>> = analyzed
-            .identities
-            .iter()
-            .filter(|identity| matches!(identity.kind, IdentityKind::Plookup))
-            .collect();
-        let new_lookups = lookups
-            .iter()
-            .map(|lookup| Lookup {
-                attribute: lookup.attribute.clone().map(|att| att.to_lowercase()),
-                counts_poly: format!(
-                    "{}_counts",
-                    lookup.attribute.clone().unwrap().to_lowercase()
-                ),
-                left: get_lookup_side(&lookup.left),
-                right: get_lookup_side(&lookup.right),
-            })
-            .collect_vec();
+        let new_lookups = lookups_from_identities(analyzed);
 
         create_lookups(self, project_name, &new_lookups);
         new_lookups
     }
 }
 
+/// Collects the lookup relations of all plookup identities
+pub fn lookups_from_identities<F: FieldElement>(analyzed: &Analyzed<F>) -> Vec<Lookup> {
+    analyzed
+        .identities
+        .iter()
+        .filter(|identity| matches!(identity.kind, IdentityKind::Plookup))
+        .map(|lookup| Lookup {
+            attribute: lookup.attribute.clone().map(|att| att.to_lowercase()),
+            counts_poly: format!(
+                "{}_counts",
+                lookup.attribute.clone().unwrap().to_lowercase()
+            ),
+            left: get_lookup_side(&lookup.left),
+            right: get_lookup_side(&lookup.right),
+            table_is_fixed: is_fixed_table(&lookup.right),
+        })
+        .collect_vec()
+}
+
 /// The attributes of a lookup contain the name of the inverse, we collect all of these to create the inverse column
 pub fn get_inverses_from_lookups(lookups: &[Lookup]) -> Vec<String> {
     lookups
@@ -371,3 +377,16 @@ fn get_lookup_side<F: FieldElement>(
             .collect_vec(),
     }
 }
+
+/// A table is fixed if its selector and all of its columns are fixed columns
+fn is_fixed_table<F: FieldElement>(def: &SelectedExpressions<AlgebraicExpression<F>>) -> bool {
+    def.selector
+        .iter()
+        .chain(def.expressions.iter())
+        .all(|expr| match expr {
+            AlgebraicExpression::Reference(a_ref) => {
+                a_ref.poly_id.ptype == PolynomialType::Constant
+            }
+            _ => false,
+        })
+}
@@ -3,7 +3,7 @@ use crate::{
     utils::{create_get_const_entities, create_get_nonconst_entities, snake_case},
 };
 use ast::{
-    analyzed::{AlgebraicExpression, Analyzed, Identity, IdentityKind},
+    analyzed::{AlgebraicExpression, Analyzed, Identity, IdentityKind, PolynomialType},
     parsed::SelectedExpressions,
 };
 use itertools::Itertools;
@@ -51,25 +51,46 @@ impl PermutationBuilder for BBFiles {
         project_name: &str,
         analyzed: &Analyzed<F>,
     ) -> Vec<Permutation> {
-        let perms: Vec<&Identity<AlgebraicExpression<F>>> = analyzed
-            .identities
-            .iter()
-            .filter(|identity| matches!(identity.kind, IdentityKind::Permutation))
-            .collect();
-        let new_perms = perms
-            .iter()
-            .map(|perm| Permutation {
-                attribute: perm.attribute.clone().map(|att| att.to_lowercase()),
-                left: get_perm_side(&perm.left),
-                right: get_perm_side(&perm.right),
-            })
-            .collect_vec();
+        let new_perms = permutations_from_identities(analyzed);
 
         create_permutations(self, project_name, &new_perms);
         new_perms
     }
 }
 
+/// Collects the permutation relations of all permutation identities.
+/// Each side can have its own selector, but at least one of them is required.
+///
+/// # Panics
+/// Panics if a permutation has no attribute naming its inverse column, has no selector
+/// or if a selector or column is not a single witness or fixed column.
+pub fn permutations_from_identities<F: FieldElement>(analyzed: &Analyzed<F>) -> Vec<Permutation> {
+    analyzed
+        .identities
+        .iter()
+        .filter(|identity| matches!(identity.kind, IdentityKind::Permutation))
+        .map(|perm| {
+            let attribute = perm.attribute.clone().unwrap_or_else(|| {
+                panic!(
+                    "{}: Permutations need an attribute naming their inverse column, e.g. #[perm_name], in: {perm}",
+                    location(perm)
+                )
+            });
+            if perm.left.selector.is_none() && perm.right.selector.is_none() {
+                panic!(
+                    "{}: Permutations need a selector on at least one side, in: {perm}",
+                    location(perm)
+                );
+            }
+            Permutation {
+                attribute: Some(attribute.to_lowercase()),
+                left: get_perm_side(perm, &perm.left),
+                right: get_perm_side(perm, &perm.right),
+            }
+        })
+        .collect_vec()
+}
+
 /// The attributes of a permutation contain the name of the inverse, we collect all of these to create the inverse column
 pub fn get_inverses_from_permutations(permutations: &[Permutation]) -> Vec<String> {
     permutations
@@ -128,18 +149,13 @@ fn create_permutation_settings_file(permutation: &Permutation) -> String {
         .clone()
         .expect("Inverse column name must be provided using attribute syntax");
 
-    // This also will need to work for both sides of this !
-    let lhs_selector = permutation
-        .left
-        .selector
-        .clone()
-        .expect("At least one selector must be provided");
-    // If a rhs selector is not present, then we use the rhs selector -- TODO(md): maybe we want the default to be always on?
-    let rhs_selector = permutation
-        .right
-        .selector
-        .clone()
-        .unwrap_or(lhs_selector.clone());
+    // If only one side has a selector, it is used for both sides -- TODO(md): maybe we want the default to be always on?
+    let (lhs_selector, rhs_selector) =
+        match (&permutation.left.selector, &permutation.right.selector) {
+            (Some(lhs), Some(rhs)) => (lhs.clone(), rhs.clone()),
+            (Some(selector), None) | (None, Some(selector)) => (selector.clone(), selector.clone()),
+            (None, None) => panic!("At least one selector must be provided"),
+        };
 
     let lhs_cols = permutation.left.cols.clone();
     let rhs_cols = permutation.right.cols.clone();
@@ -239,12 +255,29 @@ fn create_inverse_computed_at(lhs_selector: &String, rhs_selector: &String) -> S
     }}")
 }
 
+fn location<F>(identity: &Identity<AlgebraicExpression<F>>) -> String {
+    format!("{}:{}", identity.source.file, identity.source.line)
+}
+
+/// The selector and the columns of one side of `perm`. The generated relation can only
+/// refer to the columns of the trace, so every expression has to be a single witness
+/// or fixed column, not shifted.
 fn get_perm_side<F: FieldElement>(
+    perm: &Identity<AlgebraicExpression<F>>,
     def: &SelectedExpressions<AlgebraicExpression<F>>,
 ) -> PermutationSide {
-    let get_name = |expr: &AlgebraicExpression<F>| match expr {
-        AlgebraicExpression::Reference(a_ref) => sanitize_name(&a_ref.name),
-        _ => panic!("Expected reference"),
+    let get_name = |expr: &AlgebraicExpression<F>| {
+        match expr {
+        AlgebraicExpression::Reference(a_ref)
+            if !a_ref.next && a_ref.poly_id.ptype != PolynomialType::Intermediate =>
+        {
+            sanitize_name(&a_ref.name)
+        }
+        _ => panic!(
+            "{}: Permutations can only use single witness or fixed columns, please define a witness column for {expr} in: {perm}",
+            location(perm)
+        ),
+    }
     };
 
     PermutationSide {
@@ -256,3 +289,47 @@ fn get_perm_side<F: FieldElement>(
             .collect_vec(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use number::Bn254Field;
+    use pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn selectors_on_both_sides() {
+        let input = r#"
+namespace main(16);
+    col witness sel_a, a, b, sel_b, c, d;
+    #[perm_ab_cd] sel_a { a, b } is sel_b { c, d };
+"#;
+        let analyzed = analyze_string::<Bn254Field>(input);
+        let permutations = permutations_from_identities(&analyzed);
+        let [permutation] = &permutations[..] else {
+            panic!("Expected one permutation.");
+        };
+        assert_eq!(permutation.left.selector.as_deref(), Some("main_sel_a"));
+        assert_eq!(permutation.right.selector.as_deref(), Some("main_sel_b"));
+        assert_eq!(
+            get_inverses_from_permutations(&permutations),
+            ["perm_ab_cd"]
+        );
+        assert_eq!(
+            create_permutation_settings_file(permutation),
+            include_str!("../../test_data/bberg/perm_ab_cd.hpp")
+        );
+    }
+
+    #[test]
+    #[should_panic = "please define a witness column for (main.sel_b + main.sel_c) in: main.sel_a { main.a } is (main.sel_b + main.sel_c) { main.c };"]
+    fn complex_selector() {
+        let input = r#"
+namespace main(16);
+    col witness sel_a, a, sel_b, sel_c, c;
+    #[perm_a_c] sel_a { a } is (sel_b + sel_c) { c };
+"#;
+        permutations_from_identities(&analyze_string::<Bn254Field>(input));
+    }
+}
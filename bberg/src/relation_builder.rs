@@ -7,6 +7,7 @@ use ast::analyzed::{
 use ast::parsed::SelectedExpressions;
 use itertools::Itertools;
 use num_bigint::BigUint;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
@@ -30,8 +31,9 @@ type BBIdentity = (DegreeType, String);
 pub trait RelationBuilder {
     /// Create Relations
     ///
-    /// Takes in the ast ( for relations ), groups each of them by file, and then
-    /// calls 'create relation' for each
+    /// Takes in the ast ( for relations ), groups each of them by file, splits the
+    /// relations of each file by degree (see [split_relation]) and then calls
+    /// 'create relation' for each
     ///
     /// Relation output is passed back to the caller as the prover requires both:
     /// - The shifted polys
@@ -75,11 +77,19 @@ impl RelationBuilder for BBFiles {
         file_name: &str,
         analyzed_identities: &[Identity<AlgebraicExpression<F>>],
     ) -> RelationOutput {
-        // Group relations per file
-        let grouped_relations: HashMap<String, Vec<Identity<AlgebraicExpression<F>>>> =
-            group_relations_per_file(analyzed_identities);
-        let mut relations = grouped_relations.keys().cloned().collect_vec();
-        relations.sort();
+        // Group relations per file, and split them into several relation files
+        let grouped_relations: Vec<(String, Vec<Identity<AlgebraicExpression<F>>>)> =
+            group_relations_per_file(analyzed_identities)
+                .into_iter()
+                .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                .flat_map(|(file, identities)| {
+                    split_relation(&file, &identities, self.max_subrelations_per_file)
+                })
+                .collect();
+        let relations = grouped_relations
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect_vec();
 
         // Contains all of the rows in each relation, will be useful for creating composite builder types
         let mut all_rows: HashMap<String, String> = HashMap::new();
@@ -114,8 +124,9 @@ impl RelationBuilder for BBFiles {
             );
         }
 
+        // The relations split from the same file can use the same shifts
         shifted_polys.sort();
-        relations.sort();
+        shifted_polys.dedup();
 
         RelationOutput {
             relations,
@@ -211,6 +222,55 @@ fn group_relations_per_file<F: FieldElement>(
         .into_group_map_by(|identity| identity.source.file.clone().replace(".pil", ""))
 }
 
+/// Split relation
+///
+/// Compiling a relation with hundreds of subrelations takes a long time, as they all live
+/// in a single templated struct. We therefore group the polynomial identities of a relation
+/// by their degree (in increasing order of the degree) and split each group further into
+/// chunks of at most `max_subrelations` identities. The identities keep their order within a group.
+///
+/// A relation that results in a single group keeps its name, otherwise the groups are
+/// named `{name}_relation_{k}`, e.g. alu.pil with identities of degree 2 and 3 becomes
+/// alu_relation_0.hpp and alu_relation_1.hpp.
+fn split_relation<F: FieldElement>(
+    name: &str,
+    identities: &[Identity<AlgebraicExpression<F>>],
+    max_subrelations: Option<usize>,
+) -> Vec<(String, Vec<Identity<AlgebraicExpression<F>>>)> {
+    let mut by_degree: BTreeMap<DegreeType, Vec<_>> = BTreeMap::new();
+    for identity in identities
+        .iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+    {
+        by_degree
+            .entry(identity_degree(identity))
+            .or_default()
+            .push(identity.clone());
+    }
+    let groups = by_degree
+        .into_values()
+        .flat_map(|group| match max_subrelations {
+            Some(max) => group.chunks(max).map(<[_]>::to_vec).collect(),
+            None => vec![group],
+        })
+        .collect_vec();
+
+    if groups.len() <= 1 {
+        return vec![(name.to_owned(), identities.to_vec())];
+    }
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(k, group)| (format!("{name}_relation_{k}"), group))
+        .collect()
+}
+
+/// The degree of the subrelation created for a polynomial identity.
+fn identity_degree<F: FieldElement>(identity: &Identity<AlgebraicExpression<F>>) -> DegreeType {
+    create_identity(&identity.left, &mut HashSet::new(), &mut HashSet::new())
+        .map_or(0, |(degree, _)| degree)
+}
+
 fn relation_class_boilerplate(
     name: &str,
     sub_relations: &[String],
@@ -533,3 +593,102 @@ fn create_relation_labels(relation_name: &str, labels: HashMap<usize, String>) -
     "
     )
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+
+    use number::Bn254Field;
+
+    use super::*;
+
+    const ALU: &str = r#"
+namespace alu(16);
+    col witness a, b, c, sel;
+    a * (1 - a) = 0;
+    sel * (a * b - c) = 0;
+    b * (1 - b) = 0;
+    a' = b;
+    sel * (c - a * b * b) = 0;
+"#;
+
+    fn create_relations_in(
+        dir: &Path,
+        max_subrelations_per_file: Option<usize>,
+    ) -> (
+        RelationOutput,
+        Vec<Identity<AlgebraicExpression<Bn254Field>>>,
+    ) {
+        fs::write(dir.join("alu.pil"), ALU).unwrap();
+        let analyzed = pil_analyzer::analyze::<Bn254Field>(&dir.join("alu.pil"));
+        let identities = analyzed.identities_with_inlined_intermediate_polynomials();
+
+        let mut files = BBFiles::default("Alu".to_owned());
+        files.base = dir.join("out").to_string_lossy().to_string();
+        files.max_subrelations_per_file = max_subrelations_per_file;
+        (files.create_relations("alu", &identities), identities)
+    }
+
+    #[test]
+    fn split_by_degree() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let (output, identities) = create_relations_in(&dir, None);
+
+        // Degrees 1, 2, 3 and 4.
+        assert_eq!(
+            output.relations,
+            (0..4).map(|k| format!("alu_relation_{k}")).collect_vec()
+        );
+        assert_eq!(output.shifted_polys, vec!["alu_a_shift"]);
+        let contributions = output
+            .relations
+            .iter()
+            .map(|relation| {
+                let file = dir.join(format!("out/relations/generated/alu/{relation}.hpp"));
+                fs::read_to_string(file)
+                    .unwrap()
+                    .matches("//Contribution")
+                    .count()
+            })
+            .collect_vec();
+        assert_eq!(contributions, vec![1, 2, 1, 1]);
+
+        // Every identity is in exactly one relation.
+        let split = split_relation("alu", &identities, None);
+        let ids = split
+            .iter()
+            .flat_map(|(_, group)| group.iter().map(|identity| identity.id))
+            .sorted()
+            .collect_vec();
+        assert_eq!(
+            ids,
+            identities.iter().map(|identity| identity.id).collect_vec()
+        );
+    }
+
+    #[test]
+    fn split_by_max_subrelations() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let (output, _) = create_relations_in(&dir, Some(1));
+        assert_eq!(
+            output.relations,
+            (0..5).map(|k| format!("alu_relation_{k}")).collect_vec()
+        );
+
+        let (output, _) = create_relations_in(&dir, Some(2));
+        assert_eq!(output.relations.len(), 4);
+    }
+
+    #[test]
+    fn single_group_keeps_name() {
+        let identities = pil_analyzer::analyze_string::<Bn254Field>(
+            "namespace alu(16); col witness a, b; a * (1 - a) = 0; b * (1 - b) = 0;",
+        )
+        .identities_with_inlined_intermediate_polynomials();
+        let split = split_relation("alu", &identities, None);
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].0, "alu");
+        assert_eq!(split[0].1.len(), 2);
+    }
+}
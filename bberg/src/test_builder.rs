@@ -0,0 +1,210 @@
+use itertools::Itertools;
+
+use crate::{file_writer::BBFiles, utils::snake_case};
+
+pub trait TestBuilder {
+    /// Creates a test of the composer that builds the circuit from the `constants.bin`
+    /// and `commits.bin` files written by powdr, proves and verifies it, and checks
+    /// that the proof does not verify after corrupting `corrupted_witness`.
+    fn create_composer_test_cpp(
+        &mut self,
+        name: &str,
+        fixed: &[String],
+        witness: &[String],
+        corrupted_witness: &str,
+        public_cols: &[(String, usize)],
+    );
+}
+
+impl TestBuilder for BBFiles {
+    fn create_composer_test_cpp(
+        &mut self,
+        name: &str,
+        fixed: &[String],
+        witness: &[String],
+        corrupted_witness: &str,
+        public_cols: &[(String, usize)],
+    ) {
+        let test_cpp = composer_test_cpp(name, fixed, witness, corrupted_witness, public_cols);
+        self.write_file(
+            &self.test,
+            &format!("{}_composer.test.cpp", snake_case(name)),
+            &test_cpp,
+        );
+    }
+}
+
+/// The test file, see [TestBuilder::create_composer_test_cpp].
+/// `fixed` and `witness` are the (sanitized) columns in the order of the files.
+fn composer_test_cpp(
+    name: &str,
+    fixed: &[String],
+    witness: &[String],
+    corrupted_witness: &str,
+    public_cols: &[(String, usize)],
+) -> String {
+    let snake_name = snake_case(name);
+    let witness_dir_variable = format!("{}_WITNESS_DIR", snake_name.to_uppercase());
+    let num_fixed = fixed.len();
+    let num_witness = witness.len();
+
+    let assign = |values: &str, width: usize, columns: &[String]| {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| format!("rows[i].{column} = {values}[i * {width} + {index}];"))
+            .collect::<Vec<_>>()
+    };
+    let assignments = assign("fixed", num_fixed, fixed)
+        .into_iter()
+        .chain(assign("witness", num_witness, witness))
+        .join("\n        ");
+
+    let (public_inputs, verify_call) = if public_cols.is_empty() {
+        ("".to_owned(), "verifier.verify_proof(proof)")
+    } else {
+        let public_inputs = public_cols
+            .iter()
+            .map(|(column, index)| format!("public_inputs[{index}].push_back(row.{column});"))
+            .join("\n        ");
+        (
+            format!(
+                "
+    std::vector<std::vector<FF>> public_inputs({});
+    for (const auto& row : rows) {{
+        {public_inputs}
+    }}
+",
+                public_cols.len()
+            ),
+            "verifier.verify_proof(proof, public_inputs)",
+        )
+    };
+
+    format!(
+        "
+// AUTOGENERATED FILE
+#include \"barretenberg/vm/generated/{snake_name}_circuit_builder.hpp\"
+#include \"barretenberg/vm/generated/{snake_name}_composer.hpp\"
+#include \"barretenberg/vm/generated/{snake_name}_prover.hpp\"
+#include \"barretenberg/vm/generated/{snake_name}_verifier.hpp\"
+
+#include <cstdlib>
+#include <fstream>
+#include <gtest/gtest.h>
+#include <iterator>
+#include <vector>
+
+using namespace bb;
+
+namespace {{
+
+using FF = {name}Flavor::FF;
+using Row = {name}FullRow<FF>;
+
+constexpr size_t NUM_FIXED_COLUMNS = {num_fixed};
+constexpr size_t NUM_WITNESS_COLUMNS = {num_witness};
+
+// Reads the values of a file written by powdr: row by row, each value as 32 bytes in little endian.
+std::vector<FF> read_values(const std::string& path)
+{{
+    std::ifstream file(path, std::ios::binary);
+    if (!file) {{
+        throw_or_abort(\"Could not open \" + path);
+    }}
+    std::vector<uint8_t> bytes((std::istreambuf_iterator<char>(file)), std::istreambuf_iterator<char>());
+    std::vector<FF> values;
+    for (size_t offset = 0; offset + 32 <= bytes.size(); offset += 32) {{
+        std::array<uint64_t, 4> limbs{{}};
+        for (size_t i = 0; i < 32; i++) {{
+            limbs[i / 8] |= static_cast<uint64_t>(bytes[offset + i]) << (8 * (i % 8));
+        }}
+        values.emplace_back(uint256_t(limbs[0], limbs[1], limbs[2], limbs[3]));
+    }}
+    return values;
+}}
+
+// Builds the rows from constants.bin and commits.bin in the given directory.
+std::vector<Row> read_rows(const std::string& dir)
+{{
+    const auto fixed = read_values(dir + \"/constants.bin\");
+    const auto witness = read_values(dir + \"/commits.bin\");
+    const size_t num_rows =
+        NUM_FIXED_COLUMNS > 0 ? fixed.size() / NUM_FIXED_COLUMNS : witness.size() / NUM_WITNESS_COLUMNS;
+    std::vector<Row> rows(num_rows);
+    for (size_t i = 0; i < num_rows; i++) {{
+        {assignments}
+    }}
+    return rows;
+}}
+
+bool prove_and_verify(std::vector<Row> rows)
+{{
+    {public_inputs}
+    auto circuit_builder = {name}CircuitBuilder();
+    circuit_builder.set_trace(std::move(rows));
+
+    auto composer = {name}Composer();
+    auto prover = composer.create_prover(circuit_builder);
+    auto proof = prover.construct_proof();
+
+    auto verifier = composer.create_verifier(circuit_builder);
+    return {verify_call};
+}}
+
+class {name}ComposerTests : public ::testing::Test {{
+  protected:
+    void SetUp() override
+    {{
+        const char* dir = std::getenv(\"{witness_dir_variable}\");
+        if (dir == nullptr) {{
+            GTEST_SKIP() << \"Set {witness_dir_variable} to the directory with constants.bin and commits.bin.\";
+        }}
+        witness_dir = dir;
+        srs::init_crs_factory(\"../srs_db/ignition\");
+    }}
+
+    std::string witness_dir;
+}};
+
+}} // namespace
+
+TEST_F({name}ComposerTests, ProveAndVerify)
+{{
+    auto rows = read_rows(witness_dir);
+
+    auto circuit_builder = {name}CircuitBuilder();
+    circuit_builder.set_trace(std::vector<Row>(rows));
+    EXPECT_TRUE(circuit_builder.check_circuit());
+
+    EXPECT_TRUE(prove_and_verify(std::move(rows)));
+}}
+
+TEST_F({name}ComposerTests, CorruptedWitnessFails)
+{{
+    auto rows = read_rows(witness_dir);
+    ASSERT_GT(rows.size(), 1);
+    rows[1].{corrupted_witness} += FF(1);
+
+    EXPECT_FALSE(prove_and_verify(std::move(rows)));
+}}
+"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn composer_test() {
+        let fixed = ["main_FIRST".to_string()];
+        let witness = ["main_x".to_string(), "main_y".to_string()];
+        assert_eq!(
+            composer_test_cpp("Fib", &fixed, &witness, "main_y", &[]),
+            include_str!("../../test_data/bberg/fib_composer.test.cpp")
+        );
+    }
+}
@@ -17,6 +17,7 @@ use crate::permutation_builder::PermutationBuilder;
 use crate::prover_builder::ProverBuilder;
 use crate::relation_builder::RelationBuilder;
 use crate::relation_builder::RelationOutput;
+use crate::test_builder::TestBuilder;
 use crate::utils::collect_col;
 use crate::utils::flatten;
 use crate::utils::sanitize_name;
@@ -50,12 +51,21 @@ struct ColumnGroups {
 
 /// Analyzed to cpp
 ///
-/// Converts an analyzed pil AST into a set of cpp files that can be used to generate a proof
+/// Converts an analyzed pil AST into a set of cpp files that can be used to generate a proof.
+/// If `generate_tests` is set, a unit test of the composer is generated as well.
+/// Files generated by the previous run for the same `name` that are not generated
+/// anymore are deleted. With `dry_run`, the files that would be written or deleted
+/// are only reported. `max_subrelations_per_file` limits the size of the generated
+/// relation files.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn analyzed_to_cpp<F: FieldElement>(
     analyzed: &Analyzed<F>,
     fixed: &[(String, Vec<F>)],
     witness: &[(String, Vec<F>)],
     name: Option<String>,
+    generate_tests: bool,
+    dry_run: bool,
+    max_subrelations_per_file: Option<usize>,
 ) {
     // Extract public inputs information.
     let mut public_inputs: Vec<(String, usize)> = analyzed
@@ -71,12 +81,19 @@ pub(crate) fn analyzed_to_cpp<F: FieldElement>(
         .collect();
     public_inputs.sort_by(|a, b| a.1.cmp(&b.1));
 
+    // The columns in the order in which they are written to the witness files
+    let sanitize = |(name, _): &(String, Vec<F>)| sanitize_name(name);
+    let fixed_in_file_order = collect_col(fixed, sanitize);
+    let witness_in_file_order = collect_col(witness, sanitize);
+
     // Sort fixed and witness to ensure consistent ordering
     let fixed = &sort_cols(fixed);
     let witness = &sort_cols(witness);
 
     let file_name: &str = &name.unwrap_or("Example".to_owned());
     let mut bb_files = BBFiles::default(file_name.to_owned());
+    bb_files.dry_run = dry_run;
+    bb_files.max_subrelations_per_file = max_subrelations_per_file;
 
     // Inlining step to remove the intermediate poly definitions
     let mut analyzed_identities = analyzed.identities_with_inlined_intermediate_polynomials();
@@ -121,6 +138,7 @@ pub(crate) fn analyzed_to_cpp<F: FieldElement>(
         file_name,
         &relations,
         &inverses,
+        &lookups,
         &all_cols_without_inverses,
         &all_cols,
         &to_be_shifted,
@@ -158,6 +176,26 @@ pub(crate) fn analyzed_to_cpp<F: FieldElement>(
     // ----------------------- Create the Prover files -----------------------
     bb_files.create_prover_cpp(file_name, &witnesses_without_inverses, &inverses);
     bb_files.create_prover_hpp(file_name);
+
+    // ----------------------- Create the composer test -----------------------
+    if generate_tests {
+        // Corrupting a shifted column also breaks the transition constraints.
+        let corrupted_witness = witness_in_file_order
+            .iter()
+            .find(|w| to_be_shifted.contains(w))
+            .or(witness_in_file_order.first());
+        if let Some(corrupted_witness) = corrupted_witness {
+            bb_files.create_composer_test_cpp(
+                file_name,
+                &fixed_in_file_order,
+                &witness_in_file_order,
+                corrupted_witness,
+                &public_inputs,
+            );
+        }
+    }
+
+    bb_files.finish();
 }
 
 /// Get all col names
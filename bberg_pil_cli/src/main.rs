@@ -1,7 +1,11 @@
 use std::{io, path::Path};
 
+use bberg::bberg_codegen::BBergCodegen;
 use clap::Parser;
-use compiler::{compile_pil, inputs_to_query_callback, BackendType};
+use compiler::{
+    compile_pil, inputs_to_query_callback, optimize_pil, BackendType, CancellationToken, Limits,
+    UnconstrainedWitnesses, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+};
 use number::Bn254Field;
 
 #[derive(Parser)]
@@ -18,6 +22,12 @@ struct Cli {
     /// BBerg: Name of the output file for bberg
     #[arg(long)]
     name: Option<String>,
+
+    /// Only report which files would be generated and which stale files would be deleted,
+    /// without writing anything.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    dry_run: bool,
 }
 
 fn main() -> Result<(), io::Error> {
@@ -26,17 +36,53 @@ fn main() -> Result<(), io::Error> {
     let file_name = args.file;
     let output_dir = Path::new(&args.output_directory);
     let name = args.name;
+
+    if args.dry_run {
+        dry_run(Path::new(&file_name), name);
+        return Ok(());
+    }
+
     let inputs: Vec<Bn254Field> = Vec::new();
     let prove_with = Some(BackendType::BBerg);
     let external_witness_values = Vec::new();
 
-    compile_pil(
+    if let Err(errors) = compile_pil(
         Path::new(&file_name),
         output_dir,
         inputs_to_query_callback(inputs),
         prove_with,
         external_witness_values,
         name,
-    );
+        None,
+        &Limits::default(),
+        &UnconstrainedWitnesses::default(),
+        DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        &CancellationToken::default(),
+    ) {
+        for e in errors {
+            eprintln!("{e}");
+        }
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+/// Runs the code generation on the optimized PIL without witness generation and
+/// without writing any files. Only the names of the columns are needed for that.
+fn dry_run(file: &Path, name: Option<String>) {
+    let analyzed = optimize_pil(pil_analyzer::analyze::<Bn254Field>(file));
+    let fixed = analyzed
+        .constant_polys_in_source_order()
+        .into_iter()
+        .flat_map(|(sym, _)| sym.array_elements())
+        .map(|(name, _)| (name, vec![]))
+        .collect::<Vec<_>>();
+    let witness = analyzed
+        .committed_polys_in_source_order()
+        .into_iter()
+        .map(|(sym, _)| (sym.absolute_name.clone(), vec![]))
+        .collect::<Vec<_>>();
+    BBergCodegen::new(analyzed.degree())
+        .with_dry_run(true)
+        .build_ast(&analyzed, &fixed, &witness, name);
+}
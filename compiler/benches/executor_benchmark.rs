@@ -31,6 +31,27 @@ fn run_witgen<T: FieldElement>(analyzed: &Analyzed<T>, input: Vec<T>) {
     executor::witgen::WitnessGenerator::new(analyzed, &constants, query_callback).generate();
 }
 
+/// Witness generation for lookups into a 16-bit table, which is dominated by the
+/// index creation and the index queries of the fixed lookup machine.
+fn fixed_lookup_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fixed-lookup-witgen-benchmark");
+    group.sample_size(10);
+
+    let analyzed = pil_analyzer::analyze_string::<T>(
+        r#"
+namespace main(65536);
+    col fixed BYTE2(i) { i & 0xffff };
+    col fixed DOUBLE(i) { (i & 0xffff) * 2 };
+    col fixed INPUT(i) { (i * 7919 + 13) & 0xffff };
+    col witness x, y;
+    x = INPUT;
+    { x, y } in { BYTE2, DOUBLE };
+"#,
+    );
+    group.bench_function("byte2", |b| b.iter(|| run_witgen(&analyzed, vec![])));
+    group.finish();
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("keccak-executor-benchmark");
     group.sample_size(10);
@@ -40,5 +61,5 @@ fn criterion_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, fixed_lookup_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,133 @@
+//! The file `artifacts.json` next to the fixed and witness values (`constants.bin`
+//! and `commits.bin`) records the optimized PIL they were generated for, so that
+//! they are not accidentally used with a different version of the PIL.
+
+use std::fs;
+use std::path::Path;
+
+use ast::analyzed::Analyzed;
+use number::FieldElement;
+use serde::{Deserialize, Serialize};
+
+pub const ARTIFACTS_FILE_NAME: &str = "artifacts.json";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifacts {
+    /// The hash of the optimized PIL, see [optimized_pil_hash].
+    pub pil_hash: String,
+}
+
+impl Artifacts {
+    /// The artifacts for values generated for the optimized PIL `pil`.
+    pub fn new<T: FieldElement>(pil: &Analyzed<T>) -> Self {
+        Artifacts {
+            pil_hash: optimized_pil_hash(pil),
+        }
+    }
+
+    /// Reads the artifacts recorded in `dir`, if any.
+    pub fn read(dir: &Path) -> Result<Option<Self>, String> {
+        let path = dir.join(ARTIFACTS_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents =
+            fs::read(&path).map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|e| format!("Invalid artifacts file {}: {e}", path.display()))
+    }
+
+    pub fn write(&self, writer: impl std::io::Write) {
+        serde_json::to_writer_pretty(writer, self).unwrap();
+    }
+
+    /// Checks that the values in `dir` were generated for the optimized PIL `pil`.
+    /// Directories without recorded artifacts are accepted with a warning.
+    pub fn check<T: FieldElement>(dir: &Path, pil: &Analyzed<T>) -> Result<(), String> {
+        match Self::read(dir)? {
+            None => {
+                log::warn!(
+                    "{} does not record which PIL the values in it were generated for.",
+                    dir.join(ARTIFACTS_FILE_NAME).display()
+                );
+                Ok(())
+            }
+            Some(artifacts) if artifacts.pil_hash != optimized_pil_hash(pil) => Err(format!(
+                "The values in {} were generated for a different PIL (hash {} instead of {}). \
+                 Generate them again, or use --force to use them anyway.",
+                dir.display(),
+                artifacts.pil_hash,
+                optimized_pil_hash(pil)
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// The hash of the optimized PIL after inlining the intermediate polynomials (as in
+/// [crate::optimize_pil]). It is computed from the displayed PIL, so it does not depend on
+/// whitespace or comments in the source, but on every column, identity and the degree.
+/// As inlining is idempotent, the hash is the same for a PIL file and the optimized
+/// PIL file written for it.
+pub fn optimized_pil_hash<T: FieldElement>(pil: &Analyzed<T>) -> String {
+    let mut pil = pil.clone();
+    pil.identities = pil.identities_with_inlined_intermediate_polynomials();
+    backend::pil_hash(&pil)
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+
+    use super::*;
+
+    fn hash(source: &str) -> String {
+        optimized_pil_hash(&pil_analyzer::analyze_string::<GoldilocksField>(source))
+    }
+
+    #[test]
+    fn hash_ignores_formatting() {
+        let pil = "namespace N(8);\ncol witness x;\ncol intermediate = x * 2;\nintermediate = 4;\n";
+        assert_eq!(
+            hash(pil),
+            hash("namespace   N( 8 );\n// The input.\ncol witness x;\n\ncol intermediate = x*2;\nintermediate = 4;")
+        );
+        // The same PIL with inlined intermediate polynomials.
+        let inlined = "namespace N(8);\ncol witness x;\ncol intermediate = x * 2;\n(x * 2) = 4;\n";
+        assert_eq!(hash(pil), hash(inlined));
+
+        for changed in [
+            pil.replace("N(8)", "N(16)"),
+            pil.replace("x * 2", "x * 3"),
+            pil.replace("= 4", "= 5"),
+            pil.replace("col witness x;", "col witness x;\ncol witness y;"),
+        ] {
+            assert_ne!(hash(pil), hash(&changed));
+        }
+    }
+
+    #[test]
+    fn check_artifacts() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(8);\ncol witness x;\nx = 1;\n",
+        );
+        assert_eq!(Artifacts::check(&dir, &pil), Ok(()));
+
+        Artifacts::new(&pil).write(fs::File::create(dir.join(ARTIFACTS_FILE_NAME)).unwrap());
+        assert_eq!(Artifacts::check(&dir, &pil), Ok(()));
+
+        let other = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(8);\ncol witness x;\nx = 2;\n",
+        );
+        assert!(Artifacts::check(&dir, &other)
+            .unwrap_err()
+            .contains("were generated for a different PIL"));
+
+        fs::write(dir.join(ARTIFACTS_FILE_NAME), "{").unwrap();
+        assert!(Artifacts::check(&dir, &pil)
+            .unwrap_err()
+            .starts_with("Invalid artifacts file"));
+    }
+}
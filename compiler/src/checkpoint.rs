@@ -0,0 +1,270 @@
+//! Checkpoints of the intermediate state of a compilation, so that witness generation
+//! can be repeated with different prover inputs without repeating the earlier stages.
+//!
+//! A checkpoint is a directory containing the optimized PIL, the evaluated fixed columns
+//! (from [Stage::FixedColumns] on) and a manifest with the stage and a hash of the
+//! source file. The hash is only compared if a source file is given to [Checkpoint::read].
+//! It is the SHA-256 hash of the source file, which does not cover imported files:
+//! if in doubt, re-create the checkpoint.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+
+use ast::analyzed::Analyzed;
+use executor::constant_evaluator;
+use executor::witgen::{QueryCallback, WitnessGenerator};
+use number::{write_polys_file, FieldElement};
+use parser_util::hash::sha256_hex;
+
+use crate::util::{read_poly_set, FixedPolySet, PolySet};
+use crate::{compile_asm_string_to_pil, optimize_pil};
+
+const MANIFEST_FILE_NAME: &str = "checkpoint.txt";
+const PIL_FILE_NAME: &str = "optimized.pil";
+
+/// The stage of the compilation after which a checkpoint is taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// The PIL has been optimized.
+    OptimizedPil,
+    /// The fixed columns have been evaluated.
+    FixedColumns,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::OptimizedPil => write!(f, "optimized-pil"),
+            Stage::FixedColumns => write!(f, "fixed-columns"),
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "optimized-pil" => Ok(Stage::OptimizedPil),
+            "fixed-columns" => Ok(Stage::FixedColumns),
+            _ => Err(format!("Unknown checkpoint stage `{s}`.")),
+        }
+    }
+}
+
+/// The state of a compilation after a given [Stage].
+pub struct Checkpoint<T> {
+    stage: Stage,
+    source_hash: Option<String>,
+    pil: Analyzed<T>,
+    fixed: Option<Vec<(String, Vec<T>)>>,
+}
+
+impl<T: FieldElement> Checkpoint<T> {
+    /// Compiles a .pil or .asm file up to and including `stage`.
+    pub fn create(source: &Path, stage: Stage) -> Result<Self, Vec<String>> {
+        let contents = fs::read_to_string(source)
+            .map_err(|e| vec![format!("Could not read {}: {e}", source.display())])?;
+        let analyzed = if source.extension().map_or(false, |ext| ext == "asm") {
+            let pil = compile_asm_string_to_pil::<T>(&source.to_string_lossy(), &contents)?;
+            pil_analyzer::analyze_string(&format!("{pil}"))
+        } else {
+            pil_analyzer::analyze(source)
+        };
+        let mut checkpoint = Self::from_optimized_pil(optimize_pil(analyzed), stage);
+        checkpoint.source_hash = Some(sha256_hex(contents.as_bytes()));
+        Ok(checkpoint)
+    }
+
+    /// Creates a checkpoint from an already optimized PIL, evaluating the fixed columns
+    /// if required by `stage`.
+    pub fn from_optimized_pil(pil: Analyzed<T>, stage: Stage) -> Self {
+        let fixed = (stage >= Stage::FixedColumns).then(|| {
+            log::info!("Evaluating fixed columns...");
+            constant_evaluator::generate(&pil)
+                .into_iter()
+                .map(|(name, values)| (name.to_string(), values))
+                .collect()
+        });
+        Checkpoint {
+            stage,
+            source_hash: None,
+            pil,
+            fixed,
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn pil(&self) -> &Analyzed<T> {
+        &self.pil
+    }
+
+    /// The evaluated fixed columns, if the checkpoint was taken after [Stage::FixedColumns].
+    pub fn fixed_columns(&self) -> Option<&[(String, Vec<T>)]> {
+        self.fixed.as_deref()
+    }
+
+    /// Writes the checkpoint to `dir`, which is created if needed.
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(PIL_FILE_NAME), format!("{}", self.pil))?;
+        if let Some(fixed) = &self.fixed {
+            let mut writer = BufWriter::new(fs::File::create(dir.join(FixedPolySet::FILE_NAME))?);
+            write_polys_file(&mut writer, fixed);
+        }
+        // The manifest is written last, so that an incomplete checkpoint cannot be read.
+        let mut manifest = format!("stage = {}\n", self.stage);
+        if let Some(hash) = &self.source_hash {
+            manifest += &format!("source-hash = {hash}\n");
+        }
+        fs::write(dir.join(MANIFEST_FILE_NAME), manifest)?;
+        log::info!(
+            "Wrote checkpoint after stage {} to {}.",
+            self.stage,
+            dir.display()
+        );
+        Ok(())
+    }
+
+    /// Reads a checkpoint from `dir`. If `source` is given, fails if the source file
+    /// has changed since the checkpoint was created.
+    pub fn read(dir: &Path, source: Option<&Path>) -> Result<Self, String> {
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        let manifest = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("Could not read {}: {e}", manifest_path.display()))?;
+        let mut stage = None;
+        let mut hash = None;
+        for line in manifest.lines().filter(|l| !l.trim().is_empty()) {
+            match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("stage", value)) => stage = Some(value.parse::<Stage>()?),
+                Some(("source-hash", value)) => hash = Some(value.to_string()),
+                _ => return Err(format!("Invalid line in checkpoint manifest: `{line}`")),
+            }
+        }
+        let stage = stage.ok_or_else(|| "The checkpoint manifest has no stage.".to_string())?;
+
+        if let Some(source) = source {
+            let contents = fs::read_to_string(source)
+                .map_err(|e| format!("Could not read {}: {e}", source.display()))?;
+            if hash.as_deref() != Some(sha256_hex(contents.as_bytes()).as_str()) {
+                return Err(format!(
+                    "The checkpoint in {} is stale: {} has changed since it was created.",
+                    dir.display(),
+                    source.display()
+                ));
+            }
+        }
+
+        let pil = pil_analyzer::analyze(&dir.join(PIL_FILE_NAME));
+        let fixed = if stage >= Stage::FixedColumns {
+            Some(read_poly_set::<FixedPolySet, T>(&pil, dir)?.0)
+        } else {
+            None
+        };
+        Ok(Checkpoint {
+            stage,
+            source_hash: hash,
+            pil,
+            fixed,
+        })
+    }
+
+    /// Runs witness generation on the checkpointed state, evaluating the fixed columns
+    /// first if they are not part of the checkpoint.
+    pub fn generate_witness<Q: QueryCallback<T>>(
+        &self,
+        query_callback: Q,
+    ) -> Vec<(String, Vec<T>)> {
+        let fixed: Vec<(&str, Vec<T>)> = match &self.fixed {
+            Some(fixed) => fixed
+                .iter()
+                .map(|(name, values)| (name.as_str(), values.clone()))
+                .collect(),
+            None => constant_evaluator::generate(&self.pil),
+        };
+        WitnessGenerator::new(&self.pil, &fixed, query_callback).generate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use executor::witgen::unused_query_callback;
+    use number::GoldilocksField;
+    use test_log::test;
+
+    fn fibonacci() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_data/pil/fibonacci.pil")
+    }
+
+    #[test]
+    fn write_and_read() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let checkpoint =
+            Checkpoint::<GoldilocksField>::create(&fibonacci(), Stage::FixedColumns).unwrap();
+        checkpoint.write(&dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join(MANIFEST_FILE_NAME)).unwrap(),
+            format!(
+                "stage = {}\nsource-hash = {}\n",
+                Stage::FixedColumns,
+                sha256_hex(&fs::read(fibonacci()).unwrap())
+            )
+        );
+
+        let restored = Checkpoint::<GoldilocksField>::read(&dir, Some(&fibonacci())).unwrap();
+        assert_eq!(restored.stage(), Stage::FixedColumns);
+        assert_eq!(restored.pil().to_string(), checkpoint.pil().to_string());
+        assert_eq!(restored.fixed_columns(), checkpoint.fixed_columns());
+        assert_eq!(
+            restored.generate_witness(unused_query_callback()),
+            checkpoint.generate_witness(unused_query_callback())
+        );
+    }
+
+    #[test]
+    fn optimized_pil_only() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        Checkpoint::<GoldilocksField>::create(&fibonacci(), Stage::OptimizedPil)
+            .unwrap()
+            .write(&dir)
+            .unwrap();
+        assert!(!dir.join(FixedPolySet::FILE_NAME).exists());
+
+        let restored = Checkpoint::<GoldilocksField>::read(&dir, None).unwrap();
+        assert_eq!(restored.fixed_columns(), None);
+        let witness = restored.generate_witness(unused_query_callback());
+        assert_eq!(witness[1].0, "Fibonacci.y");
+        assert_eq!(witness[1].1[15], GoldilocksField::from(1597));
+    }
+
+    #[test]
+    fn stale_checkpoint() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let source = dir.join("fibonacci.pil");
+        fs::copy(fibonacci(), &source).unwrap();
+        let checkpoint_dir = dir.join("checkpoint");
+        Checkpoint::<GoldilocksField>::create(&source, Stage::FixedColumns)
+            .unwrap()
+            .write(&checkpoint_dir)
+            .unwrap();
+
+        fs::write(
+            &source,
+            fs::read_to_string(&source).unwrap().replace("16", "32"),
+        )
+        .unwrap();
+        let err = Checkpoint::<GoldilocksField>::read(&checkpoint_dir, Some(&source))
+            .err()
+            .unwrap();
+        assert!(err.contains("is stale"), "{err}");
+        // Without a source, the checkpoint is used as it is.
+        assert!(Checkpoint::<GoldilocksField>::read(&checkpoint_dir, None).is_ok());
+    }
+}
@@ -3,37 +3,71 @@
 #![deny(clippy::print_stdout)]
 
 use std::ffi::OsStr;
+use std::fmt::Display;
 use std::fs;
+use std::io::{BufReader, BufWriter};
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::Instant;
 
 use analysis::analyze;
 use analysis::convert_analyzed_to_pil_constraints;
-use ast::analyzed::Analyzed;
+use analysis::convert_analyzed_to_pil_constraints_merging_registers;
+use ast::analyzed::{Analyzed, UnconstrainedReason, ANALYZED_FILE_NAME};
 use ast::DiffMonitor;
 
+pub mod artifacts;
+pub mod checkpoint;
 pub mod util;
 mod verify;
+pub mod witness_transformer;
 
-use ast::asm_analysis::AnalysisASMFile;
-pub use backend::{BackendType, Proof};
-use executor::witgen::QueryCallback;
-use itertools::Itertools;
+use ast::asm_analysis::{AnalysisASMFile, MachineInfo};
+pub use backend::{BackendType, Proof, ProofMetadata};
+pub use executor::cancellation::{CancellationToken, Cancelled};
+use executor::witgen::{self, ColumnRangeConstraint, QueryCallback};
+pub use executor::witgen::{channel_inputs_to_query_callback, inputs_to_query_callback};
 pub use verify::{
-    verify, verify_asm_string, write_commits_to_fs, write_constants_to_fs, write_constraints_to_fs,
+    verify, verify_asm_string, verify_asm_string_with_callback, write_commits_to_fs,
+    write_constants_to_fs, write_constraints_to_fs,
 };
 
+pub use asm_to_pil::MergeReport;
 use ast::parsed::PILFile;
+use ast::source_map::SourceMap;
 use executor::constant_evaluator;
-use number::FieldElement;
+use number::{DegreeType, FieldElement};
+use parser_util::paths::{existing_target_file, file_stem};
+use parser_util::provenance::Provenance;
+pub use pil_analyzer::Limits;
+pub use pilopt::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE;
+
+/// How to report witness columns that are not constrained, see [check_unconstrained_witnesses].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnconstrainedWitnesses {
+    /// The intentionally unconstrained columns, by absolute name.
+    /// The name of an array covers all its elements.
+    pub allowed: Vec<String>,
+    /// Report the columns as errors instead of warnings.
+    pub deny: bool,
+}
+
+impl UnconstrainedWitnesses {
+    fn is_allowed(&self, name: &str) -> bool {
+        let array_name = name.split_once('[').map(|(array, _)| array);
+        self.allowed
+            .iter()
+            .any(|allowed| allowed == name || Some(allowed.as_str()) == array_name)
+    }
+}
 
 pub fn no_callback<T>() -> Option<fn(&str) -> Option<T>> {
     None
 }
 
 /// Compiles a .pil or .asm file and runs witness generation.
-/// If the file ends in .asm, converts it to .pil first.
+/// If the file ends in .asm, converts it to .pil first. A .bin file is read
+/// as analyzed PIL, see [read_analyzed_pil].
 /// Returns the compilation result if any compilation took place.
 #[allow(clippy::too_many_arguments)]
 pub fn compile_pil_or_asm<T: FieldElement>(
@@ -44,26 +78,100 @@ pub fn compile_pil_or_asm<T: FieldElement>(
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
+    degree: Option<DegreeType>,
+    cancellation: &CancellationToken,
+) -> Result<Option<CompilationResult<T>>, Vec<String>> {
+    compile_pil_or_asm_with_callback(
+        file_name,
+        inputs_to_query_callback(inputs),
+        output_dir,
+        force_overwrite,
+        prove_with,
+        external_witness_values,
+        bname,
+        degree,
+        &Limits::default(),
+        &UnconstrainedWitnesses::default(),
+        DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        cancellation,
+    )
+}
+
+/// Like [compile_pil_or_asm], but answers the prover queries with `query_callback`,
+/// e.g. one created by [channel_inputs_to_query_callback].
+/// A .pil file is rejected if it exceeds `limits`.
+/// Unconstrained witness columns are reported according to `unconstrained`.
+/// The optimizer inlines the intermediate columns with at most
+/// `max_inlined_intermediate_size` nodes.
+#[allow(clippy::too_many_arguments)]
+pub fn compile_pil_or_asm_with_callback<T: FieldElement, Q: QueryCallback<T>>(
+    file_name: &str,
+    query_callback: Q,
+    output_dir: &Path,
+    force_overwrite: bool,
+    prove_with: Option<BackendType>,
+    external_witness_values: Vec<(&str, Vec<T>)>,
+    bname: Option<String>,
+    degree: Option<DegreeType>,
+    limits: &Limits,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
 ) -> Result<Option<CompilationResult<T>>, Vec<String>> {
     if file_name.ends_with(".asm") {
-        compile_asm(
+        let contents = fs::read_to_string(file_name).unwrap();
+        Ok(compile_asm_string_with_callback(
             file_name,
-            inputs,
+            &contents,
+            query_callback,
+            None,
             output_dir,
             force_overwrite,
             prove_with,
             external_witness_values,
             bname,
+            degree,
+            unconstrained,
+            max_inlined_intermediate_size,
+            cancellation,
+        )?
+        .1)
+    } else if file_name.ends_with(".bin") {
+        if degree.is_some() {
+            return Err(vec![
+                "The degree of analyzed PIL cannot be overridden.".to_string()
+            ]);
+        }
+        let file = Path::new(file_name);
+        compile(
+            read_analyzed_pil(file).map_err(|e| vec![e])?,
+            file.file_name().unwrap(),
+            None,
+            output_dir,
+            query_callback,
+            prove_with,
+            external_witness_values,
+            bname,
+            unconstrained,
+            max_inlined_intermediate_size,
+            cancellation,
         )
+        .map(Some)
     } else {
-        Ok(Some(compile_pil(
+        compile_pil(
             Path::new(file_name),
             output_dir,
-            inputs_to_query_callback(inputs),
+            query_callback,
             prove_with,
             external_witness_values,
             bname,
-        )))
+            degree,
+            limits,
+            unconstrained,
+            max_inlined_intermediate_size,
+            cancellation,
+        )
+        .map(Some)
     }
 }
 
@@ -71,10 +179,21 @@ pub fn analyze_pil<T: FieldElement>(pil_file: &Path) -> Analyzed<T> {
     pil_analyzer::analyze(pil_file)
 }
 
+/// Reads the analyzed PIL written to [ANALYZED_FILE_NAME] by a previous compilation.
+pub fn read_analyzed_pil<T: FieldElement>(path: &Path) -> Result<Analyzed<T>, String> {
+    let file =
+        fs::File::open(path).map_err(|e| format!("Could not open {}: {e}", path.display()))?;
+    Analyzed::read_binary(BufReader::new(file)).map_err(|e| format!("{}: {e}", path.display()))
+}
+
 /// Compiles a .pil file to its json form and also tries to generate
 /// constants and committed polynomials.
 /// @returns a compilation result, containing witness and fixed columns
 /// if they could be successfully generated.
+/// The external values may also contain fixed columns, which are checked
+/// against the evaluated ones.
+/// Returns an error if the file exceeds `limits`.
+#[allow(clippy::too_many_arguments)]
 pub fn compile_pil<T: FieldElement, Q: QueryCallback<T>>(
     pil_file: &Path,
     output_dir: &Path,
@@ -82,45 +201,64 @@ pub fn compile_pil<T: FieldElement, Q: QueryCallback<T>>(
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
-) -> CompilationResult<T> {
+    degree: Option<DegreeType>,
+    limits: &Limits,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
+) -> Result<CompilationResult<T>, Vec<String>> {
     compile(
-        pil_analyzer::analyze(pil_file),
+        pil_analyzer::analyze_with_limits(pil_file, degree, limits)?,
         pil_file.file_name().unwrap(),
+        Provenance::for_input_file(pil_file),
         output_dir,
         query_callback,
         prove_with,
         external_witness_values,
         bname,
+        unconstrained,
+        max_inlined_intermediate_size,
+        cancellation,
     )
 }
 
 /// Compiles a given PIL and tries to generate fixed and witness columns.
+/// The source positions in `pil` are positions in `sources`.
 /// @returns a compilation result, containing witness and fixed columns
+#[allow(clippy::too_many_arguments)]
 pub fn compile_pil_ast<T: FieldElement, Q: QueryCallback<T>>(
     pil: &PILFile<T>,
+    sources: &SourceMap,
     file_name: &OsStr,
     output_dir: &Path,
     query_callback: Q,
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
-) -> CompilationResult<T> {
-    // TODO exporting this to string as a hack because the parser
-    // is tied into the analyzer due to imports.
+    degree: Option<DegreeType>,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
+) -> Result<CompilationResult<T>, Vec<String>> {
     compile(
-        pil_analyzer::analyze_string(&format!("{pil}")),
+        pil_analyzer::analyze_ast_with_degree(PILFile(pil.0.clone()), sources, degree),
         file_name,
+        Provenance::for_generated_input_file(&output_dir.join(file_name)),
         output_dir,
         query_callback,
         prove_with,
         external_witness_values,
         bname,
+        unconstrained,
+        max_inlined_intermediate_size,
+        cancellation,
     )
 }
 
 /// Compiles a .asm file, outputs the PIL on stdout and tries to generate
 /// fixed and witness columns.
 /// @returns a compilation result if any compilation was done.
+#[allow(clippy::too_many_arguments)]
 pub fn compile_asm<T: FieldElement>(
     file_name: &str,
     inputs: Vec<T>,
@@ -129,6 +267,8 @@ pub fn compile_asm<T: FieldElement>(
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
+    degree: Option<DegreeType>,
+    cancellation: &CancellationToken,
 ) -> Result<Option<CompilationResult<T>>, Vec<String>> {
     let contents = fs::read_to_string(file_name).unwrap();
     Ok(compile_asm_string(
@@ -141,6 +281,8 @@ pub fn compile_asm<T: FieldElement>(
         prove_with,
         external_witness_values,
         bname,
+        degree,
+        cancellation,
     )?
     .1)
 }
@@ -169,6 +311,75 @@ pub fn compile_asm_string_to_analyzed_ast<T: FieldElement>(
     Ok(analyzed)
 }
 
+/// Analyzes the contents of a .asm file without generating PIL and returns the
+/// structure of its machines, with positions as byte offsets into the source files.
+pub fn asm_machine_info<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+) -> Result<Vec<MachineInfo>, Vec<String>> {
+    Ok(compile_asm_string_to_analyzed_ast::<T>(file_name, contents, None)?.machine_info())
+}
+
+/// Converts an analyzed .asm file to PIL by running airgen and the linker.
+/// Also returns the source files the positions in the PIL refer to.
+fn link_analyzed_asm<T: FieldElement>(
+    monitor: &mut DiffMonitor,
+    mut analyzed: AnalysisASMFile<T>,
+) -> Result<(PILFile<T>, SourceMap), Vec<String>> {
+    let sources = std::mem::take(&mut analyzed.sources);
+    let constraints = convert_analyzed_to_pil_constraints(analyzed, monitor);
+    log::debug!("Run airgen");
+    let graph = airgen::compile(constraints);
+    log::debug!("Airgen done");
+    log::trace!("{graph}");
+    log::debug!("Run linker");
+    let pil = linker::link(graph)?;
+    log::debug!("Linker done");
+    log::trace!("{pil}");
+    Ok((pil, sources))
+}
+
+/// Compiles the contents of a .asm file to PIL without writing any files.
+pub fn compile_asm_string_to_pil<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+) -> Result<PILFile<T>, Vec<String>> {
+    let mut monitor = DiffMonitor::default();
+    let analyzed = compile_asm_string_to_analyzed_ast(file_name, contents, Some(&mut monitor))?;
+    link_analyzed_asm(&mut monitor, analyzed).map(|(pil, _)| pil)
+}
+
+/// Compiles the contents of a .asm file to analyzed PIL, whose identities and
+/// columns refer to their source locations in the .asm files.
+pub fn compile_asm_string_to_analyzed_pil<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+) -> Result<Analyzed<T>, Vec<String>> {
+    let mut monitor = DiffMonitor::default();
+    let analyzed = compile_asm_string_to_analyzed_ast(file_name, contents, Some(&mut monitor))?;
+    let (pil, sources) = link_analyzed_asm(&mut monitor, analyzed)?;
+    Ok(pil_analyzer::analyze_ast_with_degree(pil, &sources, None))
+}
+
+/// Like [compile_asm_string_to_pil], but lets assignment registers which are never used
+/// in the same row share their columns. Also returns a report of the merged registers.
+/// The merge does not change the values of any other column, so the executor does not
+/// need to know about it.
+pub fn compile_asm_string_to_pil_merging_registers<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+) -> Result<(PILFile<T>, MergeReport), Vec<String>> {
+    let mut monitor = DiffMonitor::default();
+    let analyzed = compile_asm_string_to_analyzed_ast(file_name, contents, Some(&mut monitor))?;
+    let (constraints, report) =
+        convert_analyzed_to_pil_constraints_merging_registers(analyzed, &mut monitor);
+    log::debug!("Run airgen");
+    let graph = airgen::compile(constraints);
+    log::debug!("Run linker");
+    let pil = linker::link(graph)?;
+    Ok((pil, report))
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn convert_analyzed_to_pil<T: FieldElement>(
     file_name: &str,
@@ -180,45 +391,47 @@ pub fn convert_analyzed_to_pil<T: FieldElement>(
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
+    degree: Option<DegreeType>,
+    cancellation: &CancellationToken,
 ) -> Result<(PathBuf, Option<CompilationResult<T>>), Vec<String>> {
-    let constraints = convert_analyzed_to_pil_constraints(analyzed, monitor);
-    log::debug!("Run airgen");
-    let graph = airgen::compile(constraints);
-    log::debug!("Airgen done");
-    log::trace!("{graph}");
-    log::debug!("Run linker");
-    let pil = linker::link(graph)?;
-    log::debug!("Linker done");
-    log::trace!("{pil}");
+    let (pil, sources) = link_analyzed_asm(monitor, analyzed)?;
 
-    let pil_file_name = format!(
-        "{}.pil",
-        Path::new(file_name).file_stem().unwrap().to_str().unwrap()
-    );
+    let pil_file_name = format!("{}.pil", file_stem(Path::new(file_name)));
 
     let pil_file_path = output_dir.join(pil_file_name);
-    if pil_file_path.exists() && !force_overwrite {
-        eprintln!(
-            "Target file {} already exists. Not overwriting.",
-            pil_file_path.to_str().unwrap()
-        );
-        return Ok((pil_file_path, None));
+    if let Some(existing) = existing_target_file(&pil_file_path) {
+        if !force_overwrite {
+            eprintln!(
+                "Target file {} already exists. Not overwriting.",
+                existing.display()
+            );
+            return Ok((pil_file_path, None));
+        }
     }
 
-    fs::write(&pil_file_path, format!("{pil}")).unwrap();
+    fs::write(
+        &pil_file_path,
+        with_provenance(Provenance::for_input_file(Path::new(file_name)), &pil),
+    )
+    .unwrap();
 
     let pil_file_name = pil_file_path.file_name().unwrap();
     Ok((
         pil_file_path.clone(),
         Some(compile_pil_ast(
             &pil,
+            &sources,
             pil_file_name,
             output_dir,
             inputs_to_query_callback(inputs),
             prove_with,
             external_witness_values,
             bname,
-        )),
+            degree,
+            &UnconstrainedWitnesses::default(),
+            DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+            cancellation,
+        )?),
     ))
 }
 
@@ -233,45 +446,49 @@ pub fn convert_analyzed_to_pil_with_callback<T: FieldElement, Q: QueryCallback<T
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
+    degree: Option<DegreeType>,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
 ) -> Result<(PathBuf, Option<CompilationResult<T>>), Vec<String>> {
-    let constraints = convert_analyzed_to_pil_constraints(analyzed, monitor);
-    log::debug!("Run airgen");
-    let graph = airgen::compile(constraints);
-    log::debug!("Airgen done");
-    log::trace!("{graph}");
-    log::debug!("Run linker");
-    let pil = linker::link(graph)?;
-    log::debug!("Linker done");
-    log::trace!("{pil}");
+    let (pil, sources) = link_analyzed_asm(monitor, analyzed)?;
 
-    let pil_file_name = format!(
-        "{}.pil",
-        Path::new(file_name).file_stem().unwrap().to_str().unwrap()
-    );
+    let pil_file_name = format!("{}.pil", file_stem(Path::new(file_name)));
 
     let pil_file_path = output_dir.join(pil_file_name);
-    if pil_file_path.exists() && !force_overwrite {
-        eprintln!(
-            "Target file {} already exists. Not overwriting.",
-            pil_file_path.to_str().unwrap()
-        );
-        return Ok((pil_file_path, None));
+    if let Some(existing) = existing_target_file(&pil_file_path) {
+        if !force_overwrite {
+            eprintln!(
+                "Target file {} already exists. Not overwriting.",
+                existing.display()
+            );
+            return Ok((pil_file_path, None));
+        }
     }
 
-    fs::write(&pil_file_path, format!("{pil}")).unwrap();
+    fs::write(
+        &pil_file_path,
+        with_provenance(Provenance::for_input_file(Path::new(file_name)), &pil),
+    )
+    .unwrap();
 
     let pil_file_name = pil_file_path.file_name().unwrap();
     Ok((
         pil_file_path.clone(),
         Some(compile_pil_ast(
             &pil,
+            &sources,
             pil_file_name,
             output_dir,
             query_callback,
             prove_with,
             external_witness_values,
             bname,
-        )),
+            degree,
+            unconstrained,
+            max_inlined_intermediate_size,
+            cancellation,
+        )?),
     ))
 }
 
@@ -292,6 +509,8 @@ pub fn compile_asm_string<T: FieldElement>(
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
+    degree: Option<DegreeType>,
+    cancellation: &CancellationToken,
 ) -> Result<(PathBuf, Option<CompilationResult<T>>), Vec<String>> {
     let mut monitor = DiffMonitor::default();
     let analyzed = compile_asm_string_to_analyzed_ast(file_name, contents, Some(&mut monitor))?;
@@ -308,6 +527,8 @@ pub fn compile_asm_string<T: FieldElement>(
         prove_with,
         external_witness_values,
         bname,
+        degree,
+        cancellation,
     )
 }
 
@@ -322,6 +543,10 @@ pub fn compile_asm_string_with_callback<T: FieldElement, Q: QueryCallback<T>>(
     prove_with: Option<BackendType>,
     external_witness_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
+    degree: Option<DegreeType>,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
 ) -> Result<(PathBuf, Option<CompilationResult<T>>), Vec<String>> {
     let mut monitor = DiffMonitor::default();
     let analyzed = compile_asm_string_to_analyzed_ast(file_name, contents, Some(&mut monitor))?;
@@ -338,6 +563,10 @@ pub fn compile_asm_string_with_callback<T: FieldElement, Q: QueryCallback<T>>(
         prove_with,
         external_witness_values,
         bname,
+        degree,
+        unconstrained,
+        max_inlined_intermediate_size,
+        cancellation,
     )
 }
 
@@ -346,45 +575,171 @@ pub struct CompilationResult<T: FieldElement> {
     pub constants: Vec<(String, Vec<T>)>,
     /// Witness columns, potentially None (if success is false)
     pub witness: Option<Vec<(String, Vec<T>)>>,
-    /// Proof, potentially None (if success is false)
-    pub proof: Option<Proof>,
+    /// Proof with its metadata, potentially None (if success is false)
+    pub proof: Option<(Proof, ProofMetadata)>,
+    /// The values of the public declarations the proof was generated for, in declaration order.
+    pub publics: Vec<(String, T)>,
     /// Serialized low level constraints, potentially None (if success is false)
     pub constraints_serialization: Option<String>,
+    /// The global range constraints witness generation derives for the columns,
+    /// empty if the fixed columns have not been evaluated.
+    pub range_constraints: Vec<ColumnRangeConstraint>,
+    /// The optimized PIL file that has been written, None if optimization has not run.
+    pub pil: Option<PilSummary>,
+}
+
+/// The optimized PIL file written during compilation.
+pub struct PilSummary {
+    pub file: PathBuf,
+    /// The hash of the optimized PIL, see [artifacts::optimized_pil_hash].
+    pub hash: String,
+    pub degree: Option<DegreeType>,
+    /// The number of witness columns (with multiplicities for arrays)
+    pub commitment_count: usize,
+    /// The number of fixed columns (with multiplicities for arrays)
+    pub constant_count: usize,
+}
+
+/// Renders a generated file, starting with the provenance header if there is one.
+pub fn with_provenance(provenance: Option<Provenance>, contents: impl Display) -> String {
+    match provenance {
+        Some(provenance) => provenance.prepend_to(&contents.to_string()),
+        None => contents.to_string(),
+    }
+}
+
+/// Optimizes a given pil and inlines its intermediate polynomials.
+pub fn optimize_pil<T: FieldElement>(analyzed: Analyzed<T>) -> Analyzed<T> {
+    optimize_pil_with_max_inlined_intermediate_size(analyzed, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE)
+}
+
+/// Like [optimize_pil], but the optimizer only removes the intermediate columns
+/// whose definition has at most `max_inlined_intermediate_size` nodes,
+/// see [pilopt::optimize_reporting_duplicates].
+pub fn optimize_pil_with_max_inlined_intermediate_size<T: FieldElement>(
+    analyzed: Analyzed<T>,
+    max_inlined_intermediate_size: usize,
+) -> Analyzed<T> {
+    log::info!("Optimizing pil...");
+    let (analyzed, _) =
+        pilopt::optimize_reporting_duplicates(analyzed, max_inlined_intermediate_size);
+
+    // md: we inline intermediate polynomials here, as honk does not have a notion of an intermediate
+    let mut mut_analyzed = analyzed;
+    mut_analyzed.identities = mut_analyzed.identities_with_inlined_intermediate_polynomials();
+    mut_analyzed
+}
+
+/// Reports the witness columns that are not constrained, see [Analyzed::unconstrained_witnesses],
+/// as warnings, or as errors if `unconstrained.deny` is set.
+/// @returns the warnings that have been logged.
+pub fn check_unconstrained_witnesses<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    unconstrained: &UnconstrainedWitnesses,
+) -> Result<Vec<String>, Vec<String>> {
+    let messages = analyzed
+        .unconstrained_witnesses_with_reasons()
+        .into_iter()
+        .filter(|(name, _, _)| !unconstrained.is_allowed(name))
+        .map(|(name, _, reason)| match reason {
+            UnconstrainedReason::Unreferenced => {
+                format!("Witness column {name} is not referenced in any identity.")
+            }
+            UnconstrainedReason::OnlyInDisabledLookups => format!(
+                "Witness column {name} only occurs on the left-hand side of lookups or permutations whose selector can be zero in all rows."
+            ),
+        })
+        .collect::<Vec<_>>();
+    if unconstrained.deny && !messages.is_empty() {
+        return Err(messages);
+    }
+    for message in &messages {
+        log::warn!("{message}");
+    }
+    Ok(messages)
 }
 
 /// Optimizes a given pil and tries to generate constants and committed polynomials.
 /// @returns a compilation result, containing witness and fixed columns, if successful.
+/// If `cancellation` is requested, stops before the next stage and returns the
+/// results computed so far.
+#[allow(clippy::too_many_arguments)]
 fn compile<T: FieldElement, Q: QueryCallback<T>>(
     analyzed: Analyzed<T>,
     file_name: &OsStr,
+    provenance: Option<Provenance>,
     output_dir: &Path,
     _query_callback: Q,
     prove_with: Option<BackendType>,
-    _external_witness_values: Vec<(&str, Vec<T>)>,
+    external_values: Vec<(&str, Vec<T>)>,
     bname: Option<String>,
-) -> CompilationResult<T> {
-    log::info!("Optimizing pil...");
-    let analyzed = pilopt::optimize(analyzed);
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
+) -> Result<CompilationResult<T>, Vec<String>> {
+    check_unconstrained_witnesses(&analyzed, unconstrained)?;
+    if let Some(backend) = &prove_with {
+        backend.check_supported(&analyzed).map_err(|e| vec![e])?;
+    }
 
-    // md: we inline intermediate polynomials here, as honk does not have a notion of an intermediate
-    let mut mut_analyzed = analyzed;
-    mut_analyzed.identities = mut_analyzed.identities_with_inlined_intermediate_polynomials();
+    let mut result = CompilationResult {
+        constants: vec![],
+        witness: None,
+        proof: None,
+        publics: vec![],
+        constraints_serialization: None,
+        range_constraints: vec![],
+        pil: None,
+    };
+
+    if cancellation.check("optimization").is_err() {
+        return Ok(result);
+    }
+    let mut_analyzed =
+        optimize_pil_with_max_inlined_intermediate_size(analyzed, max_inlined_intermediate_size);
+
+    let optimized_pil_file_name =
+        output_dir.join(format!("{}_opt.pil", file_stem(Path::new(file_name))));
+    fs::write(
+        optimized_pil_file_name.clone(),
+        with_provenance(provenance, &mut_analyzed),
+    )
+    .unwrap();
+    log::info!("Wrote {}.", optimized_pil_file_name.display());
+    let analyzed_file_name = output_dir.join(ANALYZED_FILE_NAME);
+    mut_analyzed
+        .write_binary(BufWriter::new(
+            fs::File::create(&analyzed_file_name).unwrap(),
+        ))
+        .map_err(|e| vec![e])?;
+    log::info!("Wrote {}.", analyzed_file_name.display());
+    result.pil = Some(PilSummary {
+        file: optimized_pil_file_name,
+        hash: artifacts::optimized_pil_hash(&mut_analyzed),
+        degree: mut_analyzed.degree,
+        commitment_count: mut_analyzed.commitment_count(),
+        constant_count: mut_analyzed.constant_count(),
+    });
+    cancellation.stage_completed("optimization");
 
-    let optimized_pil_file_name = output_dir.join(format!(
-        "{}_opt.pil",
-        Path::new(file_name).file_stem().unwrap().to_str().unwrap()
-    ));
-    fs::write(optimized_pil_file_name.clone(), format!("{mut_analyzed}")).unwrap();
-    log::info!("Wrote {}.", optimized_pil_file_name.to_str().unwrap());
+    if cancellation.check("fixed column evaluation").is_err() {
+        return Ok(result);
+    }
     let start = Instant::now();
     log::info!("Evaluating fixed columns...");
     let constants = constant_evaluator::generate(&mut_analyzed);
     log::info!("Took {}", start.elapsed().as_secs_f32());
+    cancellation.stage_completed("fixed column evaluation");
+    constant_evaluator::check_column_roles(&mut_analyzed, &constants)?;
+
+    result.range_constraints = witgen::range_constraint_report(&mut_analyzed, &constants);
+    witgen::check_range_assertions(&mut_analyzed, &result.range_constraints)?;
 
     let witness_names = mut_analyzed
         .committed_polys_in_source_order()
         .into_iter()
-        .map(|(sym, _)| sym.absolute_name.clone())
+        .flat_map(|(sym, _)| sym.array_elements())
+        .map(|(name, _)| name)
         .collect::<Vec<_>>();
 
     // NOTE: temporarily just append a vector to the end such that it is in the expected form for the backend
@@ -403,117 +758,39 @@ fn compile<T: FieldElement, Q: QueryCallback<T>>(
         .map(|(name, c)| (name.to_string(), c))
         .collect::<Vec<_>>();
 
+    let (external_fixed_values, _external_witness_values) =
+        util::split_external_values(&mut_analyzed, external_values);
+    util::check_external_fixed_values(&constants, &external_fixed_values).map_err(|e| vec![e])?;
+
     // Even if we don't have all constants and witnesses, some backends will
     // still output the constraint serialization.
-    let (proof, constraints_serialization) = if let Some(backend) = prove_with {
+    if let Some(backend) = prove_with {
+        if cancellation.check("proving").is_err() {
+            result.constants = constants;
+            return Ok(result);
+        }
         let factory = backend.factory::<T>();
         let backend = factory.create(mut_analyzed.degree());
 
-        backend.prove(
+        // The publics can only be evaluated if the witness values are available.
+        if witness_in_powdr_form
+            .iter()
+            .all(|(_, values)| !values.is_empty())
+        {
+            result.publics = backend::evaluate_publics(&mut_analyzed, &witness_in_powdr_form)
+                .map_err(|e| vec![e])?;
+        }
+        (result.proof, result.constraints_serialization) = backend.prove(
             &mut_analyzed,
             &constants,
             &witness_in_powdr_form,
+            &result.publics,
             None,
             bname,
-        )
-    } else {
-        (None, None)
-    };
-
-    let constants = constants
-        .into_iter()
-        .map(|(name, c)| (name.to_owned(), c))
-        .collect();
-
-    CompilationResult {
-        constants,
-        witness: None,
-        proof,
-        constraints_serialization,
+        );
+        cancellation.stage_completed("proving");
     }
-}
 
-#[allow(clippy::print_stdout)]
-pub fn inputs_to_query_callback<T: FieldElement>(inputs: Vec<T>) -> impl QueryCallback<T> {
-    // TODO: Pass bootloader inputs into this function
-    // Right now, accessing bootloader inputs will always fail, because it will be out of bounds
-    let bootloader_inputs = [];
-
-    move |query: &str| -> Result<Option<T>, String> {
-        // TODO In the future, when match statements need to be exhaustive,
-        // This function probably gets an Option as argument and it should
-        // answer None by Ok(None).
-
-        // We are expecting a tuple
-        let query = query
-            .strip_prefix('(')
-            .and_then(|q| q.strip_suffix(')'))
-            .ok_or_else(|| "Prover query has to be a tuple".to_string())?;
-        let items = query.split(',').map(|s| s.trim()).collect::<Vec<_>>();
-        match &items[..] {
-            ["\"input\"", index] => {
-                let index = index
-                    .parse::<usize>()
-                    .map_err(|e| format!("Error parsing index: {e})"))?;
-                let value = inputs.get(index).cloned();
-                if let Some(value) = value {
-                    log::trace!("Input query: Index {index} -> {value}");
-                    Ok(Some(value))
-                } else {
-                    Err(format!(
-                        "Error accessing prover inputs: Index {index} out of bounds {}",
-                        inputs.len()
-                    ))
-                }
-            }
-            ["\"data\"", index, what] => {
-                let index = index
-                    .parse::<usize>()
-                    .map_err(|e| format!("Error parsing index: {e})"))?;
-                let what = what
-                    .parse::<usize>()
-                    .map_err(|e| format!("Error parsing what: {e})"))?;
-                assert_eq!(what, 0);
-
-                let value = inputs.get(index).cloned();
-                if let Some(value) = value {
-                    log::trace!("Input query: Index {index} -> {value}");
-                    Ok(Some(value))
-                } else {
-                    Err(format!(
-                        "Error accessing prover inputs: Index {index} out of bounds {}",
-                        inputs.len()
-                    ))
-                }
-            }
-            ["\"bootloader_input\"", index] => {
-                let index = index
-                    .parse::<usize>()
-                    .map_err(|e| format!("Error parsing index: {e})"))?;
-                let value = bootloader_inputs.get(index).cloned();
-                if let Some(value) = value {
-                    log::trace!("Bootloader input query: Index {index} -> {value}");
-                    Ok(Some(value))
-                } else {
-                    Err(format!(
-                        "Error accessing bootloader inputs: Index {index} out of bounds {}",
-                        inputs.len()
-                    ))
-                }
-            }
-            ["\"print_char\"", ch] => {
-                print!(
-                    "{}",
-                    ch.parse::<u8>()
-                        .map_err(|e| format!("Invalid char to print: {e}"))?
-                        as char
-                );
-                // We do not answer None because we don't want this function to be
-                // called again.
-                Ok(Some(0.into()))
-            }
-            ["\"hint\"", value] => Ok(Some(T::from_str(value))),
-            k => Err(format!("Unsupported query: {}", k.iter().format(", "))),
-        }
-    }
+    result.constants = constants;
+    Ok(result)
 }
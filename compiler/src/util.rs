@@ -1,6 +1,13 @@
 use ast::analyzed::{Analyzed, FunctionValueDefinition, Symbol};
-use number::{read_polys_file, DegreeType, FieldElement};
-use std::{fs::File, io::BufReader, path::Path};
+use executor::cancellation::{CancellationToken, Cancelled};
+use number::{map_polys_file, read_polys_file, ColumnsWithDegree, FieldElement, MappedColumn};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
 pub trait PolySet {
     const FILE_NAME: &'static str;
@@ -31,18 +38,242 @@ impl PolySet for WitnessPolySet {
     }
 }
 
+/// The names of the columns of the poly set, in the order of `pil`.
+fn poly_set_column_names<P: PolySet, T: FieldElement>(pil: &Analyzed<T>) -> Vec<String> {
+    P::get_polys(pil)
+        .iter()
+        .flat_map(|(poly, _)| poly.array_elements())
+        .map(|(name, _id)| name)
+        .collect()
+}
+
+fn open_poly_set<P: PolySet>(dir: &Path) -> Result<(File, PathBuf), String> {
+    let path = dir.join(P::FILE_NAME);
+    let file = File::open(&path).map_err(|e| format!("Could not open {}: {e}", path.display()))?;
+    Ok((file, path))
+}
+
+/// Reads the columns of the poly set from `dir`, in the order of `pil`.
+/// Fails if the file cannot be read or its columns do not match the columns of `pil`.
 pub fn read_poly_set<P: PolySet, T: FieldElement>(
     pil: &Analyzed<T>,
     dir: &Path,
-) -> (Vec<(String, Vec<T>)>, DegreeType) {
-    let column_names: Vec<String> = P::get_polys(pil)
+) -> Result<ColumnsWithDegree<Vec<T>>, String> {
+    let (file, path) = open_poly_set::<P>(dir)?;
+    read_polys_file(
+        &mut BufReader::new(file),
+        &poly_set_column_names::<P, T>(pil),
+    )
+    .map_err(|e| format!("Could not read {}: {e}", path.display()))
+}
+
+/// Like [read_poly_set], but maps the file into memory instead of reading it,
+/// see [number::map_polys_file].
+pub fn map_poly_set<P: PolySet, T: FieldElement>(
+    pil: &Analyzed<T>,
+    dir: &Path,
+) -> Result<ColumnsWithDegree<MappedColumn<T>>, String> {
+    let (file, path) = open_poly_set::<P>(dir)?;
+    map_polys_file(&file, &poly_set_column_names::<P, T>(pil))
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))
+}
+
+/// The temporary file [write_atomically] writes to before renaming it to `path`.
+pub fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Writes a file by writing to a temporary file next to it and renaming it once complete,
+/// so that an interrupted run never leaves a partially written file at `path`.
+/// If cancellation is requested before the file is complete, the write is abandoned
+/// and the temporary file is removed.
+pub fn write_atomically(
+    path: &Path,
+    cancellation: &CancellationToken,
+    write: impl FnOnce(&mut BufWriter<File>),
+) -> Result<(), Cancelled> {
+    try_write_atomically(path, cancellation, |writer| {
+        write(writer);
+        Ok(())
+    })
+    .map_err(|e| match e {
+        WriteError::Cancelled(cancelled) => cancelled,
+        WriteError::Io(e) => panic!("{e}"),
+    })
+}
+
+/// An error of [try_write_atomically].
+#[derive(Debug)]
+pub enum WriteError {
+    Cancelled(Cancelled),
+    Io(io::Error),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::Cancelled(cancelled) => write!(f, "{cancelled}"),
+            WriteError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Like [write_atomically], but `write` can fail. In that case, the temporary file
+/// is removed and the error is returned.
+pub fn try_write_atomically(
+    path: &Path,
+    cancellation: &CancellationToken,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> Result<(), WriteError> {
+    let io_error = |e: io::Error| {
+        WriteError::Io(io::Error::new(
+            e.kind(),
+            format!("Could not write {}: {e}", path.display()),
+        ))
+    };
+    let temporary = temporary_path(path);
+    let mut writer = BufWriter::new(File::create(&temporary).map_err(io_error)?);
+    let written = write(&mut writer).and_then(|_| writer.flush());
+    drop(writer);
+    if let Err(e) = written {
+        // Ignore errors here, the write error is more relevant.
+        let _ = fs::remove_file(&temporary);
+        return Err(io_error(e));
+    }
+    if let Err(cancelled) = cancellation.check(&format!("writing {}", path.display())) {
+        fs::remove_file(&temporary).map_err(io_error)?;
+        return Err(WriteError::Cancelled(cancelled));
+    }
+    fs::rename(&temporary, path).map_err(io_error)
+}
+
+/// Column values by name.
+pub type NamedColumns<'a, T> = Vec<(&'a str, Vec<T>)>;
+
+/// Splits externally provided column values into fixed and witness columns of `pil`.
+/// Elements of arrays are named `name[index]`.
+pub fn split_external_values<'a, T: FieldElement>(
+    pil: &Analyzed<T>,
+    external_values: NamedColumns<'a, T>,
+) -> (NamedColumns<'a, T>, NamedColumns<'a, T>) {
+    let fixed_names = FixedPolySet::get_polys(pil)
         .iter()
         .flat_map(|(poly, _)| poly.array_elements())
         .map(|(name, _id)| name)
-        .collect();
+        .collect::<HashSet<_>>();
+    external_values
+        .into_iter()
+        .partition(|(name, _)| fixed_names.contains(*name))
+}
 
-    read_polys_file(
-        &mut BufReader::new(File::open(dir.join(P::FILE_NAME)).unwrap()),
-        &column_names,
-    )
+/// Checks externally provided values of fixed columns against the `evaluated` ones
+/// and reports the first mismatching row. Columns without a definition (and thus
+/// without evaluated values) cannot be checked and are skipped.
+pub fn check_external_fixed_values<T: FieldElement>(
+    evaluated: &[(String, Vec<T>)],
+    external_values: &[(&str, Vec<T>)],
+) -> Result<(), String> {
+    for (name, values) in external_values {
+        let Some((_, evaluated)) = evaluated.iter().find(|(n, _)| n == name) else {
+            log::warn!("Fixed column {name} has no definition, its external values are ignored.");
+            continue;
+        };
+        if values.len() != evaluated.len() {
+            return Err(format!(
+                "Fixed column {name} has {} external values, but the degree is {}.",
+                values.len(),
+                evaluated.len()
+            ));
+        }
+        if let Some(row) = (0..values.len()).find(|&i| values[i] != evaluated[i]) {
+            return Err(format!(
+                "External value of fixed column {name} in row {row} is {}, but it evaluates to {}.",
+                values[row], evaluated[row]
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn atomic_write() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let path = dir.join("proof.bin");
+        let cancellation = CancellationToken::new();
+
+        write_atomically(&path, &cancellation, |w| w.write_all(b"proof").unwrap()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"proof");
+        assert!(!temporary_path(&path).exists());
+
+        let path = dir.join("commits.bin");
+        let result = write_atomically(&path, &cancellation, |w| {
+            w.write_all(b"partial").unwrap();
+            cancellation.cancel();
+        });
+        assert!(matches!(result, Err(Cancelled { .. })));
+        assert!(!path.exists());
+        assert!(!temporary_path(&path).exists());
+
+        let path = dir.join("columns.csv");
+        let result = try_write_atomically(&path, &cancellation, |w| {
+            w.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        });
+        let Err(WriteError::Io(e)) = result else {
+            panic!("expected an I/O error");
+        };
+        assert!(e.to_string().contains("columns.csv: disk full"), "{e}");
+        assert!(!path.exists());
+        assert!(!temporary_path(&path).exists());
+    }
+
+    #[test]
+    fn external_fixed_values() {
+        use number::GoldilocksField;
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            r#"
+namespace N(4);
+    pol constant STEP(i) { i * 2 };
+    pol constant y[2];
+    pol commit x;
+    x = STEP;
+"#,
+        );
+        let evaluated = vec![(
+            "N.STEP".to_string(),
+            vec![0.into(), 2.into(), 4.into(), 6.into()],
+        )];
+        let column = |values: [u64; 4]| values.into_iter().map(GoldilocksField::from).collect();
+        let external = vec![
+            ("N.x", column([0, 2, 4, 6])),
+            ("N.STEP", column([0, 2, 4, 6])),
+            ("N.y[1]", column([1, 1, 1, 1])),
+        ];
+        let (external_fixed, external_witness) = split_external_values(&pil, external);
+        assert_eq!(
+            external_fixed.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            ["N.STEP", "N.y[1]"]
+        );
+        assert_eq!(external_witness[0].0, "N.x");
+        assert_eq!(
+            check_external_fixed_values(&evaluated, &external_fixed),
+            Ok(())
+        );
+
+        let wrong = vec![("N.STEP", column([0, 2, 5, 6]))];
+        assert_eq!(
+            check_external_fixed_values(&evaluated, &wrong),
+            Err(
+                "External value of fixed column N.STEP in row 2 is 5, but it evaluates to 4."
+                    .to_string()
+            )
+        );
+    }
 }
@@ -1,5 +1,5 @@
 use backend::BackendType;
-use number::write_polys_file;
+use number::write_raw_polys_file;
 use number::FieldElement;
 use std::{
     fs,
@@ -8,25 +8,48 @@ use std::{
     process::Command,
 };
 
-use crate::compile_asm_string;
+use executor::witgen::QueryCallback;
+
+use crate::{
+    compile_asm_string_with_callback, inputs_to_query_callback, CancellationToken,
+    UnconstrainedWitnesses, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+};
 
 pub fn verify_asm_string<T: FieldElement>(
     file_name: &str,
     contents: &str,
     inputs: Vec<T>,
     external_witness_values: Vec<(&str, Vec<T>)>,
+) {
+    verify_asm_string_with_callback(
+        file_name,
+        contents,
+        inputs_to_query_callback(inputs),
+        external_witness_values,
+    )
+}
+
+pub fn verify_asm_string_with_callback<T: FieldElement, Q: QueryCallback<T>>(
+    file_name: &str,
+    contents: &str,
+    query_callback: Q,
+    external_witness_values: Vec<(&str, Vec<T>)>,
 ) {
     let temp_dir = mktemp::Temp::new_dir().unwrap();
-    let (_, result) = compile_asm_string(
+    let (_, result) = compile_asm_string_with_callback(
         file_name,
         contents,
-        inputs,
+        query_callback,
         None,
         &temp_dir,
         true,
         Some(BackendType::PilStarkCli),
         external_witness_values,
         None,
+        None,
+        &UnconstrainedWitnesses::default(),
+        DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        &CancellationToken::default(),
     )
     .unwrap();
 
@@ -38,17 +61,19 @@ pub fn verify_asm_string<T: FieldElement>(
     verify(&temp_dir);
 }
 
+/// Writes the fixed columns without a header, as expected by pilcom.
 pub fn write_constants_to_fs<T: FieldElement>(constants: &[(String, Vec<T>)], output_dir: &Path) {
     let to_write = output_dir.join("constants.bin");
-    write_polys_file(
+    write_raw_polys_file(
         &mut BufWriter::new(&mut fs::File::create(to_write).unwrap()),
         constants,
     );
 }
 
+/// Writes the witness columns without a header, as expected by pilcom.
 pub fn write_commits_to_fs<T: FieldElement>(commits: &[(String, Vec<T>)], output_dir: &Path) {
     let to_write = output_dir.join("commits.bin");
-    write_polys_file(
+    write_raw_polys_file(
         &mut BufWriter::new(&mut fs::File::create(to_write).unwrap()),
         commits,
     );
@@ -64,19 +89,14 @@ pub fn verify(temp_dir: &Path) {
     let pilcom = std::env::var("PILCOM")
         .expect("Please set the PILCOM environment variable to the path to the pilcom repository.");
 
-    let constants_file = format!("{}/constants.bin", temp_dir.to_str().unwrap());
-    let commits_file = format!("{}/commits.bin", temp_dir.to_str().unwrap());
-
     let verifier_output = Command::new("node")
-        .args([
-            "--max-old-space-size=8000".to_string(), // 8GB of memory
-            format!("{pilcom}/src/main_pilverifier.js"),
-            commits_file,
-            "-j".to_string(),
-            format!("{}/constraints.json", temp_dir.to_str().unwrap()),
-            "-c".to_string(),
-            constants_file,
-        ])
+        .arg("--max-old-space-size=8000") // 8GB of memory
+        .arg(Path::new(&pilcom).join("src").join("main_pilverifier.js"))
+        .arg(temp_dir.join("commits.bin"))
+        .arg("-j")
+        .arg(temp_dir.join("constraints.json"))
+        .arg("-c")
+        .arg(temp_dir.join("constants.bin"))
         .output()
         .expect("failed to run pil verifier");
     if !verifier_output.status.success() {
@@ -0,0 +1,183 @@
+//! Extension point to post-process the witness after witness generation and before
+//! it is handed to a backend, e.g. to insert blinding rows or backend-managed columns.
+
+use std::fmt;
+
+use ast::analyzed::Analyzed;
+use number::FieldElement;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// A change made to the witness by a [WitnessTransformer].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessChange {
+    /// A new column was appended to the witness.
+    ColumnAdded(String),
+    /// The given number of rows of an existing column were changed.
+    RowsModified { column: String, rows: usize },
+}
+
+impl fmt::Display for WitnessChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessChange::ColumnAdded(column) => write!(f, "added column {column}"),
+            WitnessChange::RowsModified { column, rows } => {
+                write!(f, "modified {rows} rows of {column}")
+            }
+        }
+    }
+}
+
+/// Post-processes the witness before proving.
+pub trait WitnessTransformer<T: FieldElement> {
+    /// A short name, used in the [TransformationLog].
+    fn name(&self) -> String;
+
+    /// Transforms the witness (given in source order) in place and
+    /// returns the list of changes that were made.
+    fn transform(
+        &self,
+        pil: &Analyzed<T>,
+        witness: &mut Vec<(String, Vec<T>)>,
+    ) -> Vec<WitnessChange>;
+}
+
+/// The list of changes made by all transformers, in the order they were applied.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct TransformationLog {
+    pub entries: Vec<(String, WitnessChange)>,
+}
+
+impl fmt::Display for TransformationLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (transformer, change) in &self.entries {
+            writeln!(f, "{transformer}: {change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the transformers to the witness in the given order.
+/// Panics if a transformer leaves columns of different lengths behind.
+pub fn apply_witness_transformers<T: FieldElement>(
+    pil: &Analyzed<T>,
+    witness: &mut Vec<(String, Vec<T>)>,
+    transformers: &[Box<dyn WitnessTransformer<T>>],
+) -> TransformationLog {
+    let mut log = TransformationLog::default();
+    for transformer in transformers {
+        let name = transformer.name();
+        log::info!("Applying witness transformer {name}...");
+        let changes = transformer.transform(pil, witness);
+        if let Some((first, first_values)) = witness.first() {
+            let len = first_values.len();
+            for (column, values) in witness.iter() {
+                assert_eq!(
+                    values.len(),
+                    len,
+                    "Witness transformer {name} changed the length of column {column} (expected the same length as {first})."
+                );
+            }
+        }
+        log.entries
+            .extend(changes.into_iter().map(|change| (name.clone(), change)));
+    }
+    log
+}
+
+/// Overwrites the last `rows` rows of the given columns with random values
+/// (below 2^64), generated from a seeded random number generator.
+/// The constraints have to allow arbitrary values in these rows.
+pub struct RowBlinding {
+    pub columns: Vec<String>,
+    pub rows: usize,
+    pub seed: u64,
+}
+
+impl<T: FieldElement> WitnessTransformer<T> for RowBlinding {
+    fn name(&self) -> String {
+        "row_blinding".to_string()
+    }
+
+    fn transform(
+        &self,
+        _pil: &Analyzed<T>,
+        witness: &mut Vec<(String, Vec<T>)>,
+    ) -> Vec<WitnessChange> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        witness
+            .iter_mut()
+            .filter(|(name, _)| self.columns.contains(name))
+            .map(|(name, values)| {
+                let rows = self.rows.min(values.len());
+                let start = values.len() - rows;
+                for value in &mut values[start..] {
+                    *value = T::from(rng.gen::<u64>());
+                }
+                WitnessChange::RowsModified {
+                    column: name.clone(),
+                    rows,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+
+    use super::*;
+
+    fn witness() -> Vec<(String, Vec<GoldilocksField>)> {
+        vec![
+            (
+                "main.x".to_string(),
+                (0..8).map(GoldilocksField::from).collect(),
+            ),
+            (
+                "main.y".to_string(),
+                (0..8).map(GoldilocksField::from).collect(),
+            ),
+        ]
+    }
+
+    fn blinding(seed: u64) -> Vec<Box<dyn WitnessTransformer<GoldilocksField>>> {
+        vec![Box::new(RowBlinding {
+            columns: vec!["main.y".to_string()],
+            rows: 3,
+            seed,
+        })]
+    }
+
+    #[test]
+    fn row_blinding() {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace main(8); col witness x; col witness y;",
+        );
+        let mut blinded = witness();
+        let log = apply_witness_transformers(&pil, &mut blinded, &blinding(7));
+        assert_eq!(
+            log.entries,
+            vec![(
+                "row_blinding".to_string(),
+                WitnessChange::RowsModified {
+                    column: "main.y".to_string(),
+                    rows: 3
+                }
+            )]
+        );
+        assert_eq!(log.to_string(), "row_blinding: modified 3 rows of main.y\n");
+        assert_eq!(blinded[0], witness()[0]);
+        assert_eq!(blinded[1].1[..5], witness()[1].1[..5]);
+        assert_ne!(blinded[1].1[5..], witness()[1].1[5..]);
+
+        // The same seed produces the same witness.
+        let mut blinded_again = witness();
+        apply_witness_transformers(&pil, &mut blinded_again, &blinding(7));
+        assert_eq!(blinded, blinded_again);
+
+        let mut blinded_other_seed = witness();
+        apply_witness_transformers(&pil, &mut blinded_other_seed, &blinding(8));
+        assert_ne!(blinded, blinded_other_seed);
+    }
+}
@@ -1,5 +1,6 @@
-use compiler::verify_asm_string;
+use compiler::{verify_asm_string, verify_asm_string_with_callback};
 use number::{Bn254Field, FieldElement, GoldilocksField};
+use std::collections::HashMap;
 use std::fs;
 use test_log::test;
 
@@ -35,6 +36,8 @@ fn gen_estark_proof(file_name: &str, inputs: Vec<GoldilocksField>) {
         Some(backend::BackendType::EStark),
         vec![],
         None,
+        None,
+        &compiler::CancellationToken::default(),
     )
     .unwrap();
 }
@@ -53,6 +56,8 @@ fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
         Some(backend::BackendType::Halo2),
         vec![],
         None,
+        None,
+        &compiler::CancellationToken::default(),
     )
     .unwrap();
 }
@@ -73,6 +78,103 @@ fn simple_sum_asm() {
     gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn input_channels() {
+    let file_name = format!(
+        "{}/../test_data/asm/input_channels.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let inputs = HashMap::from([
+        (0.into(), slice_to_vec::<GoldilocksField>(&[16, 4])),
+        (1.into(), slice_to_vec(&[1, 2, 8, 5])),
+    ]);
+    verify_asm_string_with_callback(
+        &file_name,
+        &contents,
+        compiler::channel_inputs_to_query_callback(inputs),
+        vec![],
+    );
+}
+
+#[test]
+fn witgen_failure_source_location() {
+    let file_name = format!(
+        "{}/../test_data/asm/failing_instruction.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let analyzed =
+        compiler::compile_asm_string_to_analyzed_pil::<GoldilocksField>(&file_name, &contents)
+            .unwrap();
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let error = executor::witgen::WitnessGenerator::new(
+        &analyzed,
+        &constants,
+        compiler::inputs_to_query_callback(vec![]),
+    )
+    .try_generate()
+    .unwrap_err();
+    let executor::witgen::GenerationError::Failed(error) = error else {
+        panic!("Expected a failure, got {error}");
+    };
+    assert_eq!(error.kind, executor::witgen::WitgenErrorKind::Unsatisfiable);
+    // The instruction `fail` is declared on line 9.
+    let sources = error
+        .context
+        .identities
+        .iter()
+        .map(|identity| identity.source.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(sources, ["failing_instruction.asm:9"]);
+    assert!(
+        error.to_string().contains("at failing_instruction.asm:9"),
+        "{error}"
+    );
+}
+
+#[test]
+fn input_out_of_range() {
+    let file_name = format!(
+        "{}/../test_data/asm/input_channels.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let pil =
+        compiler::compile_asm_string_to_pil::<GoldilocksField>(&file_name, &contents).unwrap();
+    let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(&format!("{pil}"));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    // Four inputs are announced in channel 0, but only three are given in channel 1.
+    let inputs = HashMap::from([
+        (0.into(), slice_to_vec::<GoldilocksField>(&[16, 4])),
+        (1.into(), slice_to_vec(&[1, 2, 8])),
+    ]);
+    let error = executor::witgen::WitnessGenerator::new(
+        &analyzed,
+        &constants,
+        compiler::channel_inputs_to_query_callback(inputs),
+    )
+    .try_generate()
+    .unwrap_err();
+    let executor::witgen::GenerationError::Failed(error) = error else {
+        panic!("Expected a failure, got {error}");
+    };
+    assert_eq!(error.kind, executor::witgen::WitgenErrorKind::QueryFailed);
+    assert_eq!(error.failing_identities.len(), 1);
+    let message = &error.failing_identities[0];
+    assert!(
+        message.contains(&format!(
+            "Query (\"input\", 1, 3) for column main.X_free_value in row {} failed",
+            error.row
+        )),
+        "{message}"
+    );
+    assert!(
+        message.contains("Input index 3 requested in channel 1, but only 3 inputs were provided"),
+        "{message}"
+    );
+}
+
 #[test]
 fn secondary_block_machine_add2() {
     let f = "secondary_block_machine_add2.asm";
@@ -155,6 +257,63 @@ fn empty_vm() {
     gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn vm_to_block_permutation() {
+    let f = "vm_to_block_permutation.asm";
+    let i = [];
+    verify_asm::<GoldilocksField>(f, slice_to_vec(&i));
+    gen_halo2_proof(f, slice_to_vec(&i));
+}
+
+#[test]
+fn permutation_rejects_extra_rows() {
+    let file_name = format!(
+        "{}/../test_data/asm/vm_to_block_permutation.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    // Generates the witness and adds a call to `add(5, 6)` in the last row of `arith`,
+    // which is not called from the main machine.
+    let check_with_extra_row = |contents: &str| {
+        let pil =
+            compiler::compile_asm_string_to_pil::<GoldilocksField>(&file_name, contents).unwrap();
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(&format!("{pil}"));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let mut witness = executor::witgen::WitnessGenerator::new(
+            &analyzed,
+            &constants,
+            compiler::inputs_to_query_callback(vec![]),
+        )
+        .generate();
+        assert!(
+            executor::witness_checker::check_witness(&analyzed, &constants, &witness)
+                .unwrap()
+                .is_empty()
+        );
+        for (name, values) in &mut witness {
+            let value = match name.as_str() {
+                "main_arith.operation_id" | "main_arith._permutation_selector_1" => 0,
+                "main_arith.x" => 5,
+                "main_arith.y" => 6,
+                "main_arith.z" => 11,
+                "main_arith._permutation_selector_0" => 1,
+                _ => continue,
+            };
+            *values.last_mut().unwrap() = value.into();
+        }
+        executor::witness_checker::check_witness(&analyzed, &constants, &witness).unwrap()
+    };
+
+    let violations = check_with_extra_row(&contents);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].row, 31);
+    assert_eq!(violations[0].right, slice_to_vec(&[0, 5, 6, 11]));
+
+    // With lookups, the extra row is not noticed.
+    let lookups = contents.replace("~ arith.", "= arith.");
+    assert!(check_with_extra_row(&lookups).is_empty());
+}
+
 #[test]
 fn vm_to_block_unique_interface() {
     let f = "vm_to_block_unique_interface.asm";
@@ -360,3 +519,277 @@ fn hello_world_asm_fail() {
     let i = [1];
     verify_asm::<GoldilocksField>(f, slice_to_vec(&i));
 }
+
+#[test]
+fn provenance_headers() {
+    use parser_util::provenance::Provenance;
+    use std::path::Path;
+
+    let file_name = format!(
+        "{}/../test_data/asm/simple_sum.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let read_provenance = |path: &Path| Provenance::parse(&fs::read_to_string(path).unwrap());
+
+    // Artifacts generated from a file record it (and the files it was generated from).
+    let output_dir = mktemp::Temp::new_dir().unwrap();
+    compiler::compile_asm_string::<GoldilocksField>(
+        &file_name,
+        &contents,
+        vec![],
+        None,
+        &output_dir,
+        true,
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::CancellationToken::default(),
+    )
+    .unwrap();
+    let pil = read_provenance(&output_dir.join("simple_sum.pil"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(pil.inputs.len(), 1);
+    assert!(pil.inputs[0].0.ends_with("simple_sum.asm"));
+    let optimized = read_provenance(&output_dir.join("simple_sum_opt.pil"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(optimized.inputs.len(), 2);
+    assert!(optimized.inputs[0].0.ends_with("simple_sum.pil"));
+    assert_eq!(optimized.inputs[1], pil.inputs[0]);
+
+    // Artifacts generated from strings have no header.
+    let output_dir = mktemp::Temp::new_dir().unwrap();
+    compiler::compile_asm_string::<GoldilocksField>(
+        "from_string.asm",
+        &contents,
+        vec![],
+        None,
+        &output_dir,
+        true,
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::CancellationToken::default(),
+    )
+    .unwrap();
+    assert_eq!(read_provenance(&output_dir.join("from_string.pil")), None);
+    assert_eq!(
+        read_provenance(&output_dir.join("from_string_opt.pil")),
+        None
+    );
+}
+
+#[test]
+fn merge_assignment_registers() {
+    let f = "merge_assignment_registers.asm";
+    let file_name = format!("{}/../test_data/asm/{f}", env!("CARGO_MANIFEST_DIR"));
+    let contents = fs::read_to_string(&file_name).unwrap();
+
+    let unmerged =
+        compiler::compile_asm_string_to_pil::<GoldilocksField>(&file_name, &contents).unwrap();
+    let (merged, report) =
+        compiler::compile_asm_string_to_pil_merging_registers::<GoldilocksField>(
+            &file_name, &contents,
+        )
+        .unwrap();
+    assert_eq!(
+        report
+            .machines
+            .values()
+            .map(|m| m.merged.clone())
+            .collect::<Vec<_>>(),
+        vec![[("Z".to_string(), "X".to_string())].into()]
+    );
+
+    let run = |pil: &ast::parsed::PILFile<GoldilocksField>| {
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(&format!("{pil}"));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let witness = executor::witgen::WitnessGenerator::new(
+            &analyzed,
+            &constants,
+            compiler::inputs_to_query_callback(vec![]),
+        )
+        .generate();
+        (constants.len(), witness)
+    };
+    let (unmerged_fixed, unmerged_witness) = run(&unmerged);
+    let (merged_fixed, merged_witness) = run(&merged);
+
+    assert!(merged_witness.len() < unmerged_witness.len());
+    assert_eq!(
+        unmerged_fixed + unmerged_witness.len() - merged_fixed - merged_witness.len(),
+        report.saved_columns()
+    );
+    let unmerged_witness: HashMap<_, _> = unmerged_witness.into_iter().collect();
+    let mentions = |name: &str, reg: &str| name.split(['.', '_']).any(|part| part == reg);
+    for (name, values) in &merged_witness {
+        if name == "main.X" {
+            // X now also holds the values Z held, in rows where X is unused.
+            let sum = unmerged_witness["main.X"]
+                .iter()
+                .zip(&unmerged_witness["main.Z"])
+                .map(|(x, z)| *x + *z)
+                .collect::<Vec<_>>();
+            assert_eq!(&sum, values);
+        } else if !mentions(name, "X") {
+            assert_eq!(&unmerged_witness[name], values, "{name}");
+        }
+    }
+}
+
+#[test]
+fn witgen_profile() {
+    use executor::witgen::profiling::{WitgenProfiler, WitgenReport, WITGEN_REPORT_FILE_NAME};
+
+    let file_name = format!(
+        "{}/../test_data/asm/vm_to_block_to_block.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let pil =
+        compiler::compile_asm_string_to_pil::<GoldilocksField>(&file_name, &contents).unwrap();
+    let analyzed = compiler::optimize_pil(pil_analyzer::analyze_string::<GoldilocksField>(
+        &format!("{pil}"),
+    ));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let report_file = temp_dir.join(WITGEN_REPORT_FILE_NAME);
+    executor::witgen::WitnessGenerator::new(
+        &analyzed,
+        &constants,
+        compiler::inputs_to_query_callback(vec![]),
+    )
+    .with_witgen_profiling(Some(WitgenProfiler::create(&report_file).unwrap()))
+    .generate();
+
+    let report = WitgenReport::read(&report_file).unwrap();
+    let mut reported = report
+        .identities
+        .iter()
+        .map(|p| (p.kind.clone(), p.id))
+        .collect::<Vec<_>>();
+    reported.sort();
+    let mut expected = analyzed
+        .identities
+        .iter()
+        .map(|identity| (format!("{:?}", identity.kind).to_lowercase(), identity.id))
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(reported, expected);
+    assert!(report
+        .identities
+        .iter()
+        .any(|p| p.invocations > 0 && p.successes > 0));
+    assert!(report
+        .identities
+        .iter()
+        .all(|p| p.successes <= p.invocations && p.source.contains(':')));
+}
+
+#[test]
+fn machine_info() {
+    use ast::asm_analysis::{
+        CallableInfo, InstructionInfo, LinkInfo, RegisterInfo, RegisterTy, SourcePosition,
+        SubmachineInfo,
+    };
+
+    let f = "vm_to_block_to_block.asm";
+    let file_name = format!("{}/../test_data/asm/{f}", env!("CARGO_MANIFEST_DIR"));
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let position = |declaration: &str| {
+        Some(SourcePosition {
+            file: f.to_string(),
+            offset: contents.find(declaration).unwrap(),
+        })
+    };
+
+    let machines = compiler::asm_machine_info::<GoldilocksField>(&file_name, &contents).unwrap();
+    // The machines of the standard library are included.
+    assert!(machines.iter().any(|m| m.name == "std::binary::Binary"));
+    let machine = |name: &str| machines.iter().find(|m| m.name == name).unwrap();
+
+    let assert1 = machine("Assert1");
+    assert_eq!(assert1.position, position("machine Assert1"));
+    assert_eq!(assert1.degree.as_deref(), Some("8"));
+    assert!(assert1.registers.is_empty());
+    assert_eq!(
+        assert1.callables,
+        vec![CallableInfo {
+            name: "assert1".to_string(),
+            operation: true,
+            signature: "x ->".to_string(),
+            position: position("operation assert1"),
+        }]
+    );
+    assert_eq!(
+        assert1.submachines,
+        vec![SubmachineInfo {
+            name: "inc".to_string(),
+            ty: "Inc".to_string(),
+            position: position("Inc inc;"),
+        }]
+    );
+    assert_eq!(
+        assert1.links,
+        vec![LinkInfo {
+            flag: "1".to_string(),
+            signature: "x -> y".to_string(),
+            to: "inc.inc".to_string(),
+            permutation: false,
+            position: position("link 1"),
+        }]
+    );
+
+    let main = machine("Main");
+    assert_eq!(
+        main.registers,
+        vec![
+            RegisterInfo {
+                name: "pc".to_string(),
+                ty: RegisterTy::Pc,
+                position: position("reg pc"),
+            },
+            RegisterInfo {
+                name: "X".to_string(),
+                ty: RegisterTy::Assignment,
+                position: position("reg X"),
+            },
+            RegisterInfo {
+                name: "A".to_string(),
+                ty: RegisterTy::Write,
+                position: position("reg A"),
+            },
+        ]
+    );
+    assert_eq!(
+        main.instructions,
+        vec![
+            InstructionInfo {
+                name: "assert1".to_string(),
+                signature: "X ->".to_string(),
+                calls: Some("assert1.assert1".to_string()),
+                position: position("instr assert1"),
+            },
+            InstructionInfo {
+                name: "loop".to_string(),
+                signature: "".to_string(),
+                calls: None,
+                position: position("instr loop"),
+            },
+        ]
+    );
+    assert_eq!(
+        main.callables,
+        vec![CallableInfo {
+            name: "main".to_string(),
+            operation: false,
+            signature: "".to_string(),
+            position: position("function main"),
+        }]
+    );
+}
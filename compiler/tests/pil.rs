@@ -31,7 +31,13 @@ pub fn verify_pil_with_external_witness(
         Some(BackendType::PilStarkCli),
         external_witness_values,
         None,
-    );
+        None,
+        &compiler::Limits::default(),
+        &compiler::UnconstrainedWitnesses::default(),
+        compiler::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        &compiler::CancellationToken::default(),
+    )
+    .unwrap();
 
     compiler::write_constants_to_fs(&result.constants, &temp_dir);
     compiler::write_commits_to_fs(&result.witness.unwrap(), &temp_dir);
@@ -53,6 +59,8 @@ fn gen_estark_proof(file_name: &str, inputs: Vec<GoldilocksField>) {
         Some(BackendType::EStark),
         vec![],
         None,
+        None,
+        &compiler::CancellationToken::default(),
     )
     .unwrap();
 }
@@ -71,6 +79,8 @@ fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
         Some(BackendType::Halo2),
         vec![],
         None,
+        None,
+        &compiler::CancellationToken::default(),
     )
     .unwrap();
 }
@@ -102,6 +112,185 @@ fn fib_arrays() {
     gen_estark_proof(f, Default::default());
 }
 
+fn hint_query_callback(query: &str) -> Result<Option<GoldilocksField>, String> {
+    compiler::inputs_to_query_callback(vec![])(query)
+}
+
+#[test]
+fn witness_array_query() {
+    let f = "witness_array_query.pil";
+    let analyzed = pil_analyzer::analyze::<GoldilocksField>(Path::new(&format!(
+        "{}/../test_data/pil/{f}",
+        env!("CARGO_MANIFEST_DIR")
+    )));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let witness =
+        executor::witgen::WitnessGenerator::new(&analyzed, &constants, hint_query_callback)
+            .generate();
+    let column = |factor: u64| (0..8).map(|i| GoldilocksField::from(i * factor)).collect();
+    assert_eq!(
+        witness,
+        vec![
+            ("Main.y[0]".to_string(), column(1)),
+            ("Main.y[1]".to_string(), column(2)),
+            ("Main.y[2]".to_string(), column(3)),
+            ("Main.s".to_string(), column(2)),
+        ]
+    );
+
+    verify_pil(f, Some(hint_query_callback));
+}
+
+#[test]
+fn assert_range() {
+    let input_file = Path::new(&format!(
+        "{}/../test_data/pil/assert_range.pil",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .canonicalize()
+    .unwrap();
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let Err(errors) = compiler::compile_pil::<GoldilocksField, _>(
+        &input_file,
+        &temp_dir,
+        |_| -> _ { unreachable!() },
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::Limits::default(),
+        &compiler::UnconstrainedWitnesses::default(),
+        compiler::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        &compiler::CancellationToken::default(),
+    ) else {
+        panic!("The range assertion on Main.B should fail.");
+    };
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("Column Main.B is asserted to fit into 8 bits"));
+}
+
+#[test]
+fn column_roles() {
+    let input_file = Path::new(&format!(
+        "{}/../test_data/pil/column_roles.pil",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .canonicalize()
+    .unwrap();
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let Err(errors) = compiler::compile_pil::<GoldilocksField, _>(
+        &input_file,
+        &temp_dir,
+        |_| -> _ { unreachable!() },
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::Limits::default(),
+        &compiler::UnconstrainedWitnesses::default(),
+        compiler::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        &compiler::CancellationToken::default(),
+    ) else {
+        panic!("The role of Main.first_step should be rejected.");
+    };
+    assert_eq!(
+        errors,
+        vec!["Fixed column Main.first_step has the role first_row, so it has to be one in row 0 and zero in all other rows, but it is 1 in row 1."]
+    );
+}
+
+#[test]
+fn analyzed_artifact() {
+    let input_file = Path::new(&format!(
+        "{}/../test_data/pil/fibonacci.pil",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .canonicalize()
+    .unwrap();
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let result = compiler::compile_pil_or_asm::<GoldilocksField>(
+        input_file.to_str().unwrap(),
+        vec![],
+        &temp_dir,
+        true,
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::CancellationToken::default(),
+    )
+    .unwrap()
+    .unwrap();
+
+    let artifact = temp_dir.join(ast::analyzed::ANALYZED_FILE_NAME);
+    let analyzed = compiler::read_analyzed_pil::<GoldilocksField>(&artifact).unwrap();
+    let optimized = compiler::optimize_pil(compiler::analyze_pil::<GoldilocksField>(&input_file));
+    assert_eq!(analyzed.to_string(), optimized.to_string());
+
+    // Compiling the artifact results in the same fixed columns.
+    let output_dir = mktemp::Temp::new_dir().unwrap();
+    let from_artifact = compiler::compile_pil_or_asm::<GoldilocksField>(
+        artifact.to_str().unwrap(),
+        vec![],
+        &output_dir,
+        true,
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::CancellationToken::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(from_artifact.constants, result.constants);
+}
+
+#[test]
+fn unconstrained_witness() {
+    let input_file = Path::new(&format!(
+        "{}/../test_data/pil/unconstrained_witness.pil",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .canonicalize()
+    .unwrap();
+    let warning = "Witness column Main.free is not referenced in any identity.".to_string();
+
+    let analyzed = compiler::analyze_pil::<GoldilocksField>(&input_file);
+    assert_eq!(
+        compiler::check_unconstrained_witnesses(&analyzed, &Default::default()),
+        Ok(vec![warning.clone()])
+    );
+    let allowed = compiler::UnconstrainedWitnesses {
+        allowed: vec!["Main.free".to_string()],
+        deny: true,
+    };
+    assert_eq!(
+        compiler::check_unconstrained_witnesses(&analyzed, &allowed),
+        Ok(vec![])
+    );
+
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let Err(errors) = compiler::compile_pil::<GoldilocksField, _>(
+        &input_file,
+        &temp_dir,
+        |_| -> _ { unreachable!() },
+        None,
+        vec![],
+        None,
+        None,
+        &compiler::Limits::default(),
+        &compiler::UnconstrainedWitnesses {
+            deny: true,
+            ..Default::default()
+        },
+        compiler::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        &compiler::CancellationToken::default(),
+    ) else {
+        panic!("Main.free should be rejected.");
+    };
+    assert_eq!(errors, vec![warning]);
+}
+
 #[test]
 #[should_panic = "Witness generation failed."]
 fn test_external_witgen_fails_if_none_provided() {
@@ -203,6 +392,12 @@ fn test_pair_lookup() {
     // starky would take too long for this in debug mode
 }
 
+#[test]
+fn test_two_dimensional_table() {
+    let f = "two_dimensional_table.pil";
+    verify_pil(f, None);
+}
+
 #[test]
 fn test_block_lookup_or() {
     let f = "block_lookup_or.pil";
@@ -233,6 +428,13 @@ fn test_single_line_blocks() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn test_instances() {
+    let f = "instances.pil";
+    verify_pil(f, None);
+    gen_estark_proof(f, Default::default());
+}
+
 #[test]
 fn test_two_block_machine_functions() {
     let f = "two_block_machine_functions.pil";
@@ -240,6 +442,13 @@ fn test_two_block_machine_functions() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn test_variable_size_blocks() {
+    let f = "variable_size_blocks.pil";
+    verify_pil(f, None);
+    gen_estark_proof(f, Default::default());
+}
+
 #[test]
 fn test_fixed_columns() {
     let f = "fixed_columns.pil";
@@ -270,6 +479,191 @@ fn arith_improved() {
     );
 }
 
+#[test]
+fn record_and_replay_queries() {
+    use executor::witgen::query_recording::{replay_query_callback, QueryRecorder};
+    use executor::witgen::WitnessGenerator;
+
+    let analyzed = pil_analyzer::analyze::<GoldilocksField>(Path::new(&format!(
+        "{}/../test_data/pil/witness_lookup.pil",
+        env!("CARGO_MANIFEST_DIR")
+    )));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let recording = temp_dir.join("queries.jsonl");
+
+    let inputs = (0..16u64).map(|i| GoldilocksField::from(i % 8)).collect();
+    let recorded = WitnessGenerator::new(
+        &analyzed,
+        &constants,
+        compiler::inputs_to_query_callback(inputs),
+    )
+    .with_query_recorder(Some(QueryRecorder::create(&recording).unwrap()))
+    .generate();
+
+    let contents = std::fs::read_to_string(&recording).unwrap();
+    assert!(contents
+        .lines()
+        .any(|line| line
+            == r#"{"query":"(\"input\", 3)","answer":"3","row":3,"column":"Quad.input"}"#));
+
+    let replayed = WitnessGenerator::new(
+        &analyzed,
+        &constants,
+        replay_query_callback(&recording).unwrap(),
+    )
+    .generate();
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+fn witgen_memory_budget() {
+    use executor::witgen::memory_budget::{DegradationStep, MemoryBudget};
+    use executor::witgen::{
+        unused_query_callback, GenerationError, WitgenErrorKind, WitnessGenerator,
+    };
+
+    let analyzed = pil_analyzer::analyze::<GoldilocksField>(Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test_data/pil/memory_budget.pil"
+    )));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let generate = |budget: &MemoryBudget| {
+        WitnessGenerator::new(&analyzed, &constants, unused_query_callback())
+            .with_memory_budget(Some(budget.clone()))
+            .try_generate()
+    };
+    let unlimited = MemoryBudget::new(u64::MAX);
+    let witness = generate(&unlimited).unwrap();
+    assert!(unlimited.steps_taken().is_empty());
+
+    // Nearing the budget, witness generation degrades step by step, but the witness
+    // is the same.
+    let budget = MemoryBudget::new(350_000);
+    assert_eq!(generate(&budget).unwrap(), witness);
+    assert_eq!(
+        budget.steps_taken(),
+        [
+            DegradationStep::FinalizeAggressively,
+            DegradationStep::DropIndexes,
+            DegradationStep::SpillToDisk
+        ]
+    );
+
+    // With a tiny budget, it fails at the first memory report.
+    let budget = MemoryBudget::new(100_000);
+    let Err(GenerationError::Failed(error)) = generate(&budget) else {
+        panic!("Expected witness generation to fail");
+    };
+    assert_eq!(error.kind, WitgenErrorKind::MemoryBudgetExceeded);
+    assert_eq!(budget.current_step(), Some(DegradationStep::Abort));
+    assert!(error
+        .to_string()
+        .starts_with("Witness generation failed at row 999 (memory budget exceeded)."));
+}
+
+#[test]
+fn estark_verification_key() {
+    use executor::witgen::{unused_query_callback, WitnessGenerator};
+    use std::fs;
+
+    let file = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test_data/pil/fibonacci.pil"
+    ));
+    let factory = BackendType::EStark.factory::<GoldilocksField>();
+
+    // Prove and export the verification key.
+    let prover_dir = mktemp::Temp::new_dir().unwrap();
+    let pil = compiler::optimize_pil(pil_analyzer::analyze::<GoldilocksField>(file));
+    let fixed = executor::constant_evaluator::generate(&pil);
+    let witness = WitnessGenerator::new(&pil, &fixed, unused_query_callback()).generate();
+    let fixed = fixed
+        .into_iter()
+        .map(|(name, values)| (name.to_string(), values))
+        .collect::<Vec<_>>();
+    let publics = backend::evaluate_publics(&pil, &witness).unwrap();
+    assert_eq!(publics, vec![("out".to_string(), 1597.into())]);
+    let backend = factory.create(pil.degree());
+    let (proof, _) = backend.prove(&pil, &fixed, &witness, &publics, None, None);
+    let (proof, metadata) = proof.unwrap();
+    assert_eq!(metadata.pil_hash, backend::pil_hash(&pil));
+    fs::write(prover_dir.join("proof.bin"), proof).unwrap();
+    let mut vkey = fs::File::create(prover_dir.join("vkey.json")).unwrap();
+    backend
+        .export_verification_key(&pil, &fixed, &mut vkey)
+        .unwrap();
+
+    // Verify from scratch, with only the PIL, the verification key and the proof.
+    let verifier_dir = mktemp::Temp::new_dir().unwrap();
+    for name in ["proof.bin", "vkey.json"] {
+        fs::copy(prover_dir.join(name), verifier_dir.join(name)).unwrap();
+    }
+    let pil = compiler::optimize_pil(pil_analyzer::analyze::<GoldilocksField>(file));
+    let mut vkey = fs::File::open(verifier_dir.join("vkey.json")).unwrap();
+    let verifier = factory.create_from_verification_key(&mut vkey).unwrap();
+    let proof = fs::read(verifier_dir.join("proof.bin")).unwrap();
+    verifier.verify(&pil, &[], &proof, &publics).unwrap();
+
+    // The proof does not verify against different public values.
+    let wrong_publics = vec![("out".to_string(), 1598.into())];
+    assert!(matches!(
+        verifier.verify(&pil, &[], &proof, &wrong_publics),
+        Err(backend::Error::InvalidProof(_))
+    ));
+}
+
+#[test]
+fn estark_mapped_columns() {
+    use compiler::util::{map_poly_set, FixedPolySet, PolySet, WitnessPolySet};
+    use executor::witgen::{unused_query_callback, WitnessGenerator};
+    use number::{column_refs, write_polys_file};
+    use std::fs;
+
+    let file = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test_data/pil/fibonacci.pil"
+    ));
+    let pil = compiler::optimize_pil(pil_analyzer::analyze::<GoldilocksField>(file));
+    let fixed = executor::constant_evaluator::generate(&pil);
+    let witness = WitnessGenerator::new(&pil, &fixed, unused_query_callback()).generate();
+    let fixed = fixed
+        .into_iter()
+        .map(|(name, values)| (name.to_string(), values))
+        .collect::<Vec<_>>();
+    let publics = backend::evaluate_publics(&pil, &witness).unwrap();
+    let backend = BackendType::EStark
+        .factory::<GoldilocksField>()
+        .create(pil.degree());
+    let (in_memory, _) = backend.prove(&pil, &fixed, &witness, &publics, None, None);
+
+    // Proving from the columns mapped from the files yields the same proof.
+    let dir = mktemp::Temp::new_dir().unwrap();
+    write_polys_file(
+        &mut fs::File::create(dir.join(FixedPolySet::FILE_NAME)).unwrap(),
+        &fixed,
+    );
+    write_polys_file(
+        &mut fs::File::create(dir.join(WitnessPolySet::FILE_NAME)).unwrap(),
+        &witness,
+    );
+    let (mapped_fixed, _) = map_poly_set::<FixedPolySet, GoldilocksField>(&pil, &dir).unwrap();
+    let (mapped_witness, _) = map_poly_set::<WitnessPolySet, GoldilocksField>(&pil, &dir).unwrap();
+    assert_eq!(
+        backend::evaluate_publics(&pil, &mapped_witness).unwrap(),
+        publics
+    );
+    let (mapped, _) = backend.prove_columns(
+        &pil,
+        &column_refs(&mapped_fixed),
+        &column_refs(&mapped_witness),
+        &publics,
+        None,
+        None,
+    );
+    assert_eq!(mapped.unwrap().0, in_memory.unwrap().0);
+}
+
 mod book {
     use super::*;
     use test_log::test;
@@ -282,3 +676,446 @@ mod book {
 
     include!(concat!(env!("OUT_DIR"), "/pil_book_tests.rs"));
 }
+
+#[test]
+fn witgen_after_deduplication() {
+    for file_name in [
+        "fibonacci.pil",
+        "constant_in_identity.pil",
+        "single_line_blocks.pil",
+        "two_block_machine_functions.pil",
+    ] {
+        let input_file = format!(
+            "{}/../test_data/pil/{file_name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let witgen = |analyzed: &ast::analyzed::Analyzed<GoldilocksField>| {
+            let constants = executor::constant_evaluator::generate(analyzed);
+            executor::witgen::WitnessGenerator::new(
+                analyzed,
+                &constants,
+                executor::witgen::unused_query_callback(),
+            )
+            .generate()
+        };
+
+        let analyzed = pil_analyzer::analyze::<GoldilocksField>(Path::new(&input_file));
+        let witness = witgen(&analyzed)
+            .into_iter()
+            .collect::<std::collections::HashMap<_, _>>();
+        let (optimized, _) = pilopt::optimize_reporting_duplicates(
+            analyzed,
+            pilopt::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        );
+        // All witness columns that survive the optimization keep their values.
+        for (name, values) in witgen(&optimized) {
+            assert_eq!(witness[&name], values, "{file_name}: {name}");
+        }
+    }
+}
+
+/// Witness generation on the files in `test_data/pil`, checking the generated
+/// values and the reported errors.
+mod witgen {
+    use executor::witgen::{
+        machine_report, unused_query_callback, FailingIdentity, GenerationError, MachineKind,
+        WitgenError, WitgenErrorKind, WitnessGenerator,
+    };
+    use number::{Bn254Field, FieldElement, GoldilocksField};
+    use test_log::test;
+
+    fn read_pil(file_name: &str) -> String {
+        std::fs::read_to_string(format!(
+            "{}/../test_data/pil/{file_name}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    /// Runs witness generation on `src`, which does not query the prover.
+    fn generate<T: FieldElement>(src: &str) -> Result<Vec<(String, Vec<T>)>, GenerationError> {
+        let analyzed = pil_analyzer::analyze_string::<T>(src);
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        WitnessGenerator::new(&analyzed, &constants, unused_query_callback()).try_generate()
+    }
+
+    fn generate_failure(src: &str) -> WitgenError {
+        match generate::<GoldilocksField>(src).unwrap_err() {
+            GenerationError::Failed(error) => error,
+            error => panic!("Expected a failure, got {error}"),
+        }
+    }
+
+    fn column<T: FieldElement>(witness: &[(String, Vec<T>)], name: &str) -> Vec<u64> {
+        let (_, values) = witness.iter().find(|(n, _)| n == name).unwrap();
+        values.iter().map(|v| v.to_degree()).collect()
+    }
+
+    #[test]
+    fn unsatisfiable_row() {
+        let error = generate_failure(&read_pil("unsatisfiable_row.pil"));
+        assert_eq!(error.row, 5);
+        assert_eq!(error.kind, WitgenErrorKind::Unsatisfiable);
+        assert_eq!(error.failing_identities.len(), 1);
+        assert!(error.row_dump.contains("Current row (5)"));
+        assert!(error.row_dump.contains("Fail.x = 1"), "{}", error.row_dump);
+
+        let rows = &error.context.rows;
+        assert_eq!(rows.iter().map(|r| r.row).collect::<Vec<_>>(), [3, 4, 5, 6]);
+        assert_eq!(
+            rows[2].values,
+            [
+                ("Fail.x".to_string(), Some("1".to_string())),
+                ("Fail.y".to_string(), Some("2".to_string()))
+            ]
+        );
+        assert_eq!(
+            rows[3].values,
+            [("Fail.x".to_string(), None), ("Fail.y".to_string(), None)]
+        );
+        assert_eq!(
+            error.context.identities,
+            [FailingIdentity {
+                identity: "(Fail.FIVE * Fail.x) = (Fail.FIVE * 2);".to_string(),
+                source: "input:8".to_string(),
+                columns: vec!["Fail.x".to_string()],
+            }]
+        );
+    }
+
+    /// Generates a witness where `x` is underconstrained on row `under` and `y` is
+    /// unsatisfiable on row `unsat`.
+    fn planted_failure(degree: u64, under: u64, unsat: u64) -> WitgenError {
+        generate_failure(
+            &read_pil("planted_failure.pil")
+                .replace("Planted(8)", &format!("Planted({degree})"))
+                .replace("i == 3", &format!("i == {under}"))
+                .replace("i == 6", &format!("i == {unsat}")),
+        )
+    }
+
+    #[test]
+    fn underconstrained_row() {
+        // The identities are checked with unknown values set to zero after the rows are
+        // computed, but the first failing row is still the one that is reported.
+        for (degree, under, unsat) in [(8, 3, 6), (8, 3, 8), (16384, 5000, 12000)] {
+            let error = planted_failure(degree, under, unsat);
+            assert_eq!(error.row, under);
+            assert_eq!(error.kind, WitgenErrorKind::Underconstrained);
+            assert_eq!(
+                error.failing_identities,
+                ["(Planted.UNDER * ((Planted.x * Planted.x) - 1)) = 0;:\n    Linear constraint is not satisfiable: -1 != 0".to_string()]
+            );
+            assert_eq!(error.context.identities[0].source, "input:7");
+        }
+        let error = planted_failure(8, 6, 3);
+        assert_eq!(error.row, 3);
+        assert_eq!(error.kind, WitgenErrorKind::Unsatisfiable);
+    }
+
+    #[test]
+    fn variable_size_blocks() {
+        let witness = generate::<GoldilocksField>(&read_pil("variable_size_blocks.pil")).unwrap();
+        assert_eq!(column(&witness, "main.out")[..4], [9, 8, 64, 25]);
+        // Unused blocks are copies of the used block of the same size.
+        assert_eq!(
+            column(&witness, "Pow.acc"),
+            [3, 9, 2, 4, 8, 5, 25, 4, 16, 64, 3, 9, 3, 9, 3, 9]
+        );
+    }
+
+    #[test]
+    fn variable_size_blocks_no_matching_block() {
+        let src = read_pil("variable_size_blocks.pil")
+            .replace("[0, 1, 1, 0] + [0]*", "[0, 1, 1, 2] + [0]*");
+        let error = generate::<GoldilocksField>(&src).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("No block of machine 'Pow' satisfies the lookup"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn fixed_lookup_with_selector() {
+        let witness =
+            generate::<GoldilocksField>(&read_pil("fixed_lookup_with_selector.pil")).unwrap();
+        assert_eq!(column(&witness, "main.y"), [5, 7, 4, 6, 4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn out_of_rows() {
+        let error = generate_failure(&read_pil("out_of_rows.pil"));
+        assert_eq!(error.row, 7);
+        assert_eq!(error.kind, WitgenErrorKind::OutOfRows);
+        assert_eq!(
+            error.failing_identities,
+            vec!["Counter.x is 1 in the first row, but 9 after the last row".to_string()]
+        );
+        assert!(error.to_string().contains("--degree"));
+    }
+
+    #[test]
+    fn wrap_around() {
+        let analyzed =
+            pil_analyzer::analyze_string::<GoldilocksField>(&read_pil("wrap_around.pil"));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let witness =
+            WitnessGenerator::new(&analyzed, &constants, unused_query_callback()).generate();
+        assert_eq!(column(&witness, "Cycle.z"), [0, 0, 0, 0, 0, 0, 0, 4]);
+        assert_eq!(
+            executor::witness_checker::check_witness(&analyzed, &constants, &witness).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn indexed_let_arrays() {
+        assert_eq!(
+            generate::<GoldilocksField>(&read_pil("indexed_let_arrays.pil")),
+            generate(&read_pil("fibonacci.pil"))
+        );
+    }
+
+    #[test]
+    fn fixed_only_identities() {
+        let src = read_pil("fibonacci.pil")
+            + r#"
+    pol fixed STEP(i) { i };
+    LAST * (1 - LAST) = 0;
+    FIRST * STEP = 0;
+    LAST * (STEP - 15) = 0;
+    (1 - LAST) * (STEP' - STEP - 1) = 0;
+    LAST * STEP' = 0;
+"#;
+        assert_eq!(
+            generate::<GoldilocksField>(&src),
+            generate(&read_pil("fibonacci.pil"))
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_fixed_only_identity() {
+        let src = read_pil("fibonacci.pil")
+            + r#"
+    pol fixed STEP(i) { i };
+    (1 - LAST) * (STEP' - STEP - 1) = 0;
+    (STEP - 5) * (STEP - 6) * STEP * (1 - LAST) = 0;
+"#;
+        let error = generate_failure(&src);
+        assert_eq!(error.row, 1);
+        assert_eq!(error.kind, WitgenErrorKind::Unsatisfiable);
+        assert_eq!(error.failing_identities.len(), 1);
+        assert!(
+            error.failing_identities[0].starts_with(
+                "((((Fibonacci.STEP - 5) * (Fibonacci.STEP - 6)) * Fibonacci.STEP) * (1 - Fibonacci.LAST)) = 0"
+            ),
+            "{}",
+            error.failing_identities[0]
+        );
+    }
+
+    #[test]
+    fn permutation_into_block_machine() {
+        let witness = generate::<GoldilocksField>(&read_pil("permutation.pil")).unwrap();
+        assert_eq!(column(&witness, "main.x")[..3], [3, 5, 5]);
+        // One row per call, the unused rows are not selected.
+        assert_eq!(column(&witness, "sub.sel"), [0, 1, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(column(&witness, "sub.a")[1..4], [2, 4, 4]);
+    }
+
+    #[test]
+    fn permutation_count_mismatch() {
+        // The second call with the same values is (wrongly) answered by the row of the
+        // first one, which is fine for lookups, but not for permutations.
+        let src = read_pil("permutation.pil").replace(
+            "col witness x;",
+            "col witness x;\n    CALL * (x - A - 1) = 0;",
+        );
+        let error = generate::<GoldilocksField>(&src).unwrap_err();
+        let GenerationError::PermutationMismatch(violations) = error else {
+            panic!("Expected a permutation mismatch, got {error}");
+        };
+        assert_eq!(violations.len(), 1);
+        assert!(
+            violations[0].ends_with("in row 2: (4, 1, 5) has no match on the right side"),
+            "{}",
+            violations[0]
+        );
+    }
+
+    #[test]
+    fn permutation_check_failed() {
+        let src = read_pil("permutation.pil")
+            .replace("col witness x;", "col witness x;\n    public out = x(10);");
+        let error = generate::<GoldilocksField>(&src).unwrap_err();
+        assert_eq!(
+            error,
+            GenerationError::PermutationCheckFailed(
+                "No value for public out in column main.x.".to_string()
+            )
+        );
+        assert_eq!(
+            error.to_string(),
+            "Could not check the permutations: No value for public out in column main.x."
+        );
+    }
+
+    #[test]
+    fn machine_assignment() {
+        let analyzed =
+            pil_analyzer::analyze_string::<GoldilocksField>(&read_pil("machine_assignment.pil"));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let report = machine_report(&analyzed, &constants);
+
+        let partition = report
+            .machines
+            .iter()
+            .map(|m| (m.kind, m.witnesses.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            partition,
+            [
+                (
+                    MachineKind::Main,
+                    vec!["main.unused".into(), "main.x".into(), "main.y".into()]
+                ),
+                (MachineKind::FixedLookup, vec![]),
+                (
+                    MachineKind::Block,
+                    vec!["add.a".into(), "add.b".into(), "add.c".into()]
+                ),
+                (
+                    MachineKind::Block,
+                    vec!["double.a".into(), "double.b".into()]
+                ),
+            ]
+        );
+        assert_eq!(report.unowned_witnesses, ["main.unused"]);
+
+        let add = report.machine_of("add.c").unwrap();
+        assert_eq!(add.identities, ["add.c = (add.a + add.b);"]);
+        assert_eq!(add.connecting_identities.len(), 1);
+        assert!(add.connecting_identities[0].contains("add.latch"));
+        assert_eq!(report.machines[0].identities.len(), 3);
+        assert!(report.machines[1].connecting_identities[0].contains("main.P_IN"));
+    }
+
+    #[test]
+    fn value_ranges_enable_solving() {
+        let nibbles = read_pil("nibbles.pil");
+        let error = generate::<GoldilocksField>(&nibbles).unwrap_err();
+        assert!(matches!(error, GenerationError::Failed(_)), "{error}");
+
+        let src = nibbles
+            .replace("col witness lo;", "col witness lo in [0, 15];")
+            .replace("col witness hi;", "col witness hi in [0, 0xf];");
+        let witness = generate::<GoldilocksField>(&src).unwrap();
+        assert_eq!(witness[0].0, "N.lo");
+        assert_eq!(
+            column(&witness, "N.lo"),
+            (0..256).map(|i| i & 0xf).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            column(&witness, "N.hi"),
+            (0..256).map(|i| i >> 4).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn value_range_violation() {
+        let error =
+            generate::<GoldilocksField>(&read_pil("value_range_violation.pil")).unwrap_err();
+        let GenerationError::RangeViolation(violations) = error else {
+            panic!("Expected a range violation, got {error}");
+        };
+        assert_eq!(violations, ["N.x = 9 in row 3 is not in [0, 7]",]);
+    }
+
+    #[test]
+    fn fixed_lookup_16_bit_table() {
+        let byte2_lookup = read_pil("byte2_lookup.pil");
+        let witness = generate::<GoldilocksField>(&byte2_lookup).unwrap();
+        let [(_, x), (_, y)] = &witness[..] else {
+            panic!()
+        };
+        assert_eq!(x.len(), 1 << 16);
+        for (x, y) in x.iter().zip(y) {
+            assert_eq!(*y, *x * GoldilocksField::from(2));
+        }
+
+        // A value outside of the table is reported with the queried column and value,
+        // the last row is processed first.
+        let error = generate::<GoldilocksField>(
+            &byte2_lookup.replace("x = INPUT;", "x = INPUT + 0x10000;"),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains(
+                "Lookup into fixed columns failed: no match for query: main.BYTE2 = 123166"
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn connect_identity() {
+        let connect = read_pil("connect.pil");
+        let witness = generate::<GoldilocksField>(&connect).unwrap();
+        assert_eq!(witness[1].1, witness[0].1);
+
+        let error =
+            generate::<GoldilocksField>(&connect.replace("b = I;", "b = I * I;")).unwrap_err();
+        let GenerationError::ConnectionMismatch(violations) = error else {
+            panic!("Expected a connection mismatch, got {error}");
+        };
+        assert_eq!(violations.len(), 2);
+        assert!(
+            violations[0].ends_with("in row 2: (2, 4) is connected to (4, 2)"),
+            "{}",
+            violations[0]
+        );
+
+        let error = generate::<Bn254Field>(&connect).unwrap_err();
+        let GenerationError::ConnectionCheckFailed(reason) = error else {
+            panic!("Expected a failed connection check, got {error}");
+        };
+        assert!(
+            reason.starts_with("Connect identities can only be checked over the Goldilocks field"),
+            "{reason}"
+        );
+    }
+
+    #[test]
+    fn is_zero_hints() {
+        let is_zero = read_pil("is_zero.pil");
+        let error = generate_failure(&is_zero);
+        assert_eq!(error.kind, WitgenErrorKind::Underconstrained);
+
+        // 18446744069414584319 is p - 2, so `v ** 18446744069414584319` is the inverse of v.
+        let with_hints = is_zero
+            .replace(
+                "col witness x_inv;",
+                "col witness x_inv hint |i| X((i + 1) % 8) ** 18446744069414584319;",
+            )
+            .replace(
+                "col witness is_zero;",
+                "col witness is_zero hint |i| X((i + 1) % 8) == 0;",
+            );
+        let witness = generate::<GoldilocksField>(&with_hints).unwrap();
+        assert_eq!(column(&witness, "main.is_zero"), [0, 0, 1, 0, 0, 1, 0, 1]);
+        assert_eq!(
+            column(&witness, "main.x_inv"),
+            [1, 9223372034707292161, 0, 1, 9223372034707292161, 0, 1, 0]
+        );
+
+        // The hinted values still have to satisfy the constraints: In row 0, the wrong
+        // hint `is_zero = 1` implies `x' = 0`, which contradicts `x = X` in row 1.
+        let error =
+            generate_failure(&with_hints.replace("X((i + 1) % 8) == 0", "X((i + 1) % 8) != 0"));
+        assert_eq!(error.row, 1);
+        assert_eq!(error.kind, WitgenErrorKind::Unsatisfiable);
+    }
+}
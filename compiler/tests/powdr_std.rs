@@ -27,6 +27,8 @@ fn gen_estark_proof(file_name: &str, inputs: Vec<GoldilocksField>) {
         Some(backend::BackendType::EStark),
         vec![],
         None,
+        None,
+        &compiler::CancellationToken::default(),
     )
     .unwrap();
 }
@@ -45,6 +47,8 @@ fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
         Some(backend::BackendType::Halo2Mock),
         vec![],
         None,
+        None,
+        &compiler::CancellationToken::default(),
     )
     .unwrap();
 }
@@ -52,6 +56,25 @@ fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
 #[cfg(not(feature = "halo2"))]
 fn gen_halo2_proof(_file_name: &str, _inputs: Vec<Bn254Field>) {}
 
+/// Runs witness generation on the given program and checks the witness against all
+/// identities. Returns the error message if witness generation fails.
+fn generate_and_check_witness(file_name: &str, contents: &str) -> Result<(), String> {
+    let pil = compiler::compile_asm_string_to_pil::<GoldilocksField>(file_name, contents).unwrap();
+    let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(&format!("{pil}"));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let witness = executor::witgen::WitnessGenerator::new(
+        &analyzed,
+        &constants,
+        compiler::inputs_to_query_callback(vec![]),
+    )
+    .try_generate()
+    .map_err(|e| e.to_string())?;
+    let violations =
+        executor::witness_checker::check_witness(&analyzed, &constants, &witness).unwrap();
+    assert!(violations.is_empty(), "{violations:?}");
+    Ok(())
+}
+
 #[test]
 fn poseidon_bn254_test() {
     let f = "poseidon_bn254_test.asm";
@@ -65,6 +88,39 @@ fn poseidon_gl_test() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn poseidon_gl_witgen_is_deterministic() {
+    let file_name = format!(
+        "{}/../test_data/std/poseidon_gl_test.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    let pil =
+        compiler::compile_asm_string_to_pil::<GoldilocksField>(&file_name, &contents).unwrap();
+    let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(&format!("{pil}"));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let generate = || {
+        executor::witgen::WitnessGenerator::new(
+            &analyzed,
+            &constants,
+            compiler::inputs_to_query_callback(vec![]),
+        )
+        .generate()
+    };
+    let sequential = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(generate);
+    assert_eq!(generate(), sequential);
+}
+
+#[test]
+fn keccakf_test() {
+    let f = "keccakf_test.asm";
+    verify_asm::<GoldilocksField>(f, Default::default());
+}
+
 #[test]
 fn split_bn254_test() {
     let f = "split_bn254_test.asm";
@@ -77,3 +133,46 @@ fn split_gl_test() {
     verify_asm::<GoldilocksField>(f, Default::default());
     gen_estark_proof(f, Default::default());
 }
+
+#[test]
+fn range_check_test() {
+    let file_name = format!(
+        "{}/../test_data/std/range_check_test.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    generate_and_check_witness(&file_name, &contents).unwrap();
+
+    for (valid, invalid) in [
+        ("check_byte 255", "check_byte 256"),
+        ("check_u16 0xffff", "check_u16 0x10000"),
+    ] {
+        let contents = contents.replace(valid, invalid);
+        let error = generate_and_check_witness(&file_name, &contents).unwrap_err();
+        assert!(error.contains("(unsatisfiable)"), "{error}");
+    }
+}
+
+#[test]
+fn arith_test() {
+    let file_name = format!(
+        "{}/../test_data/std/arith_test.asm",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let contents = fs::read_to_string(&file_name).unwrap();
+    generate_and_check_witness(&file_name, &contents).unwrap();
+
+    for (valid, invalid) in [
+        ("assert_eq B, 0xfffffffe;", "assert_eq B, 0xffffffff;"),
+        ("assert_eq A, 0x0fffffff;", "assert_eq A, 0x0ffffffe;"),
+        (
+            "assert_eq A, 0xffffffff;\n        assert_eq B, 0x1234;",
+            "assert_eq A, 0;\n        assert_eq B, 0x1234;",
+        ),
+    ] {
+        assert!(contents.contains(valid));
+        let contents = contents.replace(valid, invalid);
+        let error = generate_and_check_witness(&file_name, &contents).unwrap_err();
+        assert!(error.contains("(unsatisfiable)"), "{error}");
+    }
+}
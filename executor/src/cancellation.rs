@@ -0,0 +1,118 @@
+//! Cooperative cancellation of long-running work such as witness generation and proving.
+//!
+//! A [CancellationToken] is shared between the code requesting cancellation (e.g. a
+//! signal handler) and the code doing the work, which checks it at safe points.
+//! The token also records the stages completed so far, so that a summary can be
+//! given after a cancellation.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The error returned by work that was stopped because cancellation was requested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    /// The stage at which the cancellation was noticed.
+    pub stage: String,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cancelled during {}.", self.stage)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// The first stage at which the cancellation was noticed.
+    stage: Mutex<Option<String>>,
+    completed: Mutex<Vec<String>>,
+}
+
+/// A cheaply clonable handle to request and check cancellation.
+/// All clones refer to the same cancellation state.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation. The work stops at its next safe point.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns an error if cancellation has been requested, remembering `stage`
+    /// as the stage that was aborted (if it is the first one to notice).
+    pub fn check(&self, stage: &str) -> Result<(), Cancelled> {
+        if !self.is_cancelled() {
+            return Ok(());
+        }
+        let mut aborted = self.0.stage.lock().unwrap();
+        let stage = aborted.get_or_insert_with(|| stage.to_string());
+        Err(Cancelled {
+            stage: stage.clone(),
+        })
+    }
+
+    /// The stage aborted because of the cancellation, if it has been noticed yet.
+    pub fn aborted_stage(&self) -> Option<String> {
+        self.0.stage.lock().unwrap().clone()
+    }
+
+    /// Records that `stage` has been completed.
+    pub fn stage_completed(&self, stage: &str) {
+        self.0.completed.lock().unwrap().push(stage.to_string());
+    }
+
+    /// The stages completed so far, in order.
+    pub fn completed_stages(&self) -> Vec<String> {
+        self.0.completed.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .field("aborted_stage", &self.aborted_stage())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn first_stage_is_remembered() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert_eq!(token.check("optimization"), Ok(()));
+        token.stage_completed("optimization");
+        assert_eq!(token.aborted_stage(), None);
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        let expected = Cancelled {
+            stage: "witness generation".to_string(),
+        };
+        assert_eq!(token.check("witness generation"), Err(expected.clone()));
+        assert_eq!(clone.check("proving"), Err(expected));
+        assert_eq!(
+            token.aborted_stage(),
+            Some("witness generation".to_string())
+        );
+        assert_eq!(clone.completed_stages(), vec!["optimization".to_string()]);
+    }
+}
@@ -1,86 +1,377 @@
-use std::{collections::HashMap, fmt::Display, rc::Rc};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    ops::Range,
+    rc::Rc,
+};
 
-use ast::analyzed::{Analyzed, FunctionValueDefinition};
+use ast::analyzed::{
+    Analyzed, Expression, FunctionValueDefinition, PolynomialType, Reference, Symbol, SymbolKind,
+};
+use ast::parsed::{visitor::ExpressionVisitable, ColumnRole};
 use itertools::Itertools;
 use number::{DegreeType, FieldElement};
 use pil_analyzer::evaluator::{self, Custom, EvalError, SymbolLookup, Value};
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::parallel::prelude::*;
+
+/// An error when evaluating a single fixed column with [evaluate_fixed].
+#[derive(Debug)]
+pub enum FixedColumnError {
+    /// There is no symbol of this name.
+    UnknownColumn(String),
+    /// The symbol exists, but is not a fixed column (e.g. a witness column).
+    NotFixed(String),
+    /// The fixed column is only declared, but has no definition.
+    NotDefined(String),
+    /// The requested rows are not inside the trace.
+    RowsOutOfRange {
+        rows: Range<DegreeType>,
+        degree: DegreeType,
+    },
+    /// The definition of the column or one of its dependencies failed to evaluate.
+    Evaluation(EvalError),
+}
+
+impl Display for FixedColumnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixedColumnError::UnknownColumn(name) => write!(f, "Symbol {name} not found."),
+            FixedColumnError::NotFixed(name) => write!(f, "{name} is not a fixed column."),
+            FixedColumnError::NotDefined(name) => {
+                write!(f, "Fixed column {name} is declared but not defined.")
+            }
+            FixedColumnError::RowsOutOfRange { rows, degree } => write!(
+                f,
+                "Rows {}..{} are out of range for degree {degree}.",
+                rows.start, rows.end
+            ),
+            FixedColumnError::Evaluation(e) => write!(f, "Evaluation failed: {e:?}"),
+        }
+    }
+}
+
+impl From<EvalError> for FixedColumnError {
+    fn from(e: EvalError) -> Self {
+        FixedColumnError::Evaluation(e)
+    }
+}
 
 /// Generates the constant polynomial values for all constant polynomials
 /// that are defined (and not just declared).
+/// Columns that do not depend on each other are generated in parallel.
 /// @returns the values (in source order) and the degree of the polynomials.
 pub fn generate<T: FieldElement>(analyzed: &Analyzed<T>) -> Vec<(&str, Vec<T>)> {
-    let mut other_constants = HashMap::new();
-    for (poly, value) in analyzed.constant_polys_in_source_order() {
-        if let Some(value) = value {
-            let values = generate_values(analyzed, analyzed.degree(), value, &other_constants);
-            other_constants.insert(&poly.absolute_name, values);
-        }
+    let columns = defined_columns(analyzed);
+    let column_indices: HashMap<&str, usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (poly, _))| (poly.absolute_name.as_str(), i))
+        .collect();
+
+    let mut computed_columns: HashMap<&str, Vec<T>> = HashMap::new();
+    for level in dependency_levels(analyzed, &columns, &column_indices) {
+        let values = level
+            .into_par_iter()
+            .map(|index| {
+                let (poly, value) = columns[index];
+                // A column can only refer to the values of the columns defined before it.
+                let previous_columns = computed_columns
+                    .iter()
+                    .filter(|(name, _)| column_indices[*name] < index)
+                    .map(|(name, values)| (*name, values.as_slice()))
+                    .collect();
+                let values = generate_values(
+                    analyzed,
+                    analyzed.degree(),
+                    &poly.absolute_name,
+                    value,
+                    &previous_columns,
+                );
+                (poly.absolute_name.as_str(), values)
+            })
+            .collect::<Vec<_>>();
+        computed_columns.extend(values);
     }
 
-    other_constants
+    computed_columns
         .into_iter()
         .sorted_by_key(|(name, _)| analyzed.definitions[&name.to_string()].0.id)
         .collect::<Vec<_>>()
 }
 
+/// Evaluates the fixed column `name` on the given rows only.
+/// The fixed columns it depends on are generated in full (each of them once),
+/// since the definition can access them at any row.
+pub fn evaluate_fixed<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    name: &str,
+    rows: Range<DegreeType>,
+) -> Result<Vec<T>, FixedColumnError> {
+    let degree = analyzed.degree();
+    if rows.start > rows.end || rows.end > degree {
+        return Err(FixedColumnError::RowsOutOfRange { rows, degree });
+    }
+    let (symbol, definition) = analyzed
+        .definitions
+        .get(name)
+        .ok_or_else(|| FixedColumnError::UnknownColumn(name.to_string()))?;
+    if symbol.kind != SymbolKind::Poly(PolynomialType::Constant) {
+        return Err(FixedColumnError::NotFixed(name.to_string()));
+    }
+    let definition = definition
+        .as_ref()
+        .ok_or_else(|| FixedColumnError::NotDefined(name.to_string()))?;
+
+    let columns = defined_columns(analyzed);
+    let column_indices: HashMap<&str, usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (poly, _))| (poly.absolute_name.as_str(), i))
+        .collect();
+    let index = column_indices[name];
+
+    let mut cache = HashMap::new();
+    let dependencies = referenced_previous_columns(analyzed, definition, &column_indices, index);
+    for &dependency in &dependencies {
+        generate_with_dependencies(analyzed, &columns, &column_indices, dependency, &mut cache)?;
+    }
+    let computed_columns = dependencies
+        .iter()
+        .map(|i| (columns[*i].0.absolute_name.as_str(), cache[i].as_slice()))
+        .collect();
+    Ok(match definition {
+        FunctionValueDefinition::Number(n) => {
+            vec![T::from(*n as u64); (rows.end - rows.start) as usize]
+        }
+        _ => evaluate_rows(analyzed, definition, &computed_columns, rows)?,
+    })
+}
+
+/// Generates the column at `index` and, before that, the previous columns it refers to,
+/// unless they are already in `cache`.
+/// Checks that the fixed columns with a `#[role(...)]` attribute have the canonical
+/// shape of their role for the degree, e.g. `[1] + [0]*` for `first_row`.
+/// Columns without values (i.e. only declared columns) are not checked.
+pub fn check_column_roles<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(&str, Vec<T>)],
+) -> Result<(), Vec<String>> {
+    let degree = analyzed.degree() as usize;
+    let errors = fixed_col_values
+        .iter()
+        .filter_map(|(name, values)| {
+            let role = analyzed.definitions.get(*name)?.0.role?;
+            let one_at = match role {
+                ColumnRole::FirstRow => 0,
+                ColumnRole::LastRow => degree - 1,
+            };
+            if values.len() != degree {
+                return Some(format!(
+                    "Fixed column {name} has the role {role}, but it has {} rows instead of {degree}.",
+                    values.len()
+                ));
+            }
+            let (row, value) = values.iter().enumerate().find(|(row, value)| {
+                **value != if *row == one_at { T::one() } else { T::zero() }
+            })?;
+            Some(format!(
+                "Fixed column {name} has the role {role}, so it has to be one in row {one_at} and zero in all other rows, but it is {value} in row {row}."
+            ))
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn generate_with_dependencies<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    columns: &[(&Symbol, &FunctionValueDefinition<T>)],
+    column_indices: &HashMap<&str, usize>,
+    index: usize,
+    cache: &mut HashMap<usize, Vec<T>>,
+) -> Result<(), EvalError> {
+    if cache.contains_key(&index) {
+        return Ok(());
+    }
+    let (_, definition) = columns[index];
+    let dependencies = referenced_previous_columns(analyzed, definition, column_indices, index);
+    for &dependency in &dependencies {
+        generate_with_dependencies(analyzed, columns, column_indices, dependency, cache)?;
+    }
+    let computed_columns = dependencies
+        .iter()
+        .map(|i| (columns[*i].0.absolute_name.as_str(), cache[i].as_slice()))
+        .collect();
+    let values = match definition {
+        FunctionValueDefinition::Number(n) => vec![T::from(*n as u64)],
+        _ => evaluate_rows(
+            analyzed,
+            definition,
+            &computed_columns,
+            0..analyzed.degree(),
+        )?,
+    };
+    cache.insert(index, values);
+    Ok(())
+}
+
+/// The constant polynomials that are defined, in source order.
+fn defined_columns<T>(analyzed: &Analyzed<T>) -> Vec<(&Symbol, &FunctionValueDefinition<T>)> {
+    analyzed
+        .constant_polys_in_source_order()
+        .into_iter()
+        .filter_map(|(poly, value)| value.as_ref().map(|value| (poly, value)))
+        .collect()
+}
+
+/// Groups the indices of `columns` into levels, such that each column only refers to
+/// previous columns in earlier levels. The columns of a level can be generated in parallel.
+fn dependency_levels<T>(
+    analyzed: &Analyzed<T>,
+    columns: &[(&Symbol, &FunctionValueDefinition<T>)],
+    column_indices: &HashMap<&str, usize>,
+) -> Vec<Vec<usize>> {
+    let mut column_levels: Vec<usize> = vec![];
+    let mut levels: Vec<Vec<usize>> = vec![];
+    for (index, (_, value)) in columns.iter().enumerate() {
+        let level = referenced_previous_columns(analyzed, value, column_indices, index)
+            .into_iter()
+            .map(|i| column_levels[i] + 1)
+            .max()
+            .unwrap_or_default();
+        column_levels.push(level);
+        if level == levels.len() {
+            levels.push(vec![]);
+        }
+        levels[level].push(index);
+    }
+    levels
+}
+
+/// Returns the indices of the columns before `index` that `body` refers to, either
+/// directly or through other definitions. References to later columns evaluate their
+/// definitions, so these are followed as well.
+fn referenced_previous_columns<T>(
+    analyzed: &Analyzed<T>,
+    body: &FunctionValueDefinition<T>,
+    column_indices: &HashMap<&str, usize>,
+    index: usize,
+) -> BTreeSet<usize> {
+    let mut result = BTreeSet::new();
+    let mut visited = HashSet::new();
+    let mut to_visit = vec![body];
+    while let Some(definition) = to_visit.pop() {
+        let mut names = vec![];
+        definition.pre_visit_expressions(&mut |e| {
+            if let Expression::Reference(Reference::Poly(reference)) = e {
+                names.push(reference.name.clone());
+            }
+        });
+        for name in names {
+            match column_indices.get(name.as_str()) {
+                Some(&i) if i < index => {
+                    result.insert(i);
+                }
+                _ => {
+                    if let Some((_, Some(definition))) = analyzed.definitions.get(&name) {
+                        if visited.insert(name) {
+                            to_visit.push(definition);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
 fn generate_values<T: FieldElement>(
     analyzed: &Analyzed<T>,
     degree: DegreeType,
+    name: &str,
     body: &FunctionValueDefinition<T>,
-    computed_columns: &HashMap<&str, Vec<T>>,
+    computed_columns: &HashMap<&str, &[T]>,
 ) -> Vec<T> {
+    match body {
+        FunctionValueDefinition::Number(n) => vec![T::from(*n as u64)],
+        FunctionValueDefinition::Query(_) => panic!("Query used for fixed column."),
+        FunctionValueDefinition::Hint(_) => panic!("Hint used for fixed column."),
+        _ => {
+            let values =
+                evaluate_rows(analyzed, body, computed_columns, 0..degree).unwrap_or_else(|e| {
+                    let source = &analyzed.definitions[name].0.source;
+                    panic!(
+                        "{}:{}: Could not generate fixed column {name}: {e:?}",
+                        source.file, source.line
+                    )
+                });
+            assert_eq!(values.len(), degree as usize);
+            values
+        }
+    }
+}
+
+/// Evaluates an expression or array definition of a fixed column on the given rows.
+fn evaluate_rows<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    body: &FunctionValueDefinition<T>,
+    computed_columns: &HashMap<&str, &[T]>,
+    rows: Range<DegreeType>,
+) -> Result<Vec<T>, EvalError> {
     let symbols = Symbols {
         analyzed,
         computed_columns,
     };
     // TODO we should maybe pre-compute some symbols here.
     match body {
-        FunctionValueDefinition::Number(n) => vec![T::from(*n as u64)],
-        FunctionValueDefinition::Expression(e) => (0..degree)
+        FunctionValueDefinition::Number(_) => unreachable!(),
+        FunctionValueDefinition::Expression(e) => rows
             .into_par_iter()
             .map(|i| {
                 // We could try to avoid the first evaluation to be run for each iteration,
                 // but the data is not thread-safe.
-                let fun = evaluator::evaluate(e, &symbols).unwrap();
-                evaluator::evaluate_function_call(fun, vec![Rc::new(T::from(i).into())], &symbols)
-                    .unwrap()
+                let fun = evaluator::evaluate(e, &symbols)?;
+                evaluator::evaluate_function_call(fun, vec![Rc::new(T::from(i).into())], &symbols)?
                     .try_to_number()
-                    .unwrap()
             })
             .collect(),
         FunctionValueDefinition::Array(values) => {
-            let values: Vec<_> = values
-                .iter()
-                .flat_map(|elements| {
+            let mut result = Vec::with_capacity((rows.end - rows.start) as usize);
+            let mut start = 0;
+            for elements in values {
+                let end = start + elements.size();
+                // Only evaluate the patterns that overlap with the requested rows.
+                if start < rows.end && rows.start < end {
                     let items = elements
                         .pattern()
                         .iter()
-                        .map(|v| {
-                            evaluator::evaluate(v, &symbols)
-                                .unwrap()
-                                .try_to_number()
-                                .unwrap()
-                        })
-                        .collect::<Vec<_>>();
-
-                    items
-                        .into_iter()
-                        .cycle()
-                        .take(elements.size() as usize)
-                        .collect::<Vec<_>>()
-                })
-                .collect();
-            assert_eq!(values.len(), degree as usize);
-            values
+                        .map(|v| evaluator::evaluate(v, &symbols)?.try_to_number())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    result
+                        .extend((start.max(rows.start)..end.min(rows.end)).map(|row| {
+                            items[((row - start) % items.len() as DegreeType) as usize]
+                        }));
+                }
+                start = end;
+            }
+            Ok(result)
         }
-        FunctionValueDefinition::Query(_) => panic!("Query used for fixed column."),
+        FunctionValueDefinition::Query(_) => Err(EvalError::Unsupported(
+            "Query used for fixed column.".to_string(),
+        )),
+        FunctionValueDefinition::Hint(_) => Err(EvalError::Unsupported(
+            "Hint used for fixed column.".to_string(),
+        )),
     }
 }
 
 struct Symbols<'a, T> {
     pub analyzed: &'a Analyzed<T>,
-    pub computed_columns: &'a HashMap<&'a str, Vec<T>>,
+    pub computed_columns: &'a HashMap<&'a str, &'a [T]>,
 }
 
 impl<'a, T: FieldElement> SymbolLookup<'a, T, FixedColumnRef<'a>> for Symbols<'a, T> {
@@ -108,6 +399,10 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T, FixedColumnRef<'a>> for Symbols<'a
         )
     }
 
+    fn lookup_degree(&self) -> Result<Value<'a, T, FixedColumnRef<'a>>, EvalError> {
+        Ok(Value::Number(self.analyzed.degree().into()))
+    }
+
     fn eval_function_application(
         &self,
         function: FixedColumnRef<'a>,
@@ -125,7 +420,7 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T, FixedColumnRef<'a>> for Symbols<'a
                 arguments[0]
             )));
         };
-        let data = &self.computed_columns[function.name];
+        let data = self.computed_columns[function.name];
         Ok(Value::Number(data[row.to_degree() as usize % data.len()]))
     }
 }
@@ -146,7 +441,7 @@ impl<'a> Display for FixedColumnRef<'a> {
 #[cfg(test)]
 mod test {
     use number::GoldilocksField;
-    use pil_analyzer::analyze_string;
+    use pil_analyzer::{analyze_string, analyze_string_with_degree};
     use pretty_assertions::assert_eq;
     use test_log::test;
 
@@ -156,6 +451,28 @@ mod test {
         input.into_iter().map(|x| x.into()).collect()
     }
 
+    /// Generates the columns one after the other, the way `generate` did before
+    /// it was parallelized.
+    fn generate_sequentially<T: FieldElement>(analyzed: &Analyzed<T>) -> Vec<(&str, Vec<T>)> {
+        let mut columns: Vec<(&str, Vec<T>)> = vec![];
+        for (poly, value) in defined_columns(analyzed) {
+            let previous_columns = columns
+                .iter()
+                .map(|(name, values)| (*name, values.as_slice()))
+                .collect();
+            let values = generate_values(
+                analyzed,
+                analyzed.degree(),
+                &poly.absolute_name,
+                value,
+                &previous_columns,
+            );
+            columns.push((&poly.absolute_name, values));
+        }
+        columns.sort_by_key(|(name, _)| analyzed.definitions[*name].0.id);
+        columns
+    }
+
     #[test]
     pub fn test_last() {
         let src = r#"
@@ -302,6 +619,49 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn test_dependency_levels() {
+        let src = r#"
+            constant %N = 10;
+            namespace F(%N);
+            col fixed seq(i) { i };
+            col fixed doub(i) { seq((2 * i) % %N) + 1 };
+            col fixed half_nibble(i) { i & 0x7 };
+            col fixed doubled_half_nibble(i) { half_nibble(i / 2) + later(i) };
+            col fixed later(i) { doub(i) };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let columns = defined_columns(&analyzed);
+        let column_indices = columns
+            .iter()
+            .enumerate()
+            .map(|(i, (poly, _))| (poly.absolute_name.as_str(), i))
+            .collect();
+        assert_eq!(
+            dependency_levels(&analyzed, &columns, &column_indices),
+            vec![vec![0, 2], vec![1], vec![3, 4]]
+        );
+        assert_eq!(generate(&analyzed), generate_sequentially(&analyzed));
+    }
+
+    #[test]
+    pub fn parallel_equals_sequential() {
+        let file = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../test_data/pil/block_lookup_or.pil");
+        let analyzed = pil_analyzer::analyze::<GoldilocksField>(&file);
+
+        let start = std::time::Instant::now();
+        let sequential = generate_sequentially(&analyzed);
+        let sequential_duration = start.elapsed();
+        let start = std::time::Instant::now();
+        let parallel = generate(&analyzed);
+        log::info!(
+            "Generated fixed columns sequentially in {sequential_duration:?} and in parallel in {:?}.",
+            start.elapsed()
+        );
+        assert_eq!(parallel, sequential);
+    }
+
     #[test]
     pub fn test_arrays() {
         let src = r#"
@@ -405,6 +765,86 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn byte_decomposition() {
+        let src = r#"
+            constant %N = 8;
+            namespace F(%N);
+            col fixed X(i) { 0x10203 * i + 0xff };
+            col fixed B0(i) { X(i) % 256 };
+            col fixed B1(i) { (X(i) / 256) % 256 };
+            col fixed B2(i) { (X(i) / 0x10000) % 256 };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let x = (0..8).map(|i| 0x10203 * i + 0xff).collect::<Vec<_>>();
+        assert_eq!(constants[1].1[3], 0x08.into());
+        assert_eq!(constants[2].1[3], 0x07.into());
+        assert_eq!(constants[3].1[3], 0x03.into());
+        for (byte, column) in constants[1..].iter().enumerate() {
+            assert_eq!(
+                column.1,
+                convert(x.iter().map(|x| (x >> (8 * byte)) & 0xff).collect())
+            );
+        }
+    }
+
+    #[test]
+    pub fn division_rounds_down() {
+        let src = r#"
+            constant %N = 8;
+            namespace F(%N);
+            col fixed DIV(i) { (2 * i + 1) / 3 };
+            col fixed MOD(i) { (2 * i + 1) % 3 };
+            col fixed NEG(i) { (0 - 1) % 256 };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        assert_eq!(constants[0].1, convert(vec![0, 1, 1, 2, 3, 3, 4, 5]));
+        assert_eq!(constants[1].1, convert(vec![1, 0, 2, 1, 0, 2, 1, 0]));
+        // The remainder is taken on the integer representation of -1, i.e. 2**64 - 2**32.
+        assert_eq!(constants[2].1, convert(vec![0; 8]));
+    }
+
+    #[test]
+    #[should_panic = "Could not generate fixed column F.y: DivisionByZero"]
+    pub fn division_by_zero() {
+        let src = r#"
+            constant %N = 8;
+            namespace F(%N);
+            col fixed x(i) { i / 1 };
+            col fixed y(i) { 1 % (i - 3) };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        generate(&analyzed);
+    }
+
+    #[test]
+    pub fn indexed_let_arrays() {
+        let src = r#"
+            namespace F(4);
+            let z = 2;
+            let other = [1, z];
+            let nested = [[1, 2], [3, z + 5]];
+            col fixed x(i) { other[i % 2] + nested[z - 1][i / 2] };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        assert_eq!(constants[0].1, convert(vec![4, 5, 8, 9]));
+    }
+
+    #[test]
+    #[should_panic = "input:4: Could not generate fixed column F.x: OutOfBounds"]
+    pub fn indexed_let_array_out_of_bounds() {
+        let src = r#"
+            namespace F(4);
+            let other = [1, 2];
+            col fixed x(i) { other[i] };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        generate(&analyzed);
+    }
+
     #[test]
     #[should_panic = "Cannot evaluate witness columns"]
     pub fn calling_witness() {
@@ -460,4 +900,146 @@ mod test {
         assert_eq!(constants[0], ("F.x", convert([21, 22, 23, 24].to_vec())));
         assert_eq!(constants[1], ("F.y", convert([20, 21, 22, 23].to_vec())));
     }
+
+    #[test]
+    pub fn evaluate_fixed_mid_trace() {
+        let src = r#"
+            constant %N = 16;
+            namespace F(%N);
+            col fixed BYTE(i) { i & 0xf };
+            col fixed ODD = [0, 1]*;
+            col fixed SUM(i) { BYTE((i + 3) % %N) + ODD(i) * 100 };
+            col witness w;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        for (name, values) in &constants {
+            assert_eq!(
+                evaluate_fixed(&analyzed, name, 5..11).unwrap(),
+                values[5..11].to_vec()
+            );
+        }
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.SUM", 14..16).unwrap(),
+            convert(vec![1, 102])
+        );
+        assert!(evaluate_fixed(&analyzed, "F.SUM", 3..3).unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn evaluate_fixed_arrays() {
+        let src = r#"
+            constant %N = 10;
+            namespace F(%N);
+            col fixed A = [7, 8] + [1, 2, 3]* + [9];
+            col fixed B = [A(0), A(9)] + [0]*;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.A", 1..9).unwrap(),
+            constants[0].1[1..9].to_vec()
+        );
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.A", 1..9).unwrap(),
+            convert(vec![8, 1, 2, 3, 1, 2, 3, 1])
+        );
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.B", 0..3).unwrap(),
+            convert(vec![7, 9, 0])
+        );
+    }
+
+    #[test]
+    pub fn evaluate_fixed_errors() {
+        let src = r#"
+            constant %N = 8;
+            namespace F(%N);
+            col fixed C(i) { i };
+            col witness w;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        assert!(matches!(
+            evaluate_fixed(&analyzed, "F.w", 0..8),
+            Err(FixedColumnError::NotFixed(_))
+        ));
+        assert!(matches!(
+            evaluate_fixed(&analyzed, "F.x", 0..8),
+            Err(FixedColumnError::UnknownColumn(_))
+        ));
+        assert!(matches!(
+            evaluate_fixed(&analyzed, "F.C", 4..9),
+            Err(FixedColumnError::RowsOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    pub fn column_roles() {
+        let src = r#"
+            constant %N = 8;
+            namespace F(%N);
+            #[role(first_row)] col fixed FIRST = [1] + [0]*;
+            #[role(last_row)] col fixed LAST(i) { i == %N - 1 };
+            #[role(first_row)] col fixed FIRST_TWICE = [1, 1] + [0]*;
+            #[role(last_row)] col fixed NOT_LAST = [0]*;
+            #[role(first_row)] col fixed EXTERNAL;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        assert_eq!(
+            check_column_roles(&analyzed, &constants).unwrap_err(),
+            vec![
+                "Fixed column F.FIRST_TWICE has the role first_row, so it has to be one in row 0 and zero in all other rows, but it is 1 in row 1.",
+                "Fixed column F.NOT_LAST has the role last_row, so it has to be one in row 7 and zero in all other rows, but it is 0 in row 7."
+            ]
+        );
+        assert!(check_column_roles(&analyzed, &constants[..2]).is_ok());
+    }
+
+    #[test]
+    pub fn last_via_degree() {
+        let src = r#"
+            namespace F(8);
+            col fixed LAST(i) { if i == DEGREE - 1 { 1 } else { 0 } };
+        "#;
+        for degree in [8, 16] {
+            let analyzed = analyze_string_with_degree::<GoldilocksField>(src, Some(degree));
+            let constants = generate(&analyzed);
+            let mut expected = vec![0; degree as usize];
+            expected[degree as usize - 1] = 1;
+            assert_eq!(constants, vec![("F.LAST", convert(expected))]);
+        }
+    }
+
+    #[test]
+    pub fn casting_builtins() {
+        let src = r#"
+            namespace F(8);
+            col fixed LOW(i) { fe(int(i) % 4) };
+            col fixed HIGH(i) { int(i, 3) / 4 };
+            col fixed NEG(i) { int(-1) / int(-1) };
+            col fixed TOO_WIDE(i) { int(i * 64, 8) };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.LOW", 0..8).unwrap(),
+            convert(vec![0, 1, 2, 3, 0, 1, 2, 3])
+        );
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.HIGH", 0..8).unwrap(),
+            convert(vec![0, 0, 0, 0, 1, 1, 1, 1])
+        );
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.NEG", 0..1).unwrap(),
+            convert(vec![1])
+        );
+        assert_eq!(
+            evaluate_fixed(&analyzed, "F.TOO_WIDE", 0..4).unwrap(),
+            convert(vec![0, 64, 128, 192])
+        );
+        assert!(matches!(
+            evaluate_fixed(&analyzed, "F.TOO_WIDE", 0..8),
+            Err(FixedColumnError::Evaluation(EvalError::FailedAssertion(_)))
+        ));
+    }
 }
@@ -2,5 +2,8 @@
 
 #![deny(clippy::print_stdout)]
 
+pub mod cancellation;
 pub mod constant_evaluator;
+mod parallel;
 pub mod witgen;
+pub mod witness_checker;
@@ -0,0 +1,20 @@
+//! Iteration over rows or columns that runs on the rayon thread pool if the `parallel`
+//! feature is enabled and sequentially otherwise, e.g. on wasm32 without threads.
+//! Importing `prelude::*` provides `into_par_iter` in both cases.
+
+#[cfg(not(feature = "parallel"))]
+pub trait IntoParallelIterator: IntoIterator + Sized {
+    fn into_par_iter(self) -> Self::IntoIter {
+        self.into_iter()
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<I: IntoIterator> IntoParallelIterator for I {}
+
+pub mod prelude {
+    #[cfg(not(feature = "parallel"))]
+    pub use super::IntoParallelIterator;
+    #[cfg(feature = "parallel")]
+    pub use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+}
@@ -0,0 +1,390 @@
+//! Fast path for witness columns that are fully determined by an affine recurrence.
+//!
+//! A column `x` is fast-pathed if there is a polynomial identity that only references
+//! `x`, `x'`, fixed columns and constants and is affine in `x` and `x'`, i.e. it can be
+//! written as `x' = a * x + b` on every row, where `a` and `b` depend on fixed columns only.
+//! Since the identity also holds between the last and the first row, composing the
+//! recurrence over all rows gives `x[0] = A * x[0] + B`, which determines `x[0]` if `A != 1`.
+//! The column is then computed in a single loop instead of by the solver.
+//!
+//! Anything else (other witness columns, non-affine terms, `a` not unique or the cycle
+//! not determining the first row) is left to the solver.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+use ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference,
+    AlgebraicUnaryOperator, Analyzed, Identity, IdentityKind, PolyID, PolynomialType,
+};
+use number::{DegreeType, FieldElement};
+use web_time::Instant;
+
+/// Number of rows (besides the first and the last) on which the computed columns are
+/// checked against the identities that only reference fast-pathed columns.
+const VERIFIED_ROWS: u64 = 100;
+
+/// The columns computed by the fast path.
+pub struct AffineColumns<T> {
+    /// The computed columns, by name.
+    pub columns: Vec<(String, Vec<T>)>,
+    /// Time spent detecting, computing and verifying the columns.
+    pub elapsed: Duration,
+}
+
+/// Detects witness columns determined by an affine recurrence and computes them.
+/// Columns in `skip` (e.g. columns with externally provided values) are not considered.
+pub fn compute_affine_columns<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(&str, Vec<T>)],
+    identities: &[Identity<Expression<T>>],
+    skip: &BTreeSet<&str>,
+) -> AffineColumns<T> {
+    let start = Instant::now();
+    let degree = analyzed.degree();
+    let names: HashMap<PolyID, String> = analyzed
+        .committed_polys_in_source_order()
+        .iter()
+        .flat_map(|(poly, _)| poly.array_elements())
+        .map(|(name, poly_id)| (poly_id, name))
+        .collect();
+    let evaluator = Evaluator {
+        fixed: fixed_col_values,
+        degree,
+    };
+
+    let mut columns: BTreeMap<PolyID, Vec<T>> = BTreeMap::new();
+    for identity in identities {
+        if identity.kind != IdentityKind::Polynomial {
+            continue;
+        }
+        let expr = identity.expression_for_poly_id();
+        let Some(poly_id) = recurrence_column(expr) else {
+            continue;
+        };
+        if columns.contains_key(&poly_id) || skip.contains(names[&poly_id].as_str()) {
+            continue;
+        }
+        if let Some(values) = evaluator.solve_recurrence(expr) {
+            columns.insert(poly_id, values);
+        }
+    }
+
+    // Safety net: check the identities that only reference computed columns on some rows.
+    for identity in identities {
+        if columns.is_empty() || identity.kind != IdentityKind::Polynomial {
+            continue;
+        }
+        let expr = identity.expression_for_poly_id();
+        let references = witness_references(expr);
+        if references.is_empty() || !references.iter().all(|r| columns.contains_key(r)) {
+            continue;
+        }
+        let step = (degree / VERIFIED_ROWS).max(1);
+        let holds = (0..degree)
+            .step_by(step as usize)
+            .chain([degree - 1])
+            .all(|row| {
+                evaluator.evaluate(expr, row, &|r| {
+                    let values = &columns[&r.poly_id];
+                    values[((row + r.next as DegreeType) % degree) as usize]
+                }) == Some(T::zero())
+            });
+        if !holds {
+            log::warn!("Identity {identity} does not hold for the columns computed from affine recurrences, using the solver for them.");
+            for r in references {
+                columns.remove(&r);
+            }
+        }
+    }
+
+    let columns: Vec<_> = columns
+        .into_iter()
+        .map(|(poly_id, values)| (names[&poly_id].clone(), values))
+        .collect();
+    AffineColumns {
+        columns,
+        elapsed: start.elapsed(),
+    }
+}
+
+impl<T> AffineColumns<T> {
+    /// Logs the statistics line, if any columns were computed.
+    pub fn log_statistics(&self) {
+        if !self.columns.is_empty() {
+            log::info!(
+                "Computed {} column(s) from affine recurrences without the solver in {:.3}s: {}",
+                self.columns.len(),
+                self.elapsed.as_secs_f32(),
+                self.columns
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+}
+
+/// Returns the witness column of the recurrence if `expr` only references a single witness
+/// column (in the current and the next row) and is affine in it.
+fn recurrence_column<T>(expr: &Expression<T>) -> Option<PolyID> {
+    let references = witness_references(expr);
+    let next_referenced = references_next(expr);
+    match (references.len(), next_referenced, witness_degree(expr)) {
+        (1, true, Some(1)) => references.into_iter().next(),
+        _ => None,
+    }
+}
+
+/// The witness columns referenced in `expr`.
+fn witness_references<T>(expr: &Expression<T>) -> BTreeSet<PolyID> {
+    let mut references = BTreeSet::new();
+    collect_witness_references(expr, &mut |r| {
+        references.insert(r.poly_id);
+    });
+    references
+}
+
+fn references_next<T>(expr: &Expression<T>) -> bool {
+    let mut next = false;
+    collect_witness_references(expr, &mut |r| next |= r.next);
+    next
+}
+
+fn collect_witness_references<T>(expr: &Expression<T>, f: &mut impl FnMut(&AlgebraicReference)) {
+    match expr {
+        Expression::Reference(r) if r.poly_id.ptype == PolynomialType::Committed => f(r),
+        Expression::BinaryOperation(left, _, right) => {
+            collect_witness_references(left, f);
+            collect_witness_references(right, f);
+        }
+        Expression::UnaryOperation(_, inner) => collect_witness_references(inner, f),
+        _ => {}
+    }
+}
+
+/// The degree of `expr` in the witness columns, or None if it contains anything
+/// the fast path does not support.
+fn witness_degree<T>(expr: &Expression<T>) -> Option<usize> {
+    match expr {
+        Expression::Reference(r) => match r.poly_id.ptype {
+            PolynomialType::Committed => Some(1),
+            PolynomialType::Constant => Some(0),
+            PolynomialType::Intermediate => None,
+        },
+        Expression::PublicReference(_) => None,
+        Expression::Number(_) => Some(0),
+        Expression::BinaryOperation(left, op, right) => {
+            let (left, right) = (witness_degree(left)?, witness_degree(right)?);
+            match op {
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                    Some(left.max(right))
+                }
+                AlgebraicBinaryOperator::Mul => Some(left + right),
+                AlgebraicBinaryOperator::Pow => (left == 0 && right == 0).then_some(0),
+            }
+        }
+        Expression::UnaryOperation(_, inner) => witness_degree(inner),
+    }
+}
+
+/// The inverses of the (non-zero) `values`, computed with a single field inversion.
+fn batch_inverse<T: FieldElement>(values: impl Iterator<Item = T>) -> Vec<T> {
+    let values: Vec<T> = values.collect();
+    // The product of all values before each one.
+    let mut products = Vec::with_capacity(values.len());
+    let mut product = T::one();
+    for value in &values {
+        products.push(product);
+        product = product * *value;
+    }
+    let mut inverse = T::one() / product;
+    let mut inverses = vec![T::zero(); values.len()];
+    for (i, value) in values.iter().enumerate().rev() {
+        inverses[i] = inverse * products[i];
+        inverse = inverse * *value;
+    }
+    inverses
+}
+
+struct Evaluator<'a, T> {
+    fixed: &'a [(&'a str, Vec<T>)],
+    degree: DegreeType,
+}
+
+impl<'a, T: FieldElement> Evaluator<'a, T> {
+    /// Computes the column defined by the recurrence `expr = 0` or returns None
+    /// if it is not uniquely determined.
+    fn solve_recurrence(&self, expr: &Expression<T>) -> Option<Vec<T>> {
+        // On each row, `expr = c_next * x' + c_cur * x + r`, so `x' = a * x + b`.
+        let mut terms = Vec::with_capacity(self.degree as usize);
+        for row in 0..self.degree {
+            let at = |current: u64, next: u64| {
+                self.evaluate(expr, row, &|r| T::from(if r.next { next } else { current }))
+            };
+            let r = at(0, 0)?;
+            let c_cur = at(1, 0)? - r;
+            let c_next = at(0, 1)? - r;
+            if c_next.is_zero() {
+                return None;
+            }
+            terms.push((c_cur, c_next, r));
+        }
+        let inverses = batch_inverse(terms.iter().map(|(_, c_next, _)| *c_next));
+        let coefficients: Vec<_> = terms
+            .iter()
+            .zip(inverses)
+            .map(|((c_cur, _, r), inverse)| (-*c_cur * inverse, -*r * inverse))
+            .collect();
+
+        // Compose the recurrence over the whole cycle: x[0] = a_total * x[0] + b_total.
+        let (a_total, b_total) = coefficients
+            .iter()
+            .fold((T::one(), T::zero()), |(a_acc, b_acc), (a, b)| {
+                (*a * a_acc, *a * b_acc + *b)
+            });
+        if a_total.is_one() {
+            return None;
+        }
+
+        let mut values = Vec::with_capacity(self.degree as usize);
+        let mut x = b_total / (T::one() - a_total);
+        for (a, b) in &coefficients {
+            values.push(x);
+            x = *a * x + *b;
+        }
+        Some(values)
+    }
+
+    /// Evaluates `expr` on `row`, with the values of witness references given by `witness`.
+    fn evaluate(
+        &self,
+        expr: &Expression<T>,
+        row: DegreeType,
+        witness: &impl Fn(&AlgebraicReference) -> T,
+    ) -> Option<T> {
+        Some(match expr {
+            Expression::Reference(r) => match r.poly_id.ptype {
+                PolynomialType::Committed => witness(r),
+                PolynomialType::Constant => {
+                    let row = (row + r.next as DegreeType) % self.degree;
+                    self.fixed[r.poly_id.id as usize].1[row as usize]
+                }
+                PolynomialType::Intermediate => return None,
+            },
+            Expression::PublicReference(_) => return None,
+            Expression::Number(n) => *n,
+            Expression::BinaryOperation(left, op, right) => {
+                let left = self.evaluate(left, row, witness)?;
+                let right = self.evaluate(right, row, witness)?;
+                match op {
+                    AlgebraicBinaryOperator::Add => left + right,
+                    AlgebraicBinaryOperator::Sub => left - right,
+                    AlgebraicBinaryOperator::Mul => left * right,
+                    AlgebraicBinaryOperator::Pow => left.pow(right.to_integer()),
+                }
+            }
+            Expression::UnaryOperation(op, inner) => {
+                let inner = self.evaluate(inner, row, witness)?;
+                match op {
+                    AlgebraicUnaryOperator::Plus => inner,
+                    AlgebraicUnaryOperator::Minus => -inner,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+
+    use crate::constant_evaluator::generate;
+    use crate::witgen::{unused_query_callback, WitnessGenerator};
+
+    use super::*;
+
+    fn affine_columns(src: &str) -> AffineColumns<GoldilocksField> {
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let identities = analyzed.identities_with_inlined_intermediate_polynomials();
+        compute_affine_columns(&analyzed, &constants, &identities, &Default::default())
+    }
+
+    #[test]
+    fn running_sum() {
+        let src = r#"
+namespace Sum(8);
+    pol fixed LAST(i) { i == 7 };
+    pol fixed STEP(i) { i * 3 };
+    col witness sum;
+    sum' = (1 - LAST) * (sum + STEP);
+"#;
+        let AffineColumns { columns, .. } = affine_columns(src);
+        let expected: Vec<GoldilocksField> = [0, 0, 3, 9, 18, 30, 45, 63]
+            .into_iter()
+            .map(GoldilocksField::from)
+            .collect();
+        assert_eq!(columns, vec![("Sum.sum".to_string(), expected)]);
+    }
+
+    #[test]
+    fn same_as_solver() {
+        let src = r#"
+namespace Acc(1024);
+    pol fixed LAST(i) { i == 1023 };
+    pol fixed COEFF(i) { i % 7 + 1 };
+    col witness acc;
+    col witness other;
+    acc' = (1 - LAST) * (COEFF * acc + 5);
+    other = acc * 2;
+"#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fast = WitnessGenerator::new(&analyzed, &constants, unused_query_callback()).generate();
+        let start = Instant::now();
+        let generic = WitnessGenerator::new(&analyzed, &constants, unused_query_callback())
+            .with_affine_fast_path(false)
+            .generate();
+        let generic_elapsed = start.elapsed();
+        assert_eq!(fast, generic);
+
+        let AffineColumns { columns, elapsed } = affine_columns(src);
+        assert_eq!(columns.len(), 1);
+        assert_eq!(columns[0], fast[0]);
+        // Computing the column directly is faster than walking the rows with the solver.
+        assert!(
+            elapsed < generic_elapsed,
+            "{elapsed:?} vs. {generic_elapsed:?}"
+        );
+    }
+
+    #[test]
+    fn not_affine() {
+        let src = r#"
+namespace N(8);
+    pol fixed LAST(i) { i == 7 };
+    col witness x, y;
+    // Quadratic in x.
+    x' = (1 - LAST) * (x * x + 1);
+    // References another witness column.
+    y' = (1 - LAST) * (y + x);
+"#;
+        assert!(affine_columns(src).columns.is_empty());
+    }
+
+    #[test]
+    fn underdetermined() {
+        // The recurrence does not determine the first row.
+        let src = r#"
+namespace N(8);
+    col witness x;
+    x' = x;
+"#;
+        assert!(affine_columns(src).columns.is_empty());
+    }
+}
@@ -1,16 +1,19 @@
 use std::{
     collections::HashSet,
+    mem,
     ops::{Index, IndexMut},
+    path::Path,
 };
 
 use ast::analyzed::PolyID;
 use bit_vec::BitVec;
 use number::FieldElement;
 
-use crate::witgen::rows::Row;
+use crate::witgen::rows::{Cell, Row};
+
+use super::spilled_rows::SpilledRows;
 
 /// A row entry in [FinalizableData].
-#[derive(Clone)]
 enum Entry<'a, T: FieldElement> {
     /// The row is still in progress, and range constraints are still available.
     InProgress(Row<'a, T>),
@@ -18,6 +21,8 @@ enum Entry<'a, T: FieldElement> {
     /// stored in [FinalizableData]) and a bit vector indicating which cells are known.
     /// The value of unknown cells should be ignored.
     Finalized(Vec<T>, BitVec),
+    /// A finalized row that was moved to a spill file, see [FinalizableData::spill_finalized].
+    Spilled,
 }
 
 /// A data structure that stores rows of a witness table, and behaves much like a `Vec<Row<T>>`.
@@ -25,12 +30,18 @@ enum Entry<'a, T: FieldElement> {
 /// constraints is freed. The information which cells are known is preserved, though.
 /// Once a row has been finalized, any operation trying to access it again will fail at runtime.
 /// [FinalizableData::take_transposed] can be used to access the final cells.
-#[derive(Clone)]
+/// Finalized rows can be moved out of memory with [FinalizableData::spill_finalized].
 pub struct FinalizableData<'a, T: FieldElement> {
     /// The list of rows (either in progress or finalized)
     data: Vec<Entry<'a, T>>,
     /// The list of column IDs (in sorted order), used to index finalized rows.
     column_ids: Vec<PolyID>,
+    /// The number of finalized rows, used to estimate the memory usage.
+    finalized_count: usize,
+    /// The number of spilled rows, used to estimate the memory usage.
+    spilled_count: usize,
+    /// The files with the spilled rows, together with the offset of their row indices.
+    spilled: Vec<(usize, SpilledRows<T>)>,
 }
 
 impl<'a, T: FieldElement> FinalizableData<'a, T> {
@@ -45,7 +56,13 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         let mut column_ids = column_ids.iter().cloned().collect::<Vec<_>>();
         column_ids.sort();
         let data = rows.map(Entry::InProgress).collect::<Vec<_>>();
-        Self { data, column_ids }
+        Self {
+            data,
+            column_ids,
+            finalized_count: 0,
+            spilled_count: 0,
+            spilled: vec![],
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -63,37 +80,76 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
     pub fn pop(&mut self) -> Option<Row<'a, T>> {
         match self.data.pop() {
             Some(Entry::InProgress(row)) => Some(row),
-            Some(Entry::Finalized(_, _)) => panic!("Row already finalized."),
+            Some(_) => panic!("Row already finalized."),
             None => None,
         }
     }
 
     pub fn extend(&mut self, other: Self) {
+        let offset = self.data.len();
+        self.finalized_count += other.finalized_count;
+        self.spilled_count += other.spilled_count;
+        self.spilled.extend(
+            other
+                .spilled
+                .into_iter()
+                .map(|(other_offset, rows)| (offset + other_offset, rows)),
+        );
         self.data.extend(other.data);
     }
 
     pub fn remove(&mut self, i: usize) -> Row<'a, T> {
         match self.data.remove(i) {
             Entry::InProgress(row) => row,
-            Entry::Finalized(_, _) => panic!("Row {} already finalized.", i),
+            _ => panic!("Row {} already finalized.", i),
         }
     }
 
+    /// Removes the rows from `len` on. Spilled rows stay in the spill file, but are
+    /// ignored when the data is transposed.
     pub fn truncate(&mut self, len: usize) {
+        for entry in self.data.iter().skip(len) {
+            match entry {
+                Entry::InProgress(_) => {}
+                Entry::Finalized(_, _) => self.finalized_count -= 1,
+                Entry::Spilled => self.spilled_count -= 1,
+            }
+        }
         self.data.truncate(len);
     }
 
+    /// Returns an estimate of the number of bytes used by the rows.
+    pub fn estimated_size_bytes(&self) -> u64 {
+        let in_progress = self.data.len() - self.finalized_count - self.spilled_count;
+        let in_progress_row_size = self.column_ids.len() * mem::size_of::<Cell<'a, T>>();
+        let finalized_row_size = self.column_ids.len() * mem::size_of::<T>()
+            + self.column_ids.len() / 8
+            + mem::size_of::<Entry<'a, T>>();
+        let spilled_row_size = mem::size_of::<Entry<'a, T>>();
+        (in_progress * in_progress_row_size
+            + self.finalized_count * finalized_row_size
+            + self.spilled_count * spilled_row_size) as u64
+    }
+
+    /// Returns the ith row if it exists and is still in progress.
+    pub fn get(&self, i: usize) -> Option<&Row<'a, T>> {
+        match self.data.get(i) {
+            Some(Entry::InProgress(row)) => Some(row),
+            _ => None,
+        }
+    }
+
     pub fn get_mut(&mut self, i: usize) -> Option<&mut Row<'a, T>> {
         match &mut self.data[i] {
             Entry::InProgress(row) => Some(row),
-            Entry::Finalized(_, _) => panic!("Row {} already finalized.", i),
+            _ => panic!("Row {} already finalized.", i),
         }
     }
 
     pub fn last(&self) -> Option<&Row<'a, T>> {
         match self.data.last() {
             Some(Entry::InProgress(row)) => Some(row),
-            Some(Entry::Finalized(_, _)) => panic!("Last row already finalized."),
+            Some(_) => panic!("Last row already finalized."),
             None => None,
         }
     }
@@ -116,6 +172,7 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
                 .map(|c| (row[c].value.unwrap_or_default(), row[c].value.is_known()))
                 .unzip();
             self.data[i] = Entry::Finalized(values, known_cells);
+            self.finalized_count += 1;
             true
         } else {
             false
@@ -128,6 +185,32 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         }
     }
 
+    /// Moves all finalized rows to a spill file in `dir`, from where they are only
+    /// read back by [FinalizableData::take_transposed].
+    pub fn spill_finalized(&mut self, dir: &Path) {
+        if self.finalized_count == 0 {
+            return;
+        }
+        // Rows are appended to a file with offset 0, so that the row indices are unchanged.
+        let position = match self.spilled.iter().position(|(offset, _)| *offset == 0) {
+            Some(position) => position,
+            None => {
+                let rows = SpilledRows::create(dir, self.column_ids.len());
+                self.spilled.push((0, rows));
+                self.spilled.len() - 1
+            }
+        };
+        let rows = &mut self.spilled[position].1;
+        for (i, entry) in self.data.iter_mut().enumerate() {
+            if let Entry::Finalized(values, known_cells) = entry {
+                rows.push(i, values, known_cells);
+                *entry = Entry::Spilled;
+            }
+        }
+        self.spilled_count += self.finalized_count;
+        self.finalized_count = 0;
+    }
+
     /// Takes all data out of the [FinalizableData] and returns it as a list of columns.
     /// Columns are represented as a tuple of:
     /// - A list of values
@@ -150,22 +233,49 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         // Store transposed columns in vectors for performance reasons
         let mut columns = vec![vec![]; self.column_ids.len()];
         let mut known_cells_col = vec![BitVec::new(); self.column_ids.len()];
-        for row in std::mem::take(&mut self.data) {
+        let data = std::mem::take(&mut self.data);
+        for row in &data {
             match row {
                 Entry::InProgress(_) => unreachable!(),
                 Entry::Finalized(row, known_cells) => {
-                    for (col_index, (value, is_known)) in
-                        row.into_iter().zip(known_cells).enumerate()
-                    {
+                    for (col_index, (value, is_known)) in row.iter().zip(known_cells).enumerate() {
                         known_cells_col[col_index].push(is_known);
-                        columns[col_index].push(value);
+                        columns[col_index].push(*value);
+                    }
+                }
+                // Filled in from the spill files below.
+                Entry::Spilled => {
+                    for (column, known_cells) in columns.iter_mut().zip(&mut known_cells_col) {
+                        known_cells.push(false);
+                        column.push(Default::default());
                     }
                 }
             }
         }
 
+        if !self.spilled.is_empty() {
+            log::debug!("Reading back {} spilled rows...", self.spilled_count);
+        }
+        for (offset, mut rows) in std::mem::take(&mut self.spilled) {
+            for (i, row, known_cells) in rows.read() {
+                let i = offset + i;
+                // Rows that were truncated (and possibly re-added) are ignored.
+                if !matches!(data.get(i), Some(Entry::Spilled)) {
+                    continue;
+                }
+                for (col_index, (value, is_known)) in row.into_iter().zip(&known_cells).enumerate()
+                {
+                    known_cells_col[col_index].set(i, is_known);
+                    columns[col_index][i] = value;
+                }
+            }
+        }
+
         log::debug!("Done transposing.");
 
+        self.finalized_count = 0;
+        self.spilled_count = 0;
+
         // Pair columns with their IDs
         let column_ids = std::mem::take(&mut self.column_ids);
         columns.into_iter().zip(known_cells_col).enumerate().map(
@@ -182,7 +292,7 @@ impl<'a, T: FieldElement> Index<usize> for FinalizableData<'a, T> {
     fn index(&self, index: usize) -> &Self::Output {
         match &self.data[index] {
             Entry::InProgress(row) => row,
-            Entry::Finalized(_, _) => panic!("Row {} already finalized.", index),
+            _ => panic!("Row {} already finalized.", index),
         }
     }
 }
@@ -191,7 +301,7 @@ impl<'a, T: FieldElement> IndexMut<usize> for FinalizableData<'a, T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         match &mut self.data[index] {
             Entry::InProgress(row) => row,
-            Entry::Finalized(_, _) => panic!("Row {} already finalized.", index),
+            _ => panic!("Row {} already finalized.", index),
         }
     }
 }
@@ -1,2 +1,3 @@
 pub mod column_map;
 pub mod finalizable_data;
+pub mod spilled_rows;
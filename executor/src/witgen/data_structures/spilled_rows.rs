@@ -0,0 +1,101 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bit_vec::BitVec;
+use number::FieldElement;
+
+/// Used to give each spill file of the process a unique name.
+static SPILL_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Finalized rows that were moved out of memory into a temporary file.
+/// Each row is stored as its index, the raw little-endian values and the bit vector
+/// of known cells. The file is removed when the [SpilledRows] are dropped.
+pub struct SpilledRows<T> {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    /// The number of values per row.
+    width: usize,
+    /// The number of rows written.
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: FieldElement> SpilledRows<T> {
+    /// Creates a new spill file in `dir`, for rows with `width` values.
+    pub fn create(dir: &Path, width: usize) -> Self {
+        let path = dir.join(format!(
+            "powdr-witgen-rows-{}-{}.bin",
+            std::process::id(),
+            SPILL_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .unwrap_or_else(|e| panic!("Could not create spill file {}: {e}", path.display()));
+        log::debug!("Spilling finalized rows to {}.", path.display());
+        Self {
+            path,
+            writer: BufWriter::new(file),
+            width,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The size of a row in the file.
+    fn row_size_bytes(&self) -> usize {
+        8 + self.width * value_size_bytes::<T>() + (self.width + 7) / 8
+    }
+
+    pub fn push(&mut self, index: usize, values: &[T], known_cells: &BitVec) {
+        assert_eq!(values.len(), self.width);
+        self.writer
+            .write_all(&(index as u64).to_le_bytes())
+            .unwrap();
+        for value in values {
+            self.writer.write_all(&value.to_bytes_le()).unwrap();
+        }
+        self.writer.write_all(&known_cells.to_bytes()).unwrap();
+        self.len += 1;
+    }
+
+    /// Reads all rows back, in the order they were written.
+    pub fn read(&mut self) -> impl Iterator<Item = (usize, Vec<T>, BitVec)> + '_ {
+        self.writer.flush().unwrap();
+        let mut file = self.writer.get_ref();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut reader = BufReader::new(file);
+        let (width, value_size) = (self.width, value_size_bytes::<T>());
+        let mut buf = vec![0u8; self.row_size_bytes()];
+        (0..self.len).map(move |_| {
+            reader.read_exact(&mut buf).unwrap();
+            let (index, rest) = buf.split_at(8);
+            let (values, known_cells) = rest.split_at(width * value_size);
+            let index = u64::from_le_bytes(index.try_into().unwrap()) as usize;
+            let values = values.chunks(value_size).map(T::from_bytes_le).collect();
+            let mut known_cells = BitVec::from_bytes(known_cells);
+            known_cells.truncate(width);
+            (index, values, known_cells)
+        })
+    }
+}
+
+impl<T> Drop for SpilledRows<T> {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Could not remove spill file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// The number of bytes of a serialized field element.
+fn value_size_bytes<T: FieldElement>() -> usize {
+    (T::BITS as usize + 63) / 64 * 8
+}
@@ -0,0 +1,112 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::Mutex;
+
+use number::{DegreeType, FieldElement};
+
+/// The number of rows fetched from an [ExternalWitnessProvider] at once.
+pub const EXTERNAL_WITNESS_CHUNK_SIZE: DegreeType = 1 << 10;
+
+/// Provides externally computed witness values on demand: called with the name of a
+/// witness column and a range of rows, it returns the values of the column on these
+/// rows, or `None` if the column is not computed externally.
+pub trait ExternalWitnessProvider<T>:
+    Fn(&str, Range<DegreeType>) -> Option<Vec<T>> + Send + Sync
+{
+}
+impl<T, F> ExternalWitnessProvider<T> for F where
+    F: Fn(&str, Range<DegreeType>) -> Option<Vec<T>> + Send + Sync
+{
+}
+
+/// @returns a provider that serves the given full columns.
+/// Panics if a column does not have exactly `degree` values.
+pub fn external_witness_values_provider<'a, T: FieldElement>(
+    values: Vec<(&'a str, Vec<T>)>,
+    degree: DegreeType,
+) -> impl ExternalWitnessProvider<T> + 'a {
+    let values = BTreeMap::from_iter(values);
+    for (name, column) in &values {
+        assert_eq!(
+            column.len() as DegreeType,
+            degree,
+            "External witness values for column {name} do not match the degree."
+        );
+    }
+    move |name: &str, rows: Range<DegreeType>| {
+        values
+            .get(name)
+            .map(|column| column[rows.start as usize..rows.end as usize].to_vec())
+    }
+}
+
+/// The values of a column fetched from the provider so far, by chunk index.
+/// `None` if the provider does not compute the column.
+type FetchedChunks<T> = Option<HashMap<DegreeType, Vec<T>>>;
+
+/// Externally computed witness values that are fetched in chunks from an
+/// [ExternalWitnessProvider] when a cell of a column is first needed.
+pub struct ExternalWitness<'a, T> {
+    provider: Option<Box<dyn ExternalWitnessProvider<T> + 'a>>,
+    degree: DegreeType,
+    fetched: Mutex<HashMap<String, FetchedChunks<T>>>,
+}
+
+impl<'a, T: FieldElement> ExternalWitness<'a, T> {
+    pub fn new(
+        provider: Option<Box<dyn ExternalWitnessProvider<T> + 'a>>,
+        degree: DegreeType,
+    ) -> Self {
+        ExternalWitness {
+            provider,
+            degree,
+            fetched: Default::default(),
+        }
+    }
+
+    /// Returns true if the provider computes the column.
+    pub fn provides(&self, column: &str) -> bool {
+        self.degree > 0 && self.value(column, 0).is_some()
+    }
+
+    /// Returns the external value of the column in the given row, if any.
+    pub fn value(&self, column: &str, row: DegreeType) -> Option<T> {
+        let provider = self.provider.as_ref()?;
+        let row = row % self.degree;
+        let chunk = row / EXTERNAL_WITNESS_CHUNK_SIZE;
+        let mut fetched = self.fetched.lock().unwrap();
+        let chunks = fetched
+            .entry(column.to_string())
+            .or_insert_with(|| Some(HashMap::new()))
+            .as_mut()?;
+        let values = match chunks.entry(chunk) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let rows = chunk * EXTERNAL_WITNESS_CHUNK_SIZE
+                    ..((chunk + 1) * EXTERNAL_WITNESS_CHUNK_SIZE).min(self.degree);
+                let Some(values) = provider(column, rows.clone()) else {
+                    fetched.insert(column.to_string(), None);
+                    return None;
+                };
+                assert_eq!(
+                    values.len() as DegreeType,
+                    rows.end - rows.start,
+                    "External witness provider returned {} values for rows {rows:?} of column {column}.",
+                    values.len()
+                );
+                entry.insert(values)
+            }
+        };
+        Some(values[(row % EXTERNAL_WITNESS_CHUNK_SIZE) as usize])
+    }
+
+    /// Returns all values of the column, if the provider computes it.
+    pub fn column(&self, column: &str) -> Option<Vec<T>> {
+        self.provides(column).then(|| {
+            (0..self.degree)
+                .map(|row| self.value(column, row).unwrap())
+                .collect()
+        })
+    }
+}
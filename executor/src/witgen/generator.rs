@@ -1,5 +1,6 @@
 use ast::analyzed::{
-    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference, Identity,
+    IdentityKind, PolyID,
 };
 use ast::parsed::SelectedExpressions;
 use number::{DegreeType, FieldElement};
@@ -18,8 +19,13 @@ use super::machines::{FixedLookup, Machine};
 use super::rows::{Row, RowFactory};
 use super::sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator};
 use super::vm_processor::VmProcessor;
+use super::witgen_error::{WitgenError, WitgenErrorKind};
 use super::{EvalResult, FixedData, MutableState, QueryCallback};
 
+/// The maximal number of rows before the end to which [Generator::propagate_wrap_around]
+/// propagates values.
+const MAX_WRAP_AROUND_ROWS: usize = 16;
+
 struct ProcessResult<'a, T: FieldElement> {
     eval_value: EvalValue<&'a AlgebraicReference, T>,
     block: FinalizableData<'a, T>,
@@ -51,18 +57,24 @@ impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
                 log::trace!("  {r} = {l}");
             }
 
-            let first_row = self
-                .data
-                .last()
-                .cloned()
-                .unwrap_or_else(|| self.compute_partial_first_row(mutable_state));
+            let first_row = match self.data.last() {
+                Some(row) => row.clone(),
+                None => match self.compute_partial_first_row(mutable_state) {
+                    Ok(row) => row,
+                    Err(e) => return Some(Err(e.to_string().into())),
+                },
+            };
 
             let outer_query = OuterQuery {
                 left: left.to_vec(),
                 right,
             };
             let ProcessResult { eval_value, block } =
-                self.process(first_row, 0, mutable_state, Some(outer_query));
+                match self.process(first_row, 0, mutable_state, Some(outer_query)) {
+                    Ok(result) => result,
+                    // The failure is reported as part of the failing lookup of the calling machine.
+                    Err(e) => return Some(Err(e.to_string().into())),
+                };
 
             if eval_value.is_complete() {
                 log::trace!("End processing VM '{}' (successfully)", self.name());
@@ -133,10 +145,169 @@ impl<'a, T: FieldElement> Generator<'a, T> {
     }
 
     /// Runs the machine without any arguments from the first row.
-    pub fn run<'b, Q: QueryCallback<T>>(&mut self, mutable_state: &mut MutableState<'a, 'b, T, Q>) {
+    pub fn run<'b, Q: QueryCallback<T>>(
+        &mut self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+    ) -> Result<(), WitgenError> {
         assert!(self.data.is_empty());
-        let first_row = self.compute_partial_first_row(mutable_state);
-        self.data = self.process(first_row, 0, mutable_state, None).block;
+        let first_row = self.compute_partial_first_row(mutable_state)?;
+        self.data = self.process(first_row, 0, mutable_state, None)?.block;
+        if self.data.len() as DegreeType == self.fixed_data.degree + 1 {
+            let mismatches = self.wrap_around_mismatches();
+            if !mismatches.is_empty() {
+                return Err(self.out_of_rows_error(mismatches));
+            }
+            if self.spans_wrap_around() {
+                self.propagate_wrap_around(mutable_state)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if an identity with a next reference might constrain the last row
+    /// together with the first row, i.e. it does not vanish on the last row because
+    /// of a fixed column like `(1 - LAST) * (x' - x) = 0`.
+    fn spans_wrap_around(&self) -> bool {
+        self.identities
+            .iter()
+            .filter(|identity| identity.contains_next_ref())
+            .any(|identity| match identity.kind {
+                IdentityKind::Polynomial => {
+                    !self.vanishes_on_last_row(identity.expression_for_poly_id())
+                }
+                _ => !matches!(&identity.left.selector, Some(s) if self.vanishes_on_last_row(s)),
+            })
+    }
+
+    /// Returns true if the expression is zero on the last row because of the values of
+    /// the fixed columns, regardless of the values of the witness columns.
+    fn vanishes_on_last_row(&self, e: &Expression<T>) -> bool {
+        match e {
+            Expression::Number(n) => n.is_zero(),
+            Expression::Reference(r) if r.is_fixed() => {
+                let values = self.fixed_data.fixed_cols[&r.poly_id].values;
+                let row = if r.next { 0 } else { values.len() - 1 };
+                values[row].is_zero()
+            }
+            Expression::BinaryOperation(left, op, right) => match op {
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                    self.vanishes_on_last_row(left) && self.vanishes_on_last_row(right)
+                }
+                AlgebraicBinaryOperator::Mul => {
+                    self.vanishes_on_last_row(left) || self.vanishes_on_last_row(right)
+                }
+                AlgebraicBinaryOperator::Pow => self.vanishes_on_last_row(left),
+            },
+            Expression::UnaryOperation(_, inner) => self.vanishes_on_last_row(inner),
+            _ => false,
+        }
+    }
+
+    /// The main pass computes the row after the last row (i.e. the first row again) only
+    /// from the last row. This runs the solver on the last row paired with the first row
+    /// (including all its known values) and propagates the newly determined values
+    /// backwards, up to [MAX_WRAP_AROUND_ROWS] rows.
+    fn propagate_wrap_around<Q: QueryCallback<T>>(
+        &mut self,
+        mutable_state: &mut MutableState<'a, '_, T, Q>,
+    ) -> Result<(), WitgenError> {
+        let degree = self.fixed_data.degree as usize;
+        let first_row = self.data[0].clone();
+        let after_last_row = &mut self.data[degree];
+        *after_last_row =
+            WitnessColumnMap::from(after_last_row.values().zip(first_row.values()).map(
+                |(cell1, cell2)| match (&cell1.value, &cell2.value) {
+                    (CellValue::Known(_), _) => cell1.clone(),
+                    _ => cell2.clone(),
+                },
+            ));
+
+        let known_cells =
+            |row: &Row<'a, T>| row.values().filter(|cell| cell.value.is_known()).count();
+        for row in (degree.saturating_sub(MAX_WRAP_AROUND_ROWS)..degree).rev() {
+            if self.data.get(row).is_none() {
+                // The row has already been finalized.
+                break;
+            }
+            let known_before = known_cells(&self.data[row]);
+            let data = FinalizableData::with_initial_rows_in_progress(
+                &self.witnesses,
+                [self.data[row].clone(), self.data[row + 1].clone()].into_iter(),
+            );
+            let mut processor = BlockProcessor::new(
+                row as DegreeType,
+                data,
+                mutable_state,
+                &self.identities,
+                self.fixed_data,
+                &self.witnesses,
+            );
+            let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                DefaultSequenceIterator::new(0, self.identities.len(), None),
+            );
+            let result = processor.solve(&mut sequence_iterator);
+            let mut rows = processor.finish();
+            if let Err(e) = result {
+                return Err(WitgenError {
+                    row: row as DegreeType,
+                    kind: WitgenErrorKind::Unsatisfiable,
+                    failing_identities: vec![e.to_string()],
+                    row_dump: format!(
+                        "{}\n{}",
+                        rows[0].render(&format!("Row {row}"), false, &self.witnesses),
+                        rows[1].render(
+                            &format!("Row {} (wrapping around)", row + 1),
+                            false,
+                            &self.witnesses
+                        )
+                    ),
+                    context: Default::default(),
+                });
+            }
+            self.data[row + 1] = rows.remove(1);
+            self.data[row] = rows.remove(0);
+            if known_cells(&self.data[row]) == known_before {
+                break;
+            }
+            log::debug!("Determined values in row {row} from the wrap-around constraints.");
+        }
+        Ok(())
+    }
+
+    /// Returns the columns whose known values in the first row and in the row after
+    /// the last row (which is the first row again) differ.
+    fn wrap_around_mismatches(&self) -> Vec<String> {
+        let last_row = self.data.last().unwrap();
+        self.data[0]
+            .values()
+            .zip(last_row.values())
+            .filter_map(|(cell1, cell2)| match (&cell1.value, &cell2.value) {
+                (CellValue::Known(v1), CellValue::Known(v2)) if v1 != v2 => Some(format!(
+                    "{} is {v1} in the first row, but {v2} after the last row",
+                    cell1.name
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn out_of_rows_error(&self, mismatches: Vec<String>) -> WitgenError {
+        let degree = self.fixed_data.degree;
+        WitgenError {
+            row: degree - 1,
+            kind: WitgenErrorKind::OutOfRows,
+            failing_identities: mismatches,
+            row_dump: format!(
+                "{}\n{}",
+                self.data.last().unwrap().render(
+                    &format!("Row after the last row ({degree})"),
+                    false,
+                    &self.witnesses
+                ),
+                self.data[0].render("First row (0)", false, &self.witnesses)
+            ),
+            context: Default::default(),
+        }
     }
 
     fn fill_remaining_rows<Q: QueryCallback<T>>(
@@ -147,12 +318,15 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             assert!(self.latch.is_some());
 
             let first_row = self.data.pop().unwrap();
-            let ProcessResult { block, eval_value } = self.process(
-                first_row,
-                self.data.len() as DegreeType,
-                mutable_state,
-                None,
-            );
+            // All blocks have been processed successfully, so the default block cannot fail.
+            let ProcessResult { block, eval_value } = self
+                .process(
+                    first_row,
+                    self.data.len() as DegreeType,
+                    mutable_state,
+                    None,
+                )
+                .unwrap_or_else(|e| panic!("Failed to fill the remaining rows of a machine: {e}"));
             assert!(eval_value.is_complete());
 
             self.data.extend(block);
@@ -164,7 +338,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
     fn compute_partial_first_row<Q: QueryCallback<T>>(
         &self,
         mutable_state: &mut MutableState<'a, '_, T, Q>,
-    ) -> Row<'a, T> {
+    ) -> Result<Row<'a, T>, WitgenError> {
         // Use `BlockProcessor` + `DefaultSequenceIterator` using a "block size" of 0. Because `BlockProcessor`
         // expects `data` to include the row before and after the block, this means we'll run the
         // solver on exactly one row pair.
@@ -191,10 +365,25 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         let mut sequence_iterator = ProcessingSequenceIterator::Default(
             DefaultSequenceIterator::new(0, self.identities.len(), None),
         );
-        processor.solve(&mut sequence_iterator).unwrap();
-        let first_row = processor.finish().remove(1);
-
-        first_row
+        let result = processor.solve(&mut sequence_iterator);
+        let mut rows = processor.finish();
+        match result {
+            Ok(_) => Ok(rows.remove(1)),
+            Err(e) => {
+                let last_row = self.fixed_data.degree - 1;
+                Err(WitgenError {
+                    row: 0,
+                    kind: WitgenErrorKind::Unsatisfiable,
+                    failing_identities: vec![e.to_string()],
+                    row_dump: format!(
+                        "{}\n{}",
+                        rows[0].render(&format!("Last row ({last_row})"), false, &self.witnesses),
+                        rows[1].render("First row (0)", false, &self.witnesses)
+                    ),
+                    context: Default::default(),
+                })
+            }
+        }
     }
 
     fn process<Q: QueryCallback<T>>(
@@ -203,7 +392,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         row_offset: DegreeType,
         mutable_state: &mut MutableState<'a, '_, T, Q>,
         outer_query: Option<OuterQuery<'a, T>>,
-    ) -> ProcessResult<'a, T> {
+    ) -> Result<ProcessResult<'a, T>, WitgenError> {
         log::trace!(
             "Running main machine from row {row_offset} with the following initial values in the first row:\n{}", first_row.render_values(false, None)
         );
@@ -214,6 +403,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         );
         let mut processor = VmProcessor::new(
             row_offset,
+            self.name(),
             self.fixed_data,
             &self.identities,
             &self.witnesses,
@@ -224,23 +414,25 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         if let Some(outer_query) = outer_query {
             processor = processor.with_outer_query(outer_query);
         }
-        let eval_value = processor.run();
+        let eval_value = processor.run()?;
         let block = processor.finish();
-        ProcessResult { eval_value, block }
+        Ok(ProcessResult { eval_value, block })
     }
 
     /// At the end of the solving algorithm, we'll have computed the first row twice
     /// (as row 0 and as row <degree>). This function merges the two versions.
     fn fix_first_row(&mut self) {
         assert_eq!(self.data.len() as DegreeType, self.fixed_data.degree + 1);
+        let mismatches = self.wrap_around_mismatches();
+        assert!(
+            mismatches.is_empty(),
+            "{}",
+            self.out_of_rows_error(mismatches)
+        );
 
         let last_row = self.data.pop().unwrap();
         self.data[0] = WitnessColumnMap::from(self.data[0].values().zip(last_row.values()).map(
             |(cell1, cell2)| match (&cell1.value, &cell2.value) {
-                (CellValue::Known(v1), CellValue::Known(v2)) => {
-                    assert_eq!(v1, v2);
-                    cell1.clone()
-                }
                 (CellValue::Known(_), _) => cell1.clone(),
                 _ => cell2.clone(),
             },
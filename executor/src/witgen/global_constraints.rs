@@ -1,17 +1,20 @@
 use std::collections::{BTreeMap, BTreeSet};
 
+use itertools::Itertools;
 use num_traits::Zero;
 
 use ast::analyzed::{
-    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference, Identity,
-    IdentityKind, PolyID, PolynomialType,
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference, Analyzed,
+    Identity, IdentityKind, PolyID, PolynomialType,
 };
+use serde::Serialize;
 
-use number::FieldElement;
+use number::{log2_exact, FieldElement};
 
 use crate::witgen::data_structures::column_map::{FixedColumnMap, WitnessColumnMap};
 
 use super::expression_evaluator::ExpressionEvaluator;
+use super::machines::machine_extractor::refs_in_identity;
 use super::range_constraints::RangeConstraint;
 use super::symbolic_evaluator::SymbolicEvaluator;
 use super::util::try_to_simple_poly;
@@ -53,6 +56,46 @@ impl<T: FieldElement> RangeConstraintSet<&AlgebraicReference, T> for GlobalConst
     }
 }
 
+/// A step in the derivation of a global range constraint on a column.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DerivationStep {
+    /// The constraint is given by the values of the fixed column.
+    FixedColumnValues,
+    /// The constraint is given by the `in [min, max]` annotation of the witness column.
+    Annotation,
+    /// The constraint follows from the identity and the constraints
+    /// on the other columns it references.
+    Identity {
+        identity: String,
+        columns: Vec<String>,
+    },
+}
+
+/// A global range constraint on a column, together with its derivation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ColumnRangeConstraint {
+    pub column: String,
+    /// The constraint, as `[min, max] & mask`.
+    pub constraint: String,
+    /// The values allowed by the constraint, as `[min, end)`, e.g. `[0, 2^16)`.
+    pub interval: String,
+    /// The number of bits the values of the column fit into.
+    pub bits: u64,
+    /// The steps that (successively) narrowed the constraint.
+    pub derivation: Vec<DerivationStep>,
+}
+
+/// The global range constraints derived from the fixed columns and the identities.
+struct Propagation<'a, T: FieldElement> {
+    known_constraints: BTreeMap<PolyID, RangeConstraint<T>>,
+    /// The constraints on fixed columns given by their values.
+    fixed_constraints: FixedColumnMap<Option<RangeConstraint<T>>>,
+    /// How the constraint on each column was derived.
+    derivations: BTreeMap<PolyID, Vec<DerivationStep>>,
+    retained_identities: Vec<&'a Identity<Expression<T>>>,
+    removed_identities: Vec<&'a Identity<Expression<T>>>,
+}
+
 /// Determines global constraints on witness and fixed columns.
 /// Removes identities that only serve to create range constraints from
 /// the identities vector and returns the remaining identities.
@@ -61,37 +104,13 @@ pub fn determine_global_constraints<'a, T: FieldElement>(
     fixed_data: &'a FixedData<T>,
     identities: Vec<&'a Identity<Expression<T>>>,
 ) -> (GlobalConstraints<T>, Vec<&'a Identity<Expression<T>>>) {
-    let mut known_constraints = BTreeMap::new();
-    // For these columns, we know that they are not only constrained to those bits
-    // but also have one row for each possible value.
-    // It allows us to completely remove some lookups.
-    let mut full_span = BTreeSet::new();
-    for (poly_id, col) in fixed_data.fixed_cols.iter() {
-        if let Some((cons, full)) = process_fixed_column(col.values) {
-            assert!(known_constraints.insert(poly_id, cons).is_none());
-            if full {
-                full_span.insert(poly_id);
-            }
-        }
-    }
-    let fixed_constraints = FixedColumnMap::from_indexed(
-        known_constraints.iter().map(|(p, c)| (*p, Some(c.clone()))),
-        fixed_data.fixed_cols.len(),
-    );
-
-    let mut retained_identities = vec![];
-    let mut removed_identities = vec![];
-    for identity in identities {
-        let remove;
-        (known_constraints, remove) =
-            propagate_constraints(known_constraints, identity, &full_span);
-        (if remove {
-            &mut removed_identities
-        } else {
-            &mut retained_identities
-        })
-        .push(identity);
-    }
+    let Propagation {
+        known_constraints,
+        fixed_constraints,
+        retained_identities,
+        removed_identities,
+        ..
+    } = propagate_global_constraints(fixed_data, identities);
 
     log::debug!("Determined the following global range constraints:");
     for (poly_id, con) in &known_constraints {
@@ -128,6 +147,208 @@ pub fn determine_global_constraints<'a, T: FieldElement>(
     )
 }
 
+/// Determines the global constraints on all columns like [determine_global_constraints]
+/// and returns them together with their derivations, witness columns first.
+pub fn derive_column_range_constraints<T: FieldElement>(
+    fixed_data: &FixedData<T>,
+    identities: Vec<&Identity<Expression<T>>>,
+) -> Vec<ColumnRangeConstraint> {
+    let Propagation {
+        known_constraints,
+        mut derivations,
+        ..
+    } = propagate_global_constraints(fixed_data, identities);
+    known_constraints
+        .into_iter()
+        .sorted_by_key(|(poly_id, _)| (poly_id.ptype, poly_id.id))
+        .map(|(poly_id, constraint)| ColumnRangeConstraint {
+            column: fixed_data.column_name(&poly_id).to_string(),
+            constraint: constraint.to_string(),
+            interval: format_interval(&constraint),
+            bits: constraint.bit_width(),
+            derivation: derivations.remove(&poly_id).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Formats the range of a constraint as a half-open interval, with
+/// an end that is a power of two shown as `2^bits`.
+fn format_interval<T: FieldElement>(constraint: &RangeConstraint<T>) -> String {
+    let (min, max) = constraint.range();
+    let (min, max) = (min.to_arbitrary_integer(), max.to_arbitrary_integer());
+    if min > max {
+        return format!("[{min}, {max}] wrapping around the modulus");
+    }
+    let end = max + 1u32;
+    match log2_exact(end.clone()) {
+        Some(bits) => format!("[{min}, 2^{bits})"),
+        None => format!("[{min}, {end})"),
+    }
+}
+
+/// Checks that the derived constraints are at least as tight as
+/// required by the `#[assert_range(bits)]` attributes of the witness columns.
+/// Each error contains the derivation of the constraint on the column.
+pub fn check_range_assertions<T>(
+    analyzed: &Analyzed<T>,
+    constraints: &[ColumnRangeConstraint],
+) -> Result<(), Vec<String>> {
+    let constraints = constraints
+        .iter()
+        .map(|c| (c.column.as_str(), c))
+        .collect::<BTreeMap<_, _>>();
+    let errors = analyzed
+        .committed_polys_in_source_order()
+        .into_iter()
+        .filter_map(|(symbol, _)| symbol.assert_range.map(|bits| (symbol, bits)))
+        .flat_map(|(symbol, bits)| symbol.array_elements().map(move |(name, _)| (name, bits)))
+        .filter_map(|(name, bits)| match constraints.get(name.as_str()) {
+            None => Some(format!(
+                "Column {name} is asserted to fit into {bits} bits, but no range constraint could be derived for it."
+            )),
+            Some(constraint) if constraint.bits > bits => Some(format!(
+                "Column {name} is asserted to fit into {bits} bits, but the derived range constraint only fits into {} bits. It was derived as follows:\n{}",
+                constraint.bits,
+                format_derivation(constraint, &constraints)
+            )),
+            Some(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Formats the derivation of a constraint, followed by the derivations of the
+/// constraints it depends on, indented.
+fn format_derivation(
+    constraint: &ColumnRangeConstraint,
+    constraints: &BTreeMap<&str, &ColumnRangeConstraint>,
+) -> String {
+    fn format_rec(
+        constraint: &ColumnRangeConstraint,
+        constraints: &BTreeMap<&str, &ColumnRangeConstraint>,
+        visited: &mut BTreeSet<String>,
+        depth: usize,
+        out: &mut String,
+    ) {
+        let indent = "  ".repeat(depth + 1);
+        out.push_str(&format!(
+            "{indent}{}: {} ({} bits)\n",
+            constraint.column, constraint.constraint, constraint.bits
+        ));
+        if !visited.insert(constraint.column.clone()) {
+            return;
+        }
+        for step in &constraint.derivation {
+            match step {
+                DerivationStep::FixedColumnValues => {
+                    out.push_str(&format!("{indent}  from the values of the fixed column\n"));
+                }
+                DerivationStep::Annotation => {
+                    out.push_str(&format!("{indent}  from the annotation of the column\n"));
+                }
+                DerivationStep::Identity { identity, columns } => {
+                    out.push_str(&format!("{indent}  from `{identity}`\n"));
+                    for column in columns {
+                        if let Some(input) = constraints.get(column.as_str()) {
+                            format_rec(input, constraints, visited, depth + 2, out);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut out = String::new();
+    format_rec(
+        constraint,
+        constraints,
+        &mut Default::default(),
+        0,
+        &mut out,
+    );
+    out
+}
+
+/// Derives range constraints from the values of the fixed columns and
+/// propagates them through the identities.
+fn propagate_global_constraints<'a, T: FieldElement>(
+    fixed_data: &FixedData<T>,
+    identities: Vec<&'a Identity<Expression<T>>>,
+) -> Propagation<'a, T> {
+    let mut known_constraints = BTreeMap::new();
+    let mut derivations: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    // For these columns, we know that they are not only constrained to those bits
+    // but also have one row for each possible value.
+    // It allows us to completely remove some lookups.
+    let mut full_span = BTreeSet::new();
+    for (poly_id, col) in fixed_data.fixed_cols.iter() {
+        if let Some((cons, full)) = process_fixed_column(col.values) {
+            assert!(known_constraints.insert(poly_id, cons).is_none());
+            derivations.insert(poly_id, vec![DerivationStep::FixedColumnValues]);
+            if full {
+                full_span.insert(poly_id);
+            }
+        }
+    }
+    let fixed_constraints = FixedColumnMap::from_indexed(
+        known_constraints.iter().map(|(p, c)| (*p, Some(c.clone()))),
+        fixed_data.fixed_cols.len(),
+    );
+    for (poly_id, col) in fixed_data.witness_cols.iter() {
+        if let Some((min, max)) = col.range {
+            known_constraints.insert(poly_id, RangeConstraint::from_range(min, max));
+            derivations.insert(poly_id, vec![DerivationStep::Annotation]);
+        }
+    }
+
+    let mut retained_identities = vec![];
+    let mut removed_identities = vec![];
+    for identity in identities {
+        let refs = refs_in_identity(identity);
+        let previous = refs
+            .iter()
+            .map(|poly_id| (*poly_id, known_constraints.get(poly_id).cloned()))
+            .collect::<BTreeMap<_, _>>();
+        let remove;
+        (known_constraints, remove) =
+            propagate_constraints(known_constraints, identity, &full_span);
+        for (poly_id, previous_constraint) in &previous {
+            let current = known_constraints.get(poly_id);
+            if current.is_some() && current != previous_constraint.as_ref() {
+                let columns = previous
+                    .iter()
+                    .filter(|(p, c)| *p != poly_id && c.is_some())
+                    .map(|(p, _)| fixed_data.column_name(p).to_string())
+                    .collect();
+                derivations
+                    .entry(*poly_id)
+                    .or_default()
+                    .push(DerivationStep::Identity {
+                        identity: identity.to_string(),
+                        columns,
+                    });
+            }
+        }
+        (if remove {
+            &mut removed_identities
+        } else {
+            &mut retained_identities
+        })
+        .push(identity);
+    }
+
+    Propagation {
+        known_constraints,
+        fixed_constraints,
+        derivations,
+        retained_identities,
+        removed_identities,
+    }
+}
+
 /// Analyzes a fixed column and checks if its values correspond exactly
 /// to a certain bit pattern.
 /// TODO do this on the symbolic definition instead of the values.
@@ -163,9 +384,12 @@ fn propagate_constraints<T: FieldElement>(
     match identity.kind {
         IdentityKind::Polynomial => {
             if let Some(p) = is_binary_constraint(identity.expression_for_poly_id()) {
-                assert!(known_constraints
-                    .insert(p, RangeConstraint::from_max_bit(0))
-                    .is_none());
+                // The column could already be constrained by an annotation.
+                let c = RangeConstraint::from_max_bit(0);
+                known_constraints
+                    .entry(p)
+                    .and_modify(|existing| *existing = existing.conjunction(&c))
+                    .or_insert(c);
                 remove = true;
             } else {
                 for (p, c) in
@@ -447,4 +671,98 @@ namespace Global(1024);
         );
         assert!(!removed);
     }
+
+    #[test]
+    fn report_byte_constrained_column() {
+        let pil_source = r"
+namespace Global(1024);
+    col fixed BYTE(i) { i & 0xff };
+    col witness A;
+    col witness B;
+    { A } in { BYTE };
+    B = A * 4;
+";
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(pil_source);
+        let constants = crate::constant_evaluator::generate(&analyzed);
+        let report = crate::witgen::range_constraint_report(&analyzed, &constants);
+        assert_eq!(
+            report,
+            vec![
+                ColumnRangeConstraint {
+                    column: "Global.A".to_string(),
+                    constraint: RangeConstraint::<GoldilocksField>::from_max_bit(7).to_string(),
+                    interval: "[0, 2^8)".to_string(),
+                    bits: 8,
+                    derivation: vec![DerivationStep::Identity {
+                        identity: "{ Global.A } in { Global.BYTE };".to_string(),
+                        columns: vec!["Global.BYTE".to_string()],
+                    }],
+                },
+                ColumnRangeConstraint {
+                    column: "Global.B".to_string(),
+                    constraint: RangeConstraint::<GoldilocksField>::from_mask(0x3fc_u32)
+                        .to_string(),
+                    interval: "[0, 1021)".to_string(),
+                    bits: 10,
+                    derivation: vec![DerivationStep::Identity {
+                        identity: "Global.B = (Global.A * 4);".to_string(),
+                        columns: vec!["Global.A".to_string()],
+                    }],
+                },
+                ColumnRangeConstraint {
+                    column: "Global.BYTE".to_string(),
+                    constraint: RangeConstraint::<GoldilocksField>::from_max_bit(7).to_string(),
+                    interval: "[0, 2^8)".to_string(),
+                    bits: 8,
+                    derivation: vec![DerivationStep::FixedColumnValues],
+                },
+            ]
+        );
+    }
+
+    fn check_assertions(pil_source: &str) -> Result<(), Vec<String>> {
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(pil_source);
+        let constants = crate::constant_evaluator::generate(&analyzed);
+        let report = crate::witgen::range_constraint_report(&analyzed, &constants);
+        check_range_assertions(&analyzed, &report)
+    }
+
+    #[test]
+    fn range_assertion_satisfied() {
+        let pil_source = r"
+namespace Global(1024);
+    col fixed BYTE(i) { i & 0xff };
+    #[assert_range(8)] col witness A;
+    #[assert_range(16)] col witness B;
+    { A } in { BYTE };
+    { B } in { BYTE };
+";
+        assert_eq!(check_assertions(pil_source), Ok(()));
+    }
+
+    #[test]
+    fn range_assertion_violated() {
+        let pil_source = r"
+namespace Global(1024);
+    col fixed BYTE(i) { i & 0xff };
+    col witness A;
+    #[assert_range(8)] col witness B;
+    #[assert_range(8)] col witness C;
+    { A } in { BYTE };
+    B = A * 4;
+";
+        let errors = check_assertions(pil_source).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with(
+            "Column Global.B is asserted to fit into 8 bits, but the derived range constraint only fits into 10 bits."
+        ));
+        // The derivation chain goes back to the fixed column.
+        assert!(errors[0].contains("from `Global.B = (Global.A * 4);`"));
+        assert!(errors[0].contains("from `{ Global.A } in { Global.BYTE };`"));
+        assert!(errors[0].contains("from the values of the fixed column"));
+        assert_eq!(
+            errors[1],
+            "Column Global.C is asserted to fit into 8 bits, but no range constraint could be derived for it."
+        );
+    }
 }
@@ -7,6 +7,7 @@ use ast::{
 use itertools::{Either, Itertools};
 use lazy_static::lazy_static;
 use number::FieldElement;
+use web_time::Instant;
 
 use crate::witgen::machines::Machine;
 
@@ -95,6 +96,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
         identity: &'a Identity<Expression<T>>,
         rows: &RowPair<'_, 'a, T>,
     ) -> EvalResult<'a, T> {
+        let start = self.fixed_data.witgen_profiler.map(|_| Instant::now());
         let result = match identity.kind {
             IdentityKind::Polynomial => self.process_polynomial_identity(identity, rows),
             IdentityKind::Plookup | IdentityKind::Permutation => {
@@ -107,6 +109,10 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
             }
         };
         report_identity_solving(identity, &result);
+        if let (Some(profiler), Some(start)) = (self.fixed_data.witgen_profiler, start) {
+            let success = result.as_ref().map(|r| r.is_complete()).unwrap_or_default();
+            profiler.record(identity, success, start.elapsed());
+        }
         result
     }
 
@@ -1,6 +1,7 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
 
 use super::{EvalResult, FixedData, FixedLookup};
+use crate::parallel::prelude::*;
 use crate::witgen::affine_expression::AffineExpression;
 
 use crate::witgen::block_processor::BlockProcessor;
@@ -14,8 +15,8 @@ use crate::witgen::util::try_to_simple_poly;
 use crate::witgen::{machines::Machine, EvalError, EvalValue, IncompleteCause};
 use crate::witgen::{MutableState, QueryCallback};
 use ast::analyzed::{
-    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
-    PolynomialType,
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference, Identity,
+    IdentityKind, PolyID, PolynomialType,
 };
 use ast::parsed::SelectedExpressions;
 use number::{DegreeType, FieldElement};
@@ -58,6 +59,10 @@ pub struct BlockMachine<'a, T: FieldElement> {
     data: FinalizableData<'a, T>,
     /// The set of witness columns that are actually part of this machine.
     witness_cols: HashSet<PolyID>,
+    /// The selector columns of the permutations into this machine, see
+    /// [split_permutation_selector]. They are zero in all rows that are not
+    /// called through the respective permutation.
+    permutation_selectors: HashSet<PolyID>,
     /// Cache that states the order in which to evaluate identities
     /// to make progress most quickly.
     processing_sequence_cache: ProcessingSequenceCache,
@@ -75,7 +80,11 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         // TODO we should check that the other constraints/fixed columns are also periodic.
         let periods = connecting_identities
             .iter()
-            .map(|id| try_to_period(&id.right.selector, fixed_data))
+            .map(|id| match id.kind {
+                IdentityKind::Permutation => split_permutation_selector(&id.right.selector)
+                    .and_then(|(latch, _)| try_to_period(Some(latch), fixed_data)),
+                _ => try_to_period(id.right.selector.as_ref(), fixed_data),
+            })
             .collect::<Vec<_>>();
 
         let period = periods[0].and_then(|first_period| {
@@ -111,6 +120,11 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             })
             .map(|(block_size, connecting_rhs)| {
                 assert!(block_size <= fixed_data.degree as usize);
+                let permutation_selectors = connecting_rhs
+                    .iter()
+                    .filter_map(|rhs| split_permutation_selector(&rhs.selector))
+                    .map(|(_, selector)| selector.poly_id)
+                    .collect();
                 let row_factory = RowFactory::new(fixed_data, global_range_constraints.clone());
                 // Start out with a block filled with unknown values so that we do not have to deal with wrap-around
                 // when storing machine witness data.
@@ -126,6 +140,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                     data,
                     row_factory,
                     witness_cols: witness_cols.clone(),
+                    permutation_selectors,
                     processing_sequence_cache: ProcessingSequenceCache::new(
                         block_size,
                         identities.len(),
@@ -142,7 +157,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
 /// TODO we could make this more generic and only detect the period
 /// but not enforce the offset.
 fn try_to_period<T: FieldElement>(
-    expr: &Option<Expression<T>>,
+    expr: Option<&Expression<T>>,
     fixed_data: &FixedData<T>,
 ) -> Option<usize> {
     match expr {
@@ -178,6 +193,22 @@ fn try_to_period<T: FieldElement>(
     }
 }
 
+/// Splits the right selector of a permutation into a block machine into the latch
+/// and the selector column, if it has the form `latch * selector`.
+/// Each permutation into a block machine has its own (witness) selector column,
+/// which is one in the latch row of the blocks called through this permutation.
+fn split_permutation_selector<T>(
+    selector: &Option<Expression<T>>,
+) -> Option<(&Expression<T>, &AlgebraicReference)> {
+    match selector {
+        Some(Expression::BinaryOperation(latch, AlgebraicBinaryOperator::Mul, selector)) => {
+            let selector = try_to_simple_poly(selector).filter(|s| s.is_witness())?;
+            Some((latch.as_ref(), selector))
+        }
+        _ => None,
+    }
+}
+
 impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
     fn process_plookup<'b, Q: QueryCallback<T>>(
         &mut self,
@@ -186,7 +217,9 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
         left: &[AffineExpression<&'a AlgebraicReference, T>],
         right: &'a SelectedExpressions<Expression<T>>,
     ) -> Option<EvalResult<'a, T>> {
-        if !self.connecting_rhs.contains(right) || kind != IdentityKind::Plookup {
+        if !self.connecting_rhs.contains(right)
+            || !matches!(kind, IdentityKind::Plookup | IdentityKind::Permutation)
+        {
             return None;
         }
         let previous_len = self.rows() as usize;
@@ -213,9 +246,15 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                  This might violate some internal constraints."
             );
         }
+        let block_size = self.block_size;
+        let degree = self.fixed_data.degree as usize;
+        let permutation_selectors = &self.permutation_selectors;
+        // The columns are independent, so they are completed in parallel.
         let mut data = self
             .data
             .take_transposed()
+            .collect::<Vec<_>>()
+            .into_par_iter()
             .map(|(id, (values, known_cells))| {
                 // Materialize column as Vec<Option<T>>
                 let mut values = values
@@ -226,9 +265,15 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
 
                 // For all constraints to be satisfied, unused cells have to be filled with valid values.
                 // We do this, we construct a default block, by repeating the first input to the block machine.
-                values.resize(self.fixed_data.degree as usize, None);
+                values.resize(degree, None);
 
-                let second_block_values = values.iter().skip(self.block_size).take(self.block_size);
+                // Unused blocks are not called, so they are not selected by any permutation.
+                if permutation_selectors.contains(&id) {
+                    let values = values.into_iter().map(|v| v.unwrap_or_default()).collect();
+                    return (id, values);
+                }
+
+                let second_block_values = values.iter().skip(block_size).take(block_size);
 
                 // The first block is a dummy block (filled mostly with None), the second block is the first block
                 // resulting of an actual evaluation.
@@ -239,7 +284,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                 // TODO: Determine the row-extend per column
                 let default_block = values
                     .iter()
-                    .take(self.block_size)
+                    .take(block_size)
                     .zip(second_block_values)
                     .map(|(first_block, second_block)| {
                         first_block.or(*second_block).unwrap_or_default()
@@ -249,7 +294,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                 let values = values
                     .into_iter()
                     .enumerate()
-                    .map(|(i, v)| v.unwrap_or(default_block[i % self.block_size]))
+                    .map(|(i, v)| v.unwrap_or(default_block[i % block_size]))
                     .collect::<Vec<_>>();
 
                 (id, values)
@@ -333,14 +378,26 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 UnknownStrategy::Unknown,
             );
 
-            let mut identity_processor = IdentityProcessor::new(self.fixed_data, mutable_state);
-            if let Ok(result) = identity_processor.process_link(left, right, &row_pair) {
-                if result.is_complete() && result.constraints.is_empty() {
-                    log::trace!(
-                        "End processing block machine '{}' (already solved)",
-                        self.name()
-                    );
-                    return Ok(result);
+            // With several permutations into this machine, the last row might have
+            // been called through a different one.
+            let selected = right.selector.as_ref().map_or(true, |selector| {
+                row_pair
+                    .evaluate(selector)
+                    .ok()
+                    .and_then(|s| s.constant_value())
+                    == Some(T::one())
+            });
+
+            if selected {
+                let mut identity_processor = IdentityProcessor::new(self.fixed_data, mutable_state);
+                if let Ok(result) = identity_processor.process_link(left, right, &row_pair) {
+                    if result.is_complete() && result.constraints.is_empty() {
+                        log::trace!(
+                            "End processing block machine '{}' (already solved)",
+                            self.name()
+                        );
+                        return Ok(result);
+                    }
                 }
             }
         }
@@ -408,11 +465,20 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         let row_offset = self.rows() - 1;
         // Make the block two rows larger than the block size, it includes the last row of the previous block
         // and the first row of the next block.
-        let block = FinalizableData::with_initial_rows_in_progress(
+        let mut block = FinalizableData::with_initial_rows_in_progress(
             &self.witness_cols,
             (0..(self.block_size + 2))
                 .map(|i| self.row_factory.fresh_row(i as DegreeType + row_offset)),
         );
+        // The new block is only selected by the permutation it is called through (if any),
+        // in its last row, where the latch is.
+        let called_selector = split_permutation_selector(&right.selector).map(|(_, s)| s.poly_id);
+        for row in 1..=self.block_size {
+            for selector in &self.permutation_selectors {
+                let value = (row == self.block_size && called_selector == Some(*selector)) as u64;
+                block[row][selector].value = CellValue::Known(value.into());
+            }
+        }
         let mut processor = BlockProcessor::new(
             row_offset,
             block,
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::analyzed::{
+    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
+    PolynomialType,
+};
+use ast::parsed::SelectedExpressions;
+use number::FieldElement;
+
+use crate::witgen::{
+    affine_expression::AffineExpression, util::try_to_simple_poly, EvalError, EvalResult,
+    EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
+};
+
+use super::machine_extractor::refs_in_identity;
+use super::{FixedLookup, Machine};
+
+/// A reference implementation of [Machine], meant as a template for new machines.
+/// It returns its input plus one:
+/// ```pil
+/// namespace Echo(N);
+///     col witness input, output;
+///     output = input + 1;
+/// namespace Main(N);
+///     col witness x, y;
+///     { x, y } in { Echo.input, Echo.output };
+/// ```
+/// Each call occupies one row of the machine, the remaining rows are filled with
+/// `input = 0, output = 1`, which satisfies both the identity and the lookup.
+///
+/// Since there is no way to tell this machine apart from a block machine with a
+/// block size of one, it is only detected in tests and only if its namespace is `Echo`.
+pub struct EchoMachine<'a, T: FieldElement> {
+    fixed_data: &'a FixedData<'a, T>,
+    /// The right-hand side of the connecting identities. All calls to this
+    /// machine use it, and it is used to recognize them in [Machine::process_plookup].
+    rhs: &'a SelectedExpressions<Expression<T>>,
+    input: PolyID,
+    output: PolyID,
+    /// The inputs and outputs of the calls so far, one row per call.
+    rows: Vec<(T, T)>,
+}
+
+impl<'a, T: FieldElement> EchoMachine<'a, T> {
+    /// Returns the machine if it has the shape shown in the [EchoMachine] documentation.
+    /// `connecting_identities` are the lookups into the machine, `identities` are the
+    /// identities that only reference the machine's `witnesses`. The machine has to ensure
+    /// that its witness columns satisfy all of `identities`, because the main loop does not
+    /// process them any more.
+    pub fn try_new(
+        fixed_data: &'a FixedData<'a, T>,
+        connecting_identities: &[&'a Identity<Expression<T>>],
+        identities: &[&Identity<Expression<T>>],
+        witnesses: &HashSet<PolyID>,
+    ) -> Option<Self> {
+        if !witnesses
+            .iter()
+            .all(|w| fixed_data.column_name(w).starts_with("Echo."))
+        {
+            return None;
+        }
+
+        let rhs = &connecting_identities.first()?.right;
+        if !connecting_identities
+            .iter()
+            .all(|i| i.kind == IdentityKind::Plookup && i.right == *rhs)
+            || rhs.selector.is_some()
+        {
+            return None;
+        }
+        let [input, output] = &rhs.expressions[..] else {
+            return None;
+        };
+        let (input, output) = (
+            try_to_simple_poly(input)?.poly_id,
+            try_to_simple_poly(output)?.poly_id,
+        );
+        if input.ptype != PolynomialType::Committed || output.ptype != PolynomialType::Committed {
+            return None;
+        }
+
+        // We do not check that the identity really is `output = input + 1`, but it can only
+        // reference the two columns.
+        let [identity] = identities else {
+            return None;
+        };
+        if identity.kind != IdentityKind::Polynomial
+            || refs_in_identity(identity) != [input, output].into()
+        {
+            return None;
+        }
+
+        Some(EchoMachine {
+            fixed_data,
+            rhs,
+            input,
+            output,
+            rows: vec![],
+        })
+    }
+
+    fn process_plookup_internal(
+        &mut self,
+        left: &[AffineExpression<&'a AlgebraicReference, T>],
+    ) -> EvalResult<'a, T> {
+        // The caller evaluates its side of the lookup as far as it can. If the input is not
+        // known yet, we cannot do anything and return an incomplete result. The caller will
+        // call again with the same identity once it has learned more, e.g. in the next
+        // iteration over the identities of the row.
+        let Some(input) = left[0].constant_value() else {
+            return Ok(EvalValue::incomplete(
+                IncompleteCause::NonConstantRequiredArgument("input"),
+            ));
+        };
+        let output = input + T::one();
+
+        // The output on the caller side is an affine expression, e.g. a single unknown cell.
+        // Solving `left[1] = output` assigns it, or fails if it is already known and differs.
+        let updates = (left[1].clone() - output.into()).solve()?;
+        if !updates.is_complete() {
+            // The output expression contains several unknowns. Nothing is stored, so the
+            // call can be repeated once more of them are known.
+            return Ok(updates);
+        }
+
+        // A complete result means that the caller will not repeat this call, so this is the
+        // place to store the row. Calls have to fit into the rows of the machine.
+        if self.rows.len() as u64 >= self.fixed_data.degree {
+            return Err(EvalError::from(format!(
+                "Echo machine is full: all {} rows are used.",
+                self.fixed_data.degree
+            )));
+        }
+        self.rows.push((input, output));
+        Ok(updates)
+    }
+}
+
+impl<'a, T: FieldElement> Machine<'a, T> for EchoMachine<'a, T> {
+    /// Called for every lookup or permutation whose left-hand side is processed by
+    /// another machine, so we have to check that the call is directed at us.
+    /// The left-hand side contains the values the caller knows so far, unknown cells
+    /// are variables. The constraints returned in the [EvalValue] are applied to the
+    /// caller's cells, and its completeness tells the caller whether it has to call again.
+    fn process_plookup<'b, Q: QueryCallback<T>>(
+        &mut self,
+        _mutable_state: &'b mut MutableState<'a, 'b, T, Q>,
+        kind: IdentityKind,
+        left: &[AffineExpression<&'a AlgebraicReference, T>],
+        right: &'a SelectedExpressions<Expression<T>>,
+    ) -> Option<EvalResult<'a, T>> {
+        // The mutable state gives access to the other machines, which we do not call.
+        (kind == IdentityKind::Plookup && right == self.rhs)
+            .then(|| self.process_plookup_internal(left))
+    }
+
+    /// Called once, after the main machine has processed all its rows and thus
+    /// all calls have been made. Has to return the values of all witness columns of
+    /// the machine, each with one value per row of the degree.
+    fn take_witness_col_values<'b, Q: QueryCallback<T>>(
+        &mut self,
+        _fixed_lookup: &'b mut FixedLookup<T>,
+        _query_callback: &'b mut Q,
+    ) -> HashMap<String, Vec<T>> {
+        let padding = (T::zero(), T::one());
+        let (input, output): (Vec<_>, Vec<_>) = std::mem::take(&mut self.rows)
+            .into_iter()
+            .chain(std::iter::repeat(padding))
+            .take(self.fixed_data.degree as usize)
+            .unzip();
+        [
+            (self.fixed_data.column_name(&self.input).to_string(), input),
+            (
+                self.fixed_data.column_name(&self.output).to_string(),
+                output,
+            ),
+        ]
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use number::GoldilocksField;
+    use pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        constant_evaluator::generate,
+        witgen::{
+            data_structures::column_map::FixedColumnMap, global_constraints::GlobalConstraints,
+            identity_processor::Machines, machines::machine_extractor::split_out_machines,
+            machines::KnownMachine, unused_query_callback, FixedData, MutableState,
+            WitnessGenerator,
+        },
+    };
+
+    use super::*;
+
+    const ECHO: &str = r#"
+namespace Echo(8);
+    col witness input, output;
+    output = input + 1;
+namespace Main(8);
+    col fixed SEL = [1, 0]*;
+    col fixed X(i) { i * 2 };
+    col witness x, y;
+    SEL { x, y } in { Echo.input, Echo.output };
+    (1 - SEL) * y = 0;
+    x = X;
+"#;
+
+    /// Calls the machine directly, the way a calling machine would.
+    #[test]
+    fn lifecycle() {
+        let analyzed = analyze_string::<GoldilocksField>(ECHO);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, vec![]);
+        let global_range_constraints = GlobalConstraints {
+            witness_constraints: fixed_data.witness_map_with(None),
+            fixed_constraints: FixedColumnMap::new(None, fixed_data.fixed_cols.len()),
+        };
+        let identities = analyzed.identities.iter().collect();
+        let mut extracted = split_out_machines(&fixed_data, identities, &global_range_constraints);
+        let [KnownMachine::Echo(echo)] = &mut extracted.machines[..] else {
+            panic!("Expected exactly the echo machine.");
+        };
+
+        let lookup = analyzed
+            .identities
+            .iter()
+            .find(|i| i.kind == IdentityKind::Plookup)
+            .unwrap();
+        let [x, y] = &lookup.left.expressions[..] else {
+            panic!()
+        };
+        let (x, y) = (
+            try_to_simple_poly(x).unwrap(),
+            try_to_simple_poly(y).unwrap(),
+        );
+        let known = |v: u64| AffineExpression::from(GoldilocksField::from(v));
+        let unknown = AffineExpression::from_variable_id;
+
+        // The mutable state is borrowed for the whole call, so we need a new one for each call.
+        let fixed_lookup = &mut extracted.fixed_lookup;
+        let mut call = |kind, left: &[AffineExpression<_, _>]| {
+            let mut query_callback = unused_query_callback();
+            let mut mutable_state = MutableState {
+                fixed_lookup: &mut *fixed_lookup,
+                machines: Machines::from(std::iter::empty()),
+                query_callback: &mut query_callback,
+            };
+            echo.process_plookup(&mut mutable_state, kind, left, &lookup.right)
+        };
+
+        // Not directed at this machine.
+        assert!(call(IdentityKind::Permutation, &[known(1), known(2)]).is_none());
+        // The input is not known yet, so the caller has to retry.
+        assert_eq!(
+            call(IdentityKind::Plookup, &[unknown(x), unknown(y)]),
+            Some(Ok(EvalValue::incomplete(
+                IncompleteCause::NonConstantRequiredArgument("input")
+            )))
+        );
+        // The retry, now that the input is known, determines the output.
+        let result = call(IdentityKind::Plookup, &[known(3), unknown(y)])
+            .unwrap()
+            .unwrap();
+        assert!(result.is_complete());
+        assert_eq!(result.constraints.len(), 1);
+        assert_eq!(result.constraints[0].0, y);
+        // A call where both sides are known only checks the output.
+        assert_eq!(
+            call(IdentityKind::Plookup, &[known(7), known(8)]),
+            Some(Ok(EvalValue::complete([])))
+        );
+        assert!(call(IdentityKind::Plookup, &[known(7), known(9)])
+            .unwrap()
+            .is_err());
+
+        let columns =
+            echo.take_witness_col_values(&mut extracted.fixed_lookup, &mut unused_query_callback());
+        let column = |values: &[u64]| {
+            values
+                .iter()
+                .map(|v| GoldilocksField::from(*v))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(columns["Echo.input"], column(&[3, 7, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(columns["Echo.output"], column(&[4, 8, 1, 1, 1, 1, 1, 1]));
+    }
+
+    /// Runs the machine as part of the witness generation.
+    #[test]
+    fn witgen() {
+        let analyzed = analyze_string::<GoldilocksField>(ECHO);
+        let constants = generate(&analyzed);
+        let witness = WitnessGenerator::new(&analyzed, &constants, unused_query_callback())
+            .generate()
+            .into_iter()
+            .map(|(name, values)| {
+                (
+                    name,
+                    values
+                        .into_iter()
+                        .map(|v| v.to_degree())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        assert_eq!(witness["Main.x"], [0, 2, 4, 6, 8, 10, 12, 14]);
+        assert_eq!(witness["Main.y"], [1, 0, 5, 0, 9, 0, 13, 0]);
+        assert_eq!(witness["Echo.input"], [0, 4, 8, 12, 0, 0, 0, 0]);
+        assert_eq!(witness["Echo.output"], [1, 5, 9, 13, 1, 1, 1, 1]);
+    }
+}
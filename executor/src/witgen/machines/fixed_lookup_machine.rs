@@ -1,4 +1,5 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::HashMap;
+use std::iter::once;
 use std::mem;
 use std::num::NonZeroUsize;
 
@@ -11,6 +12,7 @@ use number::FieldElement;
 
 use crate::witgen::affine_expression::AffineExpression;
 use crate::witgen::global_constraints::{GlobalConstraints, RangeConstraintSet};
+use crate::witgen::memory_budget::DegradationStep;
 use crate::witgen::range_constraints::RangeConstraint;
 use crate::witgen::rows::RowPair;
 use crate::witgen::util::try_to_simple_poly_ref;
@@ -18,7 +20,7 @@ use crate::witgen::{EvalError, EvalValue, IncompleteCause};
 use crate::witgen::{EvalResult, FixedData};
 
 type Application = (Vec<PolyID>, Vec<PolyID>);
-type Index<T> = BTreeMap<Vec<T>, IndexValue>;
+type Index<T> = HashMap<Vec<T>, IndexValue>;
 
 #[derive(Debug)]
 struct IndexValue(Option<NonZeroUsize>);
@@ -37,7 +39,8 @@ impl IndexValue {
     }
 }
 
-/// Indices for applications of fixed columns. For each application `(INPUT_COLS, OUTPUT_COLS)`, stores
+/// Indices for applications of fixed columns, created lazily on the first lookup with the
+/// given known (input) and unknown (output) columns. For each application `(INPUT_COLS, OUTPUT_COLS)`, stores
 /// - `(V, None)` if there exists two different rows where `INPUT_COLS == V` match but `OUTPUT_COLS` differ. TODO: store bitmasks of all possible outputs instead.
 /// - `(V, Some(row)` if the value of `OUTPUT_COLS` is unique when `INPUT_COLS == V`, and `row` is the first row where `INPUT_COLS ==V`
 #[derive(Default)]
@@ -79,6 +82,16 @@ impl<T: FieldElement> IndexedColumns<T> {
             return;
         }
 
+        if fixed_data.is_degraded_to(DegradationStep::DropIndexes) && !self.indices.is_empty() {
+            // We are close to the memory budget, so we only keep one index at a time.
+            // Dropped indices are re-created when they are needed again.
+            log::debug!(
+                "Dropping {} fixed lookup indices to save memory.",
+                self.indices.len()
+            );
+            self.indices.clear();
+        }
+
         let (sorted_input_fixed_columns, sorted_output_fixed_columns) = &sorted_fixed_columns;
 
         // create index for this lookup
@@ -105,41 +118,29 @@ impl<T: FieldElement> IndexedColumns<T> {
             .map(|id| fixed_data.fixed_cols[id].values)
             .collect::<Vec<_>>();
 
-        let index: BTreeMap<Vec<T>, IndexValue> = (0..fixed_data.degree as usize)
-            .fold(
-                (
-                    BTreeMap::<Vec<T>, IndexValue>::default(),
-                    HashSet::<(Vec<T>, Vec<T>)>::default(),
-                ),
-                |(mut acc, mut set), row| {
-                    let input: Vec<_> = input_column_values
-                        .iter()
-                        .map(|column| column[row])
-                        .collect();
-
-                    let output: Vec<_> = output_column_values
-                        .iter()
-                        .map(|column| column[row])
-                        .collect();
-
-                    let input_output = (input, output);
-
-                    if !set.contains(&input_output) {
-                        set.insert(input_output.clone());
-
-                        let (input, _) = input_output;
-
-                        acc.entry(input)
-                            // we have a new, different output, so we lose knowledge
-                            .and_modify(|value| {
-                                *value = IndexValue::multiple_matches();
-                            })
-                            .or_insert_with(|| IndexValue::single_row(row));
+        let mut index = Index::<T>::default();
+        for row in 0..fixed_data.degree as usize {
+            let input: Vec<_> = input_column_values
+                .iter()
+                .map(|column| column[row])
+                .collect();
+
+            index
+                .entry(input)
+                .and_modify(|value| {
+                    // If the output differs from the one in the first matching row,
+                    // we lose knowledge.
+                    if let Some(first_row) = value.row() {
+                        if output_column_values
+                            .iter()
+                            .any(|column| column[row] != column[first_row])
+                        {
+                            *value = IndexValue::multiple_matches();
+                        }
                     }
-                    (acc, set)
-                },
-            )
-            .0;
+                })
+                .or_insert_with(|| IndexValue::single_row(row));
+        }
 
         log::trace!(
             "Done creating index. Size (as flat list): entries * (num_inputs * input_size + row_pointer_size) = {} * ({} * {} bytes + {} bytes) = {} bytes",
@@ -156,6 +157,17 @@ impl<T: FieldElement> IndexedColumns<T> {
             ),
             index,
         );
+        fixed_data.record_memory_usage("fixed_lookup", self.estimated_size_bytes());
+    }
+
+    /// Returns an estimate of the number of bytes used by all indices.
+    fn estimated_size_bytes(&self) -> u64 {
+        self.indices
+            .iter()
+            .map(|((inputs, _), index)| {
+                index.len() * (inputs.len() * mem::size_of::<T>() + mem::size_of::<IndexValue>())
+            })
+            .sum::<usize>() as u64
     }
 }
 
@@ -183,20 +195,34 @@ impl<T: FieldElement> FixedLookup<T> {
     ) -> Option<EvalResult<'b, T>> {
         // This is a matching machine if it is a plookup and the RHS is fully constant.
         if kind != IdentityKind::Plookup
-            || right.selector.is_some()
-            || right.expressions.iter().any(|e| e.contains_witness_ref())
+            || right
+                .selector
+                .iter()
+                .chain(&right.expressions)
+                .any(|e| e.contains_witness_ref())
         {
             return None;
         }
 
         // get the values of the fixed columns
-        let right = right
+        let columns = right
             .expressions
             .iter()
             .map(try_to_simple_poly_ref)
             .collect::<Option<Vec<_>>>()?;
 
-        Some(self.process_plookup_internal(fixed_data, rows, left, right))
+        match &right.selector {
+            None => Some(self.process_plookup_internal(fixed_data, rows, left, columns)),
+            // A fixed column as selector is an additional column that has to be one.
+            Some(selector) => {
+                let selector = try_to_simple_poly_ref(selector)?;
+                let left = once(T::one().into())
+                    .chain(left.iter().cloned())
+                    .collect::<Vec<_>>();
+                let columns = once(selector).chain(columns).collect();
+                Some(self.process_plookup_internal(fixed_data, rows, &left, columns))
+            }
+        }
     }
 
     fn process_plookup_internal<'b>(
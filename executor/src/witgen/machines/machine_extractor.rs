@@ -1,9 +1,13 @@
 use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
 
 use super::block_machine::BlockMachine;
 use super::double_sorted_witness_machine::DoubleSortedWitnesses;
+#[cfg(test)]
+use super::echo_machine::EchoMachine;
 use super::fixed_lookup_machine::FixedLookup;
 use super::sorted_witness_machine::SortedWitnesses;
+use super::variable_block_machine::VariableBlockMachine;
 use super::FixedData;
 use super::KnownMachine;
 use crate::witgen::generator::Generator;
@@ -20,6 +24,92 @@ pub struct ExtractionOutput<'a, T: FieldElement> {
     pub machines: Vec<KnownMachine<'a, T>>,
     pub base_identities: Vec<&'a Identity<Expression<T>>>,
     pub base_witnesses: HashSet<PolyID>,
+    pub report: ExtractionReport,
+}
+
+/// The kind of machine [split_out_machines] detected for a set of witness columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineKind {
+    /// The machine that processes all witness columns and identities that
+    /// have not been assigned to another machine.
+    Main,
+    /// Processes the lookups into fixed columns, it does not own witness columns.
+    FixedLookup,
+    SortedWitnesses,
+    DoubleSortedWitnesses,
+    WriteOnceMemory,
+    Block,
+    VariableBlock,
+    Vm,
+    #[cfg(test)]
+    Echo,
+}
+
+impl Display for MachineKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MachineKind::Main => "main",
+            MachineKind::FixedLookup => "fixed lookup",
+            MachineKind::SortedWitnesses => "sorted witnesses",
+            MachineKind::DoubleSortedWitnesses => "memory",
+            MachineKind::WriteOnceMemory => "write-once memory",
+            MachineKind::Block => "block",
+            MachineKind::VariableBlock => "variable block",
+            MachineKind::Vm => "VM",
+            #[cfg(test)]
+            MachineKind::Echo => "echo",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The witness columns and identities assigned to a machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineReport {
+    pub kind: MachineKind,
+    /// The names of the witness columns owned by the machine, sorted.
+    pub witnesses: Vec<String>,
+    /// The identities processed by the machine.
+    pub identities: Vec<String>,
+    /// The identities through which other machines call into the machine.
+    pub connecting_identities: Vec<String>,
+}
+
+impl Display for MachineReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Machine: {}", self.kind)?;
+        writeln!(f, "  witnesses: {}", self.witnesses.join(", "))?;
+        writeln!(f, "  identities:")?;
+        for identity in &self.identities {
+            writeln!(f, "    {identity}")?;
+        }
+        writeln!(f, "  connecting identities:")?;
+        for identity in &self.connecting_identities {
+            writeln!(f, "    {identity}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How [split_out_machines] partitioned the witness columns and identities,
+/// for debugging which machine processes an identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractionReport {
+    /// The main machine first, then the fixed lookup (if it is called) and
+    /// the extracted machines in the order of extraction.
+    pub machines: Vec<MachineReport>,
+    /// The witness columns that are not referenced by an identity of any machine
+    /// and have no query, so no machine solves for them, sorted.
+    pub unowned_witnesses: Vec<String>,
+}
+
+impl ExtractionReport {
+    /// The report of the machine that owns the witness column `name`.
+    pub fn machine_of(&self, name: &str) -> Option<&MachineReport> {
+        self.machines
+            .iter()
+            .find(|machine| machine.witnesses.iter().any(|w| w == name))
+    }
 }
 
 /// Finds machines in the witness columns and identities
@@ -33,6 +123,17 @@ pub fn split_out_machines<'a, T: FieldElement>(
     let fixed_lookup = FixedLookup::new(global_range_constraints.clone());
 
     let mut machines: Vec<KnownMachine<T>> = vec![];
+    let mut reports = vec![];
+    let names = |witnesses: &HashSet<PolyID>| {
+        witnesses
+            .iter()
+            .map(|w| fixed.column_name(w).to_string())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    let to_strings = |identities: &[&Identity<Expression<T>>]| {
+        identities.iter().map(|i| i.to_string()).collect::<Vec<_>>()
+    };
 
     let all_witnesses = fixed.witness_cols.keys().collect::<HashSet<_>>();
     let mut remaining_witnesses = all_witnesses.clone();
@@ -78,41 +179,47 @@ pub fn split_out_machines<'a, T: FieldElement>(
             .collect::<Vec<_>>();
         assert!(connecting_identities.contains(id));
 
-        log::debug!(
-            "\nExtracted a machine with the following witnesses:\n{} \n and identities:\n{} \n and connecting identities:\n{}",
-            machine_witnesses
-                .iter()
-                .map(|s| fixed.column_name(s))
-                .sorted()
-                .collect::<Vec<_>>()
-                .join(", "),
-            machine_identities
-                .iter()
-                .map(|id| id.to_string())
-                .collect::<Vec<_>>()
-                .join("\n"),
-            connecting_identities
-                .iter()
-                .map(|id| id.to_string())
-                .collect::<Vec<_>>()
-                .join("\n"),
-        );
+        let mut report = MachineReport {
+            kind: MachineKind::Vm,
+            witnesses: names(&machine_witnesses),
+            identities: to_strings(&machine_identities),
+            connecting_identities: to_strings(&connecting_identities),
+        };
+
+        // The reference machine, see [EchoMachine].
+        #[cfg(test)]
+        if let Some(machine) = EchoMachine::try_new(
+            fixed,
+            &connecting_identities,
+            &machine_identities,
+            &machine_witnesses,
+        ) {
+            log::info!("Detected machine: echo");
+            machines.push(KnownMachine::Echo(machine));
+            report.kind = MachineKind::Echo;
+            log::debug!("{report}");
+            reports.push(report);
+            continue;
+        }
 
         if let Some(machine) =
             SortedWitnesses::try_new(fixed, &machine_identities, &machine_witnesses)
         {
             log::info!("Detected machine: sorted witnesses / write-once memory");
             machines.push(KnownMachine::SortedWitnesses(machine));
+            report.kind = MachineKind::SortedWitnesses;
         } else if let Some(machine) =
             DoubleSortedWitnesses::try_new(fixed, &machine_identities, &machine_witnesses)
         {
             log::info!("Detected machine: memory");
             machines.push(KnownMachine::DoubleSortedWitnesses(machine));
+            report.kind = MachineKind::DoubleSortedWitnesses;
         } else if let Some(machine) =
             WriteOnceMemory::try_new(fixed, &connecting_identities, &machine_identities)
         {
             log::info!("Detected machine: write-once memory");
             machines.push(KnownMachine::WriteOnceMemory(machine));
+            report.kind = MachineKind::WriteOnceMemory;
         } else if let Some(machine) = BlockMachine::try_new(
             fixed,
             &connecting_identities,
@@ -122,6 +229,17 @@ pub fn split_out_machines<'a, T: FieldElement>(
         ) {
             log::info!("Detected machine: block");
             machines.push(KnownMachine::BlockMachine(machine));
+            report.kind = MachineKind::Block;
+        } else if let Some(machine) = VariableBlockMachine::try_new(
+            fixed,
+            &connecting_identities,
+            &machine_identities,
+            &machine_witnesses,
+            global_range_constraints,
+        ) {
+            log::info!("Detected machine: variable block");
+            machines.push(KnownMachine::VariableBlockMachine(machine));
+            report.kind = MachineKind::VariableBlock;
         } else {
             log::info!("Detected machine: VM.");
             let latch = connecting_identities
@@ -151,12 +269,68 @@ pub fn split_out_machines<'a, T: FieldElement>(
                 Some(latch),
             )));
         }
+        log::debug!("{report}");
+        reports.push(report);
+    }
+
+    // Lookups whose right side does not reference witness columns are processed
+    // by the fixed lookup machine.
+    let fixed_lookups = identities
+        .iter()
+        .cloned()
+        .filter(|i| {
+            i.kind != IdentityKind::Polynomial
+                && refs_in_selected_expressions(&i.right).is_disjoint(&all_witnesses)
+        })
+        .collect::<Vec<_>>();
+    if !fixed_lookups.is_empty() {
+        reports.insert(
+            0,
+            MachineReport {
+                kind: MachineKind::FixedLookup,
+                witnesses: vec![],
+                identities: vec![],
+                connecting_identities: to_strings(&fixed_lookups),
+            },
+        );
     }
+    let main = MachineReport {
+        kind: MachineKind::Main,
+        witnesses: names(&remaining_witnesses),
+        identities: to_strings(&base_identities),
+        connecting_identities: vec![],
+    };
+    log::debug!("{main}");
+    reports.insert(0, main);
+
+    // Columns with a query are still determined by the main machine.
+    let determined = base_identities
+        .iter()
+        .flat_map(|i| refs_in_identity(i))
+        .chain(
+            remaining_witnesses
+                .iter()
+                .filter(|w| fixed.witness_cols[w].query.is_some())
+                .cloned(),
+        )
+        .collect::<HashSet<_>>();
+    let unowned_witnesses = names(&(&remaining_witnesses - &determined));
+    for name in &unowned_witnesses {
+        log::warn!(
+            "The witness column {name} is not referenced by an identity of any machine \
+             and has no query, so witness generation does not determine its values."
+        );
+    }
+
     ExtractionOutput {
         fixed_lookup,
         machines,
         base_identities,
         base_witnesses: remaining_witnesses,
+        report: ExtractionReport {
+            machines: reports,
+            unowned_witnesses,
+        },
     }
 }
 
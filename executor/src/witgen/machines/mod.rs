@@ -7,8 +7,11 @@ use number::FieldElement;
 
 use self::block_machine::BlockMachine;
 use self::double_sorted_witness_machine::DoubleSortedWitnesses;
+#[cfg(test)]
+use self::echo_machine::EchoMachine;
 pub use self::fixed_lookup_machine::FixedLookup;
 use self::sorted_witness_machine::SortedWitnesses;
+use self::variable_block_machine::VariableBlockMachine;
 use self::write_once_memory::WriteOnceMemory;
 use ast::analyzed::IdentityKind;
 
@@ -21,9 +24,12 @@ use super::QueryCallback;
 
 mod block_machine;
 mod double_sorted_witness_machine;
+#[cfg(test)]
+mod echo_machine;
 mod fixed_lookup_machine;
 pub mod machine_extractor;
 mod sorted_witness_machine;
+mod variable_block_machine;
 mod write_once_memory;
 
 /// A machine is a set of witness columns and identities where the columns
@@ -58,7 +64,10 @@ pub enum KnownMachine<'a, T: FieldElement> {
     DoubleSortedWitnesses(DoubleSortedWitnesses<T>),
     WriteOnceMemory(WriteOnceMemory<'a, T>),
     BlockMachine(BlockMachine<'a, T>),
+    VariableBlockMachine(VariableBlockMachine<'a, T>),
     Vm(Generator<'a, T>),
+    #[cfg(test)]
+    Echo(EchoMachine<'a, T>),
 }
 
 impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
@@ -76,7 +85,12 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
             }
             KnownMachine::WriteOnceMemory(m) => m.process_plookup(mutable_state, kind, left, right),
             KnownMachine::BlockMachine(m) => m.process_plookup(mutable_state, kind, left, right),
+            KnownMachine::VariableBlockMachine(m) => {
+                m.process_plookup(mutable_state, kind, left, right)
+            }
             KnownMachine::Vm(m) => m.process_plookup(mutable_state, kind, left, right),
+            #[cfg(test)]
+            KnownMachine::Echo(m) => m.process_plookup(mutable_state, kind, left, right),
         }
     }
 
@@ -98,7 +112,12 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
             KnownMachine::BlockMachine(m) => {
                 m.take_witness_col_values(fixed_lookup, query_callback)
             }
+            KnownMachine::VariableBlockMachine(m) => {
+                m.take_witness_col_values(fixed_lookup, query_callback)
+            }
             KnownMachine::Vm(m) => m.take_witness_col_values(fixed_lookup, query_callback),
+            #[cfg(test)]
+            KnownMachine::Echo(m) => m.take_witness_col_values(fixed_lookup, query_callback),
         }
     }
 }
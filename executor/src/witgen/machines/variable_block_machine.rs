@@ -0,0 +1,350 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::iter::once;
+
+use super::{EvalResult, FixedData, FixedLookup};
+use crate::witgen::affine_expression::AffineExpression;
+use crate::witgen::block_processor::BlockProcessor;
+use crate::witgen::data_structures::finalizable_data::FinalizableData;
+use crate::witgen::global_constraints::GlobalConstraints;
+use crate::witgen::identity_processor::IdentityProcessor;
+use crate::witgen::processor::OuterQuery;
+use crate::witgen::rows::{CellValue, RowFactory, RowPair, UnknownStrategy};
+use crate::witgen::sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator};
+use crate::witgen::util::try_to_simple_poly;
+use crate::witgen::{machines::Machine, EvalError, EvalValue};
+use crate::witgen::{MutableState, QueryCallback};
+use ast::analyzed::{
+    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
+};
+use ast::parsed::SelectedExpressions;
+use itertools::Itertools;
+use number::{DegreeType, FieldElement};
+
+/// The rows of a processed block and the assignments to the outer query.
+type ProcessedBlock<'a, T> = (FinalizableData<'a, T>, EvalValue<&'a AlgebraicReference, T>);
+
+/// A machine whose latch is a fixed column that is 1 at irregular positions.
+/// The rows up to and including each 1 of the latch form a block, so blocks
+/// can have different sizes (and different fixed column values).
+/// A lookup is answered by the first unused block that satisfies it.
+pub struct VariableBlockMachine<'a, T: FieldElement> {
+    /// The blocks, as (first row, size).
+    blocks: Vec<(DegreeType, usize)>,
+    /// Whether a block has been used to answer a lookup.
+    used: Vec<bool>,
+    /// The block used to answer the most recent lookup.
+    last_used: Option<usize>,
+    /// The identities calling into this machine, used to identify when this machine
+    /// is responsible and to report errors.
+    connecting_identities: Vec<&'a Identity<Expression<T>>>,
+    /// The right-hand sides of the connecting identities.
+    connecting_rhs: BTreeSet<SelectedExpressions<Expression<T>>>,
+    /// The internal identities
+    identities: Vec<&'a Identity<Expression<T>>>,
+    row_factory: RowFactory<'a, T>,
+    /// The values of the witness columns, `None` if not yet determined.
+    values: HashMap<PolyID, Vec<Option<T>>>,
+    /// The set of witness columns that are actually part of this machine.
+    witness_cols: HashSet<PolyID>,
+    fixed_data: &'a FixedData<'a, T>,
+}
+
+impl<'a, T: FieldElement> VariableBlockMachine<'a, T> {
+    pub fn try_new(
+        fixed_data: &'a FixedData<'a, T>,
+        connecting_identities: &[&'a Identity<Expression<T>>],
+        identities: &[&'a Identity<Expression<T>>],
+        witness_cols: &HashSet<PolyID>,
+        global_range_constraints: &GlobalConstraints<T>,
+    ) -> Option<Self> {
+        // All connecting identities need to use the same fixed column as latch.
+        let latch = connecting_identities[0]
+            .right
+            .selector
+            .as_ref()
+            .and_then(try_to_simple_poly)
+            .filter(|latch| latch.is_fixed())?;
+        if connecting_identities
+            .iter()
+            .any(|id| id.right.selector.as_ref().and_then(try_to_simple_poly) != Some(latch))
+        {
+            return None;
+        }
+        let values = fixed_data.fixed_cols[&latch.poly_id].values;
+        // The latch has to be binary and the last row has to end a block,
+        // so that blocks do not wrap around.
+        if !values.iter().all(|v| v.is_zero() || v.is_one()) || !values.last()?.is_one() {
+            return None;
+        }
+
+        let blocks = once(0)
+            .chain(values.iter().positions(|v| v.is_one()).map(|i| i + 1))
+            .tuple_windows()
+            .map(|(start, end)| (start as DegreeType, end - start))
+            .collect::<Vec<_>>();
+
+        Some(VariableBlockMachine {
+            used: vec![false; blocks.len()],
+            blocks,
+            last_used: None,
+            connecting_identities: connecting_identities.to_vec(),
+            connecting_rhs: connecting_identities
+                .iter()
+                .map(|id| id.right.clone())
+                .collect(),
+            identities: identities.to_vec(),
+            row_factory: RowFactory::new(fixed_data, global_range_constraints.clone()),
+            values: witness_cols
+                .iter()
+                .map(|poly_id| (*poly_id, vec![None; fixed_data.degree as usize]))
+                .collect(),
+            witness_cols: witness_cols.clone(),
+            fixed_data,
+        })
+    }
+}
+
+impl<'a, T: FieldElement> Machine<'a, T> for VariableBlockMachine<'a, T> {
+    fn process_plookup<'b, Q: QueryCallback<T>>(
+        &mut self,
+        mutable_state: &'b mut MutableState<'a, 'b, T, Q>,
+        kind: IdentityKind,
+        left: &[AffineExpression<&'a AlgebraicReference, T>],
+        right: &'a SelectedExpressions<Expression<T>>,
+    ) -> Option<EvalResult<'a, T>> {
+        if !self.connecting_rhs.contains(right) || kind != IdentityKind::Plookup {
+            return None;
+        }
+        Some(self.process_plookup_internal(mutable_state, left, right))
+    }
+
+    fn take_witness_col_values<'b, Q: QueryCallback<T>>(
+        &mut self,
+        _fixed_lookup: &'b mut FixedLookup<T>,
+        _query_callback: &'b mut Q,
+    ) -> HashMap<String, Vec<T>> {
+        // For all constraints to be satisfied, unused blocks have to be filled with
+        // valid values. We copy them from the first used block of the same size.
+        let mut default_blocks = HashMap::new();
+        for (&(start, size), _) in self
+            .blocks
+            .iter()
+            .zip(&self.used)
+            .filter(|(_, used)| **used)
+        {
+            default_blocks.entry(size).or_insert(start);
+        }
+        if default_blocks.len() < self.blocks.iter().map(|(_, size)| size).unique().count() {
+            log::warn!(
+                "Filling unused blocks of machine '{}' with zeros, because no block of the same \
+                 size is used. This might violate some internal constraints.",
+                self.name()
+            );
+        }
+
+        let blocks = &self.blocks;
+        let used = &self.used;
+        std::mem::take(&mut self.values)
+            .into_iter()
+            .map(|(poly_id, mut values)| {
+                for (&(start, size), _) in blocks.iter().zip(used).filter(|(_, used)| !**used) {
+                    if let Some(&default_start) = default_blocks.get(&size) {
+                        for i in 0..size {
+                            values[start as usize + i] = values[default_start as usize + i];
+                        }
+                    }
+                }
+                let values = values.into_iter().map(|v| v.unwrap_or_default()).collect();
+                (self.fixed_data.column_name(&poly_id).to_string(), values)
+            })
+            .collect()
+    }
+}
+
+impl<'a, T: FieldElement> VariableBlockMachine<'a, T> {
+    fn name(&self) -> &str {
+        let first_witness = self.witness_cols.iter().next().unwrap();
+        let first_witness_name = self.fixed_data.column_name(first_witness);
+        let namespace = first_witness_name
+            .rfind('.')
+            .map(|idx| &first_witness_name[..idx]);
+
+        namespace.unwrap_or(first_witness_name)
+    }
+
+    fn process_plookup_internal<'b, Q: QueryCallback<T>>(
+        &mut self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+        left: &[AffineExpression<&'a AlgebraicReference, T>],
+        right: &'a SelectedExpressions<Expression<T>>,
+    ) -> EvalResult<'a, T> {
+        log::trace!("Start processing variable block machine '{}'", self.name());
+
+        // If all values are known, this might be a query we already answered.
+        // This can happen in the loop detection case.
+        if left.iter().all(|v| v.is_constant()) {
+            if let Some(result) = self.lookup_in_last_used_block(mutable_state, left, right) {
+                return Ok(result);
+            }
+        }
+
+        let mut errors = vec![];
+        for block_index in (0..self.blocks.len()).filter(|i| !self.used[*i]) {
+            let (new_block, updates) =
+                match self.process_block(mutable_state, block_index, left, right) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        log::trace!("Block {block_index} does not satisfy the lookup: {e}");
+                        errors.push(e);
+                        continue;
+                    }
+                };
+            if !updates.is_complete() {
+                // The block could not be completed with the values known so far,
+                // so there is no point in trying other blocks.
+                log::trace!(
+                    "End processing variable block machine '{}' (incomplete)",
+                    self.name()
+                );
+                return Ok(updates);
+            }
+            self.store_block(block_index, new_block)?;
+            log::trace!(
+                "End processing variable block machine '{}' (successfully, block {block_index})",
+                self.name()
+            );
+            return Ok(updates);
+        }
+
+        if errors.is_empty() {
+            return Err(EvalError::RowsExhausted);
+        }
+        let identity = self
+            .connecting_identities
+            .iter()
+            .find(|id| &id.right == right)
+            .unwrap();
+        Err(EvalError::Generic(format!(
+            "No block of machine '{}' satisfies the lookup in {}:{} with the values {}:\n{identity}\n{}",
+            self.name(),
+            identity.source.file,
+            identity.source.line,
+            left.iter().format(", "),
+            errors.into_iter().reduce(EvalError::combine).unwrap()
+        )))
+    }
+
+    /// Checks if the latch row of the last used block satisfies the lookup.
+    fn lookup_in_last_used_block<'b, Q: QueryCallback<T>>(
+        &self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+        left: &[AffineExpression<&'a AlgebraicReference, T>],
+        right: &'a SelectedExpressions<Expression<T>>,
+    ) -> Option<EvalValue<&'a AlgebraicReference, T>> {
+        let (start, size) = self.blocks[self.last_used?];
+        let row = start + size as DegreeType - 1;
+        let mut current = self.row_factory.fresh_row(row);
+        for (poly_id, values) in &self.values {
+            if let Some(v) = values[row as usize] {
+                current[poly_id].value = CellValue::Known(v);
+            }
+        }
+        // The next row belongs to the next block, which is not relevant here.
+        let next = self.row_factory.fresh_row(row + 1);
+        let row_pair = RowPair::new(
+            &current,
+            &next,
+            row,
+            self.fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let mut identity_processor = IdentityProcessor::new(self.fixed_data, mutable_state);
+        identity_processor
+            .process_link(left, right, &row_pair)
+            .ok()
+            .filter(|result| result.is_complete() && result.constraints.is_empty())
+    }
+
+    /// Solves the block with the given index for the lookup.
+    /// The processed rows start with the last row of the previous block and end
+    /// with the first row of the next block.
+    fn process_block<'b, Q: QueryCallback<T>>(
+        &self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+        block_index: usize,
+        left: &[AffineExpression<&'a AlgebraicReference, T>],
+        right: &'a SelectedExpressions<Expression<T>>,
+    ) -> Result<ProcessedBlock<'a, T>, EvalError<T>> {
+        let (start, size) = self.blocks[block_index];
+        let degree = self.fixed_data.degree;
+        let row_offset = (start + degree - 1) % degree;
+
+        let mut previous_row = self.row_factory.fresh_row(row_offset);
+        for (poly_id, values) in &self.values {
+            if let Some(v) = values[row_offset as usize] {
+                previous_row[poly_id].value = CellValue::Known(v);
+            }
+        }
+        let block = FinalizableData::with_initial_rows_in_progress(
+            &self.witness_cols,
+            once(previous_row).chain(
+                (1..(size + 2)).map(|i| self.row_factory.fresh_row(row_offset + i as DegreeType)),
+            ),
+        );
+        let mut processor = BlockProcessor::new(
+            row_offset,
+            block,
+            mutable_state,
+            &self.identities,
+            self.fixed_data,
+            &self.witness_cols,
+        )
+        .with_outer_query(OuterQuery::new(left.to_vec(), right));
+
+        // Run the outer query on the last row of the block.
+        let mut sequence_iterator = ProcessingSequenceIterator::Default(
+            DefaultSequenceIterator::new(size, self.identities.len(), Some(size as i64 - 1)),
+        );
+        let updates = processor.solve(&mut sequence_iterator)?;
+        Ok((processor.finish(), updates))
+    }
+
+    /// Stores the values of a processed block, including the last row of the previous block,
+    /// and marks the block as used. The first row of the next block is ignored.
+    fn store_block(
+        &mut self,
+        block_index: usize,
+        new_block: FinalizableData<'a, T>,
+    ) -> Result<(), EvalError<T>> {
+        let (start, size) = self.blocks[block_index];
+        let degree = self.fixed_data.degree;
+        let row_offset = (start + degree - 1) % degree;
+
+        let mut updates = vec![];
+        for i in 0..=size {
+            let row = ((row_offset + i as DegreeType) % degree) as usize;
+            for (poly_id, cell) in new_block[i].iter() {
+                if let (Some(values), CellValue::Known(v)) =
+                    (self.values.get(&poly_id), &cell.value)
+                {
+                    match values[row] {
+                        Some(existing) if existing != *v => {
+                            return Err(EvalError::Generic(
+                                "Variable block machine overwrites existing value with different value!"
+                                    .to_string(),
+                            ));
+                        }
+                        _ => updates.push((poly_id, row, *v)),
+                    }
+                }
+            }
+        }
+        for (poly_id, row, v) in updates {
+            self.values.get_mut(&poly_id).unwrap()[row] = Some(v);
+        }
+
+        self.used[block_index] = true;
+        self.last_used = Some(block_index);
+        Ok(())
+    }
+}
@@ -217,9 +217,9 @@ impl<'a, T: FieldElement> Machine<'a, T> for WriteOnceMemory<'a, T> {
             .iter()
             .enumerate()
             .map(|(value_index, poly)| {
-                let column = self.fixed_data.witness_cols[poly]
-                    .external_values
-                    .clone()
+                let column = self
+                    .fixed_data
+                    .external_witness_column(poly)
                     .unwrap_or_else(|| {
                         let mut column = vec![T::zero(); self.fixed_data.degree as usize];
                         for (row, values) in self.data.iter() {
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Steps taken by witness generation when it approaches its memory budget, in the
+/// order in which they are taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DegradationStep {
+    /// Finalize rows much more often, so that range constraints etc. are freed early.
+    FinalizeAggressively,
+    /// Drop the indices of the fixed lookup machine. They are re-created on demand.
+    DropIndexes,
+    /// Move finalized rows of VM machines to files in the temporary directory.
+    SpillToDisk,
+    /// Give up with an error instead of being killed by the operating system.
+    Abort,
+}
+
+impl DegradationStep {
+    /// The fraction of the budget (in percent) at which this step is taken.
+    fn threshold_percent(&self) -> u64 {
+        match self {
+            DegradationStep::FinalizeAggressively => 70,
+            DegradationStep::DropIndexes => 80,
+            DegradationStep::SpillToDisk => 90,
+            DegradationStep::Abort => 100,
+        }
+    }
+
+    fn all() -> [DegradationStep; 4] {
+        [
+            DegradationStep::FinalizeAggressively,
+            DegradationStep::DropIndexes,
+            DegradationStep::SpillToDisk,
+            DegradationStep::Abort,
+        ]
+    }
+}
+
+/// Central accounting of the (approximate) memory used by witness generation.
+/// Every allocation site reports its current usage under a site name, e.g. `vm:main`
+/// or `fixed_lookup`.
+///
+/// A cheaply clonable handle: all clones refer to the same accounting, so that the
+/// caller of witness generation can inspect the degradation steps afterwards.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    /// The budget in bytes.
+    limit: u64,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+#[derive(Default)]
+struct BudgetState {
+    /// Current usage per allocation site, in bytes.
+    usage: BTreeMap<String, u64>,
+    /// The degradation steps taken so far, in order.
+    steps_taken: Vec<DegradationStep>,
+}
+
+impl BudgetState {
+    fn total_usage(&self) -> u64 {
+        self.usage.values().sum()
+    }
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            state: Default::default(),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn total_usage(&self) -> u64 {
+        self.state.lock().unwrap().total_usage()
+    }
+
+    /// Records the current usage of an allocation site and escalates the degradation
+    /// step if needed.
+    pub fn record_usage(&self, site: &str, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.usage.insert(site.to_string(), bytes);
+        let total = state.total_usage();
+        for step in DegradationStep::all() {
+            if state.steps_taken.last() >= Some(&step) {
+                continue;
+            }
+            if total as u128 * 100 >= self.limit as u128 * step.threshold_percent() as u128 {
+                log::warn!(
+                    "Witness generation uses approximately {} of its {} byte memory budget, degrading: {step:?}",
+                    total,
+                    self.limit
+                );
+                state.steps_taken.push(step);
+            }
+        }
+    }
+
+    /// The most severe degradation step taken so far, if any.
+    pub fn current_step(&self) -> Option<DegradationStep> {
+        self.state.lock().unwrap().steps_taken.last().cloned()
+    }
+
+    /// Returns true if the given degradation step (or a more severe one) has been taken.
+    pub fn is_degraded_to(&self, step: DegradationStep) -> bool {
+        self.current_step() >= Some(step)
+    }
+
+    /// All degradation steps taken so far, in order.
+    pub fn steps_taken(&self) -> Vec<DegradationStep> {
+        self.state.lock().unwrap().steps_taken.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn degradation_order() {
+        let budget = MemoryBudget::new(1000);
+        budget.record_usage("vm:main", 500);
+        assert_eq!(budget.current_step(), None);
+        budget.record_usage("vm:main", 750);
+        assert_eq!(
+            budget.steps_taken(),
+            [DegradationStep::FinalizeAggressively]
+        );
+        budget.record_usage("fixed_lookup", 100);
+        assert_eq!(
+            budget.steps_taken(),
+            [
+                DegradationStep::FinalizeAggressively,
+                DegradationStep::DropIndexes
+            ]
+        );
+        // Usage going down again does not undo any steps.
+        budget.record_usage("fixed_lookup", 0);
+        assert_eq!(budget.current_step(), Some(DegradationStep::DropIndexes));
+        budget.record_usage("vm:main", 2000);
+        assert_eq!(
+            budget.steps_taken(),
+            [
+                DegradationStep::FinalizeAggressively,
+                DegradationStep::DropIndexes,
+                DegradationStep::SpillToDisk,
+                DegradationStep::Abort
+            ]
+        );
+    }
+
+    #[test]
+    fn usage_per_site() {
+        let budget = MemoryBudget::new(1000);
+        budget.record_usage("vm:main", 100);
+        budget.record_usage("vm:main", 200);
+        budget.record_usage("fixed_lookup", 50);
+        assert_eq!(budget.total_usage(), 250);
+    }
+}
@@ -1,40 +1,66 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+use itertools::Itertools;
 
 use ast::analyzed::{
-    AlgebraicReference, Analyzed, Expression, FunctionValueDefinition, PolyID, PolynomialType,
-    SymbolKind,
+    AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition,
+    Identity, IdentityKind, PolyID, PolynomialType, SymbolKind,
 };
+use ast::parsed::ColumnRole;
 use number::{DegreeType, FieldElement};
 
+use crate::cancellation::CancellationToken;
+use crate::witness_checker;
+
 use self::data_structures::column_map::{FixedColumnMap, WitnessColumnMap};
 pub use self::eval_result::{
     Constraint, Constraints, EvalError, EvalResult, EvalStatus, EvalValue, IncompleteCause,
 };
+pub use self::external_witness::ExternalWitnessProvider;
+use self::external_witness::{external_witness_values_provider, ExternalWitness};
 use self::generator::Generator;
+pub use self::global_constraints::{check_range_assertions, ColumnRangeConstraint, DerivationStep};
+pub use self::query_callback::{channel_inputs_to_query_callback, inputs_to_query_callback};
 
 use self::identity_processor::Machines;
 use self::machines::machine_extractor::ExtractionOutput;
+pub use self::machines::machine_extractor::{ExtractionReport, MachineKind, MachineReport};
 use self::machines::{FixedLookup, Machine};
+use self::memory_budget::{DegradationStep, MemoryBudget};
+use self::profiling::WitgenProfiler;
+use self::query_recording::QueryRecorder;
+pub use self::witgen_error::{
+    FailingIdentity, FailureContext, GenerationError, RowValues, WitgenError, WitgenErrorKind,
+};
 
 mod affine_expression;
+mod affine_recurrence;
 mod block_processor;
 mod data_structures;
 mod eval_result;
 mod expression_evaluator;
+mod external_witness;
 pub mod fixed_evaluator;
 mod generator;
 mod global_constraints;
 mod identity_processor;
 mod machines;
+pub mod memory_budget;
 mod processor;
+pub mod profiling;
+mod query_callback;
 mod query_processor;
+pub mod query_recording;
 mod range_constraints;
+pub mod row_gadget;
 mod rows;
 mod sequence_iterator;
 pub mod symbolic_evaluator;
 mod symbolic_witness_evaluator;
 mod util;
 mod vm_processor;
+mod witgen_error;
 
 pub trait QueryCallback<T>: FnMut(&str) -> Result<Option<T>, String> + Send + Sync {}
 impl<T, F> QueryCallback<T> for F where F: FnMut(&str) -> Result<Option<T>, String> + Send + Sync {}
@@ -44,6 +70,49 @@ pub fn unused_query_callback<T>() -> impl QueryCallback<T> {
     |_| -> _ { unreachable!() }
 }
 
+/// Determines the global range constraints that witness generation derives for
+/// the columns, together with their derivations. See [check_range_assertions]
+/// for checking them against the `#[assert_range(bits)]` attributes.
+pub fn range_constraint_report<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(&str, Vec<T>)],
+) -> Vec<ColumnRangeConstraint> {
+    let identities = analyzed.identities_with_inlined_intermediate_polynomials();
+    let fixed = FixedData::new(analyzed, fixed_col_values, vec![]);
+    global_constraints::derive_column_range_constraints(&fixed, identities.iter().collect())
+}
+
+/// Determines which witness columns and identities witness generation assigns to which
+/// machine, the same way [WitnessGenerator::generate] does, but without generating the
+/// witness. The report is also logged at debug level during witness generation.
+pub fn machine_report<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(&str, Vec<T>)],
+) -> ExtractionReport {
+    let identities = analyzed.identities_with_inlined_intermediate_polynomials();
+    let fixed = FixedData::new(analyzed, fixed_col_values, vec![]);
+    let (constraints, retained_identities) =
+        global_constraints::determine_global_constraints(&fixed, identities.iter().collect());
+    machines::machine_extractor::split_out_machines(&fixed, retained_identities, &constraints)
+        .report
+}
+
+/// The progress of witness generation in a machine, reported to the callback
+/// set with [WitnessGenerator::with_progress_callback].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WitgenProgress {
+    /// The current row.
+    pub row: DegreeType,
+    /// The total number of rows.
+    pub degree: DegreeType,
+    /// The number of rows per second since the last report.
+    pub rows_per_second: u64,
+    /// The average percentage of successful identity evaluations since the last report.
+    pub identity_success_percentage: u64,
+}
+
+pub type ProgressCallback = Box<dyn Fn(WitgenProgress) + Send + Sync>;
+
 /// Everything [Generator] needs to mutate in order to compute a new row.
 pub struct MutableState<'a, 'b, T: FieldElement, Q: QueryCallback<T>> {
     pub fixed_lookup: &'b mut FixedLookup<T>,
@@ -55,7 +124,16 @@ pub struct WitnessGenerator<'a, 'b, T: FieldElement, Q: QueryCallback<T>> {
     analyzed: &'a Analyzed<T>,
     fixed_col_values: &'b [(&'a str, Vec<T>)],
     query_callback: Q,
-    external_witness_values: Vec<(&'a str, Vec<T>)>,
+    external_witness_provider: Option<Box<dyn ExternalWitnessProvider<T> + 'a>>,
+    memory_budget: Option<MemoryBudget>,
+    row_spilling: Option<PathBuf>,
+    cancellation: CancellationToken,
+    affine_fast_path: bool,
+    max_loop_period: usize,
+    query_recorder: Option<QueryRecorder>,
+    seeded_witness_values: Vec<(&'a str, Vec<T>)>,
+    progress_callback: Option<ProgressCallback>,
+    witgen_profiler: Option<WitgenProfiler>,
 }
 
 impl<'a, 'b, T: FieldElement, Q: QueryCallback<T>> WitnessGenerator<'a, 'b, T, Q> {
@@ -68,82 +146,249 @@ impl<'a, 'b, T: FieldElement, Q: QueryCallback<T>> WitnessGenerator<'a, 'b, T, Q
             analyzed,
             fixed_col_values,
             query_callback,
-            external_witness_values: Vec::new(),
+            external_witness_provider: None,
+            memory_budget: None,
+            row_spilling: None,
+            cancellation: CancellationToken::default(),
+            affine_fast_path: true,
+            max_loop_period: vm_processor::DEFAULT_MAX_LOOP_PERIOD,
+            query_recorder: None,
+            seeded_witness_values: Vec::new(),
+            progress_callback: None,
+            witgen_profiler: None,
         }
     }
 
+    /// Uses externally computed values for some witness columns, which have to be given
+    /// for all rows. Shorthand for [WitnessGenerator::with_external_witness_provider].
+    /// Panics if a column does not exist.
     pub fn with_external_witness_values(
         self,
         external_witness_values: Vec<(&'a str, Vec<T>)>,
     ) -> Self {
-        WitnessGenerator {
+        let missing = external_witness_values
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| {
+                !self
+                    .analyzed
+                    .committed_polys_in_source_order()
+                    .iter()
+                    .flat_map(|(poly, _)| poly.array_elements())
+                    .any(|(column, _)| column == *name)
+            })
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            panic!("External witness values for non-existent columns: {missing:?}");
+        }
+        let degree = self.analyzed.degree();
+        self.with_external_witness_provider(external_witness_values_provider(
             external_witness_values,
+            degree,
+        ))
+    }
+
+    /// Uses externally computed values for the witness columns that `provider` computes.
+    /// The provider is called with the name of a column and a range of rows when a cell
+    /// of the column is first needed, and returns the values on these rows, or `None` if
+    /// it does not compute the column. The values are checked against the constraints.
+    pub fn with_external_witness_provider(
+        self,
+        provider: impl ExternalWitnessProvider<T> + 'a,
+    ) -> Self {
+        WitnessGenerator {
+            external_witness_provider: Some(Box::new(provider)),
+            ..self
+        }
+    }
+
+    /// Sets an approximate memory budget for witness generation.
+    /// When nearing the budget, witness generation degrades step by step
+    /// (see [memory_budget::DegradationStep]) and finally fails with
+    /// [WitgenErrorKind::MemoryBudgetExceeded] instead of being killed by the
+    /// operating system. The steps taken can be inspected on the budget afterwards.
+    pub fn with_memory_budget(self, memory_budget: Option<MemoryBudget>) -> Self {
+        WitnessGenerator {
+            memory_budget,
+            ..self
+        }
+    }
+
+    /// Moves the finalized rows of VM machines to files in `dir` while witness generation
+    /// is running, they are only read back to assemble the final columns. Without this,
+    /// rows are only spilled (to the temporary directory) when nearing the memory budget.
+    /// Spilling makes witness generation slower, by about 10-20% for a simple machine
+    /// with 2^20 rows.
+    pub fn with_row_spilling(self, row_spilling: Option<PathBuf>) -> Self {
+        WitnessGenerator {
+            row_spilling,
+            ..self
+        }
+    }
+
+    /// Sets a token through which witness generation can be cancelled.
+    /// The token is checked before each row of the main machine.
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        WitnessGenerator {
+            cancellation,
+            ..self
+        }
+    }
+
+    /// Enables or disables computing witness columns that are determined by an affine
+    /// recurrence directly, without the solver (see [affine_recurrence]). Enabled by default.
+    pub fn with_affine_fast_path(self, affine_fast_path: bool) -> Self {
+        WitnessGenerator {
+            affine_fast_path,
+            ..self
+        }
+    }
+
+    /// Sets the maximal period of loops detected in the main machine (16 by default).
+    /// If the rows of the machine repeat with a period up to this value, the
+    /// following rows are copied and only checked instead of being solved.
+    pub fn with_max_loop_period(self, max_loop_period: usize) -> Self {
+        WitnessGenerator {
+            max_loop_period,
+            ..self
+        }
+    }
+
+    /// Records all prover queries and their answers with `query_recorder`, so that the
+    /// run can be reproduced with [query_recording::replay_query_callback].
+    pub fn with_query_recorder(self, query_recorder: Option<QueryRecorder>) -> Self {
+        WitnessGenerator {
+            query_recorder,
+            ..self
+        }
+    }
+
+    /// Seeds witness columns with values computed elsewhere, e.g. by an executor.
+    /// Unlike external witness values, a column can be seeded for a prefix of the rows
+    /// only. The seeded values are used as known values and checked against the
+    /// constraints, a conflict fails with [WitgenErrorKind::SeedDivergence].
+    pub fn with_seeded_witness_values(self, seeded_witness_values: Vec<(&'a str, Vec<T>)>) -> Self {
+        WitnessGenerator {
+            seeded_witness_values,
+            ..self
+        }
+    }
+
+    /// Sets a callback that is called with the progress of witness generation, e.g. to
+    /// display a progress bar. It is called whenever the progress is logged, i.e.
+    /// every 1000 rows of the main machine and of the other machines that are VMs.
+    pub fn with_progress_callback(self, progress_callback: Option<ProgressCallback>) -> Self {
+        WitnessGenerator {
+            progress_callback,
+            ..self
+        }
+    }
+
+    /// Collects per-identity statistics (invocations, successes and time) with
+    /// `witgen_profiler`, which writes them to its file at the end of witness generation
+    /// and logs the identities that took the most time, see [profiling].
+    pub fn with_witgen_profiling(self, witgen_profiler: Option<WitgenProfiler>) -> Self {
+        WitnessGenerator {
+            witgen_profiler,
             ..self
         }
     }
 
     /// Generates the committed polynomial values
     /// @returns the values (in source order) and the degree of the polynomials.
+    ///
+    /// # Panics
+    /// Panics if witness generation fails or is cancelled, use
+    /// [WitnessGenerator::try_generate] to handle these cases.
     pub fn generate(self) -> Vec<(String, Vec<T>)> {
-        let fixed = FixedData::new(
-            self.analyzed,
-            self.fixed_col_values,
-            self.external_witness_values,
-        );
+        self.try_generate().unwrap_or_else(|e| match e {
+            GenerationError::Cancelled(e) => panic!("{e}"),
+            GenerationError::Failed(e) => {
+                e.log();
+                panic!("Witness generation failed.")
+            }
+            GenerationError::PermutationMismatch(_)
+            | GenerationError::PermutationCheckFailed(_)
+            | GenerationError::ConnectionMismatch(_)
+            | GenerationError::ConnectionCheckFailed(_)
+            | GenerationError::RangeViolation(_) => {
+                panic!("{e}")
+            }
+        })
+    }
+
+    /// Generates the committed polynomial values, or returns an error if witness
+    /// generation failed or was cancelled.
+    pub fn try_generate(self) -> Result<Vec<(String, Vec<T>)>, GenerationError> {
+        self.cancellation.check("witness generation")?;
+        // Connect identities are not solved, they are only checked on the generated witness.
         let identities = self
             .analyzed
-            .identities_with_inlined_intermediate_polynomials();
-
-        let (
-            constraints,
-            // Removes identities like X * (X - 1) = 0 or { A } in { BYTES }
-            // These are already captured in the range constraints.
-            retained_identities,
-        ) = global_constraints::determine_global_constraints(&fixed, identities.iter().collect());
-        let ExtractionOutput {
-            mut fixed_lookup,
-            mut machines,
-            base_identities,
-            base_witnesses,
-        } = machines::machine_extractor::split_out_machines(
-            &fixed,
-            retained_identities,
-            &constraints,
-        );
-        let mut query_callback = self.query_callback;
-        let mut mutable_state = MutableState {
-            fixed_lookup: &mut fixed_lookup,
-            machines: Machines::from(machines.iter_mut()),
-            query_callback: &mut query_callback,
+            .identities_with_inlined_intermediate_polynomials()
+            .into_iter()
+            .filter(|identity| identity.kind != IdentityKind::Connect)
+            .collect::<Vec<_>>();
+
+        let external_witness =
+            ExternalWitness::new(self.external_witness_provider, self.analyzed.degree());
+        let affine_columns = if self.affine_fast_path {
+            // Seeded columns are not computed either, so that their values are checked.
+            let seeded = self
+                .seeded_witness_values
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<BTreeSet<_>>();
+            let mut affine_columns = affine_recurrence::compute_affine_columns(
+                self.analyzed,
+                self.fixed_col_values,
+                &identities,
+                &seeded,
+            );
+            // The same holds for the columns that are computed externally.
+            affine_columns
+                .columns
+                .retain(|(name, _)| !external_witness.provides(name));
+            affine_columns.log_statistics();
+            affine_columns.columns
+        } else {
+            vec![]
         };
-        let mut generator = Generator::new(
-            &fixed,
-            &base_identities,
-            base_witnesses,
-            &constraints,
-            // We could set the latch of the main VM here, but then we would have to detect it.
-            // Instead, the main VM will be computed in one block, directly continuing into the
-            // infinite loop after the first return.
-            None,
-        );
+        let affine_columns = affine_columns
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.clone()))
+            .collect();
 
-        generator.run(&mut mutable_state);
-
-        // Get columns from machines
-        let main_columns = generator
-            .take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback);
-        let mut columns = mutable_state
-            .machines
-            .iter_mut()
-            .flat_map(|m| {
-                m.take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback)
-                    .into_iter()
-            })
-            .chain(main_columns)
-            .collect::<BTreeMap<_, _>>();
+        let fixed = FixedData::new(self.analyzed, self.fixed_col_values, affine_columns)
+            .with_external_witness(external_witness)
+            .with_cancellation(self.cancellation.clone())
+            .with_memory_budget(self.memory_budget)
+            .with_max_loop_period(self.max_loop_period)
+            .with_row_spilling(self.row_spilling)
+            .with_query_recorder(self.query_recorder)
+            .with_seeded_witness_values(self.seeded_witness_values)
+            .with_progress_callback(self.progress_callback)
+            .with_witgen_profiler(self.witgen_profiler.as_ref());
+        if fixed.seeded_rows > 0 {
+            log::info!(
+                "Seeded the first {} rows of witness columns with known values.",
+                fixed.seeded_rows
+            );
+        }
+        if let Some(profiler) = &self.witgen_profiler {
+            profiler.register(&identities);
+        }
+
+        let columns = generate_columns(&fixed, &identities, self.query_callback);
+        if let Some(profiler) = &self.witgen_profiler {
+            if let Err(e) = profiler.finish() {
+                log::error!("{e}");
+            }
+        }
+        let mut columns = columns?;
 
         // Order columns according to the order of declaration.
-        self.analyzed
+        let witness = self
+            .analyzed
             .committed_polys_in_source_order()
             .into_iter()
             .flat_map(|(p, _)| p.array_elements())
@@ -152,16 +397,132 @@ impl<'a, 'b, T: FieldElement, Q: QueryCallback<T>> WitnessGenerator<'a, 'b, T, Q
                 assert!(!column.is_empty());
                 (name, column)
             })
-            .collect()
+            .collect::<Vec<_>>();
+
+        // The ranges are only used as range constraints by the solver,
+        // so values computed in other ways (e.g. by queries) could violate them.
+        let violations = range_violations(&fixed, &witness);
+        if !violations.is_empty() {
+            return Err(GenerationError::RangeViolation(violations));
+        }
+
+        // Machines could be called more or less often than they provide selected rows,
+        // which is fine for lookups, but not for permutations.
+        let violations =
+            witness_checker::check_permutations(self.analyzed, self.fixed_col_values, &witness)
+                .map_err(GenerationError::PermutationCheckFailed)?;
+        if !violations.is_empty() {
+            return Err(GenerationError::PermutationMismatch(
+                violations.iter().map(|v| v.to_string()).collect(),
+            ));
+        }
+        let violations =
+            witness_checker::check_connections(self.analyzed, self.fixed_col_values, &witness)
+                .map_err(GenerationError::ConnectionCheckFailed)?;
+        if !violations.is_empty() {
+            return Err(GenerationError::ConnectionMismatch(
+                violations.iter().map(|v| v.to_string()).collect(),
+            ));
+        }
+        Ok(witness)
     }
 }
 
+/// @returns a description of each value of a witness column that is outside of
+/// the range the column is annotated with.
+fn range_violations<T: FieldElement>(
+    fixed: &FixedData<T>,
+    witness: &[(String, Vec<T>)],
+) -> Vec<String> {
+    fixed
+        .witness_cols
+        .values()
+        .zip_eq(witness)
+        .filter_map(|(column, (name, values))| column.range.map(|range| (name, values, range)))
+        .flat_map(|(name, values, (min, max))| {
+            values
+                .iter()
+                .enumerate()
+                .filter(move |(_, v)| **v < min || **v > max)
+                .map(move |(row, v)| format!("{name} = {v} in row {row} is not in [{min}, {max}]"))
+        })
+        .collect()
+}
+
+/// Runs witness generation on the main machine and returns the values of all
+/// witness columns by name.
+fn generate_columns<'a, T: FieldElement, Q: QueryCallback<T>>(
+    fixed: &'a FixedData<'a, T>,
+    identities: &'a [Identity<AlgebraicExpression<T>>],
+    mut query_callback: Q,
+) -> Result<BTreeMap<String, Vec<T>>, GenerationError> {
+    let (
+        constraints,
+        // Removes identities like X * (X - 1) = 0 or { A } in { BYTES }
+        // These are already captured in the range constraints.
+        retained_identities,
+    ) = global_constraints::determine_global_constraints(fixed, identities.iter().collect());
+    let ExtractionOutput {
+        mut fixed_lookup,
+        mut machines,
+        base_identities,
+        base_witnesses,
+        report: _,
+    } = machines::machine_extractor::split_out_machines(fixed, retained_identities, &constraints);
+    let mut mutable_state = MutableState {
+        fixed_lookup: &mut fixed_lookup,
+        machines: Machines::from(machines.iter_mut()),
+        query_callback: &mut query_callback,
+    };
+    let mut generator = Generator::new(
+        fixed,
+        &base_identities,
+        base_witnesses,
+        &constraints,
+        // We could set the latch of the main VM here, but then we would have to detect it.
+        // Instead, the main VM will be computed in one block, directly continuing into the
+        // infinite loop after the first return.
+        None,
+    );
+
+    generator.run(&mut mutable_state)?;
+    fixed.cancellation.check("witness generation")?;
+
+    // Get columns from machines
+    let main_columns =
+        generator.take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback);
+    Ok(mutable_state
+        .machines
+        .iter_mut()
+        .flat_map(|m| {
+            m.take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback)
+                .into_iter()
+        })
+        .chain(main_columns)
+        .collect())
+}
+
 /// Data that is fixed for witness generation.
 pub struct FixedData<'a, T> {
     degree: DegreeType,
     fixed_cols: FixedColumnMap<FixedColumn<'a, T>>,
     witness_cols: WitnessColumnMap<WitnessColumn<'a, T>>,
+    /// The columns by name, where the elements of arrays are named `name[index]`.
     column_by_name: HashMap<String, PolyID>,
+    /// The names of the elements of the column arrays.
+    arrays: HashMap<String, Vec<String>>,
+    cancellation: CancellationToken,
+    memory_budget: Option<MemoryBudget>,
+    max_loop_period: usize,
+    /// The directory finalized rows are always spilled to, see [FixedData::row_spilling_dir].
+    row_spilling: Option<PathBuf>,
+    query_recorder: Option<QueryRecorder>,
+    /// The number of rows for which seeded values are available.
+    seeded_rows: DegreeType,
+    progress_callback: Option<ProgressCallback>,
+    witgen_profiler: Option<&'a WitgenProfiler>,
+    /// Values of witness columns that are computed externally and fetched on demand.
+    external_witness: ExternalWitness<'a, T>,
 }
 
 impl<'a, T: FieldElement> FixedData<'a, T> {
@@ -176,12 +537,23 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
             WitnessColumnMap::from(analyzed.committed_polys_in_source_order().iter().flat_map(
                 |(poly, value)| {
                     poly.array_elements()
-                        .map(|(name, poly_id)| {
+                        .enumerate()
+                        .map(|(index, (name, poly_id))| {
                             let external_values = external_witness_values.remove(name.as_str());
                             if let Some(external_values) = &external_values {
                                 assert_eq!(external_values.len(), analyzed.degree() as usize);
                             }
-                            WitnessColumn::new(poly_id.id as usize, &name, value, external_values)
+                            let array_index = poly.length.map(|_| index as u64);
+                            WitnessColumn::new(
+                                poly_id.id as usize,
+                                &name,
+                                value,
+                                external_values,
+                                array_index,
+                            )
+                            .with_range(poly.range.as_ref().map(
+                                |range| (T::from(range.min.clone()), T::from(range.max.clone())),
+                            ))
                         })
                         .collect::<Vec<_>>()
                 },
@@ -194,23 +566,150 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
             );
         }
 
-        let fixed_cols =
-            FixedColumnMap::from(fixed_col_values.iter().map(|(n, v)| FixedColumn::new(n, v)));
+        let fixed_cols = FixedColumnMap::from(fixed_col_values.iter().map(|(n, v)| {
+            let role = analyzed
+                .definitions
+                .get(*n)
+                .and_then(|(symbol, _)| symbol.role);
+            FixedColumn::new(n, v).with_role(role)
+        }));
         FixedData {
             degree: analyzed.degree(),
             fixed_cols,
             witness_cols,
             column_by_name: analyzed
                 .definitions
-                .iter()
-                .filter_map(|(name, (symbol, _))| {
-                    matches!(symbol.kind, SymbolKind::Poly(_))
-                        .then(|| (name.clone(), symbol.into()))
+                .values()
+                .filter(|(symbol, _)| matches!(symbol.kind, SymbolKind::Poly(_)))
+                .flat_map(|(symbol, _)| symbol.array_elements())
+                .collect(),
+            arrays: analyzed
+                .definitions
+                .values()
+                .filter(|(symbol, _)| {
+                    matches!(symbol.kind, SymbolKind::Poly(_)) && symbol.length.is_some()
+                })
+                .map(|(symbol, _)| {
+                    let elements = symbol.array_elements().map(|(name, _)| name).collect();
+                    (symbol.absolute_name.clone(), elements)
                 })
                 .collect(),
+            cancellation: CancellationToken::default(),
+            memory_budget: None,
+            max_loop_period: vm_processor::DEFAULT_MAX_LOOP_PERIOD,
+            row_spilling: None,
+            query_recorder: None,
+            seeded_rows: 0,
+            progress_callback: None,
+            witgen_profiler: None,
+            external_witness: ExternalWitness::new(None, analyzed.degree()),
         }
     }
 
+    pub fn with_external_witness(self, external_witness: ExternalWitness<'a, T>) -> Self {
+        FixedData {
+            external_witness,
+            ..self
+        }
+    }
+
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        FixedData {
+            cancellation,
+            ..self
+        }
+    }
+
+    pub fn with_memory_budget(self, memory_budget: Option<MemoryBudget>) -> Self {
+        FixedData {
+            memory_budget,
+            ..self
+        }
+    }
+
+    pub fn with_max_loop_period(self, max_loop_period: usize) -> Self {
+        FixedData {
+            max_loop_period,
+            ..self
+        }
+    }
+
+    pub fn with_row_spilling(self, row_spilling: Option<PathBuf>) -> Self {
+        FixedData {
+            row_spilling,
+            ..self
+        }
+    }
+
+    /// The directory finalized rows should be spilled to, if any: the configured directory,
+    /// or the temporary directory once the memory budget requires it.
+    pub fn row_spilling_dir(&self) -> Option<PathBuf> {
+        self.row_spilling.clone().or_else(|| {
+            self.is_degraded_to(DegradationStep::SpillToDisk)
+                .then(std::env::temp_dir)
+        })
+    }
+
+    /// Records the current memory usage of an allocation site, see [MemoryBudget].
+    fn record_memory_usage(&self, site: &str, bytes: u64) {
+        if let Some(budget) = &self.memory_budget {
+            budget.record_usage(site, bytes);
+        }
+    }
+
+    /// Returns true if the memory budget requires the given degradation step.
+    fn is_degraded_to(&self, step: DegradationStep) -> bool {
+        self.memory_budget
+            .as_ref()
+            .map_or(false, |budget| budget.is_degraded_to(step))
+    }
+
+    pub fn with_query_recorder(self, query_recorder: Option<QueryRecorder>) -> Self {
+        FixedData {
+            query_recorder,
+            ..self
+        }
+    }
+
+    pub fn with_progress_callback(self, progress_callback: Option<ProgressCallback>) -> Self {
+        FixedData {
+            progress_callback,
+            ..self
+        }
+    }
+
+    pub fn with_witgen_profiler(self, witgen_profiler: Option<&'a WitgenProfiler>) -> Self {
+        FixedData {
+            witgen_profiler,
+            ..self
+        }
+    }
+
+    /// Sets values for a prefix of the rows of some witness columns.
+    /// Panics if a column does not exist or has more values than the degree.
+    pub fn with_seeded_witness_values(mut self, seeded: Vec<(&str, Vec<T>)>) -> Self {
+        for (name, values) in seeded {
+            let poly_id = self
+                .try_column_by_name(name)
+                .filter(|poly_id| poly_id.ptype == PolynomialType::Committed)
+                .unwrap_or_else(|| panic!("Seeded values for non-existent column: {name}"));
+            assert!(
+                values.len() as DegreeType <= self.degree,
+                "Column {name} is seeded with {} values, but the degree is {}.",
+                values.len(),
+                self.degree
+            );
+            self.seeded_rows = self.seeded_rows.max(values.len() as DegreeType);
+            self.witness_cols[&poly_id].seeded_values = Some(values);
+        }
+        self
+    }
+
+    /// Returns true if witness generation should stop at the next safe point.
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
     fn witness_map_with<V: Clone>(&self, initial_value: V) -> WitnessColumnMap<V> {
         WitnessColumnMap::new(initial_value, self.witness_cols.len())
     }
@@ -223,28 +722,82 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
         }
     }
 
+    /// Returns the role of a fixed column from its `#[role(...)]` attribute, if any.
+    /// The shape of the columns is checked by [crate::constant_evaluator::check_column_roles].
+    pub fn column_role(&self, poly_id: &PolyID) -> Option<ColumnRole> {
+        match poly_id.ptype {
+            PolynomialType::Constant => self.fixed_cols[poly_id].role,
+            _ => None,
+        }
+    }
+
+    /// Returns the fixed columns with the given role, e.g. the `first_step`
+    /// columns of the machines for [ColumnRole::FirstRow].
+    pub fn columns_with_role(&self, role: ColumnRole) -> impl Iterator<Item = PolyID> + '_ {
+        self.fixed_cols
+            .iter()
+            .filter(move |(_, col)| col.role == Some(role))
+            .map(|(poly_id, _)| poly_id)
+    }
+
     pub fn try_column_by_name(&self, name: &str) -> Option<PolyID> {
         self.column_by_name.get(name).cloned()
     }
 
+    /// Returns the elements of the column array `name`, with their names.
+    fn try_array_by_name(&self, name: &str) -> Option<Vec<(&str, PolyID)>> {
+        self.arrays.get(name).map(|elements| {
+            elements
+                .iter()
+                .map(|element| (element.as_str(), self.column_by_name[element]))
+                .collect()
+        })
+    }
+
     fn external_witness(&self, row: DegreeType, column: &PolyID) -> Option<T> {
         let row = row % self.degree;
+        match &self.witness_cols[column].external_values {
+            Some(values) => Some(values[row as usize]),
+            None => self.external_witness.value(self.column_name(column), row),
+        }
+    }
+
+    /// Returns all values of a witness column, if it is computed externally.
+    fn external_witness_column(&self, column: &PolyID) -> Option<Vec<T>> {
         self.witness_cols[column]
             .external_values
+            .clone()
+            .or_else(|| self.external_witness.column(self.column_name(column)))
+    }
+
+    fn seeded_witness(&self, row: DegreeType, column: &PolyID) -> Option<T> {
+        let row = row % self.degree;
+        self.witness_cols[column]
+            .seeded_values
             .as_ref()
-            .map(|v| v[row as usize])
+            .and_then(|v| v.get(row as usize).cloned())
     }
 }
 
 pub struct FixedColumn<'a, T> {
     name: String,
     values: &'a Vec<T>,
+    /// The role from the `#[role(...)]` attribute of the column, if any.
+    role: Option<ColumnRole>,
 }
 
 impl<'a, T> FixedColumn<'a, T> {
     pub fn new(name: &'a str, values: &'a Vec<T>) -> FixedColumn<'a, T> {
         let name = name.to_string();
-        FixedColumn { name, values }
+        FixedColumn {
+            name,
+            values,
+            role: None,
+        }
+    }
+
+    pub fn with_role(self, role: Option<ColumnRole>) -> Self {
+        FixedColumn { role, ..self }
     }
 }
 
@@ -257,9 +810,18 @@ pub struct WitnessColumn<'a, T> {
     poly: AlgebraicReference,
     /// The prover query expression, if any.
     query: Option<&'a Expression<T>>,
+    /// The hint, a function from the row to the value, if any.
+    hint: Option<&'a Expression<T>>,
     /// A list of externally computed witness values, if any.
     /// The length of this list must be equal to the degree.
     external_values: Option<Vec<T>>,
+    /// Values for the first rows that are checked against the constraints, if any.
+    seeded_values: Option<Vec<T>>,
+    /// The index of this column in its array, if it is an element of an array.
+    /// It is passed to the query in addition to the row.
+    array_index: Option<u64>,
+    /// The range of the values from the `in [min, max]` annotation of the column, if any.
+    range: Option<(T, T)>,
 }
 
 impl<'a, T> WitnessColumn<'a, T> {
@@ -268,12 +830,18 @@ impl<'a, T> WitnessColumn<'a, T> {
         name: &str,
         value: &'a Option<FunctionValueDefinition<T>>,
         external_values: Option<Vec<T>>,
+        array_index: Option<u64>,
     ) -> WitnessColumn<'a, T> {
         let query = if let Some(FunctionValueDefinition::Query(query)) = value {
             Some(query)
         } else {
             None
         };
+        let hint = if let Some(FunctionValueDefinition::Hint(hint)) = value {
+            Some(hint)
+        } else {
+            None
+        };
         let poly = AlgebraicReference {
             poly_id: PolyID {
                 id: id as u64,
@@ -285,7 +853,303 @@ impl<'a, T> WitnessColumn<'a, T> {
         WitnessColumn {
             poly,
             query,
+            hint,
             external_values,
+            seeded_values: None,
+            array_index,
+            range: None,
         }
     }
+
+    pub fn with_range(self, range: Option<(T, T)>) -> Self {
+        WitnessColumn { range, ..self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use number::GoldilocksField;
+    use pil_analyzer::analyze_string;
+    use test_log::test;
+
+    use crate::cancellation::{CancellationToken, Cancelled};
+    use crate::constant_evaluator::generate;
+
+    use super::{
+        unused_query_callback, ColumnRole, FixedData, GenerationError, QueryCallback,
+        WitgenErrorKind, WitnessGenerator,
+    };
+
+    /// Runs witness generation on `src` with the options set by `configure`.
+    fn generate_with<Q: QueryCallback<GoldilocksField>>(
+        src: &str,
+        query_callback: Q,
+        configure: impl for<'a, 'b> FnOnce(
+            WitnessGenerator<'a, 'b, GoldilocksField, Q>,
+        ) -> WitnessGenerator<'a, 'b, GoldilocksField, Q>,
+    ) -> Result<Vec<(String, Vec<GoldilocksField>)>, GenerationError> {
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        configure(WitnessGenerator::new(&analyzed, &constants, query_callback)).try_generate()
+    }
+
+    /// Parses the row from a query of the form `("name", row)`.
+    fn queried_row(query: &str) -> u64 {
+        query
+            .trim_end_matches(')')
+            .rsplit(',')
+            .next()
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    const SUM: &str = r#"
+namespace Sum(16);
+    pol fixed ISLAST(i) { i == 15 };
+    col witness input(i) query ("in", i);
+    col witness sum;
+
+    ISLAST * sum' = 0;
+    sum' = (1 - ISLAST) * (sum + input);
+    ISLAST * input = 0;
+"#;
+
+    /// Runs witness generation, cancelling it when the input of `cancel_at_row` is queried.
+    fn generate_with_cancellation(
+        cancel_at_row: Option<u64>,
+    ) -> Result<Vec<(String, Vec<GoldilocksField>)>, GenerationError> {
+        let token = CancellationToken::new();
+        let query_token = token.clone();
+        let query_callback = move |query: &str| -> Result<Option<GoldilocksField>, String> {
+            let row = queried_row(query);
+            if Some(row) == cancel_at_row {
+                query_token.cancel();
+            }
+            // The input of the last row is determined by the constraints.
+            Ok((row < 15).then(|| GoldilocksField::from(row)))
+        };
+        generate_with(SUM, query_callback, |generator| {
+            generator.with_cancellation(token)
+        })
+    }
+
+    #[test]
+    fn cancel_at_row() {
+        assert_eq!(
+            generate_with_cancellation(Some(5)),
+            Err(GenerationError::Cancelled(Cancelled {
+                stage: "witness generation".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn not_cancelled() {
+        let witness = generate_with_cancellation(None).unwrap();
+        let (name, sum) = &witness[1];
+        assert_eq!(name, "Sum.sum");
+        assert_eq!(sum[15], GoldilocksField::from((0..15).sum::<u64>()));
+    }
+
+    const FIB: &str = r#"
+namespace Fib(16);
+    pol fixed ISLAST(i) { i == 15 };
+    col witness x, y;
+    ISLAST * (y' - 1) = 0;
+    ISLAST * (x' - 1) = 0;
+    (1 - ISLAST) * (x' - y) = 0;
+    (1 - ISLAST) * (y' - (x + y)) = 0;
+"#;
+
+    fn generate_fib() -> Vec<(String, Vec<GoldilocksField>)> {
+        generate_with(FIB, unused_query_callback(), |generator| generator).unwrap()
+    }
+
+    #[test]
+    fn seeded_witness() {
+        let witness = generate_fib();
+        let x = witness[0].1[..8].to_vec();
+        let seeded = generate_with(FIB, unused_query_callback(), |generator| {
+            generator.with_seeded_witness_values(vec![("Fib.x", x)])
+        });
+        assert_eq!(seeded.unwrap(), witness);
+    }
+
+    #[test]
+    fn seed_divergence() {
+        let mut x = generate_fib()[0].1[..8].to_vec();
+        assert_eq!(x[5], 8.into());
+        x[5] += 1.into();
+        let error = generate_with(FIB, unused_query_callback(), |generator| {
+            generator.with_seeded_witness_values(vec![("Fib.x", x)])
+        })
+        .unwrap_err();
+        let GenerationError::Failed(error) = error else {
+            panic!("Expected a failure, got {error}");
+        };
+        assert_eq!(error.kind, WitgenErrorKind::SeedDivergence);
+        assert_eq!(error.row, 4);
+        assert!(error.row_dump.contains("Fib.x = 9"), "{}", error.row_dump);
+        // Only the seeded columns are shown.
+        assert!(!error.row_dump.contains("Fib.y"), "{}", error.row_dump);
+    }
+
+    #[test]
+    fn external_witness_provider() {
+        let witness = generate_fib();
+        let y = witness[1].1.clone();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        let provided = generate_with(FIB, unused_query_callback(), |generator| {
+            generator.with_external_witness_provider(
+                move |name: &str, rows: std::ops::Range<u64>| {
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push((name.to_string(), rows.clone()));
+                    (name == "Fib.y").then(|| y[rows.start as usize..rows.end as usize].to_vec())
+                },
+            )
+        });
+        assert_eq!(provided.unwrap(), witness);
+        // Each column is requested once, in a single chunk.
+        let mut requests = requests.lock().unwrap().clone();
+        requests.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            requests,
+            vec![("Fib.x".to_string(), 0..16), ("Fib.y".to_string(), 0..16)]
+        );
+    }
+
+    #[test]
+    fn external_witness_provider_mismatch() {
+        let mut y = generate_fib()[1].1.clone();
+        y[5] += 1.into();
+        let error = generate_with(FIB, unused_query_callback(), |generator| {
+            generator.with_external_witness_provider(
+                move |name: &str, rows: std::ops::Range<u64>| {
+                    (name == "Fib.y").then(|| y[rows.start as usize..rows.end as usize].to_vec())
+                },
+            )
+        })
+        .unwrap_err();
+        let GenerationError::Failed(error) = error else {
+            panic!("Expected a failure, got {error}");
+        };
+        assert_eq!(error.kind, WitgenErrorKind::Unsatisfiable);
+    }
+
+    /// Runs witness generation on a machine whose rows repeat with a period of 6
+    /// and returns the number of prover queries.
+    fn queries_with_loop_period(max_loop_period: usize) -> usize {
+        let src = r#"
+namespace Loop(1024);
+    pol fixed STEP(i) { i % 6 };
+    col witness x(i) query ("hint", i);
+    // Not solvable for x, so x has to be provided by the prover.
+    (x - STEP) * (x - STEP - 6) = 0;
+"#;
+        let queries = Arc::new(AtomicUsize::new(0));
+        let query_count = queries.clone();
+        let query_callback = move |query: &str| -> Result<Option<GoldilocksField>, String> {
+            query_count.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(GoldilocksField::from(queried_row(query) % 6)))
+        };
+        let witness = generate_with(src, query_callback, |generator| {
+            generator.with_max_loop_period(max_loop_period)
+        })
+        .unwrap();
+        let expected: Vec<_> = (0..1024).map(|i| GoldilocksField::from(i % 6)).collect();
+        assert_eq!(witness[0].1, expected);
+        queries.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn loop_with_period_6() {
+        // With a period of 6, the loop is detected at row 100 and the remaining
+        // rows are copied without querying the prover.
+        assert!(queries_with_loop_period(16) < 200);
+        assert!(queries_with_loop_period(4) > 1000);
+    }
+
+    #[test]
+    fn progress_callback() {
+        let src = r#"
+namespace Counter(4096);
+    pol fixed ISLAST(i) { i == 4095 };
+    col witness x;
+    x' = (1 - ISLAST) * (x + 1);
+"#;
+        let reports = Arc::new(Mutex::new(vec![]));
+        let callback_reports = reports.clone();
+        generate_with(src, unused_query_callback(), |generator| {
+            generator
+                // Otherwise, the column is computed without processing the rows.
+                .with_affine_fast_path(false)
+                .with_progress_callback(Some(Box::new(move |progress| {
+                    callback_reports.lock().unwrap().push(progress)
+                })))
+        })
+        .unwrap();
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|p| p.degree == 4096 && p.row < 4096));
+        assert!(reports.windows(2).all(|w| w[0].row + 1000 <= w[1].row));
+    }
+
+    #[test]
+    fn row_spilling() {
+        let src = r#"
+namespace Sum(32768);
+    pol fixed ISLAST(i) { i == 32767 };
+    col witness x, sum;
+    x' = (1 - ISLAST) * (x + 1);
+    sum' = (1 - ISLAST) * (sum + x);
+"#;
+        let in_memory = generate_with(src, unused_query_callback(), |generator| {
+            generator.with_affine_fast_path(false)
+        });
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let spilled = generate_with(src, unused_query_callback(), |generator| {
+            generator
+                .with_affine_fast_path(false)
+                .with_row_spilling(Some(dir.to_path_buf()))
+        });
+        assert_eq!(spilled.unwrap(), in_memory.unwrap());
+        // The spill files are removed after reading them back.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn column_roles() {
+        let src = r#"
+namespace N(4);
+    #[role(first_row)] col fixed FIRST = [1] + [0]*;
+    col fixed ZERO = [0]*;
+    col witness x;
+    FIRST * x = ZERO;
+"#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed = FixedData::new(&analyzed, &constants, vec![]);
+        let first = fixed.try_column_by_name("N.FIRST").unwrap();
+        let zero = fixed.try_column_by_name("N.ZERO").unwrap();
+        assert_eq!(fixed.column_role(&first), Some(ColumnRole::FirstRow));
+        assert_eq!(fixed.column_role(&zero), None);
+        assert_eq!(
+            fixed
+                .columns_with_role(ColumnRole::FirstRow)
+                .collect::<Vec<_>>(),
+            vec![first]
+        );
+        assert!(fixed
+            .columns_with_role(ColumnRole::LastRow)
+            .next()
+            .is_none());
+    }
 }
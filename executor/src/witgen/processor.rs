@@ -1,12 +1,17 @@
 use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
 
 use ast::{
-    analyzed::{AlgebraicExpression as Expression, AlgebraicReference, Identity, PolyID},
+    analyzed::{
+        AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
+    },
     parsed::SelectedExpressions,
 };
 use number::{DegreeType, FieldElement};
 use parser_util::lines::indent;
 
+use crate::parallel::prelude::*;
 use crate::witgen::{query_processor::QueryProcessor, util::try_to_simple_poly, Constraint};
 
 use super::{
@@ -161,6 +166,28 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
         Ok(self.apply_updates(row_index, &updates, || "queries".to_string()))
     }
 
+    /// Sets the unknown values of witness columns that have a hint, see
+    /// [QueryProcessor::process_hint].
+    pub fn process_hints(&mut self, row_index: usize) -> Result<bool, EvalError<T>> {
+        let mut query_processor =
+            QueryProcessor::new(self.fixed_data, self.mutable_state.query_callback);
+        let global_row_index = self.row_offset + row_index as u64;
+        let row_pair = RowPair::new(
+            &self.data[row_index],
+            &self.data[row_index + 1],
+            global_row_index,
+            self.fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let mut updates = EvalValue::complete(vec![]);
+        for poly_id in self.fixed_data.witness_cols.keys() {
+            if self.is_relevant_witness[&poly_id] {
+                updates.combine(query_processor.process_hint(&row_pair, &poly_id)?);
+            }
+        }
+        Ok(self.apply_updates(row_index, &updates, || "hints".to_string()))
+    }
+
     /// Given a row and identity index, computes any updates and applies them.
     /// @returns the `IdentityResult`.
     pub fn process_identity(
@@ -334,10 +361,23 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
         self.data.finalize_range(range)
     }
 
+    pub fn spill_finalized(&mut self, dir: &Path) {
+        self.data.spill_finalized(dir)
+    }
+
+    pub fn estimated_size_bytes(&self) -> u64 {
+        self.data.estimated_size_bytes()
+    }
+
     pub fn row(&self, i: usize) -> &Row<'a, T> {
         &self.data[i]
     }
 
+    /// Returns the ith row if it exists and has not been finalized yet.
+    pub fn row_in_progress(&self, i: usize) -> Option<&Row<'a, T>> {
+        self.data.get(i)
+    }
+
     pub fn has_outer_query(&self) -> bool {
         self.outer_query.is_some()
     }
@@ -352,6 +392,50 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
         }
     }
 
+    /// Checks the identities returned by `identities` for each row in `rows` on that row and
+    /// the next one, assuming zero for unknown values. The rows are checked in parallel.
+    /// Machine calls cannot be evaluated without calling the machine, so any machine call
+    /// returned by `identities` is reported as incomplete.
+    /// @returns the rows on which some identities fail (in order), with all errors of the row.
+    pub fn check_rows_assuming_zero(
+        &self,
+        rows: Range<usize>,
+        identities: impl Fn(usize) -> Vec<&'a Identity<Expression<T>>> + Sync,
+    ) -> Vec<(usize, Vec<EvalError<T>>)> {
+        let data = &self.data;
+        let fixed_data = self.fixed_data;
+        let row_offset = self.row_offset;
+        rows.into_par_iter()
+            .filter_map(|row_index| {
+                let row_pair = RowPair::new(
+                    &data[row_index],
+                    &data[row_index + 1],
+                    row_offset + row_index as DegreeType,
+                    fixed_data,
+                    UnknownStrategy::Zero,
+                );
+                let errors = identities(row_index)
+                    .into_iter()
+                    .filter_map(|identity| {
+                        let error = match identity.kind {
+                            // The fact that we got to the point where we assume 0 for unknown cells, but this identity
+                            // is still not complete, means that either the inputs or the machine is under-constrained.
+                            IdentityKind::Plookup | IdentityKind::Permutation => "This machine call could not be completed. Either some inputs are missing or the machine is under-constrained.".to_string(),
+                            _ => match row_pair.evaluate(identity.expression_for_poly_id()) {
+                                Err(_) => return None,
+                                Ok(evaluated) => {
+                                    evaluated.solve_with_range_constraints(&row_pair).err()?.to_string()
+                                }
+                            },
+                        };
+                        Some(format!("{identity}:\n{}", indent(&error, "    ")).into())
+                    })
+                    .collect::<Vec<_>>();
+                (!errors.is_empty()).then_some((row_index, errors))
+            })
+            .collect()
+    }
+
     /// Checks whether a given identity is satisfied on a proposed row.
     pub fn check_row_pair(
         &mut self,
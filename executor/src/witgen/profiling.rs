@@ -0,0 +1,195 @@
+//! Per-identity statistics of a witness generation run.
+//!
+//! When enabled with [super::WitnessGenerator::with_witgen_profiling], witness generation
+//! counts how often each identity is processed and how often this completes it, and
+//! measures the time spent on it. At the end of the run, the statistics are written to
+//! a JSON file (usually [WITGEN_REPORT_FILE_NAME]) and the identities that took the most
+//! time are logged. Measuring the time has a noticeable overhead, so it is disabled by
+//! default.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use ast::analyzed::{AlgebraicExpression as Expression, Identity, IdentityKind};
+use number::FieldElement;
+use serde::{Deserialize, Serialize};
+
+/// The name of the report file written next to the witness values.
+pub const WITGEN_REPORT_FILE_NAME: &str = "witgen_report.json";
+
+/// The number of identities logged at the end of witness generation.
+const LOGGED_IDENTITIES: usize = 20;
+
+/// The statistics of one identity.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityProfile {
+    /// The ID of the identity, which is specific to its kind.
+    pub id: u64,
+    /// The kind of the identity: `polynomial`, `plookup`, `permutation` or `connect`.
+    pub kind: String,
+    /// The source location of the identity, as `file:line`.
+    pub source: String,
+    pub identity: String,
+    /// How often the identity was processed.
+    pub invocations: u64,
+    /// How often processing the identity completed it.
+    pub successes: u64,
+    /// The total time spent processing the identity in nanoseconds, including
+    /// the time spent in the machines called by lookups and permutations.
+    pub time_ns: u64,
+}
+
+/// The statistics of all identities of a witness generation run, in source order.
+/// Identities that are never processed (e.g. because they are turned into range
+/// constraints) are included with zero invocations.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitgenReport {
+    pub identities: Vec<IdentityProfile>,
+}
+
+impl WitgenReport {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read(path).map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .map_err(|e| format!("Invalid witgen report {}: {e}", path.display()))
+    }
+
+    /// A table of the `count` identities that took the most time.
+    pub fn top_table(&self, count: usize) -> String {
+        let total_ns = self
+            .identities
+            .iter()
+            .map(|p| p.time_ns)
+            .sum::<u64>()
+            .max(1);
+        let mut identities = self.identities.iter().collect::<Vec<_>>();
+        identities.sort_by_key(|p| std::cmp::Reverse(p.time_ns));
+
+        let mut table = format!(
+            "{:>10}  {:>6}  {:>11}  {:>8}  {:<24}  identity",
+            "time (ms)", "share", "invocations", "success", "source"
+        );
+        for p in identities.into_iter().take(count) {
+            let success = match p.invocations {
+                0 => "-".to_string(),
+                n => format!("{}%", p.successes * 100 / n),
+            };
+            let identity = if p.identity.chars().count() > 80 {
+                format!("{}...", p.identity.chars().take(77).collect::<String>())
+            } else {
+                p.identity.clone()
+            };
+            table.push_str(&format!(
+                "\n{:>10.3}  {:>5}%  {:>11}  {:>8}  {:<24}  {identity}",
+                p.time_ns as f64 / 1e6,
+                p.time_ns * 100 / total_ns,
+                p.invocations,
+                success,
+                p.source,
+            ));
+        }
+        table
+    }
+}
+
+/// Collects the statistics of a witness generation run and writes them to a file
+/// at its end. See [super::WitnessGenerator::with_witgen_profiling].
+pub struct WitgenProfiler {
+    path: PathBuf,
+    report: Mutex<ProfileData>,
+}
+
+#[derive(Default)]
+struct ProfileData {
+    report: WitgenReport,
+    /// The index of each identity in the report.
+    index: HashMap<(u64, IdentityKind), usize>,
+}
+
+impl WitgenProfiler {
+    /// Creates (or truncates) the report file at `path`.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        File::create(path).map_err(|e| format!("Could not create {}: {e}", path.display()))?;
+        Ok(WitgenProfiler {
+            path: path.to_path_buf(),
+            report: Default::default(),
+        })
+    }
+
+    /// Adds the identities of the PIL to the report, so that identities that are never
+    /// processed are part of it as well.
+    pub(crate) fn register<T: FieldElement>(&self, identities: &[Identity<Expression<T>>]) {
+        let mut data = self.report.lock().unwrap();
+        for identity in identities {
+            data.entry(identity);
+        }
+    }
+
+    pub(crate) fn record<T: FieldElement>(
+        &self,
+        identity: &Identity<Expression<T>>,
+        success: bool,
+        time: Duration,
+    ) {
+        let mut data = self.report.lock().unwrap();
+        let profile = data.entry(identity);
+        profile.invocations += 1;
+        profile.successes += success as u64;
+        profile.time_ns += time.as_nanos() as u64;
+    }
+
+    /// Writes the report and logs the identities that took the most time.
+    pub(crate) fn finish(&self) -> Result<(), String> {
+        let data = self.report.lock().unwrap();
+        log::info!(
+            "Witness generation profile ({} identities, top {LOGGED_IDENTITIES} by time):\n{}",
+            data.report.identities.len(),
+            data.report.top_table(LOGGED_IDENTITIES)
+        );
+        let file = File::create(&self.path)
+            .map_err(|e| format!("Could not create {}: {e}", self.path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &data.report)
+            .map_err(|e| format!("Could not write {}: {e}", self.path.display()))?;
+        log::info!("Wrote {}.", self.path.display());
+        Ok(())
+    }
+}
+
+impl ProfileData {
+    fn entry<T: FieldElement>(
+        &mut self,
+        identity: &Identity<Expression<T>>,
+    ) -> &mut IdentityProfile {
+        let identities = &mut self.report.identities;
+        let index = *self
+            .index
+            .entry((identity.id, identity.kind))
+            .or_insert_with(|| {
+                identities.push(IdentityProfile {
+                    id: identity.id,
+                    kind: kind_name(identity.kind).to_string(),
+                    source: format!("{}:{}", identity.source.file, identity.source.line),
+                    identity: identity.to_string(),
+                    invocations: 0,
+                    successes: 0,
+                    time_ns: 0,
+                });
+                identities.len() - 1
+            });
+        &mut identities[index]
+    }
+}
+
+fn kind_name(kind: IdentityKind) -> &'static str {
+    match kind {
+        IdentityKind::Polynomial => "polynomial",
+        IdentityKind::Plookup => "plookup",
+        IdentityKind::Permutation => "permutation",
+        IdentityKind::Connect => "connect",
+    }
+}
@@ -0,0 +1,108 @@
+//! Query callbacks that answer the prover queries of a program from given free inputs.
+
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use number::FieldElement;
+
+use super::QueryCallback;
+
+/// Answers the prover queries with the given free inputs, which are all in channel 0.
+pub fn inputs_to_query_callback<T: FieldElement>(inputs: Vec<T>) -> impl QueryCallback<T> {
+    channel_inputs_to_query_callback(HashMap::from([(T::zero(), inputs)]))
+}
+
+/// Answers the prover queries with the given free inputs by input channel.
+/// The query `("input", <channel>, <index>)` reads from the given channel,
+/// the single-channel form `("input", <index>)` reads from channel 0.
+#[allow(clippy::print_stdout)]
+pub fn channel_inputs_to_query_callback<T: FieldElement>(
+    inputs: HashMap<T, Vec<T>>,
+) -> impl QueryCallback<T> {
+    // TODO: Pass bootloader inputs into this function
+    // Right now, accessing bootloader inputs will always fail, because it will be out of bounds
+    let bootloader_inputs: [T; 0] = [];
+
+    let channel_input = move |channel: T, index: usize| -> Result<Option<T>, String> {
+        let channel_inputs = inputs.get(&channel).ok_or_else(|| {
+            format!("Error accessing prover inputs: No inputs in channel {channel}")
+        })?;
+        if let Some(value) = channel_inputs.get(index).cloned() {
+            log::trace!("Input query: Channel {channel}, index {index} -> {value}");
+            Ok(Some(value))
+        } else {
+            Err(format!(
+                "Error accessing prover inputs: Input index {index} requested in channel {channel}, but only {} inputs were provided",
+                channel_inputs.len()
+            ))
+        }
+    };
+
+    move |query: &str| -> Result<Option<T>, String> {
+        // TODO In the future, when match statements need to be exhaustive,
+        // This function probably gets an Option as argument and it should
+        // answer None by Ok(None).
+
+        // We are expecting a tuple
+        let query = query
+            .strip_prefix('(')
+            .and_then(|q| q.strip_suffix(')'))
+            .ok_or_else(|| "Prover query has to be a tuple".to_string())?;
+        let items = query.split(',').map(|s| s.trim()).collect::<Vec<_>>();
+        match &items[..] {
+            ["\"input\"", index] => {
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|e| format!("Error parsing index: {e})"))?;
+                channel_input(T::zero(), index)
+            }
+            ["\"input\"", channel, index] => {
+                let channel = channel
+                    .parse::<u64>()
+                    .map_err(|e| format!("Error parsing channel: {e})"))?;
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|e| format!("Error parsing index: {e})"))?;
+                channel_input(channel.into(), index)
+            }
+            ["\"data\"", index, what] => {
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|e| format!("Error parsing index: {e})"))?;
+                let what = what
+                    .parse::<usize>()
+                    .map_err(|e| format!("Error parsing what: {e})"))?;
+                assert_eq!(what, 0);
+                channel_input(T::zero(), index)
+            }
+            ["\"bootloader_input\"", index] => {
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|e| format!("Error parsing index: {e})"))?;
+                let value = bootloader_inputs.get(index).cloned();
+                if let Some(value) = value {
+                    log::trace!("Bootloader input query: Index {index} -> {value}");
+                    Ok(Some(value))
+                } else {
+                    Err(format!(
+                        "Error accessing bootloader inputs: Input index {index} requested, but only {} inputs were provided",
+                        bootloader_inputs.len()
+                    ))
+                }
+            }
+            ["\"print_char\"", ch] => {
+                print!(
+                    "{}",
+                    ch.parse::<u8>()
+                        .map_err(|e| format!("Invalid char to print: {e}"))?
+                        as char
+                );
+                // We do not answer None because we don't want this function to be
+                // called again.
+                Ok(Some(0.into()))
+            }
+            ["\"hint\"", value] => Ok(Some(T::from_str(value))),
+            k => Err(format!("Unsupported query: {}", k.iter().format(", "))),
+        }
+    }
+}
@@ -28,20 +28,48 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
 
         if let Some(query) = column.query.as_ref() {
             if rows.get_value(&column.poly).is_none() {
-                return self.process_witness_query(query, &column.poly, rows);
+                return self.process_witness_query(query, &column.poly, column.array_index, rows);
             }
         }
         // Either no query or the value is already known.
         Ok(EvalValue::complete(vec![]))
     }
 
+    /// Computes the value of a witness column from its hint, if it has one and the
+    /// value is not known yet. Unlike a query, the hint is evaluated directly
+    /// and does not go through the query callback.
+    pub fn process_hint(&mut self, rows: &RowPair<T>, poly_id: &PolyID) -> EvalResult<'a, T> {
+        let column = &self.fixed_data.witness_cols[poly_id];
+        let Some(hint) = column.hint else {
+            return Ok(EvalValue::complete(vec![]));
+        };
+        if rows.get_value(&column.poly).is_some() {
+            return Ok(EvalValue::complete(vec![]));
+        }
+        match self.evaluate_hint(hint, column.array_index, rows) {
+            Ok(value) => Ok(EvalValue::complete(vec![(
+                &column.poly,
+                Constraint::Assignment(value),
+            )])),
+            Err(EvalError::NoMatch()) => Ok(EvalValue::complete(vec![])),
+            Err(EvalError::DataNotAvailable) => {
+                Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable))
+            }
+            Err(e) => Err(super::EvalError::ProverQueryError(format!(
+                "Hint for column {} in row {} failed: {e:?}",
+                column.poly.name, rows.current_row_index
+            ))),
+        }
+    }
+
     fn process_witness_query(
         &mut self,
         query: &'a Expression<T>,
         poly: &'a AlgebraicReference,
+        array_index: Option<u64>,
         rows: &RowPair<T>,
     ) -> EvalResult<'a, T> {
-        let query_str = match self.interpolate_query(query, rows) {
+        let query_str = match self.interpolate_query(query, array_index, rows) {
             Ok(query) => query,
             Err(e) => {
                 return match e {
@@ -55,32 +83,70 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
                 };
             }
         };
-        Ok(
-            if let Some(value) =
-                (self.query_callback)(&query_str).map_err(super::EvalError::ProverQueryError)?
-            {
-                EvalValue::complete(vec![(poly, Constraint::Assignment(value))])
-            } else {
-                EvalValue::incomplete(IncompleteCause::NoQueryAnswer(
-                    query_str,
-                    poly.name.to_string(),
-                ))
-            },
-        )
+        let answer = (self.query_callback)(&query_str);
+        if let Some(recorder) = &self.fixed_data.query_recorder {
+            recorder
+                .record(&query_str, &answer, rows.current_row_index, &poly.name)
+                .map_err(super::EvalError::ProverQueryError)?;
+        }
+        let answer = answer.map_err(|e| {
+            super::EvalError::ProverQueryError(format!(
+                "Query {query_str} for column {} in row {} failed: {e}",
+                poly.name, rows.current_row_index
+            ))
+        })?;
+        Ok(if let Some(value) = answer {
+            EvalValue::complete(vec![(poly, Constraint::Assignment(value))])
+        } else {
+            EvalValue::incomplete(IncompleteCause::NoQueryAnswer(
+                query_str,
+                poly.name.to_string(),
+            ))
+        })
     }
 
     fn interpolate_query(
         &self,
         query: &'a Expression<T>,
+        array_index: Option<u64>,
         rows: &RowPair<T>,
     ) -> Result<String, EvalError> {
-        let arguments = vec![Rc::new(T::from(rows.current_row_index).into())];
+        self.evaluate_for_row(query, array_index, rows, |v| Ok(v.to_string()))
+    }
+
+    fn evaluate_hint(
+        &self,
+        hint: &'a Expression<T>,
+        array_index: Option<u64>,
+        rows: &RowPair<T>,
+    ) -> Result<T, EvalError> {
+        self.evaluate_for_row(hint, array_index, rows, |v| match v {
+            Value::Number(value) => Ok(value),
+            v => Err(EvalError::TypeError(format!(
+                "Expected the hint to return a number, but got {v}"
+            ))),
+        })
+    }
+
+    /// Calls the function `fun` with the row and converts the result using `convert`.
+    fn evaluate_for_row<R>(
+        &self,
+        fun: &'a Expression<T>,
+        array_index: Option<u64>,
+        rows: &RowPair<T>,
+        convert: impl FnOnce(Value<T, Reference>) -> Result<R, EvalError>,
+    ) -> Result<R, EvalError> {
+        // The function of an array element also receives the index of the element.
+        let arguments = std::iter::once(rows.current_row_index)
+            .chain(array_index)
+            .map(|argument| Rc::new(T::from(argument).into()))
+            .collect();
         let symbols = Symbols {
             fixed_data: self.fixed_data,
             rows,
         };
-        let fun = evaluator::evaluate(query, &symbols)?;
-        evaluator::evaluate_function_call(fun, arguments, &symbols).map(|v| v.to_string())
+        let fun = evaluator::evaluate(fun, &symbols)?;
+        convert(evaluator::evaluate_function_call(fun, arguments, &symbols)?)
     }
 }
 
@@ -93,13 +159,25 @@ struct Symbols<'a, T: FieldElement> {
 
 impl<'a, T: FieldElement> SymbolLookup<'a, T, Reference<'a>> for Symbols<'a, T> {
     fn lookup(&self, name: &'a str) -> Result<Value<'a, T, Reference<'a>>, EvalError> {
-        match self.fixed_data.try_column_by_name(name) {
-            Some(poly_id) => Ok(Value::Custom(Reference { name, poly_id })),
-            None => Err(EvalError::SymbolNotFound(format!(
+        if let Some(poly_id) = self.fixed_data.try_column_by_name(name) {
+            Ok(Value::Custom(Reference { name, poly_id }))
+        } else if let Some(elements) = self.fixed_data.try_array_by_name(name) {
+            Ok(Value::Array(
+                elements
+                    .into_iter()
+                    .map(|(name, poly_id)| Value::Custom(Reference { name, poly_id }))
+                    .collect(),
+            ))
+        } else {
+            Err(EvalError::SymbolNotFound(format!(
                 "Symbol {name} not found."
-            ))),
+            )))
         }
     }
+    fn lookup_degree(&self) -> Result<Value<'a, T, Reference<'a>>, EvalError> {
+        Ok(Value::Number(self.fixed_data.degree.into()))
+    }
+
     fn eval_function_application(
         &self,
         function: Reference<'a>,
@@ -0,0 +1,154 @@
+//! Recording and replaying of the answers to prover queries.
+//!
+//! A recording is a file with one JSON object per line and per query, containing the
+//! query, its answer (or the error returned by the query callback), and the row and
+//! column for which it was asked. Replaying a recording answers the same queries
+//! without the original query callback, so that a witness generation run can be
+//! reproduced even if the callback is nondeterministic or not available.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use number::{DegreeType, FieldElement};
+use serde::{Deserialize, Serialize};
+
+use super::QueryCallback;
+
+/// A prover query and its answer, as stored in a recording.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedQuery {
+    pub query: String,
+    /// The answer, `None` if the query callback did not know the answer.
+    pub answer: Option<String>,
+    /// The error returned by the query callback instead of an answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub row: DegreeType,
+    pub column: String,
+}
+
+/// Writes the answers to all prover queries of a witness generation run to a file.
+/// See [super::WitnessGenerator::with_query_recorder].
+pub struct QueryRecorder {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl QueryRecorder {
+    /// Creates (or truncates) the recording at `path`.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Could not create {}: {e}", path.display()))?;
+        Ok(QueryRecorder {
+            path: path.to_path_buf(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub(crate) fn record<T: FieldElement>(
+        &self,
+        query: &str,
+        answer: &Result<Option<T>, String>,
+        row: DegreeType,
+        column: &str,
+    ) -> Result<(), String> {
+        let entry = RecordedQuery {
+            query: query.to_string(),
+            answer: answer.as_ref().ok().and_then(|a| a.map(|v| v.to_string())),
+            error: answer.as_ref().err().cloned(),
+            row,
+            column: column.to_string(),
+        };
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, &entry)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(writer))
+            // Flush after each query so that the recording is complete even if
+            // the process is killed.
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Could not record query to {}: {e}", self.path.display()))
+    }
+}
+
+/// The recorded answers to a query and the index of the answer to serve next.
+type RecordedAnswers<T> = (Vec<Result<Option<T>, String>>, usize);
+
+/// Reads the recording at `path` and returns a query callback that answers queries from it.
+///
+/// If a query was asked several times, the answers are served in the recorded order,
+/// and the last one is repeated once they run out. Queries that are not part of the
+/// recording fail with an error.
+pub fn replay_query_callback<T: FieldElement>(
+    path: &Path,
+) -> Result<impl QueryCallback<T>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    let mut answers: HashMap<String, RecordedAnswers<T>> = HashMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: RecordedQuery = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: Invalid recorded query: {e}", path.display(), i + 1))?;
+        let answer = match entry.error {
+            Some(error) => Err(error),
+            None => Ok(entry.answer.map(|a| T::from_str(&a))),
+        };
+        answers.entry(entry.query).or_default().0.push(answer);
+    }
+    let recorded_count = answers.values().map(|(a, _)| a.len()).sum::<usize>();
+    let path = path.to_path_buf();
+
+    Ok(move |query: &str| -> Result<Option<T>, String> {
+        let Some((query_answers, next)) = answers.get_mut(query) else {
+            return Err(format!(
+                "Query {query} is not part of the recording {} ({recorded_count} recorded queries). \
+                Was it recorded with a different program or different inputs?",
+                path.display()
+            ));
+        };
+        let answer = query_answers[(*next).min(query_answers.len() - 1)].clone();
+        *next += 1;
+        answer
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let path = dir.join("queries.jsonl");
+        let recorder = QueryRecorder::create(&path).unwrap();
+        for (answer, row) in [
+            (Ok(Some(GoldilocksField::from(7))), 0),
+            (Ok(None), 1),
+            (Ok(Some(GoldilocksField::from(8))), 1),
+        ] {
+            recorder
+                .record(&format!("(\"x\", {})", row.min(1)), &answer, row, "N.x")
+                .unwrap();
+        }
+        recorder
+            .record::<GoldilocksField>("(\"y\", 0)", &Err("no".to_string()), 0, "N.y")
+            .unwrap();
+
+        let mut callback = replay_query_callback::<GoldilocksField>(&path).unwrap();
+        assert_eq!(callback("(\"x\", 0)"), Ok(Some(7.into())));
+        assert_eq!(callback("(\"x\", 1)"), Ok(None));
+        assert_eq!(callback("(\"x\", 1)"), Ok(Some(8.into())));
+        // The last answer is repeated.
+        assert_eq!(callback("(\"x\", 1)"), Ok(Some(8.into())));
+        assert_eq!(callback("(\"y\", 0)"), Err("no".to_string()));
+        let error = callback("(\"x\", 2)").unwrap_err();
+        assert!(error.contains("not part of the recording"), "{error}");
+    }
+}
@@ -82,6 +82,16 @@ impl<T: FieldElement> RangeConstraint<T> {
         range_width(self.min, self.max)
     }
 
+    /// Returns the number of bits needed to represent all values allowed by the constraint.
+    pub fn bit_width(&self) -> u64 {
+        let mask_bits = self.mask.num_bits() as u64;
+        if self.min <= self.max {
+            cmp::min(mask_bits, self.max.to_integer().num_bits() as u64)
+        } else {
+            mask_bits
+        }
+    }
+
     /// The range constraint of the sum of two expressions.
     pub fn combine_sum(&self, other: &Self) -> Self {
         // TODO we could use "add_with_carry" to see if this created an overflow.
@@ -311,6 +321,16 @@ mod test {
         );
     }
 
+    #[test]
+    fn bit_width() {
+        assert_eq!(RCg::from_max_bit(7).bit_width(), 8);
+        assert_eq!(RCg::from_mask(0xf00fu32).bit_width(), 16);
+        assert_eq!(RCg::from_value(0.into()).bit_width(), 0);
+        assert_eq!(RCg::from_range(3.into(), 300.into()).bit_width(), 9);
+        // A wrapping range can only be represented by the full mask.
+        assert_eq!(RCg::from_range(8.into(), 2.into()).bit_width(), 64);
+    }
+
     #[test]
     fn combine_sum() {
         assert_eq!(
@@ -0,0 +1,324 @@
+//! Testing support for "row gadgets", i.e. constraints that only span a single row
+//! (and possibly the previous one), in isolation from the machine they are part of.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use ast::analyzed::{Analyzed, IdentityKind, PolyID, PolynomialType};
+use number::{DegreeType, FieldElement};
+
+use super::{
+    block_processor::BlockProcessor,
+    data_structures::finalizable_data::FinalizableData,
+    global_constraints,
+    identity_processor::Machines,
+    machines::FixedLookup,
+    rows::{CellValue, Row, RowFactory},
+    sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator},
+    FixedData, MutableState,
+};
+
+/// The identities a [RowGadgetTester] solves.
+#[derive(Debug, Clone)]
+pub enum GadgetIdentities {
+    /// All identities of the PIL.
+    All,
+    /// The identities of the given kind with the given IDs.
+    Ids(IdentityKind, Vec<u64>),
+    /// The identities tagged with the attribute `#[tag]`.
+    Tag(String),
+}
+
+/// The reason a [RowGadgetTester] could not solve a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GadgetFailure {
+    /// There is no witness column of this name.
+    UnknownColumn(String),
+    /// The assigned values violate an identity. Contains the identity and the error.
+    Conflict(String),
+}
+
+impl fmt::Display for GadgetFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GadgetFailure::UnknownColumn(name) => write!(f, "Witness column {name} not found."),
+            GadgetFailure::Conflict(e) => write!(f, "Identity violated: {e}"),
+        }
+    }
+}
+
+/// Runs the witness generation solver on a single row for a subset of the identities,
+/// e.g. to property-test a gadget with random inputs without building a machine around it.
+/// The range constraints are derived from all identities, as in witness generation.
+pub struct RowGadgetTester<'a, T> {
+    analyzed: &'a Analyzed<T>,
+    fixed_col_values: &'a [(&'a str, Vec<T>)],
+    identities: GadgetIdentities,
+}
+
+impl<'a, T: FieldElement> RowGadgetTester<'a, T> {
+    pub fn new(
+        analyzed: &'a Analyzed<T>,
+        fixed_col_values: &'a [(&'a str, Vec<T>)],
+        identities: GadgetIdentities,
+    ) -> Self {
+        RowGadgetTester {
+            analyzed,
+            fixed_col_values,
+            identities,
+        }
+    }
+
+    /// Solves `row` given the values of some of its witness columns (`assignments`)
+    /// and of the previous row (`previous_row`, for identities with next references).
+    /// Prover queries are not answered.
+    /// @returns the known values of the witness columns in `row`, including the assigned ones.
+    /// Cells the identities do not determine are missing.
+    ///
+    /// # Panics
+    /// Panics if `row` is not in `1..degree`, because the window cannot wrap around.
+    pub fn solve(
+        &self,
+        row: DegreeType,
+        assignments: &[(&str, T)],
+        previous_row: &[(&str, T)],
+    ) -> Result<BTreeMap<String, T>, GadgetFailure> {
+        let degree = self.analyzed.degree();
+        assert!(0 < row && row < degree, "Row {row} is not in 1..{degree}.");
+        let identities = self
+            .analyzed
+            .identities_with_inlined_intermediate_polynomials();
+        let fixed_data = FixedData::new(self.analyzed, self.fixed_col_values, vec![]);
+        let (global_range_constraints, retained_identities) =
+            global_constraints::determine_global_constraints(
+                &fixed_data,
+                identities.iter().collect(),
+            );
+        let identities = retained_identities
+            .into_iter()
+            .filter(|identity| match &self.identities {
+                GadgetIdentities::All => true,
+                GadgetIdentities::Ids(kind, ids) => {
+                    identity.kind == *kind && ids.contains(&identity.id)
+                }
+                GadgetIdentities::Tag(tag) => identity.attribute.as_ref() == Some(tag),
+            })
+            .collect::<Vec<_>>();
+
+        // The window consists of the previous row, `row` and the next row.
+        let row_factory = RowFactory::new(&fixed_data, global_range_constraints.clone());
+        let mut rows = [row - 1, row, row + 1].map(|r| row_factory.fresh_row(r % degree));
+        assign(&fixed_data, &mut rows[0], previous_row)?;
+        assign(&fixed_data, &mut rows[1], assignments)?;
+
+        let witness_cols = fixed_data.witness_cols.keys().collect::<HashSet<_>>();
+        let data = FinalizableData::with_initial_rows_in_progress(&witness_cols, rows.into_iter());
+        let mut fixed_lookup = FixedLookup::new(global_range_constraints);
+        let mut query_callback = |_: &str| -> Result<Option<T>, String> { Ok(None) };
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from(std::iter::empty()),
+            query_callback: &mut query_callback,
+        };
+        let mut processor = BlockProcessor::new(
+            row - 1,
+            data,
+            &mut mutable_state,
+            &identities,
+            &fixed_data,
+            &witness_cols,
+        );
+        let mut sequence_iterator = ProcessingSequenceIterator::Default(
+            DefaultSequenceIterator::new(1, identities.len(), None),
+        );
+        processor
+            .solve(&mut sequence_iterator)
+            .map_err(|e| GadgetFailure::Conflict(e.to_string()))?;
+
+        let data = processor.finish();
+        Ok(data[1]
+            .iter()
+            .filter_map(|(_, cell)| match cell.value {
+                CellValue::Known(value) => Some((cell.name.to_string(), value)),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+fn assign<T: FieldElement>(
+    fixed_data: &FixedData<T>,
+    row: &mut Row<T>,
+    values: &[(&str, T)],
+) -> Result<(), GadgetFailure> {
+    for (name, value) in values {
+        let poly_id: PolyID = fixed_data
+            .try_column_by_name(name)
+            .filter(|poly_id| poly_id.ptype == PolynomialType::Committed)
+            .ok_or_else(|| GadgetFailure::UnknownColumn(name.to_string()))?;
+        row[&poly_id].value = CellValue::Known(*value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use number::GoldilocksField;
+    use pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+    use proptest::test_runner::{Config, TestError, TestRunner};
+
+    use crate::constant_evaluator::generate;
+
+    use super::*;
+
+    /// Adds two 32-bit numbers given as bytes, dropping the overflow.
+    /// `CARRY_IN_1` is the weight of the carry into the second byte, which is
+    /// one in the correct gadget.
+    const ADD: &str = r#"
+namespace Add(256);
+    col fixed BYTE(i) { i };
+    col witness a[4], b[4], c[4], carry[4];
+    { a[0] } in { BYTE };
+    { a[1] } in { BYTE };
+    { a[2] } in { BYTE };
+    { a[3] } in { BYTE };
+    { b[0] } in { BYTE };
+    { b[1] } in { BYTE };
+    { b[2] } in { BYTE };
+    { b[3] } in { BYTE };
+    { c[0] } in { BYTE };
+    { c[1] } in { BYTE };
+    { c[2] } in { BYTE };
+    { c[3] } in { BYTE };
+    carry[0] * (1 - carry[0]) = 0;
+    carry[1] * (1 - carry[1]) = 0;
+    carry[2] * (1 - carry[2]) = 0;
+    carry[3] * (1 - carry[3]) = 0;
+    #[add] a[0] + b[0] = c[0] + 256 * carry[0];
+    #[add] a[1] + b[1] + CARRY_IN_1 * carry[0] = c[1] + 256 * carry[1];
+    #[add] a[2] + b[2] + carry[1] = c[2] + 256 * carry[2];
+    #[add] a[3] + b[3] + carry[2] = c[3] + 256 * carry[3];
+"#;
+
+    fn bytes(name: &str, value: u32) -> Vec<(String, GoldilocksField)> {
+        value
+            .to_le_bytes()
+            .into_iter()
+            .enumerate()
+            .map(|(i, byte)| (format!("Add.{name}[{i}]"), u32::from(byte).into()))
+            .collect()
+    }
+
+    /// Solves the gadget for `a` and `b` and returns `c`.
+    fn add(tester: &RowGadgetTester<GoldilocksField>, a: u32, b: u32) -> Option<u32> {
+        let inputs = bytes("a", a)
+            .into_iter()
+            .chain(bytes("b", b))
+            .collect::<Vec<_>>();
+        let inputs = inputs
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect::<Vec<_>>();
+        let cells = tester.solve(5, &inputs, &[]).unwrap();
+        let c = (0..4)
+            .map(|i| {
+                cells
+                    .get(&format!("Add.c[{i}]"))
+                    .map(|c| c.to_degree() as u8)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(u32::from_le_bytes(c.try_into().unwrap()))
+    }
+
+    fn run_add_property(carry_in_1: u64) -> Result<(), TestError<(u32, u32)>> {
+        let src = ADD.replace("CARRY_IN_1", &carry_in_1.to_string());
+        let analyzed = analyze_string::<GoldilocksField>(&src);
+        let constants = generate(&analyzed);
+        let tester = RowGadgetTester::new(
+            &analyzed,
+            &constants,
+            GadgetIdentities::Tag("add".to_string()),
+        );
+        let mut runner = TestRunner::new(Config {
+            cases: 64,
+            failure_persistence: None,
+            ..Config::default()
+        });
+        runner.run(&(any::<u32>(), any::<u32>()), |(a, b)| {
+            prop_assert_eq!(add(&tester, a, b), Some(a.wrapping_add(b)));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn addition() {
+        run_add_property(1).unwrap();
+    }
+
+    #[test]
+    fn addition_without_carry_is_caught() {
+        let Err(TestError::Fail(_, (a, b))) = run_add_property(0) else {
+            panic!("The missing carry should be caught.");
+        };
+        // Only an overflow of the first byte is affected.
+        assert!((a & 0xff) + (b & 0xff) >= 256);
+    }
+
+    #[test]
+    fn conflicting_assignment() {
+        let src = ADD.replace("CARRY_IN_1", "1");
+        let analyzed = analyze_string::<GoldilocksField>(&src);
+        let constants = generate(&analyzed);
+        let tester = RowGadgetTester::new(
+            &analyzed,
+            &constants,
+            GadgetIdentities::Ids(IdentityKind::Polynomial, vec![4]),
+        );
+        // Only the first byte is constrained by identity 4.
+        let solved = tester
+            .solve(
+                1,
+                &[("Add.a[0]", 200.into()), ("Add.b[0]", 100.into())],
+                &[],
+            )
+            .unwrap();
+        assert_eq!(solved["Add.c[0]"], 44.into());
+        assert_eq!(solved["Add.carry[0]"], 1.into());
+        assert!(!solved.contains_key("Add.c[1]"));
+
+        let failure = tester
+            .solve(
+                1,
+                &[
+                    ("Add.a[0]", 200.into()),
+                    ("Add.b[0]", 100.into()),
+                    ("Add.c[0]", 45.into()),
+                    ("Add.carry[0]", 1.into()),
+                ],
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(failure, GadgetFailure::Conflict(_)));
+
+        assert_eq!(
+            tester.solve(1, &[("Add.d", 1.into())], &[]),
+            Err(GadgetFailure::UnknownColumn("Add.d".to_string()))
+        );
+    }
+
+    #[test]
+    fn previous_row() {
+        let src = r#"
+namespace Counter(8);
+    col witness x;
+    x' = x + 1;
+"#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let tester = RowGadgetTester::new(&analyzed, &constants, GadgetIdentities::All);
+        let solved = tester.solve(3, &[], &[("Counter.x", 41.into())]).unwrap();
+        assert_eq!(solved["Counter.x"], 42.into());
+    }
+}
@@ -119,6 +119,14 @@ impl<T: FieldElement> Row<'_, T> {
         )
     }
 
+    /// The columns in `cols` with their values (`None` if unknown), in source order.
+    pub fn column_values(&self, cols: &HashSet<PolyID>) -> Vec<(&str, Option<T>)> {
+        self.iter()
+            .filter(|(col, _)| cols.contains(col))
+            .map(|(_, cell)| (cell.name, cell.value.clone().into()))
+            .collect()
+    }
+
     /// Builds a string listing all values, one by row. Nonzero entries are
     /// first, then zero, then unknown (if `include_unknown == true`).
     pub fn render_values(&self, include_unknown: bool, cols: Option<&HashSet<PolyID>>) -> String {
@@ -173,10 +181,12 @@ impl<'a, T: FieldElement> RowFactory<'a, T> {
                 .map(|(poly_id, range_constraint)| {
                     let name = self.fixed_data.column_name(&poly_id);
                     let value = match (
-                        self.fixed_data.external_witness(row, &poly_id),
+                        self.fixed_data
+                            .external_witness(row, &poly_id)
+                            .or_else(|| self.fixed_data.seeded_witness(row, &poly_id)),
                         range_constraint.as_ref(),
                     ) {
-                        (Some(external_witness), _) => CellValue::Known(external_witness),
+                        (Some(known_value), _) => CellValue::Known(known_value),
                         (None, Some(range_constraint)) => {
                             CellValue::RangeConstraint(range_constraint.clone())
                         }
@@ -1,24 +1,44 @@
 use ast::analyzed::{
     AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
 };
-use itertools::Itertools;
 use number::{DegreeType, FieldElement};
-use parser_util::lines::indent;
 use std::cmp::max;
-use std::collections::HashSet;
-use std::time::Instant;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use web_time::Instant;
 
+use crate::parallel::prelude::*;
 use crate::witgen::identity_processor::{self};
 use crate::witgen::IncompleteCause;
 
 use super::data_structures::finalizable_data::FinalizableData;
+use super::expression_evaluator::ExpressionEvaluator;
+use super::fixed_evaluator::FixedEvaluator;
+use super::memory_budget::DegradationStep;
 use super::processor::{OuterQuery, Processor};
 
+use super::machines::machine_extractor::refs_in_identity;
 use super::rows::{Row, RowFactory, UnknownStrategy};
-use super::{Constraints, EvalError, EvalValue, FixedData, MutableState, QueryCallback};
+use super::witgen_error::{
+    FailingIdentity, FailureContext, RowValues, WitgenError, WitgenErrorKind,
+};
+use super::{
+    Constraints, EvalError, EvalValue, FixedData, MutableState, QueryCallback, WitgenProgress,
+};
 
-/// Maximal period checked during loop detection.
-const MAX_PERIOD: usize = 4;
+/// Maximal period checked during loop detection, unless configured otherwise
+/// (see [super::WitnessGenerator::with_max_loop_period]).
+pub const DEFAULT_MAX_LOOP_PERIOD: usize = 16;
+
+/// Number of rows after which most rows are finalized.
+const FINALIZE_PERIOD: DegreeType = 10000;
+/// Number of rows after which most rows are finalized if witness generation is
+/// close to its memory budget.
+const AGGRESSIVE_FINALIZE_PERIOD: DegreeType = 100;
+/// Number of rows after which the memory usage is reported to the memory budget.
+const MEMORY_REPORT_PERIOD: DegreeType = 1000;
+/// Maximal number of further rows that are logged if identities fail on several rows
+/// in [VmProcessor::validate_rows].
+const MAX_REPORTED_FAILING_ROWS: usize = 10;
 
 /// A list of identities with a flag whether it is complete.
 struct CompletableIdentities<'a, T: FieldElement> {
@@ -38,11 +58,27 @@ impl<'a, T: FieldElement> CompletableIdentities<'a, T> {
             .iter_mut()
             .map(|(identity, complete)| (*identity, complete))
     }
+
+    /// Yields the machine calls that are not complete.
+    fn incomplete_machine_calls(&self) -> impl Iterator<Item = &'a Identity<Expression<T>>> + '_ {
+        self.identities_with_complete
+            .iter()
+            .filter(|(identity, complete)| {
+                !complete
+                    && matches!(
+                        identity.kind,
+                        IdentityKind::Plookup | IdentityKind::Permutation
+                    )
+            })
+            .map(|(identity, _)| *identity)
+    }
 }
 
 pub struct VmProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
     /// The global index of the first row of [VmProcessor::data].
     row_offset: DegreeType,
+    /// The name under which the memory usage is reported to the memory budget.
+    memory_site: String,
     /// The witness columns belonging to this machine
     witnesses: HashSet<PolyID>,
     fixed_data: &'a FixedData<'a, T>,
@@ -52,6 +88,17 @@ pub struct VmProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
     /// The subset of identities that does not contain a reference to the next row
     /// (precomputed once for performance reasons)
     identities_without_next_ref: Vec<&'a Identity<Expression<T>>>,
+    /// The polynomial identities that only reference fixed columns. They are not part of
+    /// the two lists above, see [VmProcessor::fixed_only_identities].
+    fixed_only_identities: Vec<&'a Identity<Expression<T>>>,
+    /// The rows on which the fixed-only identities might not hold, if they were checked
+    /// for all rows in advance (see [VmProcessor::check_fixed_only_identities]).
+    fixed_only_violations: Option<BTreeSet<DegreeType>>,
+    /// The machine calls that could not be completed, by row. Only recorded for the
+    /// main machine, they are reported by [VmProcessor::validate_rows].
+    incomplete_machine_calls: BTreeMap<DegreeType, Vec<&'a Identity<Expression<T>>>>,
+    /// The rows before this one have been checked by [VmProcessor::validate_rows].
+    validated_until: usize,
     last_report: DegreeType,
     last_report_time: Instant,
     row_factory: RowFactory<'a, T>,
@@ -61,6 +108,7 @@ pub struct VmProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T, Q> {
     pub fn new(
         row_offset: DegreeType,
+        name: &str,
         fixed_data: &'a FixedData<'a, T>,
         identities: &[&'a Identity<Expression<T>>],
         witnesses: &'c HashSet<PolyID>,
@@ -68,17 +116,27 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         row_factory: RowFactory<'a, T>,
         mutable_state: &'c mut MutableState<'a, 'b, T, Q>,
     ) -> Self {
+        let (fixed_only_identities, identities): (Vec<&Identity<_>>, Vec<_>) =
+            identities.iter().partition(|identity| {
+                identity.kind == IdentityKind::Polynomial
+                    && !identity.expression_for_poly_id().contains_witness_ref()
+            });
         let (identities_with_next, identities_without_next): (Vec<_>, Vec<_>) = identities
-            .iter()
+            .into_iter()
             .partition(|identity| identity.contains_next_ref());
         let processor = Processor::new(row_offset, data, mutable_state, fixed_data, witnesses);
 
         VmProcessor {
             row_offset,
+            memory_site: format!("vm:{name}"),
             witnesses: witnesses.clone(),
             fixed_data,
             identities_with_next_ref: identities_with_next,
             identities_without_next_ref: identities_without_next,
+            fixed_only_identities,
+            fixed_only_violations: None,
+            incomplete_machine_calls: BTreeMap::new(),
+            validated_until: 0,
             row_factory,
             last_report: 0,
             last_report_time: Instant::now(),
@@ -97,7 +155,31 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
 
     /// Starting out with a single row (at a given offset), iteratively append rows
     /// until we have exhausted the rows or the latch expression (if available) evaluates to 1.
-    pub fn run(&mut self) -> EvalValue<&'a AlgebraicReference, T> {
+    /// Returns an error if a row cannot be computed.
+    /// For the main machine, it is also an error if the identities do not hold on the computed
+    /// rows when unknown values are set to zero, see [VmProcessor::validate_rows].
+    pub fn run(&mut self) -> Result<EvalValue<&'a AlgebraicReference, T>, WitgenError> {
+        let result = self.compute_rows();
+        if self.processor.has_outer_query() {
+            return result;
+        }
+        match result {
+            // The error is only reported if the identities hold on the rows before it,
+            // so that the first failing row is reported.
+            Err(e) => Err(self
+                .validate_rows((e.row - self.row_offset) as usize)
+                .err()
+                .unwrap_or(e)),
+            Ok(value) if value.is_complete() => {
+                self.validate_rows(self.processor.len() - 1)?;
+                Ok(value)
+            }
+            // Witness generation has been cancelled.
+            Ok(value) => Ok(value),
+        }
+    }
+
+    fn compute_rows(&mut self) -> Result<EvalValue<&'a AlgebraicReference, T>, WitgenError> {
         assert!(self.processor.len() == 1);
 
         let mut outer_assignments = vec![];
@@ -106,20 +188,64 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         let mut looping_period = None;
         let mut loop_detection_log_level = log::Level::Info;
         let rows_left = self.fixed_data.degree - self.row_offset + 1;
+        // Secondary machines usually only compute a few rows per call, so checking
+        // all rows in advance is only worth it for the main machine.
+        if !self.processor.has_outer_query() {
+            self.check_fixed_only_identities(rows_left);
+        }
         let mut finalize_start = 1;
+        let mut finalize_period = FINALIZE_PERIOD;
         for row_index in 0..rows_left {
+            // Only the main machine stops early, secondary machines finish their block
+            // so that the calling machine does not fail on an incomplete lookup.
+            if !self.processor.has_outer_query() && self.fixed_data.is_cancelled() {
+                log::info!(
+                    "Witness generation cancelled at row {}.",
+                    row_index + self.row_offset
+                );
+                return Ok(EvalValue::incomplete(IncompleteCause::Cancelled));
+            }
+
             self.maybe_log_performance(row_index);
 
-            if (row_index + 1) % 10000 == 0 {
+            if (row_index + 1) % MEMORY_REPORT_PERIOD == 0 {
+                self.fixed_data
+                    .record_memory_usage(&self.memory_site, self.processor.estimated_size_bytes());
+                if self.fixed_data.is_degraded_to(DegradationStep::Abort) {
+                    return Err(self.memory_budget_exceeded(row_index));
+                }
+                if self
+                    .fixed_data
+                    .is_degraded_to(DegradationStep::FinalizeAggressively)
+                {
+                    finalize_period = AGGRESSIVE_FINALIZE_PERIOD;
+                }
+            }
+
+            if (row_index + 1) % finalize_period == 0 {
                 // Periodically make sure most rows are finalized.
-                // Row 0 and the last MAX_PERIOD rows might be needed later, so they are not finalized.
-                let finalize_end = row_index as usize - MAX_PERIOD;
+                // Row 0 and the last rows that might be needed for loop detection
+                // (twice the maximal period) are not finalized.
+                let finalize_end = (row_index as usize)
+                    .saturating_sub(2 * self.fixed_data.max_loop_period)
+                    .max(finalize_start);
+                if !self.processor.has_outer_query() {
+                    self.validate_rows(finalize_end)?;
+                }
                 self.processor.finalize_range(finalize_start..finalize_end);
                 finalize_start = finalize_end;
+                if let Some(dir) = self.fixed_data.row_spilling_dir() {
+                    self.processor.spill_finalized(&dir);
+                }
             }
 
             // Check if we are in a loop.
-            if looping_period.is_none() && row_index % 100 == 0 && row_index > 0 {
+            // Rows with seeded values are always computed, so that the values are checked.
+            if looping_period.is_none()
+                && row_index % 100 == 0
+                && row_index > 0
+                && row_index + self.row_offset >= self.fixed_data.seeded_rows
+            {
                 looping_period = self.rows_are_repeating(row_index);
                 if let Some(p) = looping_period {
                     log::log!(
@@ -130,7 +256,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             }
             if let Some(period) = looping_period {
                 let proposed_row = self.processor.row(row_index as usize - period).clone();
-                if !self.try_proposed_row(row_index, proposed_row) {
+                if !self.try_proposed_row(row_index, proposed_row)? {
                     log::log!(
                         loop_detection_log_level,
                         "Looping failed. Trying to generate regularly again. (Use RUST_LOG=debug to see whether this happens more often.)"
@@ -146,26 +272,26 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             // add and compute some values for the next row as well.
             if looping_period.is_none() && row_index != rows_left - 1 {
                 self.ensure_has_next_row(row_index);
-                outer_assignments.extend(self.compute_row(row_index).into_iter());
+                outer_assignments.extend(self.compute_row(row_index)?.into_iter());
 
                 // Evaluate latch expression and return if it evaluates to 1.
                 if let Some(latch) = self.processor.latch_value(row_index as usize) {
                     if latch {
                         log::trace!("Machine returns!");
                         if self.processor.finshed_outer_query() {
-                            return EvalValue::complete(outer_assignments);
+                            return Ok(EvalValue::complete(outer_assignments));
                         } else {
-                            return EvalValue::incomplete_with_constraints(
+                            return Ok(EvalValue::incomplete_with_constraints(
                                 outer_assignments,
                                 IncompleteCause::BlockMachineLookupIncomplete,
-                            );
+                            ));
                         }
                     }
                 } else if self.processor.has_outer_query() {
                     // If we have an outer query (and therefore a latch expression),
                     // its value should be known at this point.
                     // Probably, we don't have all the necessary inputs.
-                    return EvalValue::incomplete(IncompleteCause::UnknownLatch);
+                    return Ok(EvalValue::incomplete(IncompleteCause::UnknownLatch));
                 }
             };
         }
@@ -175,18 +301,19 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             self.fixed_data.degree + 1
         );
 
-        EvalValue::complete(outer_assignments)
+        Ok(EvalValue::complete(outer_assignments))
     }
 
     /// Checks if the last rows are repeating and returns the period.
-    /// Only checks for periods of 1, ..., MAX_PERIOD.
+    /// Only checks for periods of 1, ..., max_loop_period.
     fn rows_are_repeating(&self, row_index: DegreeType) -> Option<usize> {
-        if row_index < MAX_PERIOD as DegreeType {
+        let max_period = self.fixed_data.max_loop_period;
+        if row_index < 2 * max_period as DegreeType {
             return None;
         }
 
         let row = row_index as usize;
-        (1..MAX_PERIOD).find(|&period| {
+        (1..=max_period).find(|&period| {
             (1..=period).all(|i| {
                 self.processor
                     .row(row - i - period)
@@ -197,6 +324,132 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         })
     }
 
+    /// Evaluates the identities that only reference fixed columns on the first `row_count`
+    /// rows and records the rows on which they do not hold (or cannot be evaluated).
+    /// Only on these rows they are processed together with the other identities,
+    /// which reports the failure in the same way as for any other identity.
+    fn check_fixed_only_identities(&mut self, row_count: DegreeType) {
+        if self.fixed_only_identities.is_empty() {
+            self.fixed_only_violations = Some(BTreeSet::new());
+            return;
+        }
+        let start = Instant::now();
+        let fixed_data = self.fixed_data;
+        let row_offset = self.row_offset;
+        let identities = &self.fixed_only_identities;
+        let violations = (0..row_count)
+            .into_par_iter()
+            .filter(|row_index| {
+                let evaluator = ExpressionEvaluator::new(FixedEvaluator::new(
+                    fixed_data,
+                    ((row_index + row_offset) % fixed_data.degree) as usize,
+                ));
+                identities.iter().any(|identity| {
+                    evaluator
+                        .evaluate(identity.expression_for_poly_id())
+                        .map_or(true, |value| value.constant_value() != Some(T::zero()))
+                })
+            })
+            .collect::<BTreeSet<_>>();
+        log::debug!(
+            "Checked {} identities on fixed columns in {:.2?}, they might not hold on {} rows.",
+            self.fixed_only_identities.len(),
+            start.elapsed(),
+            violations.len()
+        );
+        self.fixed_only_violations = Some(violations);
+    }
+
+    /// Returns the identities that only reference fixed columns and have to be processed
+    /// for the given row, i.e. all of them, unless they were checked in advance and hold.
+    fn fixed_only_identities(
+        &self,
+        row_index: DegreeType,
+        with_next_ref: bool,
+    ) -> Vec<&'a Identity<Expression<T>>> {
+        fixed_only_identities_on_row(
+            &self.fixed_only_identities,
+            &self.fixed_only_violations,
+            row_index,
+            with_next_ref,
+        )
+    }
+
+    /// Checks that the identities hold on the computed rows from the last validated row up to
+    /// (excluding) `end`, together with their next row, when unknown values are set to zero.
+    /// This is the check that the rows are "final": as all values of these rows have been
+    /// computed, the rows are independent and checked in parallel. Rows have to be validated
+    /// before they are finalized.
+    /// Fails for the first row on which an identity fails or a machine call was not completed.
+    fn validate_rows(&mut self, end: usize) -> Result<(), WitgenError> {
+        let start = self.validated_until;
+        if end <= start {
+            return Ok(());
+        }
+        log::trace!(
+            "  Checking that the identities hold on rows {} to {} when unknown values are set to 0",
+            start as DegreeType + self.row_offset,
+            end as DegreeType + self.row_offset - 1
+        );
+        let identities_without_next_ref = &self.identities_without_next_ref;
+        let identities_with_next_ref = &self.identities_with_next_ref;
+        let fixed_only_identities = &self.fixed_only_identities;
+        let fixed_only_violations = &self.fixed_only_violations;
+        let incomplete_machine_calls = &self.incomplete_machine_calls;
+        let failures = self.processor.check_rows_assuming_zero(start..end, |row| {
+            let row_index = row as DegreeType;
+            let fixed_only = |with_next_ref| {
+                fixed_only_identities_on_row(
+                    fixed_only_identities,
+                    fixed_only_violations,
+                    row_index,
+                    with_next_ref,
+                )
+            };
+            let machine_calls = incomplete_machine_calls.get(&row_index);
+            identities_without_next_ref
+                .iter()
+                .cloned()
+                .chain(fixed_only(false))
+                .chain(identities_with_next_ref.iter().cloned())
+                .chain(fixed_only(true))
+                .filter(|identity| match identity.kind {
+                    IdentityKind::Plookup | IdentityKind::Permutation => {
+                        machine_calls.is_some_and(|calls| calls.contains(identity))
+                    }
+                    _ => true,
+                })
+                .collect()
+        });
+        self.validated_until = end;
+        self.incomplete_machine_calls = self
+            .incomplete_machine_calls
+            .split_off(&(end as DegreeType));
+        if failures.is_empty() {
+            return Ok(());
+        }
+        if failures.len() > 1 {
+            log::debug!(
+                "Assuming zero for unknown values, identities also fail on {} further rows: {}{}",
+                failures.len() - 1,
+                failures
+                    .iter()
+                    .skip(1)
+                    .take(MAX_REPORTED_FAILING_ROWS)
+                    .map(|(row, _)| (*row as DegreeType + self.row_offset).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if failures.len() - 1 > MAX_REPORTED_FAILING_ROWS {
+                    ", ..."
+                } else {
+                    ""
+                }
+            );
+        }
+        let (row, errors) = failures.into_iter().next().unwrap();
+        Err(self.failure(row as DegreeType, WitgenErrorKind::Underconstrained, errors))
+    }
+
     fn ensure_has_next_row(&mut self, row_index: DegreeType) {
         assert!(self.processor.len() as DegreeType > row_index);
         if row_index == self.processor.len() as DegreeType - 1 {
@@ -207,7 +460,10 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         }
     }
 
-    fn compute_row(&mut self, row_index: DegreeType) -> Constraints<&'a AlgebraicReference, T> {
+    fn compute_row(
+        &mut self,
+        row_index: DegreeType,
+    ) -> Result<Constraints<&'a AlgebraicReference, T>, WitgenError> {
         log::trace!(
             "===== Starting to process row: {}",
             row_index + self.row_offset
@@ -216,43 +472,56 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         log::trace!("  Going over all identities until no more progress is made");
         // First, go over identities that don't reference the next row,
         // Second, propagate values to the next row by going over identities that do reference the next row.
-        let mut identities_without_next_ref =
-            CompletableIdentities::new(self.identities_without_next_ref.iter().cloned());
-        let mut identities_with_next_ref =
-            CompletableIdentities::new(self.identities_with_next_ref.iter().cloned());
-        let outer_assignments = self
-            .loop_until_no_progress(row_index, &mut identities_without_next_ref)
-            .and_then(|outer_assignments| {
-                Ok(outer_assignments
-                    .into_iter()
-                    .chain(self.loop_until_no_progress(row_index, &mut identities_with_next_ref)?)
-                    .collect::<Vec<_>>())
-            })
-            .map_err(|e| self.report_failure_and_panic_unsatisfiable(row_index, e))
-            .unwrap();
+        let mut identities_without_next_ref = CompletableIdentities::new(
+            self.identities_without_next_ref
+                .iter()
+                .cloned()
+                .chain(self.fixed_only_identities(row_index, false)),
+        );
+        let mut identities_with_next_ref = CompletableIdentities::new(
+            self.identities_with_next_ref
+                .iter()
+                .cloned()
+                .chain(self.fixed_only_identities(row_index, true)),
+        );
+        let mut outer_assignments = vec![];
+        loop {
+            outer_assignments.extend(
+                self.loop_until_no_progress(row_index, &mut identities_without_next_ref)
+                    .and_then(|outer_assignments| {
+                        Ok(outer_assignments.into_iter().chain(
+                            self.loop_until_no_progress(row_index, &mut identities_with_next_ref)?,
+                        ))
+                    })
+                    .map_err(|e| self.failure(row_index, WitgenErrorKind::Unsatisfiable, e))?,
+            );
+            // Hints are only used if the identities and queries cannot make progress,
+            // the identities are processed again to check the hinted values.
+            let hint_progress = self
+                .processor
+                .process_hints(row_index as usize)
+                .map_err(|e| self.failure(row_index, WitgenErrorKind::Unsatisfiable, vec![e]))?;
+            if !hint_progress {
+                break;
+            }
+        }
 
-        // Check that the computed row is "final" by asserting that all unknown values can
-        // be set to 0.
-        // This check is only done for the primary machine, as secondary machines might simply
+        // Whether the row is "final", i.e. the identities hold if all unknown values are
+        // set to 0, is checked later by validate_rows(), but machine calls cannot be checked
+        // there, so the incomplete ones are recorded.
+        // This is only done for the primary machine, as secondary machines might simply
         // not have all the inputs yet and therefore be underconstrained.
         if !self.processor.has_outer_query() {
-            log::trace!(
-                "  Checking that remaining identities hold when unknown values are set to 0"
-            );
-            self.process_identities(
-                row_index,
-                &mut identities_without_next_ref,
-                UnknownStrategy::Zero,
-            )
-            .and_then(|_| {
-                self.process_identities(
-                    row_index,
-                    &mut identities_with_next_ref,
-                    UnknownStrategy::Zero,
-                )
-            })
-            .map_err(|e| self.report_failure_and_panic_underconstrained(row_index, e))
-            .unwrap();
+            let incomplete_machine_calls = identities_without_next_ref
+                .incomplete_machine_calls()
+                .chain(identities_with_next_ref.incomplete_machine_calls())
+                .collect::<Vec<_>>();
+            if incomplete_machine_calls.is_empty() {
+                self.incomplete_machine_calls.remove(&row_index);
+            } else {
+                self.incomplete_machine_calls
+                    .insert(row_index, incomplete_machine_calls);
+            }
         }
 
         log::trace!(
@@ -267,7 +536,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             )
         );
 
-        outer_assignments
+        Ok(outer_assignments)
     }
 
     /// Loops over all identities and queries, until no further progress is made.
@@ -279,8 +548,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
     ) -> Result<Constraints<&'a AlgebraicReference, T>, Vec<EvalError<T>>> {
         let mut outer_assignments = vec![];
         loop {
-            let mut progress =
-                self.process_identities(row_index, identities, UnknownStrategy::Unknown)?;
+            let mut progress = self.process_identities(row_index, identities)?;
             let row_index = row_index as usize;
             if let Some(true) = self.processor.latch_value(row_index) {
                 let (outer_query_progress, new_outer_assignments) = self
@@ -307,7 +575,6 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
     /// Loops over all identities once and updates the current row and next row.
     /// Arguments:
     /// * `identities`: Identities to process. Completed identities are removed from the list.
-    /// * `unknown_strategy`: How to process unknown variables. Either use zero or keep it symbolic.
     /// Returns:
     /// * `Ok(true)`: If progress was made.
     /// * `Ok(false)`: If no progress was made.
@@ -316,7 +583,6 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         &mut self,
         row_index: DegreeType,
         identities: &mut CompletableIdentities<'a, T>,
-        unknown_strategy: UnknownStrategy,
     ) -> Result<bool, Vec<EvalError<T>>> {
         let mut progress = false;
         let mut errors = vec![];
@@ -326,21 +592,11 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                 continue;
             }
 
-            let is_machine_call = matches!(
-                identity.kind,
-                IdentityKind::Plookup | IdentityKind::Permutation
+            let result = self.processor.process_identity(
+                row_index as usize,
+                identity,
+                UnknownStrategy::Unknown,
             );
-            if is_machine_call && unknown_strategy == UnknownStrategy::Zero {
-                // The fact that we got to the point where we assume 0 for unknown cells, but this identity
-                // is still not complete, means that either the inputs or the machine is under-constrained.
-                errors.push(format!("{identity}:\n{}",
-                    indent("This machine call could not be completed. Either some inputs are missing or the machine is under-constrained.", "    ")).into());
-                continue;
-            }
-
-            let result =
-                self.processor
-                    .process_identity(row_index as usize, identity, unknown_strategy);
 
             match result {
                 Ok(res) => {
@@ -360,94 +616,155 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         }
     }
 
-    fn report_failure_and_panic_unsatisfiable(
+    /// Collects the information about a row that could not be computed.
+    /// Unknown values are only included in the rendered rows if the row is underconstrained.
+    fn failure(
         &self,
         row_index: DegreeType,
+        kind: WitgenErrorKind,
         failures: Vec<EvalError<T>>,
-    ) -> ! {
-        log::error!(
-            "\nError: Row {} failed. Set RUST_LOG=debug for more information.\n",
-            row_index + self.row_offset
-        );
+    ) -> WitgenError {
+        let include_unknown = kind == WitgenErrorKind::Underconstrained;
+        let row = row_index + self.row_offset;
         let row_index = row_index as usize;
-        log::debug!("Some identities where not satisfiable after the following values were uniquely determined (known nonzero first, then zero, unknown omitted):");
-        log::debug!(
-            "{}",
+        // If seeded values are involved, they are the likely culprit, so only show them.
+        let seeded_columns = self
+            .witnesses
+            .iter()
+            .filter(|poly_id| {
+                self.fixed_data.seeded_witness(row, poly_id).is_some()
+                    || self.fixed_data.seeded_witness(row + 1, poly_id).is_some()
+            })
+            .cloned()
+            .collect::<HashSet<_>>();
+        // A failed query is reported on its own, the identities fail because of it.
+        let failed_queries = failures
+            .iter()
+            .filter(|e| matches!(e, EvalError::ProverQueryError(_)))
+            .collect::<Vec<_>>();
+        let (kind, failures) = if failed_queries.is_empty() {
+            (kind, failures.iter().collect())
+        } else {
+            (WitgenErrorKind::QueryFailed, failed_queries)
+        };
+        let (kind, columns) =
+            if kind == WitgenErrorKind::Unsatisfiable && !seeded_columns.is_empty() {
+                (WitgenErrorKind::SeedDivergence, &seeded_columns)
+            } else {
+                (kind, &self.witnesses)
+            };
+        let row_dump = format!(
+            "{}\n{}",
             self.processor.row(row_index).render(
-                &format!("Current row ({row_index})"),
-                false,
-                &self.witnesses
-            )
-        );
-        log::debug!(
-            "{}",
+                &format!("Current row ({row})"),
+                include_unknown,
+                columns
+            ),
             self.processor.row(row_index + 1).render(
-                &format!("Next row ({})", row_index + 1),
-                false,
-                &self.witnesses
+                &format!("Next row ({})", row + 1),
+                include_unknown,
+                columns
             )
         );
-        log::debug!("Set RUST_LOG=trace to understand why these values were chosen.");
-        log::debug!(
-            "Assuming these values are correct, the following identities fail:\n{}\n",
-            failures
-                .iter()
-                .map(|r| indent(&r.to_string(), "    "))
-                .join("\n")
-        );
-        panic!("Witness generation failed.");
+        let failing_identities = failures.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        let context = FailureContext {
+            rows: (row_index.saturating_sub(2)..=row_index + 1)
+                .filter_map(|i| {
+                    let values = self
+                        .processor
+                        .row_in_progress(i)?
+                        .column_values(&self.witnesses);
+                    Some(RowValues {
+                        row: i as DegreeType + self.row_offset,
+                        values: values
+                            .into_iter()
+                            .map(|(name, value)| (name.to_string(), value.map(|v| v.to_string())))
+                            .collect(),
+                    })
+                })
+                .collect(),
+            identities: self.failing_identities(&failing_identities),
+        };
+        WitgenError {
+            row,
+            kind,
+            failing_identities,
+            row_dump,
+            context,
+        }
     }
 
-    fn report_failure_and_panic_underconstrained(
-        &self,
-        row_index: DegreeType,
-        failures: Vec<EvalError<T>>,
-    ) -> ! {
-        log::error!(
-            "\nError: Row {} failed. Set RUST_LOG=debug for more information.\n",
-            row_index + self.row_offset
-        );
-        let row_index = row_index as usize;
+    fn memory_budget_exceeded(&self, row_index: DegreeType) -> WitgenError {
+        let budget = self.fixed_data.memory_budget.as_ref().unwrap();
+        WitgenError {
+            row: row_index + self.row_offset,
+            kind: WitgenErrorKind::MemoryBudgetExceeded,
+            failing_identities: vec![format!(
+                "Approximately {} of {} bytes are used.",
+                budget.total_usage(),
+                budget.limit()
+            )],
+            row_dump: String::new(),
+            context: Default::default(),
+        }
+    }
 
-        log::debug!("Some columns could not be determined, but setting them to zero does not satisfy the constraints. This typically means that the system is underconstrained!");
-        log::debug!(
-            "{}",
-            self.processor.row(row_index).render(
-                &format!("Current row ({row_index})"),
-                true,
-                &self.witnesses
-            )
-        );
-        log::debug!(
-            "{}",
-            self.processor.row(row_index + 1).render(
-                &format!("Next row ({})", row_index + 1),
-                true,
-                &self.witnesses
-            )
-        );
-        log::debug!("\nSet RUST_LOG=trace to understand why these values were (not) chosen.");
-        log::debug!(
-            "Assuming zero for unknown values, the following identities fail:\n{}\n",
-            failures
-                .iter()
-                .map(|r| indent(&r.to_string(), "    "))
-                .join("\n")
-        );
-        panic!("Witness generation failed.");
+    /// Finds the identities the (rendered) errors are about. Errors about an identity
+    /// start with the identity, see [Processor::process_identity].
+    fn failing_identities(&self, errors: &[String]) -> Vec<FailingIdentity> {
+        self.identities_with_next_ref
+            .iter()
+            .chain(&self.identities_without_next_ref)
+            .chain(&self.fixed_only_identities)
+            .filter(|identity| {
+                let prefix = format!("{identity}:");
+                errors.iter().any(|e| e.starts_with(&prefix))
+            })
+            .map(|identity| {
+                let mut columns = refs_in_identity(identity)
+                    .into_iter()
+                    .filter(|poly_id| self.witnesses.contains(poly_id))
+                    .collect::<Vec<_>>();
+                columns.sort();
+                FailingIdentity {
+                    identity: identity.to_string(),
+                    source: format!("{}:{}", identity.source.file, identity.source.line),
+                    columns: columns
+                        .iter()
+                        .map(|poly_id| self.fixed_data.column_name(poly_id).to_string())
+                        .collect(),
+                }
+            })
+            .collect()
     }
 
     /// Verifies the proposed values for the next row.
     /// TODO this is bad for machines because we might introduce rows in the machine that are then
     /// not used.
-    fn try_proposed_row(&mut self, row_index: DegreeType, proposed_row: Row<'a, T>) -> bool {
-        let constraints_valid = self.identities_with_next_ref.iter().all(|i| {
-            self.processor
-                .check_row_pair(row_index as usize, &proposed_row, i, true)
-        }) && self.identities_without_next_ref.iter().all(|i| {
-            self.processor
-                .check_row_pair(row_index as usize, &proposed_row, i, false)
-        });
+    fn try_proposed_row(
+        &mut self,
+        row_index: DegreeType,
+        proposed_row: Row<'a, T>,
+    ) -> Result<bool, WitgenError> {
+        // Identities with next references are evaluated on the previous row.
+        let constraints_valid = self
+            .identities_with_next_ref
+            .iter()
+            .cloned()
+            .chain(self.fixed_only_identities(row_index - 1, true))
+            .all(|i| {
+                self.processor
+                    .check_row_pair(row_index as usize, &proposed_row, i, true)
+            })
+            && self
+                .identities_without_next_ref
+                .iter()
+                .cloned()
+                .chain(self.fixed_only_identities(row_index, false))
+                .all(|i| {
+                    self.processor
+                        .check_row_pair(row_index as usize, &proposed_row, i, false)
+                });
 
         if constraints_valid {
             self.processor.set_row(row_index as usize, proposed_row);
@@ -456,9 +773,9 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             // If it doesn't, we re-run compute_next_row on the previous row in order to
             // correctly forward-propagate values via next references.
             self.ensure_has_next_row(row_index - 1);
-            self.compute_row(row_index - 1);
+            self.compute_row(row_index - 1)?;
         }
-        constraints_valid
+        Ok(constraints_valid)
     }
 
     fn maybe_log_performance(&mut self, row_index: DegreeType) {
@@ -478,13 +795,44 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                 / identities_count;
 
             let row = row_index + self.row_offset;
+            let rows_per_second = (1_000_000_000 / duration.as_micros()) as u64;
+            let seeded = if row <= self.fixed_data.seeded_rows {
+                " (seeded)"
+            } else {
+                ""
+            };
             log::info!(
-                "{row} of {} rows ({}%) - {} rows/s, {identities_per_sec}k identities/s, {progress_percentage}% progress",
+                "{row} of {} rows ({}%) - {rows_per_second} rows/s{seeded}, {identities_per_sec}k identities/s, {progress_percentage}% progress",
                 self.fixed_data.degree,
                 row * 100 / self.fixed_data.degree,
-                1_000_000_000 / duration.as_micros()
             );
+            if let Some(callback) = &self.fixed_data.progress_callback {
+                callback(WitgenProgress {
+                    row,
+                    degree: self.fixed_data.degree,
+                    rows_per_second,
+                    identity_success_percentage: progress_percentage,
+                });
+            }
             self.last_report = row_index;
         }
     }
 }
+
+/// The identities that only reference fixed columns and have to be processed for the
+/// given row, see [VmProcessor::fixed_only_identities].
+fn fixed_only_identities_on_row<'a, T: FieldElement>(
+    identities: &[&'a Identity<Expression<T>>],
+    violations: &Option<BTreeSet<DegreeType>>,
+    row_index: DegreeType,
+    with_next_ref: bool,
+) -> Vec<&'a Identity<Expression<T>>> {
+    match violations {
+        Some(violations) if !violations.contains(&row_index) => vec![],
+        _ => identities
+            .iter()
+            .filter(|identity| identity.contains_next_ref() == with_next_ref)
+            .cloned()
+            .collect(),
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt;
+
+use itertools::Itertools;
+use number::DegreeType;
+use parser_util::lines::indent;
+
+use crate::cancellation::Cancelled;
+
+/// The reason why witness generation failed on a row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WitgenErrorKind {
+    /// Some identities are not satisfiable with the values determined so far.
+    Unsatisfiable,
+    /// Some columns could not be determined, and setting them to zero does not
+    /// satisfy the identities.
+    Underconstrained,
+    /// The values computed after the last row do not match the first row.
+    OutOfRows,
+    /// Some identities are not satisfiable with the seeded values, see
+    /// [super::WitnessGenerator::with_seeded_witness_values].
+    SeedDivergence,
+    /// The query callback returned an error for a prover query.
+    QueryFailed,
+    /// The memory budget was exceeded, see [super::WitnessGenerator::with_memory_budget].
+    MemoryBudgetExceeded,
+}
+
+impl WitgenErrorKind {
+    fn explanation(&self) -> &'static str {
+        match self {
+            WitgenErrorKind::Unsatisfiable => "Some identities where not satisfiable after the following values were uniquely determined (known nonzero first, then zero, unknown omitted):",
+            WitgenErrorKind::Underconstrained => "Some columns could not be determined, but setting them to zero does not satisfy the constraints. This typically means that the system is underconstrained!",
+            WitgenErrorKind::OutOfRows => "The values after the last row do not match the values in the first row. This typically means that the machine needs more rows than the degree allows: increase it, e.g. with the `--degree` flag.",
+            WitgenErrorKind::SeedDivergence => "The seeded values are not consistent with the constraints: the trace they were taken from diverges from the witness in this row. The seeded values are (known nonzero first, then zero, unknown omitted):",
+            WitgenErrorKind::QueryFailed => "A prover query could not be answered. The values determined so far are (known nonzero first, then zero, unknown omitted):",
+            WitgenErrorKind::MemoryBudgetExceeded => "Witness generation needs more memory than its budget allows, even after degrading. Increase the budget, e.g. with the `--witgen-memory-budget` flag.",
+        }
+    }
+
+    fn failing_identities_header(&self) -> &'static str {
+        match self {
+            WitgenErrorKind::Unsatisfiable => {
+                "Assuming these values are correct, the following identities fail:"
+            }
+            WitgenErrorKind::Underconstrained => {
+                "Assuming zero for unknown values, the following identities fail:"
+            }
+            WitgenErrorKind::OutOfRows => "The following columns do not match:",
+            WitgenErrorKind::SeedDivergence => {
+                "With the seeded values, the following identities fail:"
+            }
+            WitgenErrorKind::QueryFailed => "The following queries failed:",
+            WitgenErrorKind::MemoryBudgetExceeded => "Memory usage:",
+        }
+    }
+}
+
+impl fmt::Display for WitgenErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitgenErrorKind::Unsatisfiable => write!(f, "unsatisfiable"),
+            WitgenErrorKind::Underconstrained => write!(f, "underconstrained"),
+            WitgenErrorKind::OutOfRows => write!(f, "out of rows"),
+            WitgenErrorKind::SeedDivergence => write!(f, "seed divergence"),
+            WitgenErrorKind::QueryFailed => write!(f, "query failed"),
+            WitgenErrorKind::MemoryBudgetExceeded => write!(f, "memory budget exceeded"),
+        }
+    }
+}
+
+/// The values of witness columns on a row, see [FailureContext].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowValues {
+    /// The (global) row.
+    pub row: DegreeType,
+    /// The columns in source order with their values, `None` if the value is unknown.
+    pub values: Vec<(String, Option<String>)>,
+}
+
+/// An identity that fails, see [FailureContext].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailingIdentity {
+    pub identity: String,
+    /// The source location of the identity, as `file:line`.
+    pub source: String,
+    /// The witness columns referenced by the identity, in source order.
+    pub columns: Vec<String>,
+}
+
+/// The structured context of a failure, to inspect the rows around it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FailureContext {
+    /// The witness columns of the failing machine on up to two rows before the
+    /// failing row, the failing row itself and the next row. Rows that have already
+    /// been finalized are omitted.
+    pub rows: Vec<RowValues>,
+    /// The failing identities, empty if the failure is not caused by identities.
+    pub identities: Vec<FailingIdentity>,
+}
+
+/// A failure of witness generation on a specific row of a machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WitgenError {
+    /// The (global) row on which witness generation failed.
+    pub row: DegreeType,
+    pub kind: WitgenErrorKind,
+    /// The failing identities, each together with the reason why it fails.
+    /// For [WitgenErrorKind::OutOfRows], the columns that do not match.
+    pub failing_identities: Vec<String>,
+    /// The rendered current and next row.
+    pub row_dump: String,
+    pub context: FailureContext,
+}
+
+impl WitgenError {
+    /// Logs the error: a short message on the error level and the details on the debug level.
+    pub fn log(&self) {
+        log::error!(
+            "\nError: Row {} failed. Set RUST_LOG=debug for more information.\n",
+            self.row
+        );
+        if matches!(
+            self.kind,
+            WitgenErrorKind::OutOfRows | WitgenErrorKind::MemoryBudgetExceeded
+        ) {
+            // These have an easy fix, so do not hide the hint.
+            log::error!("{}", self.kind.explanation());
+        } else {
+            log::debug!("{}", self.kind.explanation());
+        }
+        log::debug!("{}", self.row_dump);
+        log::debug!("Set RUST_LOG=trace to understand why these values were (not) chosen.");
+        log::debug!(
+            "{}\n{}\n",
+            self.kind.failing_identities_header(),
+            self.rendered_failing_identities()
+        );
+    }
+
+    /// The failing identities with the reasons and, if known, their source locations.
+    fn rendered_failing_identities(&self) -> String {
+        self.failing_identities
+            .iter()
+            .map(|i| {
+                let source = self
+                    .context
+                    .identities
+                    .iter()
+                    .find(|identity| i.starts_with(&format!("{}:", identity.identity)));
+                match source {
+                    Some(identity) => indent(&format!("{i}\nat {}", identity.source), "    "),
+                    None => indent(i, "    "),
+                }
+            })
+            .join("\n")
+    }
+}
+
+impl fmt::Display for WitgenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Witness generation failed at row {} ({}).",
+            self.row, self.kind
+        )?;
+        writeln!(f, "{}", self.kind.explanation())?;
+        if !self.row_dump.is_empty() {
+            writeln!(f, "{}", self.row_dump)?;
+        }
+        write!(
+            f,
+            "{}\n{}",
+            self.kind.failing_identities_header(),
+            self.rendered_failing_identities()
+        )
+    }
+}
+
+impl std::error::Error for WitgenError {}
+
+/// The error returned by [super::WitnessGenerator::try_generate].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GenerationError {
+    Cancelled(Cancelled),
+    Failed(WitgenError),
+    /// The generated witness does not satisfy some permutations, e.g. because a machine
+    /// was called more often than it provides rows for. Contains the unmatched rows.
+    PermutationMismatch(Vec<String>),
+    /// The permutations of the generated witness could not be checked, e.g. because
+    /// a public refers to a row that does not exist.
+    PermutationCheckFailed(String),
+    /// The generated witness does not satisfy some connect identities, which are not
+    /// used to solve for values, only checked. Contains the rows where they do not hold.
+    ConnectionMismatch(Vec<String>),
+    /// The connect identities of the generated witness could not be checked, e.g.
+    /// because they are only supported over the Goldilocks field.
+    ConnectionCheckFailed(String),
+    /// The generated witness has values outside of the ranges the columns are
+    /// annotated with, `in [min, max]`. Contains the columns, rows and values.
+    RangeViolation(Vec<String>),
+}
+
+impl From<Cancelled> for GenerationError {
+    fn from(value: Cancelled) -> Self {
+        GenerationError::Cancelled(value)
+    }
+}
+
+impl From<WitgenError> for GenerationError {
+    fn from(value: WitgenError) -> Self {
+        GenerationError::Failed(value)
+    }
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationError::Cancelled(e) => write!(f, "{e}"),
+            GenerationError::Failed(e) => write!(f, "{e}"),
+            GenerationError::PermutationCheckFailed(e) => {
+                write!(f, "Could not check the permutations: {e}")
+            }
+            GenerationError::ConnectionCheckFailed(e) => {
+                write!(f, "Could not check the connect identities: {e}")
+            }
+            GenerationError::PermutationMismatch(violations)
+            | GenerationError::ConnectionMismatch(violations)
+            | GenerationError::RangeViolation(violations) => {
+                const MAX_SHOWN: usize = 10;
+                let kind = match self {
+                    GenerationError::PermutationMismatch(_) => "permutations",
+                    GenerationError::ConnectionMismatch(_) => "connect identities",
+                    _ => "column ranges",
+                };
+                writeln!(
+                    f,
+                    "The generated witness does not satisfy the following {kind}:"
+                )?;
+                for violation in violations.iter().take(MAX_SHOWN) {
+                    writeln!(f, "{}", indent(violation, "    "))?;
+                }
+                if violations.len() > MAX_SHOWN {
+                    writeln!(f, "    ... and {} more", violations.len() - MAX_SHOWN)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
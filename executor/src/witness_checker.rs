@@ -0,0 +1,540 @@
+//! Checks a complete, externally provided witness against all identities of a PIL file
+//! without running witness generation.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+
+use ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicUnaryOperator, Analyzed,
+    Identity, IdentityKind, PolyID, PolynomialType,
+};
+use ast::parsed::SelectedExpressions;
+use number::{DegreeType, FieldElement, KnownField};
+
+use crate::parallel::prelude::*;
+
+/// An identity that does not hold on a row of the witness.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation<T> {
+    /// The source location of the identity, `file:line`.
+    pub source: String,
+    pub identity: String,
+    pub kind: IdentityKind,
+    pub row: DegreeType,
+    /// The values of the left side. For polynomial identities, this is the left
+    /// side of `left = right`, for lookups and permutations the left tuple.
+    /// Empty for rows of the right side of a permutation that are not matched.
+    pub left: Vec<T>,
+    /// The values of the right side. Empty for lookups, whose left tuple has
+    /// no match on any row. For connect identities, the values of the cells the
+    /// left tuple is connected to.
+    pub right: Vec<T>,
+}
+
+impl<T: Display> Display for Violation<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let tuple = |values: &[T]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        write!(
+            f,
+            "{}: identity {} does not hold in row {}",
+            self.source, self.identity, self.row
+        )?;
+        match self.kind {
+            IdentityKind::Polynomial => write!(f, ": {} != {}", self.left[0], self.right[0]),
+            IdentityKind::Plookup => write!(
+                f,
+                ": ({}) is not in the right side",
+                tuple(&self.left).join(", ")
+            ),
+            IdentityKind::Connect => write!(
+                f,
+                ": ({}) is connected to ({})",
+                tuple(&self.left).join(", "),
+                tuple(&self.right).join(", ")
+            ),
+            _ if self.left.is_empty() => write!(
+                f,
+                ": ({}) of the right side has no match on the left side",
+                tuple(&self.right).join(", ")
+            ),
+            _ => write!(
+                f,
+                ": ({}) has no match on the right side",
+                tuple(&self.left).join(", ")
+            ),
+        }
+    }
+}
+
+/// Evaluates all identities of `analyzed` on every row of the given fixed and witness
+/// column values and returns all violations, ordered by identity and row.
+/// Next references of the last row wrap around to the first row.
+/// Fails if a column is missing or does not have exactly `degree` values.
+pub fn check_witness<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed: &[(impl AsRef<str>, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+) -> Result<Vec<Violation<T>>, String> {
+    check_identities(analyzed, fixed, witness, |_| true)
+}
+
+/// Like [check_witness], but only checks the permutation identities.
+pub fn check_permutations<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed: &[(impl AsRef<str>, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+) -> Result<Vec<Violation<T>>, String> {
+    check_identities(analyzed, fixed, witness, |kind| {
+        kind == IdentityKind::Permutation
+    })
+}
+
+/// Like [check_witness], but only checks the connect identities, see
+/// [GOLDILOCKS_CONNECTION_SHIFT] for how they refer to cells.
+pub fn check_connections<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed: &[(impl AsRef<str>, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+) -> Result<Vec<Violation<T>>, String> {
+    check_identities(analyzed, fixed, witness, |kind| {
+        kind == IdentityKind::Connect
+    })
+}
+
+/// A connect identity `{ a_0, ..., a_n } connect { S_0, ..., S_n }` requires the cell
+/// of `a_i` in row `j` to have the same value as the cell `S_i` refers to in row `j`.
+/// As in pil-stark, the cell of `a_i` in row `r` is referred to by `k^i * w^r`, where
+/// `k` is this constant and `w` is [GOLDILOCKS_ROOT_OF_UNITY] raised to the power
+/// `2^32 / degree`.
+pub const GOLDILOCKS_CONNECTION_SHIFT: u64 = 12275445934081160404;
+
+/// A primitive root of unity of order `2^32` in the Goldilocks field, the one
+/// pil-stark uses.
+pub const GOLDILOCKS_ROOT_OF_UNITY: u64 = 7277203076849721926;
+
+fn check_identities<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed: &[(impl AsRef<str>, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+    filter: impl Fn(IdentityKind) -> bool,
+) -> Result<Vec<Violation<T>>, String> {
+    let identities = analyzed.identities_with_inlined_intermediate_polynomials();
+    if !identities.iter().any(|identity| filter(identity.kind)) {
+        return Ok(vec![]);
+    }
+    let columns = Columns::new(analyzed, fixed, witness)?;
+    let mut violations = vec![];
+    for identity in identities.iter().filter(|identity| filter(identity.kind)) {
+        let violation = |row, left, right| Violation {
+            source: format!("{}:{}", identity.source.file, identity.source.line),
+            identity: identity.to_string(),
+            kind: identity.kind,
+            row,
+            left,
+            right,
+        };
+        let rows = match identity.kind {
+            IdentityKind::Polynomial => columns.check_polynomial(identity)?,
+            IdentityKind::Plookup => columns.check_lookup(identity)?,
+            IdentityKind::Permutation => columns.check_permutation(identity)?,
+            IdentityKind::Connect => columns.check_connection(identity)?,
+        };
+        violations.extend(
+            rows.into_iter()
+                .map(|(row, left, right)| violation(row, left, right)),
+        );
+    }
+    Ok(violations)
+}
+
+/// A row and the values of the left and right side of an identity on it.
+type RowValues<T> = (DegreeType, Vec<T>, Vec<T>);
+
+/// The values of all columns and publics, indexed by their ID.
+struct Columns<'a, T> {
+    degree: DegreeType,
+    values: HashMap<PolyID, &'a [T]>,
+    publics: HashMap<&'a str, T>,
+}
+
+impl<'a, T: FieldElement> Columns<'a, T> {
+    fn new(
+        analyzed: &'a Analyzed<T>,
+        fixed: &'a [(impl AsRef<str>, Vec<T>)],
+        witness: &'a [(String, Vec<T>)],
+    ) -> Result<Self, String> {
+        let degree = analyzed.degree();
+        let mut values = HashMap::new();
+        let fixed = fixed
+            .iter()
+            .map(|(n, v)| (n.as_ref(), v))
+            .collect::<Vec<_>>();
+        let witness = witness
+            .iter()
+            .map(|(n, v)| (n.as_str(), v))
+            .collect::<Vec<_>>();
+        for (polys, given) in [
+            (analyzed.constant_polys_in_source_order(), &fixed),
+            (analyzed.committed_polys_in_source_order(), &witness),
+        ] {
+            for (name, poly_id) in polys.iter().flat_map(|(poly, _)| poly.array_elements()) {
+                let (_, column) = given
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .ok_or_else(|| format!("No values given for column {name}."))?;
+                if column.len() as DegreeType != degree {
+                    return Err(format!(
+                        "Column {name} has {} values, but the degree is {degree}.",
+                        column.len()
+                    ));
+                }
+                values.insert(poly_id, column.as_slice());
+            }
+        }
+        let publics = analyzed
+            .public_declarations
+            .iter()
+            .map(|(name, decl)| {
+                let column = match decl.array_index {
+                    Some(i) => format!("{}[{i}]", decl.polynomial.name),
+                    None => decl.polynomial.name.clone(),
+                };
+                let value = witness
+                    .iter()
+                    .find(|(n, _)| *n == column)
+                    .and_then(|(_, values)| values.get(decl.index as usize))
+                    .ok_or_else(|| format!("No value for public {name} in column {column}."))?;
+                Ok((name.as_str(), *value))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Columns {
+            degree,
+            values,
+            publics,
+        })
+    }
+
+    /// Returns the row, the left and the right side of all rows the polynomial
+    /// identity does not hold on. Identities that are not of the form `left = right`
+    /// have zero on the right.
+    fn check_polynomial(
+        &self,
+        identity: &Identity<Expression<T>>,
+    ) -> Result<Vec<RowValues<T>>, String> {
+        let (left, right) = match identity.expression_for_poly_id() {
+            Expression::BinaryOperation(left, AlgebraicBinaryOperator::Sub, right) => {
+                (left.as_ref(), Some(right.as_ref()))
+            }
+            expression => (expression, None),
+        };
+        let rows = (0..self.degree)
+            .into_par_iter()
+            .map(|row| {
+                let l = self.evaluate(left, row)?;
+                let r = right.map_or(Ok(0.into()), |right| self.evaluate(right, row))?;
+                Ok((l != r).then_some((row, vec![l], vec![r])))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// Returns the row and the left tuple of all rows whose tuple is selected
+    /// but not contained in the selected tuples of the right side.
+    fn check_lookup(
+        &self,
+        identity: &Identity<Expression<T>>,
+    ) -> Result<Vec<RowValues<T>>, String> {
+        let right = self
+            .selected_tuples(&identity.right)?
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<_>>();
+        Ok(self
+            .selected_tuples(&identity.left)?
+            .into_iter()
+            .zip(0..)
+            .filter_map(|(tuple, row)| {
+                tuple
+                    .filter(|t| !right.contains(t))
+                    .map(|t| (row, t, vec![]))
+            })
+            .collect())
+    }
+
+    /// Matches the selected tuples of both sides row by row and returns the
+    /// unmatched ones, as `(row, left tuple, [])` for the left side and as
+    /// `(row, [], right tuple)` for the right side.
+    fn check_permutation(
+        &self,
+        identity: &Identity<Expression<T>>,
+    ) -> Result<Vec<RowValues<T>>, String> {
+        let mut unmatched_right: HashMap<Vec<T>, VecDeque<DegreeType>> = HashMap::new();
+        for (tuple, row) in self.selected_tuples(&identity.right)?.into_iter().zip(0..) {
+            if let Some(tuple) = tuple {
+                unmatched_right.entry(tuple).or_default().push_back(row);
+            }
+        }
+        let mut violations = vec![];
+        for (tuple, row) in self.selected_tuples(&identity.left)?.into_iter().zip(0..) {
+            let Some(tuple) = tuple else { continue };
+            let matched = unmatched_right
+                .get_mut(&tuple)
+                .and_then(|rows| rows.pop_front());
+            if matched.is_none() {
+                violations.push((row, tuple, vec![]));
+            }
+        }
+        violations.extend(
+            unmatched_right
+                .into_iter()
+                .flat_map(|(tuple, rows)| rows.into_iter().map(move |row| (row, tuple.clone())))
+                .map(|(row, tuple)| (row, vec![], tuple)),
+        );
+        violations.sort_by_key(|(row, left, _)| (left.is_empty(), *row));
+        Ok(violations)
+    }
+
+    /// Returns the row, the left tuple and the values of the cells it is connected to
+    /// of all rows where they differ, see [GOLDILOCKS_CONNECTION_SHIFT].
+    fn check_connection(
+        &self,
+        identity: &Identity<Expression<T>>,
+    ) -> Result<Vec<RowValues<T>>, String> {
+        if !matches!(T::known_field(), Some(KnownField::GoldilocksField)) {
+            return Err(format!(
+                "Connect identities can only be checked over the Goldilocks field: {identity}"
+            ));
+        }
+        if !self.degree.is_power_of_two() || self.degree > 1 << 32 {
+            return Err(format!(
+                "Connect identities need a power of two degree of at most 2^32, not {}.",
+                self.degree
+            ));
+        }
+        let column_count = identity.left.expressions.len();
+        if identity.right.expressions.len() != column_count {
+            return Err(format!(
+                "Both sides of a connect identity need the same number of columns: {identity}"
+            ));
+        }
+
+        // The root of unity of order `degree`.
+        let mut w = T::from(GOLDILOCKS_ROOT_OF_UNITY);
+        for _ in self.degree.trailing_zeros()..32 {
+            w = w * w;
+        }
+        let shifts = std::iter::successors(Some(T::one()), |k| {
+            Some(*k * T::from(GOLDILOCKS_CONNECTION_SHIFT))
+        })
+        .take(column_count)
+        .collect::<Vec<_>>();
+        let mut cells = HashMap::new();
+        let mut w_row = T::one();
+        for row in 0..self.degree {
+            for (column, k) in shifts.iter().enumerate() {
+                cells.insert(*k * w_row, (column, row as usize));
+            }
+            w_row = w_row * w;
+        }
+
+        let values = self
+            .selected_tuples(&identity.left)?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let connections = self.selected_tuples(&identity.right)?.into_iter().flatten();
+        let mut violations = vec![];
+        for ((left, connection), row) in values.iter().zip(connections).zip(0..) {
+            let connected = connection
+                .iter()
+                .map(|cell| {
+                    let (column, row) = cells.get(cell).ok_or_else(|| {
+                        format!("{cell} in row {row} does not refer to a cell: {identity}")
+                    })?;
+                    Ok(values[*row][*column])
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if *left != connected {
+                violations.push((row, left.clone(), connected));
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Evaluates the tuple on every row, `None` if the row is not selected.
+    fn selected_tuples(
+        &self,
+        selected: &SelectedExpressions<Expression<T>>,
+    ) -> Result<Vec<Option<Vec<T>>>, String> {
+        (0..self.degree)
+            .into_par_iter()
+            .map(|row| {
+                if let Some(selector) = &selected.selector {
+                    if self.evaluate(selector, row)?.is_zero() {
+                        return Ok(None);
+                    }
+                }
+                selected
+                    .expressions
+                    .iter()
+                    .map(|e| self.evaluate(e, row))
+                    .collect::<Result<_, _>>()
+                    .map(Some)
+            })
+            .collect()
+    }
+
+    fn evaluate(&self, expr: &Expression<T>, row: DegreeType) -> Result<T, String> {
+        Ok(match expr {
+            Expression::Reference(reference) => {
+                if reference.poly_id.ptype == PolynomialType::Intermediate {
+                    return Err(format!(
+                        "Intermediate column {} should have been inlined.",
+                        reference.name
+                    ));
+                }
+                let row = if reference.next {
+                    (row + 1) % self.degree
+                } else {
+                    row
+                };
+                self.values[&reference.poly_id][row as usize]
+            }
+            Expression::PublicReference(name) => *self
+                .publics
+                .get(name.as_str())
+                .ok_or_else(|| format!("Unknown public {name}."))?,
+            Expression::Number(n) => *n,
+            Expression::BinaryOperation(left, op, right) => {
+                let left = self.evaluate(left, row)?;
+                let right = self.evaluate(right, row)?;
+                match op {
+                    AlgebraicBinaryOperator::Add => left + right,
+                    AlgebraicBinaryOperator::Sub => left - right,
+                    AlgebraicBinaryOperator::Mul => left * right,
+                    AlgebraicBinaryOperator::Pow => left.pow(right.to_integer()),
+                }
+            }
+            Expression::UnaryOperation(op, inner) => {
+                let inner = self.evaluate(inner, row)?;
+                match op {
+                    AlgebraicUnaryOperator::Plus => inner,
+                    AlgebraicUnaryOperator::Minus => -inner,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const PIL: &str = r#"
+namespace N(4);
+    col fixed BYTE(i) { i };
+    col witness x, y;
+    x' = y;
+    y' = x;
+    { x } in { BYTE };
+    { x } is { y };
+"#;
+
+    fn check(x: [u64; 4], y: [u64; 4]) -> Vec<Violation<GoldilocksField>> {
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(PIL);
+        let column = |values: [u64; 4]| values.into_iter().map(GoldilocksField::from).collect();
+        let fixed = vec![("N.BYTE".to_string(), column([0, 1, 2, 3]))];
+        let witness = vec![
+            ("N.x".to_string(), column(x)),
+            ("N.y".to_string(), column(y)),
+        ];
+        check_witness(&analyzed, &fixed, &witness).unwrap()
+    }
+
+    #[test]
+    fn valid_witness() {
+        assert_eq!(check([1, 2, 1, 2], [2, 1, 2, 1]), vec![]);
+    }
+
+    #[test]
+    fn flipped_cell() {
+        let violations = check([1, 2, 5, 2], [2, 1, 2, 1]);
+        let summary = violations
+            .iter()
+            .map(|v| (v.identity.as_str(), v.row))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("N.x' = N.y;", 1),
+                ("N.y' = N.x;", 2),
+                ("{ N.x } in { N.BYTE };", 2),
+                ("{ N.x } is { N.y };", 2),
+                ("{ N.x } is { N.y };", 3),
+            ]
+        );
+        assert_eq!(violations[0].left, vec![5.into()]);
+        assert_eq!(violations[0].right, vec![1.into()]);
+        assert_eq!(
+            violations[1].to_string(),
+            "input:6: identity N.y' = N.x; does not hold in row 2: 1 != 5"
+        );
+        assert_eq!(
+            violations[2].to_string(),
+            "input:7: identity { N.x } in { N.BYTE }; does not hold in row 2: (5) is not in the right side"
+        );
+        assert_eq!(
+            violations[4].to_string(),
+            "input:8: identity { N.x } is { N.y }; does not hold in row 3: (1) of the right side has no match on the left side"
+        );
+    }
+
+    /// Connects the cells of `a` and `b` in the same row.
+    const CONNECT_PIL: &str = r#"
+namespace N(4);
+    col fixed S_A = [12275445934081160404, 15698977013907152186, 6171298135333423917, 2747767055507432135];
+    col fixed S_B = [1, 281474976710656, 18446744069414584320, 18446462594437873665];
+    col witness a, b;
+    { a, b } connect { S_A, S_B };
+"#;
+
+    fn check_connect(a: [u64; 4], b: [u64; 4]) -> Result<Vec<Violation<GoldilocksField>>, String> {
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(CONNECT_PIL);
+        let fixed = crate::constant_evaluator::generate(&analyzed);
+        let column = |values: [u64; 4]| values.into_iter().map(GoldilocksField::from).collect();
+        let witness = vec![
+            ("N.a".to_string(), column(a)),
+            ("N.b".to_string(), column(b)),
+        ];
+        check_witness(&analyzed, &fixed, &witness)
+    }
+
+    #[test]
+    fn connection() {
+        assert_eq!(check_connect([1, 2, 3, 4], [1, 2, 3, 4]), Ok(vec![]));
+        let violations = check_connect([1, 2, 3, 4], [1, 2, 7, 4]).unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "input:6: identity { N.a, N.b } connect { N.S_A, N.S_B }; does not hold in row 2: (3, 7) is connected to (7, 3)"
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_length() {
+        let analyzed = pil_analyzer::analyze_string::<GoldilocksField>(PIL);
+        let fixed = vec![("N.BYTE".to_string(), vec![0.into(); 4])];
+        let witness = vec![("N.x".to_string(), vec![0.into(); 3])];
+        assert_eq!(
+            check_witness(&analyzed, &fixed, &witness),
+            Err("Column N.x has 3 values, but the degree is 4.".to_string())
+        );
+    }
+}
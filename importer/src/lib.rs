@@ -10,15 +10,27 @@ use ast::parsed::asm::ASMProgram;
 pub use module_loader::load_module_files;
 use number::FieldElement;
 use parser::parse_asm;
+use parser_util::std_library::StdLibrary;
 use path_canonicalizer::canonicalize_paths;
 use powdr_std::add_std;
 
+/// Loads the modules of the program and the standard library and resolves all paths.
+/// The standard library is the one given by [StdLibrary::from_env].
 pub fn resolve<T: FieldElement>(
     path: Option<PathBuf>,
     module: ASMProgram<T>,
+) -> Result<ASMProgram<T>, String> {
+    resolve_with_std(path, module, &StdLibrary::from_env())
+}
+
+/// Like [resolve], but with the given standard library.
+pub fn resolve_with_std<T: FieldElement>(
+    path: Option<PathBuf>,
+    module: ASMProgram<T>,
+    std: &StdLibrary,
 ) -> Result<ASMProgram<T>, String> {
     load_module_files(path, module)
-        .and_then(add_std)
+        .and_then(|program| add_std(program, std))
         .and_then(canonicalize_paths)
 }
 
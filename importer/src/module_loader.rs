@@ -1,10 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use ast::parsed::{
     asm::{ASMProgram, Module},
     folder::Folder,
 };
+use ast::source_map::SourceMap;
 use number::FieldElement;
+use parser_util::std_library::StdLibrary;
 
 static ASM_EXTENSION: &str = "asm";
 static FOLDER_MODULE_NAME: &str = "mod";
@@ -13,11 +15,51 @@ pub fn load_module_files<T: FieldElement>(
     path: Option<PathBuf>,
     program: ASMProgram<T>,
 ) -> Result<ASMProgram<T>, String> {
-    Loader { path }.fold_program(program)
+    Loader {
+        path,
+        std: None,
+        sources: Default::default(),
+    }
+    .fold_program(program)
+}
+
+/// Like [load_module_files], but for a module of the standard library `std`,
+/// with `path` relative to its root directory.
+pub fn load_std_module_files<T: FieldElement>(
+    std: &StdLibrary,
+    path: PathBuf,
+    program: ASMProgram<T>,
+) -> Result<ASMProgram<T>, String> {
+    Loader {
+        path: Some(path),
+        std: Some(std.clone()),
+        sources: Default::default(),
+    }
+    .fold_program(program)
 }
 
 struct Loader {
     path: Option<PathBuf>,
+    /// If set, `path` is relative to the root of this standard library.
+    std: Option<StdLibrary>,
+    /// The source files of the program, which the loaded files are added to.
+    sources: SourceMap,
+}
+
+impl Loader {
+    fn read(&self, path: &Path) -> Result<String, String> {
+        match &self.std {
+            Some(std) => std.read(path),
+            None => std::fs::read_to_string(path).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn display(&self, path: &Path) -> String {
+        match &self.std {
+            Some(std) => std.display_path(path),
+            None => path.display().to_string(),
+        }
+    }
 }
 
 type Error = String;
@@ -25,6 +67,15 @@ type Error = String;
 impl<T: FieldElement> Folder<T> for Loader {
     type Error = Error;
 
+    fn fold_program(&mut self, p: ASMProgram<T>) -> Result<ASMProgram<T>, Self::Error> {
+        self.sources = p.sources;
+        let main = self.fold_module_value(p.main)?;
+        Ok(ASMProgram {
+            main,
+            sources: std::mem::take(&mut self.sources),
+        })
+    }
+
     fn fold_module(&mut self, m: Module<T>) -> Result<Module<T>, Self::Error> {
         match m {
             Module::External(name) => self
@@ -40,37 +91,42 @@ impl<T: FieldElement> Folder<T> for Loader {
                     let file_in_folder_path =
                         path.join(FOLDER_MODULE_NAME).with_extension(ASM_EXTENSION);
 
-                    let file = std::fs::read_to_string(&file_path);
+                    let file = self.read(&file_path);
 
-                    let file_in_folder = std::fs::read_to_string(&file_in_folder_path);
+                    let file_in_folder = self.read(&file_in_folder_path);
 
                     match (file, file_in_folder) {
                         // if we found it here, continue from here
-                        (Ok(file), Err(_)) => Ok((file, Some(path))),
+                        (Ok(file), Err(_)) => Ok((file, file_path, Some(path))),
                         // if we found it in a subdirectory, continue from there
-                        (Err(_), Ok(file)) => Ok((file, Some(path.join(FOLDER_MODULE_NAME)))),
+                        (Err(_), Ok(file)) => Ok((
+                            file,
+                            file_in_folder_path,
+                            Some(path.join(FOLDER_MODULE_NAME)),
+                        )),
                         (Ok(_), Ok(_)) => Err(format!(
                             "Expecting either `{}` or `{}`, found both",
-                            file_path.display(),
-                            file_in_folder_path.display()
+                            self.display(&file_path),
+                            self.display(&file_in_folder_path)
                         )),
                         (Err(_), Err(_)) => Err(format!(
                             "Expecting either `{}` or `{}`, found neither",
-                            file_path.display(),
-                            file_in_folder_path.display()
+                            self.display(&file_path),
+                            self.display(&file_in_folder_path)
                         )),
                     }
-                    .map(|(file, path)| {
-                        parser::parse_module(None, &file)
-                            .map(|res| (res, path))
-                            .unwrap_or_else(|err| {
-                                eprintln!(
-                                    "Error parsing powdr assembly file {}:",
-                                    file_path.display()
-                                );
-                                err.output_to_stderr();
-                                panic!();
-                            })
+                    .map(|(file, file_path, path)| {
+                        let mut module = parser::parse_module(None, &file).unwrap_or_else(|err| {
+                            eprintln!(
+                                "Error parsing powdr assembly file {}:",
+                                self.display(&file_path)
+                            );
+                            err.output_to_stderr();
+                            panic!();
+                        });
+                        self.sources
+                            .add_module(&self.display(&file_path), &file, &mut module);
+                        (module, path)
                     })
                 })
                 .unwrap_or(Err(
@@ -78,7 +134,16 @@ impl<T: FieldElement> Folder<T> for Loader {
                 )),
             Module::Local(m) => Ok((m, self.path.clone())),
         }
-        .and_then(|(m, path)| Loader { path }.fold_module_value(m))
+        .and_then(|(m, path)| {
+            let mut loader = Loader {
+                path,
+                std: self.std.clone(),
+                sources: std::mem::take(&mut self.sources),
+            };
+            let module = loader.fold_module_value(m);
+            self.sources = loader.sources;
+            module
+        })
         .map(Module::Local)
     }
 }
@@ -97,12 +162,15 @@ mod tests {
         let main_path = dir.join("main.asm").to_owned();
         let main_str = std::fs::read_to_string(&main_path).unwrap();
         let main = parse_asm::<Bn254Field>(None, &main_str).unwrap();
-        let main = load_module_files(Some(main_path), main);
+        // The source positions differ, so compare the printed programs.
+        let main = load_module_files(Some(main_path), main).map(|main| main.to_string());
 
         let expected = expected
             .map(|_| {
                 let expected_str = std::fs::read_to_string(dir.join("expected.asm")).unwrap();
-                parse_asm::<Bn254Field>(None, &expected_str).unwrap()
+                parse_asm::<Bn254Field>(None, &expected_str)
+                    .unwrap()
+                    .to_string()
             })
             .map_err(|e| e.to_string());
 
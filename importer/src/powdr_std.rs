@@ -1,4 +1,4 @@
-use std::{env, path::PathBuf};
+use std::path::PathBuf;
 
 use ast::parsed::{
     asm::{
@@ -7,74 +7,79 @@ use ast::parsed::{
     },
     folder::Folder,
 };
+use ast::source_map::SourceMap;
 use number::FieldElement;
-use parser::parse_asm;
+use parser::parse_module;
+use parser_util::std_library::{StdLibrary, POWDR_STD_ENV};
 
-use crate::load_module_files;
+use crate::module_loader::load_std_module_files;
 
-static POWDR_STD_ENV: &str = "POWDR_STD";
 static MOD_FILE: &str = "mod.asm";
 
-/// Loads the standard library module from the location specified in the <POWDR_STD_ENV> environment variable
-/// (or, if unset, <project_root>/std).
+/// Loads the main module of the standard library.
+/// The files of the standard library are added to `sources`.
 ///
 /// # Panics
 /// If there is an error loading the standard library
-fn load_std<T: FieldElement>() -> ASMModule<T> {
-    let default_std_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .parent()
-        .unwrap()
-        .join("std");
-    let std_path = env::var(POWDR_STD_ENV)
-        .map(PathBuf::from)
-        .unwrap_or(default_std_path);
-    let std_path = std_path.join(MOD_FILE);
-
-    match std::fs::read_to_string(&std_path) {
-        Err(_) => {
-            panic!(
-                "Couldn't find the powdr standard library at {}. Set the {} environment variable.",
-                std_path.display(),
-                POWDR_STD_ENV
-            )
-        }
-        Ok(std_source) => {
-            let std_content =
-                parse_asm::<T>(Some(std_path.as_path().to_str().unwrap()), &std_source)
-                    .unwrap_or_else(|err| {
-                        eprintln!("Error parsing powdr standard library file:");
-                        err.output_to_stderr();
-                        panic!();
-                    });
-            // This resolves all submodules and returns the standard library's main module
-            load_module_files(Some(std_path), std_content).unwrap().main
-        }
-    }
+fn load_std<T: FieldElement>(std: &StdLibrary, mut sources: SourceMap) -> ASMProgram<T> {
+    let std_path = PathBuf::from(MOD_FILE);
+    let std_source = std.read(&std_path).unwrap_or_else(|e| {
+        panic!("Couldn't load the powdr standard library: {e} Set the {POWDR_STD_ENV} environment variable.")
+    });
+    let file_name = std.display_path(&std_path);
+    let mut main = parse_module::<T>(Some(&file_name), &std_source).unwrap_or_else(|err| {
+        eprintln!("Error parsing powdr standard library file:");
+        err.output_to_stderr();
+        panic!();
+    });
+    sources.add_module(&file_name, &std_source, &mut main);
+    // This resolves all submodules
+    load_std_module_files(std, std_path, ASMProgram { main, sources }).unwrap()
 }
 
-pub fn add_std<T: FieldElement>(program: ASMProgram<T>) -> Result<ASMProgram<T>, String> {
-    StdAdder().fold_program(program)
+/// Adds the standard library as module `std` to the main module and imports it in
+/// all other modules. The name `std` is reserved, so that the standard library cannot
+/// accidentally be replaced by a local module. To use a different standard library,
+/// pass [StdLibrary::Directory].
+pub fn add_std<T: FieldElement>(
+    program: ASMProgram<T>,
+    std: &StdLibrary,
+) -> Result<ASMProgram<T>, String> {
+    StdAdder(std).fold_program(program)
 }
 
-struct StdAdder();
+struct StdAdder<'a>(&'a StdLibrary);
 
 type Error = String;
 
-impl<T: FieldElement> Folder<T> for StdAdder {
+impl<'a, T: FieldElement> Folder<T> for StdAdder<'a> {
     type Error = Error;
 
     fn fold_program(&mut self, p: ASMProgram<T>) -> Result<ASMProgram<T>, Self::Error> {
         // Add `std` to the main module
         let mut main = p.main;
+        let sources = p.sources;
+        if main.statements.iter().any(|s| match s {
+            ModuleStatement::SymbolDefinition(d) => d.name == "std",
+        }) {
+            return Err(format!(
+                "The name `std` is reserved for the standard library. \
+                 To use a different standard library, set the {POWDR_STD_ENV} environment variable."
+            ));
+        }
+        let std = load_std(self.0, sources);
         main.statements
             .push(ModuleStatement::SymbolDefinition(SymbolDefinition {
                 name: "std".to_string(),
-                value: SymbolValue::Module(Module::Local(load_std())),
+                value: SymbolValue::Module(Module::Local(std.main)),
             }));
 
         // Recurse
         let main = self.fold_module_value(main)?;
-        Ok(ASMProgram { main })
+        Ok(ASMProgram {
+            main,
+            sources: std.sources,
+        })
     }
 
     fn fold_module_value(&mut self, module: ASMModule<T>) -> Result<ASMModule<T>, Self::Error> {
@@ -87,7 +92,7 @@ impl<T: FieldElement> Folder<T> for StdAdder {
                 ModuleStatement::SymbolDefinition(d) => match d.value {
                     SymbolValue::Machine(machine) => self.fold_machine(machine).map(From::from),
                     SymbolValue::Import(import) => {
-                        <StdAdder as Folder<T>>::fold_import(self, import).map(From::from)
+                        <StdAdder<'a> as Folder<T>>::fold_import(self, import).map(From::from)
                     }
                     SymbolValue::Module(module) => self.fold_module(module).map(From::from),
                 }
@@ -117,3 +122,44 @@ impl<T: FieldElement> Folder<T> for StdAdder {
         Ok(ASMModule { statements })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use number::Bn254Field;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Resolves a file of `test_data/local_std`, which contains a local `std` directory.
+    fn resolve_local_std(file: &str, std: &StdLibrary) -> Result<String, String> {
+        let path = PathBuf::from("./test_data/local_std").join(file);
+        let input = std::fs::read_to_string(&path).unwrap();
+        let parsed = parser::parse_asm::<Bn254Field>(None, &input).unwrap();
+        crate::resolve_with_std(Some(path), parsed, std).map(|program| program.to_string())
+    }
+
+    #[test]
+    fn embedded_std() {
+        let program = resolve_local_std("main.asm", &StdLibrary::Embedded).unwrap();
+        assert!(program.contains("operation check<0> BYTE;"));
+        assert!(!program.contains("local_byte"));
+    }
+
+    #[test]
+    fn local_std_module_is_rejected() {
+        assert_eq!(
+            resolve_local_std("shadowing.asm", &StdLibrary::Embedded),
+            Err("The name `std` is reserved for the standard library. \
+                 To use a different standard library, set the POWDR_STD environment variable."
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn std_from_directory() {
+        let std = StdLibrary::Directory(PathBuf::from("./test_data/local_std/std"));
+        let program = resolve_local_std("main.asm", &std).unwrap();
+        assert!(program.contains("local_byte"));
+        assert!(!program.contains("operation check<0> BYTE;"));
+    }
+}
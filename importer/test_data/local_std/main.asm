@@ -0,0 +1,5 @@
+use std::range_check::Byte;
+
+machine Main {
+    Byte byte;
+}
@@ -0,0 +1,6 @@
+mod std;
+use std::range_check::Byte;
+
+machine Main {
+    Byte byte;
+}
@@ -0,0 +1,3 @@
+machine Byte {
+    col witness local_byte;
+}
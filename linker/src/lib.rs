@@ -1,11 +1,14 @@
 #![deny(clippy::print_stdout)]
 
 use analysis::utils::parse_pil_statement;
+use std::collections::BTreeMap;
+
 use ast::{
     object::{Location, PILGraph},
     parsed::{
+        asm::LinkKind,
         build::{direct_reference, index_access, namespaced_reference},
-        Expression, PILFile, PilStatement, SelectedExpressions,
+        BinaryOperator, Expression, PILFile, PilStatement, SelectedExpressions,
     },
 };
 use number::FieldElement;
@@ -13,6 +16,13 @@ use number::FieldElement;
 const DEFAULT_DEGREE: u64 = 1024;
 const MAIN_OPERATION_NAME: &str = "main";
 
+/// The name of the selector column of the `index`-th permutation link into a machine.
+/// It is declared in the namespace of the callee and selects the rows that are called
+/// through this link.
+fn permutation_selector(index: usize) -> String {
+    format!("_permutation_selector_{index}")
+}
+
 /// a monolithic linker which outputs a single AIR
 /// It sets the degree of submachines to the degree of the main machine, and errors out if a submachine has an explicit degree which doesn't match the main one
 pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<String>> {
@@ -26,6 +36,18 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
 
     let mut errors = vec![];
 
+    // Each permutation link gets its own selector column in the callee, they are
+    // numbered in the order the links are processed in below.
+    let mut permutation_link_count = BTreeMap::<Location, usize>::new();
+    for link in graph.objects.values().flat_map(|object| &object.links) {
+        if link.kind == LinkKind::Permutation {
+            *permutation_link_count
+                .entry(link.to.machine.location.clone())
+                .or_default() += 1;
+        }
+    }
+    let mut next_permutation_selector = BTreeMap::<Location, usize>::new();
+
     let pil = graph
         .objects
         .into_iter()
@@ -43,11 +65,19 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
 
             // create a namespace for this object
             pil.push(PilStatement::Namespace(
-                0,
+                object.start,
                 location.to_string(),
                 Expression::Number(T::from(main_degree)),
             ));
             pil.extend(object.pil);
+            // selector columns for the permutation links into this machine, they are boolean
+            for index in 0..permutation_link_count.get(&location).cloned().unwrap_or_default() {
+                let selector = permutation_selector(index);
+                pil.extend([
+                    parse_pil_statement(&format!("col witness {selector}")),
+                    parse_pil_statement(&format!("{selector} * (1 - {selector}) = 0")),
+                ]);
+            }
             for link in object.links {
                 // add the link to this namespace as a lookup
 
@@ -77,15 +107,27 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
                         )
                         .collect(),
                 };
-                // the rhs is `(instr_flag * latch) { inputs, outputs }`
+                // the rhs is `latch { inputs, outputs }` for lookups and
+                // `(latch * selector) { inputs, outputs }` for permutations
                 // get the instruction in the submachine
 
                 let params = to.operation.params;
 
                 let to_namespace = to.machine.location.clone().to_string();
 
+                let latch = namespaced_reference(to_namespace.clone(), to.machine.latch.unwrap());
+                let selector = match link.kind {
+                    LinkKind::Lookup => latch,
+                    LinkKind::Permutation => {
+                        let index = next_permutation_selector.entry(to.machine.location.clone()).or_default();
+                        let selector = namespaced_reference(to_namespace.clone(), permutation_selector(*index));
+                        *index += 1;
+                        Expression::BinaryOperation(Box::new(latch), BinaryOperator::Mul, Box::new(selector))
+                    }
+                };
+
                 let rhs = SelectedExpressions {
-                    selector: Some(namespaced_reference(to_namespace.clone(), to.machine.latch.unwrap())),
+                    selector: Some(selector),
                     expressions: to.machine.operation_id.map(|operation_id| namespaced_reference(
                         to_namespace.clone(),
                         operation_id,
@@ -101,8 +143,10 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
                     .collect(),
                 };
 
-                let lookup = PilStatement::PlookupIdentity(0, None, lhs, rhs);
-                pil.push(lookup);
+                pil.push(match link.kind {
+                    LinkKind::Lookup => PilStatement::PlookupIdentity(link.start, None, lhs, rhs),
+                    LinkKind::Permutation => PilStatement::PermutationIdentity(link.start, None, lhs, rhs),
+                });
             }
 
             if location == Location::main() {
@@ -118,7 +162,9 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
                             // call the main operation by initialising `operation_id` to that of the main operation
                             let linker_first_step = "_linker_first_step";
                             pil.extend([
-                                parse_pil_statement(&format!("col fixed {linker_first_step} = [1] + [0]*")),
+                                parse_pil_statement(&format!(
+                                    "#[role(first_row)] col fixed {linker_first_step} = [1] + [0]*"
+                                )),
                                 parse_pil_statement(&format!(
                                     "{linker_first_step} * ({operation_id} - {main_operation_id}) = 0"
                                 )),
@@ -222,7 +268,7 @@ pol commit instr__jump_to_operation;
 pol commit instr__reset;
 pol commit instr__loop;
 pol commit instr_return;
-pol constant first_step = [1] + [0]*;
+#[role(first_row)] pol constant first_step = [1] + [0]*;
 pol pc_update = ((((instr__jump_to_operation * _operation_id) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((instr__jump_to_operation + instr__loop) + instr_return)) * (pc + 1)));
 pc' = ((1 - first_step') * pc_update);
 pol constant p_line = [0, 1, 2] + [2]*;
@@ -231,7 +277,7 @@ pol constant p_instr__loop = [0, 0, 1] + [1]*;
 pol constant p_instr__reset = [1, 0, 0] + [0]*;
 pol constant p_instr_return = [0, 0, 0] + [0]*;
 { pc, instr__jump_to_operation, instr__reset, instr__loop, instr_return } in { p_line, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return };
-pol constant _block_enforcer_last_step = [0]* + [1];
+#[role(last_row)] pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _operation_id_no_change;
 _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_operation_id_no_change * (_operation_id' - _operation_id)) = 0;
@@ -275,7 +321,7 @@ pol commit Y_read_free;
 pol commit read_Y_A;
 pol commit read_Y_pc;
 Y = ((((read_Y_A * A) + (read_Y_pc * pc)) + Y_const) + (Y_read_free * Y_free_value));
-pol constant first_step = [1] + [0]*;
+#[role(first_row)] pol constant first_step = [1] + [0]*;
 A' = ((((reg_write_X_A * X) + (reg_write_Y_A * Y)) + (instr__reset * 0)) + ((1 - ((reg_write_X_A + reg_write_Y_A) + instr__reset)) * A));
 pol pc_update = ((((instr__jump_to_operation * _operation_id) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((instr__jump_to_operation + instr__loop) + instr_return)) * (pc + 1)));
 pc' = ((1 - first_step') * pc_update);
@@ -300,14 +346,14 @@ pol constant p_read_Y_pc = [0, 0, 0, 0, 0] + [0]*;
 pol constant p_reg_write_X_A = [0, 0, 0, 0, 0] + [0]*;
 pol constant p_reg_write_Y_A = [0, 0, 1, 0, 0] + [0]*;
 { pc, reg_write_X_A, reg_write_Y_A, instr_identity, instr_one, instr_nothing, instr__jump_to_operation, instr__reset, instr__loop, instr_return, X_const, X_read_free, read_X_A, read_X_pc, Y_const, Y_read_free, read_Y_A, read_Y_pc } in { p_line, p_reg_write_X_A, p_reg_write_Y_A, p_instr_identity, p_instr_one, p_instr_nothing, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return, p_X_const, p_X_read_free, p_read_X_A, p_read_X_pc, p_Y_const, p_Y_read_free, p_read_Y_A, p_read_Y_pc };
-pol constant _block_enforcer_last_step = [0]* + [1];
+#[role(last_row)] pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _operation_id_no_change;
 _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_operation_id_no_change * (_operation_id' - _operation_id)) = 0;
 instr_identity { 2, X, Y } in main_sub.instr_return { main_sub._operation_id, main_sub._input_0, main_sub._output_0 };
 instr_one { 4, Y } in main_sub.instr_return { main_sub._operation_id, main_sub._output_0 };
 instr_nothing { 3 } in main_sub.instr_return { main_sub._operation_id };
-pol constant _linker_first_step = [1] + [0]*;
+#[role(first_row)] pol constant _linker_first_step = [1] + [0]*;
 (_linker_first_step * (_operation_id - 2)) = 0;
 namespace main_sub(16);
 pol commit _operation_id(i) query ("hint", 5);
@@ -323,7 +369,7 @@ pol commit _output_0_read_free;
 pol commit read__output_0_pc;
 pol commit read__output_0__input_0;
 _output_0 = ((((read__output_0_pc * pc) + (read__output_0__input_0 * _input_0)) + _output_0_const) + (_output_0_read_free * _output_0_free_value));
-pol constant first_step = [1] + [0]*;
+#[role(first_row)] pol constant first_step = [1] + [0]*;
 ((1 - instr__reset) * (_input_0' - _input_0)) = 0;
 pol pc_update = ((((instr__jump_to_operation * _operation_id) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((instr__jump_to_operation + instr__loop) + instr_return)) * (pc + 1)));
 pc' = ((1 - first_step') * pc_update);
@@ -338,7 +384,7 @@ pol constant p_instr_return = [0, 0, 1, 1, 1, 0] + [0]*;
 pol constant p_read__output_0__input_0 = [0, 0, 1, 0, 0, 0] + [0]*;
 pol constant p_read__output_0_pc = [0, 0, 0, 0, 0, 0] + [0]*;
 { pc, instr__jump_to_operation, instr__reset, instr__loop, instr_return, _output_0_const, _output_0_read_free, read__output_0_pc, read__output_0__input_0 } in { p_line, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return, p__output_0_const, p__output_0_read_free, p_read__output_0_pc, p_read__output_0__input_0 };
-pol constant _block_enforcer_last_step = [0]* + [1];
+#[role(last_row)] pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _operation_id_no_change;
 _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_operation_id_no_change * (_operation_id' - _operation_id)) = 0;
@@ -388,7 +434,7 @@ pol commit read_X_A;
 pol commit read_X_CNT;
 pol commit read_X_pc;
 X = (((((read_X_A * A) + (read_X_CNT * CNT)) + (read_X_pc * pc)) + X_const) + (X_read_free * X_free_value));
-pol constant first_step = [1] + [0]*;
+#[role(first_row)] pol constant first_step = [1] + [0]*;
 A' = (((reg_write_X_A * X) + (instr__reset * 0)) + ((1 - (reg_write_X_A + instr__reset)) * A));
 CNT' = ((((reg_write_X_CNT * X) + (instr_dec_CNT * (CNT - 1))) + (instr__reset * 0)) + ((1 - ((reg_write_X_CNT + instr_dec_CNT) + instr__reset)) * CNT));
 pol pc_update = ((((((instr_jmpz * (instr_jmpz_pc_update + instr_jmpz_pc_update_1)) + (instr_jmp * instr_jmp_param_l)) + (instr__jump_to_operation * _operation_id)) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((((instr_jmpz + instr_jmp) + instr__jump_to_operation) + instr__loop) + instr_return)) * (pc + 1)));
@@ -413,11 +459,11 @@ pol constant p_read_X_pc = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
 pol constant p_reg_write_X_A = [0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0] + [0]*;
 pol constant p_reg_write_X_CNT = [0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
 { pc, reg_write_X_A, reg_write_X_CNT, instr_jmpz, instr_jmpz_param_l, instr_jmp, instr_jmp_param_l, instr_dec_CNT, instr_assert_zero, instr__jump_to_operation, instr__reset, instr__loop, instr_return, X_const, X_read_free, read_X_A, read_X_CNT, read_X_pc } in { p_line, p_reg_write_X_A, p_reg_write_X_CNT, p_instr_jmpz, p_instr_jmpz_param_l, p_instr_jmp, p_instr_jmp_param_l, p_instr_dec_CNT, p_instr_assert_zero, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return, p_X_const, p_X_read_free, p_read_X_A, p_read_X_CNT, p_read_X_pc };
-pol constant _block_enforcer_last_step = [0]* + [1];
+#[role(last_row)] pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _operation_id_no_change;
 _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_operation_id_no_change * (_operation_id' - _operation_id)) = 0;
-pol constant _linker_first_step = [1] + [0]*;
+#[role(first_row)] pol constant _linker_first_step = [1] + [0]*;
 (_linker_first_step * (_operation_id - 2)) = 0;
 
 "#;
@@ -462,7 +508,7 @@ pol commit instr__jump_to_operation;
 pol commit instr__reset;
 pol commit instr__loop;
 pol commit instr_return;
-pol constant first_step = [1] + [0]*;
+#[role(first_row)] pol constant first_step = [1] + [0]*;
 fp' = ((((instr_inc_fp * (fp + instr_inc_fp_param_amount)) + (instr_adjust_fp * (fp + instr_adjust_fp_param_amount))) + (instr__reset * 0)) + ((1 - ((instr_inc_fp + instr_adjust_fp) + instr__reset)) * fp));
 pol pc_update = (((((instr_adjust_fp * label) + (instr__jump_to_operation * _operation_id)) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - (((instr_adjust_fp + instr__jump_to_operation) + instr__loop) + instr_return)) * (pc + 1)));
 pc' = ((1 - first_step') * pc_update);
@@ -477,11 +523,11 @@ pol constant p_instr_inc_fp = [0, 0, 1, 0, 0] + [0]*;
 pol constant p_instr_inc_fp_param_amount = [0, 0, 7, 0, 0] + [0]*;
 pol constant p_instr_return = [0, 0, 0, 0, 0] + [0]*;
 { pc, instr_inc_fp, instr_inc_fp_param_amount, instr_adjust_fp, instr_adjust_fp_param_amount, instr_adjust_fp_param_t, instr__jump_to_operation, instr__reset, instr__loop, instr_return } in { p_line, p_instr_inc_fp, p_instr_inc_fp_param_amount, p_instr_adjust_fp, p_instr_adjust_fp_param_amount, p_instr_adjust_fp_param_t, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return };
-pol constant _block_enforcer_last_step = [0]* + [1];
+#[role(last_row)] pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _operation_id_no_change;
 _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_operation_id_no_change * (_operation_id' - _operation_id)) = 0;
-pol constant _linker_first_step = [1] + [0]*;
+#[role(first_row)] pol constant _linker_first_step = [1] + [0]*;
 (_linker_first_step * (_operation_id - 2)) = 0;
 "#;
         let graph = parse_analyse_and_compile::<GoldilocksField>(source);
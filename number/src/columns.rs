@@ -0,0 +1,244 @@
+//! Access to the values of columns that are not necessarily stored in a `Vec`,
+//! e.g. columns mapped from a binary columns file, see [map_polys_file].
+
+use std::{fs::File, marker::PhantomData, sync::Arc};
+
+use memmap2::Mmap;
+
+use crate::serialize::{check_columns, read_header, value_width};
+use crate::{ColumnsWithDegree, DegreeType, FieldElement, POLYS_FILE_MAGIC};
+
+/// Read-only access to the values of a column by row.
+pub trait ColumnAccess<T> {
+    /// The number of rows of the column.
+    fn len(&self) -> usize;
+
+    /// The value in the given row. Panics if the row is out of range.
+    fn index(&self, row: usize) -> T;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the values in row order.
+    fn values(&self) -> Box<dyn Iterator<Item = T> + '_> {
+        Box::new((0..self.len()).map(|row| self.index(row)))
+    }
+}
+
+impl<T: Copy> ColumnAccess<T> for [T] {
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn index(&self, row: usize) -> T {
+        self[row]
+    }
+}
+
+impl<T: Copy> ColumnAccess<T> for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn index(&self, row: usize) -> T {
+        self[row]
+    }
+}
+
+impl<T, C: ColumnAccess<T> + ?Sized> ColumnAccess<T> for &C {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn index(&self, row: usize) -> T {
+        (**self).index(row)
+    }
+}
+
+/// Returns the columns as references to [ColumnAccess], so that columns of different
+/// types can be passed to the same function.
+pub fn column_refs<T, C: ColumnAccess<T>>(
+    columns: &[(String, C)],
+) -> Vec<(String, &dyn ColumnAccess<T>)> {
+    columns
+        .iter()
+        .map(|(name, column)| (name.clone(), column as &dyn ColumnAccess<T>))
+        .collect()
+}
+
+/// A column of a binary columns file mapped into memory, see [map_polys_file].
+/// The values are decoded on access, nothing is copied.
+#[derive(Clone)]
+pub struct MappedColumn<T> {
+    mmap: Arc<Mmap>,
+    /// The position of the value in the first row.
+    offset: usize,
+    /// The number of bytes of a row.
+    stride: usize,
+    /// The number of bytes of a value.
+    width: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: FieldElement> ColumnAccess<T> for MappedColumn<T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn index(&self, row: usize) -> T {
+        assert!(row < self.len, "Row {row} is out of range.");
+        let start = self.offset + row * self.stride;
+        T::from_bytes_le(&self.mmap[start..start + self.width])
+    }
+}
+
+/// Like [crate::read_polys_file], but maps the file into memory instead of reading it,
+/// so that the values do not have to be held in memory.
+///
+/// The file must not be modified while the columns are in use. Columns files are
+/// replaced by renaming a new file, which does not affect an existing mapping.
+pub fn map_polys_file<T: FieldElement>(
+    file: &File,
+    columns: &[String],
+) -> Result<ColumnsWithDegree<MappedColumn<T>>, String> {
+    // Safety: The mapping is read-only and, see above, the file is not modified while mapped.
+    let mmap = unsafe { Mmap::map(file) }.map_err(|e| e.to_string())?;
+    let width = value_width::<T>();
+
+    let (names, header_len, degree) = match mmap.strip_prefix(POLYS_FILE_MAGIC) {
+        Some(mut data) => {
+            let (names, degree) = read_header(&mut data)?;
+            check_columns(&names, columns)?;
+            let stride = width * names.len();
+            if stride != 0 && (data.len() / stride) as DegreeType != degree {
+                return Err(format!(
+                    "The file contains {} rows, but the degree is {degree}.",
+                    data.len() / stride
+                ));
+            }
+            (names, mmap.len() - data.len(), degree)
+        }
+        None => {
+            log::warn!(
+                "The columns file has no header, assuming it contains the columns in the order of the PIL. \
+                Files without a header will not be supported in the next release, please regenerate it."
+            );
+            let stride = width * columns.len();
+            if stride != 0 && mmap.len() % stride != 0 {
+                return Err(format!(
+                    "The file size of {} bytes is not a multiple of the row size of {stride} bytes.",
+                    mmap.len()
+                ));
+            }
+            let degree = mmap.len().checked_div(stride).unwrap_or(0);
+            (columns.to_vec(), 0, degree as DegreeType)
+        }
+    };
+
+    let mmap = Arc::new(mmap);
+    let columns = columns
+        .iter()
+        .map(|name| {
+            let index = names.iter().position(|n| n == name).unwrap();
+            let column = MappedColumn {
+                mmap: mmap.clone(),
+                offset: header_len + index * width,
+                stride: width * names.len(),
+                width,
+                len: degree as usize,
+                _marker: PhantomData,
+            };
+            (name.clone(), column)
+        })
+        .collect();
+    Ok((columns, degree))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{read_polys_file, write_polys_file, write_raw_polys_file, Bn254Field};
+
+    use super::*;
+    use test_log::test;
+
+    fn test_polys() -> Vec<(String, Vec<Bn254Field>)> {
+        vec![
+            ("a".to_string(), (0..16).map(Bn254Field::from).collect()),
+            ("b".to_string(), (-16..0).map(Bn254Field::from).collect()),
+        ]
+    }
+
+    fn mapped_values(
+        columns: &[(String, MappedColumn<Bn254Field>)],
+    ) -> Vec<(String, Vec<Bn254Field>)> {
+        columns
+            .iter()
+            .map(|(name, column)| (name.clone(), column.values().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn map_and_read() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let path = dir.join("commits.bin");
+        let polys = test_polys();
+        write_polys_file(&mut fs::File::create(&path).unwrap(), &polys);
+
+        let names = ["b".to_string(), "a".to_string()];
+        let (mapped, degree) =
+            map_polys_file::<Bn254Field>(&File::open(&path).unwrap(), &names).unwrap();
+        assert_eq!(degree, 16);
+        assert_eq!(mapped[1].1.index(3), Bn254Field::from(3));
+        assert_eq!(mapped[0].1.len(), 16);
+        // The mapped columns are the same as the ones read into memory.
+        let read = read_polys_file::<Bn254Field>(&mut File::open(&path).unwrap(), &names)
+            .unwrap()
+            .0;
+        assert_eq!(mapped_values(&mapped), read);
+
+        let err = map_polys_file::<Bn254Field>(&File::open(&path).unwrap(), &names[..1])
+            .err()
+            .unwrap();
+        assert!(err.contains("unexpected columns: [a]"), "{err}");
+
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        let err = map_polys_file::<Bn254Field>(&File::open(&path).unwrap(), &names)
+            .err()
+            .unwrap();
+        assert_eq!(err, "The file contains 15 rows, but the degree is 16.");
+    }
+
+    #[test]
+    fn map_legacy() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let path = dir.join("constants.bin");
+        let polys = test_polys();
+        write_raw_polys_file(&mut fs::File::create(&path).unwrap(), &polys);
+        let (mapped, degree) = map_polys_file::<Bn254Field>(
+            &File::open(&path).unwrap(),
+            &["a".to_string(), "b".to_string()],
+        )
+        .unwrap();
+        assert_eq!(degree, 16);
+        assert_eq!(mapped_values(&mapped), polys);
+
+        // A truncated file without a header is rejected instead of dropping the last row.
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        let err = map_polys_file::<Bn254Field>(
+            &File::open(&path).unwrap(),
+            &["a".to_string(), "b".to_string()],
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err,
+            "The file size of 1023 bytes is not a multiple of the row size of 64 bytes."
+        );
+    }
+}
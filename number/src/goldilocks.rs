@@ -58,4 +58,13 @@ mod test {
     fn div_by_zero() {
         let _ = GoldilocksField::from(1) / GoldilocksField::from(0);
     }
+
+    #[test]
+    fn serde() {
+        let x = -GoldilocksField::from(1);
+        let json = serde_json::to_string(&x).unwrap();
+        assert_eq!(json, "\"18446744069414584320\"");
+        assert_eq!(serde_json::from_str::<GoldilocksField>(&json).unwrap(), x);
+        assert!(serde_json::from_str::<GoldilocksField>("\"18446744069414584321\"").is_err());
+    }
 }
@@ -5,14 +5,19 @@
 #[macro_use]
 mod macros;
 mod bn254;
+mod columns;
 mod goldilocks;
 mod serialize;
+pub mod test_vectors;
 mod traits;
 
 pub use serialize::{
-    read_polys_csv_file, read_polys_file, write_polys_csv_file, write_polys_file, CsvRenderMode,
+    read_polys_csv_file, read_polys_file, write_polys_csv_file, write_polys_file,
+    write_raw_polys_file, ColumnsWithDegree, CsvRenderMode, POLYS_FILE_MAGIC, POLYS_FILE_VERSION,
 };
 
+pub use columns::{column_refs, map_polys_file, ColumnAccess, MappedColumn};
+
 pub use bn254::Bn254Field;
 pub use goldilocks::GoldilocksField;
 pub use traits::KnownField;
@@ -336,6 +336,39 @@ macro_rules! powdr_field {
             }
         }
 
+        /// Field elements are serialized as their canonical integer representation,
+        /// in decimal for human-readable formats and as little-endian bytes otherwise.
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let value = self.to_arbitrary_integer();
+                if serializer.is_human_readable() {
+                    serializer.serialize_str(&value.to_string())
+                } else {
+                    serde::Serialize::serialize(&value.to_bytes_le(), serializer)
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use serde::de::Error;
+                let value = if deserializer.is_human_readable() {
+                    let value = <String as serde::Deserialize>::deserialize(deserializer)?;
+                    BigUint::from_str(&value).map_err(D::Error::custom)?
+                } else {
+                    BigUint::from_bytes_le(&<Vec<u8> as serde::Deserialize>::deserialize(
+                        deserializer,
+                    )?)
+                };
+                if value >= Self::modulus().to_arbitrary_integer() {
+                    return Err(D::Error::custom(format!(
+                        "{value} is not smaller than the modulus"
+                    )));
+                }
+                Ok(value.into())
+            }
+        }
+
         impl From<$ark_type> for $name {
             #[inline]
             fn from(value: $ark_type) -> Self {
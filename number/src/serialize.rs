@@ -1,4 +1,4 @@
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 
 use csv::{Reader, Writer};
 
@@ -13,11 +13,34 @@ pub enum CsvRenderMode {
 
 const ROW_NAME: &str = "Row";
 
+/// Renders a single field element the way it is written to a CSV file.
+pub(crate) fn render_csv_value<T: FieldElement>(value: &T, render_mode: CsvRenderMode) -> String {
+    match render_mode {
+        CsvRenderMode::SignedBase10 => format!("{value}"),
+        CsvRenderMode::UnsignedBase10 => format!("{}", value.to_integer()),
+        CsvRenderMode::Hex => format!("0x{:x}", value.to_integer()),
+    }
+}
+
+/// Parses a single field element from a CSV file, in any of the formats
+/// produced by [render_csv_value].
+pub(crate) fn parse_csv_value<T: FieldElement>(value: &str) -> T {
+    if let Some(value) = value.strip_prefix("0x") {
+        T::from_str_radix(value, 16).unwrap()
+    } else if let Some(value) = value.strip_prefix('-') {
+        -T::from_str(value)
+    } else {
+        T::from_str(value)
+    }
+}
+
+/// Writes the columns as CSV with an additional "Row" column. The rows are rendered
+/// and written one by one, so no copy of the table is kept in memory.
 pub fn write_polys_csv_file<T: FieldElement>(
     file: &mut impl Write,
     render_mode: CsvRenderMode,
     polys: &[(String, Vec<T>)],
-) {
+) -> io::Result<()> {
     let mut writer = Writer::from_writer(file);
 
     // Write headers, adding a "Row" column
@@ -26,25 +49,20 @@ pub fn write_polys_csv_file<T: FieldElement>(
         assert!(name != ROW_NAME);
         name.as_str()
     }));
-    writer.write_record(&headers).unwrap();
+    writer.write_record(&headers)?;
 
     let len = polys[0].1.len();
     for row_index in 0..len {
-        let mut row = Vec::new();
-        row.push(format!("{}", row_index));
+        writer.write_field(row_index.to_string())?;
         for (_, values) in polys {
             assert!(values.len() == len);
-            let value = match render_mode {
-                CsvRenderMode::SignedBase10 => format!("{}", values[row_index]),
-                CsvRenderMode::UnsignedBase10 => format!("{}", values[row_index].to_integer()),
-                CsvRenderMode::Hex => format!("0x{:x}", values[row_index].to_integer()),
-            };
-            row.push(value);
+            writer.write_field(render_csv_value(&values[row_index], render_mode))?;
         }
-        writer.write_record(&row).unwrap();
+        // Terminates the record.
+        writer.write_record(None::<&[u8]>)?;
     }
 
-    writer.flush().unwrap();
+    writer.flush()
 }
 
 pub fn read_polys_csv_file<T: FieldElement>(file: &mut impl Read) -> Vec<(String, Vec<T>)> {
@@ -59,14 +77,7 @@ pub fn read_polys_csv_file<T: FieldElement>(file: &mut impl Read) -> Vec<(String
     for result in reader.records() {
         let record = result.unwrap();
         for (idx, value) in record.iter().enumerate() {
-            let value = if let Some(value) = value.strip_prefix("0x") {
-                T::from_str_radix(value, 16).unwrap()
-            } else if let Some(value) = value.strip_prefix('-') {
-                -T::from_str(value)
-            } else {
-                T::from_str(value)
-            };
-            polys[idx].1.push(value);
+            polys[idx].1.push(parse_csv_value(value));
         }
     }
 
@@ -81,15 +92,57 @@ fn ceil_div(num: usize, div: usize) -> usize {
     (num + div - 1) / div
 }
 
+/// The number of bytes of a value in a binary columns file.
+pub(crate) fn value_width<T: FieldElement>() -> usize {
+    ceil_div(T::BITS as usize, 64) * 8
+}
+
+/// Named columns read from a file together with their degree, see [read_polys_file].
+pub type ColumnsWithDegree<C> = (Vec<(String, C)>, DegreeType);
+
+/// The first bytes of a binary columns file written by [write_polys_file].
+pub const POLYS_FILE_MAGIC: &[u8; 8] = b"powdrpol";
+/// The version of the header of binary columns files.
+pub const POLYS_FILE_VERSION: u32 = 1;
+
+/// Writes the columns in binary form: a header followed by the values row by row,
+/// each in little-endian encoding padded to a multiple of 8 bytes.
+///
+/// The header consists of [POLYS_FILE_MAGIC], [POLYS_FILE_VERSION] (as u32), the degree
+/// and the number of columns (as u64) and the name (as u64 length and UTF-8 bytes) and
+/// length (as u64) of each column, in the order of the values in each row. All integers
+/// are little-endian.
 pub fn write_polys_file<T: FieldElement>(file: &mut impl Write, polys: &[(String, Vec<T>)]) {
-    let width = ceil_div(T::BITS as usize, 64) * 8;
+    // TODO maybe the witness should have a proper type that
+    // explicitly has a degree or length?
+    let degree = polys.first().map_or(0, |(_, values)| values.len());
+
+    file.write_all(POLYS_FILE_MAGIC).unwrap();
+    file.write_all(&POLYS_FILE_VERSION.to_le_bytes()).unwrap();
+    file.write_all(&(degree as u64).to_le_bytes()).unwrap();
+    file.write_all(&(polys.len() as u64).to_le_bytes()).unwrap();
+    for (name, values) in polys {
+        assert_eq!(
+            values.len(),
+            degree,
+            "Column {name} has a different length."
+        );
+        file.write_all(&(name.len() as u64).to_le_bytes()).unwrap();
+        file.write_all(name.as_bytes()).unwrap();
+        file.write_all(&(values.len() as u64).to_le_bytes())
+            .unwrap();
+    }
+    write_raw_polys_file(file, polys);
+}
+
+/// Writes the values of the columns row by row without a header, as expected by pil-stark.
+pub fn write_raw_polys_file<T: FieldElement>(file: &mut impl Write, polys: &[(String, Vec<T>)]) {
+    let width = value_width::<T>();
 
     if polys.is_empty() {
         return;
     }
 
-    // TODO maybe the witness should have a proper type that
-    // explicitly has a degree or length?
     let degree = polys[0].1.len();
 
     for i in 0..degree {
@@ -101,11 +154,116 @@ pub fn write_polys_file<T: FieldElement>(file: &mut impl Write, polys: &[(String
     }
 }
 
+/// Reads a file written by [write_polys_file] and returns the values of `columns`
+/// together with the degree. Fails if the columns in the file are not exactly `columns`,
+/// columns in a different order are reordered.
+///
+/// Files without a header, which contain the values of `columns` in this order, are still
+/// read with a warning, this will be removed in the next release.
 pub fn read_polys_file<T: FieldElement>(
     file: &mut impl Read,
     columns: &[String],
+) -> Result<ColumnsWithDegree<Vec<T>>, String> {
+    let mut magic = vec![];
+    file.take(POLYS_FILE_MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .map_err(|e| e.to_string())?;
+    if magic != POLYS_FILE_MAGIC {
+        log::warn!(
+            "The columns file has no header, assuming it contains the columns in the order of the PIL. \
+            Files without a header will not be supported in the next release, please regenerate it."
+        );
+        return Ok(read_raw_polys_file(
+            &mut magic.as_slice().chain(file),
+            columns,
+        ));
+    }
+
+    let (names, degree) = read_header(file)?;
+    check_columns(&names, columns)?;
+
+    let (polys, rows) = read_raw_polys_file(file, &names);
+    if rows != degree {
+        return Err(format!(
+            "The file contains {rows} rows, but the degree is {degree}."
+        ));
+    }
+    let mut polys = polys.into_iter().map(Some).collect::<Vec<_>>();
+    let polys = columns
+        .iter()
+        .map(|name| {
+            let index = names.iter().position(|n| n == name).unwrap();
+            polys[index].take().unwrap()
+        })
+        .collect();
+    Ok((polys, degree))
+}
+
+/// Reads the header of a file written by [write_polys_file], after [POLYS_FILE_MAGIC].
+/// @returns the names of the columns in the order of the values in each row, and the degree.
+pub(crate) fn read_header(file: &mut impl Read) -> Result<(Vec<String>, DegreeType), String> {
+    let read_u64 = |file: &mut dyn Read| -> Result<u64, String> {
+        let mut bytes = [0u8; 8];
+        file.read_exact(&mut bytes)
+            .map_err(|e| format!("Invalid header: {e}"))?;
+        Ok(u64::from_le_bytes(bytes))
+    };
+    let mut version = [0u8; 4];
+    file.read_exact(&mut version)
+        .map_err(|e| format!("Invalid header: {e}"))?;
+    let version = u32::from_le_bytes(version);
+    if version != POLYS_FILE_VERSION {
+        return Err(format!(
+            "Unsupported version {version} of the columns file, expected {POLYS_FILE_VERSION}."
+        ));
+    }
+    let degree = read_u64(file)?;
+    let count = read_u64(file)?;
+    let mut names = vec![];
+    for _ in 0..count {
+        let mut name = vec![0u8; read_u64(file)? as usize];
+        file.read_exact(&mut name)
+            .map_err(|e| format!("Invalid header: {e}"))?;
+        let name = String::from_utf8(name).map_err(|e| format!("Invalid header: {e}"))?;
+        let length = read_u64(file)?;
+        if length != degree {
+            return Err(format!(
+                "Column {name} has length {length}, but the degree is {degree}."
+            ));
+        }
+        names.push(name);
+    }
+    Ok((names, degree))
+}
+
+/// Checks that the columns in a file, `names`, are exactly `columns`, in any order.
+pub(crate) fn check_columns(names: &[String], columns: &[String]) -> Result<(), String> {
+    let missing = columns
+        .iter()
+        .filter(|name| !names.contains(name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let unexpected = names
+        .iter()
+        .filter(|name| !columns.contains(name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() || !unexpected.is_empty() || names.len() != columns.len() {
+        return Err(format!(
+            "The columns in the file do not match the PIL. Missing columns: [{}], unexpected columns: [{}].",
+            missing.join(", "),
+            unexpected.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the values of `columns` written by [write_raw_polys_file].
+fn read_raw_polys_file<T: FieldElement>(
+    file: &mut impl Read,
+    columns: &[String],
 ) -> (Vec<(String, Vec<T>)>, DegreeType) {
-    let width = ceil_div(T::BITS as usize, 64) * 8;
+    let width = value_width::<T>();
 
     let bytes_to_read = width * columns.len();
 
@@ -114,6 +272,9 @@ pub fn read_polys_file<T: FieldElement>(
         .map(|name| (name.to_string(), vec![]))
         .collect();
     let mut degree = 0;
+    if columns.is_empty() {
+        return (result, degree);
+    }
 
     loop {
         let mut buf = vec![0u8; bytes_to_read];
@@ -156,11 +317,70 @@ mod tests {
         let (polys, degree) = test_polys();
 
         write_polys_file(&mut buf, &polys);
+        assert!(buf.starts_with(POLYS_FILE_MAGIC));
         let (read_polys, read_degree) = read_polys_file::<Bn254Field>(
-            &mut Cursor::new(buf),
+            &mut Cursor::new(&buf),
             &["a".to_string(), "b".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(read_polys, polys);
+        assert_eq!(read_degree, degree);
+
+        // The columns are returned in the requested order.
+        let (read_polys, _) = read_polys_file::<Bn254Field>(
+            &mut Cursor::new(&buf),
+            &["b".to_string(), "a".to_string()],
+        )
+        .unwrap();
+        assert_eq!(read_polys, vec![polys[1].clone(), polys[0].clone()]);
+
+        let mut buf: Vec<u8> = vec![];
+        write_polys_file::<Bn254Field>(&mut buf, &[]);
+        assert_eq!(
+            read_polys_file::<Bn254Field>(&mut Cursor::new(buf), &[]).unwrap(),
+            (vec![], 0)
+        );
+    }
+
+    #[test]
+    fn column_mismatch() {
+        let mut buf: Vec<u8> = vec![];
+        write_polys_file(&mut buf, &test_polys().0);
+
+        // The column `b` was renamed to `c`.
+        let err = read_polys_file::<Bn254Field>(
+            &mut Cursor::new(&buf),
+            &["a".to_string(), "c".to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "The columns in the file do not match the PIL. Missing columns: [c], unexpected columns: [b]."
         );
+        let err =
+            read_polys_file::<Bn254Field>(&mut Cursor::new(&buf), &["a".to_string()]).unwrap_err();
+        assert!(err.contains("unexpected columns: [b]"), "{err}");
+
+        // A truncated file is detected.
+        let err = read_polys_file::<Bn254Field>(
+            &mut Cursor::new(&buf[..buf.len() - 1]),
+            &["a".to_string(), "b".to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(err, "The file contains 15 rows, but the degree is 16.");
+    }
 
+    #[test]
+    fn read_legacy() {
+        let mut buf: Vec<u8> = vec![];
+        let (polys, degree) = test_polys();
+        write_raw_polys_file(&mut buf, &polys);
+        let (read_polys, read_degree) = read_polys_file::<Bn254Field>(
+            &mut Cursor::new(buf),
+            &["a".to_string(), "b".to_string()],
+        )
+        .unwrap();
         assert_eq!(read_polys, polys);
         assert_eq!(read_degree, degree);
     }
@@ -179,10 +399,27 @@ mod tests {
             CsvRenderMode::Hex,
         ] {
             let mut buf: Vec<u8> = vec![];
-            write_polys_csv_file(&mut buf, *render_mode, &polys);
+            write_polys_csv_file(&mut buf, *render_mode, &polys).unwrap();
             let read_polys = read_polys_csv_file::<Bn254Field>(&mut Cursor::new(buf));
 
             assert_eq!(read_polys, polys);
         }
     }
+
+    #[test]
+    fn csv_format() {
+        let polys = vec![
+            ("a".to_string(), vec![Bn254Field::from(0), 10.into()]),
+            ("b".to_string(), vec![Bn254Field::from(-1), 255.into()]),
+        ];
+        let mut buf: Vec<u8> = vec![];
+        write_polys_csv_file(&mut buf, CsvRenderMode::SignedBase10, &polys).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Row,a,b\n0,0,-1\n1,10,255\n"
+        );
+        let mut buf: Vec<u8> = vec![];
+        write_polys_csv_file(&mut buf, CsvRenderMode::Hex, &polys[..1]).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "Row,a\n0,0x0\n1,0xa\n");
+    }
 }
@@ -0,0 +1,256 @@
+//! Test vectors for field arithmetic and serialization.
+//!
+//! External implementations (e.g. verifiers written in other languages) can vendor
+//! the generated file to check that they agree with powdr on edge values.
+//! Generation is deterministic, so the checked-in copy can be compared against a
+//! freshly generated one.
+//!
+//! The format (version [TEST_VECTORS_VERSION]) is a JSON object with the keys
+//! - `version`: the format version, currently `1`.
+//! - `fields`: a list of objects, one per field, with the keys
+//!   - `name`: `goldilocks` or `bn254`.
+//!   - `modulus`: the field modulus as a `0x`-prefixed lowercase hex string.
+//!   - `byte_width`: the number of bytes of a serialized element.
+//!   - `values`: a list of objects describing single elements:
+//!     - `value`: the element in canonical form, as a `0x`-prefixed hex string.
+//!     - `bytes_le`: the serialized element (`to_bytes_le`), as a hex string of
+//!       `byte_width` bytes. This is the canonical form, *not* the Montgomery form.
+//!     - `montgomery_bytes_le`: the Montgomery form `value * 2^(8 * byte_width) mod p`,
+//!       in the same encoding. This is what an implementation that serializes its internal
+//!       representation would wrongly produce; it must never equal `bytes_le` for nonzero values.
+//!     - `neg`: the additive inverse, as a hex string.
+//!     - `csv_signed`, `csv_unsigned`, `csv_hex`: the CSV renderings of the element
+//!       (see [crate::CsvRenderMode]).
+//!   - `arithmetic`: a list of objects with the hex strings `a`, `b`, `add`, `sub`, `mul`
+//!     and `div` (the field division `a / b`, `null` if `b` is zero).
+//!
+//! All keys of an object are sorted alphabetically.
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+use serde_json::{json, Value};
+
+use crate::serialize::{parse_csv_value, render_csv_value};
+use crate::{BigInt, Bn254Field, CsvRenderMode, FieldElement, GoldilocksField};
+
+/// The version of the test vector format. Increment on any incompatible change.
+pub const TEST_VECTORS_VERSION: u64 = 1;
+
+/// Generates the test vectors for all supported fields as a JSON string.
+pub fn generate_test_vectors() -> String {
+    let vectors = json!({
+        "version": TEST_VECTORS_VERSION,
+        "fields": [
+            field_vectors::<GoldilocksField>("goldilocks"),
+            field_vectors::<Bn254Field>("bn254"),
+        ],
+    });
+    let mut result = serde_json::to_string_pretty(&vectors).unwrap();
+    result.push('\n');
+    result
+}
+
+/// Checks that the given test vectors agree with the implementation in this crate.
+pub fn check_test_vectors(vectors: &str) -> Result<(), String> {
+    let vectors: Value = serde_json::from_str(vectors).map_err(|e| e.to_string())?;
+    let version = vectors["version"].as_u64();
+    if version != Some(TEST_VECTORS_VERSION) {
+        return Err(format!(
+            "Unsupported test vector version {version:?}, expected {TEST_VECTORS_VERSION}."
+        ));
+    }
+    let fields = vectors["fields"]
+        .as_array()
+        .ok_or("Expected a list of fields.")?;
+    for field in fields {
+        match field["name"].as_str() {
+            Some("goldilocks") => check_field_vectors::<GoldilocksField>(field)?,
+            Some("bn254") => check_field_vectors::<Bn254Field>(field)?,
+            name => return Err(format!("Unknown field: {name:?}")),
+        }
+    }
+    Ok(())
+}
+
+/// The edge values used in the test vectors.
+fn edge_values<T: FieldElement>() -> Vec<T> {
+    let modulus = T::modulus().to_arbitrary_integer();
+    let half = (&modulus - 1u32) / 2u32;
+    vec![
+        T::from(0u32),
+        T::from(1u32),
+        T::from(2u32),
+        T::from(1u64 << 31),
+        T::from(u32::MAX),
+        T::from(1u64 << 63),
+        T::from(BigUint::from(1u32) << (T::BITS - 1)),
+        T::from(half.clone()),
+        T::from(half + 1u32),
+        -T::from(2u32),
+        -T::from(1u32),
+    ]
+}
+
+/// The subset of edge values used for the arithmetic test vectors.
+fn arithmetic_values<T: FieldElement>() -> Vec<T> {
+    let modulus = T::modulus().to_arbitrary_integer();
+    vec![
+        T::from(0u32),
+        T::from(1u32),
+        T::from(2u32),
+        T::from(BigUint::from(1u32) << (T::BITS - 1)),
+        T::from((modulus - 1u32) / 2u32),
+        -T::from(2u32),
+        -T::from(1u32),
+    ]
+}
+
+fn byte_width<T: FieldElement>() -> usize {
+    T::from(0u32).to_bytes_le().len()
+}
+
+fn to_hex<T: FieldElement>(value: &T) -> String {
+    format!("0x{:x}", value.to_arbitrary_integer())
+}
+
+fn from_hex<T: FieldElement>(value: &Value) -> Result<T, String> {
+    let value = value
+        .as_str()
+        .and_then(|v| v.strip_prefix("0x"))
+        .ok_or_else(|| format!("Expected a hex string, got {value}"))?;
+    T::from_str_radix(value, 16)
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn montgomery_bytes_le<T: FieldElement>(value: &T) -> Vec<u8> {
+    let width = byte_width::<T>();
+    let modulus = T::modulus().to_arbitrary_integer();
+    let montgomery = (value.to_arbitrary_integer() << (8 * width)) % modulus;
+    let mut bytes = montgomery.to_bytes_le();
+    bytes.resize(width, 0);
+    bytes
+}
+
+fn value_vectors<T: FieldElement>(value: &T) -> Value {
+    json!({
+        "value": to_hex(value),
+        "bytes_le": bytes_to_hex(&value.to_bytes_le()),
+        "montgomery_bytes_le": bytes_to_hex(&montgomery_bytes_le(value)),
+        "neg": to_hex(&-*value),
+        "csv_signed": render_csv_value(value, CsvRenderMode::SignedBase10),
+        "csv_unsigned": render_csv_value(value, CsvRenderMode::UnsignedBase10),
+        "csv_hex": render_csv_value(value, CsvRenderMode::Hex),
+    })
+}
+
+fn arithmetic_vectors<T: FieldElement>(a: &T, b: &T) -> Value {
+    json!({
+        "a": to_hex(a),
+        "b": to_hex(b),
+        "add": to_hex(&(*a + *b)),
+        "sub": to_hex(&(*a - *b)),
+        "mul": to_hex(&(*a * *b)),
+        "div": (!b.is_zero()).then(|| to_hex(&(*a / *b))),
+    })
+}
+
+fn field_vectors<T: FieldElement>(name: &str) -> Value {
+    let arithmetic_values = arithmetic_values::<T>();
+    json!({
+        "name": name,
+        "modulus": format!("0x{:x}", T::modulus()),
+        "byte_width": byte_width::<T>(),
+        "values": edge_values::<T>().iter().map(value_vectors).collect::<Vec<_>>(),
+        "arithmetic": arithmetic_values
+            .iter()
+            .flat_map(|a| arithmetic_values.iter().map(move |b| arithmetic_vectors(a, b)))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn check_field_vectors<T: FieldElement>(field: &Value) -> Result<(), String> {
+    let name = field["name"].as_str().unwrap_or_default();
+    let expect = |what: &str, expected: &Value, actual: Value| {
+        if *expected == actual {
+            Ok(())
+        } else {
+            Err(format!(
+                "{name}: mismatch in {what}: expected {expected}, got {actual}"
+            ))
+        }
+    };
+    expect(
+        "modulus",
+        &field["modulus"],
+        json!(format!("0x{:x}", T::modulus())),
+    )?;
+    expect("byte_width", &field["byte_width"], json!(byte_width::<T>()))?;
+
+    for vector in field["values"]
+        .as_array()
+        .ok_or("Expected a list of values.")?
+    {
+        let value: T = from_hex(&vector["value"])?;
+        expect(
+            &format!("values for {}", vector["value"]),
+            vector,
+            value_vectors(&value),
+        )?;
+        if T::from_bytes_le(&value.to_bytes_le()) != value {
+            return Err(format!("{name}: bytes round trip failed for {value}"));
+        }
+        for key in ["csv_signed", "csv_unsigned", "csv_hex"] {
+            let rendered = vector[key].as_str().unwrap_or_default();
+            if parse_csv_value::<T>(rendered) != value {
+                return Err(format!("{name}: CSV round trip failed for {rendered}"));
+            }
+        }
+    }
+
+    for vector in field["arithmetic"]
+        .as_array()
+        .ok_or("Expected a list of arithmetic vectors.")?
+    {
+        let a: T = from_hex(&vector["a"])?;
+        let b: T = from_hex(&vector["b"])?;
+        expect(
+            &format!("arithmetic for {} and {}", vector["a"], vector["b"]),
+            vector,
+            arithmetic_vectors(&a, &b),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_log::test;
+
+    fn checked_in_vectors() -> String {
+        std::fs::read_to_string(format!(
+            "{}/../test_data/number/field_test_vectors.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn checked_in_vectors_are_up_to_date() {
+        assert_eq!(generate_test_vectors(), checked_in_vectors());
+    }
+
+    #[test]
+    fn checked_in_vectors_are_valid() {
+        check_test_vectors(&checked_in_vectors()).unwrap();
+    }
+
+    #[test]
+    fn detects_mismatch() {
+        let vectors = checked_in_vectors().replacen("\"0x0\"", "\"0x1\"", 1);
+        assert!(check_test_vectors(&vectors).is_err());
+    }
+}
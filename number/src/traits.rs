@@ -1,6 +1,7 @@
 use std::{fmt, hash::Hash, ops::*};
 
 use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
 
 use crate::{AbstractNumberType, DegreeType};
 
@@ -84,6 +85,8 @@ pub trait FieldElement:
     + From<i64>
     + From<bool>
     + fmt::LowerHex
+    + Serialize
+    + for<'de> Deserialize<'de>
 {
     /// The underlying fixed-width integer type
     type Integer: BigInt;
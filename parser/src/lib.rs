@@ -3,6 +3,7 @@
 #![deny(clippy::print_stdout)]
 
 use ast::parsed::asm::ASMProgram;
+use ast::source_map::SourceMap;
 use lalrpop_util::*;
 
 use number::FieldElement;
@@ -14,28 +15,37 @@ lalrpop_mod!(
     "/powdr.rs"
 );
 
+/// Parses a PIL file. Number literals that do not fit into the field are an error.
 pub fn parse<'a, T: FieldElement>(
     file_name: Option<&str>,
     input: &'a str,
 ) -> Result<ast::parsed::PILFile<T>, ParseError<'a>> {
     powdr::PILFileParser::new()
-        .parse(input)
+        .parse(false, input)
         .map_err(|err| handle_parse_error(err, file_name, input))
 }
 
+/// Parses the main file of an assembly program. The source positions are global
+/// positions in the [SourceMap] of the program, which starts with this file.
 pub fn parse_asm<'a, T: FieldElement>(
     file_name: Option<&str>,
     input: &'a str,
 ) -> Result<ast::parsed::asm::ASMProgram<T>, ParseError<'a>> {
-    parse_module(file_name, input).map(|main| ASMProgram { main })
+    parse_module(file_name, input).map(|mut main| {
+        let mut sources = SourceMap::default();
+        sources.add_module(file_name.unwrap_or("input"), input, &mut main);
+        ASMProgram { main, sources }
+    })
 }
 
+/// Parses an assembly module. Number literals are reduced modulo the field, since
+/// modules (like the standard library) can contain machines for several fields.
 pub fn parse_module<'a, T: FieldElement>(
     file_name: Option<&str>,
     input: &'a str,
 ) -> Result<ast::parsed::asm::ASMModule<T>, ParseError<'a>> {
     powdr::ASMModuleParser::new()
-        .parse(input)
+        .parse(true, input)
         .map_err(|err| handle_parse_error(err, file_name, input))
 }
 
@@ -53,38 +63,56 @@ mod test {
     #[test]
     fn empty() {
         assert!(powdr::PILFileParser::new()
-            .parse::<GoldilocksField>("")
+            .parse::<GoldilocksField>(false, "")
             .is_ok());
     }
 
     #[test]
     fn simple_include() {
         let parsed = powdr::PILFileParser::new()
-            .parse::<GoldilocksField>("include \"x\";")
+            .parse::<GoldilocksField>(false, "include \"x\";")
             .unwrap();
         assert_eq!(
             parsed,
-            PILFile(vec![PilStatement::Include(0, "x".to_string())])
+            PILFile(vec![PilStatement::Include(0, "x".to_string(), None)])
         );
     }
 
+    #[test]
+    fn include_as() {
+        let parsed = powdr::PILFileParser::new()
+            .parse::<GoldilocksField>(false, "include \"x\" as First;")
+            .unwrap();
+        assert_eq!(
+            parsed,
+            PILFile(vec![PilStatement::Include(
+                0,
+                "x".to_string(),
+                Some("First".to_string())
+            )])
+        );
+        assert_eq!(parsed.to_string(), "include \"x\" as First;\n");
+    }
+
     #[test]
     fn start_offsets() {
         let parsed = powdr::PILFileParser::new()
-            .parse::<GoldilocksField>("include \"x\"; pol commit t;")
+            .parse::<GoldilocksField>(false, "include \"x\"; pol commit t;")
             .unwrap();
         assert_eq!(
             parsed,
             PILFile(vec![
-                PilStatement::Include(0, "x".to_string()),
+                PilStatement::Include(0, "x".to_string(), None),
                 PilStatement::PolynomialCommitDeclaration(
                     13,
+                    None,
                     vec![PolynomialName {
                         name: "t".to_string(),
                         array_size: None
                     }],
                     None,
-                    false
+                    None,
+                    None
                 )
             ])
         );
@@ -93,7 +121,7 @@ mod test {
     #[test]
     fn simple_plookup() {
         let parsed = powdr::PILFileParser::new()
-            .parse::<GoldilocksField>("f in g;")
+            .parse::<GoldilocksField>(false, "f in g;")
             .unwrap();
         assert_eq!(
             parsed,
@@ -116,6 +144,7 @@ mod test {
     fn parse_permutation_attribute() {
         let parsed = powdr::PILFileParser::new()
             .parse::<GoldilocksField>(
+                false,
                 "
             #[attribute]
             { f } is { g };",
@@ -183,6 +212,80 @@ mod test {
         parse_asm_file("asm/simple_sum.asm");
     }
 
+    #[test]
+    fn number_too_large_for_field() {
+        let input = "namespace N(4);\n    col fixed X = [1, 0xffffffff00000001, 2]*;\n";
+        let err = parse::<GoldilocksField>(Some("input"), input).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Number 18446744069414584321 does not fit into the field, its modulus is 18446744069414584321."
+        );
+        assert_eq!(err.start(), input.find("0xffff").unwrap());
+        // The largest element is fine, and so is the number in a larger field.
+        assert!(parse::<GoldilocksField>(None, &input.replace("01,", "00,")).is_ok());
+        assert!(parse::<number::Bn254Field>(None, input).is_ok());
+    }
+
+    #[test]
+    fn number_literals() {
+        let input = r#"
+namespace N(0x10);
+    col fixed X = [0x10, 0b1010, 1_000, 0xffff_ffff]*;
+    col witness x, y;
+    (x & 0xFF_FF) = y;
+    x * 0b1_0000_0000 = 2**0x8;
+"#;
+        let printed = parse::<GoldilocksField>(None, input).unwrap().to_string();
+        assert_eq!(
+            printed,
+            "namespace N(16);\npol constant X = [16, 10, 1000, 4294967295]*;\npol commit x, y;\n(x & 65535) = y;\n(x * 256) = (2 ** 8);\n"
+        );
+        // The decimal output parses to the same result.
+        assert_eq!(
+            parse::<GoldilocksField>(None, &printed)
+                .unwrap()
+                .to_string(),
+            printed
+        );
+    }
+
+    #[test]
+    fn binary_literal_too_large_for_field() {
+        let input = format!(
+            "namespace N(4);\n    col witness x;\n    x = 0b{};\n",
+            "1".repeat(64)
+        );
+        let err = parse::<GoldilocksField>(Some("input"), &input).unwrap_err();
+        assert!(err
+            .message()
+            .starts_with("Number 18446744073709551615 does not fit into the field"));
+        assert_eq!(err.start(), input.find("0b").unwrap());
+    }
+
+    #[test]
+    fn asm_number_literals() {
+        let input = r#"
+machine Main {
+    degree 0x100;
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+    instr set_masked X { A' = X & 0b1111_0000 }
+    function main {
+        A <=X= 0b1010 + 1_000;
+        set_masked 0xff;
+    }
+}
+"#;
+        let printed = parse_asm::<GoldilocksField>(None, input)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            printed,
+            "machine Main {\n\tdegree 256;\n\treg pc[@pc];\n\treg X[<=];\n\treg A;\n\tinstr set_masked X { A' = (X & 240) }\n\tfunction main {\n\tA <=X= (10 + 1000);\n\tset_masked 255;\n\t}\n}\n"
+        );
+    }
+
     mod display {
         use number::GoldilocksField;
 
@@ -243,6 +346,16 @@ public out = y(%last_row);"#;
             assert_eq!(input.trim(), printed.trim());
         }
 
+        #[test]
+        fn reparse_two_dimensional_table() {
+            let input = r#"pol constant AND(a, b) -> c { (a & b) } over (256, 256);"#;
+            let printed = format!(
+                "{}",
+                parse::<GoldilocksField>(Some("input"), input).unwrap()
+            );
+            assert_eq!(input.trim(), printed.trim());
+        }
+
         #[test]
         fn array_literals() {
             let input = r#"let x = [[1], [2], [(3 + 7)]];"#;
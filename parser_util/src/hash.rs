@@ -0,0 +1,22 @@
+//! Content hashes of generated files and their inputs.
+
+use sha2::{Digest, Sha256};
+
+/// The SHA-256 hash of `contents` as lowercase hex string, the form in which
+/// hashes are written to provenance headers, manifests and metadata files.
+pub fn sha256_hex(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -2,7 +2,13 @@
 
 #![deny(clippy::print_stdout)]
 
+pub mod hash;
 pub mod lines;
+pub mod paths;
+pub mod provenance;
+pub mod std_library;
+
+use std::fmt::Display;
 
 #[derive(Debug)]
 pub struct ParseError<'a> {
@@ -14,6 +20,15 @@ pub struct ParseError<'a> {
 }
 
 impl<'a> ParseError<'a> {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The byte offset in the input the error starts at.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
     pub fn output_to_stderr(&self) {
         use codespan_reporting::diagnostic::{Diagnostic, Label};
         use codespan_reporting::files::SimpleFiles;
@@ -31,25 +46,67 @@ impl<'a> ParseError<'a> {
     }
 }
 
-pub fn handle_parse_error<'a>(
-    err: lalrpop_util::ParseError<usize, lalrpop_util::lexer::Token, &str>,
+/// An error raised by an action of the grammar, e.g. for a number that does not fit
+/// into the field, with the location of the offending input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserError {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+impl UserError {
+    pub fn new(start: usize, end: usize, message: impl Into<String>) -> Self {
+        UserError {
+            start,
+            end,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The location in the input a user error of a grammar refers to, if any.
+pub trait UserErrorLocation {
+    fn location(&self) -> Option<(usize, usize)>;
+}
+
+impl UserErrorLocation for &str {
+    fn location(&self) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+impl UserErrorLocation for UserError {
+    fn location(&self) -> Option<(usize, usize)> {
+        Some((self.start, self.end))
+    }
+}
+
+pub fn handle_parse_error<'a, E: Display + UserErrorLocation>(
+    err: lalrpop_util::ParseError<usize, lalrpop_util::lexer::Token, E>,
     file_name: Option<&str>,
     input: &'a str,
 ) -> ParseError<'a> {
-    let (&start, &end) = match &err {
-        lalrpop_util::ParseError::InvalidToken { location } => (location, location),
+    let (start, end) = match &err {
+        lalrpop_util::ParseError::InvalidToken { location } => (*location, *location),
         lalrpop_util::ParseError::UnrecognizedEOF {
             location,
             expected: _,
-        } => (location, location),
+        } => (*location, *location),
         lalrpop_util::ParseError::UnrecognizedToken {
             token: (start, _, end),
             expected: _,
-        } => (start, end),
+        } => (*start, *end),
         lalrpop_util::ParseError::ExtraToken {
             token: (start, _, end),
-        } => (start, end),
-        lalrpop_util::ParseError::User { error: _ } => (&0, &0),
+        } => (*start, *end),
+        lalrpop_util::ParseError::User { error } => error.location().unwrap_or_default(),
     };
     ParseError {
         start,
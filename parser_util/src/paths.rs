@@ -0,0 +1,121 @@
+//! Platform-independent helpers for the names and paths of generated files.
+
+use std::path::{Path, PathBuf};
+
+/// How file names are compared when checking whether a generated file already exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseSensitivity {
+    Sensitive,
+    Insensitive,
+}
+
+impl CaseSensitivity {
+    /// The case sensitivity of the file system on the current platform.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            CaseSensitivity::Insensitive
+        } else {
+            CaseSensitivity::Sensitive
+        }
+    }
+}
+
+/// Returns the file stem of `path` as a string, replacing non-UTF8 characters.
+///
+/// # Panics
+/// Panics if the path has no file name.
+pub fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_else(|| panic!("Path {} has no file name.", path.display()))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Returns the existing file that writing to `path` would overwrite, if any.
+/// With [CaseSensitivity::Insensitive], `Foo.pil` conflicts with an existing `foo.pil`.
+pub fn existing_file(path: &Path, case_sensitivity: CaseSensitivity) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    parent
+        .read_dir()
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            match case_sensitivity {
+                CaseSensitivity::Sensitive => name == file_name,
+                CaseSensitivity::Insensitive => name.to_lowercase() == file_name.to_lowercase(),
+            }
+        })
+        .map(|entry| parent.join(entry.file_name()))
+}
+
+/// Returns the existing file that writing to `path` would overwrite, if any,
+/// using the case sensitivity of the current platform.
+pub fn existing_target_file(path: &Path) -> Option<PathBuf> {
+    existing_file(path, CaseSensitivity::native())
+}
+
+/// Converts a relative path using `/` as separator into a path using the separator
+/// of the current platform.
+pub fn platform_path(path: &str) -> PathBuf {
+    path.split('/').filter(|c| !c.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use test_log::test;
+
+    #[test]
+    fn existing_file_case_sensitivity() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        fs::write(dir.join("Foo.pil"), "").unwrap();
+
+        assert_eq!(
+            existing_file(&dir.join("Foo.pil"), CaseSensitivity::Sensitive),
+            Some(dir.join("Foo.pil"))
+        );
+        assert_eq!(
+            existing_file(&dir.join("foo.pil"), CaseSensitivity::Sensitive),
+            None
+        );
+        assert_eq!(
+            existing_file(&dir.join("foo.pil"), CaseSensitivity::Insensitive),
+            Some(dir.join("Foo.pil"))
+        );
+        assert_eq!(
+            existing_file(&dir.join("bar.pil"), CaseSensitivity::Insensitive),
+            None
+        );
+        assert_eq!(
+            existing_file(
+                &dir.join("missing_dir").join("foo.pil"),
+                CaseSensitivity::Insensitive
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn file_stems() {
+        assert_eq!(file_stem(Path::new("dir/sub/file.asm")), "file");
+        assert_eq!(file_stem(Path::new("file")), "file");
+    }
+
+    #[test]
+    fn platform_paths() {
+        assert_eq!(
+            platform_path("src/barretenberg/"),
+            Path::new("src").join("barretenberg")
+        );
+        assert_eq!(
+            platform_path("vm/generated").join("file.hpp"),
+            Path::new("vm").join("generated").join("file.hpp")
+        );
+    }
+}
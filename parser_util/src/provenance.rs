@@ -0,0 +1,248 @@
+//! Provenance headers of generated powdr-asm and PIL files.
+//!
+//! Generated files start with a block of line comments that records how they were
+//! produced, so that the information survives copying the file around:
+//!
+//! ```text
+//! // powdr provenance
+//! // version: 0.1.0
+//! // input: guest/src/lib.rs sha256:9f86d08...
+//! // coprocessors: binary, shift
+//! // generated: 2026-10-17T09:30:00Z
+//! // end powdr provenance
+//! ```
+//!
+//! Since the block only consists of comments, parsers and analyzers ignore it.
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::hash::sha256_hex;
+
+/// The version of powdr that generated a file.
+pub const POWDR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const BEGIN: &str = "// powdr provenance";
+const END: &str = "// end powdr provenance";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    pub version: String,
+    /// The names of the input files and the SHA-256 hashes (hex) of their contents.
+    /// If an input was generated by powdr itself, its own inputs follow it.
+    pub inputs: Vec<(String, String)>,
+    /// The coprocessors of the RISC-V machine, if the file was generated from RISC-V code.
+    pub coprocessors: Option<Vec<String>>,
+    /// The generation time in UTC, in RFC 3339 format.
+    pub generated: String,
+}
+
+impl Provenance {
+    /// The provenance of a file that is generated now from the given input files,
+    /// given by their names and contents.
+    pub fn new<'a>(inputs: impl IntoIterator<Item = (String, &'a [u8])>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        Provenance {
+            version: POWDR_VERSION.to_string(),
+            inputs: inputs
+                .into_iter()
+                .map(|(name, contents)| (name, sha256_hex(contents)))
+                .collect(),
+            coprocessors: None,
+            generated: format_utc(now),
+        }
+    }
+
+    /// The provenance of a file that is generated now from the file at `path`, or `None`
+    /// if `path` is not a file (e.g. because the input was given as a string).
+    /// If the input has a provenance header itself, its inputs and coprocessors are kept.
+    pub fn for_input_file(path: &Path) -> Option<Self> {
+        let contents = fs::read(path).ok()?;
+        let mut provenance = Self::new([(path.display().to_string(), contents.as_slice())]);
+        if let Some(Ok(input_provenance)) = Provenance::parse(&String::from_utf8_lossy(&contents)) {
+            provenance.inputs.extend(input_provenance.inputs);
+            provenance.coprocessors = input_provenance.coprocessors;
+        }
+        Some(provenance)
+    }
+
+    /// Like [Provenance::for_input_file], but only for inputs that were generated by powdr
+    /// with a provenance header. Generated files without a header stem from strings.
+    pub fn for_generated_input_file(path: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        split_header(&contents).0?;
+        Self::for_input_file(path)
+    }
+
+    pub fn with_coprocessors(self, coprocessors: Vec<String>) -> Self {
+        Provenance {
+            coprocessors: Some(coprocessors),
+            ..self
+        }
+    }
+
+    /// Renders the header block, including the final newline.
+    pub fn render(&self) -> String {
+        let mut lines = vec![BEGIN.to_string(), format!("// version: {}", self.version)];
+        lines.extend(
+            self.inputs
+                .iter()
+                .map(|(name, hash)| format!("// input: {name} sha256:{hash}")),
+        );
+        if let Some(coprocessors) = &self.coprocessors {
+            lines.push(format!("// coprocessors: {}", coprocessors.join(", ")));
+        }
+        lines.push(format!("// generated: {}", self.generated));
+        lines.push(END.to_string());
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// Returns `contents` with the header block in front of it.
+    pub fn prepend_to(&self, contents: &str) -> String {
+        format!("{}{contents}", self.render())
+    }
+
+    /// Reads the header block at the start of `contents`. Returns `None` if there is none.
+    pub fn parse(contents: &str) -> Option<Result<Self, String>> {
+        let header = split_header(contents).0?;
+        let mut version = None;
+        let mut inputs = vec![];
+        let mut coprocessors = None;
+        let mut generated = None;
+        for line in header.lines().skip(1) {
+            if line == END {
+                break;
+            }
+            let Some((key, value)) = line
+                .strip_prefix("// ")
+                .and_then(|line| line.split_once(": "))
+            else {
+                return Some(Err(format!("Invalid line in provenance header: {line}")));
+            };
+            match key {
+                "version" => version = Some(value.to_string()),
+                "input" => {
+                    let Some((name, hash)) = value.rsplit_once(" sha256:") else {
+                        return Some(Err(format!("Invalid input in provenance header: {value}")));
+                    };
+                    inputs.push((name.to_string(), hash.to_string()));
+                }
+                "coprocessors" => {
+                    coprocessors = Some(
+                        value
+                            .split(", ")
+                            .filter(|c| !c.is_empty())
+                            .map(|c| c.to_string())
+                            .collect(),
+                    )
+                }
+                "generated" => generated = Some(value.to_string()),
+                // Ignore unknown keys, they may have been added by later versions.
+                _ => {}
+            }
+        }
+        let (Some(version), Some(generated)) = (version, generated) else {
+            return Some(Err(
+                "The provenance header has no version or generation time.".to_string(),
+            ));
+        };
+        Some(Ok(Provenance {
+            version,
+            inputs,
+            coprocessors,
+            generated,
+        }))
+    }
+}
+
+/// Splits `contents` into the provenance header block (verbatim, including the newline
+/// after it), if present, and the rest.
+pub fn split_header(contents: &str) -> (Option<&str>, &str) {
+    if !contents.starts_with(&format!("{BEGIN}\n")) {
+        return (None, contents);
+    }
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == END {
+            return (Some(&contents[..offset]), &contents[offset..]);
+        }
+    }
+    // Without the end marker, it is just a comment.
+    (None, contents)
+}
+
+/// Formats seconds since the Unix epoch as UTC date and time in RFC 3339 format.
+fn format_utc(seconds: u64) -> String {
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // Converts days since the epoch to a civil date,
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_log::test;
+
+    fn provenance() -> Provenance {
+        Provenance::new([("guest.rs".to_string(), b"fn main() {}".as_slice())])
+            .with_coprocessors(vec!["binary".to_string(), "shift".to_string()])
+    }
+
+    #[test]
+    fn round_trip() {
+        let provenance = provenance();
+        let contents = provenance.prepend_to("machine Main { }\n");
+        assert!(contents.starts_with("// powdr provenance\n// version: "));
+        assert_eq!(Provenance::parse(&contents), Some(Ok(provenance.clone())));
+        assert_eq!(
+            split_header(&contents),
+            (Some(provenance.render().as_str()), "machine Main { }\n")
+        );
+        assert_eq!(provenance.inputs[0].0, "guest.rs");
+    }
+
+    #[test]
+    fn no_header() {
+        let contents = "// some comment\nnamespace N(8);\n";
+        assert_eq!(Provenance::parse(contents), None);
+        assert_eq!(split_header(contents), (None, contents));
+        // An unterminated block is not a header.
+        let contents = "// powdr provenance\n// version: 0.1.0\nnamespace N(8);\n";
+        assert_eq!(split_header(contents), (None, contents));
+    }
+
+    #[test]
+    fn hash() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1700000000), "2023-11-14T22:13:20Z");
+    }
+}
@@ -0,0 +1,169 @@
+//! The powdr standard library. It is embedded into the binary, so that it does not
+//! have to be present on disk, but it can be replaced by a directory.
+
+use std::path::{Path, PathBuf};
+
+/// The prefix with which PIL files include files of the standard library,
+/// e.g. `include "std::range_check.pil";`.
+pub const STD_PREFIX: &str = "std::";
+
+/// The environment variable that can be set to a directory to load the standard
+/// library from instead of the embedded one.
+pub const POWDR_STD_ENV: &str = "POWDR_STD";
+
+/// The files of the standard library, by their path relative to its root directory.
+static EMBEDDED_FILES: &[(&str, &str)] = &[
+    ("mod.asm", include_str!("../../std/mod.asm")),
+    ("arith.asm", include_str!("../../std/arith.asm")),
+    ("binary.asm", include_str!("../../std/binary.asm")),
+    ("hash/mod.asm", include_str!("../../std/hash/mod.asm")),
+    (
+        "hash/keccakf.asm",
+        include_str!("../../std/hash/keccakf.asm"),
+    ),
+    (
+        "hash/poseidon_bn254.asm",
+        include_str!("../../std/hash/poseidon_bn254.asm"),
+    ),
+    (
+        "hash/poseidon_gl.asm",
+        include_str!("../../std/hash/poseidon_gl.asm"),
+    ),
+    ("range_check.asm", include_str!("../../std/range_check.asm")),
+    ("range_check.pil", include_str!("../../std/range_check.pil")),
+    ("shift.asm", include_str!("../../std/shift.asm")),
+    ("split/mod.asm", include_str!("../../std/split/mod.asm")),
+    (
+        "split/split_bn254.asm",
+        include_str!("../../std/split/split_bn254.asm"),
+    ),
+    (
+        "split/split_gl.asm",
+        include_str!("../../std/split/split_gl.asm"),
+    ),
+];
+
+/// Where the files of the standard library are loaded from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum StdLibrary {
+    /// The files embedded into the binary.
+    #[default]
+    Embedded,
+    /// The files in the given directory.
+    Directory(PathBuf),
+}
+
+impl StdLibrary {
+    /// The directory in the [POWDR_STD_ENV] environment variable if it is set,
+    /// otherwise the embedded standard library.
+    pub fn from_env() -> Self {
+        std::env::var(POWDR_STD_ENV)
+            .map(|dir| StdLibrary::Directory(dir.into()))
+            .unwrap_or_default()
+    }
+
+    /// Reads the file at `path`, which is relative to the root of the standard library.
+    pub fn read(&self, path: &Path) -> Result<String, String> {
+        match self {
+            StdLibrary::Embedded => {
+                let path = normalize(path);
+                EMBEDDED_FILES
+                    .iter()
+                    .find(|(name, _)| *name == path)
+                    .map(|(_, contents)| contents.to_string())
+                    .ok_or_else(|| format!("The standard library has no file `{path}`."))
+            }
+            StdLibrary::Directory(dir) => {
+                let file = dir.join(path);
+                std::fs::read_to_string(&file)
+                    .map_err(|e| format!("Could not read {}: {e}", file.display()))
+            }
+        }
+    }
+
+    /// The name of the file at `path` in error messages and source references.
+    pub fn display_path(&self, path: &Path) -> String {
+        match self {
+            StdLibrary::Embedded => format!("{STD_PREFIX}{}", normalize(path)),
+            StdLibrary::Directory(dir) => dir.join(path).display().to_string(),
+        }
+    }
+}
+
+/// If `include` refers to a file of the standard library, returns its path relative
+/// to the root of the standard library, e.g. `hash/table.pil` for `std::hash::table.pil`.
+pub fn std_include_path(include: &str) -> Option<PathBuf> {
+    include
+        .strip_prefix(STD_PREFIX)
+        .map(|path| path.split("::").collect())
+}
+
+fn normalize(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn files_in(root: &Path, dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flat_map(|entry| {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    files_in(root, &path)
+                } else {
+                    vec![normalize(path.strip_prefix(root).unwrap())]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn all_files_are_embedded() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../std");
+        let mut files = files_in(&root, &root);
+        files.sort();
+        let mut embedded = EMBEDDED_FILES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        embedded.sort();
+        assert_eq!(files, embedded);
+    }
+
+    #[test]
+    fn read_embedded() {
+        let std = StdLibrary::Embedded;
+        assert!(std
+            .read(Path::new("hash/mod.asm"))
+            .unwrap()
+            .contains("mod keccakf;"));
+        assert_eq!(
+            std.read(Path::new("missing.pil")),
+            Err("The standard library has no file `missing.pil`.".to_string())
+        );
+        assert_eq!(
+            std.display_path(Path::new("hash/mod.asm")),
+            "std::hash/mod.asm"
+        );
+    }
+
+    #[test]
+    fn include_path() {
+        assert_eq!(
+            std_include_path("std::range_check.pil"),
+            Some(PathBuf::from("range_check.pil"))
+        );
+        assert_eq!(
+            std_include_path("std::hash::table.pil"),
+            Some(Path::new("hash").join("table.pil"))
+        );
+        assert_eq!(std_include_path("range_check.pil"), None);
+        assert_eq!(std_include_path("std/range_check.pil"), None);
+    }
+}
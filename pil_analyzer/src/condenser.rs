@@ -1,13 +1,17 @@
 //! Component that turns data from the PILAnalyzer into Analyzed,
 //! i.e. it turns more complex expressions in identities to simpler expressions.
 
-use std::{collections::HashMap, fmt::Display, rc::Rc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    rc::Rc,
+};
 
 use ast::{
     analyzed::{
         AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition,
         Identity, IdentityKind, PolynomialReference, PolynomialType, PublicDeclaration, Reference,
-        StatementIdentifier, Symbol, SymbolKind,
+        SourceRef, StatementIdentifier, Symbol, SymbolKind,
     },
     parsed::{visitor::ExpressionVisitable, BinaryOperator, SelectedExpressions, UnaryOperator},
 };
@@ -24,14 +28,15 @@ pub fn condense<T: FieldElement>(
     mut public_declarations: HashMap<String, PublicDeclaration>,
     identities: &[Identity<Expression<T>>],
     source_order: Vec<StatementIdentifier>,
-) -> Analyzed<T> {
+) -> Result<Analyzed<T>, Vec<String>> {
     let condenser = Condenser {
         symbols: definitions.clone(),
+        degree,
     };
 
     let mut condensed_identities = vec![];
     // Condense identities and update the source order.
-    let source_order = source_order
+    let source_order: Vec<_> = source_order
         .into_iter()
         .flat_map(|s| match s {
             StatementIdentifier::Identity(index) => {
@@ -60,7 +65,10 @@ pub fn condense<T: FieldElement>(
                 };
                 Some((
                     name.clone(),
-                    (symbol.clone(), condenser.condense_expression(e)),
+                    (
+                        symbol.clone(),
+                        condenser.condense_expression(e, &symbol.source),
+                    ),
                 ))
             } else {
                 None
@@ -68,6 +76,7 @@ pub fn condense<T: FieldElement>(
         })
         .collect();
     definitions.retain(|name, _| !intermediate_columns.contains_key(name));
+    let source_order = sort_intermediate_columns(source_order, &intermediate_columns)?;
 
     definitions.values_mut().for_each(|(_, definition)| {
         if let Some(def) = definition {
@@ -82,19 +91,135 @@ pub fn condense<T: FieldElement>(
     public_declarations
         .values_mut()
         .for_each(|public_decl| condenser.assign_id(&mut public_decl.polynomial));
-    Analyzed {
+    Ok(Analyzed {
         degree,
         definitions,
         public_declarations,
         intermediate_columns,
         identities: condensed_identities,
         source_order,
+    })
+}
+
+/// Reorders the intermediate columns in `source_order` such that each intermediate column
+/// comes after the intermediate columns it references. Independent intermediate columns
+/// stay in source order. Returns an error for each cycle between intermediate columns.
+fn sort_intermediate_columns<T>(
+    source_order: Vec<StatementIdentifier>,
+    intermediate_columns: &HashMap<String, (Symbol, AlgebraicExpression<T>)>,
+) -> Result<Vec<StatementIdentifier>, Vec<String>> {
+    let names = source_order
+        .iter()
+        .filter_map(|s| match s {
+            StatementIdentifier::Definition(name) => intermediate_columns
+                .get_key_value(name)
+                .map(|(name, _)| name.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    // The intermediate columns referenced by each intermediate column, by index.
+    let dependencies = names
+        .iter()
+        .map(|name| {
+            let mut dependencies = BTreeSet::new();
+            intermediate_columns[*name]
+                .1
+                .pre_visit_expressions(&mut |e| {
+                    if let AlgebraicExpression::Reference(r) = e {
+                        if r.poly_id.ptype == PolynomialType::Intermediate {
+                            dependencies.insert(index[r.name.as_str()]);
+                        }
+                    }
+                });
+            dependencies
+        })
+        .collect::<Vec<_>>();
+
+    let mut dependents = vec![vec![]; names.len()];
+    let mut missing = dependencies.iter().map(|d| d.len()).collect::<Vec<_>>();
+    for (i, dependencies) in dependencies.iter().enumerate() {
+        for &d in dependencies {
+            dependents[d].push(i);
+        }
+    }
+    // Always continue with the ready column that comes first in the source.
+    let mut ready = (0..names.len())
+        .filter(|&i| missing[i] == 0)
+        .collect::<BTreeSet<_>>();
+    let mut sorted = Vec::with_capacity(names.len());
+    while let Some(i) = ready.pop_first() {
+        sorted.push(i);
+        for &d in &dependents[i] {
+            missing[d] -= 1;
+            if missing[d] == 0 {
+                ready.insert(d);
+            }
+        }
+    }
+
+    if sorted.len() < names.len() {
+        let source = |i: usize| &intermediate_columns[names[i]].0.source;
+        return Err(find_cycles(&dependencies, &missing)
+            .into_iter()
+            .map(|cycle| {
+                format!(
+                    "Cyclic dependency between intermediate columns: {}",
+                    cycle
+                        .iter()
+                        .chain(cycle.first())
+                        .map(|&i| format!("{} ({})", names[i], location(source(i))))
+                        .join(" -> ")
+                )
+            })
+            .collect());
+    }
+
+    let mut sorted = sorted.into_iter().map(|i| names[i].to_string());
+    Ok(source_order
+        .into_iter()
+        .map(|s| match s {
+            StatementIdentifier::Definition(name) if intermediate_columns.contains_key(&name) => {
+                StatementIdentifier::Definition(sorted.next().unwrap())
+            }
+            s => s,
+        })
+        .collect())
+}
+
+/// Returns the cycles between the nodes which still have `missing` dependencies after
+/// a topological sort. Each node of a cycle is followed by one of its dependencies.
+fn find_cycles(dependencies: &[BTreeSet<usize>], missing: &[usize]) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; dependencies.len()];
+    let mut cycles = vec![];
+    for start in (0..dependencies.len()).filter(|&i| missing[i] > 0) {
+        let mut path = vec![];
+        let mut node = start;
+        // Follow unsorted dependencies until we reach a node of this path or an earlier one.
+        while !visited[node] {
+            visited[node] = true;
+            path.push(node);
+            node = *dependencies[node]
+                .iter()
+                .find(|&&d| missing[d] > 0)
+                .unwrap();
+        }
+        if let Some(pos) = path.iter().position(|&n| n == node) {
+            cycles.push(path.split_off(pos));
+        }
     }
+    cycles
+}
+
+fn location(source: &SourceRef) -> String {
+    format!("{}:{}", source.file, source.line)
 }
 
 pub struct Condenser<T> {
     /// All the definitions from the PIL file.
     pub symbols: HashMap<String, (Symbol, Option<FunctionValueDefinition<T>>)>,
+    /// The degree of the namespaces, if any.
+    pub degree: Option<DegreeType>,
 }
 
 impl<T: FieldElement> Condenser<T> {
@@ -114,28 +239,31 @@ impl<T: FieldElement> Condenser<T> {
         identity: &Identity<Expression<T>>,
     ) -> Vec<Identity<AlgebraicExpression<T>>> {
         if identity.kind == IdentityKind::Polynomial {
-            self.condense_expression_to_constraints(identity.expression_for_poly_id())
-                .into_iter()
-                .map(|constraint| Identity {
-                    id: identity.id,
-                    kind: identity.kind,
-                    attribute: identity.attribute.clone(),
-                    source: identity.source.clone(),
-                    left: SelectedExpressions {
-                        selector: Some(constraint),
-                        expressions: vec![],
-                    },
-                    right: Default::default(),
-                })
-                .collect()
+            self.condense_expression_to_constraints(
+                identity.expression_for_poly_id(),
+                &identity.source,
+            )
+            .into_iter()
+            .map(|constraint| Identity {
+                id: identity.id,
+                kind: identity.kind,
+                attribute: identity.attribute.clone(),
+                source: identity.source.clone(),
+                left: SelectedExpressions {
+                    selector: Some(constraint),
+                    expressions: vec![],
+                },
+                right: Default::default(),
+            })
+            .collect()
         } else {
             vec![Identity {
                 id: identity.id,
                 kind: identity.kind,
                 attribute: identity.attribute.clone(),
                 source: identity.source.clone(),
-                left: self.condense_selected_expressions(&identity.left),
-                right: self.condense_selected_expressions(&identity.right),
+                left: self.condense_selected_expressions(&identity.left, &identity.source),
+                right: self.condense_selected_expressions(&identity.right, &identity.source),
             }]
         }
     }
@@ -143,21 +271,22 @@ impl<T: FieldElement> Condenser<T> {
     fn condense_selected_expressions(
         &self,
         sel_expr: &SelectedExpressions<Expression<T>>,
+        source: &SourceRef,
     ) -> SelectedExpressions<AlgebraicExpression<T>> {
         SelectedExpressions {
             selector: sel_expr
                 .selector
                 .as_ref()
-                .map(|expr| self.condense_expression(expr)),
+                .map(|expr| self.condense_expression(expr, source)),
             expressions: sel_expr
                 .expressions
                 .iter()
-                .map(|expr| self.condense_expression(expr))
+                .map(|expr| self.condense_expression(expr, source))
                 .collect(),
         }
     }
 
-    fn condense_expression(&self, e: &Expression<T>) -> AlgebraicExpression<T> {
+    fn condense_expression(&self, e: &Expression<T>, source: &SourceRef) -> AlgebraicExpression<T> {
         evaluator::evaluate(e, &self)
             .and_then(|result| match result {
                 Value::Custom(Condensate::Expression(expr)) => Ok(expr),
@@ -167,12 +296,19 @@ impl<T: FieldElement> Condenser<T> {
                 ))),
             })
             .unwrap_or_else(|err| {
-                panic!("Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}")
+                panic!(
+                    "{}: Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}",
+                    location(source)
+                )
             })
     }
 
     /// Evaluates an expression and expects a single constraint or an array of constraints.
-    fn condense_expression_to_constraints(&self, e: &Expression<T>) -> Vec<AlgebraicExpression<T>> {
+    fn condense_expression_to_constraints(
+        &self,
+        e: &Expression<T>,
+        source: &SourceRef,
+    ) -> Vec<AlgebraicExpression<T>> {
         evaluator::evaluate(e, &self)
             .and_then(|result| match result {
                 // TODO We have to allow expressions here because the parser
@@ -198,7 +334,10 @@ impl<T: FieldElement> Condenser<T> {
                 ))),
             })
             .unwrap_or_else(|err| {
-                panic!("Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}")
+                panic!(
+                    "{}: Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}",
+                    location(source)
+                )
             })
     }
 }
@@ -253,6 +392,13 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T, Condensate<T>> for &'a Condenser<T
         Ok(AlgebraicExpression::PublicReference(name.to_string()).into())
     }
 
+    fn lookup_degree(&self) -> Result<Value<'a, T, Condensate<T>>, EvalError> {
+        let degree = self.degree.ok_or_else(|| {
+            EvalError::Unsupported("DEGREE used outside of a namespace.".to_string())
+        })?;
+        Ok(Value::Number(degree.into()))
+    }
+
     fn eval_function_application(
         &self,
         function: Condensate<T>,
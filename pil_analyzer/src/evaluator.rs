@@ -1,7 +1,7 @@
 use std::{collections::HashMap, fmt::Display, rc::Rc};
 
 use ast::{
-    analyzed::{Expression, FunctionValueDefinition, Reference, Symbol},
+    analyzed::{BuiltinFunction, Expression, FunctionValueDefinition, Reference, Symbol},
     evaluate_binary_operation, evaluate_unary_operation,
     parsed::{
         display::quote, BinaryOperator, FunctionCall, LambdaExpression, MatchArm, MatchPattern,
@@ -9,7 +9,7 @@ use ast::{
     },
 };
 use itertools::Itertools;
-use number::FieldElement;
+use number::{BigInt, FieldElement};
 
 /// Evaluates an expression given a hash map of definitions.
 pub fn evaluate_expression<'a, T: FieldElement>(
@@ -50,6 +50,7 @@ pub fn evaluate_function_call<'a, T: FieldElement, C: Custom>(
 
             internal::evaluate(&lambda.body, &local_vars, symbols)
         }
+        Value::BuiltinFunction(builtin) => evaluate_builtin_function(builtin, &arguments),
         Value::Custom(value) => symbols.eval_function_application(value, &arguments),
         e => Err(EvalError::TypeError(format!(
             "Expected function but got {e}"
@@ -57,6 +58,46 @@ pub fn evaluate_function_call<'a, T: FieldElement, C: Custom>(
     }
 }
 
+fn evaluate_builtin_function<'a, T: FieldElement, C: Custom>(
+    builtin: BuiltinFunction,
+    arguments: &[Rc<Value<'a, T, C>>],
+) -> Result<Value<'a, T, C>, EvalError> {
+    let name = builtin.name();
+    let max_arguments = match builtin {
+        BuiltinFunction::Int => 2,
+        BuiltinFunction::Fe => 1,
+    };
+    if arguments.is_empty() || arguments.len() > max_arguments {
+        Err(EvalError::TypeError(format!(
+            "Invalid function call: Supplied {} arguments to builtin {name}.",
+            arguments.len()
+        )))?
+    }
+    let numbers = arguments
+        .iter()
+        .map(|a| match a.as_ref() {
+            Value::Number(n) => Ok(*n),
+            Value::Custom(c) => Err(EvalError::Unsupported(format!(
+                "The builtin {name} is not a polynomial operation and cannot be applied to {c}, \
+                 it can only be used in expressions that are evaluated, not in constraints."
+            ))),
+            v => Err(EvalError::TypeError(format!(
+                "Expected number as argument to builtin {name} but got {v}"
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Numbers are field elements, but all integer operations already use
+    // their canonical representation, so the conversion itself is the identity.
+    if let [x, bits] = numbers[..] {
+        if x.to_integer().num_bits() as u64 > bits.to_degree() {
+            Err(EvalError::FailedAssertion(format!(
+                "The value {x} does not fit into {bits} bits in {name}({x}, {bits})."
+            )))?
+        }
+    }
+    Ok(Value::Number(numbers[0]))
+}
+
 /// Evaluation errors.
 /// TODO Most of these errors should be converted to panics as soon as we have a proper type checker.
 #[derive(Debug)]
@@ -74,6 +115,10 @@ pub enum EvalError {
     SymbolNotFound(String),
     /// Data not (yet) available
     DataNotAvailable,
+    /// Integer division or remainder with a divisor of zero.
+    DivisionByZero(String),
+    /// A value does not fit into the bit width given to the builtin `int`.
+    FailedAssertion(String),
 }
 
 #[derive(Clone, PartialEq)]
@@ -83,6 +128,7 @@ pub enum Value<'a, T, C> {
     Tuple(Vec<Self>),
     Array(Vec<Self>),
     Closure(Closure<'a, T, C>),
+    BuiltinFunction(BuiltinFunction),
     Custom(C),
 }
 
@@ -113,6 +159,7 @@ impl<'a, T: Display, C: Custom> Display for Value<'a, T, C> {
             Value::Tuple(items) => write!(f, "({})", items.iter().format(", ")),
             Value::Array(elements) => write!(f, "[{}]", elements.iter().format(", ")),
             Value::Closure(closure) => write!(f, "{closure}"),
+            Value::BuiltinFunction(builtin) => write!(f, "{}", builtin.name()),
             Value::Custom(c) => write!(f, "{c}"),
         }
     }
@@ -197,6 +244,12 @@ pub trait SymbolLookup<'a, T, C> {
             "Cannot evaluate public reference: {name}"
         )))
     }
+    /// The value of the builtin `DEGREE`.
+    fn lookup_degree(&self) -> Result<Value<'a, T, C>, EvalError> {
+        Err(EvalError::Unsupported(
+            "The degree is not known at this point.".to_string(),
+        ))
+    }
     fn eval_function_application(
         &self,
         function: C,
@@ -258,6 +311,13 @@ mod internal {
                         l.extend(std::mem::take(r));
                         Value::Array(std::mem::take(l))
                     }
+                    (
+                        Value::Number(l),
+                        BinaryOperator::Div | BinaryOperator::Mod,
+                        Value::Number(r),
+                    ) if r.is_zero() => Err(EvalError::DivisionByZero(format!(
+                        "Division by zero: {l} {op} {r} in: {expr}."
+                    )))?,
                     (Value::Number(l), _, Value::Number(r)) => {
                         Value::Number(evaluate_binary_operation(*l, *op, *r))
                     }
@@ -284,8 +344,13 @@ mod internal {
             Expression::IndexAccess(index_access) => {
                 match evaluate(&index_access.array, locals, symbols)? {
                     Value::Array(elements) => {
-                        let index =
-                            evaluate(&index_access.index, locals, symbols)?.try_to_number()?;
+                        let index = match evaluate(&index_access.index, locals, symbols)? {
+                            Value::Number(index) => index,
+                            index => Err(EvalError::TypeError(format!(
+                                "Array index has to be a constant number, but {} evaluates to {index} in: {expr}.",
+                                index_access.index
+                            )))?,
+                        };
                         if index.to_integer() >= (elements.len() as u64).into() {
                             Err(EvalError::OutOfBounds(format!(
                                 "Index access out of bounds: Tried to access element {index} of array of size {} in: {expr}.",
@@ -349,6 +414,9 @@ mod internal {
         Ok(match reference {
             Reference::LocalVar(i, _name) => (*locals[*i as usize]).clone(),
             Reference::Poly(poly) => symbols.lookup(&poly.name)?,
+            Reference::Constant(name) => symbols.lookup(name)?,
+            Reference::Degree => symbols.lookup_degree()?,
+            Reference::Builtin(builtin) => Value::BuiltinFunction(*builtin),
         })
     }
 }
@@ -420,4 +488,29 @@ mod test {
             "99".to_string()
         );
     }
+
+    #[test]
+    pub fn integer_division() {
+        let src = r#"namespace Main(16);
+            let x = 0x1234;
+            let bytes = [x % 256, (x / 256) % 256, 7 / 2, 7 % 2];
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.bytes"),
+            "[52, 18, 3, 1]".to_string()
+        );
+    }
+
+    #[test]
+    pub fn division_by_zero() {
+        let src = r#"namespace Main(16);
+            let x = 7 / (2 - 2);
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let Some(FunctionValueDefinition::Expression(x)) = &analyzed.definitions["Main.x"].1 else {
+            panic!()
+        };
+        let result = evaluate::<_, NoCustom>(x, &Definitions(&analyzed.definitions));
+        assert!(matches!(result, Err(EvalError::DivisionByZero(_))));
+    }
 }
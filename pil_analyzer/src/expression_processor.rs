@@ -1,7 +1,9 @@
 use std::{collections::HashMap, marker::PhantomData};
 
 use ast::{
-    analyzed::{Expression, PolynomialReference, Reference, RepeatedArray},
+    analyzed::{
+        BuiltinFunction, Expression, PolynomialReference, Reference, RepeatedArray, DEGREE_BUILTIN,
+    },
     parsed::{
         self, ArrayExpression, ArrayLiteral, IfExpression, LambdaExpression, MatchArm,
         MatchPattern, NamespacedPolynomialReference, SelectedExpressions,
@@ -138,9 +140,20 @@ impl<T, D: AnalysisDriver<T>> ExpressionProcessor<T, D> {
     }
 
     fn process_reference(&mut self, reference: NamespacedPolynomialReference) -> Reference {
-        if reference.namespace.is_none() && self.local_variables.contains_key(&reference.name) {
+        if let Some(name) = reference.constant_name() {
+            Reference::Constant(name.to_string())
+        } else if reference.namespace.is_none()
+            && self.local_variables.contains_key(&reference.name)
+        {
             let id = self.local_variables[&reference.name];
             Reference::LocalVar(id, reference.name.to_string())
+        } else if reference.namespace.is_none() && reference.name == DEGREE_BUILTIN {
+            Reference::Degree
+        } else if let (None, Some(builtin)) = (
+            &reference.namespace,
+            BuiltinFunction::from_name(&reference.name),
+        ) {
+            Reference::Builtin(builtin)
         } else {
             Reference::Poly(self.process_namespaced_polynomial_reference(reference))
         }
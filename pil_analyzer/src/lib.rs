@@ -3,22 +3,76 @@
 mod condenser;
 pub mod evaluator;
 pub mod expression_processor;
+mod limits;
 pub mod pil_analyzer;
+pub mod rename;
 pub mod statement_processor;
 
 use std::{collections::HashMap, path::Path};
 
 use ast::analyzed::{Analyzed, FunctionValueDefinition, SourceRef, Symbol};
-use number::FieldElement;
+use ast::parsed::PILFile;
+use ast::source_map::SourceMap;
+use number::{DegreeType, FieldElement};
+
+pub use limits::Limits;
 
 pub fn analyze<T: FieldElement>(path: &Path) -> Analyzed<T> {
     pil_analyzer::process_pil_file(path)
 }
 
+/// Analyzes a PIL file, using `degree` (if given) as the degree of all namespaces
+/// instead of the declared degrees.
+pub fn analyze_with_degree<T: FieldElement>(
+    path: &Path,
+    degree: Option<DegreeType>,
+) -> Analyzed<T> {
+    pil_analyzer::process_pil_file_with_degree(path, degree)
+}
+
+/// Like [analyze_with_degree], but returns an error instead of panicking
+/// if the file exceeds `limits`.
+pub fn analyze_with_limits<T: FieldElement>(
+    path: &Path,
+    degree: Option<DegreeType>,
+    limits: &Limits,
+) -> Result<Analyzed<T>, Vec<String>> {
+    pil_analyzer::process_pil_file_with_limits(path, degree, limits)
+}
+
 pub fn analyze_string<T: FieldElement>(contents: &str) -> Analyzed<T> {
     pil_analyzer::process_pil_file_contents(contents)
 }
 
+/// Analyzes PIL source, using `degree` (if given) as the degree of all namespaces
+/// instead of the declared degrees.
+pub fn analyze_string_with_degree<T: FieldElement>(
+    contents: &str,
+    degree: Option<DegreeType>,
+) -> Analyzed<T> {
+    pil_analyzer::process_pil_file_contents_with_degree(contents, degree)
+}
+
+/// Like [analyze_string_with_degree], but returns an error instead of panicking
+/// if the source exceeds `limits`.
+pub fn analyze_string_with_limits<T: FieldElement>(
+    contents: &str,
+    degree: Option<DegreeType>,
+    limits: &Limits,
+) -> Result<Analyzed<T>, Vec<String>> {
+    pil_analyzer::process_pil_file_contents_with_limits(contents, degree, limits)
+}
+
+/// Analyzes the PIL generated from an ASM program, keeping the source positions in
+/// the files of the program, see [pil_analyzer::process_pil_ast_with_degree].
+pub fn analyze_ast_with_degree<T: FieldElement>(
+    pil_file: PILFile<T>,
+    sources: &SourceMap,
+    degree: Option<DegreeType>,
+) -> Analyzed<T> {
+    pil_analyzer::process_pil_ast_with_degree(pil_file, sources, degree)
+}
+
 pub trait AnalysisDriver<T>: Clone + Copy {
     /// Turns a declaration into an absolute name.
     fn resolve_decl(&self, name: &str) -> String;
@@ -0,0 +1,129 @@
+//! Resource limits for analyzing untrusted PIL sources.
+
+use std::mem;
+
+use ast::{
+    analyzed::Analyzed,
+    parsed::{visitor::ExpressionVisitable, Expression, PILFile, PilStatement},
+};
+
+/// Limits on the resources a PIL source can make the analyzer and the later stages use.
+/// Exceeding a limit results in an error instead of a stack overflow or a huge allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum nesting depth of an expression.
+    /// The later stages still process expressions recursively, so this has to be
+    /// reduced when analyzing on threads with small stacks, especially in debug builds.
+    pub max_expression_depth: usize,
+    /// The maximum number of definitions and identities, after resolving
+    /// includes and lowering tables.
+    pub max_statements: usize,
+    /// The maximum number of cells, i.e. the degree times the number of
+    /// fixed and witness columns.
+    pub max_cells: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_expression_depth: 1000,
+            max_statements: 1 << 20,
+            max_cells: 1 << 36,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks the nesting depth of all expressions in `file`.
+    /// In case of an error, the expressions are removed from `file` and dropped
+    /// without recursion.
+    pub(crate) fn check_expression_depth<T>(&self, file: &mut PILFile<T>) -> Result<(), String> {
+        let mut expressions = file.0.iter_mut().map(take_expressions).collect::<Vec<_>>();
+        let too_deep = expressions
+            .iter_mut()
+            .flatten()
+            .any(|e| depth(e) > self.max_expression_depth);
+        if too_deep {
+            expressions.into_iter().flatten().for_each(drop_iteratively);
+            Err(format!(
+                "Expression nesting depth exceeds the limit of {}.",
+                self.max_expression_depth
+            ))
+        } else {
+            for (statement, expressions) in file.0.iter_mut().zip(expressions) {
+                restore_expressions(statement, expressions);
+            }
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_statement_count(&self, count: usize) -> Result<(), String> {
+        if count > self.max_statements {
+            Err(format!(
+                "Number of statements exceeds the limit of {}.",
+                self.max_statements
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that the fixed and witness columns of `analyzed` do not exceed
+    /// the maximum number of cells.
+    pub(crate) fn check_cells<T>(&self, analyzed: &Analyzed<T>) -> Result<(), String> {
+        let Some(degree) = analyzed.degree else {
+            return Ok(());
+        };
+        let columns = (analyzed.commitment_count() + analyzed.constant_count()) as u64;
+        if degree.saturating_mul(columns) > self.max_cells {
+            Err(format!(
+                "The degree {degree} times the number of columns {columns} exceeds the limit of {} cells.",
+                self.max_cells
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// An expression without sub-expressions used as a placeholder.
+fn placeholder<T>() -> Expression<T> {
+    Expression::Tuple(vec![])
+}
+
+/// Moves the top-level expressions out of `statement`, replacing them by placeholders.
+/// Since the visitor does not descend into the placeholders, this does not recurse
+/// into the expressions.
+fn take_expressions<T>(statement: &mut PilStatement<T>) -> Vec<Expression<T>> {
+    let mut expressions = vec![];
+    statement.pre_visit_expressions_mut(&mut |e| expressions.push(mem::replace(e, placeholder())));
+    expressions
+}
+
+/// Inverse of [take_expressions]. Uses post-order so that the visitor only
+/// visits the placeholders and not the restored expressions.
+fn restore_expressions<T>(statement: &mut PilStatement<T>, expressions: Vec<Expression<T>>) {
+    let mut expressions = expressions.into_iter();
+    statement.post_visit_expressions_mut(&mut |e| *e = expressions.next().unwrap());
+}
+
+fn depth<T>(e: &mut Expression<T>) -> usize {
+    let mut max_depth = 0;
+    let mut stack = vec![(e, 1)];
+    while let Some((e, depth)) = stack.pop() {
+        max_depth = max_depth.max(depth);
+        stack.extend(e.children_mut().into_iter().map(|c| (c, depth + 1)));
+    }
+    max_depth
+}
+
+fn drop_iteratively<T>(e: Expression<T>) {
+    let mut stack = vec![e];
+    while let Some(mut e) = stack.pop() {
+        stack.extend(
+            e.children_mut()
+                .into_iter()
+                .map(|c| mem::replace(c, placeholder())),
+        );
+    }
+}
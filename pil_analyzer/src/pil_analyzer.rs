@@ -2,29 +2,89 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use ast::parsed::PilStatement;
+use std::iter::once;
+
+use ast::parsed::visitor::ExpressionVisitable;
+use ast::parsed::{
+    self, build::direct_reference, BinaryOperator, FunctionDefinition, LambdaExpression,
+    NamespacedPolynomialReference, PILFile, PilStatement, SelectedExpressions, TableDefinition,
+};
+use ast::source_map::SourceMap;
 use number::{DegreeType, FieldElement};
+use parser_util::std_library::{std_include_path, StdLibrary};
 
 use ast::analyzed::{
     Analyzed, Expression, FunctionValueDefinition, Identity, PublicDeclaration, SourceRef,
     StatementIdentifier, Symbol,
 };
 
+use crate::limits::Limits;
 use crate::AnalysisDriver;
 
 use crate::statement_processor::{Counters, PILItem, StatementProcessor};
 use crate::{condenser, evaluator, expression_processor::ExpressionProcessor};
 
 pub fn process_pil_file<T: FieldElement>(path: &Path) -> Analyzed<T> {
-    let mut analyzer = PILAnalyzer::new();
+    process_pil_file_with_degree(path, None)
+}
+
+pub fn process_pil_file_with_degree<T: FieldElement>(
+    path: &Path,
+    degree: Option<DegreeType>,
+) -> Analyzed<T> {
+    process_pil_file_with_limits(path, degree, &Limits::default())
+        .unwrap_or_else(|errors| panic!("{}", errors.join("\n")))
+}
+
+/// Like [process_pil_file_with_degree], but returns an error if the file exceeds `limits`.
+pub fn process_pil_file_with_limits<T: FieldElement>(
+    path: &Path,
+    degree: Option<DegreeType>,
+    limits: &Limits,
+) -> Result<Analyzed<T>, Vec<String>> {
+    let mut analyzer = PILAnalyzer::new().with_degree(degree).with_limits(*limits);
     analyzer.process_file(path);
-    analyzer.condense()
+    analyzer.finish()
 }
 
 pub fn process_pil_file_contents<T: FieldElement>(contents: &str) -> Analyzed<T> {
-    let mut analyzer = PILAnalyzer::new();
+    process_pil_file_contents_with_degree(contents, None)
+}
+
+pub fn process_pil_file_contents_with_degree<T: FieldElement>(
+    contents: &str,
+    degree: Option<DegreeType>,
+) -> Analyzed<T> {
+    process_pil_file_contents_with_limits(contents, degree, &Limits::default())
+        .unwrap_or_else(|errors| panic!("{}", errors.join("\n")))
+}
+
+/// Like [process_pil_file_contents_with_degree], but returns an error if the
+/// source exceeds `limits`.
+pub fn process_pil_file_contents_with_limits<T: FieldElement>(
+    contents: &str,
+    degree: Option<DegreeType>,
+    limits: &Limits,
+) -> Result<Analyzed<T>, Vec<String>> {
+    let mut analyzer = PILAnalyzer::new().with_degree(degree).with_limits(*limits);
     analyzer.process_file_contents(Path::new("input"), contents);
-    analyzer.condense()
+    analyzer.finish()
+}
+
+/// Analyzes the PIL generated from an ASM program, without printing and parsing it
+/// again, so that the source positions of the statements are preserved. They are
+/// positions in `sources`, and statements without a position are attributed to the
+/// declaration of the machine, i.e. to the position of their namespace.
+pub fn process_pil_ast_with_degree<T: FieldElement>(
+    pil_file: PILFile<T>,
+    sources: &SourceMap,
+    degree: Option<DegreeType>,
+) -> Analyzed<T> {
+    let mut analyzer = PILAnalyzer::new().with_degree(degree);
+    analyzer.process_ast(pil_file, sources);
+    analyzer
+        .finish()
+        .unwrap_or_else(|errors| panic!("{}", errors.join("\n")))
 }
 
 // TODO we could further extract a component that is only responsible for
@@ -33,6 +93,8 @@ pub fn process_pil_file_contents<T: FieldElement>(contents: &str) -> Analyzed<T>
 struct PILAnalyzer<T> {
     namespace: String,
     polynomial_degree: Option<DegreeType>,
+    /// If set, used as the degree of all namespaces instead of the declared degrees.
+    degree_override: Option<DegreeType>,
     definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition<T>>)>,
     public_declarations: HashMap<String, PublicDeclaration>,
     identities: Vec<Identity<Expression<T>>>,
@@ -40,9 +102,40 @@ struct PILAnalyzer<T> {
     /// appear in the source.
     source_order: Vec<StatementIdentifier>,
     symbol_counters: Option<Counters>,
-    included_files: HashSet<PathBuf>,
+    /// The columns two-dimensional tables are lowered to, by absolute table name.
+    tables: HashMap<String, Vec<NamespacedPolynomialReference>>,
+    /// The included files, together with the prefix of the instance they were included in.
+    included_files: HashSet<(Option<String>, PathBuf)>,
+    /// The instance created by `include "file" as Prefix` that is currently processed.
+    instance: Option<Instance>,
+    /// The standard library that `std::` includes are loaded from.
+    std: StdLibrary,
     line_starts: Vec<usize>,
     current_file: PathBuf,
+    /// If set, the source positions are positions in these files instead of the current file.
+    sources: Option<SourceMap>,
+    /// The source of the current namespace, used for statements without a position.
+    namespace_source: SourceRef,
+    limits: Limits,
+    /// Violations of the limits. Once there is one, no further statements are processed.
+    limit_errors: Vec<String>,
+}
+
+/// An instance of the namespaces declared in an included file (and the files it
+/// includes), created by `include "file" as Prefix`. The namespace `N` is renamed
+/// to `Prefix_N`, the symbols outside of namespaces to `Prefix_Global` and the
+/// public declarations `p` to `Prefix_p`. Constants (`%name`) are not namespaced
+/// and cannot be defined in an instance.
+struct Instance {
+    prefix: String,
+    /// The original names of the namespaces declared in the instance.
+    namespaces: HashSet<String>,
+}
+
+impl Instance {
+    fn namespace(&self, name: &str) -> String {
+        format!("{}_{name}", self.prefix)
+    }
 }
 
 impl<T: FieldElement> PILAnalyzer<T> {
@@ -50,22 +143,58 @@ impl<T: FieldElement> PILAnalyzer<T> {
         PILAnalyzer {
             namespace: "Global".to_string(),
             polynomial_degree: None,
+            degree_override: None,
             definitions: Default::default(),
             public_declarations: Default::default(),
             identities: vec![],
             source_order: vec![],
             included_files: Default::default(),
+            instance: None,
+            std: StdLibrary::from_env(),
             line_starts: Default::default(),
             current_file: Default::default(),
+            sources: None,
+            namespace_source: Default::default(),
             symbol_counters: Some(Default::default()),
+            tables: Default::default(),
+            limits: Default::default(),
+            limit_errors: vec![],
         }
     }
 
+    /// Uses `degree` as the degree of all namespaces, ignoring the declared degrees.
+    /// Arrays with repeated elements are sized accordingly.
+    pub fn with_degree(mut self, degree: Option<DegreeType>) -> Self {
+        if let Some(degree) = degree {
+            assert!(
+                degree.is_power_of_two(),
+                "The degree has to be a power of two, but it is {degree}."
+            );
+        }
+        self.degree_override = degree;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Uses `std` for includes of the form `std::<path>` instead of [StdLibrary::from_env].
+    #[cfg(test)]
+    pub fn with_std_library(mut self, std: StdLibrary) -> Self {
+        self.std = std;
+        self
+    }
+
     pub fn process_file(&mut self, path: &Path) {
         let path = path
             .canonicalize()
             .unwrap_or_else(|e| panic!("File {path:?} not found: {e}"));
-        if !self.included_files.insert(path.clone()) {
+        if !self
+            .included_files
+            .insert((self.instance_prefix(), path.clone()))
+        {
             return;
         }
         let contents = fs::read_to_string(path.clone()).unwrap();
@@ -77,6 +206,7 @@ impl<T: FieldElement> PILAnalyzer<T> {
     pub fn process_file_contents(&mut self, path: &Path, contents: &str) {
         let old_current_file = std::mem::take(&mut self.current_file);
         let old_line_starts = std::mem::take(&mut self.line_starts);
+        let old_sources = self.sources.take();
 
         // TODO make this work for other line endings
         self.line_starts = parser_util::lines::compute_line_starts(contents);
@@ -87,16 +217,59 @@ impl<T: FieldElement> PILAnalyzer<T> {
                 err.output_to_stderr();
                 panic!();
             });
+        if let Some(instance) = &mut self.instance {
+            // Collect the namespaces first, so that references to namespaces
+            // declared further down are renamed as well.
+            instance
+                .namespaces
+                .extend(pil_file.0.iter().filter_map(|statement| match statement {
+                    PilStatement::Namespace(_, name, _) => Some(name.clone()),
+                    _ => None,
+                }));
+        }
+        self.process_statements(pil_file);
 
+        self.current_file = old_current_file;
+        self.line_starts = old_line_starts;
+        self.sources = old_sources;
+    }
+
+    /// Processes statements whose source positions are positions in `sources`.
+    /// Includes are relative to the working directory.
+    pub fn process_ast(&mut self, pil_file: PILFile<T>, sources: &SourceMap) {
+        self.current_file = PathBuf::from("input");
+        self.sources = Some(sources.clone());
+        self.process_statements(pil_file);
+        self.sources = None;
+    }
+
+    fn process_statements(&mut self, mut pil_file: PILFile<T>) {
+        if let Err(e) = self.limits.check_expression_depth(&mut pil_file) {
+            self.limit_errors.push(e);
+        }
         for statement in pil_file.0 {
+            if !self.limit_errors.is_empty() {
+                break;
+            }
             self.handle_statement(statement);
+            if let Err(e) = self.limits.check_statement_count(self.source_order.len()) {
+                self.limit_errors.push(e);
+            }
         }
+    }
 
-        self.current_file = old_current_file;
-        self.line_starts = old_line_starts;
+    /// Condenses the processed statements, unless a limit was exceeded.
+    pub fn finish(self) -> Result<Analyzed<T>, Vec<String>> {
+        if !self.limit_errors.is_empty() {
+            return Err(self.limit_errors);
+        }
+        let limits = self.limits;
+        let analyzed = self.condense()?;
+        limits.check_cells(&analyzed).map_err(|e| vec![e])?;
+        Ok(analyzed)
     }
 
-    pub fn condense(self) -> Analyzed<T> {
+    pub fn condense(self) -> Result<Analyzed<T>, Vec<String>> {
         condenser::condense(
             self.polynomial_degree,
             self.definitions,
@@ -108,58 +281,240 @@ impl<T: FieldElement> PILAnalyzer<T> {
 
     fn handle_statement(&mut self, statement: PilStatement<T>) {
         match statement {
-            PilStatement::Include(_, include) => self.handle_include(include),
-            PilStatement::Namespace(_, name, degree) => self.handle_namespace(name, degree),
-            _ => {
-                // We need a mutable reference to the counter, but it is short-lived.
-                let mut counters = self.symbol_counters.take().unwrap();
-                let items =
-                    StatementProcessor::new(self.driver(), &mut counters, self.polynomial_degree)
-                        .handle_statement(statement);
-                self.symbol_counters = Some(counters);
-                for item in items {
-                    match item {
-                        PILItem::Definition(symbol, value) => {
-                            let name = symbol.absolute_name.clone();
-                            let is_new = self
-                                .definitions
-                                .insert(name.clone(), (symbol, value))
-                                .is_none();
-                            assert!(is_new, "{name} already defined.");
-                            self.source_order
-                                .push(StatementIdentifier::Definition(name));
-                        }
-                        PILItem::PublicDeclaration(decl) => {
-                            let name = decl.name.clone();
-                            self.public_declarations.insert(name.clone(), decl);
-                            self.source_order
-                                .push(StatementIdentifier::PublicDeclaration(name));
-                        }
-                        PILItem::Identity(identity) => {
-                            let index = self.identities.len();
-                            self.source_order.push(StatementIdentifier::Identity(index));
-                            self.identities.push(identity)
-                        }
+            PilStatement::Include(_, include, None) => self.handle_include(include),
+            PilStatement::Include(_, include, Some(prefix)) => {
+                self.handle_instance_include(include, prefix)
+            }
+            PilStatement::Namespace(start, name, degree) => {
+                if let Some(sources) = &self.sources {
+                    self.namespace_source = sources.source_ref(start).unwrap_or_default();
+                }
+                self.handle_namespace(name, degree)
+            }
+            PilStatement::PolynomialConstantTableDefinition(start, name, table) => {
+                self.handle_table_definition(start, name, table)
+            }
+            PilStatement::PlookupIdentity(start, attribute, key, haystack) => {
+                let haystack = self.expand_table_lookup(&key, haystack);
+                self.process_statement(PilStatement::PlookupIdentity(
+                    start, attribute, key, haystack,
+                ))
+            }
+            _ => self.process_statement(statement),
+        }
+    }
+
+    fn process_statement(&mut self, statement: PilStatement<T>) {
+        if let (Some(instance), PilStatement::ConstantDefinition(_, name, _)) =
+            (&self.instance, &statement)
+        {
+            panic!(
+                "The constant {name} cannot be defined in the instance {}, because constants are not namespaced. Define it in the including file.",
+                instance.prefix
+            );
+        }
+        // We need a mutable reference to the counter, but it is short-lived.
+        let mut counters = self.symbol_counters.take().unwrap();
+        let items = StatementProcessor::new(self.driver(), &mut counters, self.polynomial_degree)
+            .handle_statement(statement);
+        self.symbol_counters = Some(counters);
+        for item in items {
+            match item {
+                PILItem::Definition(symbol, value) => {
+                    let name = symbol.absolute_name.clone();
+                    let is_new = self
+                        .definitions
+                        .insert(name.clone(), (symbol, value))
+                        .is_none();
+                    assert!(is_new, "{name} already defined.");
+                    self.source_order
+                        .push(StatementIdentifier::Definition(name));
+                }
+                PILItem::PublicDeclaration(mut decl) => {
+                    if let Some(instance) = &self.instance {
+                        decl.name = instance.namespace(&decl.name);
                     }
+                    let name = decl.name.clone();
+                    self.public_declarations.insert(name.clone(), decl);
+                    self.source_order
+                        .push(StatementIdentifier::PublicDeclaration(name));
+                }
+                PILItem::Identity(identity) => {
+                    let index = self.identities.len();
+                    self.source_order.push(StatementIdentifier::Identity(index));
+                    self.identities.push(identity)
+                }
+            }
+        }
+    }
+
+    /// Lowers a two-dimensional table `col fixed T(a, b) -> c { ... } over (A, B)`
+    /// into the fixed columns `T_a`, `T_b` and `T_c`.
+    /// The table is stored in row-major order: row `i` contains `a = (i / B) % A` and
+    /// `b = i % B`, so all combinations appear in the first `A * B` rows
+    /// and the table repeats after that.
+    fn handle_table_definition(&mut self, start: usize, name: String, table: TableDefinition<T>) {
+        let degree = self
+            .polynomial_degree
+            .unwrap_or_else(|| panic!("Table {name} has to be defined inside a namespace."));
+        let dimensions = table
+            .dimensions
+            .into_iter()
+            .map(|d| self.evaluate_to_degree(d))
+            .collect::<Vec<_>>();
+        let (rows, cols) = (dimensions[0], dimensions[1]);
+        assert!(
+            rows.checked_mul(cols).map_or(false, |size| size <= degree),
+            "Table {name} has {rows} x {cols} entries, which exceeds the degree {degree}."
+        );
+
+        let row = || Box::new(direct_reference("i"));
+        let number = |n: DegreeType| Box::new(parsed::Expression::Number(T::from(n)));
+        let indices = [
+            parsed::Expression::BinaryOperation(
+                Box::new(parsed::Expression::BinaryOperation(
+                    row(),
+                    BinaryOperator::Div,
+                    number(cols),
+                )),
+                BinaryOperator::Mod,
+                number(rows),
+            ),
+            parsed::Expression::BinaryOperation(row(), BinaryOperator::Mod, number(cols)),
+        ];
+        let mut output = *table.body;
+        output.post_visit_expressions_mut(&mut |e| {
+            let index = match e {
+                parsed::Expression::Reference(NamespacedPolynomialReference {
+                    namespace: None,
+                    name,
+                }) => table.params.iter().position(|p| p == name),
+                _ => None,
+            };
+            if let Some(index) = index {
+                *e = indices[index].clone();
+            }
+        });
+
+        let columns = table
+            .params
+            .iter()
+            .zip(indices.clone())
+            .chain(once((&table.output, output)))
+            .map(|(suffix, value)| {
+                let column = format!("{name}_{suffix}");
+                self.process_statement(PilStatement::PolynomialConstantDefinition(
+                    start,
+                    None,
+                    column.clone(),
+                    FunctionDefinition::Expression(parsed::Expression::LambdaExpression(
+                        LambdaExpression {
+                            params: vec!["i".to_string()],
+                            body: Box::new(value),
+                        },
+                    )),
+                ));
+                NamespacedPolynomialReference {
+                    namespace: Some(self.namespace.clone()),
+                    name: column,
                 }
+            })
+            .collect();
+        let name = self.driver().resolve_decl(&name);
+        self.tables.insert(name, columns);
+    }
+
+    /// Expands a lookup into a two-dimensional table like `{ x, y, z } in T`
+    /// into a lookup into the columns the table was lowered to.
+    fn expand_table_lookup(
+        &self,
+        key: &SelectedExpressions<parsed::Expression<T>>,
+        haystack: SelectedExpressions<parsed::Expression<T>>,
+    ) -> SelectedExpressions<parsed::Expression<T>> {
+        if let [parsed::Expression::Reference(reference)] = &haystack.expressions[..] {
+            let name = self
+                .driver()
+                .resolve_ref(&reference.namespace, &reference.name);
+            if let Some(columns) = self.tables.get(&name) {
+                assert_eq!(
+                    key.expressions.len(),
+                    columns.len(),
+                    "Lookup into table {name} needs {} expressions on the left side.",
+                    columns.len()
+                );
+                return SelectedExpressions {
+                    selector: haystack.selector,
+                    expressions: columns.iter().cloned().map(Into::into).collect(),
+                };
             }
         }
+        haystack
     }
 
+    /// Includes `std::` paths from the standard library, never from the file system
+    /// relative to the current file, so that they cannot be shadowed by local files.
     fn handle_include(&mut self, path: String) {
+        if let Some(std_path) = std_include_path(&path) {
+            let file = PathBuf::from(self.std.display_path(&std_path));
+            if !self
+                .included_files
+                .insert((self.instance_prefix(), file.clone()))
+            {
+                return;
+            }
+            let contents = self
+                .std
+                .read(&std_path)
+                .unwrap_or_else(|e| panic!("Cannot include {path}: {e}"));
+            self.process_file_contents(&file, &contents);
+            return;
+        }
         let mut dir = self.current_file.parent().unwrap().to_owned();
         dir.push(path);
         self.process_file(&dir);
     }
 
+    /// Processes `include "path" as prefix`, i.e. creates a new instance of the namespaces
+    /// declared in the file. Instances inside of instances combine the prefixes.
+    /// After the include, the namespace of the including file is restored.
+    fn handle_instance_include(&mut self, path: String, prefix: String) {
+        let prefix = match &self.instance {
+            Some(outer) => outer.namespace(&prefix),
+            None => prefix,
+        };
+        let outer_instance = self.instance.replace(Instance {
+            prefix,
+            namespaces: Default::default(),
+        });
+        let outer_namespace = std::mem::replace(
+            &mut self.namespace,
+            self.instance.as_ref().unwrap().namespace("Global"),
+        );
+        self.handle_include(path);
+        self.namespace = outer_namespace;
+        self.instance = outer_instance;
+    }
+
+    fn instance_prefix(&self) -> Option<String> {
+        self.instance
+            .as_ref()
+            .map(|instance| instance.prefix.clone())
+    }
+
+    /// The absolute name of the namespace `name` declared in or referenced from
+    /// the current file.
+    fn namespace_name(&self, name: &str) -> String {
+        match &self.instance {
+            Some(instance) if instance.namespaces.contains(name) => instance.namespace(name),
+            _ => name.to_string(),
+        }
+    }
+
     fn handle_namespace(&mut self, name: String, degree: ::ast::parsed::Expression<T>) {
-        // TODO: the polynomial degree should be handled without going through a field element. This requires having types in Expression
-        let degree = ExpressionProcessor::new(self.driver()).process_expression(degree);
-        let namespace_degree = evaluator::evaluate_expression(&degree, &self.definitions)
-            .unwrap()
-            .try_to_number()
-            .unwrap()
-            .to_degree();
+        let namespace_degree = match self.degree_override {
+            Some(degree) => degree,
+            None => self.evaluate_to_degree(degree),
+        };
         if let Some(degree) = self.polynomial_degree {
             assert_eq!(
                 degree, namespace_degree,
@@ -168,7 +523,17 @@ impl<T: FieldElement> PILAnalyzer<T> {
         } else {
             self.polynomial_degree = Some(namespace_degree);
         }
-        self.namespace = name;
+        self.namespace = self.namespace_name(&name);
+    }
+
+    fn evaluate_to_degree(&self, expr: ::ast::parsed::Expression<T>) -> DegreeType {
+        // TODO: the polynomial degree should be handled without going through a field element. This requires having types in Expression
+        let expr = ExpressionProcessor::new(self.driver()).process_expression(expr);
+        evaluator::evaluate_expression(&expr, &self.definitions)
+            .unwrap()
+            .try_to_number()
+            .unwrap()
+            .to_degree()
     }
 
     fn driver(&self) -> Driver<T> {
@@ -181,28 +546,31 @@ struct Driver<'a, T>(&'a PILAnalyzer<T>);
 
 impl<'a, T: FieldElement> AnalysisDriver<T> for Driver<'a, T> {
     fn resolve_decl(&self, name: &str) -> String {
-        if name.starts_with('%') {
-            // Constants are not namespaced
-            name.to_string()
-        } else {
-            format!("{}.{name}", self.0.namespace)
-        }
+        format!("{}.{name}", self.0.namespace)
     }
 
     fn resolve_ref(&self, namespace: &Option<String>, name: &str) -> String {
         let definitions = &self.0.definitions;
-        if name.starts_with('%') || definitions.contains_key(&name.to_string()) {
-            assert!(namespace.is_none());
-            // Constants are not namespaced
-            name.to_string()
-        } else if namespace.is_none() && definitions.contains_key(&format!("Global.{name}")) {
-            format!("Global.{name}")
-        } else {
-            format!("{}.{name}", namespace.as_ref().unwrap_or(&self.0.namespace))
+        let instance_global = self.0.instance.as_ref().map(|i| i.namespace("Global"));
+        let global = instance_global
+            .iter()
+            .map(String::as_str)
+            .chain(once("Global"))
+            .map(|global| format!("{global}.{name}"))
+            .find(|global_name| definitions.contains_key(global_name));
+        match (namespace, global) {
+            (None, Some(global_name)) => global_name,
+            (None, None) => format!("{}.{name}", self.0.namespace),
+            (Some(namespace), _) => format!("{}.{name}", self.0.namespace_name(namespace)),
         }
     }
 
     fn source_position_to_source_ref(&self, pos: usize) -> SourceRef {
+        if let Some(sources) = &self.0.sources {
+            return sources
+                .source_ref(pos)
+                .unwrap_or_else(|| self.0.namespace_source.clone());
+        }
         let file = self.0.current_file.file_name().unwrap().to_str().unwrap();
         SourceRef {
             line: parser_util::lines::offset_to_line(pos, &self.0.line_starts),
@@ -222,6 +590,11 @@ mod test {
 
     use pretty_assertions::assert_eq;
 
+    use ast::analyzed::{
+        AlgebraicExpression, AlgebraicReference, IdentityKind, NamespaceStats, PolyID,
+        PolynomialType, RepeatedArray, UnconstrainedReason,
+    };
+
     use super::*;
 
     #[test]
@@ -231,6 +604,8 @@ mod test {
     public P = T.pc(2);
 namespace Bin(65536);
     col witness bla;
+    let N = 3;
+    col fixed M(i) { (i + (%N + Bin.N)) };
 namespace T(65536);
     col fixed first_step = [1] + [0]*;
     col fixed line(i) { i };
@@ -274,6 +649,57 @@ namespace T(65536);
         assert_eq!(input, formatted);
     }
 
+    #[test]
+    fn degree_override() {
+        let input = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+"#;
+        let expected = r#"namespace N(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+"#;
+        let analyzed = process_pil_file_contents_with_degree::<GoldilocksField>(input, Some(16));
+        assert_eq!(analyzed.degree(), 16);
+        assert_eq!(analyzed.to_string(), expected);
+    }
+
+    #[test]
+    fn degree_builtin() {
+        let input = r#"namespace N(8);
+    col fixed LAST(i) { if i == DEGREE - 1 { 1 } else { 0 } };
+"#;
+        let expected = r#"namespace N(16);
+    col fixed LAST(i) { if (i == (DEGREE - 1)) { 1 } else { 0 } };
+"#;
+        let analyzed = process_pil_file_contents_with_degree::<GoldilocksField>(input, Some(16));
+        assert_eq!(analyzed.to_string(), expected);
+    }
+
+    #[test]
+    fn casting_builtins() {
+        let input = r#"namespace N(8);
+    col fixed BYTES(i) { fe(int(i, 3) * 64 % 256) };
+"#;
+        let expected = r#"namespace N(8);
+    col fixed BYTES(i) { fe(((int(i, 3) * 64) % 256)) };
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, expected);
+        let reformatted = process_pil_file_contents::<GoldilocksField>(&formatted).to_string();
+        assert_eq!(reformatted, expected);
+    }
+
+    #[test]
+    #[should_panic = "The builtin int is not a polynomial operation"]
+    fn casting_builtin_in_identity() {
+        let input = r#"namespace N(8);
+    col witness x;
+    int(x) = 1;
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
     #[test]
     fn intermediate() {
         let input = r#"namespace N(65536);
@@ -310,26 +736,152 @@ namespace T(65536);
         assert_eq!(formatted, expected);
     }
 
+    #[test]
+    fn intermediate_cycle() {
+        let input = r#"namespace N(65536);
+    col witness x;
+    col a = b + x;
+    col b = c;
+    col c = a;
+    col d = d;
+    col e = a;
+    e = x;
+"#;
+        let errors = process_pil_file_contents_with_limits::<GoldilocksField>(
+            input,
+            None,
+            &Limits::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "Cyclic dependency between intermediate columns: N.a (input:3) -> N.b (input:4) -> N.c (input:5) -> N.a (input:3)",
+                "Cyclic dependency between intermediate columns: N.d (input:6) -> N.d (input:6)",
+            ]
+        );
+    }
+
+    #[test]
+    fn intermediate_topological_order() {
+        let input = r#"namespace N(65536);
+    col witness x;
+    col a = b + x;
+    col c = x;
+    col b = x;
+    a = c;
+"#;
+        let expected = r#"namespace N(65536);
+    col witness x;
+    col c = N.x;
+    col b = N.x;
+    col a = (N.b + N.x);
+    N.a = N.c;
+"#;
+        // Analyze repeatedly, so that differently seeded hash maps are used.
+        for _ in 0..10 {
+            let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+            assert_eq!(formatted, expected);
+        }
+    }
+
     #[test]
     fn let_definitions() {
         let input = r#"constant %r = 65536;
+constant %z = 3;
 namespace N(%r);
     let x;
     let z = 2;
     let t = |i| i + z;
     let other = [1, z];
     let other_fun = |i, j| (i + 7, (|k| k - i));
+    let u = |i| i + %z * z;
 "#;
         let expected = r#"constant %r = 65536;
+constant %z = 3;
 namespace N(65536);
     col witness x;
-    constant z = 2;
+    let z = 2;
     col fixed t(i) { (i + N.z) };
     let other = [1, N.z];
     let other_fun = |i, j| ((i + 7), |k| (k - i));
+    col fixed u(i) { (i + (%z * N.z)) };
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, expected);
+        let reparsed = process_pil_file_contents::<GoldilocksField>(&formatted).to_string();
+        assert_eq!(reparsed, expected);
+    }
+
+    #[test]
+    fn indexed_let_arrays() {
+        let input = r#"namespace N(16);
+    col witness x, y;
+    let z = 2;
+    let other = [1, z];
+    let cols = [x, y];
+    let nested = [[1, 2], [3, z + 5]];
+    x = other[1 + 0];
+    cols[z - 1]' = nested[1][1 - 0] * cols[0];
+    col fixed F(i) { other[i % 2] + nested[z - 1][0] };
+"#;
+        let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    let z = 2;
+    let other = [1, N.z];
+    let cols = [N.x, N.y];
+    let nested = [[1, 2], [3, (N.z + 5)]];
+    N.x = 2;
+    N.y' = (7 * N.x);
+    col fixed F(i) { (N.other[(i % 2)] + N.nested[(N.z - 1)][0]) };
 "#;
         let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
         assert_eq!(formatted, expected);
+        let reparsed = process_pil_file_contents::<GoldilocksField>(&formatted).to_string();
+        assert_eq!(reparsed, expected);
+    }
+
+    #[test]
+    #[should_panic = "input:4: Error reducing expression to constraint:\nExpression: (N.x - N.nested[1][2])\nError: OutOfBounds(\"Index access out of bounds: Tried to access element 2 of array of size 2 in: N.nested[1][2].\")"]
+    fn indexed_let_array_out_of_bounds() {
+        let input = r#"namespace N(16);
+    col witness x;
+    let nested = [[1, 2], [3, 4]];
+    x = nested[1][2];
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    #[test]
+    #[should_panic = "input:4: Error reducing expression to constraint:\nExpression: (N.x - N.other[N.x])\nError: TypeError(\"Array index has to be a constant number, but N.x evaluates to N.x in: N.other[N.x].\")"]
+    fn indexed_let_array_non_constant_index() {
+        let input = r#"namespace N(16);
+    col witness x;
+    let other = [1, 2];
+    x = other[x];
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    #[test]
+    #[should_panic = "conflicts with the column N.z"]
+    fn constant_shadowing_column() {
+        let input = r#"constant %z = 3;
+namespace N(16);
+    col witness z;
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    #[test]
+    #[should_panic = "conflicts with the column N.z"]
+    fn column_shadowing_constant() {
+        let input = r#"namespace N(16);
+    col fixed z(i) { i };
+    constant %z = 3;
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
     }
 
     #[test]
@@ -344,6 +896,103 @@ namespace N(65536);
         assert_eq!(formatted, input);
     }
 
+    #[test]
+    fn reparse_array_query() {
+        let input = r#"namespace N(16);
+    col witness y[2](i, k) query ("hint", (i + k));
+    (N.y[0] - N.y[1]) = 0;
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, input);
+    }
+
+    #[test]
+    fn reparse_hint() {
+        let input = r#"namespace N(16);
+    col fixed X(i) { i };
+    col witness y hint |i| (N.X(i) + 1);
+    (N.y - N.X) = 1;
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, input);
+    }
+
+    #[test]
+    fn reparse_assert_range() {
+        let input = r#"namespace N(16);
+    #[assert_range(8)] col witness x;
+    #[assert_range(16)] col witness y[2];
+    col witness z;
+    (N.x - N.z) = 0;
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, input);
+    }
+
+    #[test]
+    fn reparse_value_ranges() {
+        let input = r#"namespace N(16);
+    col witness x in [0, 65535];
+    #[assert_range(8)] col witness y[2] in [1, 200];
+    (N.x - N.y[0]) = 0;
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, input);
+        let hex = input.replace("65535", "0xffff");
+        let formatted = process_pil_file_contents::<GoldilocksField>(&hex).to_string();
+        assert_eq!(formatted, input);
+    }
+
+    #[test]
+    #[should_panic = "The range in [0, 18446744069414584321] of N.x does not fit into the field."]
+    fn value_range_outside_of_field() {
+        let input = r#"namespace N(16);
+    col witness x in [0, 0xffffffff00000001];
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    #[test]
+    fn reparse_column_roles() {
+        let input = r#"namespace N(16);
+    #[role(first_row)] col fixed FIRST = [1] + [0]*;
+    #[role(last_row)] col fixed LAST(i) { (i == 15) };
+    #[role(first_row)] col fixed EXTERNAL;
+    col witness x;
+    (N.FIRST * (N.x - 1)) = 0;
+    (N.LAST * N.x) = 0;
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, input);
+    }
+
+    #[test]
+    #[should_panic = "#[role(first_row)] can only be used on fixed columns that are not arrays, but N.x is not one."]
+    fn role_on_witness_column() {
+        let input = r#"namespace N(16);
+    #[role(first_row)] col witness x;
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unknown_column_attribute() {
+        let input = r#"namespace N(16);
+    #[assert_bits(8)] col witness x;
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    #[test]
+    #[should_panic = "The query of an array has to take the row and the array index"]
+    fn array_query_without_index() {
+        let input = r#"namespace N(16);
+    col witness y[2](i) query ("hint", i);
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
     #[test]
     #[should_panic = "Operator - not supported on types"]
     fn no_direct_array_references() {
@@ -401,7 +1050,7 @@ namespace N(65536);
     on_regular_row(constrain_equal_expr(y', x + y)) = 0;
     "#;
         let expected = r#"namespace N(16);
-    constant last_row = 15;
+    let last_row = 15;
     col fixed ISLAST(i) { match i { N.last_row => 1, _ => 0, } };
     col witness x;
     col witness y;
@@ -425,7 +1074,7 @@ namespace N(65536);
     next_is_seven(y) = 0;
     "#;
         let expected = r#"namespace N(16);
-    constant last_row = 15;
+    let last_row = 15;
     col fixed ISLAST(i) { match i { N.last_row => 1, _ => 0, } };
     col witness x;
     col witness y;
@@ -447,7 +1096,7 @@ namespace N(65536);
     x - ISLAST = 0;
     "#;
         let expected = r#"namespace N(16);
-    constant last_row = 15;
+    let last_row = 15;
     col fixed ISLAST(i) { match i { N.last_row => 1, _ => 0, } };
     col witness x;
     col witness y;
@@ -457,4 +1106,581 @@ namespace N(65536);
         let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
         assert_eq!(formatted, expected);
     }
+
+    #[test]
+    fn two_dimensional_table() {
+        let input = r#"namespace N(16);
+    col fixed XOR(a, b) -> c { a ^ b } over (4, 4);
+    let x;
+    let y;
+    let z;
+    { x, y, z } in XOR;
+"#;
+        let expected = r#"namespace N(16);
+    col fixed XOR_a(i) { ((i / 4) % 4) };
+    col fixed XOR_b(i) { (i % 4) };
+    col fixed XOR_c(i) { (((i / 4) % 4) ^ (i % 4)) };
+    col witness x;
+    col witness y;
+    col witness z;
+    { N.x, N.y, N.z } in { N.XOR_a, N.XOR_b, N.XOR_c };
+"#;
+        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    #[should_panic = "Table XOR has 4 x 8 entries, which exceeds the degree 16."]
+    fn two_dimensional_table_too_large() {
+        let input = r#"namespace N(16);
+    col fixed XOR(a, b) -> c { a ^ b } over (4, 8);
+"#;
+        process_pil_file_contents::<GoldilocksField>(input);
+    }
+
+    /// Analyzes `input` with `limits` and asserts that it does not take too long.
+    fn analyze_within_time_bound(
+        input: &str,
+        limits: &Limits,
+    ) -> Result<Analyzed<GoldilocksField>, Vec<String>> {
+        let start = std::time::Instant::now();
+        let result = process_pil_file_contents_with_limits(input, None, limits);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        result
+    }
+
+    #[test]
+    fn deeply_nested_expression() {
+        let depth = 100_000;
+        let input = format!(
+            "namespace N(16);\n    col witness x;\n    x = {}x{};\n",
+            "-(".repeat(depth),
+            ")".repeat(depth)
+        );
+        assert_eq!(
+            analyze_within_time_bound(&input, &Limits::default()).unwrap_err(),
+            vec!["Expression nesting depth exceeds the limit of 1000.".to_string()]
+        );
+    }
+
+    #[test]
+    fn long_sum() {
+        let input = format!(
+            "namespace N(16);\n    col witness x;\n    x = {};\n",
+            vec!["x"; 100_000].join(" + ")
+        );
+        assert_eq!(
+            analyze_within_time_bound(&input, &Limits::default()).unwrap_err(),
+            vec!["Expression nesting depth exceeds the limit of 1000.".to_string()]
+        );
+    }
+
+    #[test]
+    fn nested_expression_within_limit() {
+        let input = format!(
+            "namespace N(16);\n    col witness x;\n    x = {};\n",
+            vec!["x"; 99].join(" + ")
+        );
+        assert!(analyze_within_time_bound(&input, &Limits::default()).is_ok());
+        let limits = Limits {
+            max_expression_depth: 99,
+            ..Default::default()
+        };
+        assert!(analyze_within_time_bound(&input, &limits).is_err());
+    }
+
+    #[test]
+    fn too_many_statements() {
+        let input = format!(
+            "namespace N(16);\n{}",
+            (0..100)
+                .map(|i| format!("    col witness x{i};\n"))
+                .collect::<String>()
+        );
+        let limits = Limits {
+            max_statements: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            analyze_within_time_bound(&input, &limits).unwrap_err(),
+            vec!["Number of statements exceeds the limit of 10.".to_string()]
+        );
+    }
+
+    #[test]
+    fn too_many_cells() {
+        let input = "namespace N(2**62);\n    col witness x;\n    col fixed F = [1]*;\n";
+        assert_eq!(
+            analyze_within_time_bound(input, &Limits::default()).unwrap_err(),
+            vec![
+                "The degree 4611686018427387904 times the number of columns 2 exceeds the limit of 68719476736 cells."
+                    .to_string()
+            ]
+        );
+        let input = "namespace N(2**62);\n    col witness x[1000000];\n";
+        assert!(analyze_within_time_bound(input, &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn add_columns() {
+        let input = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness y[2];
+    N.x' = N.y[1];
+"#;
+        let mut analyzed = process_pil_file_contents::<GoldilocksField>(input);
+        let z = analyzed.add_committed_column("N.z");
+        let last = analyzed.add_fixed_column(
+            "N.LAST",
+            FunctionValueDefinition::Array(vec![
+                RepeatedArray::new(vec![Expression::Number(0.into())], 7),
+                RepeatedArray::new(vec![Expression::Number(1.into())], 1),
+            ]),
+        );
+        assert_eq!(
+            z,
+            PolyID {
+                id: 3,
+                ptype: PolynomialType::Committed
+            }
+        );
+        assert_eq!(
+            last,
+            PolyID {
+                id: 1,
+                ptype: PolynomialType::Constant
+            }
+        );
+        let reference = |name: &str, poly_id| {
+            AlgebraicExpression::Reference(AlgebraicReference {
+                name: name.to_string(),
+                poly_id,
+                next: false,
+            })
+        };
+        let source = SourceRef {
+            file: "generated".to_string(),
+            line: 1,
+        };
+        analyzed.append_polynomial_identity(
+            reference("N.LAST", last)
+                * (reference("N.z", z)
+                    - reference(
+                        "N.x",
+                        PolyID {
+                            id: 0,
+                            ptype: PolynomialType::Committed,
+                        },
+                    )),
+            source.clone(),
+        );
+        assert_eq!(analyzed.identities.last().unwrap().source, source);
+
+        let expected = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness y[2];
+    N.x' = N.y[1];
+    col witness z;
+    col fixed LAST = [0]* + [1];
+    (N.LAST * (N.z - N.x)) = 0;
+"#;
+        assert_eq!(analyzed.to_string(), expected);
+        let reparsed = process_pil_file_contents::<GoldilocksField>(&analyzed.to_string());
+        assert_eq!(reparsed.to_string(), expected);
+        assert_eq!(PolyID::from(&reparsed.definitions["N.z"].0), z);
+        assert_eq!(PolyID::from(&reparsed.definitions["N.LAST"].0), last);
+    }
+
+    #[test]
+    fn append_identity_after_removal() {
+        let input = "namespace N(8);\n    col witness x;\n    N.x = 1;\n    N.x = 2;\n";
+        let mut analyzed = process_pil_file_contents::<GoldilocksField>(input);
+        analyzed.remove_identities(&[0].into());
+        let x = AlgebraicExpression::Reference(AlgebraicReference {
+            name: "N.x".to_string(),
+            poly_id: PolyID {
+                id: 0,
+                ptype: PolynomialType::Committed,
+            },
+            next: false,
+        });
+        analyzed.append_polynomial_identity(
+            x * AlgebraicExpression::Number(3.into()),
+            SourceRef::default(),
+        );
+        assert_eq!(
+            analyzed.to_string(),
+            "namespace N(8);\n    col witness x;\n    N.x = 2;\n    (N.x * 3) = 0;\n"
+        );
+    }
+
+    #[test]
+    fn column_dependency_graph() {
+        let input = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y, z;
+    x' = x + y;
+    FIRST * (z - y) = 0;
+"#;
+        let analyzed = process_pil_file_contents::<GoldilocksField>(input);
+        let graph = analyzed.column_dependency_graph();
+        let witness = |id| PolyID {
+            id,
+            ptype: PolynomialType::Committed,
+        };
+        let first = PolyID {
+            id: 0,
+            ptype: PolynomialType::Constant,
+        };
+        let (x, y, z) = (witness(0), witness(1), witness(2));
+        let dependencies = |poly_id| {
+            let d = &graph.columns[&poly_id];
+            assert_eq!(d.namespace, "N");
+            (
+                d.name.as_str(),
+                d.identities.iter().cloned().collect::<Vec<_>>(),
+                d.same_row.iter().cloned().collect::<Vec<_>>(),
+                d.next_row.iter().cloned().collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(graph.columns.len(), 4);
+        assert_eq!(dependencies(x), ("N.x", vec![0], vec![y], vec![]));
+        assert_eq!(
+            dependencies(y),
+            ("N.y", vec![0, 1], vec![x, first, z], vec![x])
+        );
+        assert_eq!(dependencies(z), ("N.z", vec![1], vec![first, y], vec![]));
+        assert_eq!(
+            dependencies(first),
+            ("N.FIRST", vec![1], vec![y, z], vec![])
+        );
+    }
+
+    #[test]
+    fn unconstrained_witnesses() {
+        let input = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, unused, a, sel, b, flag, c;
+    x' = x * FIRST;
+    sel { a } in { FIRST };
+    { b } in { FIRST };
+    { flag } in { FIRST };
+    flag { c } is { FIRST };
+"#;
+        let analyzed = process_pil_file_contents::<GoldilocksField>(input);
+        let witness = |id| PolyID {
+            id,
+            ptype: PolynomialType::Committed,
+        };
+        assert_eq!(
+            analyzed.unconstrained_witnesses_with_reasons(),
+            vec![
+                (
+                    "N.unused".to_string(),
+                    witness(1),
+                    UnconstrainedReason::Unreferenced
+                ),
+                (
+                    "N.a".to_string(),
+                    witness(2),
+                    UnconstrainedReason::OnlyInDisabledLookups
+                ),
+                (
+                    "N.sel".to_string(),
+                    witness(3),
+                    UnconstrainedReason::OnlyInDisabledLookups
+                ),
+            ]
+        );
+        assert_eq!(
+            analyzed.unconstrained_witnesses(),
+            vec![witness(1), witness(2), witness(3)]
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let input = r#"namespace Main(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y, sel, a[2];
+    col inv = x * y;
+    x' = inv * x + FIRST;
+    x * y ** 3 = 0;
+    sel { x } in { Byte.X };
+    sel { y } in { Byte.X };
+    sel { x } in { Byte.X };
+    { a[0] } is { Mem.m };
+namespace Byte(16);
+    col fixed X(i) { i };
+namespace Mem(16);
+    col witness m;
+"#;
+        let stats = process_pil_file_contents::<GoldilocksField>(input).stats();
+        assert_eq!(stats.degree, 16);
+        let namespace = |witness, fixed, intermediate| NamespaceStats {
+            witness,
+            fixed,
+            intermediate,
+        };
+        assert_eq!(
+            stats.namespaces.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("Byte".to_string(), namespace(0, 1, 0)),
+                ("Main".to_string(), namespace(5, 1, 1)),
+                ("Mem".to_string(), namespace(1, 0, 0)),
+            ]
+        );
+        assert_eq!(
+            stats.identities.into_iter().collect::<Vec<_>>(),
+            vec![
+                (IdentityKind::Polynomial, 2),
+                (IdentityKind::Plookup, 3),
+                (IdentityKind::Permutation, 1),
+            ]
+        );
+        let max_degree = stats.max_degree.unwrap();
+        assert_eq!(max_degree.degree, 4);
+        assert_eq!(max_degree.identity, 1);
+        assert_eq!(max_degree.source.line, 6);
+        // The duplicate lookup is only counted once.
+        assert_eq!(
+            stats.lookups.into_iter().collect::<Vec<_>>(),
+            vec![("Byte".to_string(), 2), ("Mem".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    #[should_panic = "Symbol N.x already exists."]
+    fn add_duplicate_column() {
+        let input = "namespace N(8);\n    col witness x;\n";
+        process_pil_file_contents::<GoldilocksField>(input).add_committed_column("N.x");
+    }
+
+    /// Writes a main file including the range checks of the standard library, next
+    /// to local files that have the names of the standard library's files.
+    fn std_include_files() -> mktemp::Temp {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        fs::write(
+            dir.join("main.pil"),
+            "namespace N(256);\ninclude \"std::range_check.pil\";\ncol witness x;\n{ x } in { BYTE };\n",
+        )
+        .unwrap();
+        let local = "pol constant BYTE(i) { i & 0xf };\n";
+        fs::write(dir.join("range_check.pil"), local).unwrap();
+        fs::create_dir(dir.join("std")).unwrap();
+        fs::write(dir.join("std").join("range_check.pil"), local).unwrap();
+        dir
+    }
+
+    fn fixed_columns_with_std(dir: &Path, std: StdLibrary) -> Vec<String> {
+        let mut analyzer = PILAnalyzer::<GoldilocksField>::new().with_std_library(std);
+        analyzer.process_file(&dir.join("main.pil"));
+        analyzer
+            .finish()
+            .unwrap()
+            .constant_polys_in_source_order()
+            .into_iter()
+            .map(|(sym, _)| sym.absolute_name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn include_std() {
+        let dir = std_include_files();
+        assert_eq!(
+            fixed_columns_with_std(&dir, StdLibrary::Embedded),
+            vec!["N.BYTE", "N.U16"]
+        );
+    }
+
+    #[test]
+    fn include_std_from_directory() {
+        let dir = std_include_files();
+        // Only an explicitly requested directory replaces the embedded standard library.
+        assert_eq!(
+            fixed_columns_with_std(&dir, StdLibrary::Directory(dir.join("std"))),
+            vec!["N.BYTE"]
+        );
+    }
+
+    #[test]
+    #[should_panic = "Cannot include std::missing.pil: The standard library has no file `missing.pil`."]
+    fn include_missing_std_file() {
+        process_pil_file_contents::<GoldilocksField>("include \"std::missing.pil\";");
+    }
+
+    #[test]
+    fn include_as() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        fs::write(
+            dir.join("machine.pil"),
+            r#"let K = 2;
+namespace Double(%N);
+    col witness x, y;
+    y = K * x;
+    Double.x' = Table.X;
+    public first = y(0);
+namespace Table(%N);
+    col fixed X(i) { i };
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.pil"),
+            r#"constant %N = 8;
+include "machine.pil" as First;
+include "machine.pil" as Second;
+include "machine.pil" as First;
+namespace Main(%N);
+    col witness a;
+    { a, a } in { First_Double.x, Second_Double.y };
+"#,
+        )
+        .unwrap();
+        let analyzed = crate::analyze::<GoldilocksField>(&dir.join("main.pil"));
+        let expected = r#"constant %N = 8;
+namespace First_Global(8);
+    let K = 2;
+namespace First_Double(8);
+    col witness x;
+    col witness y;
+    First_Double.y = (2 * First_Double.x);
+    First_Double.x' = First_Table.X;
+    public First_first = First_Double.y(0);
+namespace First_Table(8);
+    col fixed X(i) { i };
+namespace Second_Global(8);
+    let K = 2;
+namespace Second_Double(8);
+    col witness x;
+    col witness y;
+    Second_Double.y = (2 * Second_Double.x);
+    Second_Double.x' = Second_Table.X;
+    public Second_first = Second_Double.y(0);
+namespace Second_Table(8);
+    col fixed X(i) { i };
+namespace Main(8);
+    col witness a;
+    { Main.a, Main.a } in { First_Double.x, Second_Double.y };
+"#;
+        assert_eq!(analyzed.to_string(), expected);
+        assert_eq!(analyzed.identities.len(), 5);
+        assert_eq!(
+            analyzed.definitions["Second_Global.K"].0.id,
+            analyzed.definitions["First_Global.K"].0.id + 1
+        );
+    }
+
+    #[test]
+    #[should_panic = "The constant %K cannot be defined in the instance First"]
+    fn include_as_with_constant() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        fs::write(dir.join("machine.pil"), "constant %K = 2;\n").unwrap();
+        fs::write(dir.join("main.pil"), "include \"machine.pil\" as First;\n").unwrap();
+        crate::analyze::<GoldilocksField>(&dir.join("main.pil"));
+    }
+
+    #[test]
+    #[should_panic = "all namespaces must have the same degree"]
+    fn include_as_checks_degree() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        fs::write(
+            dir.join("machine.pil"),
+            "namespace M(16);
+col witness x;
+",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.pil"),
+            "namespace Main(8);
+include \"machine.pil\" as First;
+",
+        )
+        .unwrap();
+        crate::analyze::<GoldilocksField>(&dir.join("main.pil"));
+    }
+
+    fn binary_roundtrip<T: FieldElement>(analyzed: &Analyzed<T>) -> Result<Analyzed<T>, String> {
+        let mut artifact = vec![];
+        analyzed.write_binary(&mut artifact).unwrap();
+        Analyzed::read_binary(artifact.as_slice())
+    }
+
+    #[test]
+    fn binary_artifact_roundtrip() {
+        let test_data = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_data");
+        let mut files = vec![test_data.join("polygon-hermez/main.pil")];
+        for dir in ["pil", "pil/book"] {
+            files.extend(
+                fs::read_dir(test_data.join(dir))
+                    .unwrap()
+                    .map(|entry| entry.unwrap().path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "pil")),
+            );
+        }
+        for file in files {
+            let analyzed = crate::analyze::<GoldilocksField>(&file);
+            let read = binary_roundtrip(&analyzed).unwrap();
+            assert_eq!(read.to_string(), analyzed.to_string(), "{}", file.display());
+        }
+    }
+
+    #[test]
+    fn binary_artifact_is_deterministic() {
+        let input = "namespace N(16); col witness a, b, c; col fixed F = [1, 2]*; pol d = a + b; a = b * F; public p = c(2);";
+        let write = || {
+            let mut artifact = vec![];
+            process_pil_file_contents::<GoldilocksField>(input)
+                .write_binary(&mut artifact)
+                .unwrap();
+            artifact
+        };
+        assert_eq!(write(), write());
+    }
+
+    #[test]
+    fn binary_artifact_mismatch() {
+        let analyzed = process_pil_file_contents::<GoldilocksField>(
+            "namespace N(16); col witness a; a = a * a;",
+        );
+        let mut artifact = vec![];
+        analyzed.write_binary(&mut artifact).unwrap();
+
+        let error = Analyzed::<number::Bn254Field>::read_binary(artifact.as_slice()).unwrap_err();
+        assert!(error.contains("is for the field with modulus"), "{error}");
+
+        // The format version follows the magic bytes.
+        artifact[8] += 1;
+        let error = Analyzed::<GoldilocksField>::read_binary(artifact.as_slice()).unwrap_err();
+        assert!(error.contains("has format version 3"), "{error}");
+
+        let error = Analyzed::<GoldilocksField>::read_binary(&b"not powdr"[..]).unwrap_err();
+        assert_eq!(error, "Not an analyzed PIL artifact.");
+    }
+
+    #[test]
+    fn binary_artifact_inconsistent() {
+        let mut analyzed = process_pil_file_contents::<GoldilocksField>(
+            "namespace N(16); col witness a, b; a = b;",
+        );
+        analyzed.source_order.pop();
+        let error = binary_roundtrip(&analyzed).unwrap_err();
+        assert_eq!(
+            error,
+            "Inconsistent analyzed PIL artifact: The source order does not refer to every statement."
+        );
+
+        let mut analyzed = process_pil_file_contents::<GoldilocksField>(
+            "namespace N(16); col witness a, b; a = b;",
+        );
+        analyzed.definitions.get_mut("N.b").unwrap().0.id = 2;
+        let error = binary_roundtrip(&analyzed).unwrap_err();
+        assert_eq!(
+            error,
+            "Inconsistent analyzed PIL artifact: The witness column N.b has ID 2, expected 1."
+        );
+    }
 }
@@ -0,0 +1,683 @@
+//! Renaming of symbols in a PIL file and the files it includes.
+//!
+//! The parsed statements do not keep the locations of references, so the renaming
+//! works on the tokens of the sources: only the identifiers that refer to the renamed
+//! symbol are replaced, formatting and comments are kept as they are.
+//! References are resolved with the rules of the analyzer, with the exception that
+//! `Global` symbols are also found if they are defined after the reference.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ast::analyzed::{Analyzed, SourceRef};
+use itertools::Itertools;
+use number::FieldElement;
+use parser_util::lines::{compute_line_starts, offset_to_line};
+use parser_util::std_library::std_include_path;
+
+/// Renames the symbol with the absolute name `from` to `to` in the PIL file `path`
+/// and in all files it includes. `to` can be given with or without the namespace
+/// of `from`, but the symbol cannot be moved to a different namespace.
+///
+/// Returns the new contents of the files that changed, without writing them.
+pub fn rename<T: FieldElement>(
+    path: &Path,
+    from: &str,
+    to: &str,
+) -> Result<Vec<(PathBuf, String)>, Vec<String>> {
+    if path.extension().map_or(false, |ext| ext == "asm") {
+        return Err(vec![
+            "Renaming is only supported for .pil files.".to_string()
+        ]);
+    }
+    let analyzed = crate::analyze::<T>(path);
+    let symbols = symbol_sources(&analyzed);
+    let to = checked_target_name(&symbols, from, to).map_err(|e| vec![e])?;
+
+    let mut renamer = Renamer {
+        symbols: symbols.keys().map(String::as_str).collect(),
+        from,
+        to_local: split_name(&to).1.to_string(),
+        namespace: "Global".to_string(),
+        visited: Default::default(),
+        changed_files: vec![],
+        errors: vec![],
+    };
+    renamer.process_file(path);
+    if renamer.errors.is_empty() {
+        Ok(renamer.changed_files)
+    } else {
+        Err(renamer.errors)
+    }
+}
+
+/// The absolute names of all symbols together with the location of their declaration.
+/// Global constants are named as they are referenced, i.e. `%name`.
+fn symbol_sources<T>(analyzed: &Analyzed<T>) -> HashMap<String, &SourceRef> {
+    analyzed
+        .definitions
+        .iter()
+        .map(|(name, (symbol, _))| (name, symbol))
+        .chain(
+            analyzed
+                .intermediate_columns
+                .iter()
+                .map(|(name, (symbol, _))| (name, symbol)),
+        )
+        .map(|(name, symbol)| {
+            let name = if symbol.is_global_constant() {
+                format!("%{name}")
+            } else {
+                name.clone()
+            };
+            (name, &symbol.source)
+        })
+        .collect()
+}
+
+/// Checks that `from` exists and that it can be renamed to `to` without changing
+/// the meaning of other references. Returns the absolute new name.
+fn checked_target_name(
+    symbols: &HashMap<String, &SourceRef>,
+    from: &str,
+    to: &str,
+) -> Result<String, String> {
+    let (namespace, from_local) = split_name(from);
+    if !symbols.contains_key(from) {
+        let candidates = symbols
+            .iter()
+            .filter(|(name, _)| split_name(name).1 == from_local)
+            .map(|(name, source)| format!("{name} ({})", location(source)))
+            .sorted()
+            .collect::<Vec<_>>();
+        return Err(if candidates.is_empty() {
+            format!("Symbol `{from}` not found.")
+        } else {
+            format!(
+                "Symbol `{from}` not found, please use one of the absolute names {}.",
+                candidates.join(", ")
+            )
+        });
+    }
+
+    let (to_namespace, to_local) = split_name(to);
+    if to_namespace.is_some() && to_namespace != namespace {
+        return Err(format!(
+            "Cannot rename `{from}` to `{to}`: symbols cannot be moved to another namespace."
+        ));
+    }
+    let is_constant = from_local.starts_with('%');
+    let identifier = if is_constant {
+        to_local.strip_prefix('%')
+    } else {
+        Some(to_local)
+    };
+    if !identifier.map_or(false, is_identifier) {
+        return Err(if is_constant {
+            format!("`{to}` is not a valid constant name.")
+        } else {
+            format!("`{to}` is not a valid identifier.")
+        });
+    }
+
+    let to = match namespace {
+        Some(namespace) => format!("{namespace}.{to_local}"),
+        None => to_local.to_string(),
+    };
+    if let Some(source) = symbols.get(to.as_str()) {
+        return Err(format!(
+            "Cannot rename `{from}` to `{to}`: `{to}` is already defined at {}.",
+            location(source)
+        ));
+    }
+    // Unqualified references are resolved to `Global` symbols first, so renaming
+    // a symbol to the name of a `Global` symbol (or the other way around) would
+    // change the meaning of existing references.
+    let shadowing = symbols
+        .iter()
+        .filter(|(name, _)| {
+            let (other_namespace, other_local) = split_name(name);
+            other_local == to_local
+                && (other_namespace == Some("Global") || namespace == Some("Global"))
+        })
+        .map(|(name, source)| format!("`{name}` at {}", location(source)))
+        .sorted()
+        .collect::<Vec<_>>();
+    if !shadowing.is_empty() {
+        return Err(format!(
+            "Cannot rename `{from}` to `{to}`: it would be confused with {}.",
+            shadowing.join(", ")
+        ));
+    }
+    Ok(to)
+}
+
+fn split_name(name: &str) -> (Option<&str>, &str) {
+    match name.rsplit_once('.') {
+        Some((namespace, local)) => (Some(namespace), local),
+        None => (None, name),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(is_identifier_char)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '@')
+}
+
+fn location(source: &SourceRef) -> String {
+    format!("{}:{}", source.file, source.line)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    /// An identifier or a constant name like `%N`.
+    Identifier,
+    Number,
+    String,
+    Punctuation,
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    start: usize,
+}
+
+/// Splits PIL source into tokens, skipping whitespace and comments.
+/// Every character that is not part of an identifier, number or string is a separate
+/// punctuation token.
+fn tokenize(contents: &str) -> Vec<Token> {
+    let bytes = contents.as_bytes();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while pos < contents.len() {
+        let rest = &contents[pos..];
+        let c = rest.chars().next().unwrap();
+        let (kind, len) = if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        } else if rest.starts_with("//") {
+            pos += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        } else if rest.starts_with("/*") {
+            pos += rest[2..].find("*/").map_or(rest.len(), |end| end + 4);
+            continue;
+        } else if c == '"' {
+            (
+                TokenKind::String,
+                rest[1..].find('"').map_or(rest.len(), |end| end + 2),
+            )
+        } else if c.is_ascii_alphabetic()
+            || c == '_'
+            || (c == '%'
+                && bytes
+                    .get(pos + 1)
+                    .map_or(false, |b| b.is_ascii_alphabetic() || *b == b'_'))
+        {
+            let len = rest[1..]
+                .find(|c| !is_identifier_char(c))
+                .map_or(rest.len(), |end| end + 1);
+            (TokenKind::Identifier, len)
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (TokenKind::Number, len)
+        } else {
+            (TokenKind::Punctuation, c.len_utf8())
+        };
+        tokens.push(Token {
+            kind,
+            text: &rest[..len],
+            start: pos,
+        });
+        pos += len;
+    }
+    tokens
+}
+
+/// Names that are local to a statement (parameters of fixed column definitions and
+/// queries) or to a lambda expression.
+struct Scope {
+    names: Vec<String>,
+    /// The bracket nesting depth of a lambda expression, `None` for statements.
+    lambda_depth: Option<usize>,
+}
+
+struct Renamer<'a> {
+    symbols: HashSet<&'a str>,
+    from: &'a str,
+    to_local: String,
+    namespace: String,
+    visited: HashSet<PathBuf>,
+    changed_files: Vec<(PathBuf, String)>,
+    errors: Vec<String>,
+}
+
+/// The positions of the tokens in a statement that need special treatment.
+#[derive(Default)]
+struct StatementHead {
+    /// Identifiers that declare a symbol.
+    declarations: HashSet<usize>,
+    /// Identifiers that do not refer to a symbol, like namespace or public names.
+    ignored: HashSet<usize>,
+    namespace: Option<String>,
+    local_names: Vec<String>,
+}
+
+impl<'a> Renamer<'a> {
+    fn process_file(&mut self, path: &Path) {
+        let path = path
+            .canonicalize()
+            .unwrap_or_else(|e| panic!("File {path:?} not found: {e}"));
+        if !self.visited.insert(path.clone()) {
+            return;
+        }
+        let contents = fs::read_to_string(&path).unwrap();
+        let tokens = tokenize(&contents);
+        let line_starts = compute_line_starts(&contents);
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+        let mut edits = vec![];
+        let mut head = StatementHead::default();
+        let mut scopes: Vec<Scope> = vec![];
+        let mut depth = 0;
+        let mut statement_start = true;
+        let mut i = 0;
+        while i < tokens.len() {
+            if statement_start {
+                head = statement_head(&tokens, i);
+                if !head.local_names.is_empty() {
+                    scopes.push(Scope {
+                        names: std::mem::take(&mut head.local_names),
+                        lambda_depth: None,
+                    });
+                }
+                statement_start = false;
+            }
+            let token = &tokens[i];
+            let text_at = |j: usize| tokens.get(j).map(|t| t.text);
+            match (token.kind, token.text) {
+                (TokenKind::Punctuation, "(" | "[" | "{") => depth += 1,
+                (TokenKind::Punctuation, ")" | "]" | "}") => {
+                    depth -= 1;
+                    while lambda_depth(&scopes).map_or(false, |d| d > depth) {
+                        scopes.pop();
+                    }
+                }
+                (TokenKind::Punctuation, ",") => {
+                    while lambda_depth(&scopes).map_or(false, |d| d >= depth) {
+                        scopes.pop();
+                    }
+                }
+                (TokenKind::Punctuation, ";") if depth == 0 => {
+                    scopes.clear();
+                    statement_start = true;
+                    if let Some(namespace) = head.namespace.take() {
+                        self.namespace = namespace;
+                    }
+                }
+                (TokenKind::Punctuation, "|") if i == 0 || !ends_operand(&tokens[i - 1]) => {
+                    // The parameters of a lambda expression.
+                    let mut end = i + 1;
+                    while tokens
+                        .get(end)
+                        .map_or(false, |t| t.kind == TokenKind::Identifier || t.text == ",")
+                    {
+                        end += 1;
+                    }
+                    if text_at(end) == Some("|") {
+                        scopes.push(Scope {
+                            names: tokens[i + 1..end]
+                                .iter()
+                                .filter(|t| t.kind == TokenKind::Identifier)
+                                .map(|t| t.text.to_string())
+                                .collect(),
+                            lambda_depth: Some(depth),
+                        });
+                        i = end;
+                    }
+                }
+                (TokenKind::Identifier, "over") if depth == 0 => scopes.clear(),
+                (TokenKind::Identifier, "include")
+                    if tokens.get(i + 1).map(|t| t.kind) == Some(TokenKind::String) =>
+                {
+                    let include = &tokens[i + 1].text[1..tokens[i + 1].text.len() - 1];
+                    // Files of the standard library are not renamed in.
+                    if std_include_path(include).is_none() {
+                        self.process_file(&path.parent().unwrap().join(include));
+                    }
+                    i += 1;
+                }
+                (TokenKind::Identifier, name) => {
+                    if head.declarations.contains(&i) {
+                        if self.resolve_decl(name) == self.from {
+                            edits.push(token);
+                        }
+                    } else if text_at(i + 1) == Some(".")
+                        && tokens.get(i + 2).map(|t| t.kind) == Some(TokenKind::Identifier)
+                    {
+                        if format!("{name}.{}", tokens[i + 2].text) == self.from {
+                            edits.push(&tokens[i + 2]);
+                        }
+                        i += 2;
+                    } else if !head.ignored.contains(&i)
+                        // References to public values start with `:`.
+                        && (i == 0 || tokens[i - 1].text != ":")
+                        && !is_local(&scopes, name)
+                        && self.resolve_ref(name) == self.from
+                    {
+                        if is_local(&scopes, &self.to_local) {
+                            self.errors.push(format!(
+                                "Cannot rename `{}` to `{}` at {file_name}:{}: `{}` is a parameter there.",
+                                self.from,
+                                self.to_local,
+                                offset_to_line(token.start, &line_starts),
+                                self.to_local
+                            ));
+                        }
+                        edits.push(token);
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        if !edits.is_empty() {
+            let mut renamed = String::with_capacity(contents.len());
+            let mut pos = 0;
+            for token in edits {
+                renamed.push_str(&contents[pos..token.start]);
+                renamed.push_str(&self.to_local);
+                pos = token.start + token.text.len();
+            }
+            renamed.push_str(&contents[pos..]);
+            self.changed_files.push((path, renamed));
+        }
+    }
+
+    fn resolve_decl(&self, name: &str) -> String {
+        if name.starts_with('%') {
+            name.to_string()
+        } else {
+            format!("{}.{name}", self.namespace)
+        }
+    }
+
+    fn resolve_ref(&self, name: &str) -> String {
+        let global = format!("Global.{name}");
+        if !name.starts_with('%') && self.symbols.contains(global.as_str()) {
+            global
+        } else {
+            self.resolve_decl(name)
+        }
+    }
+}
+
+/// The depth of the innermost scope if it is a lambda expression.
+fn lambda_depth(scopes: &[Scope]) -> Option<usize> {
+    scopes.last().and_then(|s| s.lambda_depth)
+}
+
+fn is_local(scopes: &[Scope], name: &str) -> bool {
+    scopes.iter().any(|s| s.names.iter().any(|n| n == name))
+}
+
+/// Keywords that can precede the start of an expression.
+const EXPRESSION_KEYWORDS: [&str; 7] = ["in", "is", "query", "if", "else", "match", "connect"];
+
+/// Returns true if `token` can be the last token of an operand, i.e. a `|` following
+/// it is a binary operator and not the start of a lambda expression.
+fn ends_operand(token: &Token) -> bool {
+    match token.kind {
+        TokenKind::Identifier => !EXPRESSION_KEYWORDS.contains(&token.text),
+        TokenKind::Number | TokenKind::String => true,
+        TokenKind::Punctuation => matches!(token.text, ")" | "]" | "}" | "'"),
+    }
+}
+
+/// Determines the declarations and parameters of the statement starting at `start`.
+fn statement_head(tokens: &[Token], start: usize) -> StatementHead {
+    let text_at = |j: usize| tokens.get(j).map(|t| t.text);
+    let mut head = StatementHead::default();
+    // Skip the attribute of an identity.
+    let start = if text_at(start) == Some("#") && text_at(start + 1) == Some("[") {
+        head.ignored.insert(start + 2);
+        start + 4
+    } else {
+        start
+    };
+    match text_at(start) {
+        Some("namespace") => {
+            head.ignored.insert(start + 1);
+            head.namespace = text_at(start + 1).map(|n| n.to_string());
+        }
+        Some("public") => {
+            head.ignored.insert(start + 1);
+        }
+        Some("let" | "constant") => {
+            head.declarations.insert(start + 1);
+        }
+        Some("pol" | "col") => match text_at(start + 1) {
+            Some("constant" | "fixed" | "commit" | "witness") => {
+                let mut i = start + 2;
+                loop {
+                    head.declarations.insert(i);
+                    i += 1;
+                    if text_at(i) == Some("[") {
+                        i = closing_bracket(tokens, i) + 1;
+                    }
+                    match text_at(i) {
+                        Some(",") => i += 1,
+                        Some("(") => {
+                            let end = closing_bracket(tokens, i);
+                            head.local_names = tokens[i + 1..end]
+                                .iter()
+                                .filter(|t| t.kind == TokenKind::Identifier)
+                                .map(|t| t.text.to_string())
+                                .collect();
+                            // The output name of a table definition is not a reference.
+                            if text_at(end + 1) == Some("-") && text_at(end + 2) == Some(">") {
+                                head.ignored.insert(end + 3);
+                            }
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            Some("public") => {
+                if text_at(start + 2) == Some("(") {
+                    head.declarations
+                        .insert(closing_bracket(tokens, start + 2) + 1);
+                }
+            }
+            _ => {
+                head.declarations.insert(start + 1);
+            }
+        },
+        _ => {}
+    }
+    head
+}
+
+/// Returns the index of the bracket closing the one at `open`, or the number of tokens
+/// if it is not closed.
+fn closing_bracket(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.text {
+            "(" | "[" | "{" if token.kind == TokenKind::Punctuation => depth += 1,
+            ")" | "]" | "}" if token.kind == TokenKind::Punctuation => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+
+    use super::*;
+
+    const MAIN: &str = r#"constant %N = 8;
+
+namespace Main(%N);
+    pol constant FIRST = [1] + [0]*;
+    // The counter starts at zero.
+    pol commit counter;
+    FIRST * counter = 0;
+    include "constraints.pil";
+
+namespace Other(%N);
+    pol commit x(i) query ("hint", Main.counter);
+    public out = Main.counter(%N - 1);
+    x = Main.counter;
+"#;
+
+    const CONSTRAINTS: &str = r#"pol commit step;
+(1 - FIRST') * (counter' - counter - step) = 0;
+col fixed INC(i) { i + 1 };
+"#;
+
+    /// Writes the test files to a new directory and returns the directory.
+    fn write_files(files: &[(&str, &str)]) -> mktemp::Temp {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn rename_in(
+        files: &[(&str, &str)],
+        from: &str,
+        to: &str,
+    ) -> Result<HashMap<String, String>, Vec<String>> {
+        let dir = write_files(files);
+        let renamed = rename::<GoldilocksField>(&dir.join(files[0].0), from, to)?;
+        Ok(renamed
+            .into_iter()
+            .map(|(path, contents)| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, contents)
+            })
+            .collect())
+    }
+
+    #[test]
+    fn rename_across_files() {
+        let files = [("main.pil", MAIN), ("constraints.pil", CONSTRAINTS)];
+        let dir = write_files(&files);
+        let main = dir.join("main.pil");
+        let original = crate::analyze::<GoldilocksField>(&main);
+
+        let renamed = rename::<GoldilocksField>(&main, "Main.counter", "Main.steps").unwrap();
+        assert_eq!(renamed.len(), 2);
+        for (path, contents) in &renamed {
+            fs::write(path, contents).unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(&main).unwrap(),
+            MAIN.replace("pol commit counter", "pol commit steps")
+                .replace("* counter", "* steps")
+                .replace("Main.counter", "Main.steps")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("constraints.pil")).unwrap(),
+            CONSTRAINTS.replace("counter", "steps")
+        );
+
+        let reanalyzed = crate::analyze::<GoldilocksField>(&main);
+        assert_eq!(
+            reanalyzed.to_string(),
+            original.to_string().replace("counter", "steps")
+        );
+    }
+
+    #[test]
+    fn parameters_are_not_renamed() {
+        let main = r#"namespace N(8);
+    pol commit i;
+    col fixed INC(i) { i + 1 };
+    col fixed T(j, i) -> v { j + i } over (2, 4);
+    let f = |i| i * 2;
+    i' = i + INC;
+"#;
+        let renamed = rename_in(&[("main.pil", main)], "N.i", "k").unwrap();
+        assert_eq!(
+            renamed["main.pil"],
+            main.replace("pol commit i", "pol commit k")
+                .replace("i' = i + INC", "k' = k + INC")
+        );
+    }
+
+    #[test]
+    fn rename_constant() {
+        let main = "constant %N = 8;\nnamespace N(%N);\n    pol commit x[%N];\n";
+        let renamed = rename_in(&[("main.pil", main)], "%N", "%len").unwrap();
+        assert_eq!(renamed["main.pil"], main.replace("%N", "%len"));
+        assert!(rename_in(&[("main.pil", main)], "%N", "len").is_err());
+    }
+
+    #[test]
+    fn collision() {
+        let files = [("main.pil", MAIN), ("constraints.pil", CONSTRAINTS)];
+        let err = rename_in(&files, "Main.counter", "step").unwrap_err();
+        assert_eq!(
+            err,
+            vec![
+                "Cannot rename `Main.counter` to `Main.step`: `Main.step` is already defined at constraints.pil:1."
+                    .to_string()
+            ]
+        );
+        let err = rename_in(&files, "Main.counter", "Other.counter").unwrap_err();
+        assert!(err[0].contains("another namespace"), "{err:?}");
+    }
+
+    #[test]
+    fn partial_match() {
+        let files = [("main.pil", MAIN), ("constraints.pil", CONSTRAINTS)];
+        let err = rename_in(&files, "counter", "steps").unwrap_err();
+        assert_eq!(
+            err,
+            vec![
+                "Symbol `counter` not found, please use one of the absolute names Main.counter (main.pil:6)."
+                    .to_string()
+            ]
+        );
+        let err = rename_in(&files, "Main.count", "steps").unwrap_err();
+        assert_eq!(err, vec!["Symbol `Main.count` not found.".to_string()]);
+    }
+
+    #[test]
+    fn capture_by_parameter() {
+        let main = r#"namespace N(8);
+    pol commit x;
+    pol commit y(i) query ("hint", i, x);
+"#;
+        let err = rename_in(&[("main.pil", main)], "N.x", "i").unwrap_err();
+        assert_eq!(
+            err,
+            vec!["Cannot rename `N.x` to `i` at main.pil:3: `i` is a parameter there.".to_string()]
+        );
+    }
+}
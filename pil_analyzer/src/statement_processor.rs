@@ -2,9 +2,10 @@ use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 
 use ast::parsed::{
-    self, FunctionDefinition, LambdaExpression, PilStatement, PolynomialName, SelectedExpressions,
+    self, ColumnAttribute, FunctionDefinition, LambdaExpression, PilStatement, PolynomialName,
+    SelectedExpressions, ValueRange,
 };
-use number::{DegreeType, FieldElement};
+use number::{BigInt, DegreeType, FieldElement};
 
 use ast::analyzed::{
     Expression, FunctionValueDefinition, Identity, IdentityKind, PolynomialType, PublicDeclaration,
@@ -92,12 +93,15 @@ where
 
     pub fn handle_statement(&mut self, statement: PilStatement<T>) -> Vec<PILItem<T>> {
         match statement {
-            PilStatement::Include(_, _) => {
+            PilStatement::Include(_, _, _) => {
                 panic!("Includes must be handled outside the statement processor.")
             }
             PilStatement::Namespace(_, _, _) => {
                 panic!("Namespaces must be handled outside the statement processor.")
             }
+            PilStatement::PolynomialConstantTableDefinition(_, _, _) => {
+                panic!("Tables must be handled outside the statement processor.")
+            }
             PilStatement::PolynomialDefinition(start, name, value) => self
                 .handle_symbol_definition(
                     start,
@@ -109,38 +113,67 @@ where
             PilStatement::PublicDeclaration(start, name, polynomial, array_index, index) => {
                 self.handle_public_declaration(start, name, polynomial, array_index, index)
             }
-            PilStatement::PolynomialConstantDeclaration(start, polynomials) => self
-                .handle_polynomial_declarations(start, polynomials, PolynomialType::Constant, None),
-            PilStatement::PolynomialConstantDefinition(start, name, definition) => self
-                .handle_symbol_definition(
-                    start,
-                    name,
-                    None,
-                    SymbolKind::Poly(PolynomialType::Constant),
-                    Some(definition),
-                ),
-            PilStatement::PolynomialCommitDeclaration(start, polynomials, None, public_info) => {
-                self.handle_polynomial_declarations(
-                    start,
-                    polynomials,
-                    PolynomialType::Committed,
-                    public_info,
+            PilStatement::PolynomialConstantDeclaration(start, attribute, polynomials) => {
+                with_column_attribute(
+                    self.handle_polynomial_declarations(
+                        start,
+                        polynomials,
+                        PolynomialType::Constant,
+                        None,
+                    ),
+                    attribute,
                 )
             }
+            PilStatement::PolynomialConstantDefinition(start, attribute, name, definition) => {
+                with_column_attribute(
+                    self.handle_symbol_definition(
+                        start,
+                        name,
+                        None,
+                        SymbolKind::Poly(PolynomialType::Constant),
+                        Some(definition),
+                    ),
+                    attribute,
+                )
+            }
+            PilStatement::PolynomialCommitDeclaration(
+                start,
+                attribute,
+                polynomials,
+                None,
+                public_info,
+                range,
+            ) => with_value_range(
+                with_column_attribute(
+                    self.handle_polynomial_declarations(
+                        start,
+                        polynomials,
+                        PolynomialType::Committed,
+                        public_info,
+                    ),
+                    attribute,
+                ),
+                range,
+            ),
             PilStatement::PolynomialCommitDeclaration(
                 start,
+                attribute,
                 mut polynomials,
                 Some(definition),
                 _,
+                _,
             ) => {
                 assert!(polynomials.len() == 1);
                 let name = polynomials.pop().unwrap();
-                self.handle_symbol_definition(
-                    start,
-                    name.name,
-                    name.array_size,
-                    SymbolKind::Poly(PolynomialType::Committed),
-                    Some(definition),
+                with_column_attribute(
+                    self.handle_symbol_definition(
+                        start,
+                        name.name,
+                        name.array_size,
+                        SymbolKind::Poly(PolynomialType::Committed),
+                        Some(definition),
+                    ),
+                    attribute,
                 )
             }
             PilStatement::ConstantDefinition(start, name, value) => {
@@ -148,9 +181,13 @@ where
                 if let Err(err) = self.evaluate_expression(value.clone()) {
                     panic!("Could not evaluate constant: {name} = {value}: {err:?}");
                 }
-                self.handle_symbol_definition(
+                // Constants declared with `%` are not namespaced.
+                let name = name
+                    .strip_prefix('%')
+                    .expect("The grammar only allows constant names of the form `%name`.");
+                self.define_symbol(
                     start,
-                    name,
+                    name.to_string(),
                     None,
                     SymbolKind::Constant(),
                     Some(FunctionDefinition::Expression(value)),
@@ -311,6 +348,19 @@ where
         array_size: Option<::ast::parsed::Expression<T>>,
         symbol_kind: SymbolKind,
         value: Option<FunctionDefinition<T>>,
+    ) -> Vec<PILItem<T>> {
+        let name = self.driver.resolve_decl(&name);
+        self.define_symbol(start, name, array_size, symbol_kind, value)
+    }
+
+    /// Defines a symbol with the given absolute name.
+    fn define_symbol(
+        &mut self,
+        start: usize,
+        name: String,
+        array_size: Option<::ast::parsed::Expression<T>>,
+        symbol_kind: SymbolKind,
+        value: Option<FunctionDefinition<T>>,
     ) -> Vec<PILItem<T>> {
         let source = self.driver.source_position_to_source_ref(start);
         let have_array_size = array_size.is_some();
@@ -318,17 +368,26 @@ where
             .map(|l| self.evaluate_expression(l).unwrap())
             .map(|l| l.to_degree());
         if length.is_some() {
-            assert!(value.is_none());
+            assert!(
+                value.is_none()
+                    || matches!(
+                        value,
+                        Some(FunctionDefinition::Query(..) | FunctionDefinition::Hint(..))
+                    )
+            );
         }
         let id = self.counters.dispense_symbol_id(symbol_kind, length);
-        let name = self.driver.resolve_decl(&name);
         let symbol = Symbol {
             id,
             source,
             absolute_name: name.clone(),
             kind: symbol_kind,
             length,
+            assert_range: None,
+            role: None,
+            range: None,
         };
+        self.check_constant_conflict(&symbol);
 
         let value = value.map(|v| match v {
             FunctionDefinition::Number(n) => FunctionValueDefinition::Number(n),
@@ -338,14 +397,22 @@ where
                 FunctionValueDefinition::Expression(self.process_expression(expr))
             }
             FunctionDefinition::Query(params, expr) => {
-                assert!(!have_array_size);
                 assert_eq!(symbol_kind, SymbolKind::Poly(PolynomialType::Committed));
+                // The query of an array receives the row and the index of the array element.
+                assert!(
+                    !have_array_size || params.len() == 2,
+                    "The query of an array has to take the row and the array index as parameters."
+                );
                 let body = Box::new(self.expression_processor().process_function(&params, expr));
                 FunctionValueDefinition::Query(Expression::LambdaExpression(LambdaExpression {
                     params,
                     body,
                 }))
             }
+            FunctionDefinition::Hint(expr) => {
+                assert_eq!(symbol_kind, SymbolKind::Poly(PolynomialType::Committed));
+                FunctionValueDefinition::Hint(self.process_expression(expr))
+            }
             FunctionDefinition::Array(value) => {
                 let size = value.solve(self.degree.unwrap());
                 let expression = self
@@ -361,6 +428,42 @@ where
         vec![PILItem::Definition(symbol, value)]
     }
 
+    /// Panics if there is a global constant `%name` and a column `name` in any namespace,
+    /// since references to them only differ in the `%`.
+    fn check_constant_conflict(&self, symbol: &Symbol) {
+        fn local_name(name: &str) -> &str {
+            name.rsplit_once('.').map_or(name, |(_, local)| local)
+        }
+        let definitions = self.driver.definitions();
+        let (constant, column) = if symbol.is_global_constant() {
+            let column = definitions.values().map(|(s, _)| s).find(|s| {
+                matches!(s.kind, SymbolKind::Poly(_))
+                    && local_name(&s.absolute_name) == symbol.absolute_name
+            });
+            (Some(symbol), column)
+        } else if matches!(symbol.kind, SymbolKind::Poly(_)) {
+            let constant = definitions
+                .get(local_name(&symbol.absolute_name))
+                .map(|(s, _)| s)
+                .filter(|s| s.is_global_constant());
+            (constant, Some(symbol))
+        } else {
+            (None, None)
+        };
+        if let (Some(constant), Some(column)) = (constant, column) {
+            panic!(
+                "The constant %{} ({}:{}) conflicts with the column {} ({}:{}): \
+                references to them only differ in the `%`. Please rename one of them.",
+                constant.absolute_name,
+                constant.source.file,
+                constant.source.line,
+                column.absolute_name,
+                column.source.file,
+                column.source.line
+            );
+        }
+    }
+
     fn handle_public_declaration(
         &mut self,
         start: usize,
@@ -412,3 +515,56 @@ where
             .process_selected_expressions(expr)
     }
 }
+
+/// Applies the attribute of a column declaration to the declared columns.
+fn with_column_attribute<T>(
+    mut items: Vec<PILItem<T>>,
+    attribute: Option<ColumnAttribute>,
+) -> Vec<PILItem<T>> {
+    let Some(attribute) = attribute else {
+        return items;
+    };
+    for item in &mut items {
+        if let PILItem::Definition(symbol, _) = item {
+            match attribute {
+                ColumnAttribute::AssertRange(bits) => {
+                    assert!(
+                        symbol.kind == SymbolKind::Poly(PolynomialType::Committed),
+                        "{attribute} can only be used on witness columns, but {} is not one.",
+                        symbol.absolute_name
+                    );
+                    symbol.assert_range = Some(bits);
+                }
+                ColumnAttribute::Role(role) => {
+                    assert!(
+                        symbol.kind == SymbolKind::Poly(PolynomialType::Constant)
+                            && symbol.length.is_none(),
+                        "{attribute} can only be used on fixed columns that are not arrays, but {} is not one.",
+                        symbol.absolute_name
+                    );
+                    symbol.role = Some(role);
+                }
+            }
+        }
+    }
+    items
+}
+
+fn with_value_range<T: FieldElement>(
+    mut items: Vec<PILItem<T>>,
+    range: Option<ValueRange>,
+) -> Vec<PILItem<T>> {
+    for item in &mut items {
+        if let PILItem::Definition(symbol, _) = item {
+            if let Some(range) = &range {
+                assert!(
+                    range.max < T::modulus().to_arbitrary_integer(),
+                    "The range {range} of {} does not fit into the field.",
+                    symbol.absolute_name
+                );
+            }
+            symbol.range = range.clone();
+        }
+    }
+    items
+}
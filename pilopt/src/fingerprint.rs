@@ -0,0 +1,215 @@
+//! Structural fingerprints of identities and expressions.
+//!
+//! A fingerprint identifies an expression up to the simplifications of the optimizer,
+//! the order of the operands of additions and multiplications, and the sides of polynomial
+//! identities. References are replaced by a key provided by the caller, which allows
+//! comparing identities by column, or with the names of the columns abstracted.
+//!
+//! Fingerprints are built from a canonical textual form of the expression and
+//! are therefore free of collisions: two expressions have the same fingerprint
+//! exactly if their canonical forms are equal.
+
+use std::fmt::{self, Display, Formatter};
+
+use ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
+    Identity, IdentityKind,
+};
+use ast::parsed::SelectedExpressions;
+use number::FieldElement;
+
+use crate::simplify_expression;
+
+/// The canonical form of an expression or identity, to be compared or hashed.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(String);
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Returns the fingerprint of `identity`, where every reference is replaced by
+/// `reference_key(reference)` (and a `'` if it is a next reference).
+pub fn identity_fingerprint<T: FieldElement>(
+    identity: &Identity<AlgebraicExpression<T>>,
+    reference_key: &impl Fn(&AlgebraicReference) -> String,
+) -> Fingerprint {
+    let canonical = match identity.kind {
+        IdentityKind::Polynomial => {
+            let expression = simplify_expression(identity.expression_for_poly_id().clone());
+            // `a = b` and `b = a` are the same constraint.
+            let mut sides = match expression {
+                AlgebraicExpression::BinaryOperation(left, AlgebraicBinaryOperator::Sub, right) => {
+                    [
+                        canonical(&left, reference_key),
+                        canonical(&right, reference_key),
+                    ]
+                }
+                e => [canonical(&e, reference_key), "0".to_string()],
+            };
+            sides.sort();
+            format!("(= {} {})", sides[0], sides[1])
+        }
+        kind => format!(
+            "({kind:?} {} {})",
+            canonical_selected(&identity.left, reference_key),
+            canonical_selected(&identity.right, reference_key)
+        ),
+    };
+    Fingerprint(canonical)
+}
+
+/// Returns the fingerprint of `expression`, where every reference is replaced by
+/// `reference_key(reference)` (and a `'` if it is a next reference).
+pub fn expression_fingerprint<T: FieldElement>(
+    expression: &AlgebraicExpression<T>,
+    reference_key: &impl Fn(&AlgebraicReference) -> String,
+) -> Fingerprint {
+    Fingerprint(canonical(
+        &simplify_expression(expression.clone()),
+        reference_key,
+    ))
+}
+
+fn canonical_selected<T: FieldElement>(
+    selected: &SelectedExpressions<AlgebraicExpression<T>>,
+    reference_key: &impl Fn(&AlgebraicReference) -> String,
+) -> String {
+    let canonical_simplified =
+        |e: &AlgebraicExpression<T>| canonical(&simplify_expression(e.clone()), reference_key);
+    format!(
+        "({} [{}])",
+        selected
+            .selector
+            .as_ref()
+            .map(canonical_simplified)
+            .unwrap_or_else(|| "1".to_string()),
+        selected
+            .expressions
+            .iter()
+            .map(canonical_simplified)
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+fn canonical<T: FieldElement>(
+    e: &AlgebraicExpression<T>,
+    reference_key: &impl Fn(&AlgebraicReference) -> String,
+) -> String {
+    match e {
+        AlgebraicExpression::Reference(reference) => {
+            let next = if reference.next { "'" } else { "" };
+            format!("{}{next}", reference_key(reference))
+        }
+        AlgebraicExpression::PublicReference(name) => format!(":{name}"),
+        AlgebraicExpression::Number(n) => n.to_string(),
+        AlgebraicExpression::BinaryOperation(
+            _,
+            op @ (AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Mul),
+            _,
+        ) => {
+            // Flatten nested additions or multiplications so that the operands
+            // can be sorted independently of the parenthesization.
+            let mut operands = vec![];
+            collect_operands(e, *op, &mut operands);
+            let mut operands = operands
+                .into_iter()
+                .map(|operand| canonical(operand, reference_key))
+                .collect::<Vec<_>>();
+            operands.sort();
+            let op = if *op == AlgebraicBinaryOperator::Add {
+                "+"
+            } else {
+                "*"
+            };
+            format!("({op} {})", operands.join(" "))
+        }
+        AlgebraicExpression::BinaryOperation(left, op, right) => {
+            let op = match op {
+                AlgebraicBinaryOperator::Sub => "-",
+                AlgebraicBinaryOperator::Pow => "**",
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Mul => unreachable!(),
+            };
+            format!(
+                "({op} {} {})",
+                canonical(left, reference_key),
+                canonical(right, reference_key)
+            )
+        }
+        AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Plus, inner) => {
+            canonical(inner, reference_key)
+        }
+        AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, inner) => {
+            format!("(neg {})", canonical(inner, reference_key))
+        }
+    }
+}
+
+fn collect_operands<'a, T>(
+    e: &'a AlgebraicExpression<T>,
+    op: AlgebraicBinaryOperator,
+    operands: &mut Vec<&'a AlgebraicExpression<T>>,
+) {
+    match e {
+        AlgebraicExpression::BinaryOperation(left, o, right) if *o == op => {
+            collect_operands(left, op, operands);
+            collect_operands(right, op, operands);
+        }
+        _ => operands.push(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pil_analyzer::pil_analyzer::process_pil_file_contents;
+
+    use super::*;
+
+    fn fingerprints(
+        input: &str,
+        reference_key: impl Fn(&AlgebraicReference) -> String,
+    ) -> Vec<Fingerprint> {
+        process_pil_file_contents::<GoldilocksField>(input)
+            .identities
+            .iter()
+            .map(|identity| identity_fingerprint(identity, &reference_key))
+            .collect()
+    }
+
+    #[test]
+    fn canonicalization() {
+        let input = r#"namespace N(8);
+    col witness x, y, z;
+    x + (y + 2 * z) = 0;
+    0 = (z * 2 + x) + y;
+    x = y * 1;
+    y = x;
+    { x + 1, y } in { z, x };
+    { 1 + x, y } in { z, x };
+    { y, x } in { x, z };
+"#;
+        let fingerprints = fingerprints(input, |r| r.name.clone());
+        assert_eq!(fingerprints[0], fingerprints[1]);
+        assert_eq!(fingerprints[2], fingerprints[3]);
+        assert_eq!(fingerprints[4], fingerprints[5]);
+        assert_ne!(fingerprints[4], fingerprints[6]);
+        assert_eq!(fingerprints[2].to_string(), "(= N.x N.y)");
+    }
+
+    #[test]
+    fn abstracted_names() {
+        let input = r#"namespace N(8);
+    col witness x, y, a, b;
+    x' = x + y;
+    a' = a + b;
+    a' = b + a;
+"#;
+        let fingerprints = fingerprints(input, |_| "col".to_string());
+        assert_eq!(fingerprints[0], fingerprints[1]);
+        assert_eq!(fingerprints[1], fingerprints[2]);
+    }
+}
@@ -1,36 +1,65 @@
 //! PIL-based optimizer
 #![deny(clippy::print_stdout)]
 
-use std::collections::{BTreeMap, HashSet};
+pub mod fingerprint;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicUnaryOperator, Reference,
 };
 use ast::analyzed::{
-    AlgebraicReference, Analyzed, Expression, FunctionValueDefinition, IdentityKind, PolyID,
-    PolynomialReference,
+    AlgebraicReference, Analyzed, Expression, FunctionValueDefinition, Identity, IdentityKind,
+    PolyID, PolynomialReference, PolynomialType,
 };
 use ast::parsed::visitor::ExpressionVisitable;
+use fingerprint::identity_fingerprint;
 
 use number::FieldElement;
 
-pub fn optimize<T: FieldElement>(mut pil_file: Analyzed<T>) -> Analyzed<T> {
-    let col_count_pre = (pil_file.commitment_count(), pil_file.constant_count());
+/// The default maximum number of nodes of the definition of an intermediate column
+/// that is inlined, see [inline_small_intermediate_columns].
+pub const DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE: usize = 4;
+
+pub fn optimize<T: FieldElement>(pil_file: Analyzed<T>) -> Analyzed<T> {
+    optimize_reporting_duplicates(pil_file, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE).0
+}
+
+/// Like [optimize], but also returns the identities that were removed because they
+/// are duplicates of earlier identities. Intermediate columns whose definition has
+/// at most `max_inlined_intermediate_size` nodes are inlined, 0 disables inlining.
+pub fn optimize_reporting_duplicates<T: FieldElement>(
+    mut pil_file: Analyzed<T>,
+    max_inlined_intermediate_size: usize,
+) -> (Analyzed<T>, Vec<Identity<AlgebraicExpression<T>>>) {
+    let col_count_pre = (
+        pil_file.commitment_count(),
+        pil_file.constant_count(),
+        pil_file.intermediate_count(),
+    );
     remove_constant_fixed_columns(&mut pil_file);
     simplify_identities(&mut pil_file);
     extract_constant_lookups(&mut pil_file);
     remove_constant_witness_columns(&mut pil_file);
+    inline_small_intermediate_columns(&mut pil_file, max_inlined_intermediate_size);
     simplify_identities(&mut pil_file);
     remove_trivial_identities(&mut pil_file);
-    let col_count_post = (pil_file.commitment_count(), pil_file.constant_count());
+    let duplicates = remove_duplicate_identities(&mut pil_file);
+    let col_count_post = (
+        pil_file.commitment_count(),
+        pil_file.constant_count(),
+        pil_file.intermediate_count(),
+    );
     log::info!(
-        "Removed {} witness and {} fixed columns. Total count now: {} witness and {} fixed columns.",
+        "Removed {} witness, {} fixed and {} intermediate columns. Total count now: {} witness, {} fixed and {} intermediate columns.",
         col_count_pre.0 - col_count_post.0,
         col_count_pre.1 - col_count_post.1,
+        col_count_pre.2 - col_count_post.2,
         col_count_post.0,
-        col_count_post.1
+        col_count_post.1,
+        col_count_post.2
     );
-    pil_file
+    (pil_file, duplicates)
 }
 
 /// Identifies fixed columns that only have a single value, replaces every
@@ -78,7 +107,7 @@ fn constant_value<T: FieldElement>(function: &FunctionValueDefinition<T>) -> Opt
                 None
             }
         }
-        FunctionValueDefinition::Query(_) => None,
+        FunctionValueDefinition::Query(_) | FunctionValueDefinition::Hint(_) => None,
         FunctionValueDefinition::Expression(_) => None,
         FunctionValueDefinition::Number(_) => None,
     }
@@ -307,6 +336,283 @@ fn constrained_to_constant<T: FieldElement>(expr: &AlgebraicExpression<T>) -> Op
     None
 }
 
+/// Inlines the intermediate columns whose definition has at most `max_size` nodes
+/// into all expressions that reference them and removes the columns.
+///
+/// Nested intermediate columns are processed in dependency order, so the size is that
+/// of the definition after inlining. An intermediate column is kept if inlining it
+/// would increase the maximum degree of the identities and intermediate columns, if
+/// its definition contains a next reference and the column is referenced with `'`
+/// (which would need a reference two rows ahead), or if it is referenced outside of
+/// algebraic expressions, e.g. by a public declaration or a query.
+fn inline_small_intermediate_columns<T: FieldElement>(pil_file: &mut Analyzed<T>, max_size: usize) {
+    let definitions = pil_file
+        .intermediate_columns
+        .values()
+        .map(|(symbol, definition)| (symbol.id, definition.clone()))
+        .collect::<BTreeMap<_, _>>();
+    let names = pil_file
+        .intermediate_columns
+        .values()
+        .map(|(symbol, _)| (symbol.id, symbol.absolute_name.clone()))
+        .collect::<BTreeMap<_, _>>();
+    if max_size == 0 || definitions.is_empty() {
+        return;
+    }
+
+    let mut referenced_elsewhere = pil_file
+        .public_declarations
+        .values()
+        .filter_map(|public| public.polynomial.poly_id)
+        .filter(|poly_id| poly_id.ptype == PolynomialType::Intermediate)
+        .map(|poly_id| poly_id.id)
+        .collect::<HashSet<_>>();
+    pil_file.post_visit_expressions_in_definitions_mut(&mut |e: &mut Expression<_>| {
+        if let Expression::Reference(Reference::Poly(PolynomialReference {
+            poly_id: Some(poly_id),
+            ..
+        })) = e
+        {
+            if poly_id.ptype == PolynomialType::Intermediate {
+                referenced_elsewhere.insert(poly_id.id);
+            }
+        }
+    });
+    let mut referenced_with_next = HashSet::new();
+    for identity in &pil_file.identities {
+        identity.pre_visit_expressions(&mut |e| {
+            if let Some(r) = intermediate_reference(e) {
+                if r.next {
+                    referenced_with_next.insert(r.poly_id.id);
+                }
+            }
+        });
+    }
+    for definition in definitions.values() {
+        definition.pre_visit_expressions(&mut |e| {
+            if let Some(r) = intermediate_reference(e) {
+                if r.next {
+                    referenced_with_next.insert(r.poly_id.id);
+                }
+            }
+        });
+    }
+
+    // Without any inlining, references to intermediate columns have degree one.
+    let mut degrees = HashMap::new();
+    let max_degree = pil_file
+        .identities
+        .iter()
+        .map(|identity| identity_degree(identity, &degrees))
+        .chain(
+            definitions
+                .values()
+                .map(|definition| expression_degree(definition, &degrees)),
+        )
+        .max()
+        .unwrap_or_default();
+
+    let mut inlined = BTreeMap::new();
+    for id in intermediates_in_dependency_order(&definitions) {
+        let definition = inline_intermediates(definitions[&id].clone(), &inlined);
+        if referenced_elsewhere.contains(&id)
+            || expression_size(&definition) > max_size
+            || (referenced_with_next.contains(&id) && definition.contains_next_ref())
+        {
+            continue;
+        }
+        degrees.insert(id, expression_degree(&definition, &degrees));
+        let increases_degree = pil_file
+            .identities
+            .iter()
+            .filter(|identity| references_intermediate(*identity, id))
+            .map(|identity| identity_degree(identity, &degrees))
+            .chain(
+                definitions
+                    .values()
+                    .filter(|definition| references_intermediate(*definition, id))
+                    .map(|definition| expression_degree(definition, &degrees)),
+            )
+            .any(|degree| degree > max_degree);
+        if increases_degree {
+            log::debug!(
+                "Not inlining intermediate column {} because it would increase the degree.",
+                names[&id]
+            );
+            degrees.remove(&id);
+            continue;
+        }
+        inlined.insert(id, definition);
+    }
+
+    pil_file.post_visit_expressions_in_identities_mut(&mut |e: &mut AlgebraicExpression<_>| {
+        if let Some(replacement) = inlined_reference(e, &inlined) {
+            *e = replacement;
+        }
+    });
+    pil_file.remove_polynomials(
+        &inlined
+            .keys()
+            .map(|&id| PolyID {
+                id,
+                ptype: PolynomialType::Intermediate,
+            })
+            .collect(),
+    );
+}
+
+fn intermediate_reference<T>(e: &AlgebraicExpression<T>) -> Option<&AlgebraicReference> {
+    match e {
+        AlgebraicExpression::Reference(r) if r.poly_id.ptype == PolynomialType::Intermediate => {
+            Some(r)
+        }
+        _ => None,
+    }
+}
+
+fn references_intermediate<T>(
+    e: &impl ExpressionVisitable<AlgebraicExpression<T>>,
+    id: u64,
+) -> bool {
+    let mut found = false;
+    e.pre_visit_expressions(&mut |e| {
+        found |= intermediate_reference(e).is_some_and(|r| r.poly_id.id == id);
+    });
+    found
+}
+
+/// The IDs of the intermediate columns, such that each column comes after the
+/// intermediate columns its definition references.
+fn intermediates_in_dependency_order<T>(
+    definitions: &BTreeMap<u64, AlgebraicExpression<T>>,
+) -> Vec<u64> {
+    fn visit<T>(
+        id: u64,
+        definitions: &BTreeMap<u64, AlgebraicExpression<T>>,
+        visited: &mut BTreeSet<u64>,
+        order: &mut Vec<u64>,
+    ) {
+        if !visited.insert(id) {
+            return;
+        }
+        let mut dependencies = vec![];
+        definitions[&id].pre_visit_expressions(&mut |e| {
+            if let Some(r) = intermediate_reference(e) {
+                dependencies.push(r.poly_id.id);
+            }
+        });
+        for dependency in dependencies {
+            visit(dependency, definitions, visited, order);
+        }
+        order.push(id);
+    }
+
+    let mut visited = BTreeSet::new();
+    let mut order = vec![];
+    for &id in definitions.keys() {
+        visit(id, definitions, &mut visited, &mut order);
+    }
+    order
+}
+
+/// Replaces the references to the intermediate columns in `inlined` by their definitions.
+fn inline_intermediates<T: FieldElement>(
+    mut e: AlgebraicExpression<T>,
+    inlined: &BTreeMap<u64, AlgebraicExpression<T>>,
+) -> AlgebraicExpression<T> {
+    e.post_visit_expressions_mut(&mut |e| {
+        if let Some(replacement) = inlined_reference(e, inlined) {
+            *e = replacement;
+        }
+    });
+    e
+}
+
+/// The definition of the intermediate column referenced by `e` if it is in `inlined`,
+/// shifted to the next row for a next reference.
+fn inlined_reference<T: FieldElement>(
+    e: &AlgebraicExpression<T>,
+    inlined: &BTreeMap<u64, AlgebraicExpression<T>>,
+) -> Option<AlgebraicExpression<T>> {
+    let r = intermediate_reference(e)?;
+    let mut definition = inlined.get(&r.poly_id.id)?.clone();
+    if r.next {
+        definition.pre_visit_expressions_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(r) = e {
+                r.next = true;
+            }
+        });
+    }
+    Some(definition)
+}
+
+/// The number of nodes of an expression.
+fn expression_size<T>(e: &AlgebraicExpression<T>) -> usize {
+    let mut size = 0;
+    e.pre_visit_expressions(&mut |_| size += 1);
+    size
+}
+
+/// The degree of an expression, where a reference to an intermediate column has the
+/// degree given in `intermediate_degrees` or one if it is not there.
+fn expression_degree<T: FieldElement>(
+    e: &AlgebraicExpression<T>,
+    intermediate_degrees: &HashMap<u64, usize>,
+) -> usize {
+    match e {
+        AlgebraicExpression::Reference(r) => match r.poly_id.ptype {
+            PolynomialType::Intermediate => intermediate_degrees
+                .get(&r.poly_id.id)
+                .copied()
+                .unwrap_or(1),
+            _ => 1,
+        },
+        AlgebraicExpression::PublicReference(_) | AlgebraicExpression::Number(_) => 0,
+        AlgebraicExpression::BinaryOperation(left, op, right) => {
+            let left = expression_degree(left, intermediate_degrees);
+            match op {
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                    left.max(expression_degree(right, intermediate_degrees))
+                }
+                AlgebraicBinaryOperator::Mul => {
+                    left + expression_degree(right, intermediate_degrees)
+                }
+                AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                    AlgebraicExpression::Number(n) => left * n.to_degree() as usize,
+                    _ => unreachable!("Exponents have to be constants."),
+                },
+            }
+        }
+        AlgebraicExpression::UnaryOperation(_, inner) => {
+            expression_degree(inner, intermediate_degrees)
+        }
+    }
+}
+
+/// The degree of an identity. For lookups and permutations, this is the degree of
+/// the selector plus the degree of the expressions on each side.
+fn identity_degree<T: FieldElement>(
+    identity: &Identity<AlgebraicExpression<T>>,
+    intermediate_degrees: &HashMap<u64, usize>,
+) -> usize {
+    [&identity.left, &identity.right]
+        .into_iter()
+        .map(|side| {
+            side.selector
+                .as_ref()
+                .map(|s| expression_degree(s, intermediate_degrees))
+                .unwrap_or_default()
+                + side
+                    .expressions
+                    .iter()
+                    .map(|e| expression_degree(e, intermediate_degrees))
+                    .max()
+                    .unwrap_or_default()
+        })
+        .max()
+        .unwrap_or_default()
+}
+
 /// Removes identities that evaluate to zero and lookups with empty columns.
 fn remove_trivial_identities<T: FieldElement>(pil_file: &mut Analyzed<T>) {
     let to_remove = pil_file
@@ -338,12 +644,63 @@ fn remove_trivial_identities<T: FieldElement>(pil_file: &mut Analyzed<T>) {
     pil_file.remove_identities(&to_remove);
 }
 
+/// Removes identities that are structurally equal to an earlier identity,
+/// for example lookups that were generated twice, and returns them.
+/// Identities are compared by their [fingerprint](identity_fingerprint), i.e. up to
+/// the order of commutative operands and additions of zero or multiplications by one.
+fn remove_duplicate_identities<T: FieldElement>(
+    pil_file: &mut Analyzed<T>,
+) -> Vec<Identity<AlgebraicExpression<T>>> {
+    let mut seen = HashSet::new();
+    let to_remove = pil_file
+        .identities
+        .iter()
+        .enumerate()
+        .filter_map(|(index, identity)| {
+            let fingerprint = identity_fingerprint(identity, &|r| {
+                format!("{}{}", r.poly_id.ptype, r.poly_id.id)
+            });
+            (!seen.insert(fingerprint)).then(|| {
+                log::debug!("Removing duplicate identity {identity}");
+                index
+            })
+        })
+        .collect::<BTreeSet<_>>();
+    let duplicates = to_remove
+        .iter()
+        .map(|&index| pil_file.identities[index].clone())
+        .collect::<Vec<_>>();
+    if !duplicates.is_empty() {
+        let counts = [
+            IdentityKind::Polynomial,
+            IdentityKind::Plookup,
+            IdentityKind::Permutation,
+            IdentityKind::Connect,
+        ]
+        .into_iter()
+        .filter_map(|kind| {
+            let count = duplicates.iter().filter(|i| i.kind == kind).count();
+            (count > 0).then(|| format!("{count} {kind:?}"))
+        })
+        .collect::<Vec<_>>();
+        log::info!(
+            "Removed {} duplicate identities: {}.",
+            duplicates.len(),
+            counts.join(", ")
+        );
+    }
+    pil_file.remove_identities(&to_remove);
+    duplicates
+}
+
 #[cfg(test)]
 mod test {
     use number::GoldilocksField;
     use pil_analyzer::pil_analyzer::process_pil_file_contents;
 
-    use crate::optimize;
+    use ast::analyzed::IdentityKind;
+
+    use crate::{optimize, optimize_reporting_duplicates, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE};
 
     use pretty_assertions::assert_eq;
 
@@ -401,6 +758,83 @@ mod test {
         assert_eq!(optimized, expectation);
     }
 
+    #[test]
+    fn first_step_boundary_constraints() {
+        // Constraints on the first row, which link the main machine to the bootloader
+        // when using continuations, must survive the optimization: `first_step` is not
+        // constant, so it must not be propagated.
+        let input = r#"namespace main(8);
+    col fixed first_step = [1] + [0]*;
+    col fixed _linker_first_step = [1] + [0]*;
+    col fixed one = [1]*;
+    col witness pc;
+    col witness _operation_id;
+    col witness x;
+    pc' = (1 - first_step') * (pc + one);
+    _linker_first_step * (_operation_id - 2) = 0;
+    first_step * (x - pc) = 0;
+"#;
+        let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input));
+        assert_eq!(optimized.identities.len(), 3);
+        assert_eq!(optimized.constant_count(), 2);
+        let optimized = optimized.to_string();
+        assert!(optimized.contains("main.first_step'"), "{optimized}");
+        assert!(
+            optimized.contains("(main._linker_first_step * (main._operation_id - 2)) = 0;"),
+            "{optimized}"
+        );
+    }
+
+    #[test]
+    fn duplicate_identities() {
+        let input = r#"namespace N(65536);
+    col fixed cnt(i) { i };
+    col witness X;
+    col witness Y;
+    { X + Y } in { cnt };
+    X' = Y;
+    { Y + X } in { cnt };
+    Y = X';
+    { X } in { cnt };
+"#;
+        let expectation = r#"namespace N(65536);
+    col fixed cnt(i) { i };
+    col witness X;
+    col witness Y;
+    { (N.X + N.Y) } in { N.cnt };
+    N.X' = N.Y;
+    { N.X } in { N.cnt };
+"#;
+        let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input)).to_string();
+        assert_eq!(optimized, expectation);
+    }
+
+    #[test]
+    fn reported_duplicates() {
+        let input = r#"namespace N(65536);
+    col fixed cnt(i) { i };
+    col witness X;
+    col witness Y;
+    { X + Y } in { cnt };
+    X' = Y;
+    { Y + X } in { cnt };
+    Y = X' * 1;
+    { X } in { cnt };
+"#;
+        let (optimized, duplicates) = optimize_reporting_duplicates(
+            process_pil_file_contents::<GoldilocksField>(input),
+            DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+        );
+        assert_eq!(optimized.identities.len(), 3);
+        assert_eq!(
+            duplicates
+                .iter()
+                .map(|i| (i.kind, i.source.line))
+                .collect::<Vec<_>>(),
+            vec![(IdentityKind::Plookup, 7), (IdentityKind::Polynomial, 8)]
+        );
+    }
+
     #[test]
     fn intermediate() {
         let input = r#"namespace N(65536);
@@ -412,6 +846,71 @@ mod test {
     col witness x;
     col intermediate = N.x;
     N.intermediate = N.intermediate;
+"#;
+        let (optimized, _) =
+            optimize_reporting_duplicates(process_pil_file_contents::<GoldilocksField>(input), 0);
+        assert_eq!(optimized.to_string(), expectation);
+    }
+
+    #[test]
+    fn inline_intermediate() {
+        let input = r#"namespace N(65536);
+        col witness x, y;
+        col fixed FIRST = [1] + [0]*;
+        col inc = x + 1;
+        col double = inc + inc;
+        col large = x * y + y * y + x;
+        FIRST * (x' - inc) = 0;
+        x' = double + large;
+        y = inc';
+    "#;
+        let expectation = r#"namespace N(65536);
+    col witness x;
+    col witness y;
+    col fixed FIRST = [1] + [0]*;
+    col double = ((N.x + 1) + (N.x + 1));
+    col large = (((N.x * N.y) + (N.y * N.y)) + N.x);
+    (N.FIRST * (N.x' - (N.x + 1))) = 0;
+    N.x' = (N.double + N.large);
+    N.y = (N.x' + 1);
+"#;
+        let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input)).to_string();
+        assert_eq!(optimized, expectation);
+    }
+
+    #[test]
+    fn intermediate_not_inlined_if_degree_increases() {
+        let input = r#"namespace N(65536);
+        col witness x, y, z;
+        col product = x * y;
+        product * z = x;
+        z' = product;
+    "#;
+        let expectation = r#"namespace N(65536);
+    col witness x;
+    col witness y;
+    col witness z;
+    col product = (N.x * N.y);
+    (N.product * N.z) = N.x;
+    N.z' = N.product;
+"#;
+        let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input)).to_string();
+        assert_eq!(optimized, expectation);
+    }
+
+    #[test]
+    fn intermediate_with_next_not_inlined_if_used_with_next() {
+        let input = r#"namespace N(65536);
+        col witness x;
+        col next_x = x';
+        x = next_x';
+        x = next_x + 1;
+    "#;
+        let expectation = r#"namespace N(65536);
+    col witness x;
+    col next_x = N.x';
+    N.x = N.next_x';
+    N.x = (N.next_x + 1);
 "#;
         let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input)).to_string();
         assert_eq!(optimized, expectation);
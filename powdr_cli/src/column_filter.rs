@@ -0,0 +1,129 @@
+//! Selection of the columns exported with `pil --export-csv --csv-columns`.
+//!
+//! A filter is a list of glob patterns (`*` matches any sequence of characters,
+//! `?` a single character) on the fully qualified column names, e.g. `Binary.*` or
+//! `main.pc`. The elements of an array match the patterns for the name of the array.
+
+/// A list of glob patterns for column names. An empty list selects all columns.
+pub struct ColumnFilter<'a> {
+    patterns: &'a [String],
+}
+
+impl<'a> ColumnFilter<'a> {
+    pub fn new(patterns: &'a [String]) -> Self {
+        ColumnFilter { patterns }
+    }
+
+    pub fn is_selected(&self, name: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|pattern| matches(pattern, name))
+    }
+
+    /// Keeps the selected columns, in their original order.
+    pub fn apply<T>(&self, columns: Vec<(String, Vec<T>)>) -> Vec<(String, Vec<T>)> {
+        columns
+            .into_iter()
+            .filter(|(name, _)| self.is_selected(name))
+            .collect()
+    }
+
+    /// Warns about the patterns that do not match any of the column `names`,
+    /// listing similar column names.
+    pub fn warn_unmatched<'b>(&self, names: impl Iterator<Item = &'b str> + Clone) {
+        for pattern in self.patterns {
+            if names.clone().any(|name| matches(pattern, name)) {
+                continue;
+            }
+            let similar = similar_names(pattern, names.clone());
+            if similar.is_empty() {
+                log::warn!("The CSV column pattern `{pattern}` does not match any column.");
+            } else {
+                log::warn!(
+                    "The CSV column pattern `{pattern}` does not match any column, similar columns: {}",
+                    similar.join(", ")
+                );
+            }
+        }
+    }
+}
+
+/// Returns true if `name` or, for an array element `name[index]`, the name of
+/// the array matches `pattern`.
+fn matches(pattern: &str, name: &str) -> bool {
+    glob_match(pattern.as_bytes(), name.as_bytes())
+        || name
+            .split_once('[')
+            .is_some_and(|(array, _)| glob_match(pattern.as_bytes(), array.as_bytes()))
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// The (up to three) names most similar to `pattern`, if they are similar enough.
+fn similar_names<'a>(pattern: &str, names: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut similar = names
+        .map(|name| (strsim::jaro(pattern, name), name))
+        .filter(|(similarity, _)| *similarity > 0.7)
+        .collect::<Vec<_>>();
+    similar.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    similar.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(patterns: &[&str]) -> Vec<String> {
+        let columns = [
+            "main.pc",
+            "main.X",
+            "main.reg[0]",
+            "main.reg[1]",
+            "Binary.A",
+        ]
+        .into_iter()
+        .map(|name| (name.to_string(), vec![0u64]))
+        .collect();
+        let patterns = patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        ColumnFilter::new(&patterns)
+            .apply(columns)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn empty_filter() {
+        assert_eq!(names(&[]).len(), 5);
+    }
+
+    #[test]
+    fn keeps_source_order() {
+        assert_eq!(names(&["Binary.*", "main.pc"]), ["main.pc", "Binary.A"]);
+        assert_eq!(names(&["main.?"]), ["main.X"]);
+        assert_eq!(names(&["*.A", "*.X"]), ["main.X", "Binary.A"]);
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(names(&["main.reg"]), ["main.reg[0]", "main.reg[1]"]);
+        assert_eq!(names(&["main.reg[1]"]), ["main.reg[1]"]);
+    }
+
+    #[test]
+    fn unknown_pattern() {
+        assert!(names(&["main.pcc"]).is_empty());
+        assert_eq!(
+            similar_names("main.pcc", ["main.pc", "Binary.A"].into_iter()),
+            ["main.pc"]
+        );
+    }
+}
@@ -0,0 +1,635 @@
+//! Project configuration file (`powdr.toml`) for the pipeline commands.
+//!
+//! The file contains the same options as the command line flags of the `pil`, `rust`
+//! and `riscv-asm` commands, using the flag names as keys, e.g.
+//!
+//! ```toml
+//! field = "gl"
+//! output-directory = "build"
+//! inputs = [3, 2, 1, 2]
+//! # or, for larger inputs:
+//! # inputs-file = "inputs.json"
+//! prove-with = "estark"
+//! coprocessors = ["poseidon_gl"]
+//! ```
+//!
+//! A flag given on the command line takes precedence over the configuration file,
+//! which takes precedence over the default value of the flag.
+//!
+//! The resource limits for analyzing a .pil file (`max-expression-depth`,
+//! `max-statements` and `max-cells`, see [compiler::Limits]) can only be set
+//! in the configuration file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use backend::BackendType;
+use compiler::{Limits, UnconstrainedWitnesses, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE};
+use number::{DegreeType, FieldElement};
+use serde::{Deserialize, Serialize};
+use strum::VariantNames;
+
+use crate::inputs::{read_channel_inputs, read_inputs, InputError};
+use crate::{CsvRenderModeCLI, FieldArgument};
+
+/// The name of the configuration file that is searched for.
+pub const CONFIG_FILE_NAME: &str = "powdr.toml";
+
+/// Settings from the configuration file or the command line.
+/// A missing value means that the setting is not specified at this level.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_directory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Vec<u64>>,
+    /// Path to a file containing the free inputs, see [crate::inputs].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs_file: Option<String>,
+    /// Reject input values that are not smaller than the field modulus instead of
+    /// reducing them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_inputs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prove_with: Option<String>,
+    /// Only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub witness_values: Option<String>,
+    /// Only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export_csv: Option<bool>,
+    /// Only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csv_mode: Option<String>,
+    /// Glob patterns selecting the exported columns, only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csv_columns: Option<Vec<String>>,
+    /// Only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dump_range_constraints: Option<bool>,
+    /// Only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_range_constraints: Option<bool>,
+    /// Only used by the `pil` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bname: Option<String>,
+    /// Only used by the `rust` and `riscv-asm` commands.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coprocessors: Option<Vec<String>>,
+    /// Overrides the degree of all namespaces.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub degree: Option<DegreeType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub just_execute: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuations: Option<bool>,
+    /// Only used by the `rust` and `riscv-asm` commands.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_executor: Option<bool>,
+    /// Only used by the `pil` command for .pil files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_expression_depth: Option<usize>,
+    /// Only used by the `pil` command for .pil files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_statements: Option<usize>,
+    /// Only used by the `pil` command for .pil files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cells: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny_unconstrained: Option<bool>,
+    /// Witness columns that are intentionally unconstrained, see [compiler::UnconstrainedWitnesses].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_unconstrained: Option<Vec<String>>,
+    /// See [compiler::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_inlined_intermediate_size: Option<usize>,
+}
+
+/// All keys allowed in the configuration file.
+const KEYS: [&str; 25] = [
+    "field",
+    "output-directory",
+    "inputs",
+    "inputs-file",
+    "strict-inputs",
+    "force",
+    "prove-with",
+    "witness-values",
+    "export-csv",
+    "csv-mode",
+    "csv-columns",
+    "dump-range-constraints",
+    "show-range-constraints",
+    "bname",
+    "coprocessors",
+    "degree",
+    "just-execute",
+    "continuations",
+    "check-executor",
+    "max-expression-depth",
+    "max-statements",
+    "max-cells",
+    "deny-unconstrained",
+    "allow-unconstrained",
+    "max-inlined-intermediate-size",
+];
+
+impl Config {
+    /// Parses and validates a configuration file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let table: toml::Table = contents.parse().map_err(|e| format!("{e}"))?;
+        if let Some(key) = table.keys().find(|key| !KEYS.contains(&key.as_str())) {
+            return Err(match suggest_key(key) {
+                Some(suggestion) => format!("Unknown key `{key}`, did you mean `{suggestion}`?"),
+                None => format!("Unknown key `{key}`, expected one of {}.", KEYS.join(", ")),
+            });
+        }
+        let config: Config = table.try_into().map_err(|e| format!("{e}"))?;
+        if config.inputs.is_some() && config.inputs_file.is_some() {
+            return Err("Only one of `inputs` and `inputs-file` can be given.".to_string());
+        }
+        // Convert to a plan to validate the values.
+        ExecutionPlan::try_from(config.clone())?;
+        Ok(config)
+    }
+
+    /// Reads and validates a configuration file.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&contents).map_err(|e| format!("Invalid configuration {}: {e}", path.display()))
+    }
+
+    /// Returns `config_file` if given, otherwise the first `powdr.toml` found
+    /// in the directory of `input_file` or its parents.
+    pub fn find(config_file: Option<&Path>, input_file: Option<&Path>) -> Option<PathBuf> {
+        config_file
+            .map(Path::to_path_buf)
+            .or_else(|| discover(input_file.unwrap_or(Path::new("."))))
+    }
+
+    /// Loads the configuration file found by [Config::find].
+    /// Returns an empty configuration if there is no configuration file.
+    pub fn load(config_file: Option<&Path>, input_file: Option<&Path>) -> Result<Self, String> {
+        match Self::find(config_file, input_file) {
+            Some(path) => {
+                log::info!("Using configuration file {}.", path.display());
+                Self::read(&path)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Combines two configurations, preferring values from `self`.
+    /// Inputs given in any form in `self` replace all inputs in `other`.
+    pub fn or(self, other: Config) -> Config {
+        let (inputs, inputs_file) = if self.inputs.is_some() || self.inputs_file.is_some() {
+            (self.inputs, self.inputs_file)
+        } else {
+            (other.inputs, other.inputs_file)
+        };
+        Config {
+            field: self.field.or(other.field),
+            output_directory: self.output_directory.or(other.output_directory),
+            inputs,
+            inputs_file,
+            strict_inputs: self.strict_inputs.or(other.strict_inputs),
+            force: self.force.or(other.force),
+            prove_with: self.prove_with.or(other.prove_with),
+            witness_values: self.witness_values.or(other.witness_values),
+            export_csv: self.export_csv.or(other.export_csv),
+            csv_mode: self.csv_mode.or(other.csv_mode),
+            csv_columns: self.csv_columns.or(other.csv_columns),
+            dump_range_constraints: self.dump_range_constraints.or(other.dump_range_constraints),
+            show_range_constraints: self.show_range_constraints.or(other.show_range_constraints),
+            bname: self.bname.or(other.bname),
+            coprocessors: self.coprocessors.or(other.coprocessors),
+            degree: self.degree.or(other.degree),
+            just_execute: self.just_execute.or(other.just_execute),
+            continuations: self.continuations.or(other.continuations),
+            check_executor: self.check_executor.or(other.check_executor),
+            max_expression_depth: self.max_expression_depth.or(other.max_expression_depth),
+            max_statements: self.max_statements.or(other.max_statements),
+            max_cells: self.max_cells.or(other.max_cells),
+            deny_unconstrained: self.deny_unconstrained.or(other.deny_unconstrained),
+            allow_unconstrained: self.allow_unconstrained.or(other.allow_unconstrained),
+            max_inlined_intermediate_size: self
+                .max_inlined_intermediate_size
+                .or(other.max_inlined_intermediate_size),
+        }
+    }
+}
+
+/// Returns the first `powdr.toml` in the directory of `input_file` or its parents.
+fn discover(input_file: &Path) -> Option<PathBuf> {
+    let dir = match input_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let dir = fs::canonicalize(&dir).unwrap_or(dir);
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Returns the allowed key most similar to `key`, if it is similar enough.
+fn suggest_key(key: &str) -> Option<&'static str> {
+    KEYS.iter()
+        .map(|candidate| (strsim::jaro(key, candidate), *candidate))
+        .filter(|(similarity, _)| *similarity > 0.7)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+fn parse_enum<E: FromStr + VariantNames>(key: &str, value: &str) -> Result<E, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "Invalid value `{value}` for `{key}`, expected one of {}.",
+            E::VARIANTS.join(", ")
+        )
+    })
+}
+
+/// The settings of a pipeline command after merging the command line flags,
+/// the configuration file and the defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionPlan {
+    pub field: FieldArgument,
+    pub output_directory: String,
+    pub inputs: Vec<u64>,
+    pub inputs_file: Option<String>,
+    pub strict_inputs: bool,
+    pub force: bool,
+    pub prove_with: Option<BackendType>,
+    pub witness_values: Option<String>,
+    pub export_csv: bool,
+    pub csv_mode: CsvRenderModeCLI,
+    pub csv_columns: Vec<String>,
+    pub dump_range_constraints: bool,
+    pub show_range_constraints: bool,
+    pub bname: Option<String>,
+    pub coprocessors: Option<Vec<String>>,
+    pub degree: Option<DegreeType>,
+    pub just_execute: bool,
+    pub continuations: bool,
+    pub check_executor: bool,
+    pub limits: Limits,
+    pub unconstrained: UnconstrainedWitnesses,
+    pub max_inlined_intermediate_size: usize,
+}
+
+impl ExecutionPlan {
+    /// Merges the settings given on the command line with the configuration file.
+    pub fn new(flags: Config, config: Config) -> Result<Self, String> {
+        Self::try_from(flags.or(config))
+    }
+
+    /// The free inputs as field elements, by input channel. Inputs given `inline` on
+    /// the command line replace the inputs of the plan, which are in channel 0.
+    pub fn inputs<T: FieldElement>(
+        &self,
+        inline: &[String],
+    ) -> Result<HashMap<T, Vec<T>>, InputError> {
+        if !inline.is_empty() {
+            return read_channel_inputs(inline, self.strict_inputs);
+        }
+        let inputs = match &self.inputs_file {
+            Some(file) => read_inputs(None, Some(Path::new(file)), self.strict_inputs)?,
+            None => self.inputs.iter().map(|&x| x.into()).collect(),
+        };
+        Ok(HashMap::from([(T::zero(), inputs)]))
+    }
+
+    /// Converts the plan back to a (complete) configuration.
+    pub fn to_config(&self) -> Config {
+        Config {
+            field: Some(self.field.to_string()),
+            output_directory: Some(self.output_directory.clone()),
+            inputs: self.inputs_file.is_none().then(|| self.inputs.clone()),
+            inputs_file: self.inputs_file.clone(),
+            strict_inputs: Some(self.strict_inputs),
+            force: Some(self.force),
+            prove_with: self.prove_with.as_ref().map(ToString::to_string),
+            witness_values: self.witness_values.clone(),
+            export_csv: Some(self.export_csv),
+            csv_mode: Some(self.csv_mode.to_string()),
+            csv_columns: Some(self.csv_columns.clone()),
+            dump_range_constraints: Some(self.dump_range_constraints),
+            show_range_constraints: Some(self.show_range_constraints),
+            bname: self.bname.clone(),
+            coprocessors: self.coprocessors.clone(),
+            degree: self.degree,
+            just_execute: Some(self.just_execute),
+            continuations: Some(self.continuations),
+            check_executor: Some(self.check_executor),
+            max_expression_depth: Some(self.limits.max_expression_depth),
+            max_statements: Some(self.limits.max_statements),
+            max_cells: Some(self.limits.max_cells),
+            deny_unconstrained: Some(self.unconstrained.deny),
+            allow_unconstrained: Some(self.unconstrained.allowed.clone()),
+            max_inlined_intermediate_size: Some(self.max_inlined_intermediate_size),
+        }
+    }
+}
+
+/// Fills in the defaults for all settings missing in the configuration.
+impl TryFrom<Config> for ExecutionPlan {
+    type Error = String;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        Ok(ExecutionPlan {
+            field: config
+                .field
+                .map(|v| parse_enum("field", &v))
+                .transpose()?
+                .unwrap_or(FieldArgument::Gl),
+            output_directory: config.output_directory.unwrap_or_else(|| ".".to_string()),
+            inputs: config.inputs.unwrap_or_default(),
+            inputs_file: config.inputs_file,
+            strict_inputs: config.strict_inputs.unwrap_or_default(),
+            force: config.force.unwrap_or_default(),
+            prove_with: config
+                .prove_with
+                .map(|v| parse_enum("prove-with", &v))
+                .transpose()?,
+            witness_values: config.witness_values,
+            export_csv: config.export_csv.unwrap_or_default(),
+            csv_mode: config
+                .csv_mode
+                .map(|v| parse_enum("csv-mode", &v))
+                .transpose()?
+                .unwrap_or(CsvRenderModeCLI::Hex),
+            csv_columns: config.csv_columns.unwrap_or_default(),
+            dump_range_constraints: config.dump_range_constraints.unwrap_or_default(),
+            show_range_constraints: config.show_range_constraints.unwrap_or_default(),
+            bname: config.bname,
+            coprocessors: config
+                .coprocessors
+                .map(|names| {
+                    let all = riscv::CoProcessors::all_names();
+                    match names.iter().find(|name| !all.contains(&name.as_str())) {
+                        Some(name) => Err(format!(
+                            "Invalid value `{name}` for `coprocessors`, expected one of {}.",
+                            all.join(", ")
+                        )),
+                        None => Ok(names),
+                    }
+                })
+                .transpose()?,
+            degree: config
+                .degree
+                .map(|degree| {
+                    if degree.is_power_of_two() {
+                        Ok(degree)
+                    } else {
+                        Err(format!(
+                            "Invalid value `{degree}` for `degree`, expected a power of two."
+                        ))
+                    }
+                })
+                .transpose()?,
+            just_execute: config.just_execute.unwrap_or_default(),
+            continuations: config.continuations.unwrap_or_default(),
+            check_executor: config.check_executor.unwrap_or_default(),
+            limits: {
+                let default = Limits::default();
+                Limits {
+                    max_expression_depth: config
+                        .max_expression_depth
+                        .unwrap_or(default.max_expression_depth),
+                    max_statements: config.max_statements.unwrap_or(default.max_statements),
+                    max_cells: config.max_cells.unwrap_or(default.max_cells),
+                }
+            },
+            unconstrained: UnconstrainedWitnesses {
+                allowed: config.allow_unconstrained.unwrap_or_default(),
+                deny: config.deny_unconstrained.unwrap_or_default(),
+            },
+            max_inlined_intermediate_size: config
+                .max_inlined_intermediate_size
+                .unwrap_or(DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use number::GoldilocksField;
+
+    #[test]
+    fn unknown_key() {
+        assert_eq!(
+            Config::parse("forse = true").unwrap_err(),
+            "Unknown key `forse`, did you mean `force`?"
+        );
+        assert!(Config::parse("xyz = 1")
+            .unwrap_err()
+            .starts_with("Unknown key `xyz`, expected one of field, output-directory"));
+    }
+
+    #[test]
+    fn invalid_value() {
+        assert_eq!(
+            Config::parse("field = \"babybear\"").unwrap_err(),
+            "Invalid value `babybear` for `field`, expected one of gl, bn254."
+        );
+        assert!(Config::parse("force = \"yes\"").is_err());
+    }
+
+    #[test]
+    fn degree() {
+        assert_eq!(
+            Config::parse("degree = 1000").unwrap_err(),
+            "Invalid value `1000` for `degree`, expected a power of two."
+        );
+        let config = Config::parse("degree = 1024").unwrap();
+        let flags = Config {
+            degree: Some(2048),
+            ..Default::default()
+        };
+        assert_eq!(
+            ExecutionPlan::new(Config::default(), config.clone())
+                .unwrap()
+                .degree,
+            Some(1024)
+        );
+        assert_eq!(
+            ExecutionPlan::new(flags, config).unwrap().degree,
+            Some(2048)
+        );
+    }
+
+    #[test]
+    fn coprocessors() {
+        assert_eq!(
+            Config::parse("coprocessors = [\"poseidon_gl\", \"typo\"]").unwrap_err(),
+            "Invalid value `typo` for `coprocessors`, expected one of binary, shift, split_gl, poseidon_gl, keccakf, prover_input."
+        );
+        let flags = Config {
+            coprocessors: Some(vec!["typo".to_string()]),
+            ..Default::default()
+        };
+        assert!(ExecutionPlan::new(flags, Config::default()).is_err());
+        let plan = ExecutionPlan::try_from(Config::parse("coprocessors = [\"keccakf\"]").unwrap());
+        assert_eq!(
+            plan.unwrap().coprocessors,
+            Some(vec!["keccakf".to_string()])
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let plan = ExecutionPlan::try_from(Config::default()).unwrap();
+        assert_eq!(plan.limits, Limits::default());
+        let config = Config::parse("max-expression-depth = 100\nmax-cells = 1024").unwrap();
+        let plan = ExecutionPlan::try_from(config).unwrap();
+        assert_eq!(
+            plan.limits,
+            Limits {
+                max_expression_depth: 100,
+                max_cells: 1024,
+                ..Default::default()
+            }
+        );
+        assert!(Config::parse("max-statements = -1").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        let config = Config::parse(
+            r#"
+            field = "bn254"
+            inputs = [1, 2]
+            force = true
+            prove-with = "estark"
+            "#,
+        )
+        .unwrap();
+        let flags = Config {
+            inputs: Some(vec![3]),
+            prove_with: Some("pil-stark-cli".to_string()),
+            ..Default::default()
+        };
+        let plan = ExecutionPlan::new(flags, config).unwrap();
+        assert_eq!(plan.field, FieldArgument::Bn254);
+        assert_eq!(plan.inputs, vec![3]);
+        assert!(plan.force);
+        assert_eq!(plan.prove_with, Some(BackendType::PilStarkCli));
+        assert_eq!(plan.output_directory, ".");
+        assert_eq!(plan.csv_mode, CsvRenderModeCLI::Hex);
+    }
+
+    #[test]
+    fn inputs_file() {
+        assert_eq!(
+            Config::parse("inputs = [1]\ninputs-file = \"inputs.json\"").unwrap_err(),
+            "Only one of `inputs` and `inputs-file` can be given."
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("inputs.json");
+        fs::write(&file, r#"["5", "0x6"]"#).unwrap();
+        let config = Config::parse("inputs = [1, 2]").unwrap();
+        let flags = Config {
+            inputs_file: Some(file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let plan = ExecutionPlan::new(flags, config).unwrap();
+        let inputs: HashMap<GoldilocksField, _> = plan.inputs(&[]).unwrap();
+        assert_eq!(
+            inputs,
+            HashMap::from([(0.into(), vec![5.into(), 6.into()])])
+        );
+        let inputs: HashMap<GoldilocksField, _> = plan.inputs(&["7".to_string()]).unwrap();
+        assert_eq!(inputs, HashMap::from([(0.into(), vec![7.into()])]));
+        let inputs: HashMap<GoldilocksField, _> = plan.inputs(&["1=8".to_string()]).unwrap();
+        assert_eq!(inputs, HashMap::from([(1.into(), vec![8.into()])]));
+
+        let large = ["-1,0xffffffff00000001".to_string()];
+        let inputs: HashMap<GoldilocksField, _> = plan.inputs(&large).unwrap();
+        assert_eq!(inputs[&0.into()], vec![-GoldilocksField::from(1), 0.into()]);
+        let plan = ExecutionPlan::try_from(Config::parse("strict-inputs = true").unwrap()).unwrap();
+        assert!(matches!(
+            plan.inputs::<GoldilocksField>(&large),
+            Err(InputError::OutOfRange { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn flags_and_config_give_same_plan() {
+        let flags = Config {
+            field: Some("bn254".to_string()),
+            output_directory: Some("build".to_string()),
+            inputs: Some(vec![3, 2, 1, 2]),
+            force: Some(true),
+            prove_with: Some("estark".to_string()),
+            export_csv: Some(true),
+            csv_mode: Some("i".to_string()),
+            csv_columns: Some(vec!["Binary.*".to_string(), "main.pc".to_string()]),
+            bname: Some("Example".to_string()),
+            coprocessors: Some(vec!["poseidon_gl".to_string()]),
+            deny_unconstrained: Some(true),
+            allow_unconstrained: Some(vec!["main.x".to_string()]),
+            max_inlined_intermediate_size: Some(0),
+            ..Default::default()
+        };
+        let config = Config::parse(
+            r#"
+            field = "bn254"
+            output-directory = "build"
+            inputs = [3, 2, 1, 2]
+            force = true
+            prove-with = "estark"
+            export-csv = true
+            csv-mode = "i"
+            csv-columns = ["Binary.*", "main.pc"]
+            bname = "Example"
+            coprocessors = ["poseidon_gl"]
+            deny-unconstrained = true
+            allow-unconstrained = ["main.x"]
+            max-inlined-intermediate-size = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            ExecutionPlan::new(flags, Config::default()).unwrap(),
+            ExecutionPlan::new(Config::default(), config).unwrap()
+        );
+    }
+
+    #[test]
+    fn print_effective_round_trip() {
+        let plan = ExecutionPlan::try_from(Config::parse("csv-mode = \"ui\"").unwrap()).unwrap();
+        let printed = toml::to_string(&plan.to_config()).unwrap();
+        let reparsed = ExecutionPlan::try_from(Config::parse(&printed).unwrap()).unwrap();
+        assert_eq!(plan, reparsed);
+    }
+
+    #[test]
+    fn discovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join(CONFIG_FILE_NAME), "field = \"bn254\"").unwrap();
+        let input = nested.join("test.asm");
+
+        let config = Config::load(None, Some(&input)).unwrap();
+        assert_eq!(config.field, Some("bn254".to_string()));
+
+        fs::write(nested.join(CONFIG_FILE_NAME), "force = true").unwrap();
+        let config = Config::load(None, Some(&input)).unwrap();
+        assert_eq!(config.field, None);
+        assert_eq!(config.force, Some(true));
+
+        let config = Config::load(Some(&dir.path().join(CONFIG_FILE_NAME)), Some(&input)).unwrap();
+        assert_eq!(config.field, Some("bn254".to_string()));
+    }
+}
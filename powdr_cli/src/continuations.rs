@@ -0,0 +1,550 @@
+//! Running long RISC-V executions in chunks (continuations).
+//!
+//! The dry run executes the whole program and computes, for every chunk, the state the
+//! bootloader needs to resume the execution at the start of that chunk: the register
+//! values and the memory pages the chunk accesses. These states can be written to a
+//! directory, one JSON file per chunk, and each chunk can then be run on its own,
+//! possibly on a different machine.
+//!
+//! The chunk boundaries are chosen by the dry run: each chunk executes as many rows as
+//! fit into the row budget, where the rows the instructions use in the submachines are
+//! taken from [INSTRUCTION_ROW_COSTS]. The chunk states record the number of rows, so
+//! running a chunk on its own does not need to recompute them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::iter::once;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use ast::asm_analysis::AnalysisASMFile;
+use number::FieldElement;
+use riscv::bootloader::{
+    default_input, BYTES_PER_WORD, PAGE_SIZE_BYTES_LOG, PC_INDEX, REGISTER_NAMES,
+};
+use riscv::compiler::INSTRUCTION_ROW_COSTS;
+use riscv_executor::ExecutionTrace;
+use serde::{Deserialize, Serialize};
+
+/// The version of the chunk file format, has to be increased whenever the format changes.
+pub const CHUNK_FILE_VERSION: u32 = 2;
+
+/// Where the chunk states are stored, which chunk to run and how many rows a chunk
+/// may use, see `--chunks-dir`, `--chunk` and `--chunk-rows`.
+#[derive(Default)]
+pub struct ChunkOptions {
+    pub chunks_dir: Option<PathBuf>,
+    pub chunk: Option<usize>,
+    pub row_budget: Option<usize>,
+}
+
+/// The state of the machine at the start of a chunk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkState {
+    /// The version of the file format, see [CHUNK_FILE_VERSION].
+    pub version: u32,
+    pub chunk_index: usize,
+    /// The register values in the order of [REGISTER_NAMES], as decimal numbers.
+    pub registers: Vec<String>,
+    /// The pages accessed by the chunk, by page index, with one value per word.
+    pub pages: Vec<(u32, Vec<u32>)>,
+    /// The number of rows the chunk executes, including the bootloader.
+    pub rows: usize,
+    /// The rows of the full execution the chunk proves. Every row is proven by exactly
+    /// one chunk, the first row after the bootloader repeats the last row of the previous chunk.
+    pub trace_rows: Range<usize>,
+}
+
+impl ChunkState {
+    /// The state at the start of the execution.
+    fn initial<F: FieldElement>() -> Self {
+        let inputs = default_input::<F>();
+        ChunkState {
+            version: CHUNK_FILE_VERSION,
+            chunk_index: 0,
+            registers: inputs[..REGISTER_NAMES.len()]
+                .iter()
+                .map(|v| v.to_arbitrary_integer().to_string())
+                .collect(),
+            pages: vec![],
+            rows: 0,
+            trace_rows: 0..0,
+        }
+    }
+
+    /// The inputs of the bootloader that restore this state.
+    pub fn bootloader_inputs<F: FieldElement>(&self) -> Vec<F> {
+        self.registers
+            .iter()
+            .map(|v| F::from_str_radix(v, 10).unwrap())
+            .chain(once(F::from(self.pages.len() as u64)))
+            .chain(self.pages.iter().flat_map(|(page, words)| {
+                once(F::from(*page)).chain(words.iter().map(|&word| F::from(word)))
+            }))
+            .collect()
+    }
+
+    /// The name of the file storing the state of the chunk with the given index.
+    pub fn file_name(chunk_index: usize) -> String {
+        format!("chunk_{chunk_index}.json")
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), String> {
+        let path = dir.join(Self::file_name(self.chunk_index));
+        let file = fs::File::create(&path)
+            .map_err(|e| format!("Could not create chunk file {}: {e}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)
+            .map_err(|e| e.to_string())
+            .and_then(|_| writer.flush().map_err(|e| e.to_string()))
+            .map_err(|e| format!("Could not write chunk file {}: {e}", path.display()))
+    }
+
+    pub fn read<F: FieldElement>(path: &Path) -> Result<Self, String> {
+        let error = |e: String| format!("Could not read chunk file {}: {e}", path.display());
+        let contents = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let value: serde_json::Value =
+            serde_json::from_str(&contents).map_err(|e| error(e.to_string()))?;
+        // Check the version first, other versions might not even deserialize.
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(version) if version == CHUNK_FILE_VERSION as u64 => {}
+            version => {
+                return Err(error(format!(
+                    "expected version {CHUNK_FILE_VERSION}, found {}",
+                    version.map_or("none".to_string(), |v| v.to_string())
+                )))
+            }
+        }
+        let state: Self = serde_json::from_value(value).map_err(|e| error(e.to_string()))?;
+        if state.registers.len() != REGISTER_NAMES.len() {
+            return Err(error(format!(
+                "expected {} registers, found {}",
+                REGISTER_NAMES.len(),
+                state.registers.len()
+            )));
+        }
+        if let Some(e) = state
+            .registers
+            .iter()
+            .find_map(|v| F::from_str_radix(v, 10).err())
+        {
+            return Err(error(format!("invalid register value: {e}")));
+        }
+        Ok(state)
+    }
+}
+
+fn transposed_trace<F: FieldElement>(trace: &ExecutionTrace) -> HashMap<String, Vec<F>> {
+    let mut reg_values: HashMap<&str, Vec<F>> = HashMap::with_capacity(trace.reg_map.len());
+
+    for row in trace.regs_rows() {
+        for (reg_name, &index) in trace.reg_map.iter() {
+            reg_values
+                .entry(reg_name.as_str())
+                .or_default()
+                .push(row[index].0.into());
+        }
+    }
+
+    reg_values
+        .into_iter()
+        .map(|(n, c)| (riscv::compiler::register_column_name(n), c))
+        .collect()
+}
+
+/// The maximum number of steps executed in each chunk.
+fn chunk_steps<F: FieldElement>(program: &AnalysisASMFile<F>) -> usize {
+    // Run for 2**degree - 2 steps, because the executor doesn't run the dispatcher,
+    // which takes 2 rows.
+    let degree = program
+        .machines
+        .iter()
+        .fold(None, |acc, (_, m)| acc.or(m.degree.clone()))
+        .unwrap()
+        .degree;
+    let degree = F::from(degree).to_degree();
+    degree as usize - 2
+}
+
+/// The number of leading rows of a trace with the given PCs such that no machine uses
+/// more than `budget` rows, where `row_costs` are the submachine rows used at each PC.
+fn rows_within_budget<F: FieldElement>(
+    row_costs: &[Vec<(&str, usize)>],
+    pcs: &[F],
+    budget: usize,
+) -> usize {
+    let mut used: HashMap<&str, usize> = HashMap::new();
+    pcs.iter()
+        .take(budget)
+        .position(|pc| {
+            let costs = row_costs
+                .get(pc.to_degree() as usize)
+                .map_or(&[][..], |c| &c[..]);
+            costs.iter().any(|&(machine, rows)| {
+                let used = used.entry(machine).or_default();
+                *used += rows;
+                *used > budget
+            })
+        })
+        .unwrap_or_else(|| pcs.len().min(budget))
+}
+
+fn compile_program<F: FieldElement>(file_name: &str, contents: &str) -> AnalysisASMFile<F> {
+    compiler::compile_asm_string_to_analyzed_ast::<F>(file_name, contents, None).unwrap()
+}
+
+pub fn rust_continuations<F: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: HashMap<F, Vec<F>>,
+    row_budget: Option<usize>,
+) {
+    rust_continuations_dry_run(&compile_program(file_name, contents), &inputs, row_budget);
+}
+
+/// Runs the dry run and writes the state at the start of each chunk to `out_dir`.
+pub fn rust_continuations_export<F: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: HashMap<F, Vec<F>>,
+    out_dir: &Path,
+    row_budget: Option<usize>,
+) -> Result<Vec<PathBuf>, String> {
+    let chunks =
+        rust_continuations_dry_run(&compile_program(file_name, contents), &inputs, row_budget);
+    fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Could not create directory {}: {e}", out_dir.display()))?;
+    chunks
+        .iter()
+        .map(|chunk| {
+            chunk.write(out_dir)?;
+            Ok(out_dir.join(ChunkState::file_name(chunk.chunk_index)))
+        })
+        .collect()
+}
+
+/// Executes a single chunk, starting from the state stored in `chunk_file`.
+/// Returns the trace of the registers.
+pub fn rust_continuations_run_chunk<F: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: HashMap<F, Vec<F>>,
+    chunk_file: &Path,
+) -> Result<HashMap<String, Vec<F>>, String> {
+    let chunk = ChunkState::read::<F>(chunk_file)?;
+    log::info!("Running chunk {}...", chunk.chunk_index);
+    Ok(execute_chunk(
+        &compile_program(file_name, contents),
+        &inputs,
+        &chunk,
+    ))
+}
+
+fn execute_chunk<F: FieldElement>(
+    program: &AnalysisASMFile<F>,
+    inputs: &HashMap<F, Vec<F>>,
+    chunk: &ChunkState,
+) -> HashMap<String, Vec<F>> {
+    let (trace, _) =
+        riscv_executor::execute_ast::<F>(program, inputs, &chunk.bootloader_inputs(), chunk.rows);
+    transposed_trace(&trace)
+}
+
+/// Executes the program in chunks and checks that they result in the same trace as
+/// executing it at once. Returns the state at the start of each chunk.
+/// Each chunk uses at most `row_budget` rows in every machine, which defaults to
+/// (and cannot exceed) the number of rows available for the execution.
+pub fn rust_continuations_dry_run<F: FieldElement>(
+    program: &AnalysisASMFile<F>,
+    inputs: &HashMap<F, Vec<F>>,
+    row_budget: Option<usize>,
+) -> Vec<ChunkState> {
+    let max_rows = chunk_steps(program);
+    let budget = row_budget.unwrap_or(max_rows);
+    assert!(
+        budget <= max_rows,
+        "The row budget of {budget} rows exceeds the {max_rows} rows available in a chunk."
+    );
+    let row_costs = riscv_executor::submachine_rows_by_pc(program, &INSTRUCTION_ROW_COSTS);
+    let mut chunks = vec![ChunkState::initial::<F>()];
+
+    log::info!("Executing powdr-asm...");
+    let (full_trace, memory_accesses) = {
+        let trace =
+            riscv_executor::execute_ast::<F>(program, inputs, &default_input(), usize::MAX).0;
+        (transposed_trace::<F>(&trace), trace.mem)
+    };
+
+    let full_trace_length = full_trace["main.pc"].len();
+    log::info!("Total trace length: {}", full_trace_length);
+
+    let mut proven_trace = 0;
+    let mut memory_snapshot = HashMap::new();
+
+    loop {
+        let chunk = chunks.last().unwrap();
+        let chunk_index = chunk.chunk_index;
+        let bootloader_inputs = chunk.bootloader_inputs::<F>();
+        log::info!("\nRunning chunk {}...", chunk_index);
+        let execute = |rows| {
+            let (trace, memory_snapshot_update) =
+                riscv_executor::execute_ast::<F>(program, inputs, &bootloader_inputs, rows);
+            (transposed_trace::<F>(&trace), memory_snapshot_update)
+        };
+        let (mut chunk_trace, mut memory_snapshot_update) = execute(budget);
+        let (start, _) = chunk_trace["main.pc"]
+            .iter()
+            .enumerate()
+            .find(|(_, &pc)| pc == bootloader_inputs[PC_INDEX])
+            .unwrap();
+
+        let executed_rows = chunk_trace["main.pc"].len();
+        let rows = rows_within_budget(&row_costs, &chunk_trace["main.pc"], budget);
+        assert!(
+            rows > start + 1,
+            "The row budget of {budget} rows is too small, the bootloader of chunk {chunk_index} uses {start} rows."
+        );
+        if rows < executed_rows {
+            log::info!("Ending the chunk after {rows} rows to stay within the row budget.");
+            // Execute again to get the memory at the end of the shorter chunk.
+            (chunk_trace, memory_snapshot_update) = execute(rows);
+        }
+        log::info!("{} memory slots updated.", memory_snapshot_update.len());
+        memory_snapshot.extend(memory_snapshot_update);
+        log::info!("Chunk trace length: {}", chunk_trace["main.pc"].len());
+
+        log::info!("Validating chunk...");
+        let full_trace_start = match chunk_index {
+            // The bootloader execution in the first chunk is part of the full trace.
+            0 => start,
+            // Any other chunk starts at where we left off in the full trace.
+            _ => proven_trace - 1,
+        };
+        log::info!("Bootloader used {} rows.", start);
+        for i in 0..(chunk_trace["main.pc"].len() - start) {
+            for &reg in REGISTER_NAMES.iter() {
+                let chunk_i = i + start;
+                let full_i = i + full_trace_start;
+                if chunk_trace[reg][chunk_i] != full_trace[reg][full_i] {
+                    log::error!("The Chunk trace differs from the full trace!");
+                    log::error!(
+                        "Started comparing from row {start} in the chunk to row {full_trace_start} in the full trace; the difference is at offset {i}."
+                    );
+                    log::error!(
+                        "The PCs are {} and {}.",
+                        chunk_trace["main.pc"][chunk_i],
+                        full_trace["main.pc"][full_i]
+                    );
+                    log::error!(
+                        "The first difference is in register {}: {} != {} ",
+                        reg,
+                        chunk_trace[reg][chunk_i],
+                        full_trace[reg][full_i],
+                    );
+                    panic!();
+                }
+            }
+        }
+
+        let chunk = chunks.last_mut().unwrap();
+        chunk.rows = rows;
+        chunk.trace_rows = proven_trace..full_trace_start + rows - start;
+        proven_trace = chunk.trace_rows.end;
+        log::info!("Proved {} rows.", chunk.trace_rows.len());
+
+        if rows == executed_rows && executed_rows < budget {
+            log::info!("Done!");
+            break;
+        }
+
+        log::info!("Building inputs for chunk {}...", chunk_index + 1);
+        let mut accessed_pages = BTreeSet::new();
+        let start_idx = memory_accesses
+            .binary_search_by_key(&proven_trace, |a| a.idx)
+            .unwrap_or_else(|v| v);
+
+        for access in &memory_accesses[start_idx..] {
+            // proven_trace + budget is an upper bound for the last row index we'll reach in the next chunk.
+            // In practice, we'll stop earlier, because the bootloader needs to run as well, but we don't know for
+            // how long as that depends on the number of pages.
+            if access.idx >= proven_trace + budget {
+                break;
+            }
+            accessed_pages.insert(access.address >> PAGE_SIZE_BYTES_LOG);
+        }
+        log::info!(
+            "{} accessed pages: {:?}",
+            accessed_pages.len(),
+            accessed_pages
+        );
+
+        let words_per_page = (1 << (PAGE_SIZE_BYTES_LOG)) / BYTES_PER_WORD;
+        let next_chunk = ChunkState {
+            version: CHUNK_FILE_VERSION,
+            chunk_index: chunk_index + 1,
+            registers: REGISTER_NAMES
+                .iter()
+                .map(|&reg| {
+                    chunk_trace[reg]
+                        .last()
+                        .unwrap()
+                        .to_arbitrary_integer()
+                        .to_string()
+                })
+                .collect(),
+            pages: accessed_pages
+                .into_iter()
+                .map(|page| {
+                    let start_addr = page << PAGE_SIZE_BYTES_LOG;
+                    let words = (0..words_per_page)
+                        .map(|i| {
+                            let addr = start_addr + (i * BYTES_PER_WORD) as u32;
+                            *memory_snapshot.get(&addr).unwrap_or(&0)
+                        })
+                        .collect();
+                    (page, words)
+                })
+                .collect(),
+            rows: 0,
+            trace_rows: 0..0,
+        };
+        log::info!(
+            "Inputs length: {}",
+            next_chunk.bootloader_inputs::<F>().len()
+        );
+
+        chunks.push(next_chunk);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use riscv::CoProcessors;
+
+    use super::*;
+
+    /// Counts to 100000 and keeps a running sum on the stack, which makes the
+    /// execution span several chunks that each need the stack page.
+    const LOOP: &str = r#"
+.globl __runtime_start
+__runtime_start:
+    li a0, 0
+    li a1, 200000
+    sw zero, -4(sp)
+loop:
+    lw t0, -4(sp)
+    add t0, t0, a0
+    sw t0, -4(sp)
+    addi a0, a0, 1
+    bne a0, a1, loop
+    ret
+"#;
+
+    #[test]
+    fn chunk_round_trip() {
+        let asm = riscv::compiler::compile(
+            [("loop.s".to_string(), LOOP.to_string())].into(),
+            // The bootloader hashes the pages with Poseidon.
+            &CoProcessors::base().with_poseidon(),
+            true,
+        );
+        let program = compile_program::<GoldilocksField>("loop.asm", &asm);
+        let inputs = HashMap::new();
+        let chunks = rust_continuations_dry_run(&program, &inputs, None);
+        assert!(chunks.len() > 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let chunk = &chunks[2];
+        assert_eq!(chunk.pages.len(), 1);
+        chunk.write(dir.path()).unwrap();
+        let chunk_file = dir.path().join(ChunkState::file_name(2));
+        assert_eq!(
+            &ChunkState::read::<GoldilocksField>(&chunk_file).unwrap(),
+            chunk
+        );
+
+        let in_process = execute_chunk(&program, &inputs, chunk);
+        let reloaded = rust_continuations_run_chunk("loop.asm", &asm, inputs, &chunk_file).unwrap();
+        assert_eq!(in_process, reloaded);
+    }
+
+    /// Alternates between cheap instructions and instructions that use many rows of
+    /// the binary machine (`xor`) or a row of the arith machine (`mul`).
+    const MIXED: &str = r#"
+.globl __runtime_start
+__runtime_start:
+    li a0, 0
+    li a1, 20000
+    li a2, 0
+loop:
+    xor a2, a2, a0
+    mul a3, a0, a0
+    xor a2, a2, a3
+    xor a2, a2, a1
+    xor a2, a2, a3
+    addi a0, a0, 1
+    bne a0, a1, loop
+    ret
+"#;
+
+    #[test]
+    fn chunks_within_row_budget() {
+        let asm = riscv::compiler::compile(
+            [("mixed.s".to_string(), MIXED.to_string())].into(),
+            &CoProcessors::base().with_poseidon(),
+            true,
+        );
+        let program = compile_program::<GoldilocksField>("mixed.asm", &asm);
+        let inputs = HashMap::new();
+        let budget = 50000;
+        let chunks = rust_continuations_dry_run(&program, &inputs, Some(budget));
+        assert!(chunks.len() > 2);
+
+        let row_costs = riscv_executor::submachine_rows_by_pc(&program, &INSTRUCTION_ROW_COSTS);
+        for chunk in &chunks {
+            let trace = execute_chunk(&program, &inputs, chunk);
+            let pcs = &trace["main.pc"];
+            assert_eq!(pcs.len(), chunk.rows);
+            assert!(chunk.rows <= budget);
+            let mut used: HashMap<&str, usize> = HashMap::new();
+            for pc in pcs {
+                for &(machine, rows) in &row_costs[pc.to_degree() as usize] {
+                    *used.entry(machine).or_default() += rows;
+                }
+            }
+            assert!(used.values().all(|&rows| rows <= budget));
+        }
+        // The chunks end before the main machine runs out of rows, because the
+        // binary machine reaches the budget first.
+        assert!(chunks[..chunks.len() - 1]
+            .iter()
+            .all(|chunk| chunk.rows < budget));
+
+        let full_trace_length =
+            riscv_executor::execute_ast(&program, &inputs, &default_input(), usize::MAX)
+                .0
+                .row_count();
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].trace_rows.end, pair[1].trace_rows.start);
+        }
+        assert_eq!(chunks[0].trace_rows.start, 0);
+        assert_eq!(chunks.last().unwrap().trace_rows.end, full_trace_length);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunk_file = dir.path().join(ChunkState::file_name(0));
+        fs::write(&chunk_file, r#"{"version":1,"chunk_index":0}"#).unwrap();
+        assert_eq!(
+            ChunkState::read::<GoldilocksField>(&chunk_file).unwrap_err(),
+            format!(
+                "Could not read chunk file {}: expected version 2, found 1",
+                chunk_file.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Checking that the RISC-V executor and witness generation agree (`--check-executor`).
+//!
+//! Both are run on the same program and the values of the PC and of the register bank,
+//! see [riscv::compiler::register_columns], are compared row by row. The rows of the
+//! executor trace correspond to the rows of the witness, see [ExecutionTrace].
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use ast::analyzed::Analyzed;
+use ast::asm_analysis::{AnalysisASMFile, FunctionStatement};
+use ast::parsed::asm::DebugDirective;
+use compiler::channel_inputs_to_query_callback;
+use executor::constant_evaluator;
+use executor::witgen::WitnessGenerator;
+use number::FieldElement;
+use riscv::bootloader::default_input;
+use riscv::compiler::register_columns;
+use riscv_executor::ExecutionTrace;
+
+use crate::witgen_options::WitgenOptions;
+
+/// The first row in which the executor trace and the witness differ.
+#[derive(Debug, PartialEq)]
+pub struct Divergence<T> {
+    pub row: usize,
+    /// The PIL column of the first register that differs in the row.
+    pub column: String,
+    pub executor_value: T,
+    pub witness_value: T,
+    /// The PC of the executor in the row.
+    pub pc: usize,
+    /// The statements executed at `pc`, None for the proof initialization rows.
+    pub instruction: Option<String>,
+}
+
+impl<T: Display> Display for Divergence<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The executor and witness generation differ in row {}: {} is {} in the executor trace, but {} in the witness.",
+            self.row, self.column, self.executor_value, self.witness_value
+        )?;
+        match &self.instruction {
+            Some(instruction) => write!(f, "\nInstruction at pc {}: {instruction}", self.pc),
+            None => write!(
+                f,
+                "\nThe row (pc {}) is a proof initialization row.",
+                self.pc
+            ),
+        }
+    }
+}
+
+/// Runs the executor and witness generation on the powdr-asm program and fails with
+/// a description of the first row in which they differ.
+/// Uses the degree of the program, the executor runs until the program returns.
+pub fn check_executor<F: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: HashMap<F, Vec<F>>,
+    witgen_options: &WitgenOptions,
+) -> Result<(), Vec<String>> {
+    let program = compiler::compile_asm_string_to_analyzed_ast::<F>(file_name, contents, None)?;
+    log::info!("Executing powdr-asm...");
+    let (trace, _) = riscv_executor::execute_ast(&program, &inputs, &default_input(), usize::MAX);
+    log::info!("Running witness generation...");
+    let seed = witgen_options.seed_from_executor.then_some(&trace);
+    let witness = generate_witness(file_name, contents, inputs, seed, witgen_options)?;
+    match compare_traces(&program, &trace, &witness) {
+        Some(divergence) => Err(vec![divergence.to_string()]),
+        None => {
+            log::info!(
+                "The executor and witness generation agree on all {} rows of the executor trace.",
+                trace.row_count()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Runs witness generation, seeding the registers with the values of `seed` if given.
+fn generate_witness<F: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: HashMap<F, Vec<F>>,
+    seed: Option<&ExecutionTrace>,
+    witgen_options: &WitgenOptions,
+) -> Result<Vec<(String, Vec<F>)>, Vec<String>> {
+    let analyzed = compiler::compile_asm_string_to_analyzed_pil::<F>(file_name, contents)?;
+    let constants = constant_evaluator::generate(&analyzed);
+    let seeded_values = seed
+        .map(|trace| seeded_register_values(&analyzed, trace))
+        .unwrap_or_default();
+    let query_callback = witgen_options
+        .query_callback(channel_inputs_to_query_callback(inputs))
+        .map_err(|e| vec![e])?;
+    let generator = witgen_options
+        .apply(WitnessGenerator::new(&analyzed, &constants, query_callback))
+        .map_err(|e| vec![e])?;
+    generator
+        .with_seeded_witness_values(
+            seeded_values
+                .iter()
+                .map(|(column, values)| (column.as_str(), values.clone()))
+                .collect(),
+        )
+        .try_generate()
+        .map_err(|e| {
+            vec![format!(
+                "The executor succeeded, but witness generation failed: {e}"
+            )]
+        })
+}
+
+/// The values of the registers of [register_columns] that the program has, in the
+/// rows of the executor trace that the witness has.
+fn seeded_register_values<F: FieldElement>(
+    analyzed: &Analyzed<F>,
+    trace: &ExecutionTrace,
+) -> Vec<(String, Vec<F>)> {
+    let witness_columns = analyzed
+        .committed_polys_in_source_order()
+        .iter()
+        .flat_map(|(poly, _)| poly.array_elements())
+        .map(|(name, _)| name)
+        .collect::<HashSet<_>>();
+    let rows = trace.row_count().min(analyzed.degree() as usize);
+    register_columns()
+        .into_iter()
+        .filter(|(register, column)| {
+            trace.reg_map.contains_key(register) && witness_columns.contains(column)
+        })
+        .map(|(register, column)| {
+            let values = (0..rows).map(|row| trace.reg(row, &register).0.into());
+            (column, values.collect())
+        })
+        .collect()
+}
+
+/// Compares the registers of [register_columns] that the program has, in the rows
+/// present in both the trace and the witness.
+fn compare_traces<F: FieldElement>(
+    program: &AnalysisASMFile<F>,
+    trace: &ExecutionTrace,
+    witness: &[(String, Vec<F>)],
+) -> Option<Divergence<F>> {
+    let witness: HashMap<_, _> = witness
+        .iter()
+        .map(|(name, values)| (name.as_str(), values))
+        .collect();
+    let columns = register_columns()
+        .into_iter()
+        .filter(|(register, _)| trace.reg_map.contains_key(register))
+        .filter_map(|(register, column)| {
+            let values = witness.get(column.as_str())?;
+            Some((register, column, *values))
+        })
+        .collect::<Vec<_>>();
+    let rows = columns
+        .iter()
+        .map(|(_, _, values)| values.len())
+        .fold(trace.row_count(), usize::min);
+
+    (0..rows).find_map(|row| {
+        columns.iter().find_map(|(register, column, values)| {
+            let executor_value: F = trace.reg(row, register).0.into();
+            (executor_value != values[row]).then(|| {
+                let pc = trace.pc(row).0 as usize;
+                Divergence {
+                    row,
+                    column: column.clone(),
+                    executor_value,
+                    witness_value: values[row],
+                    pc,
+                    instruction: riscv_executor::statements_at_pc(program, pc)
+                        .map(format_statements),
+                }
+            })
+        })
+    })
+}
+
+/// Formats the statements of a batch, leaving out the source locations.
+fn format_statements<F: FieldElement>(statements: &[FunctionStatement<F>]) -> String {
+    statements
+        .iter()
+        .filter(|s| {
+            !matches!(
+                s,
+                FunctionStatement::DebugDirective(d)
+                    if matches!(d.directive, DebugDirective::Loc(..) | DebugDirective::File(..))
+            )
+        })
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+
+    use super::*;
+
+    const PROGRAM: &str = r#"
+machine Main {
+    degree 16;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg x0;
+    reg x1;
+    reg x2;
+
+    function main {
+        x1 <=X= 7;
+        x2 <=X= x1 + 1;
+        x1 <=X= x2 * 2;
+        return;
+    }
+}
+"#;
+
+    #[test]
+    fn agree() {
+        check_executor::<GoldilocksField>(
+            "main.asm",
+            PROGRAM,
+            HashMap::new(),
+            &WitgenOptions::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn divergence() {
+        let program = compiler::compile_asm_string_to_analyzed_ast::<GoldilocksField>(
+            "main.asm", PROGRAM, None,
+        )
+        .unwrap();
+        let (mut trace, _) =
+            riscv_executor::execute_ast(&program, &HashMap::new(), &default_input(), usize::MAX);
+        let witness = generate_witness(
+            "main.asm",
+            PROGRAM,
+            HashMap::new(),
+            None,
+            &WitgenOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(compare_traces(&program, &trace, &witness), None);
+
+        // Rows 0 and 1 initialize the proof, so `x2` is assigned in row 3 and
+        // is first read in row 4.
+        let row = 4;
+        let index = row * trace.reg_map.len() + trace.reg_map["x2"];
+        trace.regs[index] = riscv_executor::Elem(9);
+        let divergence = compare_traces(&program, &trace, &witness).unwrap();
+        assert_eq!(divergence.row, row);
+        assert_eq!(divergence.column, "main.x2");
+        assert_eq!(divergence.executor_value, 9.into());
+        assert_eq!(divergence.witness_value, 8.into());
+        assert_eq!(divergence.pc, 4);
+        assert_eq!(divergence.instruction.unwrap(), "x1 <=X= (x2 * 2);");
+    }
+
+    #[test]
+    fn seeded_from_executor() {
+        let seeded = WitgenOptions {
+            seed_from_executor: true,
+            ..Default::default()
+        };
+        check_executor::<GoldilocksField>("main.asm", PROGRAM, HashMap::new(), &seeded).unwrap();
+
+        let program = compiler::compile_asm_string_to_analyzed_ast::<GoldilocksField>(
+            "main.asm", PROGRAM, None,
+        )
+        .unwrap();
+        let (mut trace, _) =
+            riscv_executor::execute_ast(&program, &HashMap::new(), &default_input(), usize::MAX);
+        let index = 4 * trace.reg_map.len() + trace.reg_map["x2"];
+        trace.regs[index] = riscv_executor::Elem(9);
+        let errors = generate_witness::<GoldilocksField>(
+            "main.asm",
+            PROGRAM,
+            HashMap::new(),
+            Some(&trace),
+            &WitgenOptions::default(),
+        )
+        .unwrap_err();
+        assert!(errors[0].contains("(seed divergence)"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn divergence_in_initialization_row() {
+        let program = compiler::compile_asm_string_to_analyzed_ast::<GoldilocksField>(
+            "main.asm", PROGRAM, None,
+        )
+        .unwrap();
+        let (mut trace, _) =
+            riscv_executor::execute_ast(&program, &HashMap::new(), &default_input(), usize::MAX);
+        let witness = generate_witness(
+            "main.asm",
+            PROGRAM,
+            HashMap::new(),
+            None,
+            &WitgenOptions::default(),
+        )
+        .unwrap();
+
+        let index = trace.reg_map.len() + trace.reg_map["pc"];
+        trace.regs[index] = riscv_executor::Elem(0);
+        let divergence = compare_traces(&program, &trace, &witness).unwrap();
+        assert_eq!(divergence.row, 1);
+        assert_eq!(divergence.column, "main.pc");
+        assert_eq!(divergence.instruction, None);
+    }
+}
@@ -0,0 +1,382 @@
+//! Reading the free inputs of the prover from the command line or from a file.
+//!
+//! Inline inputs are a comma-separated list of numbers. An inputs file is either a
+//! JSON array of strings (if its extension is `.json`) or a raw binary file containing
+//! the field elements in little-endian encoding, each padded to a multiple of 8 bytes.
+//! In both textual forms, values are decimal or hexadecimal with a `0x` prefix, optionally
+//! preceded by `-` for the additive inverse in the field. Values that are not smaller than
+//! the field modulus are reduced with a warning, or rejected in strict mode.
+//!
+//! The inputs are read by the prover through input channels. Inline inputs can be given
+//! for several channels as `<channel>=<inputs>`, inputs without a channel and inputs from
+//! a file are in channel 0.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use number::{AbstractNumberType, BigInt, FieldElement};
+
+#[derive(Debug)]
+pub enum InputError {
+    /// Inputs were given both inline and as a file.
+    Conflict,
+    /// The value at `index` could not be parsed as a number.
+    Malformed {
+        value: String,
+        index: usize,
+        reason: String,
+    },
+    /// The value at `index` is not smaller than the field modulus (only in strict mode).
+    OutOfRange { value: String, index: usize },
+    /// The inputs file could not be read.
+    Io { path: PathBuf, error: io::Error },
+    /// The inputs file is not a JSON array of strings.
+    Json { path: PathBuf, reason: String },
+    /// The size of a binary inputs file is not a multiple of the size of a field element.
+    BinaryLength {
+        path: PathBuf,
+        length: usize,
+        width: usize,
+    },
+    /// The channel of inline inputs is not a number.
+    InvalidChannel { channel: String },
+    /// Inline inputs were given more than once for the same channel.
+    DuplicateChannel { channel: u64 },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Conflict => write!(f, "Inputs cannot be given both inline and as a file."),
+            InputError::Malformed {
+                value,
+                index,
+                reason,
+            } => {
+                write!(f, "Invalid input value \"{value}\" at index {index}: {reason}")
+            }
+            InputError::OutOfRange { value, index } => write!(
+                f,
+                "Input value {value} at index {index} is not smaller than the field modulus."
+            ),
+            InputError::Io { path, error } => {
+                write!(f, "Could not read inputs file {}: {error}", path.display())
+            }
+            InputError::Json { path, reason } => write!(
+                f,
+                "Inputs file {} is not a JSON array of strings: {reason}",
+                path.display()
+            ),
+            InputError::BinaryLength {
+                path,
+                length,
+                width,
+            } => write!(
+                f,
+                "Inputs file {} has {length} bytes, which is not a multiple of the {width} bytes of a field element.",
+                path.display()
+            ),
+            InputError::InvalidChannel { channel } => {
+                write!(f, "Invalid input channel \"{channel}\": expected a number")
+            }
+            InputError::DuplicateChannel { channel } => {
+                write!(f, "Inputs for channel {channel} are given more than once.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Reads the free inputs either from the comma-separated list `inline` or from `file`.
+/// Returns an empty list if neither is given. If `strict` is set, values that are not
+/// smaller than the field modulus are an error instead of being reduced.
+pub fn read_inputs<T: FieldElement>(
+    inline: Option<&str>,
+    file: Option<&Path>,
+    strict: bool,
+) -> Result<Vec<T>, InputError> {
+    match (inline, file) {
+        (Some(_), Some(_)) => Err(InputError::Conflict),
+        (Some(inline), None) => inline
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .enumerate()
+            .map(|(index, x)| parse_input(x, index, strict))
+            .collect(),
+        (None, Some(file)) => read_inputs_file(file, strict),
+        (None, None) => Ok(vec![]),
+    }
+}
+
+/// Reads the inline inputs of several channels. Each element of `inline` is either
+/// `<channel>=<inputs>` or just `<inputs>` for channel 0, where `<inputs>` is a
+/// comma-separated list of numbers.
+pub fn read_channel_inputs<T: FieldElement>(
+    inline: &[String],
+    strict: bool,
+) -> Result<HashMap<T, Vec<T>>, InputError> {
+    let mut channels = HashMap::new();
+    for arg in inline {
+        let (channel, inputs) = match arg.split_once('=') {
+            Some((channel, inputs)) => (
+                channel
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| InputError::InvalidChannel {
+                        channel: channel.to_string(),
+                    })?,
+                inputs,
+            ),
+            None => (0, arg.as_str()),
+        };
+        let inputs = read_inputs(Some(inputs), None, strict)?;
+        if channels.insert(T::from(channel), inputs).is_some() {
+            return Err(InputError::DuplicateChannel { channel });
+        }
+    }
+    Ok(channels)
+}
+
+fn read_inputs_file<T: FieldElement>(path: &Path, strict: bool) -> Result<Vec<T>, InputError> {
+    let contents = fs::read(path).map_err(|error| InputError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    if path.extension().map_or(false, |ext| ext == "json") {
+        let values: Vec<String> =
+            serde_json::from_slice(&contents).map_err(|e| InputError::Json {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            })?;
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, v)| parse_input(v.trim(), index, strict))
+            .collect()
+    } else {
+        let width = (T::BITS as usize + 63) / 64 * 8;
+        if contents.len() % width != 0 {
+            return Err(InputError::BinaryLength {
+                path: path.to_path_buf(),
+                length: contents.len(),
+                width,
+            });
+        }
+        contents
+            .chunks(width)
+            .enumerate()
+            .map(|(index, bytes)| {
+                let n = AbstractNumberType::from_bytes_le(bytes);
+                to_field_element(n.to_string(), n, index, strict)
+            })
+            .collect()
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal value, optionally preceded by `-`,
+/// into a field element. `index` is the position of the value in the list of inputs.
+fn parse_input<T: FieldElement>(value: &str, index: usize, strict: bool) -> Result<T, InputError> {
+    let (negative, magnitude) = match value.strip_prefix('-') {
+        Some(magnitude) => (true, magnitude),
+        None => (false, value),
+    };
+    let (digits, radix) = match magnitude.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (magnitude, 10),
+    };
+    // `parse_bytes` accepts a sign, which we only allow in the form of a single leading `-`.
+    let n = (!digits.starts_with(['+', '-']))
+        .then(|| AbstractNumberType::parse_bytes(digits.as_bytes(), radix))
+        .flatten()
+        .ok_or_else(|| InputError::Malformed {
+            value: value.to_string(),
+            index,
+            reason: if radix == 16 {
+                "expected a hexadecimal number".to_string()
+            } else {
+                "expected a decimal number or a hexadecimal number with 0x prefix".to_string()
+            },
+        })?;
+    let x: T = to_field_element(value.to_string(), n, index, strict)?;
+    Ok(if negative { -x } else { x })
+}
+
+/// Converts `n` into a field element, reducing it modulo the field modulus with a warning
+/// if it is too large, or returning an error if `strict` is set.
+fn to_field_element<T: FieldElement>(
+    value: String,
+    n: AbstractNumberType,
+    index: usize,
+    strict: bool,
+) -> Result<T, InputError> {
+    let modulus = T::modulus().to_arbitrary_integer();
+    if n < modulus {
+        Ok(n.into())
+    } else if strict {
+        Err(InputError::OutOfRange { value, index })
+    } else {
+        let reduced = n % modulus;
+        log::warn!(
+            "Input value {value} at index {index} is not smaller than the field modulus, using {reduced} instead."
+        );
+        Ok(reduced.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use number::{Bn254Field, GoldilocksField};
+
+    #[test]
+    fn inline() {
+        let inputs: Vec<GoldilocksField> = read_inputs(Some("3, 0x10,,1"), None, false).unwrap();
+        assert_eq!(inputs, vec![3.into(), 16.into(), 1.into()]);
+        assert!(read_inputs::<GoldilocksField>(None, None, false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn malformed() {
+        let err = read_inputs::<GoldilocksField>(Some("1,x2"), None, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid input value \"x2\" at index 1: expected a decimal number or a hexadecimal number with 0x prefix"
+        );
+        for value in ["0xzz", "0x", "-", "--1", "-+1", "+1", "1.5"] {
+            let err = read_inputs::<GoldilocksField>(Some(value), None, false).unwrap_err();
+            assert!(
+                matches!(err, InputError::Malformed { index: 0, .. }),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn negative() {
+        let inputs: Vec<GoldilocksField> = read_inputs(Some("-1, -0x10, -0"), None, false).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                -GoldilocksField::from(1),
+                -GoldilocksField::from(16),
+                0.into()
+            ]
+        );
+        assert_eq!(inputs[0], GoldilocksField::from(0xffffffff00000000u64));
+    }
+
+    #[test]
+    fn out_of_range() {
+        // Values that are too large are reduced modulo the field modulus.
+        let inputs: Vec<GoldilocksField> = read_inputs(
+            Some("0xffffffff00000001, 18446744069414584323, -0xffffffff00000002"),
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(inputs, vec![0.into(), 2.into(), -GoldilocksField::from(1)]);
+        // Arbitrary-precision values fit into the larger field.
+        let inputs: Vec<Bn254Field> = read_inputs(
+            Some("0xffffffff00000001, 340282366920938463463374607431768211456"),
+            None,
+            true,
+        )
+        .unwrap();
+        let x = Bn254Field::from(1u64 << 32);
+        assert_eq!(inputs[1], x * x * x * x);
+
+        let err =
+            read_inputs::<GoldilocksField>(Some("1, 0xffffffff00000001"), None, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Input value 0xffffffff00000001 at index 1 is not smaller than the field modulus."
+        );
+        let err =
+            read_channel_inputs::<GoldilocksField>(&["1=-18446744069414584321".to_string()], true)
+                .unwrap_err();
+        assert!(matches!(err, InputError::OutOfRange { index: 0, .. }));
+    }
+
+    #[test]
+    fn json_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inputs.json");
+        fs::write(&path, r#"["3", "0x2", "-1"]"#).unwrap();
+        let inputs: Vec<GoldilocksField> = read_inputs(None, Some(&path), false).unwrap();
+        assert_eq!(inputs, vec![3.into(), 2.into(), -GoldilocksField::from(1)]);
+
+        fs::write(&path, r#"["3", "y"]"#).unwrap();
+        let err = read_inputs::<GoldilocksField>(None, Some(&path), false).unwrap_err();
+        assert!(matches!(err, InputError::Malformed { index: 1, .. }));
+
+        fs::write(&path, "[3, 2]").unwrap();
+        let err = read_inputs::<GoldilocksField>(None, Some(&path), false).unwrap_err();
+        assert!(matches!(err, InputError::Json { .. }));
+    }
+
+    #[test]
+    fn binary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inputs.bin");
+        let values: Vec<GoldilocksField> = vec![3.into(), 2.into(), 0x1234.into()];
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_bytes_le()).collect();
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(
+            read_inputs::<GoldilocksField>(None, Some(&path), false).unwrap(),
+            values
+        );
+
+        // 2^64 - 1 is reduced, unless in strict mode.
+        fs::write(&path, [[0xff; 8], [1, 0, 0, 0, 0, 0, 0, 0]].concat()).unwrap();
+        assert_eq!(
+            read_inputs::<GoldilocksField>(None, Some(&path), false).unwrap(),
+            vec![0xfffffffeu64.into(), 1.into()]
+        );
+        let err = read_inputs::<GoldilocksField>(None, Some(&path), true).unwrap_err();
+        assert!(matches!(err, InputError::OutOfRange { index: 0, .. }));
+
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let err = read_inputs::<GoldilocksField>(None, Some(&path), false).unwrap_err();
+        assert!(matches!(err, InputError::BinaryLength { .. }));
+    }
+
+    #[test]
+    fn channels() {
+        let inline = ["0=1,2,3".to_string(), "1=5, 0x6".to_string()];
+        let inputs: HashMap<GoldilocksField, Vec<GoldilocksField>> =
+            read_channel_inputs(&inline, false).unwrap();
+        assert_eq!(
+            inputs,
+            HashMap::from([
+                (0.into(), vec![1.into(), 2.into(), 3.into()]),
+                (1.into(), vec![5.into(), 6.into()])
+            ])
+        );
+        // Without a channel, the inputs are in channel 0.
+        let inputs: HashMap<GoldilocksField, Vec<GoldilocksField>> =
+            read_channel_inputs(&["4,5".to_string()], false).unwrap();
+        assert_eq!(
+            inputs,
+            HashMap::from([(0.into(), vec![4.into(), 5.into()])])
+        );
+
+        let err =
+            read_channel_inputs::<GoldilocksField>(&["1,2".to_string(), "0=3".to_string()], false)
+                .unwrap_err();
+        assert!(matches!(err, InputError::DuplicateChannel { channel: 0 }));
+        let err = read_channel_inputs::<GoldilocksField>(&["x=3".to_string()], false).unwrap_err();
+        assert!(matches!(err, InputError::InvalidChannel { .. }));
+    }
+
+    #[test]
+    fn conflict() {
+        let err = read_inputs::<GoldilocksField>(Some("1"), Some(Path::new("inputs.json")), false)
+            .unwrap_err();
+        assert!(matches!(err, InputError::Conflict));
+    }
+}
@@ -1,36 +1,107 @@
 //! The powdr CLI tool
 
+mod column_filter;
+mod config;
+#[cfg(feature = "riscv")]
+mod continuations;
+#[cfg(feature = "riscv")]
+mod executor_check;
+mod inputs;
+mod pil_diff;
+mod report;
+mod row_inspector;
 mod util;
+mod witgen_options;
 
-use backend::{Backend, BackendType, Proof};
+use ast::analyzed::Analyzed;
+use backend::{Backend, BackendType, Proof, ProofFile, ProofMetadata};
 use clap::{CommandFactory, Parser, Subcommand};
-use compiler::util::{read_poly_set, FixedPolySet, WitnessPolySet};
-use compiler::{compile_asm_string, compile_pil_or_asm, CompilationResult};
+use column_filter::ColumnFilter;
+use compiler::artifacts::{Artifacts, ARTIFACTS_FILE_NAME};
+#[cfg(feature = "riscv")]
+use compiler::compile_asm_string_with_callback;
+use compiler::util::{
+    map_poly_set, read_poly_set, try_write_atomically, write_atomically, FixedPolySet, PolySet,
+    WitnessPolySet, WriteError,
+};
+use compiler::witness_transformer::{apply_witness_transformers, RowBlinding, WitnessTransformer};
+use compiler::{
+    channel_inputs_to_query_callback, compile_pil_or_asm_with_callback, CancellationToken,
+    Cancelled, CompilationResult, Limits, UnconstrainedWitnesses,
+};
+use config::{Config, ExecutionPlan};
+#[cfg(feature = "riscv")]
+use continuations::{
+    rust_continuations, rust_continuations_export, rust_continuations_run_chunk, ChunkOptions,
+    ChunkState,
+};
 use env_logger::fmt::Color;
 use env_logger::{Builder, Target};
+use executor::witgen::ColumnRangeConstraint;
 use log::LevelFilter;
 use number::write_polys_file;
-use number::{read_polys_csv_file, write_polys_csv_file, CsvRenderMode};
-use number::{Bn254Field, FieldElement, GoldilocksField};
-use riscv::bootloader::{
-    default_input, BYTES_PER_WORD, PAGE_SIZE_BYTES_LOG, PC_INDEX, REGISTER_NAMES,
+use number::{
+    column_refs, read_polys_csv_file, read_polys_file, write_polys_csv_file, CsvRenderMode,
 };
-use riscv::{compile_riscv_asm, compile_rust};
-use riscv_executor::ExecutionTrace;
-use std::collections::{BTreeSet, HashMap};
+use number::{Bn254Field, DegreeType, FieldElement, GoldilocksField, KnownField};
+use parser_util::provenance::{split_header, Provenance};
+use parser_util::ParseError;
+use report::OutputFormat;
+#[cfg(feature = "riscv")]
+use riscv::bootloader::default_input;
+#[cfg(feature = "riscv")]
+use riscv::{compile_riscv_asm, compile_riscv_elf, compile_rust};
+#[cfg(feature = "riscv")]
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::{self, BufReader, BufWriter, Read};
-use std::{borrow::Cow, fs, io::Write, path::Path};
+use std::path::PathBuf;
+use std::{fs, io::Write, path::Path};
 use strum::{Display, EnumString, EnumVariantNames};
+use witgen_options::WitgenOptions;
 
-#[derive(Clone, EnumString, EnumVariantNames, Display)]
+#[derive(Clone, Debug, PartialEq, EnumString, EnumVariantNames, Display)]
 pub enum FieldArgument {
     #[strum(serialize = "gl")]
     Gl,
-    #[strum(serialize = "bn254")]
+    /// The scalar field of BN254 (the base field of Grumpkin), `bb` for the bberg backend.
+    #[strum(to_string = "bn254", serialize = "bb")]
     Bn254,
 }
 
-#[derive(Clone, EnumString, EnumVariantNames, Display)]
+impl FieldArgument {
+    /// The field argument for the field type `T`.
+    fn of<T: FieldElement>() -> Self {
+        match T::known_field().expect("Unknown field.") {
+            KnownField::GoldilocksField => FieldArgument::Gl,
+            KnownField::Bn254Field => FieldArgument::Bn254,
+        }
+    }
+}
+
+/// The parser for the `--field` arguments, which also accepts aliases like `bb`
+/// without listing them in the help.
+fn field_argument_parser() -> impl clap::builder::TypedValueParser<Value = FieldArgument> {
+    use clap::builder::{PossibleValue, TypedValueParser};
+    use strum::VariantNames;
+    clap::builder::PossibleValuesParser::new(
+        FieldArgument::VARIANTS
+            .iter()
+            .map(PossibleValue::new)
+            .chain([PossibleValue::new("bb").hide(true)]),
+    )
+    .map(|s| s.parse::<FieldArgument>().unwrap())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, EnumString, EnumVariantNames, Display)]
+pub enum Language {
+    #[strum(serialize = "asm")]
+    Asm,
+    #[strum(serialize = "pil")]
+    Pil,
+}
+
+#[derive(Clone, Debug, PartialEq, EnumString, EnumVariantNames, Display)]
 pub enum CsvRenderModeCLI {
     #[strum(serialize = "i")]
     SignedBase10,
@@ -46,6 +117,17 @@ struct Cli {
     #[arg(long, hide = true)]
     markdown_help: bool,
 
+    /// Configuration file to use instead of the `powdr.toml` found in the directory
+    /// of the input file or one of its parents.
+    #[arg(long, global = true)]
+    config: Option<String>,
+
+    /// The output format. With `json` or `csv`, `pil`, `prove` and `verify` print their
+    /// results on stdout and the logs go to stderr [default: human]
+    #[arg(long, global = true)]
+    #[arg(value_parser = clap_enum_variants!(OutputFormat))]
+    output_format: Option<OutputFormat>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -55,30 +137,44 @@ enum Commands {
     /// Runs compilation and witness generation for .pil and .asm files.
     /// First converts .asm files to .pil, if needed.
     /// Then converts the .pil file to json and generates fixed and witness column data files.
+    /// The analyzed PIL is written to analyzed.bin, which can be used as input again.
     Pil {
         /// Input file
         file: String,
 
-        /// The field to use
+        /// The field to use [default: gl]
         #[arg(long)]
-        #[arg(default_value_t = FieldArgument::Gl)]
-        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
-        field: FieldArgument,
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
 
-        /// Output directory for the PIL file, json file and fixed and witness column data.
+        /// Output directory for the PIL file, json file and fixed and witness column data [default: .]
         #[arg(short, long)]
-        #[arg(default_value_t = String::from("."))]
-        output_directory: String,
+        output_directory: Option<String>,
 
         /// Path to a CSV file containing externally computed witness values.
+        /// It can also contain values of fixed columns, which are checked against
+        /// the evaluated ones. Elements of arrays are named `name[index]`.
         #[arg(short, long)]
         witness_values: Option<String>,
 
-        /// Comma-separated list of free inputs (numbers). Assumes queries to have the form
-        /// ("input", <index>).
+        /// Comma-separated list of free inputs (numbers), optionally prefixed by an input
+        /// channel as in `1=5,6`. Can be given once per channel, inputs without a channel are
+        /// in channel 0. Assumes queries to have the form ("input", <channel>, <index>)
+        /// or ("input", <index>) for channel 0.
         #[arg(short, long)]
-        #[arg(default_value_t = String::new())]
-        inputs: String,
+        #[arg(conflicts_with = "inputs_file")]
+        inputs: Vec<String>,
+
+        /// File containing the free inputs, either a JSON array of strings (.json)
+        /// or little-endian binary field elements.
+        #[arg(long)]
+        inputs_file: Option<String>,
+
+        /// Reject input values that are not smaller than the field modulus
+        /// instead of reducing them with a warning.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        strict_inputs: bool,
 
         /// Force overwriting of PIL output file.
         #[arg(short, long)]
@@ -90,21 +186,44 @@ enum Commands {
         #[arg(value_parser = clap_enum_variants!(BackendType))]
         prove_with: Option<BackendType>,
 
-        /// Generate a CSV file containing the fixed and witness column values. Useful for debugging purposes.
+        /// Generate a CSV file containing the fixed and witness column values (columns.csv)
+        /// and one containing only the fixed column values (fixed.csv). Useful for debugging purposes.
         #[arg(long)]
         #[arg(default_value_t = false)]
         export_csv: bool,
 
-        /// How to render field elements in the csv file
+        /// How to render field elements in the csv file [default: hex]
         #[arg(long)]
-        #[arg(default_value_t = CsvRenderModeCLI::Hex)]
         #[arg(value_parser = clap_enum_variants!(CsvRenderModeCLI))]
-        csv_mode: CsvRenderModeCLI,
+        csv_mode: Option<CsvRenderModeCLI>,
+
+        /// Comma-separated list of glob patterns (like `Binary.*,main.pc`) selecting the
+        /// columns exported to the csv files by their fully qualified names [default: all]
+        #[arg(long)]
+        csv_columns: Option<String>,
+
+        /// Write the global range constraints derived for the columns, together with
+        /// their derivations, to range_constraints.json.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        dump_range_constraints: bool,
+
+        /// Print the global range constraints derived for the columns to stderr,
+        /// one line like `main.X: [0, 2^16)` per column.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        show_range_constraints: bool,
 
         /// BBerg: Name of the output file for bberg
         #[arg(long)]
         bname: Option<String>,
 
+        /// Overrides the degree of all namespaces, has to be a power of two.
+        /// Fixed columns defined by arrays with repeated elements (like `[1] + [0]*`)
+        /// are extended to the new degree, but columns using constants like `%N` are not.
+        #[arg(long)]
+        degree: Option<u64>,
+
         /// Just execute in the RISCV/Powdr executor
         #[arg(short, long)]
         #[arg(default_value_t = false)]
@@ -114,29 +233,65 @@ enum Commands {
         #[arg(short, long)]
         #[arg(default_value_t = false)]
         continuations: bool,
+
+        /// The maximum number of rows each chunk may use in any machine, including the
+        /// rows the instructions use in the submachines. Defaults to the rows available
+        /// for the execution, i.e. the degree minus two.
+        #[arg(long)]
+        #[arg(requires = "continuations")]
+        chunk_rows: Option<usize>,
+
+        /// Report witness columns that are not constrained by any identity as errors
+        /// instead of warnings.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        deny_unconstrained: bool,
+
+        /// Comma-separated list of witness columns that are intentionally unconstrained
+        /// and are not reported. The name of an array covers all its elements.
+        #[arg(long)]
+        allow_unconstrained: Option<String>,
+
+        /// Intermediate columns whose definition has at most this many nodes are
+        /// inlined by the optimizer, 0 disables inlining.
+        #[arg(long)]
+        max_inlined_intermediate_size: Option<usize>,
     },
     /// Compiles (no-std) rust code to riscv assembly, then to powdr assembly
     /// and finally to PIL and generates fixed and witness columns.
     /// Needs `rustup target add riscv32imac-unknown-none-elf`.
+    #[cfg(feature = "riscv")]
     Rust {
         /// Input file (rust source file) or directory (containing a crate).
         file: String,
 
-        /// The field to use
+        /// The field to use [default: gl]
         #[arg(long)]
-        #[arg(default_value_t = FieldArgument::Gl)]
-        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
-        field: FieldArgument,
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
 
-        /// Comma-separated list of free inputs (numbers).
+        /// Comma-separated list of free inputs (numbers), optionally prefixed by an input
+        /// channel as in `1=5,6`. Can be given once per channel, inputs without a channel are
+        /// in channel 0. Assumes queries to have the form ("input", <channel>, <index>)
+        /// or ("input", <index>) for channel 0.
         #[arg(short, long)]
-        #[arg(default_value_t = String::new())]
-        inputs: String,
+        #[arg(conflicts_with = "inputs_file")]
+        inputs: Vec<String>,
+
+        /// File containing the free inputs, either a JSON array of strings (.json)
+        /// or little-endian binary field elements.
+        #[arg(long)]
+        inputs_file: Option<String>,
 
-        /// Directory for  output files.
+        /// Reject input values that are not smaller than the field modulus
+        /// instead of reducing them with a warning.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        strict_inputs: bool,
+
+        /// Directory for output files [default: .]
         #[arg(short, long)]
-        #[arg(default_value_t = String::from("."))]
-        output_directory: String,
+        output_directory: Option<String>,
 
         /// Force overwriting of files in output directory.
         #[arg(short, long)]
@@ -152,6 +307,12 @@ enum Commands {
         #[arg(long)]
         coprocessors: Option<String>,
 
+        /// Overrides the degree of all namespaces, has to be a power of two.
+        /// Fixed columns defined by arrays with repeated elements (like `[1] + [0]*`)
+        /// are extended to the new degree, but columns using constants like `%N` are not.
+        #[arg(long)]
+        degree: Option<u64>,
+
         /// Just execute in the RISCV/Powdr executor
         #[arg(short, long)]
         #[arg(default_value_t = false)]
@@ -161,30 +322,121 @@ enum Commands {
         #[arg(short, long)]
         #[arg(default_value_t = false)]
         continuations: bool,
+
+        /// Run both the RISCV/Powdr executor and witness generation and report the
+        /// first row in which their PC or register values differ.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        #[arg(conflicts_with_all = ["just_execute", "continuations", "prove_with"])]
+        check_executor: bool,
+
+        /// The approximate memory budget of witness generation in GB. When nearing it,
+        /// witness generation saves memory at the cost of speed and finally fails.
+        #[arg(long)]
+        #[arg(value_parser = parse_memory_budget)]
+        #[arg(requires = "check_executor")]
+        witgen_memory_budget: Option<u64>,
+
+        /// Records the prover queries of witness generation and their answers to this
+        /// file (JSON lines), so that the run can be reproduced with --replay-queries.
+        #[arg(long)]
+        #[arg(requires = "check_executor")]
+        record_queries: Option<String>,
+
+        /// Answers the prover queries of witness generation from a recording written
+        /// with --record-queries instead of from the inputs.
+        #[arg(long)]
+        #[arg(requires = "check_executor")]
+        replay_queries: Option<String>,
+
+        /// Seeds the PC and the registers of witness generation with the executor trace,
+        /// so that witness generation fails in the first row it does not agree with.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        #[arg(requires = "check_executor")]
+        seed_witgen_from_executor: bool,
+
+        /// Writes the invocations, successes and time of each identity during witness
+        /// generation to this file (JSON).
+        #[arg(long)]
+        #[arg(requires = "check_executor")]
+        witgen_profile: Option<String>,
+
+        /// Directory for the state at the start of each chunk. Without --chunk, the states
+        /// of all chunks are written to it, with --chunk, the state is read from it.
+        #[arg(long)]
+        #[arg(requires = "continuations")]
+        chunks_dir: Option<String>,
+
+        /// Only run the chunk with the given index, starting from its state in --chunks-dir.
+        #[arg(long)]
+        #[arg(requires = "chunks_dir")]
+        chunk: Option<usize>,
+
+        /// The maximum number of rows each chunk may use in any machine, including the
+        /// rows the instructions use in the submachines. Defaults to the rows available
+        /// for the execution, i.e. the degree minus two.
+        #[arg(long)]
+        #[arg(requires = "continuations")]
+        chunk_rows: Option<usize>,
+
+        /// Link the program and translate the RISC-V ELF file instead of the
+        /// textual assembly, which also works with LTO.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        via_elf: bool,
+        /// Report witness columns that are not constrained by any identity as errors
+        /// instead of warnings.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        deny_unconstrained: bool,
+
+        /// Comma-separated list of witness columns that are intentionally unconstrained
+        /// and are not reported. The name of an array covers all its elements.
+        #[arg(long)]
+        allow_unconstrained: Option<String>,
+
+        /// Intermediate columns whose definition has at most this many nodes are
+        /// inlined by the optimizer, 0 disables inlining.
+        #[arg(long)]
+        max_inlined_intermediate_size: Option<usize>,
     },
 
     /// Compiles riscv assembly to powdr assembly and then to PIL
     /// and generates fixed and witness columns.
+    #[cfg(feature = "riscv")]
     RiscvAsm {
         /// Input files
         #[arg(required = true)]
         files: Vec<String>,
 
-        /// The field to use
+        /// The field to use [default: gl]
         #[arg(long)]
-        #[arg(default_value_t = FieldArgument::Gl)]
-        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
-        field: FieldArgument,
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
 
-        /// Comma-separated list of free inputs (numbers).
+        /// Comma-separated list of free inputs (numbers), optionally prefixed by an input
+        /// channel as in `1=5,6`. Can be given once per channel, inputs without a channel are
+        /// in channel 0. Assumes queries to have the form ("input", <channel>, <index>)
+        /// or ("input", <index>) for channel 0.
         #[arg(short, long)]
-        #[arg(default_value_t = String::new())]
-        inputs: String,
+        #[arg(conflicts_with = "inputs_file")]
+        inputs: Vec<String>,
+
+        /// File containing the free inputs, either a JSON array of strings (.json)
+        /// or little-endian binary field elements.
+        #[arg(long)]
+        inputs_file: Option<String>,
 
-        /// Directory for output files.
+        /// Reject input values that are not smaller than the field modulus
+        /// instead of reducing them with a warning.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        strict_inputs: bool,
+
+        /// Directory for output files [default: .]
         #[arg(short, long)]
-        #[arg(default_value_t = String::from("."))]
-        output_directory: String,
+        output_directory: Option<String>,
 
         /// Force overwriting of files in output directory.
         #[arg(short, long)]
@@ -200,6 +452,12 @@ enum Commands {
         #[arg(long)]
         coprocessors: Option<String>,
 
+        /// Overrides the degree of all namespaces, has to be a power of two.
+        /// Fixed columns defined by arrays with repeated elements (like `[1] + [0]*`)
+        /// are extended to the new degree, but columns using constants like `%N` are not.
+        #[arg(long)]
+        degree: Option<u64>,
+
         /// Just execute in the RISCV/Powdr executor
         #[arg(short, long)]
         #[arg(default_value_t = false)]
@@ -209,6 +467,83 @@ enum Commands {
         #[arg(short, long)]
         #[arg(default_value_t = false)]
         continuations: bool,
+
+        /// Run both the RISCV/Powdr executor and witness generation and report the
+        /// first row in which their PC or register values differ.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        #[arg(conflicts_with_all = ["just_execute", "continuations", "prove_with"])]
+        check_executor: bool,
+
+        /// The approximate memory budget of witness generation in GB. When nearing it,
+        /// witness generation saves memory at the cost of speed and finally fails.
+        #[arg(long)]
+        #[arg(value_parser = parse_memory_budget)]
+        #[arg(requires = "check_executor")]
+        witgen_memory_budget: Option<u64>,
+
+        /// Records the prover queries of witness generation and their answers to this
+        /// file (JSON lines), so that the run can be reproduced with --replay-queries.
+        #[arg(long)]
+        #[arg(requires = "check_executor")]
+        record_queries: Option<String>,
+
+        /// Answers the prover queries of witness generation from a recording written
+        /// with --record-queries instead of from the inputs.
+        #[arg(long)]
+        #[arg(requires = "check_executor")]
+        replay_queries: Option<String>,
+
+        /// Seeds the PC and the registers of witness generation with the executor trace,
+        /// so that witness generation fails in the first row it does not agree with.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        #[arg(requires = "check_executor")]
+        seed_witgen_from_executor: bool,
+
+        /// Writes the invocations, successes and time of each identity during witness
+        /// generation to this file (JSON).
+        #[arg(long)]
+        #[arg(requires = "check_executor")]
+        witgen_profile: Option<String>,
+
+        /// Directory for the state at the start of each chunk. Without --chunk, the states
+        /// of all chunks are written to it, with --chunk, the state is read from it.
+        #[arg(long)]
+        #[arg(requires = "continuations")]
+        chunks_dir: Option<String>,
+
+        /// Only run the chunk with the given index, starting from its state in --chunks-dir.
+        #[arg(long)]
+        #[arg(requires = "chunks_dir")]
+        chunk: Option<usize>,
+
+        /// The maximum number of rows each chunk may use in any machine, including the
+        /// rows the instructions use in the submachines. Defaults to the rows available
+        /// for the execution, i.e. the degree minus two.
+        #[arg(long)]
+        #[arg(requires = "continuations")]
+        chunk_rows: Option<usize>,
+
+        /// The input file is a linked RISC-V ELF file instead of assembly.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        via_elf: bool,
+        /// Report witness columns that are not constrained by any identity as errors
+        /// instead of warnings.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        deny_unconstrained: bool,
+
+        /// Comma-separated list of witness columns that are intentionally unconstrained
+        /// and are not reported. The name of an array covers all its elements.
+        #[arg(long)]
+        allow_unconstrained: Option<String>,
+
+        /// Intermediate columns whose definition has at most this many nodes are
+        /// inlined by the optimizer, 0 disables inlining.
+        #[arg(long)]
+        max_inlined_intermediate_size: Option<usize>,
     },
 
     Prove {
@@ -220,11 +555,10 @@ enum Commands {
         #[arg(default_value_t = String::from("."))]
         dir: String,
 
-        /// The field to use
+        /// The field to use [default: the field recorded in the directory, or gl]
         #[arg(long)]
-        #[arg(default_value_t = FieldArgument::Gl)]
-        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
-        field: FieldArgument,
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
 
         /// Generate a proof with a given backend.
         #[arg(short, long)]
@@ -238,6 +572,160 @@ enum Commands {
         /// File containing previously generated setup parameters.
         #[arg(long)]
         params: Option<String>,
+
+        /// The index of the continuations chunk the witness is for, recorded in the
+        /// proof metadata so that the chunk proofs can be aggregated in order.
+        #[arg(long)]
+        chunk_index: Option<u64>,
+
+        /// Also write the verification key to this file, which is enough to verify
+        /// proofs together with the PIL file (see `verify --verification-key`).
+        #[arg(long)]
+        export_verification_key: Option<String>,
+
+        /// Read and write bare proofs, without the header that records the backend,
+        /// the field and the PIL of the proof, e.g. for external verifiers.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        raw_proof: bool,
+
+        /// Use the committed and fixed values even if they were generated for a
+        /// different PIL than the input PIL file.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        force: bool,
+    },
+
+    /// Aggregates proofs for the same PIL file, e.g. the proofs of consecutive chunks
+    /// of a continuations run, into `proof_aggr.bin`.
+    /// The metadata written next to each proof (`proof.meta.json` for `proof.bin`)
+    /// has to show that the proofs are compatible.
+    Aggregate {
+        /// Input PIL file
+        file: String,
+
+        /// The proofs to aggregate, in order, relative to the directory.
+        #[arg(required = true, num_args = 2..)]
+        proofs: Vec<String>,
+
+        /// Directory to find the fixed values and the proofs, and to write the aggregated proof to
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// The field to use [default: the field recorded in the directory, or gl]
+        #[arg(long)]
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
+
+        /// The backend the proofs were generated with.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        backend: BackendType,
+
+        /// File containing previously generated setup parameters.
+        #[arg(long)]
+        params: Option<String>,
+
+        /// Read and write bare proofs, without the header that records the backend,
+        /// the field and the PIL of the proof.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        raw_proof: bool,
+    },
+
+    /// Verifies a proof against the PIL file and the fixed column values.
+    /// Exits with a non-zero code if the proof is invalid or the artifacts cannot be loaded.
+    Verify {
+        /// Input PIL file
+        file: String,
+
+        /// Directory to find the fixed values and the proof
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// The field to use [default: the field recorded in the directory, or gl]
+        #[arg(long)]
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
+
+        /// The backend the proof was generated with.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        backend: BackendType,
+
+        /// File containing the proof to verify.
+        #[arg(long)]
+        #[arg(default_value_t = String::from("proof.bin"))]
+        proof: String,
+
+        /// The proof is a bare proof, without the header that records the backend,
+        /// the field and the PIL of the proof.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        raw_proof: bool,
+
+        /// File containing previously generated setup parameters.
+        #[arg(long)]
+        params: Option<String>,
+
+        /// File containing a verification key written by `prove --export-verification-key`.
+        /// If given, the fixed values are not needed.
+        #[arg(long)]
+        verification_key: Option<String>,
+
+        /// JSON file containing the expected values of the public declarations, as written
+        /// by `prove`: an array of numbers as strings, in declaration order.
+        /// Defaults to `publics.json` in the directory, if it exists.
+        #[arg(long)]
+        publics: Option<String>,
+
+        /// Use the fixed values even if they were generated for a
+        /// different PIL than the input PIL file.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        force: bool,
+    },
+
+    /// Checks a complete witness against all identities of the PIL file, without
+    /// running witness generation. Reports every identity that does not hold on a row
+    /// and exits with a non-zero code if there is one.
+    CheckWitness {
+        /// Input PIL file
+        file: String,
+
+        /// Directory to find the fixed values and, by default, the witness
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// File containing the witness, either binary like `commits.bin` or CSV like
+        /// the `columns.csv` written by `pil --export-csv`.
+        /// Defaults to `commits.bin` in the directory.
+        #[arg(short, long)]
+        witness: Option<String>,
+
+        /// Comma-separated witness columns whose last rows are overwritten with random
+        /// values before checking, like a backend that blinds them. Comparing the results
+        /// with and without blinding tells PIL-level from backend-level issues.
+        #[arg(long)]
+        blind_columns: Option<String>,
+
+        /// The number of rows overwritten by `--blind-columns` [default: 1]
+        #[arg(long)]
+        #[arg(requires = "blind_columns")]
+        blind_rows: Option<usize>,
+
+        /// The seed of the random values written by `--blind-columns` [default: 0]
+        #[arg(long)]
+        #[arg(requires = "blind_columns")]
+        blind_seed: Option<u64>,
+
+        /// The field to use [default: the field recorded in the directory, or gl]
+        #[arg(long)]
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
     },
 
     Setup {
@@ -249,22 +737,67 @@ enum Commands {
         #[arg(default_value_t = String::from("."))]
         dir: String,
 
-        /// The field to use
+        /// The field to use [default: the field recorded in the directory, or gl]
         #[arg(long)]
-        #[arg(default_value_t = FieldArgument::Gl)]
-        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
-        field: FieldArgument,
+        #[arg(value_parser = field_argument_parser())]
+        field: Option<FieldArgument>,
 
         /// Generate a proof with a given backend.
         #[arg(short, long)]
         #[arg(value_parser = clap_enum_variants!(BackendType))]
         backend: BackendType,
+
+        /// Seed for the randomness of the setup, making the written
+        /// parameters reproducible. Only meant for testing. None of the
+        /// backends of this build has a randomized setup yet.
+        #[arg(long)]
+        seed: Option<u64>,
     },
 
-    /// Parses and prints the PIL file on stdout.
+    /// Parses and prints .asm and .pil files on stdout or formats them in place.
+    /// Comments are not preserved.
     Reformat {
+        /// Input files or directories, which are searched recursively for .asm and .pil files
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// The language of the files [default: derived from the file extension]
+        #[arg(long)]
+        #[arg(value_parser = clap_enum_variants!(Language))]
+        language: Option<Language>,
+
+        /// Overwrite the files with their formatted contents.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        in_place: bool,
+
+        /// Do not print or write anything, but exit with an error if a file is not formatted.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        #[arg(conflicts_with = "in_place")]
+        check: bool,
+    },
+
+    /// Prints the provenance header of a generated .asm or .pil file: the powdr version,
+    /// the input files with their hashes, the coprocessors and the generation time.
+    Inspect {
+        /// Input file
+        file: String,
+    },
+
+    /// Renames a symbol in a .pil file and the files it includes and updates all
+    /// references to it. Comments and formatting are preserved.
+    /// Only .pil files are supported: references in asm `link` declarations
+    /// are not rewritten, so .asm input is rejected.
+    Rename {
         /// Input file
         file: String,
+
+        /// The absolute name of the symbol, e.g. `Main.x` or `%N`.
+        from: String,
+
+        /// The new name, with or without namespace.
+        to: String,
     },
 
     /// Optimizes the PIL file and outputs it on stdout.
@@ -272,34 +805,242 @@ enum Commands {
         /// Input file
         file: String,
 
+        /// Print the identities removed as duplicates, with their source locations, on stderr.
+        #[arg(short, long)]
+        #[arg(default_value_t = false)]
+        verbose: bool,
+
+        /// Print statistics about the optimized PIL on stderr, see the `stats` command.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        stats: bool,
+
+        /// Intermediate columns whose definition has at most this many nodes are
+        /// inlined, 0 disables inlining.
+        #[arg(long)]
+        #[arg(default_value_t = compiler::DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE)]
+        max_inlined_intermediate_size: usize,
+
         /// The field to use
         #[arg(long)]
         #[arg(default_value_t = FieldArgument::Gl)]
-        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        #[arg(value_parser = field_argument_parser())]
         field: FieldArgument,
     },
-}
 
-fn split_inputs<T: FieldElement>(inputs: &str) -> Vec<T> {
-    inputs
-        .split(',')
-        .map(|x| x.trim())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.parse::<u64>().unwrap().into())
-        .collect()
-}
+    /// Compares two PIL files semantically: prints added, removed and renamed columns,
+    /// added, removed and modified identities, and changes of the degree and
+    /// the public declarations. Formatting and the order of operands are ignored.
+    DiffPil {
+        /// The old version of the PIL file
+        file_a: String,
 
-fn main() -> Result<(), io::Error> {
-    let mut builder = Builder::new();
-    builder
-        .filter_level(LevelFilter::Info)
-        .parse_default_env()
-        .target(Target::Stdout)
-        .format(|buf, record| {
-            let mut style = buf.style();
+        /// The new version of the PIL file
+        file_b: String,
 
-            // we allocate as there is no way to look into the message otherwise
-            let msg = record.args().to_string();
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = field_argument_parser())]
+        field: FieldArgument,
+    },
+
+    /// Prints statistics about a PIL file: the number of columns per namespace,
+    /// the number of identities per kind, the maximum degree of the polynomial
+    /// identities and the number of lookups into each namespace.
+    Stats {
+        /// Input file
+        file: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = field_argument_parser())]
+        field: FieldArgument,
+    },
+
+    /// Prints for each column of a PIL file the identities it occurs in and the columns
+    /// it occurs together with, in the current or the next row.
+    Analyze {
+        /// Input file
+        file: String,
+
+        /// Print the graph in the DOT format of Graphviz instead of the output format.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        dot: bool,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = field_argument_parser())]
+        field: FieldArgument,
+    },
+
+    /// Runs witness generation on a PIL file and prints the witness columns on the rows
+    /// around the row where it fails, with the failing identities and their source
+    /// locations. Values that have not been determined are printed as `?`.
+    Debug {
+        /// Input file
+        file: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = field_argument_parser())]
+        field: FieldArgument,
+
+        /// Comma-separated list of free inputs (numbers), optionally prefixed by an input
+        /// channel as in `1=5,6`. Can be given once per channel.
+        #[arg(short, long)]
+        inputs: Vec<String>,
+
+        /// The row to inspect if witness generation succeeds.
+        #[arg(long)]
+        row: Option<DegreeType>,
+
+        /// Comma-separated list of glob patterns (like `Binary.*,main.pc`) selecting the
+        /// printed columns [default: the columns of the failing identities]
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// The approximate memory budget of witness generation in GB. When nearing it,
+        /// witness generation saves memory at the cost of speed and finally fails.
+        #[arg(long)]
+        #[arg(value_parser = parse_memory_budget)]
+        witgen_memory_budget: Option<u64>,
+
+        /// Records the prover queries of witness generation and their answers to this
+        /// file (JSON lines), so that the run can be reproduced with --replay-queries.
+        #[arg(long)]
+        record_queries: Option<String>,
+
+        /// Answers the prover queries of witness generation from a recording written
+        /// with --record-queries instead of from the inputs.
+        #[arg(long)]
+        replay_queries: Option<String>,
+
+        /// Writes the invocations, successes and time of each identity during witness
+        /// generation to this file (JSON).
+        #[arg(long)]
+        witgen_profile: Option<String>,
+    },
+
+    /// Evaluates a single fixed column of a PIL file on the given rows, without
+    /// generating the other fixed columns it does not depend on.
+    EvalFixed {
+        /// Input file
+        file: String,
+
+        /// The absolute name of the fixed column, e.g. `Main.BYTE`.
+        name: String,
+
+        /// The rows to evaluate, as `start..end` (end exclusive) or a single row.
+        #[arg(long)]
+        #[arg(value_parser = parse_row_range)]
+        rows: std::ops::Range<DegreeType>,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = field_argument_parser())]
+        field: FieldArgument,
+    },
+
+    /// Validates the configuration file (powdr.toml) or prints the effective configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Writes test vectors for field arithmetic and serialization as JSON,
+    /// to be consumed by external implementations.
+    GenTestVectors {
+        /// Output file. Writes to stdout if not given.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Validates the configuration file.
+    Check {
+        /// Input file, used to find powdr.toml in its directory or one of its parents.
+        file: Option<String>,
+    },
+
+    /// Prints the configuration obtained by merging the configuration file with the defaults,
+    /// as TOML in the human output format.
+    PrintEffective {
+        /// Input file, used to find powdr.toml in its directory or one of its parents.
+        file: Option<String>,
+    },
+}
+
+/// Parses a memory budget given in GB and returns it in bytes.
+fn parse_memory_budget(gb: &str) -> Result<u64, String> {
+    gb.parse::<u64>()
+        .map_err(|e| e.to_string())?
+        .checked_mul(1 << 30)
+        .ok_or_else(|| format!("{gb} GB is too large."))
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(|c| c.to_string()).collect()
+}
+
+#[cfg(feature = "riscv")]
+/// The coprocessors of the plan, whose names are validated by [ExecutionPlan].
+fn coprocessors_from_plan(plan: &ExecutionPlan) -> riscv::CoProcessors {
+    match &plan.coprocessors {
+        Some(list) => {
+            riscv::CoProcessors::try_from(list.iter().map(String::as_str).collect::<Vec<_>>())
+                .unwrap()
+        }
+        None => riscv::CoProcessors::base(),
+    }
+}
+
+/// Reads the free inputs of a pipeline command. Exits if they are invalid.
+#[allow(clippy::print_stderr)]
+fn plan_inputs<T: FieldElement>(plan: &ExecutionPlan, inline: &[String]) -> HashMap<T, Vec<T>> {
+    plan.inputs(inline).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    })
+}
+
+/// Builds the execution plan of a pipeline command from the flags given on the
+/// command line and the configuration file. Exits if the configuration is invalid.
+#[allow(clippy::print_stderr)]
+fn execution_plan(flags: Config, config_file: Option<&str>, input_file: &str) -> ExecutionPlan {
+    Config::load(config_file.map(Path::new), Some(Path::new(input_file)))
+        .and_then(|config| ExecutionPlan::new(flags, config))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+}
+
+fn main() -> Result<(), io::Error> {
+    let args = Cli::parse();
+    let output_format = args.output_format.unwrap_or_default();
+
+    let mut builder = Builder::new();
+    builder
+        .filter_level(LevelFilter::Info)
+        .parse_default_env()
+        // Keep stdout parseable if the results are printed in a machine-readable format.
+        .target(match output_format {
+            OutputFormat::Human => Target::Stdout,
+            OutputFormat::Json | OutputFormat::Csv => Target::Stderr,
+        })
+        .format(|buf, record| {
+            let mut style = buf.style();
+
+            // we allocate as there is no way to look into the message otherwise
+            let msg = record.args().to_string();
 
             // add colors for the diffs
             match &msg {
@@ -316,65 +1057,172 @@ fn main() -> Result<(), io::Error> {
         })
         .init();
 
-    let args = Cli::parse();
-
     if args.markdown_help {
         clap_markdown::print_help_markdown::<Cli>();
         Ok(())
     } else if let Some(command) = args.command {
-        run_command(command);
+        let cancellation = CancellationToken::new();
+        install_interrupt_handler(cancellation.clone());
+        run_command(command, args.config, output_format, &cancellation);
         Ok(())
     } else {
         Cli::command().print_help()
     }
 }
 
+/// The exit code used if the user cancelled the run (128 + SIGINT, like most shells).
+const CANCELLED_EXIT_CODE: i32 = 130;
+
+/// Requests cancellation of the current run on the first Ctrl-C, the work then stops
+/// at the next safe point. Exits immediately on the second Ctrl-C.
+#[allow(clippy::print_stderr)]
+fn install_interrupt_handler(cancellation: CancellationToken) {
+    ctrlc::set_handler(move || {
+        if cancellation.is_cancelled() {
+            std::process::exit(CANCELLED_EXIT_CODE);
+        }
+        eprintln!("Cancelling... Press Ctrl-C again to exit immediately.");
+        cancellation.cancel();
+    })
+    .expect("Could not install the Ctrl-C handler.");
+}
+
+/// If the run has been cancelled, prints which stages were completed and which one
+/// was aborted, and exits with [CANCELLED_EXIT_CODE].
+#[allow(clippy::print_stderr)]
+fn exit_if_cancelled(cancellation: &CancellationToken) {
+    if !cancellation.is_cancelled() {
+        return;
+    }
+    let completed = cancellation.completed_stages();
+    if completed.is_empty() {
+        eprintln!("Cancelled. No stages were completed.");
+    } else {
+        eprintln!("Cancelled. Completed stages: {}.", completed.join(", "));
+    }
+    if let Some(stage) = cancellation.aborted_stage() {
+        eprintln!("Aborted stage: {stage}.");
+    }
+    std::process::exit(CANCELLED_EXIT_CODE);
+}
+
 #[allow(clippy::print_stderr)]
-fn run_command(command: Commands) {
+fn run_command(
+    command: Commands,
+    config_file: Option<String>,
+    output_format: OutputFormat,
+    cancellation: &CancellationToken,
+) {
     match command {
+        #[cfg(feature = "riscv")]
         Commands::Rust {
             file,
             field,
             inputs,
+            inputs_file,
+            strict_inputs,
             output_directory,
             force,
             prove_with,
             coprocessors,
+            degree,
             just_execute,
             continuations,
+            check_executor,
+            witgen_memory_budget,
+            record_queries,
+            replay_queries,
+            seed_witgen_from_executor,
+            witgen_profile,
+            chunks_dir,
+            chunk,
+            chunk_rows,
+            via_elf,
+            deny_unconstrained,
+            allow_unconstrained,
+            max_inlined_intermediate_size,
         } => {
-            let coprocessors = match coprocessors {
-                Some(list) => {
-                    riscv::CoProcessors::try_from(list.split(',').collect::<Vec<_>>()).unwrap()
-                }
-                None => riscv::CoProcessors::base(),
+            let flags = Config {
+                field: field.map(|f| f.to_string()),
+                output_directory,
+                inputs_file,
+                strict_inputs: strict_inputs.then_some(true),
+                force: force.then_some(true),
+                prove_with: prove_with.map(|b| b.to_string()),
+                coprocessors: coprocessors.map(|c| split_list(&c)),
+                degree,
+                just_execute: just_execute.then_some(true),
+                continuations: continuations.then_some(true),
+                check_executor: check_executor.then_some(true),
+                deny_unconstrained: deny_unconstrained.then_some(true),
+                allow_unconstrained: allow_unconstrained.map(|c| split_list(&c)),
+                max_inlined_intermediate_size,
+                ..Default::default()
             };
+            let plan = execution_plan(flags, config_file.as_deref(), &file);
+            let field = plan.field.clone();
             if let Err(errors) = call_with_field!(run_rust::<field>(
                 &file,
-                split_inputs(&inputs),
-                Path::new(&output_directory),
-                force,
-                prove_with,
-                coprocessors,
-                just_execute,
-                continuations
+                plan_inputs(&plan, &inputs),
+                Path::new(&plan.output_directory),
+                plan.force,
+                plan.prove_with.clone(),
+                coprocessors_from_plan(&plan),
+                plan.just_execute,
+                plan.continuations,
+                plan.check_executor,
+                plan.degree,
+                &ChunkOptions {
+                    chunks_dir: chunks_dir.map(Into::into),
+                    chunk,
+                    row_budget: chunk_rows,
+                },
+                &WitgenOptions {
+                    memory_budget: witgen_memory_budget,
+                    record_queries: record_queries.map(Into::into),
+                    replay_queries: replay_queries.map(Into::into),
+                    seed_from_executor: seed_witgen_from_executor,
+                    profile: witgen_profile.map(Into::into),
+                },
+                via_elf,
+                &plan.unconstrained,
+                plan.max_inlined_intermediate_size,
+                cancellation
             )) {
+                exit_if_cancelled(cancellation);
                 eprintln!("Errors:");
                 for e in errors {
                     eprintln!("{e}");
                 }
             };
         }
+        #[cfg(feature = "riscv")]
         Commands::RiscvAsm {
             files,
             field,
             inputs,
+            inputs_file,
+            strict_inputs,
             output_directory,
             force,
             prove_with,
             coprocessors,
+            degree,
             just_execute,
             continuations,
+            check_executor,
+            witgen_memory_budget,
+            record_queries,
+            replay_queries,
+            seed_witgen_from_executor,
+            witgen_profile,
+            chunks_dir,
+            chunk,
+            chunk_rows,
+            via_elf,
+            deny_unconstrained,
+            allow_unconstrained,
+            max_inlined_intermediate_size,
         } => {
             assert!(!files.is_empty());
             let name = if files.len() == 1 {
@@ -383,93 +1231,267 @@ fn run_command(command: Commands) {
                 Cow::Borrowed("output")
             };
 
-            let coprocessors = match coprocessors {
-                Some(list) => {
-                    riscv::CoProcessors::try_from(list.split(',').collect::<Vec<_>>()).unwrap()
-                }
-                None => riscv::CoProcessors::base(),
+            let flags = Config {
+                field: field.map(|f| f.to_string()),
+                output_directory,
+                inputs_file,
+                strict_inputs: strict_inputs.then_some(true),
+                force: force.then_some(true),
+                prove_with: prove_with.map(|b| b.to_string()),
+                coprocessors: coprocessors.map(|c| split_list(&c)),
+                degree,
+                just_execute: just_execute.then_some(true),
+                continuations: continuations.then_some(true),
+                check_executor: check_executor.then_some(true),
+                deny_unconstrained: deny_unconstrained.then_some(true),
+                allow_unconstrained: allow_unconstrained.map(|c| split_list(&c)),
+                max_inlined_intermediate_size,
+                ..Default::default()
             };
+            let plan = execution_plan(flags, config_file.as_deref(), &files[0]);
+            let field = plan.field.clone();
             if let Err(errors) = call_with_field!(run_riscv_asm::<field>(
                 &name,
                 files.into_iter(),
-                split_inputs(&inputs),
-                Path::new(&output_directory),
-                force,
-                prove_with,
-                coprocessors,
-                just_execute,
-                continuations
+                plan_inputs(&plan, &inputs),
+                Path::new(&plan.output_directory),
+                plan.force,
+                plan.prove_with.clone(),
+                coprocessors_from_plan(&plan),
+                plan.just_execute,
+                plan.continuations,
+                plan.check_executor,
+                plan.degree,
+                &ChunkOptions {
+                    chunks_dir: chunks_dir.map(Into::into),
+                    chunk,
+                    row_budget: chunk_rows,
+                },
+                &WitgenOptions {
+                    memory_budget: witgen_memory_budget,
+                    record_queries: record_queries.map(Into::into),
+                    replay_queries: replay_queries.map(Into::into),
+                    seed_from_executor: seed_witgen_from_executor,
+                    profile: witgen_profile.map(Into::into),
+                },
+                via_elf,
+                &plan.unconstrained,
+                plan.max_inlined_intermediate_size,
+                cancellation
             )) {
+                exit_if_cancelled(cancellation);
                 eprintln!("Errors:");
                 for e in errors {
                     eprintln!("{e}");
                 }
             };
         }
-        Commands::Reformat { file } => {
-            let contents = fs::read_to_string(&file).unwrap();
-            match parser::parse::<GoldilocksField>(Some(&file), &contents) {
-                Ok(ast) => println!("{ast}"),
-                Err(err) => err.output_to_stderr(),
+        Commands::Config { command } => match command {
+            ConfigCommands::Check { file } => {
+                let config_file = config_file.as_deref().map(Path::new);
+                match Config::find(config_file, file.as_deref().map(Path::new)) {
+                    Some(path) => match Config::read(&path) {
+                        Ok(_) => log::info!("{} is valid.", path.display()),
+                        Err(e) => {
+                            eprintln!("{e}");
+                            std::process::exit(1);
+                        }
+                    },
+                    None => {
+                        eprintln!("No {} found.", config::CONFIG_FILE_NAME);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            ConfigCommands::PrintEffective { file } => {
+                let plan = execution_plan(
+                    Config::default(),
+                    config_file.as_deref(),
+                    file.as_deref().unwrap_or("."),
+                );
+                print_effective_config(&plan, output_format);
             }
+        },
+        Commands::Reformat {
+            files,
+            language,
+            in_place,
+            check,
+        } => reformat_files(&files, language, in_place, check),
+        Commands::Inspect { file } => inspect(&file, output_format),
+        Commands::Rename { file, from, to } => rename_symbol(&file, &from, &to),
+        Commands::OptimizePIL {
+            file,
+            field,
+            verbose,
+            stats,
+            max_inlined_intermediate_size,
+        } => {
+            call_with_field!(optimize_and_output::<field>(
+                &file,
+                verbose,
+                stats,
+                max_inlined_intermediate_size
+            ))
+        }
+        Commands::DiffPil {
+            file_a,
+            file_b,
+            field,
+        } => {
+            call_with_field!(diff_pil::<field>(&file_a, &file_b, output_format))
+        }
+        Commands::Stats { file, field } => {
+            call_with_field!(stats::<field>(&file, output_format))
+        }
+        Commands::Analyze { file, dot, field } => {
+            call_with_field!(analyze::<field>(&file, dot, output_format))
+        }
+        Commands::Debug {
+            file,
+            field,
+            inputs,
+            row,
+            columns,
+            witgen_memory_budget,
+            record_queries,
+            replay_queries,
+            witgen_profile,
+        } => {
+            let columns = columns.map(|c| split_list(&c)).unwrap_or_default();
+            let witgen_options = WitgenOptions {
+                memory_budget: witgen_memory_budget,
+                record_queries: record_queries.map(Into::into),
+                replay_queries: replay_queries.map(Into::into),
+                profile: witgen_profile.map(Into::into),
+                ..Default::default()
+            };
+            call_with_field!(debug::<field>(
+                &file,
+                &inputs,
+                row,
+                &columns,
+                &witgen_options,
+                output_format
+            ))
         }
-        Commands::OptimizePIL { file, field } => {
-            call_with_field!(optimize_and_output::<field>(&file))
+        Commands::EvalFixed {
+            file,
+            name,
+            rows,
+            field,
+        } => {
+            call_with_field!(eval_fixed::<field>(&file, &name, rows, output_format))
         }
+        Commands::GenTestVectors { output } => gen_test_vectors(output),
+        // `chunk_rows` is only used when executing, which needs the `riscv` feature.
+        #[cfg_attr(not(feature = "riscv"), allow(unused_variables))]
         Commands::Pil {
             file,
             field,
             output_directory,
             witness_values,
             inputs,
+            inputs_file,
+            strict_inputs,
             force,
             prove_with,
             export_csv,
             csv_mode,
+            csv_columns,
+            dump_range_constraints,
+            show_range_constraints,
             bname,
+            degree,
             just_execute,
             continuations,
-        } => match (just_execute, continuations) {
-            (true, true) => {
-                assert!(matches!(field, FieldArgument::Gl));
-                let contents = fs::read_to_string(&file).unwrap();
-                let inputs = split_inputs::<GoldilocksField>(&inputs);
-                rust_continuations(file.as_str(), contents.as_str(), inputs);
-            }
-            (true, false) => {
-                let contents = fs::read_to_string(&file).unwrap();
-                let inputs = split_inputs::<GoldilocksField>(&inputs);
-                let inputs: HashMap<GoldilocksField, Vec<GoldilocksField>> =
-                    vec![(GoldilocksField::from(0), inputs)]
-                        .into_iter()
-                        .collect();
-                riscv_executor::execute::<GoldilocksField>(&contents, &inputs, &default_input());
-            }
-            (false, true) => {
-                unimplemented!("Running witgen with continuations is not supported yet.")
-            }
-            (false, false) => {
-                match call_with_field!(compile_with_csv_export::<field>(
-                    file,
-                    output_directory,
-                    witness_values,
-                    inputs,
-                    force,
-                    prove_with,
-                    export_csv,
-                    csv_mode,
-                    bname
-                )) {
-                    Ok(()) => {}
-                    Err(errors) => {
-                        eprintln!("Errors:");
-                        for e in errors {
-                            eprintln!("{e}");
+            chunk_rows,
+            deny_unconstrained,
+            allow_unconstrained,
+            max_inlined_intermediate_size,
+        } => {
+            let flags = Config {
+                field: field.map(|f| f.to_string()),
+                output_directory,
+                inputs_file,
+                strict_inputs: strict_inputs.then_some(true),
+                force: force.then_some(true),
+                prove_with: prove_with.map(|b| b.to_string()),
+                witness_values,
+                export_csv: export_csv.then_some(true),
+                csv_mode: csv_mode.map(|m| m.to_string()),
+                csv_columns: csv_columns.map(|c| split_list(&c)),
+                dump_range_constraints: dump_range_constraints.then_some(true),
+                show_range_constraints: show_range_constraints.then_some(true),
+                bname,
+                degree,
+                just_execute: just_execute.then_some(true),
+                continuations: continuations.then_some(true),
+                deny_unconstrained: deny_unconstrained.then_some(true),
+                allow_unconstrained: allow_unconstrained.map(|c| split_list(&c)),
+                max_inlined_intermediate_size,
+                ..Default::default()
+            };
+            let plan = execution_plan(flags, config_file.as_deref(), &file);
+            match (plan.just_execute, plan.continuations) {
+                #[cfg(feature = "riscv")]
+                (true, true) => {
+                    assert!(matches!(plan.field, FieldArgument::Gl));
+                    let contents = fs::read_to_string(&file).unwrap();
+                    let inputs = plan_inputs::<GoldilocksField>(&plan, &inputs);
+                    rust_continuations(file.as_str(), contents.as_str(), inputs, chunk_rows);
+                }
+                #[cfg(feature = "riscv")]
+                (true, false) => {
+                    let contents = fs::read_to_string(&file).unwrap();
+                    let inputs = plan_inputs::<GoldilocksField>(&plan, &inputs);
+                    riscv_executor::execute::<GoldilocksField>(
+                        &contents,
+                        &inputs,
+                        &default_input(),
+                    );
+                }
+                #[cfg(not(feature = "riscv"))]
+                (true, _) => {
+                    eprintln!("Executing programs needs the `riscv` feature of powdr.");
+                    std::process::exit(1);
+                }
+                (false, true) => {
+                    unimplemented!("Running witgen with continuations is not supported yet.")
+                }
+                (false, false) => {
+                    let field = plan.field.clone();
+                    match call_with_field!(compile_with_csv_export::<field>(
+                        file,
+                        plan.output_directory.clone(),
+                        plan.witness_values.clone(),
+                        plan_inputs(&plan, &inputs),
+                        plan.force,
+                        plan.prove_with,
+                        plan.export_csv,
+                        plan.csv_mode,
+                        &plan.csv_columns,
+                        plan.dump_range_constraints,
+                        plan.show_range_constraints,
+                        plan.bname,
+                        plan.degree,
+                        &plan.limits,
+                        &plan.unconstrained,
+                        plan.max_inlined_intermediate_size,
+                        cancellation
+                    )) {
+                        Ok(report) => print_result(&report, output_format),
+                        Err(errors) => {
+                            exit_if_cancelled(cancellation);
+                            eprintln!("Errors:");
+                            for e in errors {
+                                eprintln!("{e}");
+                            }
                         }
-                    }
-                };
+                    };
+                }
             }
-        },
+        }
         Commands::Prove {
             file,
             dir,
@@ -477,26 +1499,150 @@ fn run_command(command: Commands) {
             backend,
             proof,
             params,
+            chunk_index,
+            export_verification_key,
+            raw_proof,
+            force,
+        } => {
+            let pil = Path::new(&file);
+            let dir = Path::new(&dir);
+            let field = resolve_field(field, dir).unwrap_or_else(|e| panic!("{e}"));
+            if let Some(report) = call_with_field!(read_and_prove::<field>(
+                pil,
+                dir,
+                &backend,
+                proof,
+                params,
+                chunk_index,
+                export_verification_key,
+                raw_proof,
+                force,
+                cancellation
+            )) {
+                print_result(&report, output_format);
+            }
+        }
+        Commands::Aggregate {
+            file,
+            proofs,
+            dir,
+            field,
+            backend,
+            params,
+            raw_proof,
+        } => {
+            let pil = Path::new(&file);
+            let dir = Path::new(&dir);
+            let field = resolve_field(field, dir).unwrap_or_else(|e| panic!("{e}"));
+            match call_with_field!(read_and_aggregate::<field>(
+                pil,
+                dir,
+                &backend,
+                &proofs,
+                params,
+                raw_proof,
+                cancellation
+            )) {
+                Ok(Some(report)) => print_result(&report, output_format),
+                // On cancellation, the caller reports the aborted write.
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Could not aggregate the proofs: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Verify {
+            file,
+            dir,
+            field,
+            backend,
+            proof,
+            raw_proof,
+            params,
+            verification_key,
+            publics,
+            force,
         } => {
             let pil = Path::new(&file);
             let dir = Path::new(&dir);
-            call_with_field!(read_and_prove::<field>(pil, dir, &backend, proof, params));
+            let field = resolve_field(field, dir).unwrap_or_else(|e| panic!("{e}"));
+            match call_with_field!(read_and_verify::<field>(
+                pil,
+                dir,
+                &backend,
+                &proof,
+                raw_proof,
+                params,
+                verification_key,
+                publics,
+                force
+            )) {
+                Ok(()) => {
+                    log::info!("Proof is valid.");
+                    print_result(&report::VerifyReport { valid: true }, output_format);
+                }
+                Err(backend::Error::InvalidProof(reason)) => {
+                    eprintln!("Proof invalid: {reason}");
+                    print_result(&report::VerifyReport { valid: false }, output_format);
+                    std::process::exit(1);
+                }
+                Err(backend::Error::IO(e)) => {
+                    eprintln!("Could not load artifacts: {e}");
+                    std::process::exit(2);
+                }
+                Err(e) => {
+                    eprintln!("Could not verify proof: {e}");
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::CheckWitness {
+            file,
+            dir,
+            witness,
+            blind_columns,
+            blind_rows,
+            blind_seed,
+            field,
+        } => {
+            let dir = Path::new(&dir);
+            let field = resolve_field(field, dir).unwrap_or_else(|e| panic!("{e}"));
+            let blinding = blind_columns.map(|columns| RowBlinding {
+                columns: split_list(&columns),
+                rows: blind_rows.unwrap_or(1),
+                seed: blind_seed.unwrap_or(0),
+            });
+            call_with_field!(check_witness::<field>(
+                Path::new(&file),
+                dir,
+                witness,
+                blinding,
+                output_format
+            ));
         }
         Commands::Setup {
             size,
             dir,
             field,
             backend,
+            seed,
         } => {
-            call_with_field!(setup::<field>(size, dir, backend));
+            let field = resolve_field(field, Path::new(&dir)).unwrap_or_else(|e| panic!("{e}"));
+            call_with_field!(setup::<field>(size, dir, backend, seed));
         }
     };
+    exit_if_cancelled(cancellation);
 }
 
-fn setup<F: FieldElement>(size: u64, dir: String, backend_type: BackendType) {
+fn setup<F: FieldElement>(size: u64, dir: String, backend_type: BackendType, seed: Option<u64>) {
     let dir = Path::new(&dir);
 
-    let backend = backend_type.factory::<F>().create(size);
+    let factory = backend_type.factory::<F>();
+    let backend = match seed {
+        Some(seed) => factory.create_with_seed(size, seed),
+        None => factory.create(size),
+    };
     write_backend_to_fs(backend.as_ref(), dir);
 }
 
@@ -508,16 +1654,25 @@ fn write_backend_to_fs<F: FieldElement>(be: &dyn Backend<F>, output_dir: &Path)
     log::info!("Wrote params.bin.");
 }
 
+#[cfg(feature = "riscv")]
 #[allow(clippy::too_many_arguments)]
 fn run_rust<F: FieldElement>(
     file_name: &str,
-    inputs: Vec<F>,
+    inputs: HashMap<F, Vec<F>>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
     coprocessors: riscv::CoProcessors,
     just_execute: bool,
     continuations: bool,
+    check_executor: bool,
+    degree: Option<DegreeType>,
+    chunk_options: &ChunkOptions,
+    witgen_options: &WitgenOptions,
+    via_elf: bool,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
 ) -> Result<(), Vec<String>> {
     let (asm_file_path, asm_contents) = compile_rust(
         file_name,
@@ -525,11 +1680,12 @@ fn run_rust<F: FieldElement>(
         force_overwrite,
         &coprocessors,
         continuations,
+        via_elf,
     )
     .ok_or_else(|| vec!["could not compile rust".to_string()])?;
 
     handle_riscv_asm(
-        asm_file_path.to_str().unwrap(),
+        &asm_file_path.to_string_lossy(),
         &asm_contents,
         inputs,
         output_dir,
@@ -537,34 +1693,65 @@ fn run_rust<F: FieldElement>(
         prove_with,
         just_execute,
         continuations,
+        check_executor,
+        degree,
+        chunk_options,
+        witgen_options,
+        unconstrained,
+        max_inlined_intermediate_size,
+        cancellation,
     )?;
     Ok(())
 }
 
+#[cfg(feature = "riscv")]
 #[allow(clippy::too_many_arguments)]
 fn run_riscv_asm<F: FieldElement>(
     original_file_name: &str,
     file_names: impl Iterator<Item = String>,
-    inputs: Vec<F>,
+    inputs: HashMap<F, Vec<F>>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
     coprocessors: riscv::CoProcessors,
     just_execute: bool,
     continuations: bool,
+    check_executor: bool,
+    degree: Option<DegreeType>,
+    chunk_options: &ChunkOptions,
+    witgen_options: &WitgenOptions,
+    via_elf: bool,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
 ) -> Result<(), Vec<String>> {
-    let (asm_file_path, asm_contents) = compile_riscv_asm(
-        original_file_name,
-        file_names,
-        output_dir,
-        force_overwrite,
-        &coprocessors,
-        false,
-    )
-    .ok_or_else(|| vec!["could not compile RISC-V assembly".to_string()])?;
+    let (asm_file_path, asm_contents) = if via_elf {
+        let [elf_file] = &file_names.collect::<Vec<_>>()[..] else {
+            return Err(vec!["Exactly one ELF file is required.".to_string()]);
+        };
+        compile_riscv_elf(
+            original_file_name,
+            Path::new(elf_file),
+            output_dir,
+            force_overwrite,
+            &coprocessors,
+            continuations,
+        )
+        .ok_or_else(|| vec!["could not translate the RISC-V ELF file".to_string()])?
+    } else {
+        compile_riscv_asm(
+            original_file_name,
+            file_names,
+            output_dir,
+            force_overwrite,
+            &coprocessors,
+            continuations,
+        )
+        .ok_or_else(|| vec!["could not compile RISC-V assembly".to_string()])?
+    };
 
     handle_riscv_asm(
-        asm_file_path.to_str().unwrap(),
+        &asm_file_path.to_string_lossy(),
         &asm_contents,
         inputs,
         output_dir,
@@ -572,249 +1759,196 @@ fn run_riscv_asm<F: FieldElement>(
         prove_with,
         just_execute,
         continuations,
+        check_executor,
+        degree,
+        chunk_options,
+        witgen_options,
+        unconstrained,
+        max_inlined_intermediate_size,
+        cancellation,
     )?;
     Ok(())
 }
 
+#[cfg(feature = "riscv")]
 #[allow(clippy::too_many_arguments)]
 fn handle_riscv_asm<F: FieldElement>(
     file_name: &str,
     contents: &str,
-    inputs: Vec<F>,
+    inputs: HashMap<F, Vec<F>>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
     just_execute: bool,
     continuations: bool,
+    check_executor: bool,
+    degree: Option<DegreeType>,
+    chunk_options: &ChunkOptions,
+    witgen_options: &WitgenOptions,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
 ) -> Result<(), Vec<String>> {
+    if check_executor {
+        return executor_check::check_executor(file_name, contents, inputs, witgen_options);
+    }
     match (just_execute, continuations) {
-        (true, true) => {
-            rust_continuations(file_name, contents, inputs);
-        }
+        (true, true) => match chunk_options {
+            ChunkOptions {
+                chunks_dir: Some(dir),
+                chunk: Some(chunk),
+                ..
+            } => {
+                let trace = rust_continuations_run_chunk(
+                    file_name,
+                    contents,
+                    inputs,
+                    &dir.join(ChunkState::file_name(*chunk)),
+                )
+                .map_err(|e| vec![e])?;
+                log::info!("Chunk trace length: {}", trace["main.pc"].len());
+            }
+            ChunkOptions {
+                chunks_dir: Some(dir),
+                chunk: None,
+                row_budget,
+            } => {
+                let files =
+                    rust_continuations_export(file_name, contents, inputs, dir, *row_budget)
+                        .map_err(|e| vec![e])?;
+                log::info!("Wrote {} chunk files to {}.", files.len(), dir.display());
+            }
+            ChunkOptions {
+                chunks_dir: None,
+                row_budget,
+                ..
+            } => rust_continuations(file_name, contents, inputs, *row_budget),
+        },
         (true, false) => {
-            let mut inputs_hash: HashMap<F, Vec<F>> = HashMap::default();
-            inputs_hash.insert(0u32.into(), inputs);
-            riscv_executor::execute::<F>(contents, &inputs_hash, &default_input());
+            riscv_executor::execute::<F>(contents, &inputs, &default_input());
         }
         (false, true) => {
             unimplemented!("Running witgen with continuations is not supported yet.")
         }
         (false, false) => {
-            compile_asm_string(
+            compile_asm_string_with_callback(
                 file_name,
                 contents,
-                inputs,
+                channel_inputs_to_query_callback(inputs),
                 None,
                 output_dir,
                 force_overwrite,
                 prove_with,
                 vec![],
                 None,
+                degree,
+                unconstrained,
+                max_inlined_intermediate_size,
+                cancellation,
             )?;
         }
     }
     Ok(())
 }
 
-fn transposed_trace<F: FieldElement>(trace: &ExecutionTrace) -> HashMap<String, Vec<F>> {
-    let mut reg_values: HashMap<&str, Vec<F>> = HashMap::with_capacity(trace.reg_map.len());
+#[allow(clippy::too_many_arguments)]
+fn compile_with_csv_export<T: FieldElement>(
+    file: String,
+    output_directory: String,
+    witness_values: Option<String>,
+    inputs: HashMap<T, Vec<T>>,
+    force: bool,
+    prove_with: Option<BackendType>,
+    export_csv: bool,
+    csv_mode: CsvRenderModeCLI,
+    csv_columns: &[String],
+    dump_range_constraints: bool,
+    show_range_constraints: bool,
+    bname: Option<String>,
+    degree: Option<DegreeType>,
+    limits: &Limits,
+    unconstrained: &UnconstrainedWitnesses,
+    max_inlined_intermediate_size: usize,
+    cancellation: &CancellationToken,
+) -> Result<report::PilReport, Vec<String>> {
+    let external_witness_values = witness_values
+        .map(|csv_path| {
+            let csv_file = fs::File::open(csv_path).unwrap();
+            let mut csv_writer = BufReader::new(&csv_file);
+            read_polys_csv_file::<T>(&mut csv_writer)
+        })
+        .unwrap_or(vec![]);
+
+    // Convert Vec<(String, Vec<T>)> to Vec<(&str, Vec<T>)>
+    let (strings, values): (Vec<_>, Vec<_>) = external_witness_values.into_iter().unzip();
+    let external_witness_values = strings.iter().map(AsRef::as_ref).zip(values).collect();
+
+    let output_dir = Path::new(&output_directory);
+    let result = compile_pil_or_asm_with_callback::<T, _>(
+        &file,
+        channel_inputs_to_query_callback(inputs),
+        output_dir,
+        force,
+        prove_with.clone(),
+        external_witness_values,
+        bname,
+        degree,
+        limits,
+        unconstrained,
+        max_inlined_intermediate_size,
+        cancellation,
+    )?;
+    let cancelled = |e: Cancelled| vec![e.to_string()];
+    cancellation.check("writing results").map_err(cancelled)?;
+
+    let mut report = report::PilReport {
+        artifacts: vec![],
+        degree: None,
+        witness_columns: None,
+        fixed_columns: None,
+    };
+    let mut artifacts = vec![];
+    if let Some(ref compilation_result) = result {
+        if let Some(pil) = &compilation_result.pil {
+            artifacts.push(pil.file.clone());
+            report.degree = pil.degree;
+            report.witness_columns = Some(pil.commitment_count);
+            report.fixed_columns = Some(pil.constant_count);
+        }
+        if show_range_constraints {
+            for c in &compilation_result.range_constraints {
+                eprintln!("{}: {}", c.column, c.interval);
+            }
+        }
+        if dump_range_constraints {
+            artifacts.push(
+                write_range_constraints_to_fs(
+                    &compilation_result.range_constraints,
+                    output_dir,
+                    cancellation,
+                )
+                .map_err(cancelled)?,
+            );
+        }
+        artifacts.extend(
+            serialize_result_witness(output_dir, compilation_result, cancellation)
+                .map_err(cancelled)?,
+        );
 
-    for row in trace.regs_rows() {
-        for (reg_name, &index) in trace.reg_map.iter() {
-            reg_values
-                .entry(reg_name)
-                .or_default()
-                .push(row[index].0.into());
-        }
-    }
-
-    reg_values
-        .into_iter()
-        .map(|(n, c)| (format!("main.{}", n), c))
-        .collect()
-}
-
-fn rust_continuations<F: FieldElement>(file_name: &str, contents: &str, inputs: Vec<F>) {
-    let mut bootloader_inputs = default_input();
-
-    let program =
-        compiler::compile_asm_string_to_analyzed_ast::<F>(file_name, contents, None).unwrap();
-
-    let inputs: HashMap<F, Vec<F>> = vec![(F::from(0), inputs)].into_iter().collect();
-
-    log::info!("Executing powdr-asm...");
-    let (full_trace, memory_accesses) = {
-        let trace =
-            riscv_executor::execute_ast::<F>(&program, &inputs, &bootloader_inputs, usize::MAX).0;
-        (transposed_trace::<F>(&trace), trace.mem)
-    };
-
-    let full_trace_length = full_trace["main.pc"].len();
-    log::info!("Total trace length: {}", full_trace_length);
-
-    let mut proven_trace = 0;
-    let mut chunk_index = 0;
-    let mut memory_snapshot = HashMap::new();
-
-    loop {
-        log::info!("\nRunning chunk {}...", chunk_index);
-        // Run for 2**degree - 2 steps, because the executor doesn't run the dispatcher,
-        // which takes 2 rows.
-        let degree = program
-            .machines
-            .iter()
-            .fold(None, |acc, (_, m)| acc.or(m.degree.clone()))
-            .unwrap()
-            .degree;
-        let degree = F::from(degree).to_degree();
-        let num_rows = degree as usize - 2;
-        let (chunk_trace, memory_snapshot_update) = {
-            let (trace, memory_snapshot_update) =
-                riscv_executor::execute_ast::<F>(&program, &inputs, &bootloader_inputs, num_rows);
-            (transposed_trace(&trace), memory_snapshot_update)
-        };
-        log::info!("{} memory slots updated.", memory_snapshot_update.len());
-        memory_snapshot.extend(memory_snapshot_update);
-        log::info!("Chunk trace length: {}", chunk_trace["main.pc"].len());
-
-        log::info!("Validating chunk...");
-        let (start, _) = chunk_trace["main.pc"]
-            .iter()
-            .enumerate()
-            .find(|(_, &pc)| pc == bootloader_inputs[PC_INDEX])
-            .unwrap();
-        let full_trace_start = match chunk_index {
-            // The bootloader execution in the first chunk is part of the full trace.
-            0 => start,
-            // Any other chunk starts at where we left off in the full trace.
-            _ => proven_trace - 1,
-        };
-        log::info!("Bootloader used {} rows.", start);
-        for i in 0..(chunk_trace["main.pc"].len() - start) {
-            for &reg in REGISTER_NAMES.iter() {
-                let chunk_i = i + start;
-                let full_i = i + full_trace_start;
-                if chunk_trace[reg][chunk_i] != full_trace[reg][full_i] {
-                    log::error!("The Chunk trace differs from the full trace!");
-                    log::error!(
-                        "Started comparing from row {start} in the chunk to row {full_trace_start} in the full trace; the difference is at offset {i}."
-                    );
-                    log::error!(
-                        "The PCs are {} and {}.",
-                        chunk_trace["main.pc"][chunk_i],
-                        full_trace["main.pc"][full_i]
-                    );
-                    log::error!(
-                        "The first difference is in register {}: {} != {} ",
-                        reg,
-                        chunk_trace[reg][chunk_i],
-                        full_trace[reg][full_i],
-                    );
-                    panic!();
-                }
-            }
-        }
-
-        if chunk_trace["main.pc"].len() < num_rows {
-            log::info!("Done!");
-            break;
-        }
-
-        let new_rows = match chunk_index {
-            0 => num_rows,
-            // Minus 1 because the first row was proven already.
-            _ => num_rows - start - 1,
-        };
-        proven_trace += new_rows;
-        log::info!("Proved {} rows.", new_rows);
-
-        log::info!("Building inputs for chunk {}...", chunk_index + 1);
-        let mut accessed_pages = BTreeSet::new();
-        let start_idx = memory_accesses
-            .binary_search_by_key(&proven_trace, |a| a.idx)
-            .unwrap_or_else(|v| v);
-
-        for access in &memory_accesses[start_idx..] {
-            // proven_trace + num_rows is an upper bound for the last row index we'll reach in the next chunk.
-            // In practice, we'll stop earlier, because the bootloader needs to run as well, but we don't know for
-            // how long as that depends on the number of pages.
-            if access.idx >= proven_trace + num_rows {
-                break;
-            }
-            accessed_pages.insert(access.address >> PAGE_SIZE_BYTES_LOG);
-        }
-        log::info!(
-            "{} accessed pages: {:?}",
-            accessed_pages.len(),
-            accessed_pages
-        );
-
-        bootloader_inputs = vec![];
-        for &reg in REGISTER_NAMES.iter() {
-            bootloader_inputs.push(*chunk_trace[reg].last().unwrap());
-        }
-        bootloader_inputs.push((accessed_pages.len() as u64).into());
-        for &page in accessed_pages.iter() {
-            let start_addr = page << PAGE_SIZE_BYTES_LOG;
-            bootloader_inputs.push(page.into());
-            let words_per_page = (1 << (PAGE_SIZE_BYTES_LOG)) / BYTES_PER_WORD;
-            for i in 0..words_per_page {
-                let addr = start_addr + (i * BYTES_PER_WORD) as u32;
-                bootloader_inputs.push((*memory_snapshot.get(&addr).unwrap_or(&0)).into());
-            }
-        }
-
-        log::info!("Inputs length: {}", bootloader_inputs.len());
-
-        chunk_index += 1;
-    }
-}
-
-#[allow(clippy::too_many_arguments)]
-fn compile_with_csv_export<T: FieldElement>(
-    file: String,
-    output_directory: String,
-    witness_values: Option<String>,
-    inputs: String,
-    force: bool,
-    prove_with: Option<BackendType>,
-    export_csv: bool,
-    csv_mode: CsvRenderModeCLI,
-    bname: Option<String>,
-) -> Result<(), Vec<String>> {
-    let external_witness_values = witness_values
-        .map(|csv_path| {
-            let csv_file = fs::File::open(csv_path).unwrap();
-            let mut csv_writer = BufReader::new(&csv_file);
-            read_polys_csv_file::<T>(&mut csv_writer)
-        })
-        .unwrap_or(vec![]);
-
-    // Convert Vec<(String, Vec<T>)> to Vec<(&str, Vec<T>)>
-    let (strings, values): (Vec<_>, Vec<_>) = external_witness_values.into_iter().unzip();
-    let external_witness_values = strings.iter().map(AsRef::as_ref).zip(values).collect();
-
-    let output_dir = Path::new(&output_directory);
-    let result = compile_pil_or_asm::<T>(
-        &file,
-        split_inputs(&inputs),
-        output_dir,
-        force,
-        prove_with.clone(),
-        external_witness_values,
-        bname,
-    )?;
-
-    if let Some(ref compilation_result) = result {
-        serialize_result_witness(output_dir, compilation_result);
-
-        if let Some(_backend) = prove_with {
-            write_proving_results_to_fs(
-                false,
-                &compilation_result.proof,
-                &compilation_result.constraints_serialization,
-                output_dir,
-            );
+        if let Some(backend) = prove_with {
+            artifacts.extend(
+                write_proving_results_to_fs(
+                    &backend,
+                    false,
+                    false,
+                    &compilation_result.proof,
+                    &compilation_result.publics,
+                    &compilation_result.constraints_serialization,
+                    output_dir,
+                    cancellation,
+                )
+                .map_err(cancelled)?,
+            );
         }
     }
 
@@ -822,52 +1956,97 @@ fn compile_with_csv_export<T: FieldElement>(
         // Compilation result is None if the ASM file has not been compiled
         // (e.g. it has been compiled before and the force flag is not set)
         if let Some(compilation_result) = result {
-            let csv_path = Path::new(&output_directory).join("columns.csv");
-            export_columns_to_csv::<T>(
-                compilation_result.constants,
-                compilation_result.witness,
-                &csv_path,
-                csv_mode,
+            let write_error = |e: WriteError| vec![e.to_string()];
+            let filter = ColumnFilter::new(csv_columns);
+            let witness = compilation_result.witness.unwrap_or_default();
+            filter.warn_unmatched(
+                compilation_result
+                    .constants
+                    .iter()
+                    .chain(&witness)
+                    .map(|(name, _)| name.as_str()),
             );
+            let constants = filter.apply(compilation_result.constants);
+            let witness = filter.apply(witness);
+            if !csv_columns.is_empty() && constants.is_empty() && witness.is_empty() {
+                return Err(vec![format!(
+                    "No column matches the CSV column patterns `{}`.",
+                    csv_columns.join(",")
+                )]);
+            }
+            if !constants.is_empty() {
+                let fixed_csv_path = Path::new(&output_directory).join("fixed.csv");
+                export_columns_to_csv::<T>(
+                    &constants,
+                    &fixed_csv_path,
+                    csv_mode.clone(),
+                    cancellation,
+                )
+                .map_err(write_error)?;
+                artifacts.push(fixed_csv_path);
+            }
+
+            let columns = constants.into_iter().chain(witness).collect::<Vec<_>>();
+            let csv_path = Path::new(&output_directory).join("columns.csv");
+            export_columns_to_csv::<T>(&columns, &csv_path, csv_mode, cancellation)
+                .map_err(write_error)?;
+            artifacts.push(csv_path);
         }
     }
-    Ok(())
+    report.artifacts = artifacts
+        .into_iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    Ok(report)
 }
 
 fn export_columns_to_csv<T: FieldElement>(
-    fixed: Vec<(String, Vec<T>)>,
-    witness: Option<Vec<(String, Vec<T>)>>,
+    columns: &[(String, Vec<T>)],
     csv_path: &Path,
     render_mode: CsvRenderModeCLI,
-) {
-    let columns = fixed
-        .into_iter()
-        .chain(witness.unwrap_or(vec![]))
-        .collect::<Vec<_>>();
-
-    let mut csv_file = fs::File::create(csv_path).unwrap();
-    let mut csv_writer = BufWriter::new(&mut csv_file);
-
+    cancellation: &CancellationToken,
+) -> Result<(), WriteError> {
     let render_mode = match render_mode {
         CsvRenderModeCLI::SignedBase10 => CsvRenderMode::SignedBase10,
         CsvRenderModeCLI::UnsignedBase10 => CsvRenderMode::UnsignedBase10,
         CsvRenderModeCLI::Hex => CsvRenderMode::Hex,
     };
 
-    write_polys_csv_file(&mut csv_writer, render_mode, &columns);
+    try_write_atomically(csv_path, cancellation, |csv_writer| {
+        write_polys_csv_file(csv_writer, render_mode, columns)
+    })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn read_and_prove<T: FieldElement>(
     file: &Path,
     dir: &Path,
     backend_type: &BackendType,
     proof_path: Option<String>,
     params: Option<String>,
-) {
+    chunk_index: Option<u64>,
+    export_verification_key: Option<String>,
+    raw_proof: bool,
+    force: bool,
+    cancellation: &CancellationToken,
+) -> Option<report::ProveReport> {
+    if let Err(e) = resolve_field(Some(FieldArgument::of::<T>()), dir) {
+        panic!("{e}");
+    }
     let pil = pilopt::optimize(compiler::analyze_pil::<T>(file));
+    if !force {
+        if let Err(e) = Artifacts::check(dir, &pil) {
+            panic!("{e}");
+        }
+    }
+    if let Err(e) = backend_type.check_supported(&pil) {
+        panic!("{e}");
+    }
 
-    let fixed = read_poly_set::<FixedPolySet, T>(&pil, dir);
-    let witness = read_poly_set::<WitnessPolySet, T>(&pil, dir);
+    // The columns are mapped from the files, so that backends that support it
+    // do not need to hold them in memory.
+    let fixed = map_poly_set::<FixedPolySet, T>(&pil, dir).unwrap_or_else(|e| panic!("{e}"));
+    let witness = map_poly_set::<WitnessPolySet, T>(&pil, dir).unwrap_or_else(|e| panic!("{e}"));
 
     assert_eq!(fixed.1, witness.1);
 
@@ -881,70 +2060,757 @@ fn read_and_prove<T: FieldElement>(
 
     let proof = proof_path.map(|filename| {
         let mut buf = Vec::new();
-        fs::File::open(dir.join(filename))
+        fs::File::open(dir.join(&filename))
             .unwrap()
             .read_to_end(&mut buf)
             .unwrap();
-        buf
+        if raw_proof {
+            buf
+        } else {
+            ProofFile::open::<T>(&buf, backend_type, &backend::pil_hash(&pil))
+                .unwrap_or_else(|e| panic!("Could not read {filename}: {e}"))
+        }
     });
     let is_aggr = proof.is_some();
 
-    let (proof, constraints_serialization) = backend.prove(&pil, &fixed.0, &witness.0, proof, None);
-    write_proving_results_to_fs(is_aggr, &proof, &constraints_serialization, dir);
+    let publics = backend::evaluate_publics(&pil, &witness.0)
+        .unwrap_or_else(|e| panic!("Could not evaluate the publics: {e}"));
+
+    cancellation.check("proving").ok()?;
+    let (mut proof, constraints_serialization) = backend.prove_columns(
+        &pil,
+        &column_refs(&fixed.0),
+        &column_refs(&witness.0),
+        &publics,
+        proof,
+        None,
+    );
+    if let Some((_, metadata)) = &mut proof {
+        metadata.chunk_index = chunk_index;
+    }
+    cancellation.stage_completed("proving");
+    // On cancellation, the caller reports the aborted write.
+    let written = write_proving_results_to_fs(
+        backend_type,
+        raw_proof,
+        is_aggr,
+        &proof,
+        &publics,
+        &constraints_serialization,
+        dir,
+        cancellation,
+    )
+    .ok()?;
+    // The proof is written first, if there is one.
+    let report = report::ProveReport {
+        proof: proof.as_ref().map(|_| written[0].display().to_string()),
+        size_bytes: proof.as_ref().map(|(proof, _)| proof.len()),
+    };
+
+    if let Some(filename) = export_verification_key {
+        let to_write = dir.join(filename);
+        let (fixed, _) =
+            read_poly_set::<FixedPolySet, T>(&pil, dir).unwrap_or_else(|e| panic!("{e}"));
+        if write_atomically(&to_write, cancellation, |writer| {
+            backend
+                .export_verification_key(&pil, &fixed, writer)
+                .unwrap()
+        })
+        .is_ok()
+        {
+            log::info!("Wrote {}.", to_write.display());
+        }
+    }
+    Some(report)
 }
 
-#[allow(clippy::print_stdout)]
-fn optimize_and_output<T: FieldElement>(file: &str) {
+/// Reads the given proofs and their metadata from `dir`, checks that they can be
+/// aggregated and aggregates them one after the other into `proof_aggr.bin`.
+/// Returns `None` if the aggregation has been cancelled.
+fn read_and_aggregate<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    backend_type: &BackendType,
+    proof_paths: &[String],
+    params: Option<String>,
+    raw_proof: bool,
+    cancellation: &CancellationToken,
+) -> Result<Option<report::ProveReport>, String> {
+    let pil = pilopt::optimize(compiler::analyze_pil::<T>(file));
+    let pil_hash = backend::pil_hash(&pil);
+
+    let read =
+        |path: &Path| fs::read(path).map_err(|e| format!("Could not read {}: {e}", path.display()));
+    let (proofs, metadata): (Vec<_>, Vec<_>) = proof_paths
+        .iter()
+        .map(|proof_path| {
+            let path = dir.join(proof_path);
+            let metadata_path = path.with_file_name(ProofMetadata::file_name(
+                &path.file_name().unwrap().to_string_lossy(),
+            ));
+            let metadata: ProofMetadata = serde_json::from_slice(&read(&metadata_path)?)
+                .map_err(|e| format!("Invalid metadata in {}: {e}", metadata_path.display()))?;
+            let proof = read(&path)?;
+            let proof = if raw_proof {
+                proof
+            } else {
+                ProofFile::open::<T>(&proof, backend_type, &pil_hash)
+                    .map_err(|e| format!("Could not read {}: {e}", path.display()))?
+            };
+            Ok((proof, metadata))
+        })
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter()
+        .unzip();
+    backend::check_aggregation_compatible(&metadata)?;
+    if metadata[0].pil_hash != pil_hash {
+        return Err(format!("The proofs are not for {}.", file.display()));
+    }
+
+    let (fixed, degree) = read_poly_set::<FixedPolySet, T>(&pil, dir)?;
+    let builder = backend_type.factory::<T>();
+    let backend = match params {
+        Some(filename) => {
+            let mut file = fs::File::open(dir.join(filename)).map_err(|e| e.to_string())?;
+            builder
+                .create_from_setup(&mut file)
+                .map_err(|e| e.to_string())?
+        }
+        None => builder.create(degree),
+    };
+
+    let mut proofs = proofs.into_iter();
+    let mut aggregated = proofs.next().unwrap();
+    for (i, proof) in proofs.enumerate() {
+        if cancellation.check("aggregation").is_err() {
+            return Ok(None);
+        }
+        log::info!("Aggregating proof {}...", i + 1);
+        aggregated = backend
+            .aggregate(&pil, &fixed, &aggregated, &proof)
+            .map_err(|e| e.to_string())?;
+    }
+    cancellation.stage_completed("aggregation");
+
+    let aggregated_metadata = ProofMetadata {
+        aggregated: metadata.into_iter().map(|m| m.transcript_hash).collect(),
+        ..ProofMetadata::new(&pil, &aggregated)
+    };
+    let size_bytes = aggregated.len();
+    let Ok(written) = write_proving_results_to_fs::<T>(
+        backend_type,
+        raw_proof,
+        true,
+        &Some((aggregated, aggregated_metadata)),
+        &[],
+        &None,
+        dir,
+        cancellation,
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(report::ProveReport {
+        proof: Some(written[0].display().to_string()),
+        size_bytes: Some(size_bytes),
+    }))
+}
+
+/// Reads the proof, the expected publics and either the verification key or the fixed
+/// columns (and optionally the setup parameters) from `dir` and verifies the proof.
+/// Unless `force` is set, the fixed columns have to be generated for the given PIL file.
+#[allow(clippy::too_many_arguments)]
+fn read_and_verify<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    backend_type: &BackendType,
+    proof_path: &str,
+    raw_proof: bool,
+    params: Option<String>,
+    verification_key: Option<String>,
+    publics_path: Option<String>,
+    force: bool,
+) -> Result<(), backend::Error> {
+    let pil = pilopt::optimize(compiler::analyze_pil::<T>(file));
+
+    let builder = backend_type.factory::<T>();
+    let (backend, fixed) = if let Some(filename) = verification_key {
+        let mut file = fs::File::open(dir.join(filename))?;
+        (builder.create_from_verification_key(&mut file)?, vec![])
+    } else {
+        if !force {
+            Artifacts::check(dir, &pil)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let (fixed, degree) = read_poly_set::<FixedPolySet, T>(&pil, dir)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let backend = if let Some(filename) = params {
+            let mut file = fs::File::open(dir.join(filename))?;
+            builder.create_from_setup(&mut file)?
+        } else {
+            builder.create(degree)
+        };
+        (backend, fixed)
+    };
+
+    let proof = fs::read(dir.join(proof_path))?;
+    let proof = if raw_proof {
+        proof
+    } else {
+        ProofFile::open::<T>(&proof, backend_type, &backend::pil_hash(&pil)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Could not read {proof_path}: {e}"),
+            )
+        })?
+    };
+    let publics = read_publics(&pil, dir, publics_path)?;
+
+    backend.verify(&pil, &fixed, &proof, &publics)
+}
+
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn check_witness<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    witness_path: Option<String>,
+    blinding: Option<RowBlinding>,
+    output_format: OutputFormat,
+) {
+    let transformers = blinding
+        .into_iter()
+        .map(|blinding| Box::new(blinding) as Box<dyn WitnessTransformer<T>>)
+        .collect::<Vec<_>>();
+    match read_and_check_witness::<T>(file, dir, witness_path, &transformers) {
+        Ok(violations) => {
+            print!(
+                "{}",
+                report::render(
+                    &report::WitnessViolationReport::from(&violations[..]),
+                    output_format
+                )
+            );
+            if !violations.is_empty() {
+                eprintln!("The witness violates {} constraint(s).", violations.len());
+                std::process::exit(1);
+            }
+            log::info!("The witness satisfies all identities.");
+        }
+        Err(e) => {
+            eprintln!("Could not check the witness: {e}");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Reads the fixed columns from `dir` and the witness from the given file (binary or,
+/// if it ends with `.csv`, CSV) or from `commits.bin` in `dir`, applies the witness
+/// transformers and returns all rows on which an identity does not hold.
+fn read_and_check_witness<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    witness_path: Option<String>,
+    transformers: &[Box<dyn WitnessTransformer<T>>],
+) -> Result<Vec<executor::witness_checker::Violation<T>>, String> {
+    let pil = pilopt::optimize(compiler::analyze_pil::<T>(file));
+
+    let open = |path: &Path| {
+        fs::File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("Could not open {}: {e}", path.display()))
+    };
+    let (fixed, _) = read_poly_set::<FixedPolySet, T>(&pil, dir)?;
+
+    let witness_path = witness_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| dir.join(WitnessPolySet::FILE_NAME));
+    let mut witness_file = open(&witness_path)?;
+    let mut witness = if witness_path.extension().map_or(false, |ext| ext == "csv") {
+        read_polys_csv_file::<T>(&mut witness_file)
+    } else {
+        let column_names = WitnessPolySet::get_polys(&pil)
+            .iter()
+            .flat_map(|(poly, _)| poly.array_elements())
+            .map(|(name, _id)| name)
+            .collect::<Vec<_>>();
+        read_polys_file::<T>(&mut witness_file, &column_names)
+            .map_err(|e| format!("Could not read {}: {e}", witness_path.display()))?
+            .0
+    };
+    let transformation_log = apply_witness_transformers(&pil, &mut witness, transformers);
+    if !transformation_log.entries.is_empty() {
+        log::info!("Transformed the witness:\n{transformation_log}");
+    }
+
+    executor::witness_checker::check_witness(&pil, &fixed, &witness)
+}
+
+/// Reads the expected values of the public declarations of `pil` from the given file
+/// or, if not given, from `publics.json` in `dir` if it exists.
+fn read_publics<T: FieldElement>(
+    pil: &Analyzed<T>,
+    dir: &Path,
+    publics_path: Option<String>,
+) -> Result<Vec<(String, T)>, backend::Error> {
+    let path = match publics_path {
+        Some(path) => Some(dir.join(path)),
+        None => Some(dir.join(PUBLICS_FILE_NAME)).filter(|path| path.exists()),
+    };
+    let values = inputs::read_inputs::<T>(None, path.as_deref(), true)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let mut declarations = pil.public_declarations.values().collect::<Vec<_>>();
+    declarations.sort_by_key(|decl| decl.id);
+    if values.len() != declarations.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Expected values for {} publics, but got {}.",
+                declarations.len(),
+                values.len()
+            ),
+        )
+        .into());
+    }
+    Ok(declarations
+        .into_iter()
+        .map(|decl| decl.name.clone())
+        .zip(values)
+        .collect())
+}
+
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn optimize_and_output<T: FieldElement>(
+    file: &str,
+    verbose: bool,
+    stats: bool,
+    max_inlined_intermediate_size: usize,
+) {
+    let (optimized, duplicates) = pilopt::optimize_reporting_duplicates(
+        compiler::analyze_pil::<T>(Path::new(file)),
+        max_inlined_intermediate_size,
+    );
+    if verbose {
+        for identity in duplicates {
+            eprintln!(
+                "Removed duplicate identity at {}:{}: {identity}",
+                identity.source.file, identity.source.line
+            );
+        }
+    }
+    if stats {
+        let report = report::StatsReport::from(&optimized.stats());
+        eprint!("{}", report::render(&report, OutputFormat::Human));
+    }
     println!(
         "{}",
-        pilopt::optimize(compiler::analyze_pil::<T>(Path::new(file)))
+        compiler::with_provenance(Provenance::for_input_file(Path::new(file)), optimized)
     );
 }
 
-fn serialize_result_witness<T: FieldElement>(output_dir: &Path, results: &CompilationResult<T>) {
-    write_constants_to_fs(&results.constants, output_dir);
-    let witness = results.witness.as_ref().unwrap();
-    write_commits_to_fs(witness, output_dir);
+/// Reformats the given files and the .asm and .pil files in the given directories.
+/// Exits with an error if a file cannot be parsed or, in check mode, is not formatted.
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn reformat_files(files: &[String], language: Option<Language>, in_place: bool, check: bool) {
+    let mut failed = false;
+    for file in files.iter().flat_map(|file| source_files(Path::new(file))) {
+        let file = file.to_string_lossy();
+        let language = language.unwrap_or_else(|| language_of(&file));
+        let contents = fs::read_to_string(file.as_ref()).unwrap();
+        let reformatted = match reformat(&file, &contents, language) {
+            Ok(reformatted) => reformatted,
+            Err(err) => {
+                err.output_to_stderr();
+                failed = true;
+                continue;
+            }
+        };
+        if check {
+            if reformatted != contents {
+                eprintln!("{file} is not formatted.");
+                failed = true;
+            }
+        } else if in_place {
+            if reformatted != contents {
+                fs::write(file.as_ref(), reformatted).unwrap();
+                log::info!("Reformatted {file}.");
+            }
+        } else {
+            print!("{reformatted}");
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
 }
 
-fn write_constants_to_fs<T: FieldElement>(constants: &[(String, Vec<T>)], output_dir: &Path) {
-    let to_write = output_dir.join("constants.bin");
-    write_polys_file(
-        &mut BufWriter::new(&mut fs::File::create(&to_write).unwrap()),
-        constants,
+/// The file itself or, for a directory, the .asm and .pil files in it and its
+/// subdirectories, sorted by path.
+fn source_files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    let mut entries = fs::read_dir(path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    entries.sort();
+    entries
+        .into_iter()
+        .flat_map(|path| {
+            if path.is_dir() {
+                source_files(&path)
+            } else if path
+                .extension()
+                .map_or(false, |ext| ext == "asm" || ext == "pil")
+            {
+                vec![path]
+            } else {
+                vec![]
+            }
+        })
+        .collect()
+}
+
+fn language_of(file: &str) -> Language {
+    if file.ends_with(".asm") {
+        Language::Asm
+    } else {
+        Language::Pil
+    }
+}
+
+/// Parses and prints the file, keeping its provenance header (if any) verbatim.
+fn reformat<'a>(
+    file: &str,
+    contents: &'a str,
+    language: Language,
+) -> Result<String, ParseError<'a>> {
+    let (header, _) = split_header(contents);
+    let formatted = match language {
+        Language::Asm => parser::parse_asm::<GoldilocksField>(Some(file), contents)?.to_string(),
+        Language::Pil => parser::parse::<GoldilocksField>(Some(file), contents)?.to_string(),
+    };
+    Ok(format!("{}{formatted}\n", header.unwrap_or_default()))
+}
+
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn inspect(file: &str, output_format: OutputFormat) {
+    let contents = fs::read_to_string(file).unwrap();
+    match Provenance::parse(&contents) {
+        Some(Ok(provenance)) => print!(
+            "{}",
+            report::render(&report::ProvenanceReport::from(provenance), output_format)
+        ),
+        Some(Err(e)) => {
+            eprintln!("{file}: {e}");
+            std::process::exit(1);
+        }
+        None => {
+            eprintln!("{file} has no provenance header.");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[allow(clippy::print_stderr)]
+fn rename_symbol(file: &str, from: &str, to: &str) {
+    let renamed = pil_analyzer::rename::rename::<GoldilocksField>(Path::new(file), from, to)
+        .unwrap_or_else(|errors| {
+            for e in errors {
+                eprintln!("{e}");
+            }
+            std::process::exit(1);
+        });
+    for (path, contents) in renamed {
+        fs::write(&path, contents).unwrap();
+        log::info!("Updated {}.", path.display());
+    }
+}
+
+#[allow(clippy::print_stdout)]
+fn diff_pil<T: FieldElement>(file_a: &str, file_b: &str, output_format: OutputFormat) {
+    let diff = pil_diff::diff_pil(
+        &compiler::analyze_pil::<T>(Path::new(file_a)),
+        &compiler::analyze_pil::<T>(Path::new(file_b)),
     );
+    let rendered = report::render(&diff, output_format);
+    match output_format {
+        // Log the lines so that removed and added items are colored like other diffs.
+        OutputFormat::Human => rendered.lines().for_each(|line| log::info!("{line}")),
+        OutputFormat::Json | OutputFormat::Csv => print!("{rendered}"),
+    }
+}
+
+#[allow(clippy::print_stdout)]
+fn analyze<T: FieldElement>(file: &str, dot: bool, output_format: OutputFormat) {
+    let analyzed = compiler::analyze_pil::<T>(Path::new(file));
+    let report = report::ColumnDependencyReport::from(&analyzed.column_dependency_graph());
+    if dot {
+        print!("{}", report.render_dot());
+    } else {
+        print!("{}", report::render(&report, output_format));
+    }
+}
+
+#[allow(clippy::print_stdout)]
+fn stats<T: FieldElement>(file: &str, output_format: OutputFormat) {
+    let analyzed = compiler::analyze_pil::<T>(Path::new(file));
+    let report = report::StatsReport::from(&analyzed.stats());
+    print!("{}", report::render(&report, output_format));
+}
+
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn debug<T: FieldElement>(
+    file: &str,
+    inputs: &[String],
+    row: Option<DegreeType>,
+    columns: &[String],
+    witgen_options: &WitgenOptions,
+    output_format: OutputFormat,
+) {
+    let analyzed = compiler::analyze_pil::<T>(Path::new(file));
+    let constants = executor::constant_evaluator::generate(&analyzed);
+    let result = inputs::read_channel_inputs(inputs, false)
+        .map_err(|e| e.to_string())
+        .and_then(|inputs| {
+            row_inspector::inspect_rows(
+                &analyzed,
+                &constants,
+                channel_inputs_to_query_callback(inputs),
+                row,
+                columns,
+                witgen_options,
+            )
+        });
+    match result {
+        Ok(report) => print!("{}", report::render(&report, output_format)),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn eval_fixed<T: FieldElement>(
+    file: &str,
+    name: &str,
+    rows: std::ops::Range<DegreeType>,
+    output_format: OutputFormat,
+) {
+    let analyzed = compiler::analyze_pil::<T>(Path::new(file));
+    let first_row = rows.start;
+    match executor::constant_evaluator::evaluate_fixed(&analyzed, name, rows) {
+        Ok(values) => print!(
+            "{}",
+            report::render(
+                &report::FixedColumnReport::new(name, first_row, &values),
+                output_format
+            )
+        ),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses `start..end` or a single row number.
+fn parse_row_range(value: &str) -> Result<std::ops::Range<DegreeType>, String> {
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<DegreeType>()
+            .map_err(|e| format!("invalid row {n}: {e}"))
+    };
+    match value.split_once("..") {
+        Some((start, end)) => Ok(parse(start)?..parse(end)?),
+        None => {
+            let row = parse(value)?;
+            Ok(row..row + 1)
+        }
+    }
+}
+
+/// Prints the result of `pil`, `prove` or `verify` unless the output format is human,
+/// in which case the logs already describe it.
+#[allow(clippy::print_stdout)]
+fn print_result(result: &impl report::Report, output_format: OutputFormat) {
+    if output_format != OutputFormat::Human {
+        print!("{}", report::render(result, output_format));
+    }
+}
+
+#[allow(clippy::print_stdout)]
+fn print_effective_config(plan: &ExecutionPlan, output_format: OutputFormat) {
+    print!("{}", report::render(&plan.to_config(), output_format));
+}
+
+#[allow(clippy::print_stdout)]
+fn gen_test_vectors(output: Option<String>) {
+    let vectors = number::test_vectors::generate_test_vectors();
+    match output {
+        Some(output) => {
+            fs::write(&output, vectors).unwrap();
+            log::info!("Wrote {output}.");
+        }
+        None => print!("{vectors}"),
+    }
+}
+
+fn serialize_result_witness<T: FieldElement>(
+    output_dir: &Path,
+    results: &CompilationResult<T>,
+    cancellation: &CancellationToken,
+) -> Result<Vec<PathBuf>, Cancelled> {
+    let mut written = vec![write_constants_to_fs(
+        &results.constants,
+        output_dir,
+        cancellation,
+    )?];
+    if let Some(witness) = &results.witness {
+        written.push(write_commits_to_fs(witness, output_dir, cancellation)?);
+    }
+    if let Some(pil) = &results.pil {
+        let artifacts = Artifacts {
+            pil_hash: pil.hash.clone(),
+        };
+        write_atomically(
+            &output_dir.join(ARTIFACTS_FILE_NAME),
+            cancellation,
+            |writer| artifacts.write(writer),
+        )?;
+    }
+    Ok(written)
+}
+
+fn write_range_constraints_to_fs(
+    range_constraints: &[ColumnRangeConstraint],
+    output_dir: &Path,
+    cancellation: &CancellationToken,
+) -> Result<PathBuf, Cancelled> {
+    let report = report::RangeConstraintsReport::from(range_constraints.to_vec());
+    let to_write = output_dir.join("range_constraints.json");
+    write_atomically(&to_write, cancellation, |writer| {
+        writer
+            .write_all(report::render(&report, OutputFormat::Json).as_bytes())
+            .unwrap()
+    })?;
+    log::info!("Wrote {}.", to_write.display());
+    Ok(to_write)
+}
+
+fn write_constants_to_fs<T: FieldElement>(
+    constants: &[(String, Vec<T>)],
+    output_dir: &Path,
+    cancellation: &CancellationToken,
+) -> Result<PathBuf, Cancelled> {
+    let to_write = output_dir.join("constants.bin");
+    write_atomically(&to_write, cancellation, |writer| {
+        write_polys_file(writer, constants)
+    })?;
     log::info!("Wrote {}.", to_write.display());
+    write_atomically(&output_dir.join(FIELD_FILE_NAME), cancellation, |writer| {
+        writeln!(writer, "{}", FieldArgument::of::<T>()).unwrap()
+    })?;
+    Ok(to_write)
+}
+
+/// The file next to `constants.bin` that records the field the values were generated for.
+const FIELD_FILE_NAME: &str = "field.txt";
+
+/// @returns the field recorded in the directory, if any.
+fn read_field(dir: &Path) -> Result<Option<FieldArgument>, String> {
+    let path = dir.join(FIELD_FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let field =
+        fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    field
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("Invalid field `{}` in {}.", field.trim(), path.display()))
 }
 
-fn write_commits_to_fs<T: FieldElement>(commits: &[(String, Vec<T>)], output_dir: &Path) {
+/// Resolves the field to use with the values in the directory: the given field, which
+/// has to match the recorded one, or else the recorded field, or else gl.
+fn resolve_field(field: Option<FieldArgument>, dir: &Path) -> Result<FieldArgument, String> {
+    match (field, read_field(dir)?) {
+        (Some(field), Some(recorded)) if field != recorded => Err(format!(
+            "The values in {} were generated for the field {recorded}, not {field}.",
+            dir.display()
+        )),
+        (Some(field), _) | (None, Some(field)) => Ok(field),
+        (None, None) => Ok(FieldArgument::Gl),
+    }
+}
+
+fn write_commits_to_fs<T: FieldElement>(
+    commits: &[(String, Vec<T>)],
+    output_dir: &Path,
+    cancellation: &CancellationToken,
+) -> Result<PathBuf, Cancelled> {
     let to_write = output_dir.join("commits.bin");
-    write_polys_file(
-        &mut BufWriter::new(&mut fs::File::create(&to_write).unwrap()),
-        commits,
-    );
+    write_atomically(&to_write, cancellation, |writer| {
+        write_polys_file(writer, commits)
+    })?;
     log::info!("Wrote {}.", to_write.display());
+    Ok(to_write)
 }
 
-fn write_proving_results_to_fs(
+/// Writes the proof with its metadata and the publics. Unless `raw_proof` is set,
+/// the proof is written as a [ProofFile].
+/// @returns the paths of the written files.
+#[allow(clippy::too_many_arguments)]
+fn write_proving_results_to_fs<T: FieldElement>(
+    backend: &BackendType,
+    raw_proof: bool,
     is_aggregation: bool,
-    proof: &Option<Proof>,
+    proof: &Option<(Proof, ProofMetadata)>,
+    publics: &[(String, T)],
     constraints_serialization: &Option<String>,
     output_dir: &Path,
-) {
+    cancellation: &CancellationToken,
+) -> Result<Vec<PathBuf>, Cancelled> {
+    let mut written = vec![];
     match proof {
-        Some(proof) => {
+        Some((proof, metadata)) => {
             let fname = if is_aggregation {
                 "proof_aggr.bin"
             } else {
                 "proof.bin"
             };
 
-            // No need to bufferize the writing, because we write the whole
-            // proof in one call.
             let to_write = output_dir.join(fname);
-            let mut proof_file = fs::File::create(&to_write).unwrap();
-            proof_file.write_all(proof).unwrap();
+            write_atomically(&to_write, cancellation, |writer| {
+                if raw_proof {
+                    writer.write_all(proof).unwrap()
+                } else {
+                    ProofFile::new::<T>(backend, metadata.pil_hash.clone(), proof.clone())
+                        .write(writer)
+                        .unwrap()
+                }
+            })?;
+            log::info!("Wrote {}.", to_write.display());
+            written.push(to_write);
+
+            let to_write = output_dir.join(ProofMetadata::file_name(fname));
+            write_atomically(&to_write, cancellation, |writer| {
+                serde_json::to_writer_pretty(writer, metadata).unwrap()
+            })?;
             log::info!("Wrote {}.", to_write.display());
+            written.push(to_write);
+
+            if !publics.is_empty() {
+                let to_write = output_dir.join(PUBLICS_FILE_NAME);
+                write_atomically(&to_write, cancellation, |writer| {
+                    write_publics(writer, publics)
+                })?;
+                log::info!("Wrote {}.", to_write.display());
+                written.push(to_write);
+            }
         }
         None => log::warn!("No proof was generated"),
     }
@@ -952,18 +2818,52 @@ fn write_proving_results_to_fs(
     match constraints_serialization {
         Some(json) => {
             let to_write = output_dir.join("constraints.json");
-            let mut file = fs::File::create(&to_write).unwrap();
-            file.write_all(json.as_bytes()).unwrap();
+            write_atomically(&to_write, cancellation, |writer| {
+                writer.write_all(json.as_bytes()).unwrap()
+            })?;
             log::info!("Wrote {}.", to_write.display());
+            written.push(to_write);
         }
         None => log::warn!("Constraints were not JSON serialized"),
     }
+    Ok(written)
+}
+
+/// The file the values of the public declarations are written to, next to the proof.
+const PUBLICS_FILE_NAME: &str = "publics.json";
+
+/// Writes the values of the public declarations as a JSON array of numbers as strings,
+/// which is also the format the pil-stark verifier expects.
+fn write_publics<T: FieldElement>(writer: &mut impl Write, publics: &[(String, T)]) {
+    let values = publics
+        .iter()
+        .map(|(_, value)| value.to_arbitrary_integer().to_string())
+        .collect::<Vec<_>>();
+    serde_json::to_writer(writer, &values).unwrap();
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{run_command, Commands, CsvRenderModeCLI, FieldArgument};
-    use backend::BackendType;
+    use crate::report::{self, OutputFormat, PilReport};
+    use crate::witgen_options::WitgenOptions;
+    use crate::{
+        compile_with_csv_export, language_of, parse_row_range, read_and_aggregate,
+        read_and_check_witness, read_and_verify, read_field, reformat, resolve_field,
+        row_inspector, run_command, source_files, Cli, Commands, CsvRenderModeCLI, FieldArgument,
+        Language,
+    };
+    use backend::{BackendType, ProofFile, ProofMetadata};
+    use clap::Parser;
+    use compiler::witness_transformer::{RowBlinding, WitnessTransformer};
+    use compiler::CancellationToken;
+    use compiler::Limits;
+    use compiler::{UnconstrainedWitnesses, DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE};
+    use executor::witgen::QueryCallback;
+    use number::{write_polys_csv_file, write_polys_file, CsvRenderMode, GoldilocksField};
+    use parser_util::provenance::Provenance;
+    use std::collections::{BTreeSet, HashMap};
+    use std::fs;
+    use std::path::Path;
 
     #[test]
     fn test_simple_sum() {
@@ -976,19 +2876,34 @@ mod test {
         );
         let pil_command = Commands::Pil {
             file,
-            field: FieldArgument::Bn254,
-            output_directory: output_dir_str.clone(),
+            field: Some(FieldArgument::Bn254),
+            output_directory: Some(output_dir_str.clone()),
             witness_values: None,
-            inputs: "3,2,1,2".into(),
+            inputs: vec!["3,2,1,2".into()],
+            inputs_file: None,
+            strict_inputs: false,
             force: false,
             prove_with: Some(BackendType::PilStarkCli),
             export_csv: true,
-            csv_mode: CsvRenderModeCLI::Hex,
+            csv_mode: Some(CsvRenderModeCLI::Hex),
+            csv_columns: None,
+            dump_range_constraints: false,
+            show_range_constraints: false,
             bname: Some("Example".into()),
+            degree: None,
             just_execute: false,
             continuations: false,
+            chunk_rows: None,
+            deny_unconstrained: false,
+            allow_unconstrained: None,
+            max_inlined_intermediate_size: None,
         };
-        run_command(pil_command);
+        run_command(
+            pil_command,
+            None,
+            OutputFormat::Human,
+            &CancellationToken::default(),
+        );
 
         #[cfg(feature = "halo2")]
         {
@@ -1000,12 +2915,754 @@ mod test {
             let prove_command = Commands::Prove {
                 file,
                 dir: output_dir_str,
-                field: FieldArgument::Bn254,
+                field: Some(FieldArgument::Bn254),
                 backend: BackendType::Halo2Mock,
                 proof: None,
                 params: None,
+                chunk_index: None,
+                export_verification_key: None,
+                raw_proof: false,
+                force: false,
             };
-            run_command(prove_command);
+            run_command(
+                prove_command,
+                None,
+                OutputFormat::Human,
+                &CancellationToken::default(),
+            );
         }
     }
+
+    #[test]
+    fn deny_unconstrained() {
+        let cli = Cli::try_parse_from([
+            "powdr",
+            "pil",
+            "--deny-unconstrained",
+            "--allow-unconstrained",
+            "main.x,main.y",
+            "a.pil",
+        ])
+        .unwrap();
+        let Some(Commands::Pil {
+            deny_unconstrained,
+            allow_unconstrained,
+            ..
+        }) = cli.command
+        else {
+            panic!()
+        };
+        assert!(deny_unconstrained);
+        assert_eq!(allow_unconstrained.as_deref(), Some("main.x,main.y"));
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let file = format!(
+            "{}/../test_data/pil/unconstrained_witness.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let compile = |unconstrained: &UnconstrainedWitnesses| {
+            compile_with_csv_export::<GoldilocksField>(
+                file.clone(),
+                output_dir.path().to_string_lossy().to_string(),
+                None,
+                HashMap::new(),
+                false,
+                None,
+                false,
+                CsvRenderModeCLI::Hex,
+                &[],
+                false,
+                false,
+                None,
+                None,
+                &Limits::default(),
+                unconstrained,
+                DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+                &CancellationToken::default(),
+            )
+        };
+        assert!(compile(&UnconstrainedWitnesses::default()).is_ok());
+        let Err(errors) = compile(&UnconstrainedWitnesses {
+            deny: true,
+            ..Default::default()
+        }) else {
+            panic!("Main.free should be rejected.");
+        };
+        assert_eq!(
+            errors,
+            vec!["Witness column Main.free is not referenced in any identity."]
+        );
+    }
+
+    #[test]
+    fn csv_column_filter() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let file = format!(
+            "{}/../test_data/asm/vm_to_block_to_block.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let export = |patterns: &[&str]| {
+            let patterns = patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+            compile_with_csv_export::<GoldilocksField>(
+                file.clone(),
+                output_dir.path().to_string_lossy().to_string(),
+                None,
+                HashMap::new(),
+                true,
+                None,
+                true,
+                CsvRenderModeCLI::Hex,
+                &patterns,
+                false,
+                false,
+                None,
+                None,
+                &Limits::default(),
+                &UnconstrainedWitnesses::default(),
+                DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+                &CancellationToken::default(),
+            )
+        };
+        let header = || {
+            fs::read_to_string(output_dir.path().join("columns.csv"))
+                .unwrap()
+                .lines()
+                .next()
+                .unwrap()
+                .to_string()
+        };
+
+        export(&[]).unwrap();
+        let all_columns = header();
+
+        // The columns are kept in source order, unknown patterns are only a warning.
+        export(&[
+            "*._block_enforcer_last_step",
+            "main.first_step",
+            "main.missing",
+        ])
+        .unwrap();
+        assert_eq!(
+            header(),
+            "Row,main.first_step,main._block_enforcer_last_step,\
+             main_assert1._block_enforcer_last_step,main_assert1_inc._block_enforcer_last_step"
+        );
+
+        let Err(errors) = export(&["main.missing"]) else {
+            panic!("No column should be selected.");
+        };
+        assert_eq!(
+            errors,
+            vec!["No column matches the CSV column patterns `main.missing`."]
+        );
+        export(&[]).unwrap();
+        assert_eq!(header(), all_columns);
+    }
+
+    #[test]
+    fn pil_json_output() {
+        let cli =
+            Cli::try_parse_from(["powdr", "pil", "--output-format", "json", "a.asm"]).unwrap();
+        assert_eq!(cli.output_format, Some(OutputFormat::Json));
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let file = format!(
+            "{}/../test_data/asm/simple_sum.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let inputs = [3, 2, 1, 2].map(GoldilocksField::from).to_vec();
+        let result = compile_with_csv_export::<GoldilocksField>(
+            file,
+            output_dir.path().to_string_lossy().to_string(),
+            None,
+            HashMap::from([(0.into(), inputs)]),
+            false,
+            Some(BackendType::PilStarkCli),
+            true,
+            CsvRenderModeCLI::Hex,
+            &[],
+            false,
+            false,
+            None,
+            None,
+            &Limits::default(),
+            &UnconstrainedWitnesses::default(),
+            DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+            &CancellationToken::default(),
+        )
+        .unwrap();
+        let json = report::render(&result, OutputFormat::Json);
+        let result: PilReport = serde_json::from_str(&json).unwrap();
+
+        let names = result
+            .artifacts
+            .iter()
+            .map(|artifact| {
+                assert!(Path::new(artifact).exists(), "{artifact}");
+                Path::new(artifact).file_name().unwrap().to_str().unwrap()
+            })
+            .collect::<Vec<_>>();
+        for name in [
+            "simple_sum_opt.pil",
+            "constants.bin",
+            "constraints.json",
+            "fixed.csv",
+            "columns.csv",
+        ] {
+            assert!(names.contains(&name), "{name} missing in {names:?}");
+        }
+        assert_eq!(result.degree, Some(1024));
+        assert!(result.witness_columns.unwrap() > 0);
+        assert!(result.fixed_columns.unwrap() > 0);
+        assert_eq!(read_field(output_dir.path()), Ok(Some(FieldArgument::Gl)));
+    }
+
+    #[test]
+    fn field_of_values() {
+        let cli = Cli::try_parse_from(["powdr", "prove", "a.pil", "-b", "estark", "--field", "bb"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Prove {
+                field: Some(FieldArgument::Bn254),
+                ..
+            })
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(resolve_field(None, dir.path()), Ok(FieldArgument::Gl));
+        fs::write(dir.path().join("field.txt"), "bn254\n").unwrap();
+        assert_eq!(resolve_field(None, dir.path()), Ok(FieldArgument::Bn254));
+        assert_eq!(
+            resolve_field(Some(FieldArgument::Bn254), dir.path()),
+            Ok(FieldArgument::Bn254)
+        );
+        assert!(resolve_field(Some(FieldArgument::Gl), dir.path())
+            .unwrap_err()
+            .contains("generated for the field bn254, not gl"));
+    }
+
+    #[test]
+    fn verify_missing_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = format!(
+            "{}/../test_data/pil/fibonacci.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let result = read_and_verify::<GoldilocksField>(
+            Path::new(&file),
+            dir.path(),
+            &BackendType::EStark,
+            "proof.bin",
+            false,
+            None,
+            None,
+            None,
+            false,
+        );
+        assert!(matches!(result, Err(backend::Error::IO(_))));
+    }
+
+    #[test]
+    fn verify_stale_artifacts() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let file = format!(
+            "{}/../test_data/pil/fibonacci.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        // Writes constants.bin and artifacts.json.
+        compile_with_csv_export::<GoldilocksField>(
+            file.clone(),
+            output_dir.path().to_string_lossy().to_string(),
+            None,
+            HashMap::new(),
+            false,
+            None,
+            false,
+            CsvRenderModeCLI::Hex,
+            &[],
+            false,
+            false,
+            None,
+            None,
+            &Limits::default(),
+            &UnconstrainedWitnesses::default(),
+            DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+            &CancellationToken::default(),
+        )
+        .unwrap();
+        assert!(output_dir.path().join("artifacts.json").exists());
+
+        // The PIL file has been changed since the values were generated.
+        let changed = output_dir.path().join("changed.pil");
+        fs::write(
+            &changed,
+            fs::read_to_string(&file)
+                .unwrap()
+                .replace("(x' - y)", "(x' - y - 1)"),
+        )
+        .unwrap();
+
+        let verify = |file: &Path, force: bool| {
+            read_and_verify::<GoldilocksField>(
+                file,
+                output_dir.path(),
+                &BackendType::EStark,
+                "proof.bin",
+                false,
+                None,
+                None,
+                None,
+                force,
+            )
+        };
+        let stale = |file: &Path, force: bool| match verify(file, force) {
+            Err(backend::Error::IO(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+                assert!(e.to_string().contains("were generated for a different PIL"));
+                true
+            }
+            // There is no proof to verify.
+            Err(backend::Error::IO(e)) if e.kind() == std::io::ErrorKind::NotFound => false,
+            result => panic!("{result:?}"),
+        };
+        assert!(!stale(Path::new(&file), false));
+        assert!(stale(&changed, false));
+        assert!(!stale(&changed, true));
+
+        // A fixed column has been renamed, which is an error instead of a panic.
+        let renamed = output_dir.path().join("renamed.pil");
+        fs::write(
+            &renamed,
+            fs::read_to_string(&file).unwrap().replace("LAST", "END"),
+        )
+        .unwrap();
+        match verify(&renamed, true) {
+            Err(backend::Error::IO(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+                assert!(e.to_string().contains("constants.bin"), "{e}");
+                assert!(e.to_string().contains("Fibonacci.END"), "{e}");
+            }
+            result => panic!("{result:?}"),
+        }
+    }
+
+    #[test]
+    fn check_witness() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let file = format!(
+            "{}/../test_data/pil/fibonacci.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        // Writes constants.bin.
+        compile_with_csv_export::<GoldilocksField>(
+            file.clone(),
+            output_dir.path().to_string_lossy().to_string(),
+            None,
+            HashMap::new(),
+            false,
+            None,
+            false,
+            CsvRenderModeCLI::Hex,
+            &[],
+            false,
+            false,
+            None,
+            None,
+            &Limits::default(),
+            &UnconstrainedWitnesses::default(),
+            DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+            &CancellationToken::default(),
+        )
+        .unwrap();
+        let (mut x, mut y) = (
+            vec![GoldilocksField::from(1)],
+            vec![GoldilocksField::from(1)],
+        );
+        for i in 0..15 {
+            x.push(y[i]);
+            y.push(x[i] + y[i]);
+        }
+        let mut witness = vec![
+            ("Fibonacci.x".to_string(), x),
+            ("Fibonacci.y".to_string(), y),
+        ];
+        let commits = output_dir.path().join("commits.bin");
+        write_polys_file(&mut fs::File::create(&commits).unwrap(), &witness);
+        write_polys_csv_file(
+            &mut fs::File::create(output_dir.path().join("witness.csv")).unwrap(),
+            CsvRenderMode::Hex,
+            &witness,
+        )
+        .unwrap();
+
+        let check = |witness: Option<&str>| {
+            read_and_check_witness::<GoldilocksField>(
+                Path::new(&file),
+                output_dir.path(),
+                witness.map(|w| output_dir.path().join(w).to_string_lossy().to_string()),
+                &[],
+            )
+        };
+        assert_eq!(check(None), Ok(vec![]));
+        assert_eq!(check(Some("witness.csv")), Ok(vec![]));
+
+        // Blinding the last two rows of y breaks the transition constraints into them.
+        let blinding: Box<dyn WitnessTransformer<GoldilocksField>> = Box::new(RowBlinding {
+            columns: vec!["Fibonacci.y".to_string()],
+            rows: 2,
+            seed: 0,
+        });
+        let blinded_rows = read_and_check_witness::<GoldilocksField>(
+            Path::new(&file),
+            output_dir.path(),
+            None,
+            &[blinding],
+        )
+        .unwrap()
+        .iter()
+        .map(|v| v.row)
+        .collect::<BTreeSet<_>>();
+        assert_eq!(blinded_rows, [13, 14].into());
+
+        witness[1].1[5] += 1.into();
+        write_polys_file(&mut fs::File::create(&commits).unwrap(), &witness);
+        let violations = check(None).unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.identity.as_str(), v.row))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "((1 - Fibonacci.LAST) * (Fibonacci.x' - Fibonacci.y)) = 0;",
+                    5
+                ),
+                (
+                    "((1 - Fibonacci.LAST) * (Fibonacci.y' - (Fibonacci.x + Fibonacci.y))) = 0;",
+                    4
+                ),
+                (
+                    "((1 - Fibonacci.LAST) * (Fibonacci.y' - (Fibonacci.x + Fibonacci.y))) = 0;",
+                    5
+                ),
+            ]
+        );
+
+        fs::remove_file(&commits).unwrap();
+        assert!(check(None).unwrap_err().contains("commits.bin"));
+    }
+
+    #[test]
+    fn aggregate_checks_metadata() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let dir = output_dir.path();
+        let file = format!(
+            "{}/../test_data/pil/fibonacci.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        // Writes constants.bin.
+        compile_with_csv_export::<GoldilocksField>(
+            file.clone(),
+            dir.to_string_lossy().to_string(),
+            None,
+            HashMap::new(),
+            false,
+            None,
+            false,
+            CsvRenderModeCLI::Hex,
+            &[],
+            false,
+            false,
+            None,
+            None,
+            &Limits::default(),
+            &UnconstrainedWitnesses::default(),
+            DEFAULT_MAX_INLINED_INTERMEDIATE_SIZE,
+            &CancellationToken::default(),
+        )
+        .unwrap();
+        let pil = pilopt::optimize(compiler::analyze_pil::<GoldilocksField>(Path::new(&file)));
+        for (chunk, pil_hash) in [(0, None), (1, None), (3, None), (2, Some("00"))] {
+            let proof = vec![chunk as u8];
+            let mut metadata = ProofMetadata::new(&pil, &proof);
+            metadata.chunk_index = Some(chunk);
+            if let Some(pil_hash) = pil_hash {
+                metadata.pil_hash = pil_hash.to_string();
+            }
+            let mut proof_file = vec![];
+            ProofFile::new::<GoldilocksField>(
+                &BackendType::EStark,
+                backend::pil_hash(&pil),
+                proof.clone(),
+            )
+            .write(&mut proof_file)
+            .unwrap();
+            fs::write(dir.join(format!("proof_{chunk}.bin")), proof_file).unwrap();
+            fs::write(dir.join(format!("raw_proof_{chunk}.bin")), proof).unwrap();
+            fs::write(
+                dir.join(format!("raw_proof_{chunk}.meta.json")),
+                serde_json::to_vec(&metadata).unwrap(),
+            )
+            .unwrap();
+            fs::write(
+                dir.join(format!("proof_{chunk}.meta.json")),
+                serde_json::to_vec(&metadata).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let aggregate_with = |proofs: &[&str], backend: BackendType, raw_proof: bool| {
+            read_and_aggregate::<GoldilocksField>(
+                Path::new(&file),
+                dir,
+                &backend,
+                &proofs.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+                None,
+                raw_proof,
+                &CancellationToken::default(),
+            )
+            .map(|_| ())
+        };
+        let aggregate = |proofs: &[&str]| aggregate_with(proofs, BackendType::EStark, false);
+        assert_eq!(
+            aggregate(&["proof_0.bin", "proof_1.bin"]),
+            Err("the backend does not support proof aggregation".to_string())
+        );
+        assert_eq!(
+            aggregate_with(
+                &["raw_proof_0.bin", "raw_proof_1.bin"],
+                BackendType::EStark,
+                true
+            ),
+            Err("the backend does not support proof aggregation".to_string())
+        );
+        assert!(aggregate(&["raw_proof_0.bin", "raw_proof_1.bin"])
+            .unwrap_err()
+            .ends_with("raw_proof_0.bin: Not a powdr proof file, bare proofs have to be read as raw proofs."));
+        assert!(aggregate_with(
+            &["proof_0.bin", "proof_1.bin"],
+            BackendType::PilStarkCli,
+            false
+        )
+        .unwrap_err()
+        .ends_with("The proof was generated with the backend estark, not pil-stark-cli."));
+        assert!(aggregate(&["proof_1.bin", "proof_3.bin"])
+            .unwrap_err()
+            .contains("chunks have to be consecutive"));
+        assert!(aggregate(&["proof_2.bin", "proof_3.bin"])
+            .unwrap_err()
+            .starts_with("Proof 1 is for a different PIL"));
+        assert!(aggregate(&["proof_0.bin", "proof_4.bin"])
+            .unwrap_err()
+            .contains("proof_4.meta.json"));
+        assert!(!dir.join("proof_aggr.bin").exists());
+    }
+
+    #[test]
+    fn reformat_keeps_provenance_header() {
+        let header =
+            Provenance::new([("main.asm".to_string(), b"machine Main {}".as_slice())]).render();
+        let contents = format!("{header}namespace N(8);\n    col witness x;\n    x = x;\n");
+        let reformatted = reformat("test.pil", &contents, Language::Pil).unwrap();
+        assert!(reformatted.starts_with(&header), "{reformatted}");
+        assert_eq!(
+            reformat("test.pil", &reformatted, Language::Pil).unwrap(),
+            reformatted
+        );
+        assert_eq!(
+            Provenance::parse(&reformatted),
+            Provenance::parse(&contents)
+        );
+    }
+
+    #[test]
+    fn reformat_is_idempotent() {
+        let test_data = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_data");
+        let files = [test_data.join("asm"), test_data.join("pil")]
+            .iter()
+            .flat_map(|dir| source_files(dir))
+            .collect::<Vec<_>>();
+        assert!(files.len() > 50);
+        for file in files {
+            let name = file.to_string_lossy();
+            let contents = fs::read_to_string(&file).unwrap();
+            let language = language_of(&name);
+            let Ok(reformatted) = reformat(&name, &contents, language) else {
+                panic!("Could not parse {name}.");
+            };
+            assert_eq!(
+                reformat(&name, &reformatted, language).unwrap(),
+                reformatted,
+                "Reformatting {name} is not idempotent."
+            );
+        }
+    }
+
+    #[test]
+    fn debug_failing_row() {
+        let file = format!(
+            "{}/../test_data/pil/unsatisfiable_row.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let analyzed = compiler::analyze_pil::<GoldilocksField>(Path::new(&file));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let inspect = |patterns: &[&str]| {
+            let patterns = patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+            row_inspector::inspect_rows(
+                &analyzed,
+                &constants,
+                executor::witgen::unused_query_callback(),
+                None,
+                &patterns,
+                &WitgenOptions::default(),
+            )
+            .unwrap()
+        };
+
+        let report = inspect(&[]);
+        assert_eq!(report.rows, [3, 4, 5, 6]);
+        assert_eq!(report.columns.len(), 1);
+        assert_eq!(report.columns[0].column, "Fail.x");
+        assert_eq!(report.columns[0].values[2], Some("1".to_string()));
+        assert_eq!(report.identities.len(), 1);
+        assert!(report.identities[0]
+            .source
+            .ends_with("unsatisfiable_row.pil:8"));
+        let output = report::render(&report, OutputFormat::Human);
+        assert!(output.contains("Witness generation failed at row 5"));
+        assert!(output.contains("Fail.x"));
+        assert!(output.contains("(Fail.FIVE * Fail.x) = (Fail.FIVE * 2);"));
+
+        let report = inspect(&["Fail.y"]);
+        assert_eq!(report.columns.len(), 1);
+        assert_eq!(report.columns[0].column, "Fail.y");
+        assert_eq!(report.columns[0].values[2], Some("2".to_string()));
+
+        let cli = Cli::try_parse_from(["powdr", "debug", &file, "--columns", "Fail.*"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Debug { columns: Some(columns), .. }) if columns == "Fail.*"
+        ));
+    }
+
+    #[test]
+    fn debug_memory_budget() {
+        let file = format!(
+            "{}/../test_data/pil/memory_budget.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let cli =
+            Cli::try_parse_from(["powdr", "debug", &file, "--witgen-memory-budget", "4"]).unwrap();
+        let Some(Commands::Debug {
+            witgen_memory_budget,
+            ..
+        }) = cli.command
+        else {
+            panic!("Expected the debug command");
+        };
+        assert_eq!(witgen_memory_budget, Some(4 << 30));
+        let too_large = (u64::MAX >> 29).to_string();
+        assert!(Cli::try_parse_from([
+            "powdr",
+            "debug",
+            &file,
+            "--witgen-memory-budget",
+            &too_large
+        ])
+        .is_err());
+
+        let analyzed = compiler::analyze_pil::<GoldilocksField>(Path::new(&file));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let report = row_inspector::inspect_rows(
+            &analyzed,
+            &constants,
+            executor::witgen::unused_query_callback(),
+            None,
+            &[],
+            &WitgenOptions {
+                // Far below a GB, so that witness generation runs out of memory.
+                memory_budget: Some(100_000),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(report
+            .failure
+            .unwrap()
+            .contains("at row 999 (memory budget exceeded)"));
+    }
+
+    #[test]
+    fn debug_record_and_replay_queries() {
+        let file = format!(
+            "{}/../test_data/pil/witness_lookup.pil",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let temp_dir = tempfile::tempdir().unwrap();
+        let recording = temp_dir.path().join("queries.jsonl");
+        let profile = temp_dir.path().join("witgen_report.json");
+        let cli = Cli::try_parse_from([
+            "powdr",
+            "debug",
+            &file,
+            "--record-queries",
+            recording.to_str().unwrap(),
+            "--witgen-profile",
+            profile.to_str().unwrap(),
+        ])
+        .unwrap();
+        let Some(Commands::Debug {
+            record_queries,
+            witgen_profile,
+            ..
+        }) = cli.command
+        else {
+            panic!("Expected the debug command");
+        };
+
+        let analyzed = compiler::analyze_pil::<GoldilocksField>(Path::new(&file));
+        let constants = executor::constant_evaluator::generate(&analyzed);
+        let inspect = |query_callback: Box<dyn QueryCallback<GoldilocksField>>,
+                       witgen_options: WitgenOptions| {
+            let report = row_inspector::inspect_rows(
+                &analyzed,
+                &constants,
+                query_callback,
+                Some(3),
+                &[],
+                &witgen_options,
+            )
+            .unwrap();
+            report::render(&report, OutputFormat::Json)
+        };
+        let inputs = (0..16u64).map(|i| (i % 8).into()).collect();
+        let recorded = inspect(
+            Box::new(compiler::inputs_to_query_callback(inputs)),
+            WitgenOptions {
+                record_queries: record_queries.map(Into::into),
+                profile: witgen_profile.map(Into::into),
+                ..Default::default()
+            },
+        );
+        assert!(!executor::witgen::profiling::WitgenReport::read(&profile)
+            .unwrap()
+            .identities
+            .is_empty());
+
+        let replayed = inspect(
+            Box::new(executor::witgen::unused_query_callback()),
+            WitgenOptions {
+                replay_queries: Some(recording),
+                ..Default::default()
+            },
+        );
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    fn row_ranges() {
+        assert_eq!(parse_row_range("5..11"), Ok(5..11));
+        assert_eq!(parse_row_range("7"), Ok(7..8));
+        assert!(parse_row_range("5..x").is_err());
+    }
 }
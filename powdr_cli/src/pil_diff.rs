@@ -0,0 +1,622 @@
+//! Semantic diff of two analyzed PIL files for `diff-pil`.
+//!
+//! Columns are matched by name, so a pure renumbering of the columns is not a
+//! change. Columns that only exist in one of the files are matched by the structural
+//! fingerprints of the identities they appear in (with the names of all columns
+//! abstracted), so that a renamed column is reported as a rename and not as a
+//! removal and an addition. Identities are compared by fingerprint after applying
+//! the renames, i.e. modulo simplification and the order of commutative operands.
+
+use std::collections::{BTreeSet, HashMap};
+
+use ast::analyzed::{
+    AlgebraicExpression, AlgebraicReference, Analyzed, Identity, PolyID, PublicDeclaration,
+};
+use ast::parsed::visitor::ExpressionVisitable;
+use number::FieldElement;
+use pilopt::fingerprint::{expression_fingerprint, identity_fingerprint, Fingerprint};
+use serde::Serialize;
+use strum::Display;
+
+use crate::report::Report;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Item {
+    Degree,
+    Column,
+    Identity,
+    Public,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Renamed,
+    Modified,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub item: Item,
+    pub change: ChangeKind,
+    /// The item in the first file, `None` if it was added.
+    pub before: Option<String>,
+    /// The item in the second file, `None` if it was removed.
+    pub after: Option<String>,
+}
+
+/// The semantic differences between two PIL files, in the order degree,
+/// columns, identities and public declarations.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PilDiff {
+    pub changes: Vec<Change>,
+}
+
+impl Report for PilDiff {
+    fn headers(&self) -> Vec<String> {
+        ["item", "change", "before", "after"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.changes
+            .iter()
+            .map(|c| {
+                vec![
+                    c.item.to_string(),
+                    c.change.to_string(),
+                    c.before.clone().unwrap_or_default(),
+                    c.after.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    /// One `-` line per removed and one `+` line per added item, since identities
+    /// are too long for a table. Renames and modifications are introduced by a line
+    /// naming the change.
+    fn render_human(&self) -> String {
+        if self.changes.is_empty() {
+            return "No differences.\n".to_string();
+        }
+        self.changes
+            .iter()
+            .map(|c| match (&c.before, &c.after) {
+                (Some(before), Some(after)) => {
+                    format!("{} {}:\n-{before}\n+{after}\n", c.change, c.item)
+                }
+                (Some(before), None) => format!("-{}: {before}\n", c.item),
+                (None, Some(after)) => format!("+{}: {after}\n", c.item),
+                (None, None) => unreachable!(),
+            })
+            .collect()
+    }
+}
+
+pub fn diff_pil<T: FieldElement>(a: &Analyzed<T>, b: &Analyzed<T>) -> PilDiff {
+    let mut changes = vec![];
+    if a.degree != b.degree {
+        changes.push(Change {
+            item: Item::Degree,
+            change: ChangeKind::Modified,
+            before: a.degree.map(|d| d.to_string()),
+            after: b.degree.map(|d| d.to_string()),
+        });
+    }
+
+    let columns_a = Columns::new(a);
+    let columns_b = Columns::new(b);
+    let renames = diff_columns(&columns_a, &columns_b, &mut changes);
+    let name_in_b = |name: &str| {
+        renames
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    };
+    let key_a = |r: &AlgebraicReference| name_in_b(&columns_a.name(r));
+    let key_b = |r: &AlgebraicReference| columns_b.name(r);
+
+    diff_identities(a, b, &key_a, &key_b, &mut changes);
+    diff_publics(a, b, &name_in_b, &mut changes);
+    PilDiff { changes }
+}
+
+/// The columns (or array elements) of a PIL file in source order.
+struct Columns<'a, T> {
+    pil: &'a Analyzed<T>,
+    columns: Vec<Column>,
+    names: HashMap<PolyID, String>,
+}
+
+struct Column {
+    name: String,
+    poly_id: PolyID,
+    /// The definition of a fixed column, or the fingerprint of the definition
+    /// of an intermediate column.
+    definition: Option<String>,
+    /// The definition as it appears in the file.
+    source: Option<String>,
+}
+
+impl<'a, T: FieldElement> Columns<'a, T> {
+    fn new(pil: &'a Analyzed<T>) -> Self {
+        let committed_and_fixed = pil
+            .committed_polys_in_source_order()
+            .into_iter()
+            .chain(pil.constant_polys_in_source_order())
+            .flat_map(|(symbol, definition)| {
+                symbol.array_elements().map(move |(name, poly_id)| Column {
+                    name,
+                    poly_id,
+                    definition: definition.as_ref().map(|d| d.to_string()),
+                    source: definition.as_ref().map(|d| d.to_string()),
+                })
+            });
+        let intermediate = pil
+            .intermediate_polys_in_source_order()
+            .into_iter()
+            .flat_map(|(symbol, definition)| {
+                symbol.array_elements().map(move |(name, poly_id)| Column {
+                    name,
+                    poly_id,
+                    definition: Some(
+                        expression_fingerprint(definition, &|r| abstract_key(r, poly_id))
+                            .to_string(),
+                    ),
+                    source: Some(format!(" = {definition}")),
+                })
+            });
+        let columns = committed_and_fixed.chain(intermediate).collect::<Vec<_>>();
+        let names = columns
+            .iter()
+            .map(|c| (c.poly_id, c.name.clone()))
+            .collect();
+        Columns {
+            pil,
+            columns,
+            names,
+        }
+    }
+
+    fn name(&self, reference: &AlgebraicReference) -> String {
+        self.names
+            .get(&reference.poly_id)
+            .cloned()
+            .unwrap_or_else(|| reference.name.clone())
+    }
+
+    fn find(&self, column: &Column) -> Option<&Column> {
+        self.columns
+            .iter()
+            .find(|c| c.name == column.name && c.poly_id.ptype == column.poly_id.ptype)
+    }
+
+    fn contains_name(&self, column: &Column) -> bool {
+        self.find(column).is_some()
+    }
+
+    /// The structure of the constraints on a column, independent of its name and
+    /// the names of all other columns.
+    fn signature(&self, column: &Column) -> (String, Option<String>, Vec<Fingerprint>) {
+        let mut fingerprints = self
+            .pil
+            .identities
+            .iter()
+            .filter(|identity| references(identity).contains(&column.poly_id))
+            .map(|identity| identity_fingerprint(identity, &|r| abstract_key(r, column.poly_id)))
+            .collect::<Vec<_>>();
+        fingerprints.sort();
+        (
+            column.poly_id.ptype.to_string(),
+            column.definition.clone(),
+            fingerprints,
+        )
+    }
+}
+
+/// Replaces references to `column` by `$self` and all other references by their type.
+fn abstract_key(reference: &AlgebraicReference, column: PolyID) -> String {
+    if reference.poly_id == column {
+        "$self".to_string()
+    } else {
+        format!("${}", reference.poly_id.ptype)
+    }
+}
+
+fn references<T>(identity: &Identity<AlgebraicExpression<T>>) -> BTreeSet<PolyID> {
+    let mut references = BTreeSet::new();
+    identity.pre_visit_expressions(&mut |e| {
+        if let AlgebraicExpression::Reference(reference) = e {
+            references.insert(reference.poly_id);
+        }
+    });
+    references
+}
+
+/// Reports added, removed, renamed and modified columns and returns the renames
+/// (from the name in `a` to the name in `b`).
+fn diff_columns<T: FieldElement>(
+    a: &Columns<T>,
+    b: &Columns<T>,
+    changes: &mut Vec<Change>,
+) -> HashMap<String, String> {
+    let removed = a
+        .columns
+        .iter()
+        .filter(|c| !b.contains_name(c))
+        .map(|c| (c, a.signature(c)))
+        .collect::<Vec<_>>();
+    let mut added = b
+        .columns
+        .iter()
+        .filter(|c| !a.contains_name(c))
+        .map(|c| Some((c, b.signature(c))))
+        .collect::<Vec<_>>();
+
+    let mut renames = vec![];
+    let mut removed_columns = vec![];
+    for (column, signature) in removed {
+        let renamed = added
+            .iter_mut()
+            .find(|candidate| matches!(candidate, Some((_, s)) if *s == signature))
+            .and_then(Option::take);
+        match renamed {
+            Some((new_column, _)) => renames.push((column.name.clone(), new_column.name.clone())),
+            None => removed_columns.push(column.name.clone()),
+        }
+    }
+    changes.extend(removed_columns.into_iter().map(|name| Change {
+        item: Item::Column,
+        change: ChangeKind::Removed,
+        before: Some(name),
+        after: None,
+    }));
+    changes.extend(added.into_iter().flatten().map(|(column, _)| Change {
+        item: Item::Column,
+        change: ChangeKind::Added,
+        before: None,
+        after: Some(column.name.clone()),
+    }));
+    changes.extend(renames.iter().map(|(from, to)| Change {
+        item: Item::Column,
+        change: ChangeKind::Renamed,
+        before: Some(from.clone()),
+        after: Some(to.clone()),
+    }));
+    let describe = |column: &Column| {
+        format!(
+            "{}{}",
+            column.name,
+            column.source.as_deref().unwrap_or_default()
+        )
+    };
+    changes.extend(a.columns.iter().filter_map(|before| {
+        let after = b.find(before)?;
+        (before.definition != after.definition).then(|| Change {
+            item: Item::Column,
+            change: ChangeKind::Modified,
+            before: Some(describe(before)),
+            after: Some(describe(after)),
+        })
+    }));
+    renames.into_iter().collect()
+}
+
+/// Reports added, removed and modified identities. A removed and an added identity
+/// of the same kind are reported as a modification if they share a column.
+fn diff_identities<T: FieldElement>(
+    a: &Analyzed<T>,
+    b: &Analyzed<T>,
+    key_a: &impl Fn(&AlgebraicReference) -> String,
+    key_b: &impl Fn(&AlgebraicReference) -> String,
+    changes: &mut Vec<Change>,
+) {
+    let mut unmatched_b: HashMap<Fingerprint, Vec<usize>> = HashMap::new();
+    for (i, identity) in b.identities.iter().enumerate().rev() {
+        unmatched_b
+            .entry(identity_fingerprint(identity, key_b))
+            .or_default()
+            .push(i);
+    }
+    let removed = a
+        .identities
+        .iter()
+        .filter(|identity| {
+            unmatched_b
+                .get_mut(&identity_fingerprint(identity, key_a))
+                .and_then(|indices| indices.pop())
+                .is_none()
+        })
+        .collect::<Vec<_>>();
+    let mut added = unmatched_b
+        .into_values()
+        .flatten()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|i| Some(&b.identities[i]))
+        .collect::<Vec<_>>();
+
+    let columns = |identity: &Identity<AlgebraicExpression<T>>,
+                   key: &dyn Fn(&AlgebraicReference) -> String| {
+        let mut columns = BTreeSet::new();
+        identity.pre_visit_expressions(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                columns.insert(key(reference));
+            }
+        });
+        columns
+    };
+    let mut removed_identities = vec![];
+    let mut modified = vec![];
+    for identity in removed {
+        let identity_columns = columns(identity, key_a);
+        let candidate = added
+            .iter_mut()
+            .filter(|candidate| matches!(candidate, Some(c) if c.kind == identity.kind))
+            .map(|candidate| {
+                let shared = columns(candidate.unwrap(), key_b)
+                    .intersection(&identity_columns)
+                    .count();
+                (shared, candidate)
+            })
+            .filter(|(shared, _)| *shared > 0)
+            // Prefer the first of the candidates sharing the most columns.
+            .reduce(|best, c| if c.0 > best.0 { c } else { best })
+            .and_then(|(_, candidate)| candidate.take());
+        match candidate {
+            Some(new_identity) => modified.push((identity, new_identity)),
+            None => removed_identities.push(identity),
+        }
+    }
+
+    changes.extend(removed_identities.into_iter().map(|identity| Change {
+        item: Item::Identity,
+        change: ChangeKind::Removed,
+        before: Some(identity.to_string()),
+        after: None,
+    }));
+    changes.extend(added.into_iter().flatten().map(|identity| Change {
+        item: Item::Identity,
+        change: ChangeKind::Added,
+        before: None,
+        after: Some(identity.to_string()),
+    }));
+    changes.extend(modified.into_iter().map(|(before, after)| Change {
+        item: Item::Identity,
+        change: ChangeKind::Modified,
+        before: Some(before.to_string()),
+        after: Some(after.to_string()),
+    }));
+}
+
+/// Reports added, removed and modified public declarations, matched by name.
+fn diff_publics<T>(
+    a: &Analyzed<T>,
+    b: &Analyzed<T>,
+    name_in_b: &impl Fn(&str) -> String,
+    changes: &mut Vec<Change>,
+) {
+    let describe = |public: &PublicDeclaration, name_of: &dyn Fn(&str) -> String| {
+        let index = public
+            .array_index
+            .map(|i| format!("[{i}]"))
+            .unwrap_or_default();
+        format!(
+            "{} = {}{index}({})",
+            public.name,
+            name_of(&public.polynomial.name),
+            public.index
+        )
+    };
+    let identity_name = |name: &str| name.to_string();
+    let mut names = a
+        .public_declarations
+        .keys()
+        .chain(b.public_declarations.keys())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let change = match (
+            a.public_declarations.get(name),
+            b.public_declarations.get(name),
+        ) {
+            (Some(before), Some(after)) => {
+                (describe(before, name_in_b) != describe(after, &identity_name)).then(|| Change {
+                    item: Item::Public,
+                    change: ChangeKind::Modified,
+                    before: Some(describe(before, &identity_name)),
+                    after: Some(describe(after, &identity_name)),
+                })
+            }
+            (Some(before), None) => Some(Change {
+                item: Item::Public,
+                change: ChangeKind::Removed,
+                before: Some(describe(before, &identity_name)),
+                after: None,
+            }),
+            (None, Some(after)) => Some(Change {
+                item: Item::Public,
+                change: ChangeKind::Added,
+                before: None,
+                after: Some(describe(after, &identity_name)),
+            }),
+            (None, None) => unreachable!(),
+        };
+        changes.extend(change);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pil_analyzer::pil_analyzer::process_pil_file_contents;
+
+    use super::*;
+    use crate::report::{render, OutputFormat};
+
+    const PIL: &str = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness y;
+    FIRST * (x - 1) = 0;
+    x' = x + y;
+    { y } in { FIRST };
+"#;
+
+    fn diff(a: &str, b: &str) -> PilDiff {
+        diff_pil(
+            &process_pil_file_contents::<GoldilocksField>(a),
+            &process_pil_file_contents::<GoldilocksField>(b),
+        )
+    }
+
+    #[test]
+    fn no_changes() {
+        let diff = diff(PIL, PIL);
+        assert_eq!(diff, PilDiff::default());
+        assert_eq!(render(&diff, OutputFormat::Human), "No differences.\n");
+    }
+
+    #[test]
+    fn rename_only() {
+        let renamed = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness acc;
+    col witness y;
+    (acc - 1) * FIRST = 0;
+    acc' = y + acc;
+    { y } in { FIRST };
+"#;
+        let diff = diff(PIL, renamed);
+        assert_eq!(
+            diff.changes,
+            vec![Change {
+                item: Item::Column,
+                change: ChangeKind::Renamed,
+                before: Some("N.x".to_string()),
+                after: Some("N.acc".to_string()),
+            }]
+        );
+        assert_eq!(
+            render(&diff, OutputFormat::Json),
+            r#"{
+  "changes": [
+    {
+      "item": "column",
+      "change": "renamed",
+      "before": "N.x",
+      "after": "N.acc"
+    }
+  ]
+}
+"#
+        );
+        assert_eq!(
+            render(&diff, OutputFormat::Human),
+            "renamed column:\n-N.x\n+N.acc\n"
+        );
+    }
+
+    #[test]
+    fn modification() {
+        let modified = r#"namespace N(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness y;
+    FIRST * (x - 1) = 0;
+    x' = x + 2 * y;
+    { y } in { FIRST };
+"#;
+        let diff = diff(PIL, modified);
+        let before = process_pil_file_contents::<GoldilocksField>(PIL).identities[1].to_string();
+        let after =
+            process_pil_file_contents::<GoldilocksField>(modified).identities[1].to_string();
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change {
+                    item: Item::Degree,
+                    change: ChangeKind::Modified,
+                    before: Some("8".to_string()),
+                    after: Some("16".to_string()),
+                },
+                Change {
+                    item: Item::Identity,
+                    change: ChangeKind::Modified,
+                    before: Some(before),
+                    after: Some(after),
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let changed = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness z;
+    FIRST * (x - 1) = 0;
+    x' = x;
+    z = 7;
+"#;
+        let changes = diff(PIL, changed)
+            .changes
+            .into_iter()
+            .map(|c| (c.item, c.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (Item::Column, ChangeKind::Removed),
+                (Item::Column, ChangeKind::Added),
+                (Item::Identity, ChangeKind::Removed),
+                (Item::Identity, ChangeKind::Added),
+                (Item::Identity, ChangeKind::Modified),
+            ]
+        );
+    }
+
+    #[test]
+    fn optimized() {
+        let file = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../test_data/pil/simple_div.pil");
+        let pil = pil_analyzer::analyze::<GoldilocksField>(&file);
+        let optimized = pilopt::optimize(pil_analyzer::analyze::<GoldilocksField>(&file));
+        let diff = diff_pil(&pil, &optimized);
+        let removed = |item| {
+            diff.changes
+                .iter()
+                .filter(|c| c.item == item && c.change == ChangeKind::Removed)
+                .map(|c| c.before.clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(removed(Item::Column), ["SimpleDiv.Y", "SimpleDiv.Z"]);
+        assert_eq!(
+            removed(Item::Identity),
+            ["SimpleDiv.Z = 3;", "SimpleDiv.Y = 13;"]
+        );
+        // The identities using the removed columns are modified, all others are unchanged.
+        assert_eq!(diff.changes.len(), 6);
+        assert!(render(&diff, OutputFormat::Human).starts_with(
+            "-column: SimpleDiv.Y\n-column: SimpleDiv.Z\n-identity: SimpleDiv.Z = 3;\n"
+        ));
+    }
+
+    #[test]
+    fn modified_column() {
+        let changed = PIL.replace("[1] + [0]*", "[1, 1] + [0]*");
+        let diff = diff(PIL, &changed);
+        assert_eq!(
+            render(&diff, OutputFormat::Human),
+            "modified column:\n-N.FIRST = [1] + [0]*\n+N.FIRST = [1, 1] + [0]*\n"
+        );
+    }
+}
@@ -0,0 +1,763 @@
+//! Shared output formats for the inspection commands and the results of `pil`,
+//! `prove` and `verify`.
+//!
+//! A report is a typed struct that can be serialized to JSON and rendered as a table.
+//! The human-readable form defaults to an aligned table, but reports that already
+//! have an established format (like the TOML of `config print-effective`) can keep it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use ast::analyzed::{ColumnDependencyGraph, PolyID, Stats};
+use executor::witgen::{ColumnRangeConstraint, DerivationStep};
+use executor::witness_checker::Violation;
+use number::DegreeType;
+use parser_util::provenance::Provenance;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString, EnumVariantNames};
+
+use crate::config::Config;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, EnumVariantNames, Display)]
+pub enum OutputFormat {
+    #[default]
+    #[strum(serialize = "human")]
+    Human,
+    #[strum(serialize = "json")]
+    Json,
+    #[strum(serialize = "csv")]
+    Csv,
+}
+
+pub trait Report: Serialize {
+    /// The column headers of the table form of the report.
+    fn headers(&self) -> Vec<String>;
+
+    /// The rows of the table form of the report.
+    fn rows(&self) -> Vec<Vec<String>>;
+
+    /// The human-readable form of the report.
+    fn render_human(&self) -> String {
+        render_table(&self.headers(), &self.rows())
+    }
+}
+
+/// Renders `report` in the given format. The result ends with a newline.
+pub fn render(report: &impl Report, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Human => report.render_human(),
+        OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(report).unwrap()),
+        OutputFormat::Csv => std::iter::once(report.headers())
+            .chain(report.rows())
+            .map(|row| {
+                let fields = row.iter().map(|field| csv_field(field)).collect::<Vec<_>>();
+                format!("{}\n", fields.join(","))
+            })
+            .collect(),
+    }
+}
+
+/// Renders a table with left-aligned columns separated by two spaces.
+fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let widths = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header.chars().count()])
+                .max()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let render_row = |row: &[String]| {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(field, width)| format!("{field:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
+    std::iter::once(render_row(headers))
+        .chain(rows.iter().map(|row| render_row(row)))
+        .collect()
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The effective configuration of `config print-effective`.
+impl Report for Config {
+    fn headers(&self) -> Vec<String> {
+        vec!["key".to_string(), "value".to_string()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let toml::Value::Table(table) = toml::Value::try_from(self).unwrap() else {
+            unreachable!()
+        };
+        table
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    toml::Value::String(s) => s,
+                    value => value.to_string(),
+                };
+                vec![key, value]
+            })
+            .collect()
+    }
+
+    fn render_human(&self) -> String {
+        toml::to_string(self).unwrap()
+    }
+}
+
+/// The provenance header of a generated file, for `inspect`.
+#[derive(Serialize)]
+pub struct ProvenanceReport {
+    version: String,
+    inputs: Vec<ProvenanceInput>,
+    coprocessors: Option<Vec<String>>,
+    generated: String,
+}
+
+#[derive(Serialize)]
+struct ProvenanceInput {
+    file: String,
+    sha256: String,
+}
+
+impl From<Provenance> for ProvenanceReport {
+    fn from(provenance: Provenance) -> Self {
+        ProvenanceReport {
+            version: provenance.version,
+            inputs: provenance
+                .inputs
+                .into_iter()
+                .map(|(file, sha256)| ProvenanceInput { file, sha256 })
+                .collect(),
+            coprocessors: provenance.coprocessors,
+            generated: provenance.generated,
+        }
+    }
+}
+
+impl Report for ProvenanceReport {
+    fn headers(&self) -> Vec<String> {
+        vec!["key".to_string(), "value".to_string()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let row = |key: &str, value: String| vec![key.to_string(), value];
+        std::iter::once(row("version", self.version.clone()))
+            .chain(
+                self.inputs
+                    .iter()
+                    .map(|input| row("input", format!("{} sha256:{}", input.file, input.sha256))),
+            )
+            .chain(
+                self.coprocessors
+                    .as_ref()
+                    .map(|coprocessors| row("coprocessors", coprocessors.join(", "))),
+            )
+            .chain([row("generated", self.generated.clone())])
+            .collect()
+    }
+}
+
+/// The values of a fixed column on some rows, for `eval-fixed`.
+#[derive(Serialize)]
+pub struct FixedColumnReport {
+    column: String,
+    rows: Vec<FixedColumnRow>,
+}
+
+#[derive(Serialize)]
+struct FixedColumnRow {
+    row: u64,
+    value: String,
+}
+
+impl FixedColumnReport {
+    pub fn new(column: &str, first_row: u64, values: &[impl Display]) -> Self {
+        FixedColumnReport {
+            column: column.to_string(),
+            rows: (first_row..)
+                .zip(values)
+                .map(|(row, value)| FixedColumnRow {
+                    row,
+                    value: value.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Report for FixedColumnReport {
+    fn headers(&self) -> Vec<String> {
+        vec!["row".to_string(), self.column.clone()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .map(|row| vec![row.row.to_string(), row.value.clone()])
+            .collect()
+    }
+}
+
+/// The global range constraints witness generation derives, for `--dump-range-constraints`.
+#[derive(Serialize)]
+pub struct RangeConstraintsReport {
+    columns: Vec<ColumnRangeConstraint>,
+}
+
+impl From<Vec<ColumnRangeConstraint>> for RangeConstraintsReport {
+    fn from(columns: Vec<ColumnRangeConstraint>) -> Self {
+        RangeConstraintsReport { columns }
+    }
+}
+
+impl Report for RangeConstraintsReport {
+    fn headers(&self) -> Vec<String> {
+        ["column", "constraint", "values", "bits", "derived from"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.columns
+            .iter()
+            .map(|c| {
+                let derivation = c
+                    .derivation
+                    .iter()
+                    .map(|step| match step {
+                        DerivationStep::FixedColumnValues => "fixed column values".to_string(),
+                        DerivationStep::Annotation => "annotation".to_string(),
+                        DerivationStep::Identity { identity, .. } => identity.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                vec![
+                    c.column.clone(),
+                    c.constraint.clone(),
+                    c.interval.clone(),
+                    c.bits.to_string(),
+                    derivation.join(" "),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// The result of `pil`: the written files and the size of the optimized PIL.
+/// The sizes are missing if the PIL has not been (re-)generated.
+#[derive(Serialize, Deserialize)]
+pub struct PilReport {
+    pub artifacts: Vec<String>,
+    pub degree: Option<DegreeType>,
+    pub witness_columns: Option<usize>,
+    pub fixed_columns: Option<usize>,
+}
+
+impl Report for PilReport {
+    fn headers(&self) -> Vec<String> {
+        vec!["key".to_string(), "value".to_string()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let row =
+            |key: &str, value: Option<String>| vec![key.to_string(), value.unwrap_or_default()];
+        self.artifacts
+            .iter()
+            .map(|artifact| row("artifact", Some(artifact.clone())))
+            .chain([
+                row("degree", self.degree.map(|d| d.to_string())),
+                row(
+                    "witness columns",
+                    self.witness_columns.map(|c| c.to_string()),
+                ),
+                row("fixed columns", self.fixed_columns.map(|c| c.to_string())),
+            ])
+            .collect()
+    }
+}
+
+/// The result of `prove`, the proof is missing if the backend did not generate one.
+#[derive(Serialize, Deserialize)]
+pub struct ProveReport {
+    pub proof: Option<String>,
+    pub size_bytes: Option<usize>,
+}
+
+impl Report for ProveReport {
+    fn headers(&self) -> Vec<String> {
+        vec!["proof".to_string(), "size (bytes)".to_string()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.proof.clone().unwrap_or_default(),
+            self.size_bytes.map(|s| s.to_string()).unwrap_or_default(),
+        ]]
+    }
+}
+
+/// The result of `verify`.
+#[derive(Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub valid: bool,
+}
+
+impl Report for VerifyReport {
+    fn headers(&self) -> Vec<String> {
+        vec!["valid".to_string()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.valid.to_string()]]
+    }
+}
+
+/// The rows on which identities do not hold, for `check-witness`.
+#[derive(Serialize)]
+pub struct WitnessViolationReport {
+    violations: Vec<WitnessViolation>,
+}
+
+#[derive(Serialize)]
+struct WitnessViolation {
+    source: String,
+    identity: String,
+    row: DegreeType,
+    left: Vec<String>,
+    right: Vec<String>,
+}
+
+impl<T: Display> From<&[Violation<T>]> for WitnessViolationReport {
+    fn from(violations: &[Violation<T>]) -> Self {
+        let values = |values: &[T]| values.iter().map(|v| v.to_string()).collect();
+        WitnessViolationReport {
+            violations: violations
+                .iter()
+                .map(|v| WitnessViolation {
+                    source: v.source.clone(),
+                    identity: v.identity.clone(),
+                    row: v.row,
+                    left: values(&v.left),
+                    right: values(&v.right),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Report for WitnessViolationReport {
+    fn headers(&self) -> Vec<String> {
+        ["source", "identity", "row", "left", "right"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.violations
+            .iter()
+            .map(|v| {
+                vec![
+                    v.source.clone(),
+                    v.identity.clone(),
+                    v.row.to_string(),
+                    v.left.join(" "),
+                    v.right.join(" "),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// The values of witness columns on the rows around the row where witness generation
+/// fails (or a given row), together with the identities involved, for `debug`.
+#[derive(Serialize)]
+pub struct RowInspectionReport {
+    /// The failure, if witness generation failed.
+    pub failure: Option<String>,
+    pub rows: Vec<DegreeType>,
+    pub columns: Vec<InspectedColumn>,
+    pub identities: Vec<InspectedIdentity>,
+}
+
+#[derive(Serialize)]
+pub struct InspectedColumn {
+    pub column: String,
+    /// The values on the inspected rows, `None` if witness generation has not
+    /// determined the value.
+    pub values: Vec<Option<String>>,
+}
+
+#[derive(Serialize)]
+pub struct InspectedIdentity {
+    pub source: String,
+    pub identity: String,
+}
+
+impl Report for RowInspectionReport {
+    fn headers(&self) -> Vec<String> {
+        std::iter::once("column".to_string())
+            .chain(self.rows.iter().map(|row| format!("row {row}")))
+            .collect()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.columns
+            .iter()
+            .map(|c| {
+                std::iter::once(c.column.clone())
+                    .chain(
+                        c.values
+                            .iter()
+                            .map(|v| v.clone().unwrap_or_else(|| "?".to_string())),
+                    )
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn render_human(&self) -> String {
+        let failure = self
+            .failure
+            .as_ref()
+            .map(|failure| format!("{failure}\n\n"))
+            .unwrap_or_default();
+        let identities = self
+            .identities
+            .iter()
+            .map(|i| vec![i.source.clone(), i.identity.clone()])
+            .collect::<Vec<_>>();
+        format!(
+            "{failure}{}\n{}",
+            render_table(&self.headers(), &self.rows()),
+            render_table(&["source".to_string(), "identity".to_string()], &identities)
+        )
+    }
+}
+
+/// The columns with the identities they occur in and the columns they occur together
+/// with, for `analyze`.
+#[derive(Serialize)]
+pub struct ColumnDependencyReport {
+    columns: Vec<ColumnDependencyEntry>,
+}
+
+#[derive(Serialize)]
+struct ColumnDependencyEntry {
+    column: String,
+    kind: String,
+    namespace: String,
+    identities: Vec<usize>,
+    same_row: Vec<String>,
+    next_row: Vec<String>,
+}
+
+impl From<&ColumnDependencyGraph> for ColumnDependencyReport {
+    fn from(graph: &ColumnDependencyGraph) -> Self {
+        let names = |poly_ids: &BTreeSet<PolyID>| {
+            poly_ids
+                .iter()
+                .map(|poly_id| graph.columns[poly_id].name.clone())
+                .collect()
+        };
+        ColumnDependencyReport {
+            columns: graph
+                .columns
+                .iter()
+                .map(|(poly_id, dependencies)| ColumnDependencyEntry {
+                    column: dependencies.name.clone(),
+                    kind: poly_id.ptype.to_string(),
+                    namespace: dependencies.namespace.clone(),
+                    identities: dependencies.identities.iter().cloned().collect(),
+                    same_row: names(&dependencies.same_row),
+                    next_row: names(&dependencies.next_row),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ColumnDependencyReport {
+    /// Renders the graph in the DOT format of Graphviz, with a cluster per namespace.
+    /// Columns occurring in the same row are joined by an undirected edge, a dashed
+    /// edge points to the columns referenced in the next row.
+    pub fn render_dot(&self) -> String {
+        let mut namespaces: BTreeMap<&str, Vec<&ColumnDependencyEntry>> = BTreeMap::new();
+        for column in &self.columns {
+            namespaces
+                .entry(&column.namespace)
+                .or_default()
+                .push(column);
+        }
+        let same_row = self
+            .columns
+            .iter()
+            .flat_map(|column| {
+                column.same_row.iter().map(|other| {
+                    let a = column.column.as_str();
+                    (a.min(other.as_str()), a.max(other.as_str()))
+                })
+            })
+            .collect::<BTreeSet<_>>();
+
+        let mut dot = "digraph columns {\n".to_string();
+        for (namespace, columns) in namespaces {
+            dot += &format!("    subgraph \"cluster_{namespace}\" {{\n");
+            dot += &format!("        label = \"{namespace}\";\n");
+            for column in columns {
+                let shape = if column.kind == "fixed" {
+                    "box"
+                } else {
+                    "ellipse"
+                };
+                dot += &format!("        \"{}\" [shape = {shape}];\n", column.column);
+            }
+            dot += "    }\n";
+        }
+        for (a, b) in same_row {
+            dot += &format!("    \"{a}\" -> \"{b}\" [dir = none];\n");
+        }
+        for column in &self.columns {
+            for other in &column.next_row {
+                dot += &format!(
+                    "    \"{}\" -> \"{other}\" [style = dashed];\n",
+                    column.column
+                );
+            }
+        }
+        dot + "}\n"
+    }
+}
+
+impl Report for ColumnDependencyReport {
+    fn headers(&self) -> Vec<String> {
+        [
+            "column",
+            "kind",
+            "namespace",
+            "identities",
+            "same row",
+            "next row",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.columns
+            .iter()
+            .map(|c| {
+                vec![
+                    c.column.clone(),
+                    c.kind.clone(),
+                    c.namespace.clone(),
+                    c.identities
+                        .iter()
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    c.same_row.join(" "),
+                    c.next_row.join(" "),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Statistics about the columns and identities of a PIL file, for `stats`.
+#[derive(Serialize)]
+pub struct StatsReport {
+    degree: DegreeType,
+    namespaces: Vec<NamespaceStatsEntry>,
+    identities: BTreeMap<String, usize>,
+    max_degree: Option<MaxDegreeEntry>,
+    lookups: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+struct NamespaceStatsEntry {
+    namespace: String,
+    witness: usize,
+    fixed: usize,
+    intermediate: usize,
+}
+
+#[derive(Serialize)]
+struct MaxDegreeEntry {
+    degree: DegreeType,
+    identity: usize,
+    source: String,
+}
+
+impl From<&Stats> for StatsReport {
+    fn from(stats: &Stats) -> Self {
+        StatsReport {
+            degree: stats.degree,
+            namespaces: stats
+                .namespaces
+                .iter()
+                .map(|(namespace, columns)| NamespaceStatsEntry {
+                    namespace: namespace.clone(),
+                    witness: columns.witness,
+                    fixed: columns.fixed,
+                    intermediate: columns.intermediate,
+                })
+                .collect(),
+            identities: stats
+                .identities
+                .iter()
+                .map(|(kind, count)| (format!("{kind:?}").to_lowercase(), *count))
+                .collect(),
+            max_degree: stats.max_degree.as_ref().map(|max| MaxDegreeEntry {
+                degree: max.degree,
+                identity: max.identity,
+                source: format!("{}:{}", max.source.file, max.source.line),
+            }),
+            lookups: stats.lookups.clone(),
+        }
+    }
+}
+
+impl Report for StatsReport {
+    fn headers(&self) -> Vec<String> {
+        ["statistic", "scope", "value"].map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let row = |statistic: &str, scope: &str, value: &dyn Display| {
+            vec![statistic.to_string(), scope.to_string(), value.to_string()]
+        };
+        std::iter::once(row("degree", "", &self.degree))
+            .chain(self.namespaces.iter().flat_map(|n| {
+                [
+                    row("witness columns", &n.namespace, &n.witness),
+                    row("fixed columns", &n.namespace, &n.fixed),
+                    row("intermediate columns", &n.namespace, &n.intermediate),
+                ]
+            }))
+            .chain(
+                self.identities
+                    .iter()
+                    .map(|(kind, count)| row("identities", kind, count)),
+            )
+            .chain(
+                self.max_degree
+                    .iter()
+                    .map(|max| row("max degree", &max.source, &max.degree)),
+            )
+            .chain(
+                self.lookups
+                    .iter()
+                    .map(|(namespace, count)| row("lookups into", namespace, count)),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ExecutionPlan;
+
+    fn effective_config() -> Config {
+        ExecutionPlan::try_from(Config {
+            inputs: Some(vec![3, 2]),
+            coprocessors: Some(vec!["poseidon_gl".to_string()]),
+            ..Default::default()
+        })
+        .unwrap()
+        .to_config()
+    }
+
+    #[test]
+    fn json_is_stable() {
+        let golden = include_str!("../../test_data/reports/effective_config.json");
+        assert_eq!(render(&effective_config(), OutputFormat::Json), golden);
+    }
+
+    #[test]
+    fn csv() {
+        let csv = render(&effective_config(), OutputFormat::Csv);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("key,value"));
+        assert!(csv.contains("\ninputs,\"[3, 2]\"\n"), "{csv}");
+        assert!(csv.contains("\nfield,gl\n"), "{csv}");
+    }
+
+    #[test]
+    fn human() {
+        assert_eq!(
+            render(&effective_config(), OutputFormat::Human),
+            toml::to_string(&effective_config()).unwrap()
+        );
+    }
+
+    #[test]
+    fn table() {
+        let headers = vec!["name".to_string(), "rows".to_string()];
+        let rows = vec![
+            vec!["main".to_string(), "1024".to_string()],
+            vec!["binary".to_string(), "8".to_string()],
+        ];
+        assert_eq!(
+            render_table(&headers, &rows),
+            "name    rows\nmain    1024\nbinary  8\n"
+        );
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn stats() {
+        let src = "namespace N(8);\n    col witness x, y;\n    x' = x * y;\n    { x } in { y };\n";
+        let analyzed = pil_analyzer::analyze_string::<number::GoldilocksField>(src);
+        let report = StatsReport::from(&analyzed.stats());
+        assert_eq!(
+            render(&report, OutputFormat::Csv),
+            "statistic,scope,value
+degree,,8
+witness columns,N,2
+fixed columns,N,0
+intermediate columns,N,0
+identities,plookup,1
+identities,polynomial,1
+max degree,input:3,2
+lookups into,N,1
+"
+        );
+    }
+
+    #[test]
+    fn column_dependencies_dot() {
+        let src = "namespace N(8);\n    col witness x, y;\n    x' = x + y;\n";
+        let analyzed = pil_analyzer::analyze_string::<number::GoldilocksField>(src);
+        let report = ColumnDependencyReport::from(&analyzed.column_dependency_graph());
+        assert_eq!(
+            report.render_dot(),
+            r#"digraph columns {
+    subgraph "cluster_N" {
+        label = "N";
+        "N.x" [shape = ellipse];
+        "N.y" [shape = ellipse];
+    }
+    "N.x" -> "N.y" [dir = none];
+    "N.y" -> "N.x" [style = dashed];
+}
+"#
+        );
+    }
+}
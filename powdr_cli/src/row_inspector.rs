@@ -0,0 +1,147 @@
+//! Inspection of the witness around the row where witness generation fails, for `debug`.
+//!
+//! If witness generation fails, the columns referenced by the failing identities (or
+//! the columns selected by patterns) are shown on the rows before and after the failing
+//! row, with the values determined so far. If it succeeds, the same is done for a given row, with all witness columns and
+//! the identities referencing them.
+
+use std::collections::BTreeSet;
+
+use ast::analyzed::{AlgebraicExpression, Analyzed};
+use ast::parsed::visitor::ExpressionVisitable;
+use executor::witgen::{GenerationError, QueryCallback, WitnessGenerator};
+use number::{DegreeType, FieldElement};
+
+use crate::column_filter::ColumnFilter;
+use crate::report::{InspectedColumn, InspectedIdentity, RowInspectionReport};
+use crate::witgen_options::WitgenOptions;
+
+/// Runs witness generation and returns the rows around the failing row or, if
+/// witness generation succeeds, around `row`. If `patterns` is not empty, it
+/// selects the columns instead of the failing identities.
+pub fn inspect_rows<T: FieldElement, Q: QueryCallback<T>>(
+    analyzed: &Analyzed<T>,
+    constants: &[(&str, Vec<T>)],
+    query_callback: Q,
+    row: Option<DegreeType>,
+    patterns: &[String],
+    witgen_options: &WitgenOptions,
+) -> Result<RowInspectionReport, String> {
+    let filter = ColumnFilter::new(patterns);
+    let generator = WitnessGenerator::new(
+        analyzed,
+        constants,
+        witgen_options.query_callback(query_callback)?,
+    );
+    match witgen_options.apply(generator)?.try_generate() {
+        Err(GenerationError::Failed(e)) => {
+            if let Some(row) = row {
+                log::warn!("Witness generation failed, ignoring the row {row}.");
+            }
+            let failure = e.to_string().trim_end().to_string();
+            let context = e.context;
+            let all_columns: Vec<String> = context
+                .rows
+                .first()
+                .map(|r| r.values.iter().map(|(name, _)| name.clone()).collect())
+                .unwrap_or_default();
+            let involved = context
+                .identities
+                .iter()
+                .flat_map(|i| i.columns.iter().cloned())
+                .collect::<BTreeSet<_>>();
+            // Patterns select from all columns, by default only the involved ones are shown.
+            filter.warn_unmatched(all_columns.iter().map(String::as_str));
+            let columns = all_columns
+                .into_iter()
+                .filter(|name| {
+                    if patterns.is_empty() {
+                        involved.is_empty() || involved.contains(name)
+                    } else {
+                        filter.is_selected(name)
+                    }
+                })
+                .collect::<Vec<_>>();
+            Ok(RowInspectionReport {
+                failure: Some(failure),
+                rows: context.rows.iter().map(|r| r.row).collect(),
+                columns: columns
+                    .into_iter()
+                    .map(|column| {
+                        let values = context
+                            .rows
+                            .iter()
+                            .map(|r| {
+                                r.values
+                                    .iter()
+                                    .find(|(name, _)| *name == column)
+                                    .and_then(|(_, value)| value.clone())
+                            })
+                            .collect();
+                        InspectedColumn { column, values }
+                    })
+                    .collect(),
+                identities: context
+                    .identities
+                    .into_iter()
+                    .map(|i| InspectedIdentity {
+                        source: i.source,
+                        identity: i.identity,
+                    })
+                    .collect(),
+            })
+        }
+        Err(e) => Err(e.to_string()),
+        Ok(witness) => {
+            let row = row.ok_or(
+                "Witness generation succeeded, use --row to inspect the rows around a given row.",
+            )?;
+            let degree = analyzed.degree();
+            if row >= degree {
+                return Err(format!(
+                    "Row {row} is out of range, the degree is {degree}."
+                ));
+            }
+            let rows = row.saturating_sub(2)..=(row + 1).min(degree - 1);
+            filter.warn_unmatched(witness.iter().map(|(name, _)| name.as_str()));
+            let columns = witness
+                .into_iter()
+                .filter(|(name, _)| filter.is_selected(name))
+                .map(|(column, values)| InspectedColumn {
+                    column,
+                    values: rows
+                        .clone()
+                        .map(|row| Some(values[row as usize].to_string()))
+                        .collect(),
+                })
+                .collect::<Vec<_>>();
+            let selected = columns
+                .iter()
+                .map(|c| c.column.as_str())
+                .collect::<BTreeSet<_>>();
+            let identities = analyzed
+                .identities_with_inlined_intermediate_polynomials()
+                .into_iter()
+                .filter(|identity| {
+                    let mut references = false;
+                    identity.pre_visit_expressions(&mut |e| {
+                        if let AlgebraicExpression::Reference(reference) = e {
+                            references |= selected.contains(reference.name.as_str());
+                        }
+                    });
+                    references
+                })
+                .map(|identity| InspectedIdentity {
+                    source: format!("{}:{}", identity.source.file, identity.source.line),
+                    identity: identity.to_string(),
+                })
+                .collect();
+            Ok(RowInspectionReport {
+                failure: None,
+                rows: rows.collect(),
+                columns,
+                identities,
+            })
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Options of witness generation for the commands that run it: `debug` and the
+//! `--check-executor` mode of `rust` and `riscv-asm`.
+
+use std::path::PathBuf;
+
+use executor::witgen::memory_budget::MemoryBudget;
+use executor::witgen::profiling::WitgenProfiler;
+use executor::witgen::query_recording::{replay_query_callback, QueryRecorder};
+use executor::witgen::{QueryCallback, WitnessGenerator};
+use number::FieldElement;
+
+#[derive(Default)]
+pub struct WitgenOptions {
+    /// The approximate memory budget in bytes, given in GB by `--witgen-memory-budget`,
+    /// see [WitnessGenerator::with_memory_budget].
+    pub memory_budget: Option<u64>,
+    /// The file the prover queries and their answers are recorded to.
+    pub record_queries: Option<PathBuf>,
+    /// A recording written with `record_queries` that answers the prover queries
+    /// instead of the inputs.
+    pub replay_queries: Option<PathBuf>,
+    /// Seeds the PC and the registers with the executor trace, see
+    /// [WitnessGenerator::with_seeded_witness_values]. Only `--check-executor` runs
+    /// the executor, `debug` ignores it.
+    pub seed_from_executor: bool,
+    /// The file the per-identity statistics are written to, see [WitgenProfiler].
+    pub profile: Option<PathBuf>,
+}
+
+impl WitgenOptions {
+    /// Returns the query callback for witness generation: `query_callback` or,
+    /// if queries are replayed, one that answers them from the recording.
+    pub fn query_callback<'c, T: FieldElement>(
+        &self,
+        query_callback: impl QueryCallback<T> + 'c,
+    ) -> Result<Box<dyn QueryCallback<T> + 'c>, String> {
+        Ok(match &self.replay_queries {
+            Some(path) => Box::new(replay_query_callback(path)?),
+            None => Box::new(query_callback),
+        })
+    }
+
+    pub fn apply<'a, 'b, T: FieldElement, Q: QueryCallback<T>>(
+        &self,
+        generator: WitnessGenerator<'a, 'b, T, Q>,
+    ) -> Result<WitnessGenerator<'a, 'b, T, Q>, String> {
+        let query_recorder = self
+            .record_queries
+            .as_deref()
+            .map(QueryRecorder::create)
+            .transpose()?;
+        let profiler = self
+            .profile
+            .as_deref()
+            .map(WitgenProfiler::create)
+            .transpose()?;
+        Ok(generator
+            .with_memory_budget(self.memory_budget.map(MemoryBudget::new))
+            .with_query_recorder(query_recorder)
+            .with_witgen_profiling(profiler))
+    }
+}
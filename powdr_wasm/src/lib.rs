@@ -0,0 +1,169 @@
+//! PIL analysis and witness generation for the browser, e.g. for a playground.
+//! Build with `wasm-pack build powdr_wasm --target web`.
+//!
+//! Everything runs in the Goldilocks field. Since its elements do not fit into
+//! JavaScript numbers, column values are returned as decimal strings.
+
+use ast::analyzed::{Analyzed, Symbol};
+use executor::constant_evaluator;
+use executor::witgen::{inputs_to_query_callback, WitnessGenerator};
+use number::{DegreeType, FieldElement, GoldilocksField};
+use pil_analyzer::Limits;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// The optimized PIL and its columns, returned by [analyze].
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Analysis {
+    pub degree: Option<DegreeType>,
+    /// The names of the witness columns, with one entry per array element.
+    pub witness_columns: Vec<String>,
+    /// The names of the fixed columns, with one entry per array element.
+    pub fixed_columns: Vec<String>,
+    pub identity_count: usize,
+    /// The optimized PIL, as it would be written to `<name>_opt.pil`.
+    pub optimized_pil: String,
+}
+
+/// The values of all columns, returned by [generate_witness].
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Columns {
+    pub fixed: Vec<(String, Vec<String>)>,
+    pub witness: Vec<(String, Vec<String>)>,
+}
+
+#[wasm_bindgen(start)]
+fn start() {
+    console_error_panic_hook::set_once();
+}
+
+/// Analyzes and optimizes the PIL source `pil`, which cannot include other files
+/// except for the standard library. Throws if the source exceeds the default [Limits].
+#[wasm_bindgen]
+pub fn analyze(pil: &str) -> Result<JsValue, JsError> {
+    to_js(&analysis(pil).map_err(|e| JsError::new(&e))?)
+}
+
+/// Evaluates the fixed columns of `pil` and generates the witness, answering the
+/// queries `("input", i)` with `inputs[i]`. Throws if the witness cannot be generated.
+#[wasm_bindgen(js_name = generateWitness)]
+pub fn generate_witness(pil: &str, inputs: Vec<u64>) -> Result<JsValue, JsError> {
+    to_js(&columns(pil, inputs).map_err(|e| JsError::new(&e))?)
+}
+
+/// The result of [analyze], without the conversion to JavaScript.
+pub fn analysis(pil: &str) -> Result<Analysis, String> {
+    let pil = optimized(pil)?;
+    let names = |polys: Vec<&(Symbol, _)>| {
+        polys
+            .into_iter()
+            .flat_map(|(symbol, _)| symbol.array_elements().map(|(name, _)| name))
+            .collect()
+    };
+    Ok(Analysis {
+        degree: pil.degree,
+        witness_columns: names(pil.committed_polys_in_source_order()),
+        fixed_columns: names(pil.constant_polys_in_source_order()),
+        identity_count: pil.identities.len(),
+        optimized_pil: pil.to_string(),
+    })
+}
+
+/// The result of [generate_witness], without the conversion to JavaScript.
+pub fn columns(pil: &str, inputs: Vec<u64>) -> Result<Columns, String> {
+    let pil = optimized(pil)?;
+    if pil.degree.is_none() {
+        return Err("The PIL does not declare a degree.".to_string());
+    }
+    let fixed = constant_evaluator::generate(&pil);
+    let inputs = inputs.into_iter().map(GoldilocksField::from).collect();
+    let witness = WitnessGenerator::new(&pil, &fixed, inputs_to_query_callback(inputs))
+        .try_generate()
+        .map_err(|e| e.to_string())?;
+    Ok(Columns {
+        fixed: fixed
+            .into_iter()
+            .map(|(name, values)| (name.to_string(), to_strings(values)))
+            .collect(),
+        witness: witness
+            .into_iter()
+            .map(|(name, values)| (name, to_strings(values)))
+            .collect(),
+    })
+}
+
+fn optimized(pil: &str) -> Result<Analyzed<GoldilocksField>, String> {
+    let analyzed = pil_analyzer::analyze_string_with_limits(pil, None, &Limits::default())
+        .map_err(|errors| errors.join("\n"))?;
+    Ok(pilopt::optimize(analyzed))
+}
+
+fn to_strings(values: Vec<GoldilocksField>) -> Vec<String> {
+    values
+        .into_iter()
+        .map(|v| v.to_arbitrary_integer().to_string())
+        .collect()
+}
+
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(value).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SUM: &str = r#"
+namespace Sum(4);
+    col fixed ISLAST = [0, 0, 0, 1];
+    col witness input(i) query ("input", i);
+    col witness sum;
+    col witness unused[2];
+
+    ISLAST * sum' = 0;
+    sum' = (1 - ISLAST) * (sum + input);
+    ISLAST * input = 0;
+"#;
+
+    #[test]
+    fn analyze_sum() {
+        let analysis = analysis(SUM).unwrap();
+        assert_eq!(analysis.degree, Some(4));
+        assert_eq!(
+            analysis.witness_columns,
+            ["Sum.input", "Sum.sum", "Sum.unused[0]", "Sum.unused[1]"]
+        );
+        assert_eq!(analysis.fixed_columns, ["Sum.ISLAST"]);
+        assert_eq!(analysis.identity_count, 3);
+        assert!(analysis.optimized_pil.contains("namespace Sum(4);"));
+    }
+
+    #[test]
+    fn analysis_errors() {
+        let error = analysis("namespace N(2**62);\ncol witness x;\n").unwrap_err();
+        assert!(error.contains("exceeds the limit"), "{error}");
+    }
+
+    #[test]
+    fn generate_sum() {
+        let columns = columns(SUM, vec![3, 2, 1]).unwrap();
+        assert_eq!(
+            columns.fixed,
+            [(
+                "Sum.ISLAST".to_string(),
+                to_strings(vec![0.into(), 0.into(), 0.into(), 1.into()])
+            )]
+        );
+        assert_eq!(columns.witness[0].0, "Sum.input");
+        assert_eq!(columns.witness[0].1, ["3", "2", "1", "0"]);
+        assert_eq!(columns.witness[1].0, "Sum.sum");
+        assert_eq!(columns.witness[1].1, ["0", "3", "5", "6"]);
+    }
+
+    #[test]
+    fn missing_inputs() {
+        let error = columns(SUM, vec![3]).unwrap_err();
+        assert!(error.contains("only 1 inputs were provided"), "{error}");
+    }
+}
@@ -0,0 +1,26 @@
+//! Runs the entry points in a JavaScript engine: `wasm-pack test --node powdr_wasm`.
+#![cfg(target_arch = "wasm32")]
+
+use powdr_wasm::{analysis, analyze, columns, generate_witness, Analysis, Columns};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const FIBONACCI: &str = include_str!("../../test_data/pil/fibonacci.pil");
+
+#[wasm_bindgen_test]
+fn analyze_fibonacci() {
+    let value = analyze(FIBONACCI).unwrap();
+    let analysis_from_js: Analysis = serde_wasm_bindgen::from_value(value).unwrap();
+    assert_eq!(analysis_from_js, analysis(FIBONACCI).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn generate_fibonacci() {
+    let value = generate_witness(FIBONACCI, vec![]).unwrap();
+    let columns_from_js: Columns = serde_wasm_bindgen::from_value(value).unwrap();
+    assert_eq!(columns_from_js, columns(FIBONACCI, vec![]).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn invalid_pil_throws() {
+    assert!(analyze("namespace N(2**62);\ncol witness x;\n").is_err());
+}
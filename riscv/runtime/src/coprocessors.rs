@@ -6,6 +6,9 @@ extern "C" {
     // during the reachability analysis.
     fn poseidon_gl_coprocessor(data: *mut [u64; 12]);
 
+    // This will be replaced by a call to the Keccak-f coprocessor.
+    fn keccakf_coprocessor(data: *mut [u64; 25]);
+
     // This will be replaced by a call to prover input.
     fn input_coprocessor(index: u32, what: u32) -> u32;
 }
@@ -49,3 +52,11 @@ pub fn poseidon_gl_unsafe(mut data: [u64; 12]) -> [u64; 4] {
 
     [data[0], data[1], data[2], data[3]]
 }
+
+/// Applies the Keccak-f[1600] permutation to the state in place,
+/// using the Keccak-f coprocessor in PIL.
+pub fn keccakf(state: &mut [u64; 25]) {
+    unsafe {
+        keccakf_coprocessor(state as *mut [u64; 25]);
+    }
+}
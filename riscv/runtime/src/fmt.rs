@@ -8,6 +8,17 @@ macro_rules! print {
     }};
 }
 
+#[macro_export]
+macro_rules! println {
+    () => {{
+        $crate::fmt::print_str("\n");
+    }};
+    ($($arg:tt)+) => {{
+        $crate::fmt::print_args(format_args!( $($arg)+));
+        $crate::fmt::print_str("\n");
+    }};
+}
+
 pub fn print_args(args: fmt::Arguments) {
     fmt::write(&mut ProverWriter {}, args).unwrap();
 }
@@ -30,6 +41,8 @@ pub fn print_str(s: &str) {
     }
 }
 
+/// Prints a byte using `ebreak`. This is only seen by the executor and
+/// does not create any rows in the proof.
 #[inline]
 fn print_prover_char(c: u8) {
     let mut value = c as u32;
@@ -0,0 +1,36 @@
+use crate::coprocessors::keccakf;
+
+/// The number of bytes absorbed per permutation for a 256-bit output.
+const KECCAK256_RATE: usize = 136;
+
+/// Computes the Keccak-256 hash (as used by Ethereum, not the padding of SHA3-256)
+/// of `data`, using the Keccak-f coprocessor.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+
+    let mut chunks = data.chunks_exact(KECCAK256_RATE);
+    for chunk in &mut chunks {
+        absorb(&mut state, chunk);
+        keccakf(&mut state);
+    }
+
+    let remainder = chunks.remainder();
+    let mut last = [0u8; KECCAK256_RATE];
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[remainder.len()] ^= 0x01;
+    last[KECCAK256_RATE - 1] ^= 0x80;
+    absorb(&mut state, &last);
+    keccakf(&mut state);
+
+    let mut output = [0u8; 32];
+    for (out, lane) in output.chunks_exact_mut(8).zip(state.iter()) {
+        out.copy_from_slice(&lane.to_le_bytes());
+    }
+    output
+}
+
+fn absorb(state: &mut [u64; 25], block: &[u8]) {
+    for (lane, bytes) in state.iter_mut().zip(block.chunks_exact(8)) {
+        *lane ^= u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+}
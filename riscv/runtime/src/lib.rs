@@ -14,6 +14,7 @@ use crate::fmt::print_str;
 mod allocator;
 pub mod coprocessors;
 pub mod fmt;
+pub mod hash;
 
 #[panic_handler]
 unsafe fn panic(panic: &PanicInfo<'_>) -> ! {
@@ -1,6 +1,7 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt,
+    iter::once,
 };
 
 use asm_utils::{
@@ -68,12 +69,12 @@ impl Architecture for RiscvArchitecture {
     fn instruction_ends_control_flow(instr: &str) -> bool {
         match instr {
             "li" | "lui" | "la" | "mv" | "add" | "addi" | "sub" | "neg" | "mul" | "mulh"
-            | "mulhu" | "mulhsu" | "divu" | "remu" | "xor" | "xori" | "and" | "andi" | "or"
-            | "ori" | "not" | "slli" | "sll" | "srli" | "srl" | "srai" | "seqz" | "snez"
-            | "slt" | "slti" | "sltu" | "sltiu" | "sgtz" | "beq" | "beqz" | "bgeu" | "bltu"
-            | "blt" | "bge" | "bltz" | "blez" | "bgtz" | "bgez" | "bne" | "bnez" | "jal"
-            | "jalr" | "call" | "ecall" | "ebreak" | "lw" | "lb" | "lbu" | "lh" | "lhu" | "sw"
-            | "sh" | "sb" | "nop" | "fence" | "fence.i" | "amoadd.w" | "amoadd.w.aq"
+            | "mulhu" | "mulhsu" | "div" | "divu" | "rem" | "remu" | "xor" | "xori" | "and"
+            | "andi" | "or" | "ori" | "not" | "slli" | "sll" | "srli" | "srl" | "srai" | "seqz"
+            | "snez" | "slt" | "slti" | "sltu" | "sltiu" | "sgtz" | "beq" | "beqz" | "bgeu"
+            | "bltu" | "blt" | "bge" | "bltz" | "blez" | "bgtz" | "bgez" | "bne" | "bnez"
+            | "jal" | "jalr" | "call" | "ecall" | "ebreak" | "lw" | "lb" | "lbu" | "lh" | "lhu"
+            | "sw" | "sh" | "sb" | "nop" | "fence" | "fence.i" | "amoadd.w" | "amoadd.w.aq"
             | "amoadd.w.rl" | "amoadd.w.aqrl" | "lr.w" | "lr.w.aq" | "lr.w.rl" | "lr.w.aqrl"
             | "sc.w" | "sc.w.aq" | "sc.w.rl" | "sc.w.aqrl" => false,
             "j" | "jr" | "tail" | "ret" | "unimp" => true,
@@ -103,8 +104,6 @@ pub fn compile(
     coprocessors: &CoProcessors,
     with_bootloader: bool,
 ) -> String {
-    // stack grows towards zero
-    let stack_start = 0x10000;
     // data grows away from zero
     let data_start = 0x10100;
 
@@ -190,6 +189,54 @@ pub fn compile(
         },
     );
 
+    let debug_files = file_ids
+        .into_iter()
+        .map(|(id, dir, file)| format!("debug file {id} {} {};", quote(&dir), quote(&file)))
+        .collect();
+    riscv_program(
+        debug_files,
+        statements,
+        &data_positions,
+        data_code,
+        coprocessors,
+        with_bootloader,
+    )
+}
+
+/// The machine that implements multiplication and division, and its instance.
+const ARITH_IMPORT: &str = "use std::arith::Arith;";
+const ARITH_MACHINE: (&str, &str) = ("arith", "Arith");
+
+/// The number of rows each call of an instruction of the RISC-V machine uses in the
+/// submachine it calls, as (instruction, submachine instance, rows). Every instruction
+/// also uses one row of the main machine, shared with the other statements of its batch.
+pub const INSTRUCTION_ROW_COSTS: [(&str, &str, usize); 10] = [
+    ("mul", "arith", 1),
+    ("divremu", "arith", 1),
+    ("and", "binary", 4),
+    ("or", "binary", 4),
+    ("xor", "binary", 4),
+    ("shl", "shift", 4),
+    ("shr", "shift", 4),
+    ("split_gl", "split_gl", 8),
+    ("poseidon_gl", "poseidon_gl", 31),
+    ("keccakf", "keccakf", 25),
+];
+
+/// Where the stack starts, it grows towards zero.
+pub(crate) const STACK_START: u32 = 0x10000;
+
+/// Builds the powdr-asm machine from the RISC-V `statements` and the code that
+/// initializes the memory, `data_code`. References to the data objects in
+/// `data_positions` are replaced by their addresses.
+pub(crate) fn riscv_program(
+    debug_files: Vec<String>,
+    statements: Vec<Statement>,
+    data_positions: &BTreeMap<String, u32>,
+    data_code: Vec<String>,
+    coprocessors: &CoProcessors,
+    with_bootloader: bool,
+) -> String {
     let bootloader_lines = if with_bootloader {
         let (bootloader, _) = bootloader();
         log::debug!("Adding Bootloader:\n{}", bootloader);
@@ -201,19 +248,18 @@ pub fn compile(
         vec![]
     };
 
-    let program: Vec<String> = file_ids
+    let program: Vec<String> = debug_files
         .into_iter()
-        .map(|(id, dir, file)| format!("debug file {id} {} {};", quote(&dir), quote(&file)))
         .chain(bootloader_lines)
         .chain(["call __data_init;".to_string()])
         .chain(call_every_submachine(coprocessors))
         .chain([
-            format!("// Set stack pointer\nx2 <=X= {stack_start};"),
+            format!("// Set stack pointer\nx2 <=X= {STACK_START};"),
             "call __runtime_start;".to_string(),
             "return;".to_string(), // This is not "riscv ret", but "return from powdr asm function".
         ])
         .chain(
-            substitute_symbols_with_values(statements, &data_positions)
+            substitute_symbols_with_values(statements, data_positions)
                 .into_iter()
                 .flat_map(|v| process_statement(v, coprocessors)),
         )
@@ -238,10 +284,11 @@ pub fn compile(
     assert!((18..=20).contains(&degree));
     let degree = 1 << degree;
 
+    // The arithmetic machine implements the M extension, so it is always present.
     riscv_machine(
-        &coprocessors.machine_imports(),
+        &[&[ARITH_IMPORT][..], &coprocessors.machine_imports()].concat(),
         &preamble(degree, coprocessors),
-        &coprocessors.declarations(),
+        &[&[ARITH_MACHINE][..], &coprocessors.declarations()].concat(),
         program,
     )
 }
@@ -418,6 +465,29 @@ fn substitute_symbols_with_values(
     statements
 }
 
+/// Returns the name of the PIL column that holds the values of `register` of the
+/// main machine, for example to seed witness generation with an executor trace.
+/// The rows of the column correspond to the rows of the executor trace.
+pub fn register_column_name(register: &str) -> String {
+    // The instance of the `Main` machine is called `main`.
+    format!("main.{register}")
+}
+
+/// The program counter and the register bank of the main machine, as pairs of the
+/// name of the register in the executor trace and the name of its PIL column,
+/// see [register_column_name]. Assignment and coprocessor registers are not included.
+pub fn register_columns() -> Vec<(String, String)> {
+    once("pc".to_string())
+        .chain((0..32).map(|i| format!("x{i}")))
+        .chain((1..=4).map(|i| format!("tmp{i}")))
+        .chain(once("lr_sc_reservation".to_string()))
+        .map(|register| {
+            let column = register_column_name(&register);
+            (register, column)
+        })
+        .collect()
+}
+
 fn riscv_machine(
     machines: &[&str],
     preamble: &str,
@@ -651,54 +721,21 @@ fn preamble(degree: u64, coprocessors: &CoProcessors) -> String {
     instr wrap16 Y -> X { Y = Y_b5 * 2**32 + Y_b6 * 2**40 + X, X = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 }
     col witness Y_b5;
     col witness Y_b6;
-    col witness Y_b7;
-    col witness Y_b8;
     { Y_b5 } in { bytes };
     { Y_b6 } in { bytes };
-    { Y_b7 } in { bytes };
-    { Y_b8 } in { bytes };
-
-    col witness REM_b1;
-    col witness REM_b2;
-    col witness REM_b3;
-    col witness REM_b4;
-    { REM_b1 } in { bytes };
-    { REM_b2 } in { bytes };
-    { REM_b3 } in { bytes };
-    { REM_b4 } in { bytes };
 
-    // implements Z = Y / X and W = Y % X.
-    instr divremu Y, X -> Z, W {
-        // main division algorithm:
-        // Y is the known dividend
-        // X is the known divisor
-        // Z is the unknown quotient
-        // W is the unknown remainder
-        // if X is zero, remainder is set to dividend, as per RISC-V specification:
-        X * Z + W = Y,
-
-        // remainder >= 0:
-        W = REM_b1 + REM_b2 * 0x100 + REM_b3 * 0x10000 + REM_b4 * 0x1000000,
-
-        // remainder < divisor, conditioned to X not being 0:
-        (1 - XIsZero) * (X - W - 1 - Y_b5 - Y_b6 * 0x100 - Y_b7 * 0x10000 - Y_b8 * 0x1000000) = 0,
-
-        // in case X is zero, we set quotient according to RISC-V specification
-        XIsZero * (Z - 0xffffffff) = 0,
-
-        // quotient is 32 bits:
-        Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000
-    }
+    // ============== multiplication and division ==============
 
     // Multiply two 32-bits unsigned, return the upper and lower unsigned 32-bit
     // halves of the result.
     // X is the lower half (least significant bits)
     // Y is the higher half (most significant bits)
-    instr mul Z, W -> X, Y {
-        Z * W = X + Y * 2**32,
-        X = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
-        Y = Y_b5 + Y_b6 * 0x100 + Y_b7 * 0x10000 + Y_b8 * 0x1000000
-    }
+    instr mul Z, W -> X, Y = arith.mul
+
+    // implements Z = Y / X and W = Y % X.
+    // If X is zero, the quotient is 0xffffffff and the remainder is Y,
+    // as per RISC-V specification.
+    instr divremu Y, X -> Z, W = arith.divremu
 "#
 }
 
@@ -857,6 +894,25 @@ fn only_if_no_write_to_zero_vec(statements: Vec<String>, reg: Register) -> Vec<S
     }
 }
 
+/// Prepares a signed division of `r1` by `r2`: Stores their absolute values in tmp1
+/// and tmp2 and sets tmp3 and tmp4 to 1 if they are non-negative.
+fn signed_divremu(r1: Register, r2: Register) -> Vec<String> {
+    vec![
+        format!("tmp1 <== to_signed({r1});"),
+        format!("tmp2 <== to_signed({r2});"),
+        // tmp3 is 1 if tmp1 is non-negative
+        "tmp3 <== is_positive(tmp1 + 1);".into(),
+        // tmp4 is 1 if tmp2 is non-negative
+        "tmp4 <== is_positive(tmp2 + 1);".into(),
+        // If tmp1 is negative, convert to positive
+        "skip_if_zero 0, tmp3;".into(),
+        "tmp1 <=X= 0 - tmp1;".into(),
+        // If tmp2 is negative, convert to positive
+        "skip_if_zero 0, tmp4;".into(),
+        "tmp2 <=X= 0 - tmp2;".into(),
+    ]
+}
+
 fn try_coprocessor_substitution(label: &str, coprocessors: &CoProcessors) -> Option<String> {
     coprocessors
         .substitutions()
@@ -967,6 +1023,42 @@ fn process_instruction(instr: &str, args: &[Argument], coprocessors: &CoProcesso
             let (rd, r1, r2) = rrr(args);
             only_if_no_write_to_zero(format!("tmp1, {rd} <== divremu({r1}, {r2});"), rd)
         }
+        "div" => {
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                signed_divremu(r1, r2)
+                    .into_iter()
+                    .chain([
+                        // tmp3 is 1 if the quotient is negative, i.e. if the signs differ
+                        // and the divisor is not zero (the quotient is then always -1).
+                        "tmp3 <== is_not_equal_zero(tmp3 - tmp4);".into(),
+                        format!("tmp4 <== is_not_equal_zero({r2});"),
+                        "tmp3 <== is_positive(tmp3 + tmp4 - 1);".into(),
+                        "tmp1, tmp2 <== divremu(tmp1, tmp2);".into(),
+                        "skip_if_zero tmp3, 1;".into(),
+                        "tmp1 <== wrap_signed(0 - tmp1);".into(),
+                        format!("{rd} <=X= tmp1;"),
+                    ])
+                    .collect(),
+                rd,
+            )
+        }
+        "rem" => {
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                signed_divremu(r1, r2)
+                    .into_iter()
+                    .chain([
+                        "tmp1, tmp2 <== divremu(tmp1, tmp2);".into(),
+                        // The remainder has the sign of the dividend.
+                        "skip_if_zero 0, tmp3;".into(),
+                        "tmp2 <== wrap_signed(0 - tmp2);".into(),
+                        format!("{rd} <=X= tmp2;"),
+                    ])
+                    .collect(),
+                rd,
+            )
+        }
 
         // bitwise
         "xor" => {
@@ -1237,9 +1329,9 @@ fn process_instruction(instr: &str, args: &[Argument], coprocessors: &CoProcesso
         }
         "ebreak" => {
             assert!(args.is_empty());
-            // This is using x0 on purpose, because we do not want to introduce
-            // nondeterminism with this.
-            vec!["x0 <=X= ${ (\"print_char\", x10) };\n".to_string()]
+            // Printing is only done by the executor, it does not create
+            // any rows in the proof.
+            vec!["debug print x10;".to_string()]
         }
         "ret" => {
             assert!(args.is_empty());
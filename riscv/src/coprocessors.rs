@@ -65,6 +65,18 @@ instr poseidon_gl A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11 -> X, Y, Z, W
     runtime_function_impl: Some(("poseidon_gl_coprocessor", poseidon_gl_call)),
 };
 
+static KECCAKF_COPROCESSOR: CoProcessor = CoProcessor {
+    name: "keccakf",
+    ty: "KeccakF",
+    import: "use std::hash::keccakf::KeccakF;",
+    instructions: r#"
+// ================== keccak instructions ==============
+instr keccakf KI0, KI1, KI2, KI3, KI4, KI5, KI6, KI7, KI8, KI9, KI10, KI11, KI12, KI13, KI14, KI15, KI16, KI17, KI18, KI19, KI20, KI21, KI22, KI23, KI24, KI25, KI26, KI27, KI28, KI29, KI30, KI31, KI32, KI33, KI34, KI35, KI36, KI37, KI38, KI39, KI40, KI41, KI42, KI43, KI44, KI45, KI46, KI47, KI48, KI49 -> KO0, KO1, KO2, KO3, KO4, KO5, KO6, KO7, KO8, KO9, KO10, KO11, KO12, KO13, KO14, KO15, KO16, KO17, KO18, KO19, KO20, KO21, KO22, KO23, KO24, KO25, KO26, KO27, KO28, KO29, KO30, KO31, KO32, KO33, KO34, KO35, KO36, KO37, KO38, KO39, KO40, KO41, KO42, KO43, KO44, KO45, KO46, KO47, KO48, KO49 = keccakf.keccakf
+
+"#,
+    runtime_function_impl: Some(("keccakf_coprocessor", keccakf_call)),
+};
+
 static INPUT_COPROCESSOR: CoProcessor = CoProcessor {
     name: "prover_input",
     ty: "",
@@ -73,11 +85,12 @@ static INPUT_COPROCESSOR: CoProcessor = CoProcessor {
     runtime_function_impl: Some(("input_coprocessor", prover_input_call)),
 };
 
-static ALL_COPROCESSORS: [(&str, &CoProcessor); 5] = [
+static ALL_COPROCESSORS: [(&str, &CoProcessor); 6] = [
     (BINARY_COPROCESSOR.name, &BINARY_COPROCESSOR),
     (SHIFT_COPROCESSOR.name, &SHIFT_COPROCESSOR),
     (SPLIT_GL_COPROCESSOR.name, &SPLIT_GL_COPROCESSOR),
     (POSEIDON_GL_COPROCESSOR.name, &POSEIDON_GL_COPROCESSOR),
+    (KECCAKF_COPROCESSOR.name, &KECCAKF_COPROCESSOR),
     (INPUT_COPROCESSOR.name, &INPUT_COPROCESSOR),
 ];
 
@@ -139,6 +152,24 @@ impl CoProcessors {
         self
     }
 
+    pub fn with_keccakf(mut self) -> Self {
+        self.coprocessors
+            .insert(KECCAKF_COPROCESSOR.name, &KECCAKF_COPROCESSOR);
+        self
+    }
+
+    /// The names of all coprocessors that can be selected.
+    pub fn all_names() -> Vec<&'static str> {
+        ALL_COPROCESSORS.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.coprocessors
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
     pub fn has(&self, key: &str) -> bool {
         self.coprocessors.contains_key(key)
     }
@@ -197,20 +228,28 @@ impl CoProcessors {
     }
 
     pub fn registers(&self) -> String {
+        let mut regs = vec![];
+
         // Poseidon has 12 inputs and 4 outputs.
         // The base RISCV machine has 4 assignment registers.
         // Therefore we need to add 12 assignment registers when using Poseidon.
         // Moreover, we also need 12 extra general purpose registers to store the
         // input values.
-
-        if !self.coprocessors.contains_key(POSEIDON_GL_COPROCESSOR.name) {
-            return String::new();
+        if self.coprocessors.contains_key(POSEIDON_GL_COPROCESSOR.name) {
+            regs.extend((0..12).map(|i| format!("reg A{i}[<=];")));
+            regs.extend((0..12).map(|i| format!("reg P{i};")));
         }
 
-        let a_regs: Vec<String> = (0..12).map(|i| format!("reg A{}[<=];", i)).collect();
-        let p_regs: Vec<String> = (0..12).map(|i| format!("reg P{};", i)).collect();
+        // Keccak-f has 50 inputs and 50 outputs (the 32-bit words of the state),
+        // which need their own assignment registers, and 50 general purpose registers
+        // to store the state.
+        if self.coprocessors.contains_key(KECCAKF_COPROCESSOR.name) {
+            regs.extend((0..50).map(|i| format!("reg KI{i}[<=];")));
+            regs.extend((0..50).map(|i| format!("reg KO{i}[<=];")));
+            regs.extend((0..50).map(|i| format!("reg K{i};")));
+        }
 
-        [a_regs, p_regs].concat().join("\n")
+        regs.join("\n")
     }
 }
 
@@ -253,6 +292,29 @@ fn poseidon_gl_call() -> String {
         .collect()
 }
 
+fn keccakf_call() -> String {
+    // The x10 register points to the state of 25 64-bit lanes, i.e. 50 32-bit words,
+    // which are loaded into the K registers, permuted and stored back in place.
+    let decoding = |i| format!("K{i}, tmp2 <== mload({} + x10);\n", i * 4);
+    let encoding = |i| format!("mstore {} + x10, K{i};\n", i * 4);
+
+    let registers = keccakf_registers();
+    let call = format!("{registers} <== keccakf({registers});\n");
+
+    (0..50)
+        .map(decoding)
+        .chain(std::iter::once(call))
+        .chain((0..50).map(encoding))
+        .collect()
+}
+
+fn keccakf_registers() -> String {
+    (0..50)
+        .map(|i| format!("K{i}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 fn prover_input_call() -> String {
     "x10 <=X= ${ (\"data\", x11, x10) };".to_string()
 }
@@ -264,7 +326,8 @@ pub fn call_every_submachine(coprocessors: &CoProcessors) -> Vec<String> {
     // is called at least once. This is needed for witgen until it can do default blocks
     // automatically.
     // https://github.com/powdr-labs/powdr/issues/548
-    let mut calls = vec![];
+    // The arithmetic machine is not a coprocessor, it is always present.
+    let mut calls = vec!["x10, x11 <== mul(x10, x10);".to_string()];
     if coprocessors.has(BINARY_COPROCESSOR.name) {
         calls.push("x10 <== and(x10, x10);".to_string());
     }
@@ -285,6 +348,12 @@ pub fn call_every_submachine(coprocessors: &CoProcessors) -> Vec<String> {
         ]);
     }
 
+    if coprocessors.has(KECCAKF_COPROCESSOR.name) {
+        let registers = keccakf_registers();
+        calls.push(format!("{registers} <== keccakf({registers});"));
+        calls.extend((0..50).map(|i| format!("K{i} <=X= 0;")));
+    }
+
     calls.extend(vec!["x10 <=X= 0;".to_string(), "x11 <=X= 0;".to_string()]);
 
     calls
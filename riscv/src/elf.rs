@@ -0,0 +1,722 @@
+//! Translation of linked RISC-V (rv32imac) ELF files to powdr assembly.
+//!
+//! This is an alternative to compiling the textual assembly emitted by rustc:
+//! it also works with LTO and sees the data that was placed by the linker.
+//! The code is decoded instruction by instruction and the data sections are
+//! stored to memory at their linked addresses before the program starts.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use itertools::Itertools;
+use object::elf::{
+    FileHeader32, EM_RISCV, SHF_ALLOC, SHF_EXECINSTR, SHN_ABS, SHN_UNDEF, SHT_PROGBITS, SHT_SYMTAB,
+    STT_FUNC, STT_NOTYPE, STT_OBJECT,
+};
+use object::read::elf::{FileHeader, SectionHeader, Sym};
+use object::LittleEndian;
+
+use crate::compiler::{riscv_program, Register, STACK_START};
+use crate::{Argument, CoProcessors, Expression, Statement};
+
+/// Translates the linked RISC-V ELF file `elf_bytes` to a powdr-asm file.
+///
+/// Calls to undefined functions are resolved by the name of the absolute symbol
+/// they jump to, so coprocessor functions are best defined with `--defsym`.
+/// Aligned data words that hold the address of a function are turned into
+/// references to its label, so that function pointers keep working.
+pub fn translate(
+    elf_bytes: &[u8],
+    coprocessors: &CoProcessors,
+    with_bootloader: bool,
+) -> Result<String, String> {
+    let elf = Elf::parse(elf_bytes)?;
+
+    let code = elf
+        .sections
+        .iter()
+        .filter(|s| s.is_code())
+        .map(|s| decode_section(s.addr, s.contents))
+        .collect::<Result<Vec<_>, _>>()?
+        .concat();
+
+    let labels = elf.labels();
+    let functions = elf.function_addresses();
+    let statements = translate_code(&code, &labels, &functions);
+    let data_code = data_image(&elf)?
+        .into_iter()
+        .flat_map(|(addr, value)| {
+            if functions.contains(&value) {
+                vec![
+                    format!("tmp1 <== load_label({});", label(&labels, value)),
+                    format!("mstore 0x{addr:x}, tmp1;"),
+                ]
+            } else {
+                vec![format!("mstore 0x{addr:x}, 0x{value:x};")]
+            }
+        })
+        .collect();
+
+    Ok(riscv_program(
+        vec![],
+        statements,
+        &BTreeMap::new(),
+        data_code,
+        coprocessors,
+        with_bootloader,
+    ))
+}
+
+/// The label of the code at `addr`.
+fn label(labels: &BTreeMap<u32, String>, addr: u32) -> String {
+    labels
+        .get(&addr)
+        .cloned()
+        .unwrap_or_else(|| format!("__elf_{addr:x}"))
+}
+
+struct Elf<'a> {
+    sections: Vec<Section<'a>>,
+    symbols: Vec<Symbol>,
+}
+
+struct Section<'a> {
+    kind: u32,
+    flags: u32,
+    addr: u32,
+    size: u32,
+    contents: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    fn is_code(&self) -> bool {
+        self.kind == SHT_PROGBITS
+            && self.flags & (SHF_ALLOC | SHF_EXECINSTR) == SHF_ALLOC | SHF_EXECINSTR
+    }
+
+    fn is_data(&self) -> bool {
+        self.kind == SHT_PROGBITS && self.flags & (SHF_ALLOC | SHF_EXECINSTR) == SHF_ALLOC
+    }
+
+    fn contains(&self, addr: u32) -> bool {
+        (self.addr..self.addr + self.size).contains(&addr)
+    }
+}
+
+struct Symbol {
+    name: String,
+    value: u32,
+    kind: u8,
+    section: u16,
+}
+
+impl<'a> Elf<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, String> {
+        let error = |e: object::Error| format!("Invalid ELF file: {e}");
+        let header = FileHeader32::<LittleEndian>::parse(bytes).map_err(error)?;
+        let endian = header.endian().map_err(error)?;
+        if header.e_machine(endian) != EM_RISCV {
+            return Err("Not a RISC-V ELF file.".to_string());
+        }
+
+        let section_table = header.sections(endian, bytes).map_err(error)?;
+        let sections = section_table
+            .iter()
+            .map(|section| {
+                let addr = section.sh_addr(endian);
+                let size = section.sh_size(endian);
+                if addr.checked_add(size).is_none() {
+                    return Err(format!(
+                        "Invalid ELF file: the section at 0x{addr:x} with size 0x{size:x} exceeds the address space."
+                    ));
+                }
+                Ok(Section {
+                    kind: section.sh_type(endian),
+                    flags: section.sh_flags(endian),
+                    addr,
+                    size,
+                    contents: section.data(endian, bytes).map_err(error)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let symbol_table = section_table
+            .symbols(endian, bytes, SHT_SYMTAB)
+            .map_err(error)?;
+        let symbols = symbol_table
+            .iter()
+            .map(|symbol| {
+                let name = symbol.name(endian, symbol_table.strings()).map_err(error)?;
+                Ok(Symbol {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    value: symbol.st_value(endian),
+                    kind: symbol.st_type(),
+                    section: symbol.st_shndx(endian),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Elf { sections, symbols })
+    }
+
+    /// The name used as label for each code address and absolute symbol.
+    /// If there are several symbols at an address, the first one in alphabetical order is used.
+    fn labels(&self) -> BTreeMap<u32, String> {
+        let mut labels = BTreeMap::new();
+        for symbol in &self.symbols {
+            let in_code = symbol.section == SHN_ABS
+                || self
+                    .sections
+                    .get(symbol.section as usize)
+                    .map_or(false, |s| s.is_code() && s.contains(symbol.value));
+            if symbol.section != SHN_UNDEF
+                && in_code
+                && [STT_NOTYPE, STT_OBJECT, STT_FUNC].contains(&symbol.kind)
+                && !symbol.name.is_empty()
+                // Mapping symbols like `$x` and local labels of the assembler.
+                && !symbol.name.starts_with('$')
+                && !symbol.name.starts_with(".L")
+            {
+                labels
+                    .entry(symbol.value)
+                    .and_modify(|label: &mut String| {
+                        if symbol.name < *label {
+                            *label = symbol.name.clone();
+                        }
+                    })
+                    .or_insert_with(|| symbol.name.clone());
+            }
+        }
+        // Local symbols can have the same name at different addresses.
+        let duplicates = labels
+            .values()
+            .duplicates()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        labels.retain(|_, label| !duplicates.contains(label));
+        labels
+    }
+
+    /// The addresses of all functions, which are the values treated as code pointers.
+    fn function_addresses(&self) -> BTreeSet<u32> {
+        self.symbols
+            .iter()
+            .filter(|s| s.kind == STT_FUNC && s.section != SHN_UNDEF && s.section != SHN_ABS)
+            .map(|s| s.value)
+            .collect()
+    }
+}
+
+/// The initial memory: the non-zero words of all data sections, by address.
+fn data_image(elf: &Elf) -> Result<BTreeMap<u32, u32>, String> {
+    let mut words = BTreeMap::new();
+    for section in elf.sections.iter().filter(|s| s.is_data() && s.size > 0) {
+        if section.addr < STACK_START {
+            return Err(format!(
+                "Data at 0x{:x} overlaps with the stack, which starts at 0x{STACK_START:x}.",
+                section.addr
+            ));
+        }
+        for (i, byte) in section.contents.iter().enumerate() {
+            let addr = section.addr + i as u32;
+            *words.entry(addr & !3).or_default() |= (*byte as u32) << (8 * (addr & 3));
+        }
+    }
+    words.retain(|_, value| *value != 0);
+    Ok(words)
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// A decoded instruction, compressed instructions are expanded.
+/// The fields that the instruction does not use are zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Instruction {
+    addr: u32,
+    name: &'static str,
+    rd: u8,
+    rs1: u8,
+    rs2: u8,
+    imm: i32,
+}
+
+fn decode_section(start: u32, bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+    while offset + 2 <= bytes.len() {
+        let addr = start + offset as u32;
+        let low = u16_at(bytes, offset) as u32;
+        let instruction = if low & 3 == 3 {
+            if offset + 4 > bytes.len() {
+                return Err(format!("Truncated instruction at 0x{addr:x}: 0x{low:x}"));
+            }
+            let encoding = u32_at(bytes, offset);
+            offset += 4;
+            decode(encoding)
+        } else {
+            offset += 2;
+            decode_compressed(low)
+        };
+        let instruction = instruction
+            .ok_or_else(|| format!("Unsupported instruction at 0x{addr:x}: 0x{low:x}"))?;
+        instructions.push(Instruction {
+            addr,
+            ..instruction
+        });
+    }
+    Ok(instructions)
+}
+
+fn bits(encoding: u32, high: u32, low: u32) -> u32 {
+    (encoding >> low) & ((1 << (high - low + 1)) - 1)
+}
+
+/// Sign-extends the lowest `width` bits of `value`.
+fn sign_extend(value: u32, width: u32) -> i32 {
+    ((value << (32 - width)) as i32) >> (32 - width)
+}
+
+fn instruction(name: &'static str, rd: u32, rs1: u32, rs2: u32, imm: i32) -> Option<Instruction> {
+    Some(Instruction {
+        addr: 0,
+        name,
+        rd: rd as u8,
+        rs1: rs1 as u8,
+        rs2: rs2 as u8,
+        imm,
+    })
+}
+
+/// Decodes a 32 bit instruction.
+fn decode(encoding: u32) -> Option<Instruction> {
+    let rd = bits(encoding, 11, 7);
+    let rs1 = bits(encoding, 19, 15);
+    let rs2 = bits(encoding, 24, 20);
+    let funct3 = bits(encoding, 14, 12);
+    let funct7 = bits(encoding, 31, 25);
+    let i_imm = sign_extend(bits(encoding, 31, 20), 12);
+    let s_imm = sign_extend((funct7 << 5) | rd, 12);
+    let b_imm = sign_extend(
+        (bits(encoding, 31, 31) << 12)
+            | (bits(encoding, 7, 7) << 11)
+            | (bits(encoding, 30, 25) << 5)
+            | (bits(encoding, 11, 8) << 1),
+        13,
+    );
+    let j_imm = sign_extend(
+        (bits(encoding, 31, 31) << 20)
+            | (bits(encoding, 19, 12) << 12)
+            | (bits(encoding, 20, 20) << 11)
+            | (bits(encoding, 30, 21) << 1),
+        21,
+    );
+    let u_imm = (encoding & 0xfffff000) as i32;
+    match bits(encoding, 6, 0) {
+        0b0110111 => instruction("lui", rd, 0, 0, u_imm),
+        0b0010111 => instruction("auipc", rd, 0, 0, u_imm),
+        0b1101111 => instruction("jal", rd, 0, 0, j_imm),
+        0b1100111 if funct3 == 0 => instruction("jalr", rd, rs1, 0, i_imm),
+        0b1100011 => {
+            let name = match funct3 {
+                0b000 => "beq",
+                0b001 => "bne",
+                0b100 => "blt",
+                0b101 => "bge",
+                0b110 => "bltu",
+                0b111 => "bgeu",
+                _ => return None,
+            };
+            instruction(name, 0, rs1, rs2, b_imm)
+        }
+        0b0000011 => {
+            let name = match funct3 {
+                0b000 => "lb",
+                0b001 => "lh",
+                0b010 => "lw",
+                0b100 => "lbu",
+                0b101 => "lhu",
+                _ => return None,
+            };
+            instruction(name, rd, rs1, 0, i_imm)
+        }
+        0b0100011 => {
+            let name = match funct3 {
+                0b000 => "sb",
+                0b001 => "sh",
+                0b010 => "sw",
+                _ => return None,
+            };
+            instruction(name, 0, rs1, rs2, s_imm)
+        }
+        0b0010011 => {
+            let shamt = rs2 as i32;
+            match (funct3, funct7) {
+                (0b000, _) => instruction("addi", rd, rs1, 0, i_imm),
+                (0b010, _) => instruction("slti", rd, rs1, 0, i_imm),
+                (0b011, _) => instruction("sltiu", rd, rs1, 0, i_imm),
+                (0b100, _) => instruction("xori", rd, rs1, 0, i_imm),
+                (0b110, _) => instruction("ori", rd, rs1, 0, i_imm),
+                (0b111, _) => instruction("andi", rd, rs1, 0, i_imm),
+                (0b001, 0b0000000) => instruction("slli", rd, rs1, 0, shamt),
+                (0b101, 0b0000000) => instruction("srli", rd, rs1, 0, shamt),
+                (0b101, 0b0100000) => instruction("srai", rd, rs1, 0, shamt),
+                _ => None,
+            }
+        }
+        0b0110011 => {
+            let name = match (funct7, funct3) {
+                (0b0000000, 0b000) => "add",
+                (0b0100000, 0b000) => "sub",
+                (0b0000000, 0b001) => "sll",
+                (0b0000000, 0b010) => "slt",
+                (0b0000000, 0b011) => "sltu",
+                (0b0000000, 0b100) => "xor",
+                (0b0000000, 0b101) => "srl",
+                (0b0100000, 0b101) => "sra",
+                (0b0000000, 0b110) => "or",
+                (0b0000000, 0b111) => "and",
+                (0b0000001, 0b000) => "mul",
+                (0b0000001, 0b001) => "mulh",
+                (0b0000001, 0b010) => "mulhsu",
+                (0b0000001, 0b011) => "mulhu",
+                (0b0000001, 0b100) => "div",
+                (0b0000001, 0b101) => "divu",
+                (0b0000001, 0b110) => "rem",
+                (0b0000001, 0b111) => "remu",
+                _ => return None,
+            };
+            instruction(name, rd, rs1, rs2, 0)
+        }
+        0b0001111 => match funct3 {
+            0b000 => instruction("fence", 0, 0, 0, 0),
+            0b001 => instruction("fence.i", 0, 0, 0, 0),
+            _ => None,
+        },
+        0b1110011 => match encoding {
+            0x00000073 => instruction("ecall", 0, 0, 0, 0),
+            0x00100073 => instruction("ebreak", 0, 0, 0, 0),
+            // `csrrw x0, cycle, x0`, which is what the assembler emits for `unimp`.
+            0xc0001073 => instruction("unimp", 0, 0, 0, 0),
+            _ => None,
+        },
+        0b0101111 if funct3 == 0b010 => {
+            let name = match (bits(encoding, 31, 27), bits(encoding, 26, 25)) {
+                (0b00010, 0b00) => "lr.w",
+                (0b00010, 0b10) => "lr.w.aq",
+                (0b00010, 0b01) => "lr.w.rl",
+                (0b00010, 0b11) => "lr.w.aqrl",
+                (0b00011, 0b00) => "sc.w",
+                (0b00011, 0b10) => "sc.w.aq",
+                (0b00011, 0b01) => "sc.w.rl",
+                (0b00011, 0b11) => "sc.w.aqrl",
+                (0b00000, 0b00) => "amoadd.w",
+                (0b00000, 0b10) => "amoadd.w.aq",
+                (0b00000, 0b01) => "amoadd.w.rl",
+                (0b00000, 0b11) => "amoadd.w.aqrl",
+                _ => return None,
+            };
+            instruction(name, rd, rs1, rs2, 0)
+        }
+        _ => None,
+    }
+}
+
+/// Decodes a 16 bit instruction of the "C" extension to the instruction it expands to.
+fn decode_compressed(encoding: u32) -> Option<Instruction> {
+    let b = |high, low| bits(encoding, high, low);
+    // The registers x8 to x15 of the three bit register fields.
+    let rd_short = b(4, 2) + 8;
+    let rs1_short = b(9, 7) + 8;
+    let rd = b(11, 7);
+    let rs2 = b(6, 2);
+    let imm6 = sign_extend((b(12, 12) << 5) | b(6, 2), 6);
+    let j_imm = sign_extend(
+        (b(12, 12) << 11)
+            | (b(8, 8) << 10)
+            | (b(10, 9) << 8)
+            | (b(6, 6) << 7)
+            | (b(7, 7) << 6)
+            | (b(2, 2) << 5)
+            | (b(11, 11) << 4)
+            | (b(5, 3) << 1),
+        12,
+    );
+    let b_imm = sign_extend(
+        (b(12, 12) << 8) | (b(6, 5) << 6) | (b(2, 2) << 5) | (b(11, 10) << 3) | (b(4, 3) << 1),
+        9,
+    );
+    let word_offset = ((b(5, 5) << 6) | (b(12, 10) << 3) | (b(6, 6) << 2)) as i32;
+    match (b(1, 0), b(15, 13)) {
+        _ if encoding == 0 => instruction("unimp", 0, 0, 0, 0),
+        (0b00, 0b000) => {
+            let imm = (b(10, 7) << 6) | (b(12, 11) << 4) | (b(5, 5) << 3) | (b(6, 6) << 2);
+            instruction("addi", rd_short, 2, 0, imm as i32)
+        }
+        (0b00, 0b010) => instruction("lw", rd_short, rs1_short, 0, word_offset),
+        (0b00, 0b110) => instruction("sw", 0, rs1_short, rd_short, word_offset),
+        (0b01, 0b000) => instruction("addi", rd, rd, 0, imm6),
+        (0b01, 0b001) => instruction("jal", 1, 0, 0, j_imm),
+        (0b01, 0b010) => instruction("addi", rd, 0, 0, imm6),
+        (0b01, 0b011) if rd == 2 => {
+            let imm = (b(12, 12) << 9)
+                | (b(4, 3) << 7)
+                | (b(5, 5) << 6)
+                | (b(2, 2) << 5)
+                | (b(6, 6) << 4);
+            instruction("addi", 2, 2, 0, sign_extend(imm, 10))
+        }
+        (0b01, 0b011) => instruction("lui", rd, 0, 0, imm6 << 12),
+        (0b01, 0b100) => {
+            let rd = rs1_short;
+            let shamt = ((b(12, 12) << 5) | b(6, 2)) as i32;
+            match (b(11, 10), b(12, 12), b(6, 5)) {
+                (0b00, _, _) => instruction("srli", rd, rd, 0, shamt),
+                (0b01, _, _) => instruction("srai", rd, rd, 0, shamt),
+                (0b10, _, _) => instruction("andi", rd, rd, 0, imm6),
+                (0b11, 0, 0b00) => instruction("sub", rd, rd, rd_short, 0),
+                (0b11, 0, 0b01) => instruction("xor", rd, rd, rd_short, 0),
+                (0b11, 0, 0b10) => instruction("or", rd, rd, rd_short, 0),
+                (0b11, 0, 0b11) => instruction("and", rd, rd, rd_short, 0),
+                _ => None,
+            }
+        }
+        (0b01, 0b101) => instruction("jal", 0, 0, 0, j_imm),
+        (0b01, 0b110) => instruction("beq", 0, rs1_short, 0, b_imm),
+        (0b01, 0b111) => instruction("bne", 0, rs1_short, 0, b_imm),
+        (0b10, 0b000) => instruction("slli", rd, rd, 0, ((b(12, 12) << 5) | rs2) as i32),
+        (0b10, 0b010) => {
+            let imm = (b(3, 2) << 6) | (b(12, 12) << 5) | (b(6, 4) << 2);
+            instruction("lw", rd, 2, 0, imm as i32)
+        }
+        (0b10, 0b100) => match (b(12, 12), rd, rs2) {
+            (0, _, 0) => instruction("jalr", 0, rd, 0, 0),
+            (0, _, _) => instruction("add", rd, 0, rs2, 0),
+            (1, 0, 0) => instruction("ebreak", 0, 0, 0, 0),
+            (1, _, 0) => instruction("jalr", 1, rd, 0, 0),
+            (1, _, _) => instruction("add", rd, rd, rs2, 0),
+            _ => None,
+        },
+        (0b10, 0b110) => {
+            let imm = (b(8, 7) << 6) | (b(12, 9) << 2);
+            instruction("sw", 0, 2, rs2, imm as i32)
+        }
+        _ => None,
+    }
+}
+
+/// Translates the decoded instructions to RISC-V statements in the form the
+/// textual assembly is parsed to, with labels for all targets of jumps.
+///
+/// `auipc` and `lui` are translated to loading the absolute value. Combined with
+/// the next `jalr`, `auipc` becomes a call or a tail call, and combined with `addi`,
+/// both become a `load_dynamic` of a label if they compute the address of a function.
+fn translate_code(
+    code: &[Instruction],
+    labels: &BTreeMap<u32, String>,
+    functions: &BTreeSet<u32>,
+) -> Vec<Statement> {
+    let targets = code
+        .iter()
+        .filter_map(|i| match i.name {
+            "jal" | "beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu" => {
+                Some(i.addr.wrapping_add(i.imm as u32))
+            }
+            _ => None,
+        })
+        .chain(labels.keys().cloned())
+        .chain(functions.iter().cloned())
+        .collect::<BTreeSet<_>>();
+
+    let reg = |r: u8| Argument::Register(Register::new(r));
+    let number = |n: i64| Argument::Expression(Expression::Number(n));
+    let symbol = |addr: u32| Argument::Expression(Expression::Symbol(label(labels, addr)));
+    let offset = |imm: i32, r: u8| {
+        Argument::RegOffset(Some(Expression::Number(imm as i64)), Register::new(r))
+    };
+    let statement = |name: &str, args| Statement::Instruction(name.to_string(), args);
+
+    let mut statements = vec![];
+    let mut i = 0;
+    while i < code.len() {
+        let current = code[i];
+        if targets.contains(&current.addr) {
+            statements.push(Statement::Label(label(labels, current.addr)));
+        }
+        let next = code.get(i + 1).filter(|next| !targets.contains(&next.addr));
+        let upper = match current.name {
+            "auipc" => Some(current.addr.wrapping_add(current.imm as u32)),
+            "lui" => Some(current.imm as u32),
+            _ => None,
+        };
+        let combined = match (upper, next) {
+            (Some(upper), Some(next)) if next.rs1 == current.rd && current.rd != 0 => {
+                let value = upper.wrapping_add(next.imm as u32);
+                match next.name {
+                    "jalr" if current.name == "auipc" && next.rd == 1 => {
+                        Some(statement("call", vec![symbol(value)]))
+                    }
+                    "jalr" if current.name == "auipc" && next.rd == 0 => {
+                        Some(statement("tail", vec![symbol(value)]))
+                    }
+                    "addi" if next.rd == current.rd && functions.contains(&value) => {
+                        Some(statement("load_dynamic", vec![reg(next.rd), symbol(value)]))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(combined) = combined {
+            statements.push(combined);
+            i += 2;
+            continue;
+        }
+
+        let Instruction {
+            addr,
+            name,
+            rd,
+            rs1,
+            rs2,
+            imm,
+        } = current;
+        statements.push(match name {
+            "auipc" | "lui" => statement("li", vec![reg(rd), number(upper.unwrap() as i64)]),
+            "jal" => match rd {
+                0 => statement("j", vec![symbol(addr.wrapping_add(imm as u32))]),
+                1 => statement("call", vec![symbol(addr.wrapping_add(imm as u32))]),
+                _ => panic!("Unsupported jal with link register x{rd} at 0x{addr:x}."),
+            },
+            "jalr" => match (rd, rs1, imm) {
+                (0, 1, 0) => statement("ret", vec![]),
+                (0, _, 0) => statement("jr", vec![reg(rs1)]),
+                (1, _, 0) => statement("jalr", vec![reg(rs1)]),
+                _ => panic!("Unsupported jalr at 0x{addr:x}."),
+            },
+            "beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu" => statement(
+                name,
+                vec![reg(rs1), reg(rs2), symbol(addr.wrapping_add(imm as u32))],
+            ),
+            "lb" | "lh" | "lw" | "lbu" | "lhu" => statement(name, vec![reg(rd), offset(imm, rs1)]),
+            "sb" | "sh" | "sw" => statement(name, vec![reg(rs2), offset(imm, rs1)]),
+            "addi" | "slti" | "sltiu" | "xori" | "ori" | "andi" | "slli" | "srli" | "srai" => {
+                statement(name, vec![reg(rd), reg(rs1), number(imm as i64)])
+            }
+            "fence" | "fence.i" | "ecall" | "ebreak" | "unimp" => statement(name, vec![]),
+            _ if name.starts_with("lr.w") => statement(name, vec![reg(rd), offset(0, rs1)]),
+            _ if name.starts_with("sc.w") || name.starts_with("amoadd.w") => {
+                statement(name, vec![reg(rd), reg(rs2), offset(0, rs1)])
+            }
+            _ => statement(name, vec![reg(rd), reg(rs1), reg(rs2)]),
+        });
+        i += 1;
+    }
+    statements
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decoded(name: &'static str, rd: u8, rs1: u8, rs2: u8, imm: i32) -> Option<Instruction> {
+        Some(Instruction {
+            addr: 0,
+            name,
+            rd,
+            rs1,
+            rs2,
+            imm,
+        })
+    }
+
+    #[test]
+    fn decode_instructions() {
+        assert_eq!(decode(0x11e52583), decoded("lw", 11, 10, 0, 286));
+        assert_eq!(decode(0x030080e7), decoded("jalr", 1, 1, 0, 48));
+        assert_eq!(decode(0x00b58633), decoded("add", 12, 11, 11, 0));
+        assert_eq!(decode(0x00012537), decoded("lui", 10, 0, 0, 0x12000));
+        assert_eq!(decode(0xfe0718e3), decoded("bne", 0, 14, 0, -16));
+        assert_eq!(decode(0x0000_0073), decoded("ecall", 0, 0, 0, 0));
+        assert_eq!(decode(0x0000_1073), None);
+    }
+
+    #[test]
+    fn decode_compressed_instructions() {
+        assert_eq!(decode_compressed(0x1141), decoded("addi", 2, 2, 0, -16));
+        assert_eq!(decode_compressed(0xc606), decoded("sw", 0, 2, 1, 12));
+        assert_eq!(decode_compressed(0x4415), decoded("addi", 8, 0, 0, 5));
+        assert_eq!(decode_compressed(0xfc75), decoded("bne", 0, 8, 0, -4));
+        assert_eq!(decode_compressed(0x95b2), decoded("add", 11, 11, 12, 0));
+        assert_eq!(decode_compressed(0x8082), decoded("jalr", 0, 1, 0, 0));
+        assert_eq!(decode_compressed(0x0000), decoded("unimp", 0, 0, 0, 0));
+    }
+
+    /// An ELF header for RISC-V with `section_count` section headers directly after it.
+    fn elf_header(section_count: u16) -> Vec<u8> {
+        let mut header = vec![0u8; 52];
+        header[..7].copy_from_slice(b"\x7fELF\x01\x01\x01");
+        header[16..20].copy_from_slice(&[2, 0, 243, 0]);
+        header[20..24].copy_from_slice(&1u32.to_le_bytes());
+        header[32..36].copy_from_slice(&52u32.to_le_bytes());
+        header[40..42].copy_from_slice(&52u16.to_le_bytes());
+        header[46..48].copy_from_slice(&40u16.to_le_bytes());
+        header[48..50].copy_from_slice(&section_count.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn invalid_elf_files() {
+        let coprocessors = CoProcessors::base();
+        let translate = |bytes: &[u8]| translate(bytes, &coprocessors, false).unwrap_err();
+
+        assert!(translate(b"not an ELF file").starts_with("Invalid ELF file"));
+        let mut other_machine = elf_header(0);
+        other_machine[18] = 62;
+        assert_eq!(translate(&other_machine), "Not a RISC-V ELF file.");
+        // The section headers are missing.
+        assert!(translate(&elf_header(2)).starts_with("Invalid ELF file"));
+
+        // A code section whose contents would end beyond the end of the file.
+        let mut elf = elf_header(2);
+        elf.extend([0u8; 40]);
+        let section = [
+            SHT_PROGBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            0x100,
+            0xffff_fff0,
+            0x100,
+        ];
+        elf.extend(section.iter().flat_map(|v| v.to_le_bytes()));
+        elf.extend([0u8; 20]);
+        assert!(translate(&elf).starts_with("Invalid ELF file"));
+
+        // A half instruction at the end of the code.
+        assert_eq!(
+            decode_section(0x100, &[0x13, 0x05]).unwrap_err(),
+            "Truncated instruction at 0x100: 0x513"
+        );
+    }
+
+    #[test]
+    fn translate_calls_and_branches() {
+        // auipc ra, 0; jalr 8(ra); c.li s0, 5 (with a gap); c.bnez s0, -4; c.ret
+        let code = [0x00000097, 0x008080e7]
+            .into_iter()
+            .map(|e| decode(e).unwrap())
+            .chain([0x4415, 0xfc75, 0x8082].map(|e| decode_compressed(e).unwrap()))
+            .zip([0x100, 0x104, 0x108, 0x10c, 0x10e])
+            .map(|(i, addr)| Instruction { addr, ..i })
+            .collect::<Vec<_>>();
+        let labels = [(0x100, "f".to_string())].into();
+        let statements = translate_code(&code, &labels, &BTreeSet::new());
+        assert_eq!(
+            statements.iter().map(|s| s.to_string()).collect::<String>(),
+            "f:\n  call __elf_108\n__elf_108:\n  addi x8, x0, 5\n  bne x8, x0, __elf_108\n  ret \n"
+        );
+    }
+}
@@ -5,10 +5,12 @@ use std::{
     collections::BTreeMap,
     ffi::OsStr,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Stdio},
 };
 
 use mktemp::Temp;
+use parser_util::paths::{existing_target_file, file_stem};
+use parser_util::provenance::Provenance;
 use serde_json::Value as JsonValue;
 use std::fs;
 
@@ -19,6 +21,7 @@ pub mod bootloader;
 pub mod compiler;
 mod coprocessors;
 mod disambiguator;
+pub mod elf;
 pub mod parser;
 
 type Statement = asm_utils::ast::Statement<Register, FunctionKind>;
@@ -27,6 +30,8 @@ type Expression = asm_utils::ast::Expression<FunctionKind>;
 
 /// Compiles a rust file all the way down to PIL and generates
 /// fixed and witness columns.
+/// If `via_elf` is set, the program is linked and the ELF file is translated
+/// instead of the textual assembly.
 #[allow(clippy::print_stderr)]
 pub fn compile_rust(
     file_name: &str,
@@ -34,32 +39,63 @@ pub fn compile_rust(
     force_overwrite: bool,
     coprocessors: &CoProcessors,
     with_bootloader: bool,
+    via_elf: bool,
 ) -> Option<(PathBuf, String)> {
-    let riscv_asm = if file_name.ends_with("Cargo.toml") {
-        compile_rust_crate_to_riscv_asm(file_name, output_dir)
+    let manifest = if file_name.ends_with("Cargo.toml") {
+        Some(file_name.to_string())
     } else if fs::metadata(file_name).unwrap().is_dir() {
-        compile_rust_crate_to_riscv_asm(&format!("{file_name}/Cargo.toml"), output_dir)
+        Some(
+            Path::new(file_name)
+                .join("Cargo.toml")
+                .to_string_lossy()
+                .to_string(),
+        )
     } else {
-        compile_rust_to_riscv_asm(file_name, output_dir)
+        None
     };
     if !output_dir.exists() {
         fs::create_dir_all(output_dir).unwrap()
     }
+
+    if via_elf {
+        let elf = match &manifest {
+            Some(manifest) => compile_rust_crate_to_riscv_elf(manifest, output_dir, coprocessors),
+            None => compile_rust_to_riscv_elf(file_name, output_dir, coprocessors),
+        };
+        return write_powdr_asm(
+            file_name,
+            output_dir,
+            force_overwrite,
+            coprocessors,
+            &rust_input_files(Path::new(file_name)),
+            || {
+                elf::translate(&elf, coprocessors, with_bootloader)
+                    .unwrap_or_else(|e| panic!("Could not translate {file_name}: {e}"))
+            },
+        );
+    }
+
+    let riscv_asm = match &manifest {
+        Some(manifest) => compile_rust_crate_to_riscv_asm(manifest, output_dir),
+        None => compile_rust_to_riscv_asm(file_name, output_dir),
+    };
     for (asm_file_name, contents) in &riscv_asm {
         let riscv_asm_file_name = output_dir.join(format!(
             "{}_riscv_{asm_file_name}.asm",
-            Path::new(file_name).file_stem().unwrap().to_str().unwrap(),
+            file_stem(Path::new(file_name)),
         ));
-        if riscv_asm_file_name.exists() && !force_overwrite {
-            eprintln!(
-                "Target file {} already exists. Not overwriting.",
-                riscv_asm_file_name.to_str().unwrap()
-            );
-            return None;
+        if let Some(existing) = existing_target_file(&riscv_asm_file_name) {
+            if !force_overwrite {
+                eprintln!(
+                    "Target file {} already exists. Not overwriting.",
+                    existing.display()
+                );
+                return None;
+            }
         }
 
         fs::write(riscv_asm_file_name.clone(), contents).unwrap();
-        log::info!("Wrote {}", riscv_asm_file_name.to_str().unwrap());
+        log::info!("Wrote {}", riscv_asm_file_name.display());
     }
 
     compile_riscv_asm_bundle(
@@ -69,10 +105,46 @@ pub fn compile_rust(
         force_overwrite,
         coprocessors,
         with_bootloader,
+        &rust_input_files(Path::new(file_name)),
     )
 }
 
-#[allow(clippy::print_stderr)]
+/// Returns the source files of the guest program at `path` (a Rust file,
+/// a crate directory or its `Cargo.toml`) to be recorded in the provenance header.
+fn rust_input_files(path: &Path) -> Vec<PathBuf> {
+    let crate_dir = if path.ends_with("Cargo.toml") {
+        path.parent().unwrap()
+    } else if path.is_dir() {
+        path
+    } else {
+        return vec![path.to_path_buf()];
+    };
+    let mut files = vec![crate_dir.join("Cargo.toml")];
+    collect_files(&crate_dir.join("src"), &mut files);
+    files
+}
+
+/// Adds all files in `dir` and its subdirectories to `files`, in a stable order.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries = entries
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files);
+        } else {
+            files.push(entry);
+        }
+    }
+}
+
+/// Compiles RISC-V assembly to powdr-asm and writes it to the output directory.
+/// If `input_files` is not empty, the powdr-asm starts with a provenance header
+/// recording them.
 pub fn compile_riscv_asm_bundle(
     original_file_name: &str,
     riscv_asm_files: BTreeMap<String, String>,
@@ -80,27 +152,81 @@ pub fn compile_riscv_asm_bundle(
     force_overwrite: bool,
     coprocessors: &CoProcessors,
     with_bootloader: bool,
+    input_files: &[PathBuf],
 ) -> Option<(PathBuf, String)> {
-    let powdr_asm_file_name = output_dir.join(format!(
-        "{}.asm",
-        Path::new(original_file_name)
-            .file_stem()
-            .unwrap()
-            .to_str()
-            .unwrap()
-    ));
-    if powdr_asm_file_name.exists() && !force_overwrite {
-        eprintln!(
-            "Target file {} already exists. Not overwriting.",
-            powdr_asm_file_name.to_str().unwrap()
-        );
-        return None;
+    write_powdr_asm(
+        original_file_name,
+        output_dir,
+        force_overwrite,
+        coprocessors,
+        input_files,
+        || compiler::compile(riscv_asm_files, coprocessors, with_bootloader),
+    )
+}
+
+/// Translates a linked RISC-V ELF file to powdr-asm and writes it to the output directory.
+pub fn compile_riscv_elf(
+    original_file_name: &str,
+    elf_file: &Path,
+    output_dir: &Path,
+    force_overwrite: bool,
+    coprocessors: &CoProcessors,
+    with_bootloader: bool,
+) -> Option<(PathBuf, String)> {
+    let elf = fs::read(elf_file).unwrap();
+    write_powdr_asm(
+        original_file_name,
+        output_dir,
+        force_overwrite,
+        coprocessors,
+        &[elf_file.to_path_buf()],
+        || {
+            elf::translate(&elf, coprocessors, with_bootloader)
+                .unwrap_or_else(|e| panic!("Could not translate {}: {e}", elf_file.display()))
+        },
+    )
+}
+
+/// Writes the powdr-asm returned by `compile` to the output directory, see
+/// [compile_riscv_asm_bundle].
+#[allow(clippy::print_stderr)]
+fn write_powdr_asm(
+    original_file_name: &str,
+    output_dir: &Path,
+    force_overwrite: bool,
+    coprocessors: &CoProcessors,
+    input_files: &[PathBuf],
+    compile: impl FnOnce() -> String,
+) -> Option<(PathBuf, String)> {
+    let powdr_asm_file_name =
+        output_dir.join(format!("{}.asm", file_stem(Path::new(original_file_name))));
+    if let Some(existing) = existing_target_file(&powdr_asm_file_name) {
+        if !force_overwrite {
+            eprintln!(
+                "Target file {} already exists. Not overwriting.",
+                existing.display()
+            );
+            return None;
+        }
     }
 
-    let powdr_asm = compiler::compile(riscv_asm_files, coprocessors, with_bootloader);
+    let mut powdr_asm = compile();
+    if !input_files.is_empty() {
+        let inputs = input_files
+            .iter()
+            .map(|file| (file.display().to_string(), fs::read(file).unwrap()))
+            .collect::<Vec<_>>();
+        let provenance = Provenance::new(
+            inputs
+                .iter()
+                .map(|(name, contents)| (name.clone(), contents.as_slice())),
+        )
+        .with_coprocessors(coprocessors.names());
+        powdr_asm = provenance.prepend_to(&powdr_asm);
+    }
 
     fs::write(powdr_asm_file_name.clone(), &powdr_asm).unwrap();
-    log::info!("Wrote {}", powdr_asm_file_name.to_str().unwrap());
+    log::info!("Wrote {}", powdr_asm_file_name.display());
 
     Some((powdr_asm_file_name, powdr_asm))
 }
@@ -115,22 +241,49 @@ pub fn compile_riscv_asm(
     coprocessors: &CoProcessors,
     with_bootloader: bool,
 ) -> Option<(PathBuf, String)> {
+    let riscv_asm_files = file_names
+        .map(|name| {
+            let contents = fs::read_to_string(&name).unwrap();
+            (name, contents)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let input_files = riscv_asm_files
+        .keys()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
     compile_riscv_asm_bundle(
         original_file_name,
-        file_names
-            .map(|name| {
-                let contents = fs::read_to_string(&name).unwrap();
-                (name, contents)
-            })
-            .collect(),
+        riscv_asm_files,
         output_dir,
         force_overwrite,
         coprocessors,
         with_bootloader,
+        &input_files,
     )
 }
 
 pub fn compile_rust_to_riscv_asm(input_file: &str, output_dir: &Path) -> BTreeMap<String, String> {
+    let crate_dir = rust_file_to_crate(input_file);
+    compile_rust_crate_to_riscv_asm(&crate_dir.join("Cargo.toml").to_string_lossy(), output_dir)
+}
+
+/// Like [compile_rust_to_riscv_asm], but links the program to a RISC-V ELF file,
+/// see [compile_rust_crate_to_riscv_elf].
+pub fn compile_rust_to_riscv_elf(
+    input_file: &str,
+    output_dir: &Path,
+    coprocessors: &CoProcessors,
+) -> Vec<u8> {
+    let crate_dir = rust_file_to_crate(input_file);
+    compile_rust_crate_to_riscv_elf(
+        &crate_dir.join("Cargo.toml").to_string_lossy(),
+        output_dir,
+        coprocessors,
+    )
+}
+
+/// Creates a temporary crate with the rust file as library that depends on the runtime.
+fn rust_file_to_crate(input_file: &str) -> Temp {
     let crate_dir = Temp::new_dir().unwrap();
     // TODO is there no easier way?
     let mut cargo_file = crate_dir.clone();
@@ -147,7 +300,7 @@ edition = "2021"
 [dependencies]
 runtime = {{ path = "./runtime" }}
             "#,
-            Path::new(input_file).file_stem().unwrap().to_str().unwrap()
+            file_stem(Path::new(input_file))
         ),
     )
     .unwrap();
@@ -195,7 +348,11 @@ runtime = {{ path = "./runtime" }}
     )
     .unwrap();
 
-    compile_rust_crate_to_riscv_asm(cargo_file.to_str().unwrap(), output_dir)
+    let mut hash_file = runtime_file.clone();
+    hash_file.push("hash.rs");
+    fs::write(hash_file, include_bytes!("../runtime/src/hash.rs")).unwrap();
+
+    crate_dir
 }
 
 macro_rules! as_ref [
@@ -244,13 +401,123 @@ pub fn compile_rust_crate_to_riscv_asm(
     assemblies
 }
 
+/// The toolchain used to compile to RISC-V.
+const TOOLCHAIN: &str = "+nightly-2023-01-03";
+
+/// Where the runtime functions of the coprocessors are placed in the ELF file.
+/// They are never executed: the calls to them are replaced by coprocessor instructions.
+const COPROCESSOR_FUNCTIONS_START: usize = 0x1000;
+
+/// Compiles the crate with the manifest `input_dir` as static library and links it
+/// to a RISC-V ELF file, which is returned.
+pub fn compile_rust_crate_to_riscv_elf(
+    input_dir: &str,
+    output_dir: &Path,
+    coprocessors: &CoProcessors,
+) -> Vec<u8> {
+    let target_dir = output_dir.join("cargo_target_elf");
+    let output = build_cargo_staticlib_command(input_dir, &target_dir)
+        .stderr(Stdio::inherit())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let library = static_library_from_cargo_messages(&output.stdout);
+
+    let elf_file = library.with_extension("elf");
+    let link_status = Command::new(rust_lld())
+        .args(["-flavor", "gnu", "--gc-sections"])
+        .args(["--entry=__runtime_start", "--undefined=__runtime_start"])
+        .args(
+            coprocessors
+                .runtime_names()
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    format!(
+                        "--defsym={name}=0x{:x}",
+                        COPROCESSOR_FUNCTIONS_START + 0x10 * i
+                    )
+                }),
+        )
+        .arg("-o")
+        .arg(&elf_file)
+        .arg(&library)
+        .status()
+        .unwrap();
+    assert!(link_status.success());
+    log::info!("Wrote {}", elf_file.display());
+
+    fs::read(elf_file).unwrap()
+}
+
+fn build_cargo_staticlib_command(input_dir: &str, target_dir: &Path) -> Command {
+    let mut cmd = Command::new("cargo");
+    cmd.args(as_ref![
+        OsStr;
+        TOOLCHAIN,
+        "rustc",
+        "--release",
+        "-Z",
+        "build-std=core,alloc",
+        "-Z",
+        "build-std-features=compiler-builtins-mem",
+        "--target",
+        "riscv32imac-unknown-none-elf",
+        "--lib",
+        "--crate-type",
+        "staticlib",
+        "--message-format",
+        "json",
+        "--target-dir",
+        target_dir,
+        "--manifest-path",
+        input_dir,
+    ]);
+    cmd
+}
+
+/// Returns the static library built by cargo from its JSON messages.
+fn static_library_from_cargo_messages(messages: &[u8]) -> PathBuf {
+    serde_json::Deserializer::from_slice(messages)
+        .into_iter::<JsonValue>()
+        .map(|message| message.unwrap())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .filter_map(|message| {
+            let JsonValue::Array(filenames) = &message["filenames"] else {
+                return None;
+            };
+            filenames
+                .iter()
+                .filter_map(|f| f.as_str())
+                .find(|f| f.ends_with(".a"))
+                .map(PathBuf::from)
+        })
+        .last()
+        .expect("cargo did not build a static library")
+}
+
+/// The path of the linker shipped with the toolchain.
+fn rust_lld() -> PathBuf {
+    let output = Command::new("rustc")
+        .args([TOOLCHAIN, "--print", "sysroot"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let sysroot = PathBuf::from(String::from_utf8(output.stdout).unwrap().trim());
+    fs::read_dir(sysroot.join("lib").join("rustlib"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path().join("bin").join("rust-lld"))
+        .find(|path| path.exists())
+        .expect("rust-lld not found in the toolchain")
+}
+
 fn build_cargo_command(input_dir: &str, target_dir: &Path, produce_build_plan: bool) -> Command {
     let mut cmd = Command::new("cargo");
     cmd.env("RUSTFLAGS", "--emit=asm -g");
 
     let args = as_ref![
         OsStr;
-        "+nightly-2023-01-03",
+        TOOLCHAIN,
         "build",
         "--release",
         "-Z",
@@ -301,16 +568,15 @@ fn output_files_from_cargo_build_plan(
             // Strip the target_dir, so that the path becomes relative.
             let parent = output.parent().unwrap().strip_prefix(target_dir).unwrap();
             if Some(OsStr::new("rmeta")) == output.extension()
-                && parent.ends_with("riscv32imac-unknown-none-elf/release/deps")
+                && parent.ends_with(
+                    Path::new("riscv32imac-unknown-none-elf")
+                        .join("release")
+                        .join("deps"),
+                )
             {
                 // Have to convert to string to remove the "lib" prefix:
-                let name_stem = output
-                    .file_stem()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .strip_prefix("lib")
-                    .unwrap();
+                let name_stem = file_stem(output);
+                let name_stem = name_stem.strip_prefix("lib").unwrap();
 
                 let mut asm_name = parent.join(name_stem);
                 asm_name.set_extension("s");
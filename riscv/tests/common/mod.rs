@@ -1,6 +1,6 @@
 use compiler::{
     compile_asm_string, verify, write_commits_to_fs, write_constants_to_fs,
-    write_constraints_to_fs, BackendType,
+    write_constraints_to_fs, BackendType, CancellationToken,
 };
 use number::GoldilocksField;
 use riscv::bootloader::default_input;
@@ -30,6 +30,8 @@ pub fn verify_riscv_asm_string(file_name: &str, contents: &str, inputs: Vec<Gold
         Some(BackendType::PilStarkCli),
         vec![],
         None,
+        None,
+        &CancellationToken::default(),
     )
     .unwrap();
 
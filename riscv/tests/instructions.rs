@@ -19,3 +19,30 @@ mod instruction_tests {
 
     include!(concat!(env!("OUT_DIR"), "/instruction_tests.rs"));
 }
+
+/// Runs the tests of the multiplication and division instructions, which cover the
+/// edge cases (e.g. `0xffffffff * 0xffffffff`, division by zero and `-2**31 / -1`),
+/// in the executor only. The tests above also run witness generation, but are too slow.
+#[test]
+fn arith_instructions_in_executor() {
+    use number::GoldilocksField;
+    use riscv::compiler::compile;
+    use riscv::CoProcessors;
+
+    for name in [
+        "mul", "mulh", "mulhsu", "mulhu", "div", "divu", "rem", "remu",
+    ] {
+        let path = format!(
+            "{}/tests/instruction_tests/generated/{name}.S",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let assembly = std::fs::read_to_string(path).unwrap();
+        let powdr_asm = compile(
+            [(name.to_string(), assembly)].into(),
+            &CoProcessors::base(),
+            false,
+        );
+        // A failing test executes `unimp`, which panics.
+        riscv_executor::execute::<GoldilocksField>(&powdr_asm, &Default::default(), &[]);
+    }
+}
@@ -6,7 +6,7 @@ use mktemp::Temp;
 use number::GoldilocksField;
 use test_log::test;
 
-use riscv::CoProcessors;
+use riscv::{bootloader::default_input, CoProcessors};
 
 #[test]
 #[ignore = "Too slow"]
@@ -29,6 +29,13 @@ fn test_poseidon_gl() {
     verify_riscv_file(case, vec![], &CoProcessors::base().with_poseidon());
 }
 
+#[test]
+#[ignore = "Too slow"]
+fn test_keccakf() {
+    let case = "keccakf_via_coprocessor.rs";
+    verify_riscv_file(case, vec![], &CoProcessors::base().with_keccakf());
+}
+
 #[test]
 #[ignore = "Too slow"]
 fn test_sum() {
@@ -112,6 +119,34 @@ fn test_password() {
     verify_riscv_crate(case, vec![], &CoProcessors::base());
 }
 
+#[test]
+#[ignore = "Too slow"]
+fn test_sum_via_elf() {
+    let case = "sum.rs";
+    let inputs = [16, 4, 1, 2, 8, 5]
+        .iter()
+        .map(|&x| x.into())
+        .collect::<Vec<GoldilocksField>>();
+    let coprocessors = CoProcessors::base();
+    let temp_dir = Temp::new_dir().unwrap();
+    let file = format!("tests/riscv_data/{case}");
+
+    let riscv_asm = riscv::compile_rust_to_riscv_asm(&file, &temp_dir);
+    let via_asm = riscv::compiler::compile(riscv_asm, &coprocessors, false);
+    let elf = riscv::compile_rust_to_riscv_elf(&file, &temp_dir, &coprocessors);
+    let via_elf = riscv::elf::translate(&elf, &coprocessors, false).unwrap();
+
+    // The code and data are at different addresses, so we compare the registers
+    // that do not hold addresses at the end: the stack pointer and the saved registers.
+    let final_regs = |powdr_asm: &str| {
+        let inputs = [(0u32.into(), inputs.clone())].into();
+        let trace = riscv_executor::execute_with_trace(powdr_asm, &inputs, &default_input());
+        let regs = trace.final_regs();
+        [2, 8, 9, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27].map(|i| regs[format!("x{i}").as_str()])
+    };
+    assert_eq!(final_regs(&via_asm), final_regs(&via_elf));
+}
+
 /*
 mstore(0, 666)
 return(0, 32)
@@ -131,6 +166,26 @@ fn test_evm() {
     );
 }
 */
+#[test]
+fn test_println() {
+    let case = "println.rs";
+    let temp_dir = Temp::new_dir().unwrap();
+    let riscv_asm =
+        riscv::compile_rust_to_riscv_asm(&format!("tests/riscv_data/{case}"), &temp_dir);
+    let powdr_asm = riscv::compiler::compile(riscv_asm, &CoProcessors::base(), false);
+
+    let inputs = [(0u32.into(), vec![42u32.into()])].into();
+    let trace = riscv_executor::execute_with_trace::<GoldilocksField>(
+        &powdr_asm,
+        &inputs,
+        &default_input(),
+    );
+    assert_eq!(
+        String::from_utf8(trace.stdout).unwrap(),
+        "Hello, world!\nThe input is 42.\n"
+    );
+}
+
 #[test]
 #[ignore = "Too slow"]
 #[should_panic(expected = "Witness generation failed.")]
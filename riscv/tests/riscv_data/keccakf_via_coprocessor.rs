@@ -0,0 +1,41 @@
+#![no_std]
+
+use runtime::hash::keccak256;
+
+#[no_mangle]
+fn main() {
+    let h = keccak256(&[]);
+    assert_eq!(
+        h,
+        [
+            0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+            0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+            0x5d, 0x85, 0xa4, 0x70,
+        ]
+    );
+
+    let h = keccak256(b"abc");
+    assert_eq!(
+        h,
+        [
+            0x4e, 0x03, 0x65, 0x7a, 0xea, 0x45, 0xa9, 0x4f, 0xc7, 0xd4, 0x7b, 0xa8, 0x26, 0xc8,
+            0xd6, 0x67, 0xc0, 0xd1, 0xe6, 0xe3, 0x3a, 0x64, 0xa0, 0x36, 0xec, 0x44, 0xf5, 0x8f,
+            0xa1, 0x2d, 0x6c, 0x45,
+        ]
+    );
+
+    // More than one block of input
+    let mut data = [0u8; 200];
+    for (i, d) in data.iter_mut().enumerate() {
+        *d = i as u8;
+    }
+    let h = keccak256(&data);
+    assert_eq!(
+        h,
+        [
+            0xbf, 0xb0, 0xaa, 0x97, 0x86, 0x3e, 0x79, 0x79, 0x43, 0xcf, 0x7c, 0x33, 0xbb, 0x7e,
+            0x88, 0x0b, 0xb4, 0x54, 0x3f, 0x3d, 0x27, 0x03, 0xc0, 0x92, 0x3c, 0x69, 0x01, 0xc2,
+            0xaf, 0x57, 0xb8, 0x90,
+        ]
+    );
+}
@@ -0,0 +1,10 @@
+#![no_std]
+
+use runtime::{get_prover_input, println};
+
+#[no_mangle]
+pub fn main() {
+    println!("Hello, world!");
+    let input = get_prover_input(0);
+    println!("The input is {input}.");
+}
@@ -0,0 +1,166 @@
+/// Round constants of the iota step.
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rotation offsets of the rho step, indexed by x + 5 * y.
+const ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Applies the Keccak-f[1600] permutation to the state, whose lanes are indexed by x + 5 * y.
+/// It's equivalent to std::hash::keccakf::KeccakF from the Powdr standard library.
+pub fn keccakf(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // Theta
+        let c: [u64; 5] = std::array::from_fn(|x| (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]));
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+
+        // Rho and pi
+        let mut b = [0u64; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] =
+                    state[x + 5 * y].rotate_left(ROTATIONS[x + 5 * y]);
+            }
+        }
+
+        // Chi
+        for x in 0..5 {
+            for y in 0..5 {
+                state[x + 5 * y] =
+                    b[x + 5 * y] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+            }
+        }
+
+        // Iota
+        state[0] ^= round_constant;
+    }
+}
+
+/// Applies the permutation to a state given as 32-bit words, the lower half of each lane first.
+pub fn keccakf_words(words: &[u32]) -> [u32; 50] {
+    assert_eq!(words.len(), 50);
+    let mut state: [u64; 25] =
+        std::array::from_fn(|i| words[2 * i] as u64 | ((words[2 * i + 1] as u64) << 32));
+    keccakf(&mut state);
+    std::array::from_fn(|i| (state[i / 2] >> (32 * (i % 2))) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keccakf() {
+        // See test vectors at:
+        // https://github.com/XKCP/XKCP/blob/master/tests/TestVectors/KeccakF-1600-IntermediateValues.txt
+        let mut state = [0; 25];
+        keccakf(&mut state);
+        assert_eq!(
+            state,
+            [
+                0xf1258f7940e1dde7,
+                0x84d5ccf933c0478a,
+                0xd598261ea65aa9ee,
+                0xbd1547306f80494d,
+                0x8b284e056253d057,
+                0xff97a42d7f8e6fd4,
+                0x90fee5a0a44647c4,
+                0x8c5bda0cd6192e76,
+                0xad30a6f71b19059c,
+                0x30935ab7d08ffc64,
+                0xeb5aa93f2317d635,
+                0xa9a6e6260d712103,
+                0x81a57c16dbcf555f,
+                0x43b831cd0347c826,
+                0x01f22f1a11a5569f,
+                0x05e5635a21d9ae61,
+                0x64befef28cc970f2,
+                0x613670957bc46611,
+                0xb87c5a554fd00ecb,
+                0x8c3ee88a1ccf32c8,
+                0x940c7922ae3a2614,
+                0x1841f924a2c509e4,
+                0x16f53526e70465c2,
+                0x75f644e97f30a13b,
+                0xeaf1ff7b5ceca249,
+            ]
+        );
+
+        keccakf(&mut state);
+        assert_eq!(
+            state,
+            [
+                0x2d5c954df96ecb3c,
+                0x6a332cd07057b56d,
+                0x093d8d1270d76b6c,
+                0x8a20d9b25569d094,
+                0x4f9c4f99e5e7f156,
+                0xf957b9a2da65fb38,
+                0x85773dae1275af0d,
+                0xfaf4f247c3d810f7,
+                0x1f1b9ee6f79a8759,
+                0xe4fecc0fee98b425,
+                0x68ce61b6b9ce68a1,
+                0xdeea66c4ba8f974f,
+                0x33c43d836eafb1f5,
+                0xe00654042719dbd9,
+                0x7cf8a9f009831265,
+                0xfd5449a6bf174743,
+                0x97ddad33d8994b40,
+                0x48ead5fc5d0be774,
+                0xe3b8c8ee55b7b03c,
+                0x91a0226e649e42e9,
+                0x900e3129e7badd7b,
+                0x202a9ec5faa3cce8,
+                0x5b3402464e1c3db6,
+                0x609f4e62a44c1059,
+                0x20d06cd26a8fbf5c,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keccakf_words() {
+        let mut state = [0; 25];
+        keccakf(&mut state);
+        let words = keccakf_words(&[0; 50]);
+        assert_eq!(words[0], 0x40e1dde7);
+        assert_eq!(words[1], 0xf1258f79);
+        for (i, lane) in state.iter().enumerate() {
+            assert_eq!(
+                words[2 * i] as u64 | ((words[2 * i + 1] as u64) << 32),
+                *lane
+            );
+        }
+    }
+}
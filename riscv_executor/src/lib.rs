@@ -9,10 +9,7 @@
 //! TODO: perform determinism verification for each instruction independently
 //! from execution.
 
-use std::{
-    collections::HashMap,
-    io::{self, Write},
-};
+use std::collections::HashMap;
 
 use ast::{
     asm_analysis::{AnalysisASMFile, CallableSymbol, FunctionStatement, LabelStatement, Machine},
@@ -21,6 +18,7 @@ use ast::{
 use builder::TraceBuilder;
 use number::{BigInt, FieldElement};
 
+mod keccakf;
 mod poseidon_gl;
 
 /// Initial value of the PC.
@@ -31,7 +29,7 @@ mod poseidon_gl;
 /// TODO: get this value from some authoritative place
 const PC_INITIAL_VAL: usize = 2;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Elem(pub i64);
 
 impl Elem {
@@ -89,22 +87,34 @@ impl From<usize> for Elem {
 
 pub type MemoryState = HashMap<u32, u32>;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MemOperationKind {
     Read,
     Write,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MemOperation {
     /// Line of the register trace the memory operation happened.
+    /// This is the row after the one of the instruction performing it,
+    /// i.e. the row where the results of the instruction appear.
     pub idx: usize,
     pub kind: MemOperationKind,
     pub address: u32,
+    /// The value written or read.
+    pub value: u32,
 }
 
-pub struct ExecutionTrace<'a> {
-    pub reg_map: HashMap<&'a str, usize>,
+/// The trace of an execution of the main machine.
+///
+/// There is one row per value of the PC, i.e. per batch of statements. A row
+/// contains the values of the (non-assignment) registers before executing the
+/// batch. The first [PC_INITIAL_VAL] rows are the rows used for proof
+/// initialization, in which all registers except the PC are zero. The last row
+/// contains the values after executing the last statement.
+pub struct ExecutionTrace {
+    /// The index of each register in a row.
+    pub reg_map: HashMap<String, usize>,
 
     /// Values of the registers in the execution trace.
     ///
@@ -112,11 +122,14 @@ pub struct ExecutionTrace<'a> {
     /// registers.
     pub regs: Vec<Elem>,
 
-    /// Writes and reads to memory.
+    /// Writes and reads to memory, in the order they were executed.
     pub mem: Vec<MemOperation>,
+
+    /// The bytes printed by the program, see [Executor::print_byte].
+    pub stdout: Vec<u8>,
 }
 
-impl<'a> ExecutionTrace<'a> {
+impl ExecutionTrace {
     /// Split the values of the registers' trace into rows.
     pub fn regs_rows(&self) -> impl Iterator<Item = &[Elem]> {
         self.regs.chunks_exact(self.reg_map.len())
@@ -125,6 +138,37 @@ impl<'a> ExecutionTrace<'a> {
     pub fn row(&self, idx: usize) -> &[Elem] {
         &self.regs[(idx * self.reg_map.len())..((idx + 1) * self.reg_map.len())]
     }
+
+    /// The number of rows, including the proof initialization rows.
+    pub fn row_count(&self) -> usize {
+        self.regs.len() / self.reg_map.len()
+    }
+
+    /// The value of the register `name` in row `idx`.
+    pub fn reg(&self, idx: usize, name: &str) -> Elem {
+        self.row(idx)[self.reg_map[name]]
+    }
+
+    /// The value of the PC in row `idx`.
+    pub fn pc(&self, idx: usize) -> Elem {
+        self.reg(idx, "pc")
+    }
+
+    /// The values of the registers after the execution.
+    pub fn final_regs(&self) -> HashMap<&str, Elem> {
+        let last_row = self.row(self.row_count() - 1);
+        self.reg_map
+            .iter()
+            .map(|(name, &index)| (name.as_str(), last_row[index]))
+            .collect()
+    }
+
+    /// The memory writes whose results appear in row `idx`.
+    pub fn mem_writes(&self, idx: usize) -> impl Iterator<Item = &MemOperation> {
+        self.mem
+            .iter()
+            .filter(move |op| op.idx == idx && op.kind == MemOperationKind::Write)
+    }
 }
 
 mod builder {
@@ -150,8 +194,8 @@ mod builder {
             .collect()
     }
 
-    pub struct TraceBuilder<'a, 'b> {
-        trace: ExecutionTrace<'a>,
+    pub struct TraceBuilder<'b> {
+        trace: ExecutionTrace,
 
         /// First register of current row.
         /// Next row is reg_map.len() elems ahead.
@@ -176,22 +220,22 @@ mod builder {
         mem: HashMap<u32, u32>,
     }
 
-    impl<'a, 'b> TraceBuilder<'a, 'b> {
+    impl<'b> TraceBuilder<'b> {
         /// Creates a new builder.
         ///
         /// May fail if max_rows_len is too small or if the main machine is
         /// empty. In this case, the final (empty) execution trace is returned
         /// in Err.
         pub fn new<T: FieldElement>(
-            main: &'a Machine<T>,
+            main: &Machine<T>,
             batch_to_line_map: &'b [u32],
             max_rows_len: usize,
-        ) -> Result<Self, Box<(ExecutionTrace<'a>, MemoryState)>> {
+        ) -> Result<Self, Box<(ExecutionTrace, MemoryState)>> {
             let reg_map = register_names(main)
                 .into_iter()
                 .enumerate()
-                .map(|(i, name)| (name, i))
-                .collect::<HashMap<&str, usize>>();
+                .map(|(i, name)| (name.to_string(), i))
+                .collect::<HashMap<String, usize>>();
 
             let reg_len = reg_map.len();
 
@@ -215,6 +259,7 @@ mod builder {
                     reg_map,
                     regs: values,
                     mem: Vec::new(),
+                    stdout: Vec::new(),
                 },
                 next_statement_line: 1,
                 batch_to_line_map,
@@ -305,22 +350,25 @@ mod builder {
                 idx: self.curr_idx / self.reg_len() + 1,
                 kind: MemOperationKind::Write,
                 address: addr,
+                value: val,
             });
 
             self.mem.insert(addr, val);
         }
 
         pub(crate) fn get_mem(&mut self, addr: u32) -> u32 {
+            let val = *self.mem.get(&addr).unwrap_or(&0);
             self.trace.mem.push(MemOperation {
                 idx: self.curr_idx / self.reg_len() + 1,
                 kind: MemOperationKind::Read,
                 address: addr,
+                value: val,
             });
 
-            *self.mem.get(&addr).unwrap_or(&0)
+            val
         }
 
-        pub fn finish(mut self) -> (ExecutionTrace<'a>, MemoryState) {
+        pub fn finish(mut self) -> (ExecutionTrace, MemoryState) {
             // remove the last row (future row), as it is not part of the trace
             self.trace.regs.drain((self.curr_idx + self.reg_len())..);
             (self.trace, self.mem)
@@ -370,6 +418,69 @@ fn get_main_machine<T: FieldElement>(program: &AnalysisASMFile<T>) -> &Machine<T
     panic!();
 }
 
+/// Returns the batch of statements of the main function that is executed at `pc`,
+/// or None if `pc` is one of the proof initialization rows or after the last batch.
+pub fn statements_at_pc<T: FieldElement>(
+    program: &AnalysisASMFile<T>,
+    pc: usize,
+) -> Option<&[FunctionStatement<T>]> {
+    let CallableSymbol::Function(main_function) = &get_main_machine(program).callable.0["main"]
+    else {
+        panic!("main function missing")
+    };
+    main_function
+        .body
+        .statements
+        .iter_batches()
+        .nth(pc.checked_sub(PC_INITIAL_VAL)?)
+        .map(|batch| batch.statements)
+}
+
+/// Returns, indexed by PC, the rows each batch of the main function uses in the submachines,
+/// given the rows a call of each instruction uses as (instruction, submachine, rows).
+/// The rows of a submachine are summed up over the instructions of the batch.
+pub fn submachine_rows_by_pc<'a, T: FieldElement>(
+    program: &AnalysisASMFile<T>,
+    instruction_rows: &[(&str, &'a str, usize)],
+) -> Vec<Vec<(&'a str, usize)>> {
+    let CallableSymbol::Function(main_function) = &get_main_machine(program).callable.0["main"]
+    else {
+        panic!("main function missing")
+    };
+    let batches = main_function.body.statements.iter_batches().map(|batch| {
+        // Instructions with outputs are called in the right hand side of assignments.
+        let mut instructions = vec![];
+        for s in batch.statements {
+            match s {
+                FunctionStatement::Instruction(i) => instructions.push(i.instruction.as_str()),
+                FunctionStatement::Assignment(a) => {
+                    if let Expression::FunctionCall(FunctionCall { function, .. }) = a.rhs.as_ref()
+                    {
+                        if let Expression::Reference(f) = function.as_ref() {
+                            instructions.push(f.name.as_str());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut rows: Vec<(&str, usize)> = vec![];
+        for instruction in instructions {
+            for &(_, machine, count) in instruction_rows
+                .iter()
+                .filter(|(name, _, _)| *name == instruction)
+            {
+                match rows.iter_mut().find(|(m, _)| *m == machine) {
+                    Some((_, rows)) => *rows += count,
+                    None => rows.push((machine, count)),
+                }
+            }
+        }
+        rows
+    });
+    (0..PC_INITIAL_VAL).map(|_| vec![]).chain(batches).collect()
+}
+
 struct PreprocessedMain<'a, T: FieldElement> {
     statements: Vec<&'a FunctionStatement<T>>,
     label_map: HashMap<&'a str, Elem>,
@@ -410,7 +521,9 @@ fn preprocess_main_function<T: FieldElement>(machine: &Machine<T>) -> Preprocess
                             assert_eq!(*idx, debug_files.len() + 1);
                             debug_files.push((dir.as_str(), file.as_str()));
                         }
-                        DebugDirective::Loc(_, _, _) | DebugDirective::OriginalInstruction(_) => {
+                        DebugDirective::Loc(_, _, _)
+                        | DebugDirective::OriginalInstruction(_)
+                        | DebugDirective::Print(_) => {
                             // keep debug locs for debugging purposes
                             statements.push(s);
                         }
@@ -438,14 +551,26 @@ fn preprocess_main_function<T: FieldElement>(machine: &Machine<T>) -> Preprocess
 }
 
 struct Executor<'a, 'b, F: FieldElement> {
-    proc: TraceBuilder<'a, 'b>,
+    proc: TraceBuilder<'b>,
     label_map: HashMap<&'a str, Elem>,
     inputs: HashMap<F, Vec<F>>,
     bootloader_inputs: &'b [F],
-    stdout: io::Stdout,
+    stdout: Vec<u8>,
+    /// Start of the line of `stdout` that has not been logged yet.
+    line_start: usize,
 }
 
 impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
+    /// Appends a byte to the output of the program and logs each completed line.
+    fn print_byte(&mut self, byte: u8) {
+        self.stdout.push(byte);
+        if byte == b'\n' {
+            let line = &self.stdout[self.line_start..self.stdout.len() - 1];
+            log::info!("{}", String::from_utf8_lossy(line));
+            self.line_start = self.stdout.len();
+        }
+    }
+
     fn exec_instruction(&mut self, name: &str, args: &[Expression<F>]) -> Vec<Elem> {
         let args = args
             .iter()
@@ -620,6 +745,11 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                 let result = poseidon_gl::poseidon_gl(&inputs);
                 result.into_iter().map(Elem::from_fe).collect()
             }
+            "keccakf" => {
+                let inputs = args.iter().take(50).map(|arg| arg.u()).collect::<Vec<_>>();
+                let result = keccakf::keccakf_words(&inputs);
+                result.into_iter().map(Elem::from).collect()
+            }
             instr => {
                 panic!("unknown instruction: {instr}");
             }
@@ -716,8 +846,14 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                         let val = self.eval_expression(&t[1])[0];
                         break 'input vec![match name.as_str() {
                             "input" => {
-                                let idx = val.u() as usize;
-                                to_u32(&self.inputs[&F::zero()][idx]).unwrap().into()
+                                // Either ("input", <index>) for channel 0
+                                // or ("input", <channel>, <index>).
+                                let (channel, idx) = match t.get(2) {
+                                    Some(index) => (val.fe(), self.eval_expression(index)[0]),
+                                    None => (F::zero(), val),
+                                };
+                                let idx = idx.u() as usize;
+                                to_u32(&self.inputs[&channel][idx]).unwrap().into()
                             }
                             "data" => {
                                 let idx = val.u() as usize;
@@ -730,7 +866,7 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                                 to_u32(&self.bootloader_inputs[idx]).unwrap().into()
                             }
                             "print_char" => {
-                                self.stdout.write_all(&[val.u() as u8]).unwrap();
+                                self.print_byte(val.u() as u8);
                                 // what is print_char supposed to return?
                                 Elem::zero()
                             }
@@ -749,12 +885,12 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
     }
 }
 
-pub fn execute_ast<'a, T: FieldElement>(
-    program: &'a AnalysisASMFile<T>,
+pub fn execute_ast<T: FieldElement>(
+    program: &AnalysisASMFile<T>,
     inputs: &HashMap<T, Vec<T>>,
     bootloader_inputs: &[T],
     max_steps_to_execute: usize,
-) -> (ExecutionTrace<'a>, MemoryState) {
+) -> (ExecutionTrace, MemoryState) {
     let main_machine = get_main_machine(program);
     let PreprocessedMain {
         statements,
@@ -773,7 +909,8 @@ pub fn execute_ast<'a, T: FieldElement>(
         label_map,
         inputs: inputs.clone(),
         bootloader_inputs,
-        stdout: io::stdout(),
+        stdout: Vec::new(),
+        line_start: 0,
     };
 
     let mut curr_pc = 0u32;
@@ -807,6 +944,10 @@ pub fn execute_ast<'a, T: FieldElement>(
                     DebugDirective::OriginalInstruction(insn) => {
                         log::trace!("  {insn}");
                     }
+                    DebugDirective::Print(reg) => {
+                        let byte = e.proc.get_reg(reg).u() as u8;
+                        e.print_byte(byte);
+                    }
                     DebugDirective::File(_, _, _) => unreachable!(),
                 };
 
@@ -823,7 +964,12 @@ pub fn execute_ast<'a, T: FieldElement>(
         };
     }
 
-    e.proc.finish()
+    if e.line_start < e.stdout.len() {
+        log::info!("{}", String::from_utf8_lossy(&e.stdout[e.line_start..]));
+    }
+    let (mut trace, memory) = e.proc.finish();
+    trace.stdout = e.stdout;
+    (trace, memory)
 }
 
 /// Execute a Powdr/RISCV assembly source.
@@ -835,6 +981,16 @@ pub fn execute<F: FieldElement>(
     inputs: &HashMap<F, Vec<F>>,
     bootloader_inputs: &[F],
 ) {
+    execute_with_trace(asm_source, inputs, bootloader_inputs);
+}
+
+/// Execute a Powdr/RISCV assembly source and return the execution trace,
+/// see [ExecutionTrace] for the order of its rows.
+pub fn execute_with_trace<F: FieldElement>(
+    asm_source: &str,
+    inputs: &HashMap<F, Vec<F>>,
+    bootloader_inputs: &[F],
+) -> ExecutionTrace {
     log::info!("Parsing...");
     let parsed = parser::parse_asm::<F>(None, asm_source).unwrap();
     log::info!("Resolving imports...");
@@ -843,7 +999,7 @@ pub fn execute<F: FieldElement>(
     let analyzed = analysis::analyze(resolved, &mut ast::DiffMonitor::default()).unwrap();
 
     log::info!("Executing...");
-    execute_ast(&analyzed, inputs, bootloader_inputs, usize::MAX);
+    execute_ast(&analyzed, inputs, bootloader_inputs, usize::MAX).0
 }
 
 fn to_u32<F: FieldElement>(val: &F) -> Option<u32> {
@@ -859,3 +1015,94 @@ fn to_u32<F: FieldElement>(val: &F) -> Option<u32> {
         }
     })
 }
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use test_log::test;
+
+    use super::*;
+
+    const PROGRAM: &str = r#"
+machine Main {
+    degree 16;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg x0;
+    reg x1;
+    reg x2;
+
+    instr mstore X, Y {}
+
+    function main {
+        x1 <=X= 7;
+        x2 <=X= x1 + 1;
+        mstore 8, x2;
+        return;
+    }
+}
+"#;
+
+    #[test]
+    fn trace_of_simple_program() {
+        let trace = execute_with_trace::<GoldilocksField>(PROGRAM, &HashMap::new(), &[]);
+
+        // The initialization rows and one row per statement, including `return`.
+        assert_eq!(trace.row_count(), PC_INITIAL_VAL + 4);
+        for row in 0..PC_INITIAL_VAL {
+            assert_eq!(trace.pc(row), Elem(row as i64));
+            assert_eq!(trace.reg(row, "x1"), Elem(0));
+        }
+
+        let first = PC_INITIAL_VAL;
+        assert_eq!(trace.pc(first), Elem(first as i64));
+        assert_eq!(trace.reg(first, "x1"), Elem(0));
+        assert_eq!(trace.reg(first + 1, "x1"), Elem(7));
+        assert_eq!(trace.reg(first + 2, "x2"), Elem(8));
+
+        assert_eq!(
+            trace.mem_writes(first + 3).collect::<Vec<_>>(),
+            vec![&MemOperation {
+                idx: first + 3,
+                kind: MemOperationKind::Write,
+                address: 8,
+                value: 8,
+            }]
+        );
+        assert_eq!(trace.mem_writes(first + 2).count(), 0);
+
+        let final_regs = trace.final_regs();
+        assert_eq!(final_regs["x1"], Elem(7));
+        assert_eq!(final_regs["x2"], Elem(8));
+    }
+
+    #[test]
+    fn print() {
+        let program = r#"
+machine Main {
+    degree 16;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg x0;
+    reg x1;
+
+    function main {
+        x1 <=X= 72;
+        debug print x1;
+        x1 <=X= 105;
+        debug print x1;
+        x1 <=X= 10;
+        debug print x1;
+        return;
+    }
+}
+"#;
+        let trace = execute_with_trace::<GoldilocksField>(program, &HashMap::new(), &[]);
+        assert_eq!(trace.stdout, b"Hi\n");
+        // The print directives do not create rows.
+        assert_eq!(trace.row_count(), PC_INITIAL_VAL + 4);
+    }
+}
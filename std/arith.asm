@@ -0,0 +1,87 @@
+// Multiplication and division of 32-bit unsigned values.
+//
+// Every operation takes one row, which checks that
+//     X * Y + Z = LO + HI * 2**32.
+// All values are decomposed into two 16-bit limbs and the product is computed
+// limb by limb, using range-checked carries. No intermediate value exceeds 2**35,
+// so the identities hold over the integers and nothing can wrap around the field.
+machine Arith(latch, operation_id) {
+
+    // lower bound degree is 65536
+
+    // LO and HI are the lower and upper 32 bits of X * Y.
+    operation mul<0> X, Y -> LO, HI;
+
+    // Y and Z are the quotient and remainder of LO / X.
+    // As in RISC-V, dividing by zero results in the quotient 0xffffffff and the remainder LO.
+    operation divremu<1> LO, X -> Y, Z;
+
+    col witness operation_id;
+    operation_id * (1 - operation_id) = 0;
+
+    col fixed latch = [1]*;
+    col fixed U16(i) { i & 0xffff };
+
+    // Witness generation cannot solve for the quotient and the remainder,
+    // so they are provided as hints.
+    col witness X;
+    col witness Y(i) query ("hint", if X(i) == 0 { 0xffffffff } else { LO(i) / X(i) });
+    col witness Z(i) query ("hint", if X(i) == 0 { LO(i) } else { LO(i) % X(i) });
+    col witness LO;
+    col witness HI;
+
+    // 1. Decompose the values into 16-bit limbs
+
+    col witness X_0, X_1, Y_0, Y_1, Z_0, Z_1, LO_0, LO_1, HI_0, HI_1;
+    { X_0 } in { U16 };
+    { X_1 } in { U16 };
+    { Y_0 } in { U16 };
+    { Y_1 } in { U16 };
+    { Z_0 } in { U16 };
+    { Z_1 } in { U16 };
+    { LO_0 } in { U16 };
+    { LO_1 } in { U16 };
+    { HI_0 } in { U16 };
+    { HI_1 } in { U16 };
+
+    X = X_0 + X_1 * 2**16;
+    Y = Y_0 + Y_1 * 2**16;
+    Z = Z_0 + Z_1 * 2**16;
+    LO = LO_0 + LO_1 * 2**16;
+    HI = HI_0 + HI_1 * 2**16;
+
+    // 2. Check X * Y + Z = LO + HI * 2**32 limb by limb
+
+    // The carry out of the lowest limb has at most 16 bits, the one out of
+    // the second limb at most 17 bits.
+    col witness CARRY_0, CARRY_1, CARRY_1_BIT;
+    { CARRY_0 } in { U16 };
+    { CARRY_1 } in { U16 };
+    CARRY_1_BIT * (1 - CARRY_1_BIT) = 0;
+
+    X_0 * Y_0 + Z_0 = LO_0 + CARRY_0 * 2**16;
+    X_0 * Y_1 + X_1 * Y_0 + Z_1 + CARRY_0 = LO_1 + CARRY_1 * 2**16 + CARRY_1_BIT * 2**32;
+    X_1 * Y_1 + CARRY_1 + CARRY_1_BIT * 2**16 = HI_0 + HI_1 * 2**16;
+
+    // 3. Operation-specific constraints
+
+    // A multiplication does not add anything to the product.
+    (1 - operation_id) * Z = 0;
+
+    // The product of the quotient and the divisor plus the remainder is the dividend...
+    operation_id * HI = 0;
+
+    // ...and the remainder is smaller than the divisor, unless the divisor is zero.
+    // If X is not zero, X_is_zero has to be zero. If X is zero, it has to be one,
+    // because otherwise the difference below is negative and cannot be decomposed.
+    col witness X_is_zero(i) query ("hint", X(i) == 0);
+    X_is_zero * (1 - X_is_zero) = 0;
+    X_is_zero * X = 0;
+    col witness REM_DIFF_0, REM_DIFF_1;
+    { REM_DIFF_0 } in { U16 };
+    { REM_DIFF_1 } in { U16 };
+    REM_DIFF_0 + REM_DIFF_1 * 2**16 = operation_id * (1 - X_is_zero) * (X - Z - 1);
+
+    // If the divisor is zero, the quotient is 0xffffffff.
+    operation_id * X_is_zero * (Y - 0xffffffff) = 0;
+}
@@ -0,0 +1,103 @@
+// Implements the Keccak-f[1600] permutation.
+// The state consists of 25 lanes of 64 bits, which are passed in and out
+// as 50 32-bit words: the lower and then the upper half of each lane,
+// with the lanes ordered by x + 5 * y.
+machine KeccakF(LASTBLOCK, operation_id) {
+
+    // Applies the 24 rounds of the permutation to the input state and returns the new state.
+    operation keccakf<0> input[0], input[1], input[2], input[3], input[4], input[5], input[6], input[7], input[8], input[9], input[10], input[11], input[12], input[13], input[14], input[15], input[16], input[17], input[18], input[19], input[20], input[21], input[22], input[23], input[24], input[25], input[26], input[27], input[28], input[29], input[30], input[31], input[32], input[33], input[34], input[35], input[36], input[37], input[38], input[39], input[40], input[41], input[42], input[43], input[44], input[45], input[46], input[47], input[48], input[49] -> output[0], output[1], output[2], output[3], output[4], output[5], output[6], output[7], output[8], output[9], output[10], output[11], output[12], output[13], output[14], output[15], output[16], output[17], output[18], output[19], output[20], output[21], output[22], output[23], output[24], output[25], output[26], output[27], output[28], output[29], output[30], output[31], output[32], output[33], output[34], output[35], output[36], output[37], output[38], output[39], output[40], output[41], output[42], output[43], output[44], output[45], output[46], output[47], output[48], output[49];
+
+    col witness operation_id;
+
+    // Each block has one row per round, followed by a row with the resulting state.
+    constant %nRounds = 24;
+    constant %rowsPerHash = %nRounds + 1;
+
+    pol constant FIRSTBLOCK(i) { (i % %rowsPerHash) == 0 };
+    pol constant LASTBLOCK(i) { (i % %rowsPerHash) == %rowsPerHash - 1 };
+    // Like LASTBLOCK, but also 1 in the last row of the table
+    // Specified this way because we can't access the degree in the match statement
+    pol constant LAST = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]* + [1];
+
+    // The bits of the round constants. Only the bits at positions 2^j - 1 can be set.
+    pol constant RC_0 = [1, 0, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 0, 1, 0, 0]*;
+    pol constant RC_1 = [0, 1, 1, 0, 1, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 1, 1, 0, 1, 1, 0, 0, 0, 0, 0]*;
+    pol constant RC_3 = [0, 0, 1, 0, 1, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 0]*;
+    pol constant RC_7 = [0, 1, 1, 0, 1, 0, 1, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 1, 1, 0, 0, 0]*;
+    pol constant RC_15 = [0, 1, 1, 1, 1, 0, 1, 1, 0, 0, 1, 0, 1, 0, 1, 1, 1, 0, 1, 0, 1, 1, 0, 1, 0]*;
+    pol constant RC_31 = [0, 0, 0, 1, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 1, 0]*;
+    pol constant RC_63 = [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 0, 1, 1, 1, 0, 1, 0]*;
+
+    // The rotation offsets of the rho step, indexed by x + 5 * y.
+    let R = [0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14];
+
+    // The state before the round, one bit per column.
+    // Bit z of lane (x, y) is at index 64 * (x + 5 * y) + z.
+    pol commit a[1600];
+    // The parities of the columns of the state, bit z of column x is at index 64 * x + z.
+    pol commit c[320];
+    // The state after the theta step, indexed like the state.
+    pol commit t[1600];
+
+    // The words of the initial state (constrained to be equal to the state
+    // in the first row and then repeated until the end of the block)
+    pol commit input[50];
+    // The words of the state in the current row
+    pol commit output[50];
+
+    let fold = |length, f, initial, folder| match length {
+        0 => initial,
+        _ => folder(fold(length - 1, f, initial, folder), f(length - 1))
+    };
+    let make_array = |length, f| fold(length, f, [], |acc, e| acc + [e]);
+    let sum = |length, f| fold(length, f, 0, |acc, e| acc + e);
+    let concat = |acc, e| acc + e;
+
+    // Exclusive or of two bits
+    let xor = |x, y| x + y - 2 * x * y;
+
+    let bit_a = |x, y, z| a[64 * (x + 5 * y) + z];
+    let bit_t = |x, y, z| t[64 * (x + 5 * y) + z];
+    let bit_c = |x, z| c[64 * x + z];
+
+    // All bits of the state are binary.
+    fold(25, |l| make_array(64, |z| a[64 * l + z] * (1 - a[64 * l + z]) == 0), [], concat);
+
+    // Theta step
+    fold(5, |x| make_array(64, |z| bit_c(x, z) == xor(xor(xor(xor(bit_a(x, 0, z), bit_a(x, 1, z)), bit_a(x, 2, z)), bit_a(x, 3, z)), bit_a(x, 4, z))), [], concat);
+    fold(25, |l| make_array(64, |z| bit_t(l % 5, l / 5, z) == xor(xor(bit_a(l % 5, l / 5, z), bit_c((l % 5 + 4) % 5, z)), bit_c((l % 5 + 1) % 5, (z + 63) % 64))), [], concat);
+
+    // Rho and pi steps: the bit that ends up at bit z of lane (x, y)
+    let bit_b = |x, y, z| bit_t((x + 3 * y) % 5, x, (z + 64 - R[(x + 3 * y) % 5 + 5 * x]) % 64);
+
+    // Chi step
+    let chi = |x, y, z| xor(bit_b(x, y, z), (1 - bit_b((x + 1) % 5, y, z)) * bit_b((x + 2) % 5, y, z));
+
+    // Iota step, only affects bit z of lane (0, 0)
+    let iota = |x, z| match z {
+        0 => xor(x, RC_0),
+        1 => xor(x, RC_1),
+        3 => xor(x, RC_3),
+        7 => xor(x, RC_7),
+        15 => xor(x, RC_15),
+        31 => xor(x, RC_31),
+        63 => xor(x, RC_63),
+        _ => x
+    };
+    let round = |l, z| match l {
+        0 => iota(chi(0, 0, z), z),
+        _ => chi(l % 5, l / 5, z)
+    };
+
+    // The next row contains the state after the round.
+    let equal_unless_last = |x, y| (1 - LAST) * (x - y) == 0;
+    fold(25, |l| make_array(64, |z| equal_unless_last(a[64 * l + z]', round(l, z))), [], concat);
+
+    // The lower (half = 0) or upper (half = 1) 32 bits of lane l
+    let word = |l, half| sum(32, |z| a[64 * l + 32 * half + z] * 2**z);
+
+    // The input is the state in the first row of the block, the output is the state in the current row.
+    make_array(50, |i| equal_unless_last(input[i], input[i]'));
+    make_array(50, |i| FIRSTBLOCK * (input[i] - word(i / 2, i % 2)) == 0);
+    make_array(50, |i| output[i] == word(i / 2, i % 2));
+}
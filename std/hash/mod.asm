@@ -1,2 +1,3 @@
+mod keccakf;
 mod poseidon_bn254;
 mod poseidon_gl;
\ No newline at end of file
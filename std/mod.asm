@@ -1,4 +1,6 @@
+mod arith;
 mod binary;
 mod hash;
+mod range_check;
 mod shift;
 mod split;
\ No newline at end of file
@@ -0,0 +1,22 @@
+// Range checks against fixed tables. The degree of the machines has to be at
+// least the size of the range, i.e. 256 for `Byte` and 65536 for `U16`.
+// A machine that is parametric in the number of bits is not possible yet,
+// as machines cannot be parameterized.
+
+// Checks that a value is in the range [0, 2**8).
+machine Byte(latch, operation_id) {
+    operation check<0> BYTE;
+
+    col fixed latch = [1]*;
+    col fixed operation_id = [0]*;
+    col fixed BYTE(i) { i & 0xff };
+}
+
+// Checks that a value is in the range [0, 2**16).
+machine U16(latch, operation_id) {
+    operation check<0> U16;
+
+    col fixed latch = [1]*;
+    col fixed operation_id = [0]*;
+    col fixed U16(i) { i & 0xffff };
+}
@@ -0,0 +1,15 @@
+// Witness generation fails in the row of `fail`, because the constraints of the
+// instruction contradict each other. Used to test the source locations in the error.
+
+machine Main {
+    reg pc[@pc];
+
+    pol commit B;
+
+    instr fail { B = 1, B = 2 }
+
+    function main {
+        fail;
+        return;
+    }
+}
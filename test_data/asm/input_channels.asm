@@ -0,0 +1,38 @@
+// Verifies that a sum of the inputs in channel 1 has been computed properly.
+// Channel 0: sum, cnt
+// Channel 1: x_1, x_2, ..., x_cnt
+// Channel 0 is read with the single-channel form ("input", <index>),
+// channel 1 with ("input", <channel>, <index>).
+
+machine Main {
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+    reg CNT;
+
+    col witness XInv;
+    col witness XIsZero;
+    XIsZero  = 1 - X * XInv;
+    XIsZero * X = 0;
+    XIsZero * (1 - XIsZero) = 0;
+
+    instr jmpz X, l: label { pc' = XIsZero * l + (1 - XIsZero) * (pc + 1) }
+    instr jmp l: label { pc' = l }
+    instr dec_CNT { CNT' = CNT - 1 }
+    instr assert_zero X { XIsZero = 1 }
+
+    function main {
+        CNT <=X= ${ ("input", 1) };
+
+        start::
+        jmpz CNT, check;
+        A <=X= A + ${ ("input", 1, CNT - 1) };
+        dec_CNT;
+        jmp start;
+
+        check::
+        A <=X= A - ${ ("input", 0, 0) };
+        assert_zero A;
+        return;
+    }
+}
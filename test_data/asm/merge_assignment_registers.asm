@@ -0,0 +1,23 @@
+// `X` and `Y` are used by the same instruction, but `Z` is never used
+// together with `X`, so it can share its columns.
+machine Main {
+    degree 8;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    instr incr X -> Y { Y = X + 1 }
+
+    instr assert_zero Z { Z = 0 }
+
+    function main {
+        A <=X= 2;
+        A <== incr(A);
+        A <== incr(A);
+        assert_zero A - 4;
+        return;
+    }
+}
@@ -0,0 +1,40 @@
+machine Arith(latch, operation_id) {
+
+    operation add<0> x, y -> z;
+
+    operation sub<1> x, y -> z;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    col witness z;
+    z = (1 - operation_id) * (x + y) + operation_id * (x - y);
+}
+
+machine Main {
+
+    degree 32;
+
+    Arith arith;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    // Every call is matched with exactly one row of `arith`.
+    instr add X, Y -> Z ~ arith.add
+    instr sub X, Y -> Z ~ arith.sub
+    instr assert_eq X, Y { X = Y }
+
+    function main {
+        A <== add(2, 1);
+        A <== sub(A, 1);
+        assert_eq A, 2;
+        A <== add(A, A);
+        assert_eq A, 4;
+        return;
+    }
+}
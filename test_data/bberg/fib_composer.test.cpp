@@ -0,0 +1,108 @@
+
+// AUTOGENERATED FILE
+#include "barretenberg/vm/generated/fib_circuit_builder.hpp"
+#include "barretenberg/vm/generated/fib_composer.hpp"
+#include "barretenberg/vm/generated/fib_prover.hpp"
+#include "barretenberg/vm/generated/fib_verifier.hpp"
+
+#include <cstdlib>
+#include <fstream>
+#include <gtest/gtest.h>
+#include <iterator>
+#include <vector>
+
+using namespace bb;
+
+namespace {
+
+using FF = FibFlavor::FF;
+using Row = FibFullRow<FF>;
+
+constexpr size_t NUM_FIXED_COLUMNS = 1;
+constexpr size_t NUM_WITNESS_COLUMNS = 2;
+
+// Reads the values of a file written by powdr: row by row, each value as 32 bytes in little endian.
+std::vector<FF> read_values(const std::string& path)
+{
+    std::ifstream file(path, std::ios::binary);
+    if (!file) {
+        throw_or_abort("Could not open " + path);
+    }
+    std::vector<uint8_t> bytes((std::istreambuf_iterator<char>(file)), std::istreambuf_iterator<char>());
+    std::vector<FF> values;
+    for (size_t offset = 0; offset + 32 <= bytes.size(); offset += 32) {
+        std::array<uint64_t, 4> limbs{};
+        for (size_t i = 0; i < 32; i++) {
+            limbs[i / 8] |= static_cast<uint64_t>(bytes[offset + i]) << (8 * (i % 8));
+        }
+        values.emplace_back(uint256_t(limbs[0], limbs[1], limbs[2], limbs[3]));
+    }
+    return values;
+}
+
+// Builds the rows from constants.bin and commits.bin in the given directory.
+std::vector<Row> read_rows(const std::string& dir)
+{
+    const auto fixed = read_values(dir + "/constants.bin");
+    const auto witness = read_values(dir + "/commits.bin");
+    const size_t num_rows =
+        NUM_FIXED_COLUMNS > 0 ? fixed.size() / NUM_FIXED_COLUMNS : witness.size() / NUM_WITNESS_COLUMNS;
+    std::vector<Row> rows(num_rows);
+    for (size_t i = 0; i < num_rows; i++) {
+        rows[i].main_FIRST = fixed[i * 1 + 0];
+        rows[i].main_x = witness[i * 2 + 0];
+        rows[i].main_y = witness[i * 2 + 1];
+    }
+    return rows;
+}
+
+bool prove_and_verify(std::vector<Row> rows)
+{
+    
+    auto circuit_builder = FibCircuitBuilder();
+    circuit_builder.set_trace(std::move(rows));
+
+    auto composer = FibComposer();
+    auto prover = composer.create_prover(circuit_builder);
+    auto proof = prover.construct_proof();
+
+    auto verifier = composer.create_verifier(circuit_builder);
+    return verifier.verify_proof(proof);
+}
+
+class FibComposerTests : public ::testing::Test {
+  protected:
+    void SetUp() override
+    {
+        const char* dir = std::getenv("FIB_WITNESS_DIR");
+        if (dir == nullptr) {
+            GTEST_SKIP() << "Set FIB_WITNESS_DIR to the directory with constants.bin and commits.bin.";
+        }
+        witness_dir = dir;
+        srs::init_crs_factory("../srs_db/ignition");
+    }
+
+    std::string witness_dir;
+};
+
+} // namespace
+
+TEST_F(FibComposerTests, ProveAndVerify)
+{
+    auto rows = read_rows(witness_dir);
+
+    auto circuit_builder = FibCircuitBuilder();
+    circuit_builder.set_trace(std::vector<Row>(rows));
+    EXPECT_TRUE(circuit_builder.check_circuit());
+
+    EXPECT_TRUE(prove_and_verify(std::move(rows)));
+}
+
+TEST_F(FibComposerTests, CorruptedWitnessFails)
+{
+    auto rows = read_rows(witness_dir);
+    ASSERT_GT(rows.size(), 1);
+    rows[1].main_y += FF(1);
+
+    EXPECT_FALSE(prove_and_verify(std::move(rows)));
+}
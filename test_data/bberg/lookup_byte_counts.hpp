@@ -0,0 +1,18 @@
+
+            // Counts of the fixed lookup table lookup_byte
+            {
+                std::map<std::array<uint256_t, 1>, size_t> table_rows;
+                for (size_t i = 0; i < rows.size(); i++) {
+                    if (polys.main_sel_byte[i] == 1) {
+                        table_rows.try_emplace(std::array<uint256_t, 1>{ uint256_t(polys.main_BYTE[i]) }, i);
+                    }
+                }
+                for (size_t i = 0; i < rows.size(); i++) {
+                    if (polys.main_sel[i] == 1) {
+                        auto entry = table_rows.find(std::array<uint256_t, 1>{ uint256_t(polys.main_x[i]) });
+                        if (entry != table_rows.end()) {
+                            polys.lookup_byte_counts[entry->second] += FF(1);
+                        }
+                    }
+                }
+            }
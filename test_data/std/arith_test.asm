@@ -0,0 +1,57 @@
+use std::arith::Arith;
+
+machine Main {
+    reg pc[@pc];
+    reg X0[<=];
+    reg X1[<=];
+    reg X2[<=];
+    reg X3[<=];
+    reg A;
+    reg B;
+
+    degree 65536;
+
+    Arith arith;
+
+    instr mul X0, X1 -> X2, X3 = arith.mul
+    instr divremu X0, X1 -> X2, X3 = arith.divremu
+
+    instr assert_eq X0, X1 {
+        X0 = X1
+    }
+
+    function main {
+
+        // Largest product, close to the Goldilocks modulus
+        A, B <== mul(0xffffffff, 0xffffffff);
+        assert_eq A, 1;
+        assert_eq B, 0xfffffffe;
+
+        A, B <== mul(0x12345678, 0x9abcdef0);
+        assert_eq A, 0x242d2080;
+        assert_eq B, 0x0b00ea4e;
+
+        A, B <== mul(0, 0xffffffff);
+        assert_eq A, 0;
+        assert_eq B, 0;
+
+        A, B <== divremu(0xffffffff, 0x10);
+        assert_eq A, 0x0fffffff;
+        assert_eq B, 0xf;
+
+        A, B <== divremu(0xfffffffe, 0xffffffff);
+        assert_eq A, 0;
+        assert_eq B, 0xfffffffe;
+
+        A, B <== divremu(0xffffffff, 1);
+        assert_eq A, 0xffffffff;
+        assert_eq B, 0;
+
+        // Division by zero
+        A, B <== divremu(0x1234, 0);
+        assert_eq A, 0xffffffff;
+        assert_eq B, 0x1234;
+
+        return;
+    }
+}
@@ -0,0 +1,277 @@
+use std::hash::keccakf::KeccakF;
+
+machine Main {
+    degree 256;
+
+    reg pc[@pc];
+    reg X0[<=];
+    reg X1[<=];
+    reg X2[<=];
+    reg X3[<=];
+    reg X4[<=];
+    reg X5[<=];
+    reg X6[<=];
+    reg X7[<=];
+    reg X8[<=];
+    reg X9[<=];
+    reg X10[<=];
+    reg X11[<=];
+    reg X12[<=];
+    reg X13[<=];
+    reg X14[<=];
+    reg X15[<=];
+    reg X16[<=];
+    reg X17[<=];
+    reg X18[<=];
+    reg X19[<=];
+    reg X20[<=];
+    reg X21[<=];
+    reg X22[<=];
+    reg X23[<=];
+    reg X24[<=];
+    reg X25[<=];
+    reg X26[<=];
+    reg X27[<=];
+    reg X28[<=];
+    reg X29[<=];
+    reg X30[<=];
+    reg X31[<=];
+    reg X32[<=];
+    reg X33[<=];
+    reg X34[<=];
+    reg X35[<=];
+    reg X36[<=];
+    reg X37[<=];
+    reg X38[<=];
+    reg X39[<=];
+    reg X40[<=];
+    reg X41[<=];
+    reg X42[<=];
+    reg X43[<=];
+    reg X44[<=];
+    reg X45[<=];
+    reg X46[<=];
+    reg X47[<=];
+    reg X48[<=];
+    reg X49[<=];
+    reg Y0[<=];
+    reg Y1[<=];
+    reg Y2[<=];
+    reg Y3[<=];
+    reg Y4[<=];
+    reg Y5[<=];
+    reg Y6[<=];
+    reg Y7[<=];
+    reg Y8[<=];
+    reg Y9[<=];
+    reg Y10[<=];
+    reg Y11[<=];
+    reg Y12[<=];
+    reg Y13[<=];
+    reg Y14[<=];
+    reg Y15[<=];
+    reg Y16[<=];
+    reg Y17[<=];
+    reg Y18[<=];
+    reg Y19[<=];
+    reg Y20[<=];
+    reg Y21[<=];
+    reg Y22[<=];
+    reg Y23[<=];
+    reg Y24[<=];
+    reg Y25[<=];
+    reg Y26[<=];
+    reg Y27[<=];
+    reg Y28[<=];
+    reg Y29[<=];
+    reg Y30[<=];
+    reg Y31[<=];
+    reg Y32[<=];
+    reg Y33[<=];
+    reg Y34[<=];
+    reg Y35[<=];
+    reg Y36[<=];
+    reg Y37[<=];
+    reg Y38[<=];
+    reg Y39[<=];
+    reg Y40[<=];
+    reg Y41[<=];
+    reg Y42[<=];
+    reg Y43[<=];
+    reg Y44[<=];
+    reg Y45[<=];
+    reg Y46[<=];
+    reg Y47[<=];
+    reg Y48[<=];
+    reg Y49[<=];
+    reg A0;
+    reg A1;
+    reg A2;
+    reg A3;
+    reg A4;
+    reg A5;
+    reg A6;
+    reg A7;
+    reg A8;
+    reg A9;
+    reg A10;
+    reg A11;
+    reg A12;
+    reg A13;
+    reg A14;
+    reg A15;
+    reg A16;
+    reg A17;
+    reg A18;
+    reg A19;
+    reg A20;
+    reg A21;
+    reg A22;
+    reg A23;
+    reg A24;
+    reg A25;
+    reg A26;
+    reg A27;
+    reg A28;
+    reg A29;
+    reg A30;
+    reg A31;
+    reg A32;
+    reg A33;
+    reg A34;
+    reg A35;
+    reg A36;
+    reg A37;
+    reg A38;
+    reg A39;
+    reg A40;
+    reg A41;
+    reg A42;
+    reg A43;
+    reg A44;
+    reg A45;
+    reg A46;
+    reg A47;
+    reg A48;
+    reg A49;
+
+    KeccakF keccakf;
+
+    instr keccakf X0, X1, X2, X3, X4, X5, X6, X7, X8, X9, X10, X11, X12, X13, X14, X15, X16, X17, X18, X19, X20, X21, X22, X23, X24, X25, X26, X27, X28, X29, X30, X31, X32, X33, X34, X35, X36, X37, X38, X39, X40, X41, X42, X43, X44, X45, X46, X47, X48, X49 -> Y0, Y1, Y2, Y3, Y4, Y5, Y6, Y7, Y8, Y9, Y10, Y11, Y12, Y13, Y14, Y15, Y16, Y17, Y18, Y19, Y20, Y21, Y22, Y23, Y24, Y25, Y26, Y27, Y28, Y29, Y30, Y31, Y32, Y33, Y34, Y35, Y36, Y37, Y38, Y39, Y40, Y41, Y42, Y43, Y44, Y45, Y46, Y47, Y48, Y49 = keccakf.keccakf
+
+    instr assert_eq X0, X1 {
+        X0 = X1
+    }
+
+    function main {
+
+        // The state after applying the permutation to the zero state, see
+        // https://github.com/XKCP/XKCP/blob/master/tests/TestVectors/KeccakF-1600-IntermediateValues.txt
+        A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12, A13, A14, A15, A16, A17, A18, A19, A20, A21, A22, A23, A24, A25, A26, A27, A28, A29, A30, A31, A32, A33, A34, A35, A36, A37, A38, A39, A40, A41, A42, A43, A44, A45, A46, A47, A48, A49 <== keccakf(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+        assert_eq A0, 1088544231;
+        assert_eq A1, 4045770617;
+        assert_eq A2, 868239242;
+        assert_eq A3, 2228604153;
+        assert_eq A4, 2790959598;
+        assert_eq A5, 3583518238;
+        assert_eq A6, 1870678349;
+        assert_eq A7, 3172288304;
+        assert_eq A8, 1649659991;
+        assert_eq A9, 2334674437;
+        assert_eq A10, 2140041172;
+        assert_eq A11, 4288128045;
+        assert_eq A12, 2756069316;
+        assert_eq A13, 2432624032;
+        assert_eq A14, 3591974518;
+        assert_eq A15, 2354829836;
+        assert_eq A16, 454624668;
+        assert_eq A17, 2905646839;
+        assert_eq A18, 3499097188;
+        assert_eq A19, 814963383;
+        assert_eq A20, 588764725;
+        assert_eq A21, 3948587327;
+        assert_eq A22, 225517827;
+        assert_eq A23, 2846287398;
+        assert_eq A24, 3687798111;
+        assert_eq A25, 2175106070;
+        assert_eq A26, 55035942;
+        assert_eq A27, 1136144845;
+        assert_eq A28, 296048287;
+        assert_eq A29, 32648986;
+        assert_eq A30, 567914081;
+        assert_eq A31, 98919258;
+        assert_eq A32, 2362011890;
+        assert_eq A33, 1690238706;
+        assert_eq A34, 2076468753;
+        assert_eq A35, 1630957717;
+        assert_eq A36, 1339035339;
+        assert_eq A37, 3095157333;
+        assert_eq A38, 483341000;
+        assert_eq A39, 2352933002;
+        assert_eq A40, 2923046420;
+        assert_eq A41, 2483845410;
+        assert_eq A42, 2730822116;
+        assert_eq A43, 406976804;
+        assert_eq A44, 3875825090;
+        assert_eq A45, 385168678;
+        assert_eq A46, 2133893435;
+        assert_eq A47, 1979073769;
+        assert_eq A48, 1559011913;
+        assert_eq A49, 3941728123;
+
+        // Applying the permutation again
+        A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12, A13, A14, A15, A16, A17, A18, A19, A20, A21, A22, A23, A24, A25, A26, A27, A28, A29, A30, A31, A32, A33, A34, A35, A36, A37, A38, A39, A40, A41, A42, A43, A44, A45, A46, A47, A48, A49 <== keccakf(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12, A13, A14, A15, A16, A17, A18, A19, A20, A21, A22, A23, A24, A25, A26, A27, A28, A29, A30, A31, A32, A33, A34, A35, A36, A37, A38, A39, A40, A41, A42, A43, A44, A45, A46, A47, A48, A49);
+        assert_eq A0, 4184787772;
+        assert_eq A1, 761042253;
+        assert_eq A2, 1884796269;
+        assert_eq A3, 1781738704;
+        assert_eq A4, 1893165932;
+        assert_eq A5, 155028754;
+        assert_eq A6, 1432998036;
+        assert_eq A7, 2317408690;
+        assert_eq A8, 3857183062;
+        assert_eq A9, 1335644057;
+        assert_eq A10, 3664116536;
+        assert_eq A11, 4183275938;
+        assert_eq A12, 309702413;
+        assert_eq A13, 2239184302;
+        assert_eq A14, 3285717239;
+        assert_eq A15, 4210356807;
+        assert_eq A16, 4154099545;
+        assert_eq A17, 521903846;
+        assert_eq A18, 4002984997;
+        assert_eq A19, 3841903631;
+        assert_eq A20, 3117312161;
+        assert_eq A21, 1758355894;
+        assert_eq A22, 3129972559;
+        assert_eq A23, 3739903684;
+        assert_eq A24, 1857008117;
+        assert_eq A25, 868498819;
+        assert_eq A26, 656006105;
+        assert_eq A27, 3758511108;
+        assert_eq A28, 159584869;
+        assert_eq A29, 2096671216;
+        assert_eq A30, 3205973827;
+        assert_eq A31, 4250159526;
+        assert_eq A32, 3633924928;
+        assert_eq A33, 2547887411;
+        assert_eq A34, 1561061236;
+        assert_eq A35, 1223349756;
+        assert_eq A36, 1438101564;
+        assert_eq A37, 3820538094;
+        assert_eq A38, 1688093417;
+        assert_eq A39, 2443190894;
+        assert_eq A40, 3887783291;
+        assert_eq A41, 2416849193;
+        assert_eq A42, 4205038824;
+        assert_eq A43, 539664069;
+        assert_eq A44, 1310473654;
+        assert_eq A45, 1530135110;
+        assert_eq A46, 2756448345;
+        assert_eq A47, 1621053026;
+        assert_eq A48, 1787805532;
+        assert_eq A49, 550530258;
+
+        return;
+    }
+}
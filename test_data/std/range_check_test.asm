@@ -0,0 +1,27 @@
+use std::range_check::Byte;
+use std::range_check::U16;
+
+machine Main {
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+
+    degree 65536;
+
+    Byte byte;
+    U16 u16;
+
+    instr check_byte X = byte.check
+    instr check_u16 X = u16.check
+
+    function main {
+        check_byte 0;
+        check_byte 255;
+
+        A <=X= 0x1234;
+        check_u16 A;
+        check_u16 0xffff;
+
+        return;
+    }
+}
@@ -26,6 +26,7 @@ pub fn check<T: FieldElement>(file: ASMProgram<T>) -> Result<AnalysisASMFile<T>,
     let machines = TypeChecker::default().check_module(file.main, &ctx)?;
     Ok(AnalysisASMFile {
         machines: machines.into_iter().collect(),
+        sources: file.sources,
     })
 }
 
@@ -80,20 +81,23 @@ impl<T: FieldElement> TypeChecker<T> {
                     start,
                     flag,
                     params,
+                    kind,
                     to,
                 }) => {
                     links.push(LinkDefinitionStatement {
                         start,
                         flag,
                         params,
+                        kind,
                         to,
                     });
                 }
                 MachineStatement::Pil(_start, statement) => {
                     pil.push(statement);
                 }
-                MachineStatement::Submachine(_, ty, name) => {
+                MachineStatement::Submachine(start, ty, name) => {
                     submachines.push(SubmachineDeclaration {
+                        start,
                         name,
                         ty: AbsoluteSymbolPath::default().join(ty),
                     });
@@ -226,6 +230,7 @@ impl<T: FieldElement> TypeChecker<T> {
         }
 
         let machine = Machine {
+            start: machine.start,
             degree,
             latch,
             operation_id,